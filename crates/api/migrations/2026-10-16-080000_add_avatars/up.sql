@@ -0,0 +1,8 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+ALTER TABLE users
+ADD COLUMN avatar_path VARCHAR;
+ALTER TABLE teams
+ADD COLUMN avatar_path VARCHAR;
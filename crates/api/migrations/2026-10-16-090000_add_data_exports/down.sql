@@ -0,0 +1,7 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS data_exports;
+DROP TYPE IF EXISTS data_export_status;
@@ -0,0 +1,16 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+CREATE TYPE data_export_status AS ENUM ('PENDING', 'COMPLETED', 'FAILED');
+
+CREATE TABLE data_exports (
+    id UUID PRIMARY KEY DEFAULT uuidv7(),
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    status data_export_status NOT NULL DEFAULT 'PENDING',
+    file_path VARCHAR,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_data_exports_user_id ON data_exports(user_id);
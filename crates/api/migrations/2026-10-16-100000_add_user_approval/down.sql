@@ -0,0 +1,8 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_users_approval_status;
+ALTER TABLE users DROP COLUMN IF EXISTS approval_status;
+DROP TYPE IF EXISTS approval_status;
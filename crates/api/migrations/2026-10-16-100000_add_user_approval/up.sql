@@ -0,0 +1,10 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+CREATE TYPE approval_status AS ENUM ('PENDING', 'APPROVED', 'REJECTED');
+
+-- Existing users have been approved implicitly
+ALTER TABLE users ADD COLUMN approval_status approval_status NOT NULL DEFAULT 'APPROVED';
+
+CREATE INDEX idx_users_approval_status ON users(approval_status);
@@ -0,0 +1,6 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- NULL until the points have been calculated for the first time
+ALTER TABLE solves ADD COLUMN awarded_points INTEGER;
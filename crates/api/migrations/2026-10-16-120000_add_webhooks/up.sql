@@ -0,0 +1,30 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+CREATE TYPE webhook_event AS ENUM ('SOLVE', 'FIRST_BLOOD', 'REGISTRATION', 'INSTANCE_START');
+
+CREATE TABLE webhooks (
+    id UUID PRIMARY KEY DEFAULT uuidv7(),
+    url VARCHAR NOT NULL,
+    -- Used to sign the payloads, so receivers can verify they were sent by us
+    secret VARCHAR NOT NULL,
+    events webhook_event[] NOT NULL,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE webhook_deliveries (
+    id UUID PRIMARY KEY DEFAULT uuidv7(),
+    webhook_id UUID REFERENCES webhooks(id) ON DELETE CASCADE NOT NULL,
+    event webhook_event NOT NULL,
+    payload TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    -- Status code of the last attempt, if the receiver responded
+    response_status INTEGER,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    delivered_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_webhook_deliveries_webhook_id ON webhook_deliveries(webhook_id, created_at);
@@ -0,0 +1,26 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- Flags handed out to actors, to recognize when one is submitted by someone else
+CREATE TABLE issued_flags (
+    id UUID PRIMARY KEY DEFAULT uuidv7(),
+    challenge_id VARCHAR NOT NULL,
+    actor VARCHAR NOT NULL,
+    flag VARCHAR NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (challenge_id, actor)
+);
+
+CREATE INDEX idx_issued_flags_flag ON issued_flags(flag);
+
+CREATE TABLE cheating_incidents (
+    id UUID PRIMARY KEY DEFAULT uuidv7(),
+    challenge_id VARCHAR NOT NULL,
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    -- Actor slug of the submitter and of the actor the flag was issued to
+    submitting_actor VARCHAR NOT NULL,
+    owning_actor VARCHAR NOT NULL,
+    submitted_flag VARCHAR NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
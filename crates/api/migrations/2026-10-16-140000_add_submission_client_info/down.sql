@@ -0,0 +1,11 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_invalid_submissions_ip_address;
+DROP INDEX IF EXISTS idx_solves_ip_address;
+ALTER TABLE invalid_submissions DROP COLUMN IF EXISTS user_agent;
+ALTER TABLE invalid_submissions DROP COLUMN IF EXISTS ip_address;
+ALTER TABLE solves DROP COLUMN IF EXISTS user_agent;
+ALTER TABLE solves DROP COLUMN IF EXISTS ip_address;
@@ -0,0 +1,11 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+ALTER TABLE solves ADD COLUMN ip_address INET;
+ALTER TABLE solves ADD COLUMN user_agent VARCHAR;
+ALTER TABLE invalid_submissions ADD COLUMN ip_address INET;
+ALTER TABLE invalid_submissions ADD COLUMN user_agent VARCHAR;
+
+CREATE INDEX idx_solves_ip_address ON solves(ip_address);
+CREATE INDEX idx_invalid_submissions_ip_address ON invalid_submissions(ip_address);
@@ -0,0 +1,8 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS notification_reads;
+DROP TABLE IF EXISTS notifications;
+DROP TYPE IF EXISTS notification_kind;
@@ -0,0 +1,28 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+CREATE TYPE notification_kind AS ENUM ('TEAM_INVITE', 'HINT_RELEASE', 'NEW_CHALLENGE', 'ADMIN_MESSAGE');
+
+CREATE TABLE notifications (
+    id UUID PRIMARY KEY DEFAULT uuidv7(),
+    -- NULL for notifications sent to everyone
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    kind notification_kind NOT NULL,
+    title VARCHAR NOT NULL,
+    body TEXT NOT NULL DEFAULT '',
+    -- The challenge the notification is about, if any
+    challenge_id VARCHAR,
+    -- In the future for notifications that are only shown later, e.g. scheduled challenge releases
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_notifications_user_id ON notifications(user_id, created_at);
+
+-- Kept separately so notifications sent to everyone can be read by each user
+CREATE TABLE notification_reads (
+    notification_id UUID REFERENCES notifications(id) ON DELETE CASCADE NOT NULL,
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    read_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (notification_id, user_id)
+);
@@ -0,0 +1,6 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- Used to find the first solvers of a challenge
+CREATE INDEX idx_solves_challenge_id_solved_at ON solves(challenge_id, solved_at);
@@ -0,0 +1,9 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- This file should undo anything in `up.sql`
+DROP INDEX idx_solves_team_id_challenge_id;
+INSERT INTO solves SELECT * FROM solves_team_duplicates;
+DROP TABLE solves_team_duplicates;
+ALTER TABLE solves DROP COLUMN team_id;
@@ -0,0 +1,22 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- The team the user was in when solving, so a team can only solve each challenge once
+ALTER TABLE solves ADD COLUMN team_id UUID REFERENCES teams(id) ON DELETE SET NULL;
+
+UPDATE solves SET team_id = users.team_id FROM users WHERE users.id = solves.user_id;
+
+-- Solves by teammates after the first one never awarded points, they are moved to a backup table
+-- to make room for the index
+CREATE TABLE solves_team_duplicates AS SELECT * FROM solves WHERE FALSE;
+WITH duplicates AS (
+    DELETE FROM solves USING solves AS earlier
+    WHERE solves.team_id = earlier.team_id
+        AND solves.challenge_id = earlier.challenge_id
+        AND (earlier.solved_at, earlier.id) < (solves.solved_at, solves.id)
+    RETURNING solves.*
+)
+INSERT INTO solves_team_duplicates SELECT * FROM duplicates;
+
+CREATE UNIQUE INDEX idx_solves_team_id_challenge_id ON solves(team_id, challenge_id) WHERE team_id IS NOT NULL;
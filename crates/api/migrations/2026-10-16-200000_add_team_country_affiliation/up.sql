@@ -0,0 +1,8 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- ISO 3166-1 alpha-2 code, e.g. for flag icons on the scoreboard
+ALTER TABLE teams ADD COLUMN country_code VARCHAR(2);
+-- University, company or club the team belongs to
+ALTER TABLE teams ADD COLUMN affiliation VARCHAR;
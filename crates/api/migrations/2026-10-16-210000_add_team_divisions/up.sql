@@ -0,0 +1,6 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- ID of a division from event.yml, e.g. student or open
+ALTER TABLE teams ADD COLUMN division VARCHAR;
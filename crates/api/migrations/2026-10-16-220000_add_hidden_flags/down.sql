@@ -0,0 +1,7 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- This file should undo anything in `up.sql`
+ALTER TABLE teams DROP COLUMN hidden;
+ALTER TABLE users DROP COLUMN hidden;
@@ -0,0 +1,8 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- Hidden users and teams (e.g. organizers and test accounts) can play, but their solves
+-- don't count on the scoreboard or in statistics
+ALTER TABLE users ADD COLUMN hidden BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE teams ADD COLUMN hidden BOOLEAN NOT NULL DEFAULT FALSE;
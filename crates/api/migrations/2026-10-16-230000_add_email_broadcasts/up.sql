@@ -0,0 +1,29 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+CREATE TYPE email_delivery_status AS ENUM ('PENDING', 'SENT', 'FAILED');
+
+CREATE TABLE email_broadcasts (
+    id UUID PRIMARY KEY DEFAULT uuidv7(),
+    subject VARCHAR NOT NULL,
+    body_md TEXT NOT NULL,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- One row per recipient, sent by the email worker of any api replica
+CREATE TABLE email_deliveries (
+    id UUID PRIMARY KEY DEFAULT uuidv7(),
+    broadcast_id UUID REFERENCES email_broadcasts(id) ON DELETE CASCADE NOT NULL,
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    status email_delivery_status NOT NULL DEFAULT 'PENDING',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    error VARCHAR,
+    -- Pending deliveries are picked up once this has passed, which also covers retries
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    sent_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_email_deliveries_broadcast_id ON email_deliveries(broadcast_id);
+CREATE INDEX idx_email_deliveries_pending ON email_deliveries(next_attempt_at) WHERE status = 'PENDING';
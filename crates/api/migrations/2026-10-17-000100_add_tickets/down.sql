@@ -0,0 +1,23 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS ticket_messages;
+DROP TABLE IF EXISTS tickets;
+DROP TYPE IF EXISTS ticket_status;
+
+-- Enum values can't be dropped, so the types are recreated without them
+DELETE FROM notifications WHERE kind = 'TICKET_UPDATE';
+ALTER TYPE notification_kind RENAME TO notification_kind_old;
+CREATE TYPE notification_kind AS ENUM ('TEAM_INVITE', 'HINT_RELEASE', 'NEW_CHALLENGE', 'ADMIN_MESSAGE');
+ALTER TABLE notifications ALTER COLUMN kind TYPE notification_kind USING kind::text::notification_kind;
+DROP TYPE notification_kind_old;
+
+DELETE FROM webhook_deliveries WHERE event = 'TICKET_CREATED';
+UPDATE webhooks SET events = array_remove(events, 'TICKET_CREATED');
+ALTER TYPE webhook_event RENAME TO webhook_event_old;
+CREATE TYPE webhook_event AS ENUM ('SOLVE', 'FIRST_BLOOD', 'REGISTRATION', 'INSTANCE_START');
+ALTER TABLE webhook_deliveries ALTER COLUMN event TYPE webhook_event USING event::text::webhook_event;
+ALTER TABLE webhooks ALTER COLUMN events TYPE webhook_event[] USING events::text[]::webhook_event[];
+DROP TYPE webhook_event_old;
@@ -0,0 +1,34 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+CREATE TYPE ticket_status AS ENUM ('OPEN', 'ANSWERED', 'CLOSED');
+
+ALTER TYPE notification_kind ADD VALUE 'TICKET_UPDATE';
+ALTER TYPE webhook_event ADD VALUE 'TICKET_CREATED';
+
+CREATE TABLE tickets (
+    id UUID PRIMARY KEY DEFAULT uuidv7(),
+    -- The player who opened the ticket
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    subject VARCHAR NOT NULL,
+    -- The challenge the ticket is about, if any
+    challenge_id VARCHAR,
+    status ticket_status NOT NULL DEFAULT 'OPEN',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_tickets_user_id ON tickets(user_id);
+CREATE INDEX idx_tickets_challenge_id ON tickets(challenge_id);
+
+CREATE TABLE ticket_messages (
+    id UUID PRIMARY KEY DEFAULT uuidv7(),
+    ticket_id UUID REFERENCES tickets(id) ON DELETE CASCADE NOT NULL,
+    -- NULL once the author's account was deleted
+    author_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    body TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_ticket_messages_ticket_id ON ticket_messages(ticket_id, created_at);
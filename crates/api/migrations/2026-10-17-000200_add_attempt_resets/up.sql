@@ -0,0 +1,12 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- Wrong submissions before the reset don't count towards the attempt limit of a challenge
+CREATE TABLE attempt_resets (
+    challenge_id VARCHAR NOT NULL,
+    -- ID of the team, or of the user if they are not in a team
+    actor_id UUID NOT NULL,
+    reset_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (challenge_id, actor_id)
+);
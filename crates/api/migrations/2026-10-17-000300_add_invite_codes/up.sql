@@ -0,0 +1,19 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+CREATE TABLE invite_codes (
+    id UUID PRIMARY KEY DEFAULT uuidv7(),
+    code VARCHAR NOT NULL UNIQUE,
+    max_uses INTEGER NOT NULL DEFAULT 1,
+    uses INTEGER NOT NULL DEFAULT 0,
+    -- Role users registering with the code get, players if NULL
+    role user_role,
+    -- Division the teams of users registering with the code have to compete in
+    division VARCHAR,
+    expires_at TIMESTAMPTZ,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE users ADD COLUMN invite_code_id UUID REFERENCES invite_codes(id) ON DELETE SET NULL;
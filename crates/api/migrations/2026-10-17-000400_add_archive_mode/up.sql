@@ -0,0 +1,13 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- Single row, so all api replicas see the same state
+CREATE TABLE archive_mode (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    -- Set by an admin, archive mode can also start automatically once the event ended
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO archive_mode (id) VALUES (TRUE);
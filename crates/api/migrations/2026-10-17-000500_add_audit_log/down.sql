@@ -0,0 +1,8 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- This file should undo anything in `up.sql`
+ALTER TABLE teams DROP COLUMN IF EXISTS disqualified;
+DROP TABLE IF EXISTS audit_log;
+DROP TYPE IF EXISTS audit_action;
@@ -0,0 +1,24 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+CREATE TYPE audit_action AS ENUM ('SOLVE_REVOKED', 'SCORE_ZEROED', 'TEAM_DISQUALIFIED', 'TEAM_REQUALIFIED');
+
+CREATE TABLE audit_log (
+    id UUID PRIMARY KEY DEFAULT uuidv7(),
+    -- NULL once the admin's account was deleted
+    admin_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    action audit_action NOT NULL,
+    -- The team or user the action was taken against, kept after they are deleted
+    target_id UUID,
+    -- What was changed, e.g. the challenge of a revoked solve
+    details TEXT NOT NULL,
+    reason TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_audit_log_created_at ON audit_log(created_at);
+CREATE INDEX idx_audit_log_target_id ON audit_log(target_id);
+
+-- Disqualified teams are left out of the scoreboard and can't submit flags anymore
+ALTER TABLE teams ADD COLUMN disqualified BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,17 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- This file should undo anything in `up.sql`
+ALTER TABLE solves DROP COLUMN IF EXISTS playtest;
+
+-- Enum values can't be dropped, so the type is recreated without it
+UPDATE users SET role = 'PLAYER' WHERE role = 'TESTER';
+UPDATE invite_codes SET role = NULL WHERE role = 'TESTER';
+ALTER TYPE user_role RENAME TO user_role_old;
+CREATE TYPE user_role AS ENUM ('PLAYER', 'AUTHOR', 'ADMIN');
+ALTER TABLE users ALTER COLUMN role DROP DEFAULT;
+ALTER TABLE users ALTER COLUMN role TYPE user_role USING role::text::user_role;
+ALTER TABLE users ALTER COLUMN role SET DEFAULT 'PLAYER';
+ALTER TABLE invite_codes ALTER COLUMN role TYPE user_role USING role::text::user_role;
+DROP TYPE user_role_old;
@@ -0,0 +1,9 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- Testers can solve unreleased challenges that are open for playtesting
+ALTER TYPE user_role ADD VALUE 'TESTER' BEFORE 'AUTHOR';
+
+-- Solves made before the challenge was released don't count for scoring or first blood
+ALTER TABLE solves ADD COLUMN playtest BOOLEAN NOT NULL DEFAULT FALSE;
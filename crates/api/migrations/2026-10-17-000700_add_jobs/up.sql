@@ -0,0 +1,29 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+CREATE TYPE job_status AS ENUM ('PENDING', 'RUNNING', 'SUCCEEDED', 'FAILED');
+
+-- Deferred work, run by the job worker of any api replica
+CREATE TABLE jobs (
+    id UUID PRIMARY KEY DEFAULT uuidv7(),
+    -- Selects the handler the job is run with
+    kind VARCHAR NOT NULL,
+    -- JSON passed to the handler
+    payload TEXT NOT NULL,
+    status job_status NOT NULL DEFAULT 'PENDING',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL DEFAULT 5,
+    -- Error of the last failed attempt
+    error TEXT,
+    -- Pending jobs are picked up once this has passed, which also covers retries
+    run_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- Running jobs are picked up again after this, in case the replica running them stopped
+    locked_until TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_jobs_pending ON jobs(run_at) WHERE status = 'PENDING';
+CREATE INDEX idx_jobs_running ON jobs(locked_until) WHERE status = 'RUNNING';
+CREATE INDEX idx_jobs_created_at ON jobs(created_at);
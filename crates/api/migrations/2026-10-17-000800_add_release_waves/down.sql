@@ -0,0 +1,15 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS released_waves;
+
+-- Enum values can't be dropped, so the type is recreated without it
+DELETE FROM webhook_deliveries WHERE event = 'CHALLENGE_RELEASE';
+UPDATE webhooks SET events = array_remove(events, 'CHALLENGE_RELEASE');
+ALTER TYPE webhook_event RENAME TO webhook_event_old;
+CREATE TYPE webhook_event AS ENUM ('SOLVE', 'FIRST_BLOOD', 'REGISTRATION', 'INSTANCE_START', 'TICKET_CREATED');
+ALTER TABLE webhook_deliveries ALTER COLUMN event TYPE webhook_event USING event::text::webhook_event;
+ALTER TABLE webhooks ALTER COLUMN events TYPE webhook_event[] USING events::text[]::webhook_event[];
+DROP TYPE webhook_event_old;
@@ -0,0 +1,14 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+ALTER TYPE webhook_event ADD VALUE 'CHALLENGE_RELEASE';
+
+-- Release waves of the event config that were announced, so only one api replica announces each
+CREATE TABLE released_waves (
+    name VARCHAR NOT NULL,
+    release_time TIMESTAMPTZ NOT NULL,
+    announced_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- A wave is announced again if it is moved to another time
+    PRIMARY KEY (name, release_time)
+);
@@ -0,0 +1,18 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- This file should undo anything in `up.sql`
+-- Playtest solves of users and teams that solved the challenge again after the release would take
+-- the same slot
+DELETE FROM solves USING solves AS scored
+WHERE solves.playtest
+    AND NOT scored.playtest
+    AND solves.challenge_id = scored.challenge_id
+    AND (solves.user_id = scored.user_id OR solves.team_id = scored.team_id);
+
+DROP INDEX idx_solves_team_id_challenge_id;
+CREATE UNIQUE INDEX idx_solves_team_id_challenge_id ON solves(team_id, challenge_id) WHERE team_id IS NOT NULL;
+
+DROP INDEX idx_solves_user_id_challenge_id;
+ALTER TABLE solves ADD CONSTRAINT solves_user_id_challenge_id_key UNIQUE (user_id, challenge_id);
@@ -0,0 +1,11 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- Playtest solves don't take the slot of the solve after the release, so testers and their teams
+-- can still score. Each user and team can have one of each.
+ALTER TABLE solves DROP CONSTRAINT solves_user_id_challenge_id_key;
+CREATE UNIQUE INDEX idx_solves_user_id_challenge_id ON solves(user_id, challenge_id, playtest);
+
+DROP INDEX idx_solves_team_id_challenge_id;
+CREATE UNIQUE INDEX idx_solves_team_id_challenge_id ON solves(team_id, challenge_id, playtest) WHERE team_id IS NOT NULL;
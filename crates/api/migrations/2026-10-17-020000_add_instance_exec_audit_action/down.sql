@@ -0,0 +1,12 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- This file should undo anything in `up.sql`
+
+-- Enum values can't be dropped, so the type is recreated without it
+DELETE FROM audit_log WHERE action = 'INSTANCE_EXEC';
+ALTER TYPE audit_action RENAME TO audit_action_old;
+CREATE TYPE audit_action AS ENUM ('SOLVE_REVOKED', 'SCORE_ZEROED', 'TEAM_DISQUALIFIED', 'TEAM_REQUALIFIED');
+ALTER TABLE audit_log ALTER COLUMN action TYPE audit_action USING action::text::audit_action;
+DROP TYPE audit_action_old;
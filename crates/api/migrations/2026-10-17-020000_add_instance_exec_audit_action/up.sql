@@ -0,0 +1,6 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- Commands admins run in instances are recorded in the audit log
+ALTER TYPE audit_action ADD VALUE 'INSTANCE_EXEC';
@@ -0,0 +1,8 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- This file should undo anything in `up.sql`
+DROP INDEX idx_teams_slug_lower;
+DROP INDEX idx_teams_name_lower;
+DROP INDEX idx_users_username_lower;
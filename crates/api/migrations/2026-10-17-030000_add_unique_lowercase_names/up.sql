@@ -0,0 +1,8 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- Names are compared case-insensitively, these catch two registrations taking the same name at once
+CREATE UNIQUE INDEX idx_users_username_lower ON users (LOWER(username));
+CREATE UNIQUE INDEX idx_teams_name_lower ON teams (LOWER(name));
+CREATE UNIQUE INDEX idx_teams_slug_lower ON teams (LOWER(slug));
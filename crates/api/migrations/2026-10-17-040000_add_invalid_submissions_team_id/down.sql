@@ -0,0 +1,14 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- This file should undo anything in `up.sql`
+
+-- Enum values can't be dropped, so the type is recreated without it
+DELETE FROM audit_log WHERE action = 'CHALLENGE_REJUDGED';
+ALTER TYPE audit_action RENAME TO audit_action_old;
+CREATE TYPE audit_action AS ENUM ('SOLVE_REVOKED', 'SCORE_ZEROED', 'TEAM_DISQUALIFIED', 'TEAM_REQUALIFIED', 'INSTANCE_EXEC');
+ALTER TABLE audit_log ALTER COLUMN action TYPE audit_action USING action::text::audit_action;
+DROP TYPE audit_action_old;
+
+ALTER TABLE invalid_submissions DROP COLUMN team_id;
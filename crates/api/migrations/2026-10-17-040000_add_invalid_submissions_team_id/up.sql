@@ -0,0 +1,11 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- The team the user was in when submitting, a rejudged submission counts for it like a solve would
+ALTER TABLE invalid_submissions ADD COLUMN team_id UUID REFERENCES teams(id) ON DELETE SET NULL;
+
+UPDATE invalid_submissions SET team_id = users.team_id FROM users WHERE users.id = invalid_submissions.user_id;
+
+-- Rejudging challenges is recorded in the audit log
+ALTER TYPE audit_action ADD VALUE 'CHALLENGE_REJUDGED';
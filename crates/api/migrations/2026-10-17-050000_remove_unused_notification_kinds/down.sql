@@ -0,0 +1,7 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- This file should undo anything in `up.sql`
+ALTER TYPE notification_kind ADD VALUE 'TEAM_INVITE';
+ALTER TYPE notification_kind ADD VALUE 'HINT_RELEASE';
@@ -0,0 +1,11 @@
+-- SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+--
+-- SPDX-License-Identifier: AGPL-3.0-or-later
+
+-- Teams are joined with codes instead of invites and there are no hints, so these were never sent.
+-- Enum values can't be dropped, so the type is recreated without them
+DELETE FROM notifications WHERE kind IN ('TEAM_INVITE', 'HINT_RELEASE');
+ALTER TYPE notification_kind RENAME TO notification_kind_old;
+CREATE TYPE notification_kind AS ENUM ('NEW_CHALLENGE', 'ADMIN_MESSAGE', 'TICKET_UPDATE');
+ALTER TABLE notifications ALTER COLUMN kind TYPE notification_kind USING kind::text::notification_kind;
+DROP TYPE notification_kind_old;
@@ -1,13 +1,9 @@
-use juniper::{EmptySubscription, RootNode};
+use juniper::RootNode;
 
-use plfanzen_api::graphql::{Mutation, Query};
+use plfanzen_api::graphql::{Mutation, Query, Subscription};
 
 fn main() {
-    let schema = RootNode::new(
-        Query,
-        Mutation,
-        EmptySubscription::<plfanzen_api::graphql::Context>::new(),
-    );
+    let schema = RootNode::new(Query, Mutation, Subscription);
 
     let result = schema.as_sdl();
 
@@ -0,0 +1,638 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::{
+    net::SocketAddr,
+    num::{ParseFloatError, ParseIntError},
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+/// Used if neither --config nor CONFIG_FILE is given and the file exists.
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to read config file {0}: {1}")]
+    Read(PathBuf, std::io::Error),
+    #[error("Failed to parse config file {0}: {1}")]
+    Parse(PathBuf, String),
+    #[error("Unsupported config file format {0}, expected .toml, .yaml or .yml")]
+    UnsupportedFormat(PathBuf),
+    #[error("Invalid value for {0}: {1}")]
+    InvalidEnv(&'static str, String),
+    #[error("Invalid configuration:\n  - {}", .0.join("\n  - "))]
+    Invalid(Vec<String>),
+}
+
+/// Where user-uploaded files like avatars and data exports are stored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// A directory, storage_dir
+    #[default]
+    Local,
+    /// The S3 bucket s3_bucket
+    S3,
+}
+
+/// Settings of the api server.
+///
+/// Loaded from a TOML or YAML file, every value can be overridden by the environment variable
+/// with the same name in uppercase (e.g. DATABASE_URL).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Address the HTTP server listens on
+    pub listen_addr: SocketAddr,
+    pub database_url: Option<String>,
+    /// gRPC endpoint of the manager, e.g. http://manager:50051
+    pub manager_endpoint: Option<String>,
+    /// Path to the key used to sign tokens, generated on first start if it doesn't exist
+    pub signing_key_file: PathBuf,
+    /// PEM certificate chain, TLS is enabled if this and tls_key_file are set
+    pub tls_cert_file: Option<PathBuf>,
+    pub tls_key_file: Option<PathBuf>,
+    /// Seconds to wait for active requests to finish when shutting down
+    pub shutdown_timeout: u64,
+    /// Sentry DSN, panics and unexpected errors are reported there if set
+    pub sentry_dsn: Option<String>,
+    /// Environment the reported errors are tagged with, e.g. production
+    pub sentry_environment: Option<String>,
+    /// Secret of the push webhooks of the challenge repository, the endpoint only exists if set
+    pub git_webhook_secret: Option<String>,
+    /// Branch whose pushes trigger a sync, the one the manager syncs from if unset
+    pub git_webhook_branch: Option<String>,
+    /// Estimated strength new passwords need, 40 bits if unset
+    pub password_min_entropy_bits: Option<f64>,
+    /// Reject new passwords which are in the HaveIBeenPwned database
+    pub password_breach_check: bool,
+    /// Broadcast emails sent per minute to the addresses of each email provider, 30 if unset
+    pub email_rate_limit_per_provider: Option<usize>,
+    /// Discord channel admins are alerted in when a team submits the flag of another team
+    pub discord_cheating_channel_id: Option<u64>,
+    pub discord_cheating_guild_id: Option<u64>,
+    /// Token of the Discord bot posting solves and invalid submissions
+    pub discord_token: Option<String>,
+    /// Discord channel solves are announced in
+    pub discord_solves_channel_id: Option<u64>,
+    pub discord_solves_guild_id: Option<u64>,
+    /// Discord channel invalid submissions are announced in, without the submitted flag
+    pub discord_public_invalid_submissions_channel_id: Option<u64>,
+    pub discord_public_invalid_submissions_guild_id: Option<u64>,
+    /// Discord channel invalid submissions are announced in, including the submitted flag
+    pub discord_private_invalid_submissions_channel_id: Option<u64>,
+    pub discord_private_invalid_submissions_guild_id: Option<u64>,
+    /// SMTP server emails are sent through, email is disabled unless all email_* values are set
+    pub email_smtp_server: Option<String>,
+    pub email_smtp_username: Option<String>,
+    pub email_smtp_password: Option<String>,
+    /// Sender of all emails, e.g. "Plfanzen CTF <ctf@example.com>"
+    pub email_from_address: Option<String>,
+    /// Secret used to sign Altcha challenges
+    pub altcha_secret_key: Option<String>,
+    /// Cap instance (host name) and keys of the site
+    pub cap_instance_url: Option<String>,
+    pub cap_site_key: Option<String>,
+    pub cap_secret_key: Option<String>,
+    pub hcaptcha_site_key: Option<String>,
+    pub hcaptcha_secret_key: Option<String>,
+    pub turnstile_site_key: Option<String>,
+    pub turnstile_secret_key: Option<String>,
+    /// Require solving a CAPTCHA when logging in, not only when registering
+    pub captcha_on_login: bool,
+    /// OTLP endpoint traces are exported to, e.g. http://otel-collector:4317
+    pub otel_exporter_otlp_endpoint: Option<String>,
+    /// JSON file with the only queries anonymous users may run, any query is allowed if unset
+    pub graphql_allowlist_file: Option<PathBuf>,
+    pub storage_backend: StorageBackend,
+    /// Directory files are stored in with the local storage backend
+    pub storage_dir: PathBuf,
+    /// Bucket files are stored in with the S3 storage backend
+    pub s3_bucket: Option<String>,
+    /// URL stored files are served from (e.g. a CDN), the api serves them itself if unset
+    pub storage_public_url: Option<String>,
+    /// Bearer token Prometheus has to send to scrape /metrics, which is disabled if unset
+    pub metrics_token: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            listen_addr: SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 0], 3000)),
+            database_url: None,
+            manager_endpoint: None,
+            signing_key_file: PathBuf::from("key.json"),
+            tls_cert_file: None,
+            tls_key_file: None,
+            shutdown_timeout: 30,
+            sentry_dsn: None,
+            sentry_environment: None,
+            git_webhook_secret: None,
+            git_webhook_branch: None,
+            password_min_entropy_bits: None,
+            password_breach_check: false,
+            email_rate_limit_per_provider: None,
+            discord_cheating_channel_id: None,
+            discord_cheating_guild_id: None,
+            discord_token: None,
+            discord_solves_channel_id: None,
+            discord_solves_guild_id: None,
+            discord_public_invalid_submissions_channel_id: None,
+            discord_public_invalid_submissions_guild_id: None,
+            discord_private_invalid_submissions_channel_id: None,
+            discord_private_invalid_submissions_guild_id: None,
+            email_smtp_server: None,
+            email_smtp_username: None,
+            email_smtp_password: None,
+            email_from_address: None,
+            altcha_secret_key: None,
+            cap_instance_url: None,
+            cap_site_key: None,
+            cap_secret_key: None,
+            hcaptcha_site_key: None,
+            hcaptcha_secret_key: None,
+            turnstile_site_key: None,
+            turnstile_secret_key: None,
+            captcha_on_login: false,
+            otel_exporter_otlp_endpoint: None,
+            graphql_allowlist_file: None,
+            storage_backend: StorageBackend::Local,
+            storage_dir: PathBuf::from("/data/storage"),
+            s3_bucket: None,
+            storage_public_url: None,
+            metrics_token: None,
+        }
+    }
+}
+
+/// Options passed on the command line.
+#[derive(Debug, Default)]
+pub struct CliArgs {
+    pub config_file: Option<PathBuf>,
+    /// Only validate the configuration and exit
+    pub check_config: bool,
+}
+
+impl CliArgs {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut cli = CliArgs::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--check-config" => cli.check_config = true,
+                "--config" => {
+                    let path = args.next().ok_or("--config requires a path")?;
+                    cli.config_file = Some(PathBuf::from(path));
+                }
+                _ => match arg.strip_prefix("--config=") {
+                    Some(path) => cli.config_file = Some(PathBuf::from(path)),
+                    None => return Err(format!("Unknown argument: {}", arg)),
+                },
+            }
+        }
+        Ok(cli)
+    }
+}
+
+impl Config {
+    /// Returns the config file to load, if any.
+    ///
+    /// The file is taken from `config_file`, CONFIG_FILE or config.toml in the working directory, in that order.
+    pub fn find_file(config_file: Option<&Path>) -> Option<PathBuf> {
+        config_file
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::var_os("CONFIG_FILE").map(PathBuf::from))
+            .or_else(|| {
+                let default = PathBuf::from(DEFAULT_CONFIG_FILE);
+                default.exists().then_some(default)
+            })
+    }
+
+    /// Loads the config file found by [`Config::find_file`] (if any) and applies overrides from
+    /// the environment.
+    pub fn load(config_file: Option<&Path>) -> Result<Self, ConfigError> {
+        let mut config = match config_file {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        config.apply_env(|name| std::env::var(name).ok())?;
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let content =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Read(path.to_path_buf(), e))?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&content)
+                .map_err(|e| ConfigError::Parse(path.to_path_buf(), e.to_string())),
+            Some("yaml" | "yml") => serde_yaml::from_str(&content)
+                .map_err(|e| ConfigError::Parse(path.to_path_buf(), e.to_string())),
+            _ => Err(ConfigError::UnsupportedFormat(path.to_path_buf())),
+        }
+    }
+
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        if let Some(listen_addr) = var("LISTEN_ADDR") {
+            self.listen_addr = listen_addr.parse().map_err(|e: std::net::AddrParseError| {
+                ConfigError::InvalidEnv("LISTEN_ADDR", e.to_string())
+            })?;
+        }
+        if let Some(database_url) = var("DATABASE_URL") {
+            self.database_url = Some(database_url);
+        }
+        if let Some(manager_endpoint) = var("MANAGER_ENDPOINT") {
+            self.manager_endpoint = Some(manager_endpoint);
+        }
+        if let Some(signing_key_file) = var("SIGNING_KEY_FILE") {
+            self.signing_key_file = PathBuf::from(signing_key_file);
+        }
+        if let Some(tls_cert_file) = var("TLS_CERT_FILE") {
+            self.tls_cert_file = Some(PathBuf::from(tls_cert_file));
+        }
+        if let Some(tls_key_file) = var("TLS_KEY_FILE") {
+            self.tls_key_file = Some(PathBuf::from(tls_key_file));
+        }
+        if let Some(shutdown_timeout) = var("SHUTDOWN_TIMEOUT") {
+            self.shutdown_timeout = shutdown_timeout.parse().map_err(|e: ParseIntError| {
+                ConfigError::InvalidEnv("SHUTDOWN_TIMEOUT", e.to_string())
+            })?;
+        }
+        if let Some(sentry_dsn) = var("SENTRY_DSN") {
+            self.sentry_dsn = Some(sentry_dsn);
+        }
+        if let Some(sentry_environment) = var("SENTRY_ENVIRONMENT") {
+            self.sentry_environment = Some(sentry_environment);
+        }
+        if let Some(git_webhook_secret) = var("GIT_WEBHOOK_SECRET") {
+            self.git_webhook_secret = Some(git_webhook_secret);
+        }
+        if let Some(git_webhook_branch) = var("GIT_WEBHOOK_BRANCH") {
+            self.git_webhook_branch = Some(git_webhook_branch);
+        }
+        if let Some(bits) = var("PASSWORD_MIN_ENTROPY_BITS") {
+            self.password_min_entropy_bits = Some(bits.parse().map_err(|e: ParseFloatError| {
+                ConfigError::InvalidEnv("PASSWORD_MIN_ENTROPY_BITS", e.to_string())
+            })?);
+        }
+        if let Some(breach_check) = var("PASSWORD_BREACH_CHECK") {
+            self.password_breach_check = breach_check == "true" || breach_check == "1";
+        }
+        if let Some(rate_limit) = var("EMAIL_RATE_LIMIT_PER_PROVIDER") {
+            self.email_rate_limit_per_provider =
+                Some(rate_limit.parse().map_err(|e: ParseIntError| {
+                    ConfigError::InvalidEnv("EMAIL_RATE_LIMIT_PER_PROVIDER", e.to_string())
+                })?);
+        }
+        for (name, value) in [
+            (
+                "DISCORD_CHEATING_CHANNEL_ID",
+                &mut self.discord_cheating_channel_id,
+            ),
+            (
+                "DISCORD_CHEATING_GUILD_ID",
+                &mut self.discord_cheating_guild_id,
+            ),
+            (
+                "DISCORD_SOLVES_CHANNEL_ID",
+                &mut self.discord_solves_channel_id,
+            ),
+            ("DISCORD_SOLVES_GUILD_ID", &mut self.discord_solves_guild_id),
+            (
+                "DISCORD_PUBLIC_INVALID_SUBMISSIONS_CHANNEL_ID",
+                &mut self.discord_public_invalid_submissions_channel_id,
+            ),
+            (
+                "DISCORD_PUBLIC_INVALID_SUBMISSIONS_GUILD_ID",
+                &mut self.discord_public_invalid_submissions_guild_id,
+            ),
+            (
+                "DISCORD_PRIVATE_INVALID_SUBMISSIONS_CHANNEL_ID",
+                &mut self.discord_private_invalid_submissions_channel_id,
+            ),
+            (
+                "DISCORD_PRIVATE_INVALID_SUBMISSIONS_GUILD_ID",
+                &mut self.discord_private_invalid_submissions_guild_id,
+            ),
+        ] {
+            if let Some(id) = var(name) {
+                *value =
+                    Some(id.parse().map_err(|e: ParseIntError| {
+                        ConfigError::InvalidEnv(name, e.to_string())
+                    })?);
+            }
+        }
+        for (name, value) in [
+            ("DISCORD_TOKEN", &mut self.discord_token),
+            ("EMAIL_SMTP_SERVER", &mut self.email_smtp_server),
+            ("EMAIL_SMTP_USERNAME", &mut self.email_smtp_username),
+            ("EMAIL_SMTP_PASSWORD", &mut self.email_smtp_password),
+            ("EMAIL_FROM_ADDRESS", &mut self.email_from_address),
+            ("ALTCHA_SECRET_KEY", &mut self.altcha_secret_key),
+            ("CAP_INSTANCE_URL", &mut self.cap_instance_url),
+            ("CAP_SITE_KEY", &mut self.cap_site_key),
+            ("CAP_SECRET_KEY", &mut self.cap_secret_key),
+            ("HCAPTCHA_SITE_KEY", &mut self.hcaptcha_site_key),
+            ("HCAPTCHA_SECRET_KEY", &mut self.hcaptcha_secret_key),
+            ("TURNSTILE_SITE_KEY", &mut self.turnstile_site_key),
+            ("TURNSTILE_SECRET_KEY", &mut self.turnstile_secret_key),
+            (
+                "OTEL_EXPORTER_OTLP_ENDPOINT",
+                &mut self.otel_exporter_otlp_endpoint,
+            ),
+        ] {
+            if let Some(v) = var(name) {
+                *value = Some(v);
+            }
+        }
+        if let Some(allowlist_file) = var("GRAPHQL_ALLOWLIST_FILE") {
+            self.graphql_allowlist_file = Some(PathBuf::from(allowlist_file));
+        }
+        if let Some(storage_backend) = var("STORAGE_BACKEND") {
+            self.storage_backend = match storage_backend.as_str() {
+                "local" => StorageBackend::Local,
+                "s3" => StorageBackend::S3,
+                _ => {
+                    return Err(ConfigError::InvalidEnv(
+                        "STORAGE_BACKEND",
+                        "expected local or s3".to_string(),
+                    ));
+                }
+            };
+        }
+        if let Some(storage_dir) = var("STORAGE_DIR") {
+            self.storage_dir = PathBuf::from(storage_dir);
+        }
+        if let Some(s3_bucket) = var("S3_BUCKET") {
+            self.s3_bucket = Some(s3_bucket);
+        }
+        if let Some(storage_public_url) = var("STORAGE_PUBLIC_URL") {
+            self.storage_public_url = Some(storage_public_url);
+        }
+        if let Some(metrics_token) = var("METRICS_TOKEN") {
+            self.metrics_token = Some(metrics_token);
+        }
+        if let Some(captcha_on_login) = var("CAPTCHA_ON_LOGIN") {
+            self.captcha_on_login = captcha_on_login == "true" || captcha_on_login == "1";
+        }
+        Ok(())
+    }
+
+    /// Checks the configuration for missing or invalid values, reporting all problems at once.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut errors = vec![];
+
+        match self.database_url.as_deref() {
+            None | Some("") => errors.push("database_url must be set".to_string()),
+            Some(url) if !url.starts_with("postgres://") && !url.starts_with("postgresql://") => {
+                errors.push("database_url must be a postgres:// URL".to_string())
+            }
+            _ => {}
+        }
+
+        match self.manager_endpoint.as_deref() {
+            None | Some("") => errors.push("manager_endpoint must be set".to_string()),
+            Some(endpoint) => {
+                if let Err(e) = tonic::transport::Endpoint::from_shared(endpoint.to_string()) {
+                    errors.push(format!("manager_endpoint is not a valid URL: {}", e));
+                }
+            }
+        }
+
+        match (&self.tls_cert_file, &self.tls_key_file) {
+            (Some(cert_file), Some(key_file)) => {
+                for file in [cert_file, key_file] {
+                    if !file.is_file() {
+                        errors.push(format!("{} does not exist", file.display()));
+                    }
+                }
+            }
+            (None, None) => {}
+            _ => errors.push("tls_cert_file and tls_key_file must be set together".to_string()),
+        }
+
+        if self.signing_key_file.exists() && !self.signing_key_file.is_file() {
+            errors.push(format!(
+                "signing_key_file {} is not a file",
+                self.signing_key_file.display()
+            ));
+        }
+
+        if let Some(dsn) = self.sentry_dsn.as_deref().filter(|dsn| !dsn.is_empty())
+            && crate::error_reporting::Dsn::parse(dsn).is_none()
+        {
+            errors.push("sentry_dsn is not a valid Sentry DSN".to_string());
+        }
+
+        for (name, channel_id, guild_id) in [
+            (
+                "discord_cheating",
+                self.discord_cheating_channel_id,
+                self.discord_cheating_guild_id,
+            ),
+            (
+                "discord_solves",
+                self.discord_solves_channel_id,
+                self.discord_solves_guild_id,
+            ),
+            (
+                "discord_public_invalid_submissions",
+                self.discord_public_invalid_submissions_channel_id,
+                self.discord_public_invalid_submissions_guild_id,
+            ),
+            (
+                "discord_private_invalid_submissions",
+                self.discord_private_invalid_submissions_channel_id,
+                self.discord_private_invalid_submissions_guild_id,
+            ),
+        ] {
+            if channel_id.is_some() != guild_id.is_some() {
+                errors.push(format!(
+                    "{name}_channel_id and {name}_guild_id must be set together"
+                ));
+            }
+        }
+
+        let smtp = [
+            &self.email_smtp_server,
+            &self.email_smtp_username,
+            &self.email_smtp_password,
+            &self.email_from_address,
+        ];
+        if smtp.iter().any(|value| value.is_some()) && !smtp.iter().all(|value| value.is_some()) {
+            errors.push(
+                "email_smtp_server, email_smtp_username, email_smtp_password and \
+                 email_from_address must be set together"
+                    .to_string(),
+            );
+        }
+        if let Some(from) = &self.email_from_address
+            && let Err(e) = from.parse::<lettre::message::Mailbox>()
+        {
+            errors.push(format!("email_from_address is not a valid address: {}", e));
+        }
+        if let Some(server) = &self.email_smtp_server
+            && let Err(e) =
+                lettre::AsyncSmtpTransport::<lettre::Tokio1Executor>::relay(server.as_str())
+        {
+            errors.push(format!("email_smtp_server {} is invalid: {}", server, e));
+        }
+
+        let cap = [
+            &self.cap_instance_url,
+            &self.cap_site_key,
+            &self.cap_secret_key,
+        ];
+        if cap.iter().any(|value| value.is_some()) && !cap.iter().all(|value| value.is_some()) {
+            errors.push(
+                "cap_instance_url, cap_site_key and cap_secret_key must be set together"
+                    .to_string(),
+            );
+        }
+        for (name, site_key, secret_key) in [
+            (
+                "hcaptcha",
+                &self.hcaptcha_site_key,
+                &self.hcaptcha_secret_key,
+            ),
+            (
+                "turnstile",
+                &self.turnstile_site_key,
+                &self.turnstile_secret_key,
+            ),
+        ] {
+            if site_key.is_some() != secret_key.is_some() {
+                errors.push(format!(
+                    "{name}_site_key and {name}_secret_key must be set together"
+                ));
+            }
+        }
+        let captcha_providers = [
+            self.altcha_secret_key.is_some(),
+            self.cap_secret_key.is_some(),
+            self.hcaptcha_secret_key.is_some(),
+            self.turnstile_secret_key.is_some(),
+        ];
+        if captcha_providers.into_iter().filter(|&set| set).count() > 1 {
+            errors.push("Only one CAPTCHA provider can be configured".to_string());
+        }
+
+        if self.metrics_token.as_deref() == Some("") {
+            errors.push("metrics_token must not be empty".to_string());
+        }
+
+        match self.storage_backend {
+            StorageBackend::S3 if self.s3_bucket.as_deref().is_none_or(str::is_empty) => {
+                errors.push("s3_bucket must be set for the s3 storage backend".to_string())
+            }
+            StorageBackend::Local if self.storage_dir.exists() && !self.storage_dir.is_dir() => {
+                errors.push(format!(
+                    "storage_dir {} is not a directory",
+                    self.storage_dir.display()
+                ))
+            }
+            _ => {}
+        }
+
+        if let Some(endpoint) = &self.otel_exporter_otlp_endpoint
+            && let Err(e) = tonic::transport::Endpoint::from_shared(endpoint.clone())
+        {
+            errors.push(format!(
+                "otel_exporter_otlp_endpoint is not a valid URL: {}",
+                e
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(errors))
+        }
+    }
+
+    pub fn database_url(&self) -> &str {
+        self.database_url
+            .as_deref()
+            .expect("database_url is checked by validate()")
+    }
+
+    pub fn manager_endpoint(&self) -> &str {
+        self.manager_endpoint
+            .as_deref()
+            .expect("manager_endpoint is checked by validate()")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_overrides_file() {
+        let mut config: Config = toml::from_str(
+            r#"
+            database_url = "postgres://file/db"
+            manager_endpoint = "http://manager:50051"
+            "#,
+        )
+        .unwrap();
+        config
+            .apply_env(|name| match name {
+                "DATABASE_URL" => Some("postgres://env/db".to_string()),
+                "LISTEN_ADDR" => Some("127.0.0.1:8080".to_string()),
+                "PASSWORD_BREACH_CHECK" => Some("true".to_string()),
+                "DISCORD_SOLVES_CHANNEL_ID" => Some("1".to_string()),
+                "DISCORD_SOLVES_GUILD_ID" => Some("2".to_string()),
+                "HCAPTCHA_SITE_KEY" => Some("site".to_string()),
+                "HCAPTCHA_SECRET_KEY" => Some("secret".to_string()),
+                "CAPTCHA_ON_LOGIN" => Some("1".to_string()),
+                "STORAGE_BACKEND" => Some("s3".to_string()),
+                "S3_BUCKET" => Some("uploads".to_string()),
+                _ => None,
+            })
+            .unwrap();
+
+        assert_eq!(config.database_url(), "postgres://env/db");
+        assert_eq!(config.manager_endpoint(), "http://manager:50051");
+        assert_eq!(config.listen_addr, "127.0.0.1:8080".parse().unwrap());
+        assert!(config.password_breach_check);
+        assert_eq!(config.discord_solves_channel_id, Some(1));
+        assert_eq!(config.hcaptcha_secret_key.as_deref(), Some("secret"));
+        assert!(config.captcha_on_login);
+        assert_eq!(config.storage_backend, StorageBackend::S3);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_reports_all_errors() {
+        let config = Config {
+            tls_cert_file: Some(PathBuf::from("cert.pem")),
+            storage_backend: StorageBackend::S3,
+            ..Default::default()
+        };
+        let Err(ConfigError::Invalid(errors)) = config.validate() else {
+            panic!("Expected the config to be invalid");
+        };
+        assert_eq!(errors.len(), 4);
+    }
+
+    #[test]
+    fn test_validate_partial_integrations() {
+        let config = Config {
+            database_url: Some("postgres://localhost/db".to_string()),
+            manager_endpoint: Some("http://manager:50051".to_string()),
+            discord_solves_channel_id: Some(1),
+            email_smtp_server: Some("smtp.example.com".to_string()),
+            altcha_secret_key: Some("secret".to_string()),
+            turnstile_secret_key: Some("secret".to_string()),
+            ..Default::default()
+        };
+        let Err(ConfigError::Invalid(errors)) = config.validate() else {
+            panic!("Expected the config to be invalid");
+        };
+        // Discord guild, the other SMTP settings, the Turnstile site key and two CAPTCHA providers
+        assert_eq!(errors.len(), 4);
+    }
+}
@@ -28,15 +28,37 @@ use super::schema::*;
 #[ExistingTypePath = "crate::db::schema::sql_types::UserRole"]
 pub enum UserRole {
     Player,
+    /// Can solve unreleased challenges that are open for playtesting
+    Tester,
     Author,
     Admin,
 }
 
+/// Whether an admin has allowed the user to sign in
+#[derive(
+    diesel_derive_enum::DbEnum,
+    Debug,
+    PartialEq,
+    Eq,
+    Deserialize,
+    Serialize,
+    Clone,
+    Copy,
+    GraphQLEnum,
+)]
+#[DbValueStyle = "UPPERCASE"]
+#[ExistingTypePath = "crate::db::schema::sql_types::ApprovalStatus"]
+pub enum ApprovalStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
 /* =========================
  * USERS
  * ========================= */
 
-#[derive(Queryable, Selectable, Identifiable, Debug)]
+#[derive(Queryable, Selectable, Identifiable, Debug, Clone)]
 #[diesel(table_name = users)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct User {
@@ -51,6 +73,12 @@ pub struct User {
     pub email_verified_at: Option<DateTime<Utc>>,
     pub is_active: bool,
     pub team_id: Option<Uuid>,
+    pub avatar_path: Option<String>,
+    pub approval_status: ApprovalStatus,
+    /// Excluded from the scoreboard and statistics, e.g. organizers and test accounts
+    pub hidden: bool,
+    /// The invite code the user registered with, if any
+    pub invite_code_id: Option<Uuid>,
 }
 
 #[derive(Insertable, Debug)]
@@ -64,6 +92,8 @@ pub struct NewUser {
     pub email_verified_at: Option<DateTime<Utc>>,
     pub is_active: bool,
     pub team_id: Option<Uuid>,
+    pub approval_status: ApprovalStatus,
+    pub invite_code_id: Option<Uuid>,
 }
 
 /* =========================
@@ -108,6 +138,16 @@ pub struct Team {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub join_code: Option<String>,
+    pub avatar_path: Option<String>,
+    /// ISO 3166-1 alpha-2 code in uppercase
+    pub country_code: Option<String>,
+    pub affiliation: Option<String>,
+    /// ID of the division in the event config
+    pub division: Option<String>,
+    /// Excluded from the scoreboard and statistics, e.g. test teams
+    pub hidden: bool,
+    /// Excluded from the scoreboard and barred from submitting flags for cheating
+    pub disqualified: bool,
 }
 
 #[derive(Insertable, Debug)]
@@ -116,6 +156,18 @@ pub struct NewTeam {
     pub name: String,
     pub slug: String,
     pub join_code: Option<String>,
+    pub country_code: Option<String>,
+    pub affiliation: Option<String>,
+    pub division: Option<String>,
+}
+
+/// Changes to the details of a team, fields that are None are kept
+#[derive(AsChangeset, Debug)]
+#[diesel(table_name = teams)]
+pub struct TeamDetailsChangeset {
+    pub country_code: Option<Option<String>>,
+    pub affiliation: Option<Option<String>>,
+    pub updated_at: DateTime<Utc>,
 }
 
 /* =========================
@@ -132,15 +184,27 @@ pub struct Solve {
     pub challenge_id: String,
     pub solved_at: DateTime<Utc>,
     pub submitted_flag: String,
+    /// Points this solve is currently worth, 0 if a teammate solved the challenge first
+    pub awarded_points: Option<i32>,
+    pub ip_address: Option<ipnet::IpNet>,
+    pub user_agent: Option<String>,
+    /// Team of the user at the time of the solve
+    pub team_id: Option<Uuid>,
+    /// Made before the challenge was released, so it doesn't count for scoring
+    pub playtest: bool,
 }
 
 #[derive(Insertable, Debug)]
 #[diesel(table_name = solves)]
 pub struct NewSolve {
     pub user_id: Uuid,
+    pub team_id: Option<Uuid>,
     pub challenge_id: String,
     pub submitted_flag: String,
     pub solved_at: DateTime<Utc>,
+    pub ip_address: Option<ipnet::IpNet>,
+    pub user_agent: Option<String>,
+    pub playtest: bool,
 }
 
 /* =========================
@@ -157,6 +221,10 @@ pub struct InvalidSubmission {
     pub challenge_id: String,
     pub submitted_flag: String,
     pub submitted_at: DateTime<Utc>,
+    pub ip_address: Option<ipnet::IpNet>,
+    pub user_agent: Option<String>,
+    /// The team the user was in when submitting
+    pub team_id: Option<Uuid>,
 }
 
 #[derive(Insertable, Debug)]
@@ -166,4 +234,495 @@ pub struct NewInvalidSubmission {
     pub challenge_id: String,
     pub submitted_flag: String,
     pub submitted_at: DateTime<Utc>,
+    pub ip_address: Option<ipnet::IpNet>,
+    pub user_agent: Option<String>,
+    pub team_id: Option<Uuid>,
+}
+
+/* =========================
+ * DATA EXPORTS
+ * ========================= */
+
+#[derive(
+    diesel_derive_enum::DbEnum,
+    Debug,
+    PartialEq,
+    Eq,
+    Deserialize,
+    Serialize,
+    Clone,
+    Copy,
+    GraphQLEnum,
+)]
+#[DbValueStyle = "UPPERCASE"]
+#[ExistingTypePath = "crate::db::schema::sql_types::DataExportStatus"]
+pub enum DataExportStatus {
+    Pending,
+    Completed,
+    Failed,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug)]
+#[diesel(table_name = data_exports)]
+#[diesel(belongs_to(User))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DataExport {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub status: DataExportStatus,
+    pub file_path: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = data_exports)]
+pub struct NewDataExport {
+    pub user_id: Uuid,
+}
+
+/* =========================
+ * WEBHOOKS
+ * ========================= */
+
+/// Platform events webhooks can subscribe to
+#[derive(
+    diesel_derive_enum::DbEnum,
+    Debug,
+    PartialEq,
+    Eq,
+    Deserialize,
+    Serialize,
+    Clone,
+    Copy,
+    GraphQLEnum,
+)]
+#[DbValueStyle = "SCREAMING_SNAKE_CASE"]
+#[ExistingTypePath = "crate::db::schema::sql_types::WebhookEvent"]
+pub enum WebhookEvent {
+    Solve,
+    /// The first solve of a challenge
+    FirstBlood,
+    Registration,
+    InstanceStart,
+    TicketCreated,
+    /// A release wave of the event config was released
+    ChallengeRelease,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Debug, Clone)]
+#[diesel(table_name = webhooks)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Webhook {
+    pub id: Uuid,
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<WebhookEvent>,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = webhooks)]
+pub struct NewWebhook {
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<WebhookEvent>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug)]
+#[diesel(table_name = webhook_deliveries)]
+#[diesel(belongs_to(Webhook))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: WebhookEvent,
+    pub payload: String,
+    pub attempts: i32,
+    pub response_status: Option<i32>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = webhook_deliveries)]
+pub struct NewWebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: WebhookEvent,
+    pub payload: String,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = issued_flags)]
+pub struct NewIssuedFlag {
+    pub challenge_id: String,
+    pub actor: String,
+    pub flag: String,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug)]
+#[diesel(table_name = cheating_incidents)]
+#[diesel(belongs_to(User))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CheatingIncident {
+    pub id: Uuid,
+    pub challenge_id: String,
+    pub user_id: Uuid,
+    pub submitting_actor: String,
+    pub owning_actor: String,
+    pub submitted_flag: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = cheating_incidents)]
+pub struct NewCheatingIncident {
+    pub challenge_id: String,
+    pub user_id: Uuid,
+    pub submitting_actor: String,
+    pub owning_actor: String,
+    pub submitted_flag: String,
+}
+
+#[derive(Queryable, Selectable, Insertable, AsChangeset, Debug, Clone)]
+#[diesel(table_name = email_preferences)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct EmailPreferences {
+    pub user_id: Uuid,
+    pub team_member_joined: bool,
+    pub team_member_left: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+/* =========================
+ * EMAIL BROADCASTS
+ * ========================= */
+
+#[derive(
+    diesel_derive_enum::DbEnum,
+    Debug,
+    PartialEq,
+    Eq,
+    Deserialize,
+    Serialize,
+    Clone,
+    Copy,
+    GraphQLEnum,
+)]
+#[DbValueStyle = "UPPERCASE"]
+#[ExistingTypePath = "crate::db::schema::sql_types::EmailDeliveryStatus"]
+pub enum EmailDeliveryStatus {
+    Pending,
+    Sent,
+    /// Sending failed too often, it won't be retried
+    Failed,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Debug, Clone)]
+#[diesel(table_name = email_broadcasts)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct EmailBroadcast {
+    pub id: Uuid,
+    pub subject: String,
+    pub body_md: String,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = email_broadcasts)]
+pub struct NewEmailBroadcast {
+    pub subject: String,
+    pub body_md: String,
+    pub created_by: Option<Uuid>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Debug, Clone)]
+#[diesel(table_name = email_deliveries)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct EmailDelivery {
+    pub id: Uuid,
+    pub broadcast_id: Uuid,
+    pub user_id: Uuid,
+    pub status: EmailDeliveryStatus,
+    pub attempts: i32,
+    pub error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = email_deliveries)]
+pub struct NewEmailDelivery {
+    pub broadcast_id: Uuid,
+    pub user_id: Uuid,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = maintenance_mode)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct MaintenanceMode {
+    pub enabled: bool,
+    pub message: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = archive_mode)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ArchiveMode {
+    pub enabled: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(
+    diesel_derive_enum::DbEnum,
+    Debug,
+    PartialEq,
+    Eq,
+    Deserialize,
+    Serialize,
+    Clone,
+    Copy,
+    GraphQLEnum,
+)]
+#[DbValueStyle = "SCREAMING_SNAKE_CASE"]
+#[ExistingTypePath = "crate::db::schema::sql_types::NotificationKind"]
+pub enum NotificationKind {
+    NewChallenge,
+    AdminMessage,
+    /// A ticket was answered or a player replied to it
+    TicketUpdate,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Debug, Clone)]
+#[diesel(table_name = notifications)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Notification {
+    pub id: Uuid,
+    /// None if the notification was sent to everyone
+    pub user_id: Option<Uuid>,
+    pub kind: NotificationKind,
+    pub title: String,
+    pub body: String,
+    pub challenge_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = notifications)]
+pub struct NewNotification {
+    pub user_id: Option<Uuid>,
+    pub kind: NotificationKind,
+    pub title: String,
+    pub body: String,
+    pub challenge_id: Option<String>,
+    /// Notifications are only shown from this time on, defaults to now
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = notification_reads)]
+pub struct NewNotificationRead {
+    pub notification_id: Uuid,
+    pub user_id: Uuid,
+}
+
+/* =========================
+ * TICKETS
+ * ========================= */
+
+#[derive(
+    diesel_derive_enum::DbEnum,
+    Debug,
+    PartialEq,
+    Eq,
+    Deserialize,
+    Serialize,
+    Clone,
+    Copy,
+    GraphQLEnum,
+)]
+#[DbValueStyle = "UPPERCASE"]
+#[ExistingTypePath = "crate::db::schema::sql_types::TicketStatus"]
+pub enum TicketStatus {
+    /// Waiting for an answer from the organizers or challenge authors
+    Open,
+    /// Waiting for the player
+    Answered,
+    Closed,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug, Clone)]
+#[diesel(table_name = tickets)]
+#[diesel(belongs_to(User))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Ticket {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub subject: String,
+    pub challenge_id: Option<String>,
+    pub status: TicketStatus,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = tickets)]
+pub struct NewTicket {
+    pub user_id: Uuid,
+    pub subject: String,
+    pub challenge_id: Option<String>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug, Clone)]
+#[diesel(table_name = ticket_messages)]
+#[diesel(belongs_to(Ticket))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct TicketMessage {
+    pub id: Uuid,
+    pub ticket_id: Uuid,
+    pub author_id: Option<Uuid>,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = ticket_messages)]
+pub struct NewTicketMessage {
+    pub ticket_id: Uuid,
+    pub author_id: Option<Uuid>,
+    pub body: String,
+}
+
+/* =========================
+ * INVITE CODES
+ * ========================= */
+
+#[derive(Queryable, Selectable, Identifiable, Debug, Clone)]
+#[diesel(table_name = invite_codes)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct InviteCode {
+    pub id: Uuid,
+    pub code: String,
+    pub max_uses: i32,
+    pub uses: i32,
+    pub role: Option<UserRole>,
+    pub division: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = invite_codes)]
+pub struct NewInviteCode {
+    pub code: String,
+    pub max_uses: i32,
+    pub role: Option<UserRole>,
+    pub division: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_by: Option<Uuid>,
+}
+
+#[derive(
+    diesel_derive_enum::DbEnum,
+    Debug,
+    PartialEq,
+    Eq,
+    Deserialize,
+    Serialize,
+    Clone,
+    Copy,
+    GraphQLEnum,
+)]
+#[DbValueStyle = "SCREAMING_SNAKE_CASE"]
+#[ExistingTypePath = "crate::db::schema::sql_types::AuditAction"]
+pub enum AuditAction {
+    SolveRevoked,
+    ScoreZeroed,
+    TeamDisqualified,
+    TeamRequalified,
+    InstanceExec,
+    ChallengeRejudged,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Debug, Clone)]
+#[diesel(table_name = audit_log)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AuditLogEntry {
+    pub id: Uuid,
+    /// None once the admin's account was deleted
+    pub admin_id: Option<Uuid>,
+    pub action: AuditAction,
+    /// The team or user the action was taken against
+    pub target_id: Option<Uuid>,
+    pub details: String,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = audit_log)]
+pub struct NewAuditLogEntry {
+    pub admin_id: Option<Uuid>,
+    pub action: AuditAction,
+    pub target_id: Option<Uuid>,
+    pub details: String,
+    pub reason: Option<String>,
+}
+
+/* =========================
+ * JOBS
+ * ========================= */
+
+#[derive(
+    diesel_derive_enum::DbEnum,
+    Debug,
+    PartialEq,
+    Eq,
+    Deserialize,
+    Serialize,
+    Clone,
+    Copy,
+    GraphQLEnum,
+)]
+#[DbValueStyle = "UPPERCASE"]
+#[ExistingTypePath = "crate::db::schema::sql_types::JobStatus"]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Succeeded,
+    /// All attempts failed, it won't be retried
+    Failed,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Debug, Clone)]
+#[diesel(table_name = jobs)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Job {
+    pub id: Uuid,
+    pub kind: String,
+    /// JSON passed to the handler
+    pub payload: String,
+    pub status: JobStatus,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub error: Option<String>,
+    pub run_at: DateTime<Utc>,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = jobs)]
+pub struct NewJob {
+    pub kind: String,
+    pub payload: String,
+    pub max_attempts: i32,
+    pub run_at: DateTime<Utc>,
 }
@@ -1,9 +1,160 @@
 // @generated automatically by Diesel CLI.
 
 pub mod sql_types {
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "audit_action"))]
+    pub struct AuditAction;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "approval_status"))]
+    pub struct ApprovalStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "data_export_status"))]
+    pub struct DataExportStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "email_delivery_status"))]
+    pub struct EmailDeliveryStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "job_status"))]
+    pub struct JobStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "notification_kind"))]
+    pub struct NotificationKind;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "ticket_status"))]
+    pub struct TicketStatus;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "user_role"))]
     pub struct UserRole;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "webhook_event"))]
+    pub struct WebhookEvent;
+}
+
+diesel::table! {
+    archive_mode (id) {
+        id -> Bool,
+        enabled -> Bool,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    attempt_resets (challenge_id, actor_id) {
+        challenge_id -> Varchar,
+        actor_id -> Uuid,
+        reset_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::AuditAction;
+
+    audit_log (id) {
+        id -> Uuid,
+        admin_id -> Nullable<Uuid>,
+        action -> AuditAction,
+        target_id -> Nullable<Uuid>,
+        details -> Text,
+        reason -> Nullable<Text>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    cheating_incidents (id) {
+        id -> Uuid,
+        challenge_id -> Varchar,
+        user_id -> Uuid,
+        submitting_actor -> Varchar,
+        owning_actor -> Varchar,
+        submitted_flag -> Varchar,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::DataExportStatus;
+
+    data_exports (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        status -> DataExportStatus,
+        file_path -> Nullable<Varchar>,
+        created_at -> Timestamptz,
+        completed_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    email_broadcasts (id) {
+        id -> Uuid,
+        subject -> Varchar,
+        body_md -> Text,
+        created_by -> Nullable<Uuid>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EmailDeliveryStatus;
+
+    email_deliveries (id) {
+        id -> Uuid,
+        broadcast_id -> Uuid,
+        user_id -> Uuid,
+        status -> EmailDeliveryStatus,
+        attempts -> Int4,
+        error -> Nullable<Varchar>,
+        next_attempt_at -> Timestamptz,
+        sent_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    email_preferences (user_id) {
+        user_id -> Uuid,
+        team_member_joined -> Bool,
+        team_member_left -> Bool,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    issued_flags (id) {
+        id -> Uuid,
+        challenge_id -> Varchar,
+        actor -> Varchar,
+        flag -> Varchar,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::UserRole;
+
+    invite_codes (id) {
+        id -> Uuid,
+        code -> Varchar,
+        max_uses -> Int4,
+        uses -> Int4,
+        role -> Nullable<UserRole>,
+        division -> Nullable<Varchar>,
+        expires_at -> Nullable<Timestamptz>,
+        created_by -> Nullable<Uuid>,
+        created_at -> Timestamptz,
+    }
 }
 
 diesel::table! {
@@ -13,6 +164,68 @@ diesel::table! {
         challenge_id -> Varchar,
         submitted_flag -> Varchar,
         submitted_at -> Timestamptz,
+        ip_address -> Nullable<Inet>,
+        user_agent -> Nullable<Varchar>,
+        team_id -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::JobStatus;
+
+    jobs (id) {
+        id -> Uuid,
+        kind -> Varchar,
+        payload -> Text,
+        status -> JobStatus,
+        attempts -> Int4,
+        max_attempts -> Int4,
+        error -> Nullable<Text>,
+        run_at -> Timestamptz,
+        locked_until -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        finished_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    maintenance_mode (id) {
+        id -> Bool,
+        enabled -> Bool,
+        message -> Nullable<Varchar>,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    notification_reads (notification_id, user_id) {
+        notification_id -> Uuid,
+        user_id -> Uuid,
+        read_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::NotificationKind;
+
+    notifications (id) {
+        id -> Uuid,
+        user_id -> Nullable<Uuid>,
+        kind -> NotificationKind,
+        title -> Varchar,
+        body -> Text,
+        challenge_id -> Nullable<Varchar>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    released_waves (name, release_time) {
+        name -> Varchar,
+        release_time -> Timestamptz,
+        announced_at -> Timestamptz,
     }
 }
 
@@ -35,6 +248,11 @@ diesel::table! {
         challenge_id -> Varchar,
         solved_at -> Timestamptz,
         submitted_flag -> Varchar,
+        awarded_points -> Nullable<Int4>,
+        ip_address -> Nullable<Inet>,
+        user_agent -> Nullable<Varchar>,
+        team_id -> Nullable<Uuid>,
+        playtest -> Bool,
     }
 }
 
@@ -47,12 +265,45 @@ diesel::table! {
         join_code -> Nullable<Varchar>,
         #[max_length = 255]
         slug -> Varchar,
+        avatar_path -> Nullable<Varchar>,
+        #[max_length = 2]
+        country_code -> Nullable<Varchar>,
+        affiliation -> Nullable<Varchar>,
+        division -> Nullable<Varchar>,
+        hidden -> Bool,
+        disqualified -> Bool,
+    }
+}
+
+diesel::table! {
+    ticket_messages (id) {
+        id -> Uuid,
+        ticket_id -> Uuid,
+        author_id -> Nullable<Uuid>,
+        body -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::TicketStatus;
+
+    tickets (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        subject -> Varchar,
+        challenge_id -> Nullable<Varchar>,
+        status -> TicketStatus,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::UserRole;
+    use super::sql_types::ApprovalStatus;
 
     users (id) {
         id -> Uuid,
@@ -66,18 +317,83 @@ diesel::table! {
         email_verified_at -> Nullable<Timestamptz>,
         is_active -> Bool,
         team_id -> Nullable<Uuid>,
+        avatar_path -> Nullable<Varchar>,
+        approval_status -> ApprovalStatus,
+        hidden -> Bool,
+        invite_code_id -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::WebhookEvent;
+
+    webhook_deliveries (id) {
+        id -> Uuid,
+        webhook_id -> Uuid,
+        event -> WebhookEvent,
+        payload -> Text,
+        attempts -> Int4,
+        response_status -> Nullable<Int4>,
+        error -> Nullable<Text>,
+        created_at -> Timestamptz,
+        delivered_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::WebhookEvent;
+
+    webhooks (id) {
+        id -> Uuid,
+        url -> Varchar,
+        secret -> Varchar,
+        events -> Array<WebhookEvent>,
+        is_active -> Bool,
+        created_at -> Timestamptz,
     }
 }
 
+diesel::joinable!(cheating_incidents -> users (user_id));
+diesel::joinable!(data_exports -> users (user_id));
+diesel::joinable!(email_deliveries -> email_broadcasts (broadcast_id));
+diesel::joinable!(email_deliveries -> users (user_id));
+diesel::joinable!(email_preferences -> users (user_id));
 diesel::joinable!(invalid_submissions -> users (user_id));
+diesel::joinable!(notification_reads -> notifications (notification_id));
+diesel::joinable!(notification_reads -> users (user_id));
+diesel::joinable!(notifications -> users (user_id));
 diesel::joinable!(sessions -> users (user_id));
 diesel::joinable!(solves -> users (user_id));
+diesel::joinable!(ticket_messages -> tickets (ticket_id));
+diesel::joinable!(tickets -> users (user_id));
 diesel::joinable!(users -> teams (team_id));
+diesel::joinable!(webhook_deliveries -> webhooks (webhook_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    archive_mode,
+    attempt_resets,
+    audit_log,
+    cheating_incidents,
+    data_exports,
+    email_broadcasts,
+    email_deliveries,
+    email_preferences,
     invalid_submissions,
+    invite_codes,
+    issued_flags,
+    jobs,
+    maintenance_mode,
+    notification_reads,
+    notifications,
+    released_waves,
     sessions,
     solves,
     teams,
+    ticket_messages,
+    tickets,
     users,
+    webhook_deliveries,
+    webhooks,
 );
@@ -1,23 +1,21 @@
-use std::env;
-
 use serenity::prelude::*;
 
 use tokio::sync::OnceCell;
 
+use crate::config::Config;
+
 static DISCORD_CLIENT: OnceCell<Client> = OnceCell::const_new();
 
-pub async fn get_client() -> Option<&'static Client> {
-    if std::env::var("DISCORD_TOKEN").is_err() {
-        return None;
-    }
+/// Returns the client used to post messages, None if discord_token isn't set.
+pub async fn get_client(config: &Config) -> Option<&'static Client> {
+    let token = config.discord_token.as_deref()?;
     Some(
         DISCORD_CLIENT
             .get_or_init(|| async {
-                let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
                 // Set gateway intents, which decides what events the bot will be notified about
                 let intents = GatewayIntents::empty();
 
-                Client::builder(&token, intents)
+                Client::builder(token, intents)
                     .await
                     .expect("Err creating client")
             })
@@ -25,30 +23,25 @@ pub async fn get_client() -> Option<&'static Client> {
     )
 }
 
-pub async fn run_new_client() -> serenity::Result<()> {
-    let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
+pub async fn run_new_client(token: &str) -> serenity::Result<()> {
     // Set gateway intents, which decides what events the bot will be notified about
     let intents = GatewayIntents::empty();
 
-    let mut client = Client::builder(&token, intents)
+    let mut client = Client::builder(token, intents)
         .await
         .expect("Err creating client");
     client.start().await
 }
 
-pub async fn remind_xtea() -> serenity::Result<()> {
+pub async fn remind_xtea(config: &Config) -> serenity::Result<()> {
     use serenity::all::{Builder, ChannelId, CreateMessage, GuildId};
 
     loop {
-        if let Some(client) = get_client().await {
+        if let Some(client) = get_client(config).await {
             if let Some(discord_public_invalid_submissions_channel) =
-                std::env::var("DISCORD_PUBLIC_INVALID_SUBMISSIONS_CHANNEL_ID")
-                    .ok()
-                    .and_then(|id| id.parse::<u64>().ok())
+                config.discord_public_invalid_submissions_channel_id
                 && let Some(discord_public_invalid_submissions_guild) =
-                    std::env::var("DISCORD_PUBLIC_INVALID_SUBMISSIONS_GUILD_ID")
-                        .ok()
-                        .and_then(|id| id.parse::<u64>().ok())
+                    config.discord_public_invalid_submissions_guild_id
             {
                 Builder::execute(
                     CreateMessage::new().content("<@496716151263330304> still hasn't submitted the Google CTF application. Plz submit it now or you will no longer be allowed to play in the sandbox!"),
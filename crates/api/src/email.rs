@@ -0,0 +1,70 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::sync::OnceLock;
+
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor, message::Mailbox,
+    transport::smtp::authentication::Credentials,
+};
+use thiserror::Error;
+
+use crate::config::Config;
+
+struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+/// The SMTP connection, set up by [`init`] if the email_smtp_* and email_from_address settings are
+/// configured.
+static MAILER: OnceLock<Mailer> = OnceLock::new();
+
+/// Sets up sending emails. The settings are checked by [`Config::validate`].
+pub fn init(config: &Config) {
+    let (Some(server), Some(username), Some(password), Some(from)) = (
+        &config.email_smtp_server,
+        &config.email_smtp_username,
+        &config.email_smtp_password,
+        &config.email_from_address,
+    ) else {
+        return;
+    };
+    let transport = AsyncSmtpTransport::<Tokio1Executor>::relay(server)
+        .expect("email_smtp_server is checked by validate()")
+        .credentials(Credentials::new(username.clone(), password.clone()))
+        .build();
+    let from = from
+        .parse()
+        .expect("email_from_address is checked by validate()");
+    let _ = MAILER.set(Mailer { transport, from });
+}
+
+#[derive(Error, Debug)]
+pub enum EmailError {
+    #[error("Email is not configured")]
+    NotConfigured,
+    #[error("Invalid email address: {0}")]
+    InvalidAddress(#[from] lettre::address::AddressError),
+    #[error("Failed to build email: {0}")]
+    BuildError(#[from] lettre::error::Error),
+    #[error("Failed to send email: {0}")]
+    SmtpError(#[from] lettre::transport::smtp::Error),
+}
+
+pub fn is_configured() -> bool {
+    MAILER.get().is_some()
+}
+
+/// Sends a plain text email.
+pub async fn send_email(to: &str, subject: &str, body: String) -> Result<(), EmailError> {
+    let mailer = MAILER.get().ok_or(EmailError::NotConfigured)?;
+    let message = Message::builder()
+        .from(mailer.from.clone())
+        .to(to.parse()?)
+        .subject(subject)
+        .body(body)?;
+    mailer.transport.send(message).await?;
+    Ok(())
+}
@@ -0,0 +1,162 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::cell::RefCell;
+
+use diesel::connection::{Instrumentation, InstrumentationEvent};
+use serde_json::json;
+
+pub use plfanzen_error_reporting::{Dsn, capture, is_enabled};
+
+tokio::task_local! {
+    /// Queries that failed while handling the current GraphQL request
+    static FAILED_QUERIES: RefCell<Vec<FailedQuery>>;
+}
+
+/// A database query that returned an error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedQuery {
+    pub message: String,
+    pub query: String,
+}
+
+/// Starts reporting panics, failures of the manager and database errors if a DSN is configured.
+///
+/// Must be called before the first database connection is opened, connections only report errors
+/// if they are opened afterwards.
+pub fn init(dsn: Option<&str>, environment: Option<String>) {
+    plfanzen_error_reporting::init("plfanzen-api", dsn, environment);
+    if is_enabled()
+        && let Err(e) = diesel::connection::set_default_instrumentation(report_failed_queries)
+    {
+        tracing::warn!("Failed to report database errors: {}", e);
+    }
+}
+
+/// Collects the failed queries of GraphQL requests, and reports those outside of requests, e.g. in
+/// background jobs, directly.
+fn report_failed_queries() -> Option<Box<dyn Instrumentation>> {
+    Some(Box::new(|event: InstrumentationEvent<'_>| {
+        let InstrumentationEvent::FinishQuery {
+            query,
+            error: Some(error),
+            ..
+        } = event
+        else {
+            return;
+        };
+        let query = query.to_string();
+        let failed = FailedQuery {
+            message: error.to_string(),
+            // Bind parameters may contain personal data
+            query: query
+                .split_once(" -- binds:")
+                .map_or(query.as_str(), |(sql, _)| sql)
+                .to_string(),
+        };
+        let in_request = FAILED_QUERIES
+            .try_with(|queries| queries.borrow_mut().push(failed.clone()))
+            .is_ok();
+        if !in_request {
+            capture("database", failed.message, json!({ "query": failed.query }));
+        }
+    }))
+}
+
+/// Runs a GraphQL request, returning its response together with the queries that failed during it.
+pub async fn track_failed_queries<T>(request: impl Future<Output = T>) -> (T, Vec<FailedQuery>) {
+    FAILED_QUERIES
+        .scope(RefCell::new(Vec::new()), async {
+            let response = request.await;
+            let failed = FAILED_QUERIES.with(|queries| queries.take());
+            (response, failed)
+        })
+        .await
+}
+
+/// gRPC status codes (as shown in the error message) that mean the manager failed, as opposed to
+/// rejecting the request
+const INTERNAL_GRPC_STATUSES: [&str; 4] = [
+    "status: 'Internal error'",
+    "status: 'Unknown error'",
+    "status: 'The service is currently unavailable'",
+    "status: 'Unrecoverable data loss or corruption'",
+];
+
+/// Whether a GraphQL error message comes from a failure of the manager.
+///
+/// Errors of the api itself are expected outcomes like "Challenge not found" and are not reported.
+/// Panics in resolvers are reported by the panic hook, database errors by
+/// [`capture_graphql_errors`] if they end up in the response.
+fn is_internal_error(message: &str) -> bool {
+    INTERNAL_GRPC_STATUSES
+        .iter()
+        .any(|status| message.starts_with(status))
+}
+
+/// Reports the errors in a GraphQL response that were caused by failures of the manager or the
+/// database.
+///
+/// Database errors the resolvers handle, e.g. a name that is already taken, are not in the
+/// response and therefore not reported.
+pub fn capture_graphql_errors(
+    operation: &str,
+    response: &str,
+    failed_queries: &[FailedQuery],
+    request: serde_json::Value,
+) {
+    if !is_enabled() {
+        return;
+    }
+    let Ok(response) = serde_json::from_str::<serde_json::Value>(response) else {
+        return;
+    };
+    let Some(errors) = response["errors"].as_array() else {
+        return;
+    };
+    for error in errors {
+        let Some(message) = error["message"].as_str() else {
+            continue;
+        };
+        if !error["extensions"]["code"].is_null() {
+            continue;
+        }
+        if is_internal_error(message) {
+            capture(
+                "graphql",
+                message,
+                json!({
+                    "operation": operation,
+                    "path": error["path"],
+                    "request": request,
+                }),
+            );
+        } else if let Some(failed) = failed_queries.iter().find(|q| q.message == message) {
+            capture(
+                "database",
+                message,
+                json!({
+                    "operation": operation,
+                    "path": error["path"],
+                    "query": failed.query,
+                    "request": request,
+                }),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_manager_failures_are_internal() {
+        let internal = tonic::Status::internal("Failed to deploy challenge").to_string();
+        assert!(is_internal_error(&internal));
+        let not_found = tonic::Status::not_found("Challenge not found").to_string();
+        assert!(!is_internal_error(&not_found));
+        assert!(!is_internal_error("Authentication required"));
+    }
+}
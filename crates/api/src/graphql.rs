@@ -4,11 +4,14 @@
 
 use std::net::IpAddr;
 
-use juniper::EmptySubscription;
 pub use mutation::Mutation;
 pub use query::Query;
+pub use subscription::Subscription;
 
-use crate::{db::models::UserRole, graphql::handlers::challenges::CtfChallengeMetadata};
+use crate::{
+    db::models::UserRole,
+    graphql::handlers::{challenges::CtfChallengeMetadata, scoreboard::Scoreboard},
+};
 
 use diesel::prelude::*;
 use diesel_async::RunQueryDsl;
@@ -18,16 +21,39 @@ use std::time::Duration;
 pub mod auth;
 mod captcha;
 mod handlers;
+mod loaders;
 mod mutation;
 mod query;
+mod subscription;
 
+pub use captcha::init as init_captcha;
+pub use handlers::avatars::retrieve_avatar;
 pub use handlers::challenges::export::{export_challenge, retrieve_file};
+pub use handlers::challenges::orphaned_instances::start_orphan_collector;
+pub use handlers::email_broadcasts::start_email_worker;
+pub use handlers::exports::export_data;
+pub use handlers::maintenance::check_maintenance;
+pub use handlers::repo::git_webhook::handle_git_webhook;
+pub use handlers::repo::release_waves::start_release_watcher;
+pub use handlers::scoreboard::ctftime::ctftime_scoreboard;
+pub use handlers::scoreboard::points::start_points_worker;
+pub use handlers::users::data_export::{
+    DATA_EXPORT_JOB, download_data_export, run_data_export_job,
+};
+
+/// Connection to the manager, passing on the trace context of the current request
+pub type GrpcChannel = tonic::service::interceptor::InterceptedService<
+    tonic::transport::Channel,
+    crate::telemetry::TraceContextInterceptor,
+>;
 
 #[derive(Clone)]
 pub struct BaseContext {
-    pub grpc_client: tonic::transport::Channel,
+    pub grpc_client: GrpcChannel,
     pub db_pool: diesel_async::pooled_connection::bb8::Pool<diesel_async::AsyncPgConnection>,
     pub keypair: ed25519_dalek::SigningKey,
+    pub config: std::sync::Arc<crate::config::Config>,
+    pub storage: crate::storage::Storage,
 }
 
 pub struct Context {
@@ -37,7 +63,9 @@ pub struct Context {
     user: Option<AuthenticatedUser>,
     challenges_cache:
         moka::future::Cache<String, Result<Vec<CtfChallengeMetadata>, juniper::FieldError>>,
+    scoreboard: tokio::sync::OnceCell<Scoreboard>,
     total_competitors: i32,
+    loaders: loaders::Loaders,
 }
 
 impl juniper::Context for Context {}
@@ -49,14 +77,25 @@ pub struct AuthenticatedUser {
     pub team_id: Option<uuid::Uuid>,
     pub username: String,
     pub team_slug: Option<String>,
+    pub session_id: Option<uuid::Uuid>,
 }
 
+/// Who challenges are scoped to: the team of a user, or the user if they are not in a team
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Actor {
     User { id: uuid::Uuid, username: String },
     Team { id: uuid::Uuid, slug: String },
 }
 
 impl Actor {
+    /// ID of the team or user
+    pub fn id(&self) -> uuid::Uuid {
+        match self {
+            Actor::User { id, .. } | Actor::Team { id, .. } => *id,
+        }
+    }
+
+    /// The actor as sent to the manager, e.g. "team-foo" or "user-bar"
     pub fn slug(&self) -> String {
         match self {
             Actor::User { username, .. } => format!("user-{username}"),
@@ -66,40 +105,59 @@ impl Actor {
 }
 
 impl AuthenticatedUser {
-    pub fn actor(&self) -> String {
-        match &self.team_slug {
-            Some(slug) => format!("team-{slug}"),
-            None => format!("user-{}", self.username),
-        }
-    }
-
-    pub fn actor_details(&self) -> Actor {
-        match &self.team_id {
-            Some(id) => Actor::Team {
-                id: id.clone(),
-                slug: self.team_slug.clone().unwrap_or_default(),
+    pub fn actor(&self) -> Actor {
+        match (self.team_id, &self.team_slug) {
+            (Some(id), Some(slug)) => Actor::Team {
+                id,
+                slug: slug.clone(),
             },
-            None => Actor::User {
-                id: self.user_id.clone(),
+            _ => Actor::User {
+                id: self.user_id,
                 username: self.username.clone(),
             },
         }
     }
 }
 
+/// Number of teams and users without a team competing in the event.
+///
+/// Only active, approved players that aren't hidden count, so staff, test accounts and empty teams
+/// don't affect scoring.
+pub(crate) async fn count_competitors(
+    db_pool: &diesel_async::pooled_connection::bb8::Pool<diesel_async::AsyncPgConnection>,
+) -> juniper::FieldResult<i32> {
+    use crate::db::models::ApprovalStatus;
+    use crate::db::schema::{teams, users};
+
+    let competitors = users::table
+        .left_join(teams::table)
+        .filter(users::is_active.eq(true))
+        .filter(users::approval_status.eq(ApprovalStatus::Approved))
+        .filter(users::role.eq(UserRole::Player))
+        .filter(users::hidden.eq(false))
+        .filter(
+            teams::hidden
+                .nullable()
+                .eq(false)
+                .or(teams::id.nullable().is_null()),
+        )
+        .filter(
+            teams::disqualified
+                .nullable()
+                .eq(false)
+                .or(teams::id.nullable().is_null()),
+        )
+        .select(diesel::dsl::sql::<diesel::sql_types::BigInt>(
+            "COUNT(DISTINCT COALESCE(users.team_id, users.id))",
+        ))
+        .get_result::<i64>(&mut db_pool.get().await?)
+        .await?;
+    Ok(competitors as i32)
+}
+
 #[cached::proc_macro::cached(time = 300, key = "()", convert = "{ }", result = true)]
 async fn get_total_competitors(context: &Context) -> juniper::FieldResult<i32> {
-    // This chould be optimized, but for now this is fine
-    let conn = &mut context.get_db_conn().await;
-    use crate::db::schema::teams::dsl::*;
-    let team_count: i64 = teams.count().get_result(conn).await?;
-    use crate::db::schema::users::dsl::*;
-    let user_count: i64 = users.count().get_result(conn).await?;
-    if team_count == 0 {
-        Ok(user_count as i32)
-    } else {
-        Ok(team_count as i32)
-    }
+    count_competitors(&context.base.db_pool).await
 }
 
 impl Context {
@@ -109,13 +167,16 @@ impl Context {
         user_agent: String,
         user_details: Option<AuthenticatedUser>,
     ) -> Self {
+        let loaders = loaders::Loaders::new(base.db_pool.clone(), user_details.as_ref());
         let mut tmp = Self {
             base,
             ip,
             user_agent,
             user: user_details,
             challenges_cache: moka::future::Cache::builder().build(),
+            scoreboard: tokio::sync::OnceCell::new(),
             total_competitors: 0,
+            loaders,
         };
         tmp.total_competitors = get_total_competitors(&tmp).await.unwrap_or(0);
         tmp
@@ -134,9 +195,7 @@ impl Context {
 
     fn repo_client(
         &self,
-    ) -> crate::manager_api::repository_service_client::RepositoryServiceClient<
-        tonic::transport::Channel,
-    > {
+    ) -> crate::manager_api::repository_service_client::RepositoryServiceClient<GrpcChannel> {
         crate::manager_api::repository_service_client::RepositoryServiceClient::new(
             self.base.grpc_client.clone(),
         )
@@ -144,14 +203,23 @@ impl Context {
 
     pub fn challenges_client(
         &self,
-    ) -> crate::manager_api::challenges_service_client::ChallengesServiceClient<
-        tonic::transport::Channel,
-    > {
+    ) -> crate::manager_api::challenges_service_client::ChallengesServiceClient<GrpcChannel> {
         crate::manager_api::challenges_service_client::ChallengesServiceClient::new(
             self.base.grpc_client.clone(),
         )
     }
 
+    /// Who made the request, attached to reported errors.
+    pub fn error_report_context(&self) -> serde_json::Value {
+        serde_json::json!({
+            "ip": self.ip.to_string(),
+            "user_agent": self.user_agent,
+            "user_id": self.user.as_ref().map(|u| u.user_id.to_string()),
+            "username": self.user.as_ref().map(|u| u.username.as_str()),
+            "team_id": self.user.as_ref().and_then(|u| u.team_id.map(|id| id.to_string())),
+        })
+    }
+
     pub fn is_authenticated(&self) -> bool {
         self.user.is_some()
     }
@@ -195,6 +263,11 @@ impl Context {
         &self.ip
     }
 
+    /// The client IP as a single-address network, which is how IPs are stored in the database
+    pub fn get_ip_net(&self) -> ipnet::IpNet {
+        ipnet::IpNet::from(self.ip)
+    }
+
     pub fn get_user_agent(&self) -> &str {
         &self.user_agent
     }
@@ -204,4 +277,4 @@ impl Context {
     }
 }
 
-pub type Schema = juniper::RootNode<Query, Mutation, EmptySubscription<Context>>;
+pub type Schema = juniper::RootNode<Query, Mutation, Subscription>;
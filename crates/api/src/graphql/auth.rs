@@ -76,6 +76,9 @@ pub struct AuthJwtPayload {
     pub username: String,
     pub team_slug: Option<String>,
     pub team_id: Option<Uuid>,
+    /// The session this token was issued for
+    #[serde(default)]
+    pub session_id: Option<Uuid>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -189,6 +192,7 @@ mod tests {
             username: "testuser".to_string(),
             team_slug: None,
             team_id: None,
+            session_id: None,
         };
 
         let jwt_payload = JwtPayload::new_with_duration(
@@ -220,6 +224,7 @@ mod tests {
             username: "testuser".to_string(),
             team_slug: None,
             team_id: None,
+            session_id: None,
         };
         let jwt_payload = JwtPayload::new_with_duration(
             uuid::Uuid::now_v7(),
@@ -244,6 +249,7 @@ mod tests {
             username: "testuser".to_string(),
             team_slug: None,
             team_id: None,
+            session_id: None,
         };
         let jwt_payload = JwtPayload::new_with_duration(
             uuid::Uuid::now_v7(),
@@ -1,32 +1,70 @@
-use std::sync::LazyLock;
+use std::sync::OnceLock;
+
+use crate::config::Config;
 
 mod altcha;
 mod cap;
 mod dummy;
+mod hcaptcha;
+mod turnstile;
 
-static CAPTCHA_PROVIDER: LazyLock<Box<dyn CaptchaProvider + Send + Sync>> = LazyLock::new(|| {
-    for provider in [
-        Box::new(altcha::AltchaProvider) as Box<dyn CaptchaProvider + Send + Sync>,
-        Box::new(cap::CapProvider) as Box<dyn CaptchaProvider + Send + Sync>,
-    ] {
-        if provider.is_available() {
-            tracing::info!("Using CAPTCHA provider: {:?}", provider.provider_type());
-            return provider;
-        }
-    }
-    Box::new(dummy::DummyProvider)
-});
+static CAPTCHA_PROVIDER: OnceLock<Box<dyn CaptchaProvider + Send + Sync>> = OnceLock::new();
+
+/// Sets up the CAPTCHA provider configured in `config`, challenges always pass if there is none.
+pub fn init(config: &Config) {
+    let provider: Box<dyn CaptchaProvider + Send + Sync> =
+        if let Some(secret_key) = &config.altcha_secret_key {
+            Box::new(altcha::AltchaProvider {
+                secret_key: secret_key.clone(),
+            })
+        } else if let (Some(instance_url), Some(site_key), Some(secret_key)) = (
+            &config.cap_instance_url,
+            &config.cap_site_key,
+            &config.cap_secret_key,
+        ) {
+            Box::new(cap::CapProvider {
+                instance_url: instance_url.clone(),
+                site_key: site_key.clone(),
+                secret_key: secret_key.clone(),
+            })
+        } else if let (Some(site_key), Some(secret_key)) =
+            (&config.hcaptcha_site_key, &config.hcaptcha_secret_key)
+        {
+            Box::new(hcaptcha::HCaptchaProvider {
+                site_key: site_key.clone(),
+                secret_key: secret_key.clone(),
+            })
+        } else if let (Some(site_key), Some(secret_key)) =
+            (&config.turnstile_site_key, &config.turnstile_secret_key)
+        {
+            Box::new(turnstile::TurnstileProvider {
+                site_key: site_key.clone(),
+                secret_key: secret_key.clone(),
+            })
+        } else {
+            Box::new(dummy::DummyProvider)
+        };
+    tracing::info!("Using CAPTCHA provider: {:?}", provider.provider_type());
+    let _ = CAPTCHA_PROVIDER.set(provider);
+}
+
+fn provider() -> &'static (dyn CaptchaProvider + Send + Sync) {
+    CAPTCHA_PROVIDER
+        .get_or_init(|| Box::new(dummy::DummyProvider))
+        .as_ref()
+}
 
 #[derive(juniper::GraphQLEnum, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CaptchaProviderType {
     Altcha,
     Cap,
+    HCaptcha,
+    Turnstile,
     Dummy,
 }
 
 #[async_trait::async_trait]
 pub trait CaptchaProvider {
-    fn is_available(&self) -> bool;
     fn provider_type(&self) -> CaptchaProviderType;
     async fn get_challenge(&self) -> juniper::FieldResult<serde_json::Value>;
     async fn verify_response(&self, challenge: &str, response: &str) -> juniper::FieldResult<bool>;
@@ -41,9 +79,9 @@ pub struct CaptchaChallenge {
 pub async fn get_captcha_challenge(
     _context: &super::Context,
 ) -> juniper::FieldResult<CaptchaChallenge> {
-    let challenge = CAPTCHA_PROVIDER.get_challenge().await?;
+    let challenge = provider().get_challenge().await?;
     let captcha_challenge = CaptchaChallenge {
-        provider_type: CAPTCHA_PROVIDER.provider_type(),
+        provider_type: provider().provider_type(),
         challenge: serde_json::to_string(&challenge)?,
     };
     Ok(captcha_challenge)
@@ -53,5 +91,32 @@ pub async fn verify_captcha_response(
     challenge: &str,
     response: &str,
 ) -> juniper::FieldResult<bool> {
-    CAPTCHA_PROVIDER.verify_response(challenge, response).await
+    provider().verify_response(challenge, response).await
+}
+
+/// Verifies a response token with a siteverify endpoint, as used by hCaptcha and Turnstile.
+async fn siteverify(url: &str, params: &[(&str, &str)]) -> juniper::FieldResult<bool> {
+    let resp = reqwest::Client::new()
+        .post(url)
+        .timeout(std::time::Duration::from_secs(5))
+        .form(params)
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        tracing::warn!("CAPTCHA verification HTTP error: {}", resp.status());
+        return Ok(false);
+    }
+
+    let json: serde_json::Value = resp.json().await?;
+    let success = json
+        .get("success")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+
+    if !success {
+        tracing::info!("CAPTCHA verification failed: {:?}", json);
+    }
+
+    Ok(success)
 }
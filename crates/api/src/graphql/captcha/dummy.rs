@@ -6,10 +6,6 @@ pub struct DummyProvider;
 
 #[async_trait::async_trait]
 impl CaptchaProvider for DummyProvider {
-    fn is_available(&self) -> bool {
-        true
-    }
-
     fn provider_type(&self) -> CaptchaProviderType {
         CaptchaProviderType::Dummy
     }
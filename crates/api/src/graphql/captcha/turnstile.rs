@@ -0,0 +1,37 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use serde_json::json;
+
+use crate::graphql::captcha::{CaptchaProvider, CaptchaProviderType, siteverify};
+
+pub struct TurnstileProvider {
+    pub site_key: String,
+    pub secret_key: String,
+}
+
+#[async_trait::async_trait]
+impl CaptchaProvider for TurnstileProvider {
+    fn provider_type(&self) -> CaptchaProviderType {
+        CaptchaProviderType::Turnstile
+    }
+
+    async fn get_challenge(&self) -> juniper::FieldResult<serde_json::Value> {
+        Ok(json!({
+            "site_key": self.site_key,
+        }))
+    }
+
+    async fn verify_response(
+        &self,
+        _challenge: &str,
+        response: &str,
+    ) -> juniper::FieldResult<bool> {
+        siteverify(
+            "https://challenges.cloudflare.com/turnstile/v0/siteverify",
+            &[("secret", self.secret_key.as_str()), ("response", response)],
+        )
+        .await
+    }
+}
@@ -0,0 +1,109 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::time::Duration;
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use juniper::{FieldResult, graphql_object, graphql_value};
+
+use crate::{
+    db::models::{ArchiveMode, UserRole},
+    graphql::{Actor, BaseContext, Context},
+};
+
+#[graphql_object]
+impl ArchiveMode {
+    /// Whether an admin put the platform into archive mode
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Whether the platform is currently a read-only archive, because an admin enabled it or
+    /// because the event ended and the event is configured to be archived afterwards
+    pub async fn active(&self, ctx: &Context) -> FieldResult<bool> {
+        is_archived(&ctx.base).await
+    }
+
+    pub fn updated_at(&self) -> String {
+        self.updated_at.to_rfc3339()
+    }
+}
+
+// Checked for every submission, but replicas only need to pick up changes within a few seconds
+#[cached::proc_macro::cached(time = 5, key = "()", convert = "{ }", result = true)]
+async fn fetch_archive_mode(base: &BaseContext) -> FieldResult<ArchiveMode> {
+    use crate::db::schema::archive_mode;
+    let state = archive_mode::table
+        .select(ArchiveMode::as_select())
+        .first::<ArchiveMode>(&mut base.db_pool.get().await?)
+        .await?;
+    Ok(state)
+}
+
+/// Whether the platform is a read-only archive right now.
+pub async fn is_archived(base: &BaseContext) -> FieldResult<bool> {
+    if fetch_archive_mode(base).await?.enabled {
+        return Ok(true);
+    }
+    // Without an event config (e.g. before the first sync), only admins can archive
+    let Ok(event_config) = crate::graphql::handlers::event::fetch_event_config(base).await else {
+        return Ok(false);
+    };
+    Ok(event_config.archive_after_end
+        && chrono::Utc::now().timestamp() > event_config.end_time as i64)
+}
+
+/// Returns an error if the platform is archived. Admins are exempt.
+pub async fn require_not_archived(ctx: &Context) -> FieldResult<()> {
+    if ctx.role().is_some_and(|r| r >= UserRole::Admin) {
+        return Ok(());
+    }
+    if is_archived(&ctx.base).await? {
+        return Err(juniper::FieldError::new(
+            "The event is over, this platform is now a read-only archive",
+            graphql_value!({ "code": "ARCHIVED" }),
+        ));
+    }
+    Ok(())
+}
+
+/// The actor challenges are listed for when someone browses the archive without signing in.
+///
+/// It has no solves, and only challenge metadata is rendered for it.
+pub fn visitor_actor() -> Actor {
+    Actor::User {
+        id: uuid::Uuid::nil(),
+        username: "visitor".to_string(),
+    }
+}
+
+pub async fn get_archive_mode(ctx: &Context) -> FieldResult<ArchiveMode> {
+    // Public, so clients can show the archive instead of the event
+    fetch_archive_mode(&ctx.base).await
+}
+
+pub async fn set_archive_mode(ctx: &Context, new_enabled: bool) -> FieldResult<ArchiveMode> {
+    ctx.require_role_min(UserRole::Admin)?;
+    let admin = ctx.require_authentication()?;
+
+    use crate::db::schema::archive_mode;
+    let state = diesel::update(archive_mode::table)
+        .set((
+            archive_mode::enabled.eq(new_enabled),
+            archive_mode::updated_at.eq(chrono::Utc::now()),
+        ))
+        .returning(ArchiveMode::as_returning())
+        .get_result(&mut ctx.get_db_conn().await)
+        .await?;
+
+    use cached::Cached;
+    FETCH_ARCHIVE_MODE.lock().await.cache_clear();
+    tracing::info!(
+        "Archive mode {} by {}",
+        if new_enabled { "enabled" } else { "disabled" },
+        admin.username
+    );
+    Ok(state)
+}
@@ -0,0 +1,106 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use juniper::{FieldResult, graphql_object};
+
+use crate::{
+    db::models::{AuditAction, AuditLogEntry, NewAuditLogEntry, User, UserRole},
+    graphql::{AuthenticatedUser, Context},
+};
+
+#[graphql_object]
+impl AuditLogEntry {
+    pub fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    /// The admin who took the action, null once their account was deleted
+    pub async fn admin(&self, ctx: &Context) -> FieldResult<Option<User>> {
+        use crate::db::schema::users;
+        let Some(admin_id) = self.admin_id else {
+            return Ok(None);
+        };
+        let admin = users::table
+            .filter(users::id.eq(admin_id))
+            .select(User::as_select())
+            .first::<User>(&mut ctx.get_db_conn().await)
+            .await
+            .optional()?;
+        Ok(admin)
+    }
+
+    pub fn action(&self) -> AuditAction {
+        self.action
+    }
+
+    /// ID of the team or user the action was taken against
+    pub fn target_id(&self) -> Option<String> {
+        self.target_id.map(|id| id.to_string())
+    }
+
+    pub fn details(&self) -> &str {
+        &self.details
+    }
+
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    pub fn created_at(&self) -> String {
+        self.created_at.to_rfc3339()
+    }
+}
+
+/// Records an admin action in the audit log.
+///
+/// Meant to be called in the same transaction as the action, so it can't happen unrecorded.
+pub(crate) async fn record(
+    conn: &mut AsyncPgConnection,
+    admin: &AuthenticatedUser,
+    action: AuditAction,
+    target_id: Option<uuid::Uuid>,
+    details: String,
+    reason: Option<String>,
+) -> QueryResult<()> {
+    use crate::db::schema::audit_log;
+
+    diesel::insert_into(audit_log::table)
+        .values(NewAuditLogEntry {
+            admin_id: Some(admin.user_id),
+            action,
+            target_id,
+            details,
+            reason,
+        })
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// The audit log, newest entries first (admin only).
+pub async fn get_audit_log(
+    ctx: &Context,
+    target_id: Option<uuid::Uuid>,
+    offset: i32,
+    limit: i32,
+) -> FieldResult<Vec<AuditLogEntry>> {
+    use crate::db::schema::audit_log;
+
+    ctx.require_role_min(UserRole::Admin)?;
+    let mut query = audit_log::table
+        .order(audit_log::created_at.desc())
+        .select(AuditLogEntry::as_select())
+        .offset(offset.max(0) as i64)
+        .limit(limit.clamp(1, 500) as i64)
+        .into_boxed();
+    if let Some(target_id) = target_id {
+        query = query.filter(audit_log::target_id.eq(target_id));
+    }
+    let entries = query
+        .load::<AuditLogEntry>(&mut ctx.get_db_conn().await)
+        .await?;
+    Ok(entries)
+}
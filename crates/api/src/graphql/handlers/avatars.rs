@@ -0,0 +1,142 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::io::Cursor;
+
+use base64::Engine;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use image::{ImageFormat, imageops::FilterType};
+
+use crate::{
+    db::models::{Team, User},
+    graphql::Context,
+};
+
+/// Maximum size of an uploaded image before processing
+const MAX_UPLOAD_SIZE: usize = 5 * 1024 * 1024;
+/// Avatars are cropped and resized to a square of this size
+const AVATAR_SIZE: u32 = 256;
+
+/// Validates an uploaded image and converts it to a square PNG.
+///
+/// Re-encoding also strips any metadata (like EXIF location data) from the upload.
+fn process_avatar(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() > MAX_UPLOAD_SIZE {
+        return Err(format!(
+            "Avatar must be smaller than {} MiB",
+            MAX_UPLOAD_SIZE / 1024 / 1024
+        ));
+    }
+    let format = image::guess_format(data).map_err(|_| "Unrecognized image format")?;
+    if !matches!(
+        format,
+        ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP | ImageFormat::Gif
+    ) {
+        return Err("Avatar must be a PNG, JPEG, WebP or GIF image".to_string());
+    }
+    let mut reader = image::ImageReader::with_format(Cursor::new(data), format);
+    let mut limits = image::Limits::default();
+    limits.max_image_width = Some(8192);
+    limits.max_image_height = Some(8192);
+    reader.limits(limits);
+    let img = reader
+        .decode()
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+    let img = img.resize_to_fill(AVATAR_SIZE, AVATAR_SIZE, FilterType::Lanczos3);
+    let mut out = Cursor::new(vec![]);
+    img.write_to(&mut out, ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode avatar: {}", e))?;
+    Ok(out.into_inner())
+}
+
+/// Decodes, processes and stores an avatar, returning its storage path.
+async fn store_avatar(
+    ctx: &Context,
+    kind: &str,
+    owner_id: uuid::Uuid,
+    image_base64: &str,
+) -> juniper::FieldResult<String> {
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(image_base64.trim())
+        .map_err(|_| juniper::FieldError::new("Invalid base64 data", juniper::Value::null()))?;
+    let png = tokio::task::spawn_blocking(move || process_avatar(&data))
+        .await?
+        .map_err(|e| juniper::FieldError::new(e, juniper::Value::null()))?;
+
+    // A new path for every upload, so browsers and CDNs don't keep showing the old avatar
+    let path = format!(
+        "avatars/{}/{}-{}.png",
+        kind,
+        owner_id,
+        uuid::Uuid::now_v7().simple()
+    );
+    ctx.base.storage.put_file(&path, png).await?;
+    Ok(path)
+}
+
+async fn delete_old_avatar(ctx: &Context, old_path: Option<String>) {
+    if let Some(old_path) = old_path
+        && let Err(e) = ctx.base.storage.delete_file(&old_path).await
+    {
+        tracing::warn!("Failed to delete old avatar {}: {}", old_path, e);
+    }
+}
+
+pub async fn upload_avatar(ctx: &Context, image_base64: String) -> juniper::FieldResult<User> {
+    let current_user = ctx.require_authentication()?;
+
+    let path = store_avatar(ctx, "users", current_user.user_id, &image_base64).await?;
+
+    use crate::db::schema::users::dsl::*;
+    let conn = &mut ctx.get_db_conn().await;
+    let old_path: Option<String> = users
+        .filter(id.eq(current_user.user_id))
+        .select(avatar_path)
+        .first(conn)
+        .await?;
+    let user = diesel::update(users.filter(id.eq(current_user.user_id)))
+        .set(avatar_path.eq(Some(path)))
+        .get_result::<User>(conn)
+        .await?;
+    delete_old_avatar(ctx, old_path).await;
+
+    Ok(user)
+}
+
+pub async fn upload_team_avatar(ctx: &Context, image_base64: String) -> juniper::FieldResult<Team> {
+    let current_user = ctx.require_authentication()?;
+    let team_id_val = current_user
+        .team_id
+        .ok_or_else(|| juniper::FieldError::new("User is not in a team", juniper::Value::null()))?;
+
+    let path = store_avatar(ctx, "teams", team_id_val, &image_base64).await?;
+
+    use crate::db::schema::teams::dsl::*;
+    let conn = &mut ctx.get_db_conn().await;
+    let old_path: Option<String> = teams
+        .filter(id.eq(team_id_val))
+        .select(avatar_path)
+        .first(conn)
+        .await?;
+    let team = diesel::update(teams.filter(id.eq(team_id_val)))
+        .set(avatar_path.eq(Some(path)))
+        .returning(Team::as_returning())
+        .get_result(conn)
+        .await?;
+    delete_old_avatar(ctx, old_path).await;
+
+    Ok(team)
+}
+
+/// Serves a stored avatar, `path` being e.g. "avatars/users/<id>.png".
+pub async fn retrieve_avatar(ctx: &Context, path: &str) -> Result<Vec<u8>, (u16, String)> {
+    if !path.starts_with("avatars/") || !path.ends_with(".png") || path.contains("..") {
+        return Err((404, "Not found".to_string()));
+    }
+    ctx.base.storage.get_file(path).await.map_err(|e| match e {
+        object_store::Error::NotFound { .. } => (404, "Not found".to_string()),
+        e => (500, format!("Failed to retrieve avatar: {}", e)),
+    })
+}
@@ -2,13 +2,20 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
+pub mod attempts;
+pub mod cheating;
 pub mod export;
 pub mod flags;
 pub mod instances;
 pub mod invalid_submissions;
+pub mod orphaned_instances;
+pub mod rejudge;
+pub mod solvers;
 pub mod solves;
+pub mod submission_ips;
+pub mod top_solvers;
 
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::LazyLock, time::Duration};
 
 use diesel::prelude::*;
 use diesel_async::RunQueryDsl;
@@ -16,12 +23,96 @@ use juniper::graphql_object;
 
 use crate::{
     db::models::UserRole,
-    graphql::{Actor, Context},
+    graphql::{Actor, AuthenticatedUser, Context},
     manager_api::{
         ListChallengesRequest, SolvedChallenge, challenges_service_client::ChallengesServiceClient,
     },
 };
 
+/// How long a challenge list is reused if no solve or sync happens in between
+const CHALLENGE_LIST_TTL: Duration = Duration::from_secs(30);
+
+/// Key of the shared challenge list cache: actor slug, which unreleased challenges are included
+/// and the number of competitors (which the scoring scripts depend on).
+type ChallengeListKey = (String, PreReleaseAccess, i32);
+
+/// Challenge lists returned by the manager, shared between requests.
+///
+/// Listing challenges makes the manager load them from disk and run their scoring scripts,
+/// so the lists are kept for a short time and dropped on every solve and repository sync.
+/// Other api replicas only see the change once their own entries expire.
+static CHALLENGE_LIST_CACHE: LazyLock<
+    moka::future::Cache<ChallengeListKey, Vec<CtfChallengeMetadata>>,
+> = LazyLock::new(|| {
+    moka::future::Cache::builder()
+        .max_capacity(10_000)
+        .time_to_live(CHALLENGE_LIST_TTL)
+        .build()
+});
+
+/// Drops all cached challenge lists, e.g. because points or the challenges themselves changed.
+pub fn invalidate_challenge_lists() {
+    CHALLENGE_LIST_CACHE.invalidate_all();
+}
+
+/// Whether the username is among the authors listed in a challenge's metadata, ignoring case.
+pub fn is_listed_author(authors: &[String], username: &str) -> bool {
+    authors.iter().any(|a| a.eq_ignore_ascii_case(username))
+}
+
+/// Which challenges a user can see and start instances of before they are released
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PreReleaseAccess {
+    /// Only released challenges
+    Released,
+    /// Also the challenges that are open for playtesting
+    Playtest,
+    /// Also the challenges that list the username among their authors
+    Authored(String),
+    All,
+}
+
+impl PreReleaseAccess {
+    /// Admins can access all challenges, authors only their own ones and testers those open for
+    /// playtesting.
+    pub fn for_user(user: Option<&AuthenticatedUser>) -> Self {
+        match user {
+            Some(user) if user.role >= UserRole::Admin => PreReleaseAccess::All,
+            Some(user) if user.role >= UserRole::Author => {
+                PreReleaseAccess::Authored(user.username.clone())
+            }
+            Some(user) if user.role >= UserRole::Tester => PreReleaseAccess::Playtest,
+            _ => PreReleaseAccess::Released,
+        }
+    }
+
+    fn allows(&self, authors: &[String], playtest: bool) -> bool {
+        match self {
+            PreReleaseAccess::Released => false,
+            PreReleaseAccess::Playtest => playtest,
+            PreReleaseAccess::Authored(username) => is_listed_author(authors, username),
+            PreReleaseAccess::All => true,
+        }
+    }
+}
+
+/// Whether the current user may use the challenge before its release, which admins, the
+/// challenge's authors and, while it's open for playtesting, testers can.
+pub async fn has_pre_release_access(
+    context: &Context,
+    challenge_id: &str,
+) -> juniper::FieldResult<bool> {
+    let access = PreReleaseAccess::for_user(context.user.as_ref());
+    if let PreReleaseAccess::Released | PreReleaseAccess::All = access {
+        return Ok(access == PreReleaseAccess::All);
+    }
+    // The challenge lists of authors and testers include the unreleased challenges they can access
+    Ok(get_challenges(context)
+        .await?
+        .iter()
+        .any(|c| c.id == challenge_id && access.allows(&c.authors, c.playtest)))
+}
+
 #[derive(QueryableByName)]
 struct SolveRankResult {
     #[diesel(sql_type = diesel::sql_types::Text)]
@@ -42,74 +133,104 @@ pub struct CtfChallengeMetadata {
     pub authors: Vec<String>,
     /// Description of the challenge in Markdown format
     pub description_md: String,
+    /// Translated descriptions by locale, if the challenge is translated
+    pub description_translations: HashMap<String, String>,
     pub categories: Vec<String>,
     pub difficulty: String,
     // Path to attached files
     pub attachments: Vec<String>,
+    pub files: Vec<ChallengeFile>,
+    /// Sanitized source bundle, if the challenge can be exported and it was packed in advance
+    pub source: Option<ChallengeFile>,
     pub release_time: Option<i32>,
     pub end_time: Option<i32>,
+    /// Wrong submissions allowed per team, unlimited if None
+    pub max_attempts: Option<i32>,
+    /// Whether testers can solve the challenge before its release
+    pub playtest: bool,
     pub points: i32,
     /// Whether the user can start an instance of this challenge
     pub can_start: bool,
     pub can_export: bool,
+    /// Whether everyone connects to the same deployment instead of starting an instance
+    pub shared: bool,
+    pub shared_connection_info: Vec<instances::CtfChallengeConnectionInfo>,
+}
+
+/// A file attached to a challenge, as rendered for the current actor
+#[derive(Debug, Clone)]
+pub struct ChallengeFile {
+    pub challenge_id: String,
+    pub name: String,
+    pub size: u64,
+    pub sha256: String,
+    /// Where the file packed during the sync is stored, empty if it is rendered for every actor
+    pub url: String,
+}
+
+#[graphql_object]
+impl ChallengeFile {
+    /// Path of the file relative to the challenge, as listed in the attachments
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Size in bytes
+    fn size(&self) -> f64 {
+        // GraphQL has no 64-bit integers, but sizes are exact in a double up to 8 PiB
+        self.size as f64
+    }
+
+    /// Hex-encoded SHA-256 digest, to verify downloads
+    fn sha256(&self) -> &str {
+        &self.sha256
+    }
+
+    fn download_url(&self) -> String {
+        if !self.url.is_empty() {
+            return self.url.clone();
+        }
+        format!("/retrieve-file/{}/{}", self.challenge_id, self.name)
+    }
 }
 
+/// Returns the solve count of every solved challenge, and the actor's rank among its solvers.
+///
+/// Like on the scoreboard, only the first solve of each team counts and hidden users and teams
+/// as well as playtest solves are left out, unless the actor is one of them.
 async fn get_actor_solves(
     actor_details: Actor,
     db_pool: diesel_async::pooled_connection::bb8::Pool<diesel_async::AsyncPgConnection>,
 ) -> juniper::FieldResult<HashMap<String, SolvedChallenge>> {
     let mut conn = db_pool.get().await?;
 
-    let actor_solves = match actor_details {
-        Actor::User { id: uid, .. } => {
-            diesel::sql_query(
-                "WITH first_solves AS (
-                    SELECT challenge_id, user_id, MIN(solved_at) as first_solve_at
-                    FROM solves
-                    GROUP BY challenge_id, user_id
-                ),
-                user_ranks AS (
-                    SELECT 
-                        challenge_id,
-                        user_id,
-                        ROW_NUMBER() OVER (PARTITION BY challenge_id ORDER BY first_solve_at ASC) as solve_rank,
-                        COUNT(*) OVER (PARTITION BY challenge_id) as total_solves
-                    FROM first_solves
-                )
-                SELECT challenge_id, solve_rank, total_solves
-                FROM user_ranks
-                WHERE user_id = $1"
-            )
-            .bind::<diesel::sql_types::Uuid, _>(uid)
-            .load::<SolveRankResult>(&mut conn)
-            .await?
-        }
-        Actor::Team { id: team_id, .. } => {
-            diesel::sql_query(
-                "WITH team_first_solves AS (
-                    SELECT s.challenge_id, u.team_id, MIN(s.solved_at) as first_solve_at
-                    FROM solves s
-                    INNER JOIN users u ON s.user_id = u.id
-                    WHERE u.team_id IS NOT NULL
-                    GROUP BY s.challenge_id, u.team_id
-                ),
-                team_ranks AS (
-                    SELECT 
-                        challenge_id,
-                        team_id,
-                        ROW_NUMBER() OVER (PARTITION BY challenge_id ORDER BY first_solve_at ASC) as solve_rank,
-                        COUNT(*) OVER (PARTITION BY challenge_id) as total_solves
-                    FROM team_first_solves
-                )
-                SELECT challenge_id, solve_rank, total_solves
-                FROM team_ranks
-                WHERE team_id = $1"
-            )
-            .bind::<diesel::sql_types::Uuid, _>(team_id)
-            .load::<SolveRankResult>(&mut conn)
-            .await?
-        }
-    };
+    let actor_solves = diesel::sql_query(
+        "WITH actor_first_solves AS (
+            SELECT s.challenge_id, COALESCE(s.team_id, u.id) AS actor_id, MIN(s.solved_at) AS first_solve_at
+            FROM solves s
+            INNER JOIN users u ON s.user_id = u.id
+            LEFT JOIN teams t ON t.id = s.team_id
+            WHERE (NOT s.playtest AND NOT u.hidden AND NOT COALESCE(t.hidden OR t.disqualified, FALSE)) OR COALESCE(s.team_id, u.id) = $1
+            GROUP BY s.challenge_id, COALESCE(s.team_id, u.id)
+        ),
+        actor_ranks AS (
+            SELECT
+                challenge_id,
+                actor_id,
+                ROW_NUMBER() OVER (PARTITION BY challenge_id ORDER BY first_solve_at ASC) AS solve_rank,
+                COUNT(*) OVER (PARTITION BY challenge_id) AS total_solves
+            FROM actor_first_solves
+        )
+        SELECT
+            challenge_id,
+            MAX(CASE WHEN actor_id = $1 THEN solve_rank ELSE 0 END) AS solve_rank,
+            MAX(total_solves) AS total_solves
+        FROM actor_ranks
+        GROUP BY challenge_id",
+    )
+    .bind::<diesel::sql_types::Uuid, _>(actor_details.id())
+    .load::<SolveRankResult>(&mut conn)
+    .await?;
 
     let mut result: HashMap<String, SolvedChallenge> = HashMap::new();
     for row in actor_solves {
@@ -127,8 +248,8 @@ async fn get_actor_solves(
 
 async fn get_challenges_for_actor_internal(
     db_pool: &diesel_async::pooled_connection::bb8::Pool<diesel_async::AsyncPgConnection>,
-    mut challs_client: ChallengesServiceClient<tonic::transport::Channel>,
-    current_role: Option<UserRole>,
+    mut challs_client: ChallengesServiceClient<crate::graphql::GrpcChannel>,
+    access: PreReleaseAccess,
     actor: Actor,
     total_competitors: i32,
 ) -> juniper::FieldResult<Vec<CtfChallengeMetadata>> {
@@ -139,31 +260,56 @@ async fn get_challenges_for_actor_internal(
             actor: actor_str,
             solved_challenges: solves,
             total_competitors: total_competitors as u64,
-            require_release: current_role.is_none() || current_role.unwrap() < UserRole::Author,
+            require_release: access == PreReleaseAccess::Released,
         })
         .await?
         .into_inner()
         .challenges;
 
-    let can_see_hidden = current_role.is_some_and(|r| r >= UserRole::Author);
     let current_ts = chrono::Utc::now().timestamp() as u32;
 
     let result = challs
         .into_iter()
-        .filter(|c| can_see_hidden || (c.release_timestamp.unwrap_or(0) as u32) <= current_ts)
+        .filter(|c| {
+            (c.release_timestamp.unwrap_or(0) as u32) <= current_ts
+                || access.allows(&c.authors, c.playtest)
+        })
         .map(|c| CtfChallengeMetadata {
-            id: c.id,
+            id: c.id.clone(),
             name: c.name,
             authors: c.authors,
             description_md: c.description,
+            description_translations: c.description_translations,
             categories: c.categories,
             difficulty: c.difficulty,
             attachments: c.attachments,
+            files: c
+                .files
+                .into_iter()
+                .map(|file| ChallengeFile {
+                    challenge_id: c.id.clone(),
+                    name: file.name,
+                    size: file.size,
+                    sha256: file.sha256,
+                    url: file.url,
+                })
+                .collect(),
+            source: c.source.map(|file| ChallengeFile {
+                challenge_id: c.id.clone(),
+                name: file.name,
+                size: file.size,
+                sha256: file.sha256,
+                url: file.url,
+            }),
             release_time: c.release_timestamp.map(|t| t as i32),
             end_time: c.end_timestamp.map(|t| t as i32),
+            max_attempts: c.max_attempts.map(|a| a as i32),
+            playtest: c.playtest,
             points: c.points as i32,
             can_start: c.can_start,
             can_export: c.can_export,
+            shared: c.shared,
+            shared_connection_info: instances::convert_connection_info(c.shared_connection_info),
         })
         .collect();
     Ok(result)
@@ -173,27 +319,142 @@ pub async fn get_challenges_for_actor(
     context: &Context,
     actor: Actor,
 ) -> juniper::FieldResult<Vec<CtfChallengeMetadata>> {
-    let current_role = context.user.as_ref().map(|u| u.role);
+    let access = PreReleaseAccess::for_user(context.user.as_ref());
     let challenges_client = context.challenges_client();
     let total_competitors = context.total_competitors;
+    let key = (actor.slug(), access.clone(), total_competitors);
     context
         .challenges_cache
         .get_with(actor.slug(), async {
-            get_challenges_for_actor_internal(
-                &context.base.db_pool,
-                challenges_client,
-                current_role,
-                actor,
-                total_competitors,
-            )
-            .await
+            CHALLENGE_LIST_CACHE
+                .try_get_with(
+                    key,
+                    get_challenges_for_actor_internal(
+                        &context.base.db_pool,
+                        challenges_client,
+                        access,
+                        actor,
+                        total_competitors,
+                    ),
+                )
+                .await
+                .map_err(|e| (*e).clone())
         })
         .await
 }
 
 pub async fn get_challenges(context: &Context) -> juniper::FieldResult<Vec<CtfChallengeMetadata>> {
+    // Once archived, everyone can browse the challenges without an account
+    if context.user.is_none() && super::archive::is_archived(&context.base).await? {
+        return get_challenges_for_actor(context, super::archive::visitor_actor()).await;
+    }
     let auth = context.require_authentication()?;
-    get_challenges_for_actor(context, auth.actor_details()).await
+    get_challenges_for_actor(context, auth.actor()).await
+}
+
+#[derive(juniper::GraphQLEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChallengeSort {
+    Name,
+    /// Most points first
+    Points,
+    /// Newest first
+    ReleaseTime,
+}
+
+/// Criteria to narrow down a challenge list, all of which must match
+#[derive(Debug, Default)]
+pub struct ChallengeFilter {
+    pub category: Option<String>,
+    pub difficulty: Option<String>,
+    pub solved: Option<bool>,
+    /// Case-insensitive search in the name and description
+    pub search: Option<String>,
+}
+
+impl ChallengeFilter {
+    fn matches(&self, challenge: &CtfChallengeMetadata) -> bool {
+        if let Some(category) = &self.category
+            && !challenge
+                .categories
+                .iter()
+                .any(|c| c.eq_ignore_ascii_case(category))
+        {
+            return false;
+        }
+        if let Some(difficulty) = &self.difficulty
+            && !challenge.difficulty.eq_ignore_ascii_case(difficulty)
+        {
+            return false;
+        }
+        if let Some(search) = &self.search {
+            let search = search.to_lowercase();
+            if !challenge.name.to_lowercase().contains(&search)
+                && !challenge.description_md.to_lowercase().contains(&search)
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl CtfChallengeMetadata {
+    /// Uses the description in the given locale, if the challenge is translated into it.
+    pub fn localized(mut self, locale: Option<&str>) -> Self {
+        self.description_md =
+            crate::i18n::localize(&self.description_md, &self.description_translations, locale)
+                .to_string();
+        self
+    }
+}
+
+/// Lists the challenges of the current user, filtered and sorted.
+///
+/// This only uses the list returned by the manager (and whether challenges are solved), so it
+/// happens before any per-challenge field is resolved. Descriptions are shown and searched in
+/// the given locale.
+pub async fn get_filtered_challenges(
+    context: &Context,
+    filter: ChallengeFilter,
+    sort: Option<ChallengeSort>,
+    locale: Option<String>,
+) -> juniper::FieldResult<Vec<CtfChallengeMetadata>> {
+    let mut challenges = get_challenges(context)
+        .await?
+        .into_iter()
+        .map(|c| c.localized(locale.as_deref()))
+        .filter(|c| filter.matches(c))
+        .collect::<Vec<_>>();
+
+    if let Some(solved) = filter.solved {
+        // Loaded as one batch, which the solved field of the challenges reuses
+        let solved_flags = futures_util::future::try_join_all(
+            challenges
+                .iter()
+                .map(|c| context.loaders.solved_challenges.get(c.id.clone())),
+        )
+        .await?;
+        challenges = challenges
+            .into_iter()
+            .zip(solved_flags)
+            .filter(|(_, is_solved)| *is_solved == solved)
+            .map(|(c, _)| c)
+            .collect();
+    }
+
+    match sort {
+        Some(ChallengeSort::Name) => {
+            challenges.sort_by_key(|c| c.name.to_lowercase());
+        }
+        Some(ChallengeSort::Points) => {
+            challenges.sort_by_key(|c| std::cmp::Reverse(c.points));
+        }
+        Some(ChallengeSort::ReleaseTime) => {
+            challenges.sort_by_key(|c| std::cmp::Reverse(c.release_time));
+        }
+        None => {}
+    }
+    Ok(challenges)
 }
 
 #[graphql_object]
@@ -210,6 +471,16 @@ impl CtfChallengeMetadata {
     fn description_md(&self) -> &str {
         &self.description_md
     }
+    /// Locales the description is translated into, empty if it isn't translated
+    fn locales(&self) -> Vec<&str> {
+        let mut locales = self
+            .description_translations
+            .keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        locales.sort();
+        locales
+    }
     fn categories(&self) -> &Vec<String> {
         &self.categories
     }
@@ -219,12 +490,31 @@ impl CtfChallengeMetadata {
     fn attachments(&self) -> &Vec<String> {
         &self.attachments
     }
+    fn files(&self) -> &Vec<ChallengeFile> {
+        &self.files
+    }
+    /// Sanitized source bundle, null if it can only be downloaded through /export-challenge/
+    fn source(&self) -> Option<&ChallengeFile> {
+        self.source.as_ref()
+    }
     fn release_time(&self) -> Option<i32> {
         self.release_time
     }
     fn end_time(&self) -> Option<i32> {
         self.end_time
     }
+    /// Wrong submissions allowed per team, unlimited if null
+    fn max_attempts(&self) -> Option<i32> {
+        self.max_attempts
+    }
+    /// Whether testers can solve the challenge before its release
+    fn playtest(&self) -> bool {
+        self.playtest
+    }
+    /// Wrong submissions the current user's team can still make, null if unlimited
+    async fn remaining_attempts(&self, context: &Context) -> juniper::FieldResult<Option<i32>> {
+        attempts::get_remaining_attempts(context, self).await
+    }
     fn points(&self) -> i32 {
         self.points
     }
@@ -233,6 +523,16 @@ impl CtfChallengeMetadata {
         self.can_start
     }
 
+    /// Whether the challenge runs once for everyone, players don't start instances of it then
+    fn shared(&self) -> bool {
+        self.shared
+    }
+
+    /// How to connect to a shared challenge, empty for other challenges
+    fn shared_connection_info(&self) -> &Vec<instances::CtfChallengeConnectionInfo> {
+        &self.shared_connection_info
+    }
+
     async fn instance(
         &self,
         context: &Context,
@@ -241,38 +541,12 @@ impl CtfChallengeMetadata {
     }
 
     async fn solved(&self, context: &Context) -> juniper::FieldResult<bool> {
-        let Ok(user) = context.require_authentication() else {
+        if !context.is_authenticated() {
             return Ok(false);
-        };
+        }
 
         // Check if there is a solve record for this user (or their team) and this challenge
-        let conn = &mut context.get_db_conn().await;
-
-        use crate::db::schema::solves::dsl::*;
-
-        let solve_count = if let Some(team_id_val) = user.team_id {
-            solves
-                .filter(challenge_id.eq(&self.id))
-                .filter(
-                    user_id.nullable().eq_any(
-                        crate::db::schema::users::table
-                            .filter(crate::db::schema::users::team_id.eq(team_id_val))
-                            .select(crate::db::schema::users::id.nullable()),
-                    ),
-                )
-                .count()
-                .get_result::<i64>(conn)
-                .await?
-        } else {
-            solves
-                .filter(challenge_id.eq(&self.id))
-                .filter(user_id.eq(user.user_id))
-                .count()
-                .get_result::<i64>(conn)
-                .await?
-        };
-
-        Ok(solve_count > 0)
+        context.loaders.solved_challenges.get(self.id.clone()).await
     }
 
     /// Whether the challenge source code can be exported by the user
@@ -281,16 +555,59 @@ impl CtfChallengeMetadata {
     }
 
     async fn solves(&self, context: &Context) -> juniper::FieldResult<i32> {
-        let conn = &mut context.get_db_conn().await;
+        let solve_count = context
+            .loaders
+            .challenge_solve_counts
+            .get(self.id.clone())
+            .await?;
+        Ok(solve_count as i32)
+    }
+
+    /// Teams or users who solved the challenge, earliest first, as shown on the scoreboard
+    async fn solved_by(
+        &self,
+        context: &Context,
+        #[graphql(default = 0)] offset: i32,
+        #[graphql(default = 50)] limit: i32,
+    ) -> juniper::FieldResult<solvers::ChallengeSolverPage> {
+        solvers::get_challenge_solvers(context, &self.id, offset, limit).await
+    }
 
-        use crate::db::schema::solves::dsl::*;
+    /// The first teams or users to solve the challenge
+    async fn top_solvers(
+        &self,
+        context: &Context,
+        #[graphql(default = 3)] limit: i32,
+    ) -> juniper::FieldResult<Vec<top_solvers::TopSolve>> {
+        top_solvers::get_top_solvers(context, &self.id, limit).await
+    }
+}
 
-        let solve_count = solves
-            .filter(challenge_id.eq(&self.id))
-            .count()
-            .get_result::<i64>(conn)
-            .await?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        Ok(solve_count as i32)
+    #[test]
+    fn test_listed_author_ignores_case() {
+        let authors = vec!["Alice".to_string(), "bob".to_string()];
+        assert!(is_listed_author(&authors, "alice"));
+        assert!(is_listed_author(&authors, "BOB"));
+        assert!(!is_listed_author(&authors, "mallory"));
+    }
+
+    #[test]
+    fn test_authors_only_access_their_challenges() {
+        let authors = vec!["alice".to_string()];
+        assert!(PreReleaseAccess::All.allows(&authors, false));
+        assert!(PreReleaseAccess::Authored("Alice".to_string()).allows(&authors, false));
+        assert!(!PreReleaseAccess::Authored("bob".to_string()).allows(&authors, false));
+        assert!(!PreReleaseAccess::Released.allows(&authors, true));
+    }
+
+    #[test]
+    fn test_testers_only_access_playtests() {
+        let authors = vec!["alice".to_string()];
+        assert!(PreReleaseAccess::Playtest.allows(&authors, true));
+        assert!(!PreReleaseAccess::Playtest.allows(&authors, false));
     }
 }
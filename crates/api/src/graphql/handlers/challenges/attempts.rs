@@ -0,0 +1,140 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use juniper::{FieldResult, graphql_value};
+
+use crate::{
+    db::models::UserRole,
+    graphql::{AuthenticatedUser, Context},
+};
+
+use super::CtfChallengeMetadata;
+
+/// Wrong submissions of the user's team (or the user) for a challenge since the last reset.
+async fn count_wrong_attempts(
+    conn: &mut AsyncPgConnection,
+    user: &AuthenticatedUser,
+    challenge_id: &str,
+) -> QueryResult<i64> {
+    use crate::db::schema::{attempt_resets, invalid_submissions, users};
+
+    let reset_at = attempt_resets::table
+        .filter(attempt_resets::challenge_id.eq(challenge_id))
+        .filter(attempt_resets::actor_id.eq(user.actor().id()))
+        .select(attempt_resets::reset_at)
+        .first::<chrono::DateTime<chrono::Utc>>(conn)
+        .await
+        .optional()?;
+    let mut query = invalid_submissions::table
+        .filter(invalid_submissions::challenge_id.eq(challenge_id))
+        .into_boxed();
+    query = match user.team_id {
+        Some(team_id) => query.filter(
+            invalid_submissions::user_id.eq_any(
+                users::table
+                    .filter(users::team_id.eq(team_id))
+                    .select(users::id),
+            ),
+        ),
+        None => query.filter(invalid_submissions::user_id.eq(user.user_id)),
+    };
+    if let Some(reset_at) = reset_at {
+        query = query.filter(invalid_submissions::submitted_at.gt(reset_at));
+    }
+    query.count().get_result(conn).await
+}
+
+/// Wrong submissions the current user's team can still make, None if they are unlimited.
+///
+/// Authors and admins are never locked out.
+pub async fn get_remaining_attempts(
+    context: &Context,
+    challenge: &CtfChallengeMetadata,
+) -> FieldResult<Option<i32>> {
+    let (Some(user), Some(max_attempts)) = (&context.user, challenge.max_attempts) else {
+        return Ok(None);
+    };
+    if user.role >= UserRole::Author {
+        return Ok(None);
+    }
+    let used = count_wrong_attempts(&mut *context.get_db_conn().await, user, &challenge.id).await?;
+    Ok(Some((max_attempts as i64 - used).max(0) as i32))
+}
+
+/// Rejects the submission if the user's team used up the wrong submissions allowed for the
+/// challenge. Authors and admins are exempt.
+///
+/// Must run in the transaction recording the submission, see `reserve_submission`.
+pub async fn enforce_attempt_quota(
+    conn: &mut AsyncPgConnection,
+    user: &AuthenticatedUser,
+    challenge: &CtfChallengeMetadata,
+) -> FieldResult<()> {
+    if user.role >= UserRole::Author {
+        return Ok(());
+    }
+    let Some(max_attempts) = challenge.max_attempts else {
+        return Ok(());
+    };
+    let used = count_wrong_attempts(conn, user, &challenge.id).await?;
+    if used >= max_attempts as i64 {
+        return Err(juniper::FieldError::new(
+            "You have used up all attempts for this challenge",
+            graphql_value!({ "code": "ATTEMPTS_EXHAUSTED" }),
+        ));
+    }
+    Ok(())
+}
+
+/// Lets a team (or user without a team) submit flags for a challenge again after using up
+/// its attempts (admin only).
+pub async fn reset_attempts(
+    ctx: &Context,
+    challenge_id: String,
+    actor_id: uuid::Uuid,
+) -> FieldResult<bool> {
+    use crate::db::schema::{attempt_resets, teams, users};
+
+    ctx.require_role_min(UserRole::Admin)?;
+    let admin = ctx.require_authentication()?;
+    let conn = &mut ctx.get_db_conn().await;
+    let team_exists = diesel::select(diesel::dsl::exists(
+        teams::table.filter(teams::id.eq(actor_id)),
+    ))
+    .get_result::<bool>(conn)
+    .await?;
+    let user_exists = diesel::select(diesel::dsl::exists(
+        users::table.filter(users::id.eq(actor_id)),
+    ))
+    .get_result::<bool>(conn)
+    .await?;
+    if !team_exists && !user_exists {
+        return Err(juniper::FieldError::new(
+            "Team or user not found",
+            juniper::Value::null(),
+        ));
+    }
+
+    let now = chrono::Utc::now();
+    diesel::insert_into(attempt_resets::table)
+        .values((
+            attempt_resets::challenge_id.eq(&challenge_id),
+            attempt_resets::actor_id.eq(actor_id),
+            attempt_resets::reset_at.eq(now),
+        ))
+        .on_conflict((attempt_resets::challenge_id, attempt_resets::actor_id))
+        .do_update()
+        .set(attempt_resets::reset_at.eq(now))
+        .execute(conn)
+        .await?;
+    tracing::info!(
+        "Attempts of {} for challenge {} reset by {}",
+        actor_id,
+        challenge_id,
+        admin.username
+    );
+    Ok(true)
+}
@@ -0,0 +1,226 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use juniper::{FieldResult, graphql_object};
+use serenity::all::{Builder, ChannelId, CreateMessage, GuildId};
+
+use crate::{
+    config::Config,
+    db::models::{CheatingIncident, NewCheatingIncident, NewIssuedFlag, User, UserRole},
+    graphql::{Actor, AuthenticatedUser, Context},
+    manager_api::GetActorFlagRequest,
+};
+
+type DbPool = diesel_async::pooled_connection::bb8::Pool<diesel_async::AsyncPgConnection>;
+
+#[graphql_object]
+impl CheatingIncident {
+    pub fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    /// The challenge the submitted flag belongs to
+    pub fn challenge_id(&self) -> &str {
+        &self.challenge_id
+    }
+
+    pub async fn user(&self, ctx: &Context) -> FieldResult<User> {
+        use crate::db::schema::users::dsl::*;
+        let user_record = users
+            .filter(id.eq(self.user_id))
+            .first::<User>(&mut ctx.get_db_conn().await)
+            .await?;
+        Ok(user_record)
+    }
+
+    /// Actor slug of the submitter
+    pub fn submitting_actor(&self) -> &str {
+        &self.submitting_actor
+    }
+
+    /// Actor slug of the team or user the flag was issued to
+    pub fn owning_actor(&self) -> &str {
+        &self.owning_actor
+    }
+
+    pub fn submitted_flag(&self) -> &str {
+        &self.submitted_flag
+    }
+
+    pub fn created_at(&self) -> String {
+        self.created_at.to_rfc3339()
+    }
+}
+
+async fn store_issued_flag(
+    db_pool: &DbPool,
+    issued: NewIssuedFlag,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use crate::db::schema::issued_flags::dsl::*;
+
+    let mut conn = db_pool.get().await?;
+    // Flags can change when the repository is synced, so the latest one wins
+    diesel::insert_into(issued_flags)
+        .values(&issued)
+        .on_conflict((challenge_id, actor))
+        .do_update()
+        .set(flag.eq(&issued.flag))
+        .execute(&mut conn)
+        .await?;
+    Ok(())
+}
+
+/// Remembers the flag of a challenge the actor just received, in the background.
+///
+/// Called whenever actor-specific content (instances, exports, files) is handed out,
+/// so flags submitted by someone else can be traced back to their owner.
+pub fn record_issued_flag(ctx: &Context, challenge_id: String, actor: &Actor) {
+    let actor = actor.slug();
+    let db_pool = ctx.base.db_pool.clone();
+    let mut challenges_client = ctx.challenges_client();
+    tokio::spawn(async move {
+        let flag = match challenges_client
+            .get_actor_flag(GetActorFlagRequest {
+                challenge_id: challenge_id.clone(),
+                actor: actor.clone(),
+            })
+            .await
+        {
+            Ok(response) => response.into_inner().flag,
+            Err(e) => {
+                tracing::error!(
+                    "Failed to get flag of {} for {}: {}",
+                    challenge_id,
+                    actor,
+                    e
+                );
+                return;
+            }
+        };
+        // Flags checked by a script can't be attributed to an actor
+        let Some(flag) = flag else {
+            return;
+        };
+        if let Err(e) = store_issued_flag(
+            &db_pool,
+            NewIssuedFlag {
+                challenge_id: challenge_id.clone(),
+                actor: actor.clone(),
+                flag,
+            },
+        )
+        .await
+        {
+            tracing::error!(
+                "Failed to record flag of {} for {}: {}",
+                challenge_id,
+                actor,
+                e
+            );
+        }
+    });
+}
+
+/// Remembers a correctly submitted flag as belonging to the submitter.
+pub async fn record_solved_flag(
+    ctx: &Context,
+    challenge_id: String,
+    actor: &Actor,
+    flag: String,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    store_issued_flag(
+        &ctx.base.db_pool,
+        NewIssuedFlag {
+            challenge_id,
+            actor: actor.slug(),
+            flag,
+        },
+    )
+    .await
+}
+
+/// Checks whether an incorrect flag was issued to another actor and records an incident if so.
+///
+/// The submission is still rejected, but admins are alerted.
+pub async fn detect_flag_sharing(
+    ctx: &Context,
+    user: &AuthenticatedUser,
+    submitted_flag: &str,
+) -> FieldResult<Option<CheatingIncident>> {
+    use crate::db::schema::{cheating_incidents, issued_flags};
+
+    let submitting_actor = user.actor().slug();
+    let incident = {
+        let conn = &mut ctx.get_db_conn().await;
+        let owner = issued_flags::table
+            .filter(issued_flags::flag.eq(submitted_flag))
+            .filter(issued_flags::actor.ne(&submitting_actor))
+            .select((issued_flags::challenge_id, issued_flags::actor))
+            .first::<(String, String)>(conn)
+            .await
+            .optional()?;
+        let Some((challenge_id, owning_actor)) = owner else {
+            return Ok(None);
+        };
+
+        diesel::insert_into(cheating_incidents::table)
+            .values(NewCheatingIncident {
+                challenge_id,
+                user_id: user.user_id,
+                submitting_actor,
+                owning_actor,
+                submitted_flag: submitted_flag.to_string(),
+            })
+            .returning(CheatingIncident::as_returning())
+            .get_result(conn)
+            .await?
+    };
+    tracing::warn!(
+        "Possible flag sharing: {} submitted the flag of {} for challenge {}",
+        incident.submitting_actor,
+        incident.owning_actor,
+        incident.challenge_id
+    );
+    alert_admins(&ctx.base.config, &incident, &user.username).await?;
+    Ok(Some(incident))
+}
+
+async fn alert_admins(
+    config: &Config,
+    incident: &CheatingIncident,
+    username: &str,
+) -> FieldResult<()> {
+    if let Some(discord_cheating_channel) = config.discord_cheating_channel_id
+        && let Some(discord_cheating_guild) = config.discord_cheating_guild_id
+        && let Some(discord_bot) = crate::discord::get_client(config).await
+    {
+        Builder::execute(
+            CreateMessage::new().content(format!(
+                ":rotating_light: User **{}** ({}) submitted the flag of **{}** for challenge **{}**.",
+                username, incident.submitting_actor, incident.owning_actor, incident.challenge_id
+            )),
+            &discord_bot.http,
+            (
+                ChannelId::new(discord_cheating_channel),
+                Some(GuildId::new(discord_cheating_guild)),
+            ),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+pub async fn get_cheating_incidents(ctx: &Context) -> FieldResult<Vec<CheatingIncident>> {
+    ctx.require_role_min(UserRole::Admin)?;
+
+    use crate::db::schema::cheating_incidents::dsl::*;
+    let incidents = cheating_incidents
+        .order(created_at.desc())
+        .select(CheatingIncident::as_select())
+        .load::<CheatingIncident>(&mut ctx.get_db_conn().await)
+        .await?;
+    Ok(incidents)
+}
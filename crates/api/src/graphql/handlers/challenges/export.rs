@@ -10,19 +10,23 @@ pub async fn export_challenge(
         .require_authentication()
         .map_err(|e| (401, format!("Authentication required: {:?}", e)))?;
     let actor = auth.actor();
+    let require_release = !super::has_pre_release_access(&ctx, &challenge_id)
+        .await
+        .map_err(|e| (500, format!("Failed to load challenge: {}", e.message())))?;
 
     let mut challenges_client = ctx.challenges_client();
 
     let resp = challenges_client
         .export_challenge(crate::manager_api::ExportChallengeRequest {
-            actor,
-            challenge_id,
-            require_release: auth.role < crate::graphql::UserRole::Author,
+            actor: actor.slug(),
+            challenge_id: challenge_id.clone(),
+            require_release,
         })
         .await;
 
     match resp {
         Ok(response) => {
+            super::cheating::record_issued_flag(&ctx, challenge_id, &actor);
             let response = response.into_inner();
             Ok(response.challenge_archive)
         }
@@ -50,20 +54,24 @@ pub async fn retrieve_file(
         .require_authentication()
         .map_err(|e| (401, format!("Authentication required: {:?}", e)))?;
     let actor = auth.actor();
+    let require_release = !super::has_pre_release_access(&ctx, &challenge_id)
+        .await
+        .map_err(|e| (500, format!("Failed to load challenge: {}", e.message())))?;
 
     let mut challenges_client = ctx.challenges_client();
 
     let resp = challenges_client
         .retrieve_file(crate::manager_api::RetrieveFileRequest {
-            actor,
-            challenge_id,
+            actor: actor.slug(),
+            challenge_id: challenge_id.clone(),
             filename,
-            require_release: auth.role < crate::graphql::UserRole::Author,
+            require_release,
         })
         .await;
 
     match resp {
         Ok(response) => {
+            super::cheating::record_issued_flag(&ctx, challenge_id, &actor);
             let response = response.into_inner();
             Ok(response.file_content)
         }
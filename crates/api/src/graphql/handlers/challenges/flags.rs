@@ -1,15 +1,130 @@
 use crate::{
     db::{
-        models::{NewSolve, Solve},
+        models::{NewInvalidSubmission, NewSolve, Solve, UserRole, WebhookEvent},
         schema::solves,
     },
-    graphql::Context,
+    graphql::{AuthenticatedUser, Context},
     manager_api::CheckFlagRequest,
 };
 use diesel::prelude::*;
-use diesel_async::RunQueryDsl;
+use diesel_async::{
+    AsyncConnection, AsyncPgConnection, RunQueryDsl, scoped_futures::ScopedFutureExt,
+};
+use juniper::graphql_value;
 use serenity::all::{Builder, ChannelId, CreateMessage, GuildId};
 
+/// Rejects the submission if the actor submitted a flag for this challenge less than `cooldown`
+/// seconds ago.
+async fn enforce_submission_cooldown(
+    conn: &mut AsyncPgConnection,
+    user: &AuthenticatedUser,
+    challenge_id: &str,
+    cooldown: i32,
+) -> juniper::FieldResult<()> {
+    use crate::db::schema::{invalid_submissions, users};
+
+    let member_ids = match user.team_id {
+        Some(team_id) => {
+            users::table
+                .filter(users::team_id.eq(team_id))
+                .select(users::id)
+                .load::<uuid::Uuid>(conn)
+                .await?
+        }
+        None => vec![user.user_id],
+    };
+    let last_solve = solves::table
+        .filter(solves::challenge_id.eq(challenge_id))
+        .filter(solves::user_id.eq_any(&member_ids))
+        .select(diesel::dsl::max(solves::solved_at))
+        .get_result::<Option<chrono::DateTime<chrono::Utc>>>(conn)
+        .await?;
+    let last_invalid = invalid_submissions::table
+        .filter(invalid_submissions::challenge_id.eq(challenge_id))
+        .filter(invalid_submissions::user_id.eq_any(&member_ids))
+        .select(diesel::dsl::max(invalid_submissions::submitted_at))
+        .get_result::<Option<chrono::DateTime<chrono::Utc>>>(conn)
+        .await?;
+
+    let Some(last_submission) = last_solve.max(last_invalid) else {
+        return Ok(());
+    };
+    let elapsed = (chrono::Utc::now() - last_submission).num_seconds();
+    let remaining = cooldown as i64 - elapsed;
+    if remaining > 0 {
+        let remaining = remaining as i32;
+        return Err(juniper::FieldError::new(
+            format!(
+                "Please wait {} seconds before submitting another flag for this challenge",
+                remaining
+            ),
+            graphql_value!({ "code": "SUBMISSION_COOLDOWN", "retryAfter": remaining }),
+        ));
+    }
+    Ok(())
+}
+
+/// Records a submission as invalid before its flag is checked, if the actor may submit a flag for
+/// the challenge now. Submissions of the same actor for a challenge wait for each other here, so
+/// concurrent ones can't all pass the cooldown and attempt checks before any of them is recorded.
+/// Authors and admins are exempt from both.
+///
+/// Returns the id of the record, which is removed again if the flag turns out to be correct.
+async fn reserve_submission(
+    context: &Context,
+    user: &AuthenticatedUser,
+    challenge: &super::CtfChallengeMetadata,
+    submission: &NewInvalidSubmission,
+) -> juniper::FieldResult<uuid::Uuid> {
+    use crate::db::schema::invalid_submissions;
+
+    let cooldown = if user.role >= UserRole::Author {
+        None
+    } else {
+        crate::graphql::handlers::event::fetch_event_config(&context.base)
+            .await?
+            .submission_cooldown
+            .filter(|c| *c > 0)
+    };
+    let lock_key = format!("submission:{}:{}", user.actor().id(), challenge.id);
+    let mut conn = context.get_db_conn().await;
+    conn.transaction::<_, juniper::FieldError, _>(|conn| {
+        async move {
+            diesel::sql_query("SELECT pg_advisory_xact_lock(hashtext($1))")
+                .bind::<diesel::sql_types::Text, _>(&lock_key)
+                .execute(conn)
+                .await?;
+            if let Some(cooldown) = cooldown {
+                enforce_submission_cooldown(conn, user, &challenge.id, cooldown).await?;
+            }
+            super::attempts::enforce_attempt_quota(conn, user, challenge).await?;
+            Ok(diesel::insert_into(invalid_submissions::table)
+                .values(submission)
+                .returning(invalid_submissions::id)
+                .get_result(conn)
+                .await?)
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+/// Records a solve, None if the user or their team already solved the challenge. Playtest solves
+/// are kept apart from the ones after the release, which still count.
+async fn record_solve(
+    conn: &mut AsyncPgConnection,
+    solve: &NewSolve,
+) -> QueryResult<Option<Solve>> {
+    diesel::insert_into(solves::table)
+        .values(solve)
+        .on_conflict_do_nothing()
+        .returning(Solve::as_returning())
+        .get_result(conn)
+        .await
+        .optional()
+}
+
+#[tracing::instrument(skip(context, flag))]
 pub async fn submit_flag(
     context: &Context,
     challenge_id: String,
@@ -27,58 +142,163 @@ pub async fn submit_flag(
     }
     let ts_now = chrono::Utc::now();
     let user = context.require_authentication()?;
+    crate::graphql::handlers::event::require_event_running(context).await?;
+    crate::graphql::handlers::archive::require_not_archived(context).await?;
+    crate::graphql::handlers::scoreboard::penalties::require_not_disqualified(context, &user)
+        .await?;
 
-    // TODO: This allows submitting flags for unreleased challenges. We should probably fix that.
+    // Unreleased challenges are only listed for the users who may solve them already
+    let challenges = super::get_challenges(context).await?;
+    let Some(challenge) = challenges.iter().find(|c| c.id == challenge_id) else {
+        return Err(juniper::FieldError::new(
+            "Challenge not found",
+            juniper::Value::null(),
+        ));
+    };
+    let new_invalid_submission = NewInvalidSubmission {
+        user_id: user.user_id,
+        challenge_id: challenge_id.clone(),
+        submitted_flag: flag.clone(),
+        submitted_at: ts_now,
+        ip_address: Some(context.get_ip_net()),
+        user_agent: Some(context.get_user_agent().to_string()),
+        team_id: user.team_id,
+    };
+    let reservation_id =
+        reserve_submission(context, &user, challenge, &new_invalid_submission).await?;
 
     let mut challenges_client = context.challenges_client();
-
-    let mut solved_challenge = challenges_client
-        .check_flag(CheckFlagRequest {
-            actor: user.actor(),
-            challenge_id: Some(challenge_id.clone()),
-            flag: flag.to_string(),
-        })
-        .await
-        .map(|r| r.into_inner().solved_challenge_id)
-        .unwrap_or_else(|e| {
-            tracing::error!("Failed to check flag: {}", e);
-            None
-        });
-
-    if solved_challenge.is_none() {
-        solved_challenge = challenges_client
+    let checked = async {
+        let solved_challenge = challenges_client
             .check_flag(CheckFlagRequest {
-                actor: user.actor(),
-                challenge_id: None,
+                actor: user.actor().slug(),
+                challenge_id: Some(challenge_id.clone()),
                 flag: flag.to_string(),
             })
-            .await
-            .map(|r| r.into_inner().solved_challenge_id)
-            .unwrap_or_else(|e| {
-                tracing::error!("Failed to check flag: {}", e);
-                None
-            });
+            .await?
+            .into_inner()
+            .solved_challenge_id;
+        if solved_challenge.is_some() {
+            return Ok(solved_challenge);
+        }
+        Ok::<_, tonic::Status>(
+            challenges_client
+                .check_flag(CheckFlagRequest {
+                    actor: user.actor().slug(),
+                    challenge_id: None,
+                    flag: flag.to_string(),
+                })
+                .await?
+                .into_inner()
+                .solved_challenge_id
+                .filter(|id| challenges.iter().any(|c| &c.id == id)),
+        )
     }
+    .await;
+    let solved_challenge = match checked {
+        Ok(solved_challenge) => solved_challenge,
+        Err(e) => {
+            tracing::error!("Failed to check flag: {}", e);
+            // The flag wasn't checked, so the submission doesn't use up an attempt
+            use crate::db::schema::invalid_submissions;
+            diesel::delete(
+                invalid_submissions::table.filter(invalid_submissions::id.eq(reservation_id)),
+            )
+            .execute(&mut context.get_db_conn().await)
+            .await?;
+            return Err(juniper::FieldError::new(
+                "Failed to check flag, please try again",
+                juniper::Value::null(),
+            ));
+        }
+    };
+
+    crate::metrics::FLAG_SUBMISSIONS
+        .with_label_values(&[if solved_challenge.is_some() {
+            "correct"
+        } else {
+            "incorrect"
+        }])
+        .inc();
 
     if let Some(challenge_id) = &solved_challenge {
-        let new_submission = NewSolve {
-            user_id: user.user_id,
-            challenge_id: challenge_id.clone(),
-            submitted_flag: flag,
-            solved_at: ts_now,
+        // Solves before the release come from testers, authors or admins trying the challenge
+        let playtest = challenges
+            .iter()
+            .find(|c| &c.id == challenge_id)
+            .and_then(|c| c.release_time)
+            .is_some_and(|release_time| i64::from(release_time) > ts_now.timestamp());
+        let is_first_blood = {
+            use crate::db::schema::{invalid_submissions, users};
+
+            let conn = &mut context.get_db_conn().await;
+            // The team in the token may be outdated
+            let team_id = users::table
+                .filter(users::id.eq(user.user_id))
+                .select(users::team_id)
+                .first::<Option<uuid::Uuid>>(conn)
+                .await?;
+            let new_submission = NewSolve {
+                user_id: user.user_id,
+                team_id,
+                challenge_id: challenge_id.clone(),
+                submitted_flag: flag.clone(),
+                solved_at: ts_now,
+                ip_address: Some(context.get_ip_net()),
+                user_agent: Some(context.get_user_agent().to_string()),
+                playtest,
+            };
+            let is_first_blood = !playtest
+                && !diesel::select(diesel::dsl::exists(
+                    solves::table
+                        .filter(solves::challenge_id.eq(challenge_id))
+                        .filter(solves::playtest.eq(false)),
+                ))
+                .get_result::<bool>(conn)
+                .await?;
+            diesel::delete(
+                invalid_submissions::table.filter(invalid_submissions::id.eq(reservation_id)),
+            )
+            .execute(conn)
+            .await?;
+            if record_solve(conn, &new_submission).await?.is_none() {
+                return Err(juniper::FieldError::new(
+                    match team_id {
+                        Some(_) => "Your team has already solved this challenge",
+                        None => "You have already solved this challenge",
+                    },
+                    graphql_value!({ "code": "ALREADY_SOLVED" }),
+                ));
+            }
+            is_first_blood
         };
-        diesel::insert_into(solves::table)
-            .values(&new_submission)
-            .returning(Solve::as_returning())
-            .execute(&mut context.get_db_conn().await)
+        super::cheating::record_solved_flag(context, challenge_id.clone(), &user.actor(), flag)
             .await?;
-        if let Some(discord_solves_channel) = std::env::var("DISCORD_SOLVES_CHANNEL_ID")
-            .ok()
-            .and_then(|id| id.parse::<u64>().ok())
-            && let Some(discord_solves_guild) = std::env::var("DISCORD_SOLVES_GUILD_ID")
-                .ok()
-                .and_then(|id| id.parse::<u64>().ok())
-            && let Some(discord_bot) = crate::discord::get_client().await
+
+        let webhook_data = serde_json::json!({
+            "challenge_id": challenge_id,
+            "user_id": user.user_id,
+            "username": user.username,
+            "team_id": user.team_id,
+            "team_slug": user.team_slug,
+            "solved_at": ts_now.to_rfc3339(),
+            "playtest": playtest,
+        });
+        if is_first_blood {
+            crate::webhooks::dispatch(
+                &context.base.db_pool,
+                WebhookEvent::FirstBlood,
+                webhook_data.clone(),
+            );
+        }
+        crate::webhooks::dispatch(&context.base.db_pool, WebhookEvent::Solve, webhook_data);
+        crate::graphql::handlers::scoreboard::points::request_recompute(Some(challenge_id.clone()));
+        super::invalidate_challenge_lists();
+        // Announcing playtests would reveal unreleased challenges
+        if !playtest
+            && let Some(discord_solves_channel) = context.base.config.discord_solves_channel_id
+            && let Some(discord_solves_guild) = context.base.config.discord_solves_guild_id
+            && let Some(discord_bot) = crate::discord::get_client(&context.base.config).await
         {
             if let Some(ref team) = user.team_slug {
                 Builder::execute(
@@ -109,26 +329,18 @@ pub async fn submit_flag(
             }
         }
     } else {
-        let new_invalid_submission = crate::db::models::NewInvalidSubmission {
-            // This can be unwrap()ed safely because of the authentication check at the start of the function
-            user_id: user.user_id,
-            challenge_id,
-            submitted_flag: flag,
-            submitted_at: ts_now,
-        };
-        diesel::insert_into(crate::db::schema::invalid_submissions::table)
-            .values(&new_invalid_submission)
-            .execute(&mut context.get_db_conn().await)
-            .await?;
-        if let Some(discord_client) = crate::discord::get_client().await {
+        super::cheating::detect_flag_sharing(
+            context,
+            &user,
+            &new_invalid_submission.submitted_flag,
+        )
+        .await?;
+        let config = &context.base.config;
+        if let Some(discord_client) = crate::discord::get_client(config).await {
             if let Some(discord_invalid_submissions_channel) =
-                std::env::var("DISCORD_PUBLIC_INVALID_SUBMISSIONS_CHANNEL_ID")
-                    .ok()
-                    .and_then(|id| id.parse::<u64>().ok())
+                config.discord_public_invalid_submissions_channel_id
                 && let Some(discord_invalid_submissions_guild) =
-                    std::env::var("DISCORD_PUBLIC_INVALID_SUBMISSIONS_GUILD_ID")
-                        .ok()
-                        .and_then(|id| id.parse::<u64>().ok())
+                    config.discord_public_invalid_submissions_guild_id
             {
                 Builder::execute(
                     CreateMessage::new().content(format!(
@@ -144,13 +356,9 @@ pub async fn submit_flag(
                 .await?;
             }
             if let Some(discord_private_invalid_submissions_channel) =
-                std::env::var("DISCORD_PRIVATE_INVALID_SUBMISSIONS_CHANNEL_ID")
-                    .ok()
-                    .and_then(|id| id.parse::<u64>().ok())
+                config.discord_private_invalid_submissions_channel_id
                 && let Some(discord_private_invalid_submissions_guild) =
-                    std::env::var("DISCORD_PRIVATE_INVALID_SUBMISSIONS_GUILD_ID")
-                        .ok()
-                        .and_then(|id| id.parse::<u64>().ok())
+                    config.discord_private_invalid_submissions_guild_id
             {
                 Builder::execute(
                     CreateMessage::new().content(format!(
@@ -166,3 +374,90 @@ pub async fn submit_flag(
     }
     Ok(solved_challenge)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Needs a PostgreSQL database, e.g. TEST_DATABASE_URL=postgres://localhost/plfanzen_test
+    async fn test_connection() -> Option<AsyncPgConnection> {
+        let Ok(database_url) = std::env::var("TEST_DATABASE_URL") else {
+            eprintln!("TEST_DATABASE_URL is not set, skipping");
+            return None;
+        };
+        let mut migration_conn = diesel::pg::PgConnection::establish(&database_url).unwrap();
+        crate::db::run_migrations(&mut migration_conn).unwrap();
+        let mut conn = AsyncPgConnection::establish(&database_url).await.unwrap();
+        conn.begin_test_transaction().await.unwrap();
+        Some(conn)
+    }
+
+    #[tokio::test]
+    async fn test_solve_after_playtest() {
+        use crate::db::schema::{teams, users};
+
+        let Some(mut conn) = test_connection().await else {
+            return;
+        };
+        let team_id = diesel::insert_into(teams::table)
+            .values((teams::name.eq("Testers"), teams::slug.eq("testers")))
+            .returning(teams::id)
+            .get_result::<uuid::Uuid>(&mut conn)
+            .await
+            .unwrap();
+        let mut user_ids = vec![];
+        for name in ["tester", "teammate"] {
+            user_ids.push(
+                diesel::insert_into(users::table)
+                    .values((
+                        users::username.eq(name),
+                        users::display_name.eq(name),
+                        users::password_hash.eq(""),
+                        users::email.eq(format!("{}@example.com", name)),
+                        users::team_id.eq(team_id),
+                    ))
+                    .returning(users::id)
+                    .get_result::<uuid::Uuid>(&mut conn)
+                    .await
+                    .unwrap(),
+            );
+        }
+        let solve = |user_id, playtest| NewSolve {
+            user_id,
+            team_id: Some(team_id),
+            challenge_id: "web".to_string(),
+            submitted_flag: "flag{test}".to_string(),
+            solved_at: chrono::Utc::now(),
+            ip_address: None,
+            user_agent: None,
+            playtest,
+        };
+
+        // Before the release
+        assert!(
+            record_solve(&mut conn, &solve(user_ids[0], true))
+                .await
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            record_solve(&mut conn, &solve(user_ids[1], true))
+                .await
+                .unwrap()
+                .is_none()
+        );
+        // After the release, the team can still score, but only once
+        assert!(
+            record_solve(&mut conn, &solve(user_ids[0], false))
+                .await
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            record_solve(&mut conn, &solve(user_ids[1], false))
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
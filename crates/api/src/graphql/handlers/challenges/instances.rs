@@ -2,9 +2,51 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use juniper::{GraphQLEnum, GraphQLObject};
+use std::{
+    pin::Pin,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
 
-use crate::{db::models::UserRole, graphql::Context, manager_api::Protocol};
+use futures_util::{Stream, StreamExt, TryStreamExt};
+use juniper::{FieldResult, GraphQLEnum, GraphQLObject, graphql_value};
+
+use crate::{
+    db::models::{AuditAction, UserRole},
+    graphql::Context,
+    manager_api::Protocol,
+};
+
+pub type InstanceLogStream = Pin<Box<dyn Stream<Item = FieldResult<InstanceLogLine>> + Send>>;
+
+/// How long an actor has to wait between two extensions of an instance
+const EXTEND_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Last extension per actor slug and challenge, entries expire once the cooldown is over
+static LAST_EXTENSIONS: LazyLock<moka::future::Cache<(String, String), Instant>> =
+    LazyLock::new(|| {
+        moka::future::Cache::builder()
+            .max_capacity(100_000)
+            .time_to_live(EXTEND_COOLDOWN)
+            .build()
+    });
+
+/// Commands run in instances by admins are cancelled after this long
+const EXEC_TIMEOUT: Duration = Duration::from_secs(30);
+/// Output of commands beyond this many bytes per stream is dropped
+const MAX_EXEC_OUTPUT: usize = 1024 * 1024;
+
+/// How long an actor has to wait between two restarts of an instance
+const RESTART_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Last restart per actor slug and challenge, entries expire once the cooldown is over
+static LAST_RESTARTS: LazyLock<moka::future::Cache<(String, String), Instant>> =
+    LazyLock::new(|| {
+        moka::future::Cache::builder()
+            .max_capacity(100_000)
+            .time_to_live(RESTART_COOLDOWN)
+            .build()
+    });
 
 #[derive(Debug, Clone, PartialEq, Eq, GraphQLEnum)]
 pub enum ConnectionProtocol {
@@ -24,8 +66,34 @@ pub struct CtfChallengeConnectionInfo {
     pub ssh_password: Option<String>,
 }
 
+/// Skips connections with protocols this version of the API doesn't know
+pub fn convert_connection_info(
+    connection_info: Vec<crate::manager_api::ConnectionInfo>,
+) -> Vec<CtfChallengeConnectionInfo> {
+    connection_info
+        .into_iter()
+        .filter_map(|ci| {
+            Some(CtfChallengeConnectionInfo {
+                host: ci.host,
+                port: ci.port as i32,
+                protocol: match Protocol::try_from(ci.protocol).ok()? {
+                    Protocol::TcpTls => ConnectionProtocol::TcpTls,
+                    Protocol::Https => ConnectionProtocol::Https,
+                    Protocol::Udp => ConnectionProtocol::Udp,
+                    Protocol::Ssh => ConnectionProtocol::Ssh,
+                    Protocol::Tcp => ConnectionProtocol::Tcp,
+                },
+                ssh_username: ci.ssh_username,
+                ssh_password: ci.ssh_password,
+            })
+        })
+        .collect()
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, GraphQLEnum)]
 pub enum InstanceState {
+    /// Waiting for the cluster to have capacity for the instance
+    Queued,
     Creating,
     Running,
     // Terminating is not reported to users
@@ -35,23 +103,106 @@ pub enum InstanceState {
 pub struct InstanceStatus {
     pub state: InstanceState,
     pub connection_info: Vec<CtfChallengeConnectionInfo>,
+    /// When the instance is deleted unless it is extended, null if it doesn't expire
+    pub expires_at: Option<String>,
+    /// Position in the queue while the instance is queued, starting at 1
+    pub queue_position: Option<i32>,
+    /// Why the instance isn't becoming ready, if it failed
+    pub failure_hint: Option<String>,
+    /// Details of the failure, only visible to admins
+    pub failure_reason: Option<InstanceFailure>,
+    /// How far the start got, while the instance is creating
+    pub progress: Option<InstanceProgress>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, GraphQLEnum)]
+pub enum InstanceStage {
+    NamespaceCreated,
+    /// Deployments, services, routes and network policies are being created
+    CreatingResources,
+    /// Pods are waiting for a node
+    Scheduling,
+    PullingImages,
+    /// Containers are running, but not ready yet
+    Starting,
+    Ready,
+}
+
+impl From<crate::manager_api::InstanceStage> for InstanceStage {
+    fn from(stage: crate::manager_api::InstanceStage) -> Self {
+        use crate::manager_api::InstanceStage as Stage;
+        match stage {
+            Stage::NamespaceCreated => InstanceStage::NamespaceCreated,
+            Stage::CreatingResources => InstanceStage::CreatingResources,
+            Stage::Scheduling => InstanceStage::Scheduling,
+            Stage::PullingImages => InstanceStage::PullingImages,
+            Stage::Starting => InstanceStage::Starting,
+            Stage::Ready => InstanceStage::Ready,
+        }
+    }
+}
+
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct InstanceProgress {
+    pub stage: InstanceStage,
+    pub ready_pods: i32,
+    pub total_pods: i32,
+}
+
+/// Why an instance failed to start, as reported by Kubernetes
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct InstanceFailure {
+    /// Like ImagePullBackOff, CrashLoopBackOff, OOMKilled or Unschedulable
+    pub reason: String,
+    pub message: String,
+    pub pod: String,
+    pub container: Option<String>,
+    /// The latest warning events of the instance, oldest first
+    pub events: Vec<String>,
 }
 
+#[tracing::instrument(skip(context))]
 pub async fn launch_challenge_instance(
     context: &Context,
     challenge_id: String,
 ) -> juniper::FieldResult<bool> {
     let auth = context.require_authentication()?;
+    crate::graphql::handlers::event::require_event_running(context).await?;
+    crate::graphql::handlers::archive::require_not_archived(context).await?;
+    // Authors can only test their own challenges before the release
+    let require_release = !super::has_pre_release_access(context, &challenge_id).await?;
 
     let mut challenges_client = context.challenges_client();
 
     challenges_client
         .start_challenge_instance(crate::manager_api::StartChallengeInstanceRequest {
-            challenge_id,
-            actor: auth.actor(),
-            require_release: auth.role == UserRole::Player,
+            challenge_id: challenge_id.clone(),
+            actor: auth.actor().slug(),
+            require_release,
         })
-        .await?;
+        .await
+        .map_err(|status| {
+            if status.code() == tonic::Code::ResourceExhausted {
+                juniper::FieldError::new(
+                    status.message(),
+                    graphql_value!({ "code": "INSTANCE_QUOTA_EXCEEDED" }),
+                )
+            } else {
+                status.into()
+            }
+        })?;
+
+    super::cheating::record_issued_flag(context, challenge_id.clone(), &auth.actor());
+    crate::webhooks::dispatch(
+        &context.base.db_pool,
+        crate::db::models::WebhookEvent::InstanceStart,
+        serde_json::json!({
+            "challenge_id": challenge_id,
+            "actor": auth.actor().slug(),
+            "user_id": auth.user_id,
+            "username": auth.username,
+        }),
+    );
 
     Ok(true)
 }
@@ -67,13 +218,87 @@ pub async fn stop_challenge_instance(
     challenges_client
         .stop_challenge_instance(crate::manager_api::StopChallengeInstanceRequest {
             challenge_id,
-            actor: auth.actor(),
+            actor: auth.actor().slug(),
         })
         .await?;
 
     Ok(true)
 }
 
+/// Postpones the deletion of the actor's instance, returning when it expires now.
+///
+/// Limited to one extension per minute, so the maximum lifetime can't be reached by accident.
+pub async fn extend_challenge_instance(
+    context: &Context,
+    challenge_id: String,
+) -> juniper::FieldResult<String> {
+    let auth = context.require_authentication()?;
+    crate::graphql::handlers::event::require_event_running(context).await?;
+    let key = (auth.actor().slug(), challenge_id.clone());
+    if let Some(last_extension) = LAST_EXTENSIONS.get(&key).await {
+        let remaining = EXTEND_COOLDOWN.saturating_sub(last_extension.elapsed());
+        let retry_after = remaining.as_secs().max(1) as i32;
+        return Err(juniper::FieldError::new(
+            format!(
+                "Please wait {} seconds before extending this instance again",
+                retry_after
+            ),
+            graphql_value!({ "code": "EXTEND_COOLDOWN", "retryAfter": retry_after }),
+        ));
+    }
+
+    let mut challenges_client = context.challenges_client();
+    let expires_at = challenges_client
+        .extend_challenge_instance(crate::manager_api::ExtendChallengeInstanceRequest {
+            challenge_id,
+            actor: auth.actor().slug(),
+        })
+        .await?
+        .into_inner()
+        .expires_at;
+    LAST_EXTENSIONS.insert(key, Instant::now()).await;
+
+    Ok(chrono::DateTime::from_timestamp(expires_at, 0)
+        .unwrap_or_default()
+        .to_rfc3339())
+}
+
+/// Recreates the pods of the actor's instance to get a fresh state, keeping its hostnames.
+///
+/// Limited to one restart per minute, as the containers take a while to come back up.
+pub async fn restart_challenge_instance(
+    context: &Context,
+    challenge_id: String,
+) -> juniper::FieldResult<bool> {
+    let auth = context.require_authentication()?;
+    crate::graphql::handlers::event::require_event_running(context).await?;
+    let key = (auth.actor().slug(), challenge_id.clone());
+    if let Some(last_restart) = LAST_RESTARTS.get(&key).await {
+        let remaining = RESTART_COOLDOWN.saturating_sub(last_restart.elapsed());
+        let retry_after = remaining.as_secs().max(1) as i32;
+        return Err(juniper::FieldError::new(
+            format!(
+                "Please wait {} seconds before restarting this instance again",
+                retry_after
+            ),
+            graphql_value!({ "code": "RESTART_COOLDOWN", "retryAfter": retry_after }),
+        ));
+    }
+
+    let mut challenges_client = context.challenges_client();
+    let success = challenges_client
+        .restart_challenge_instance(crate::manager_api::RestartChallengeInstanceRequest {
+            challenge_id,
+            actor: auth.actor().slug(),
+        })
+        .await?
+        .into_inner()
+        .success;
+    LAST_RESTARTS.insert(key, Instant::now()).await;
+
+    Ok(success)
+}
+
 pub async fn get_challenge_instance_status(
     context: &Context,
     challenge_id: String,
@@ -85,11 +310,22 @@ pub async fn get_challenge_instance_status(
     let response = challenges_client
         .get_challenge_instance_status(crate::manager_api::GetChallengeInstanceStatusRequest {
             challenge_id,
-            actor: auth.actor(),
+            actor: auth.actor().slug(),
         })
         .await?
         .into_inner();
 
+    if let Some(queue_position) = response.queue_position {
+        return Ok(Some(InstanceStatus {
+            state: InstanceState::Queued,
+            connection_info: vec![],
+            expires_at: None,
+            queue_position: Some(queue_position as i32),
+            failure_hint: None,
+            failure_reason: None,
+            progress: None,
+        }));
+    }
     if !response.is_deployed {
         return Ok(None);
     }
@@ -100,24 +336,192 @@ pub async fn get_challenge_instance_status(
         } else {
             InstanceState::Creating
         },
-        connection_info: response
-            .connection_info
-            .into_iter()
-            .filter_map(|ci| {
-                Some(CtfChallengeConnectionInfo {
-                    host: ci.host,
-                    port: ci.port as i32,
-                    protocol: match Protocol::try_from(ci.protocol).ok()? {
-                        Protocol::TcpTls => ConnectionProtocol::TcpTls,
-                        Protocol::Https => ConnectionProtocol::Https,
-                        Protocol::Udp => ConnectionProtocol::Udp,
-                        Protocol::Ssh => ConnectionProtocol::Ssh,
-                        Protocol::Tcp => ConnectionProtocol::Tcp,
-                    },
-                    ssh_username: ci.ssh_username,
-                    ssh_password: ci.ssh_password,
-                })
-            })
-            .collect(),
+        connection_info: convert_connection_info(response.connection_info),
+        expires_at: response
+            .expires_at
+            .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+            .map(|t| t.to_rfc3339()),
+        queue_position: None,
+        failure_hint: response.failure_hint,
+        failure_reason: response
+            .failure_reason
+            .filter(|_| context.require_role_min(UserRole::Admin).is_ok())
+            .map(|failure| InstanceFailure {
+                reason: failure.reason,
+                message: failure.message,
+                pod: failure.pod,
+                container: failure.container,
+                events: failure.events,
+            }),
+        progress: response.progress.map(|progress| InstanceProgress {
+            stage: progress.stage().into(),
+            ready_pods: progress.ready_pods as i32,
+            total_pods: progress.total_pods as i32,
+        }),
     }))
 }
+
+/// A line logged by a container of an instance
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct InstanceLogLine {
+    pub pod: String,
+    pub container: String,
+    pub line: String,
+}
+
+impl From<crate::manager_api::InstanceLogLine> for InstanceLogLine {
+    fn from(line: crate::manager_api::InstanceLogLine) -> Self {
+        Self {
+            pod: line.pod,
+            container: line.container,
+            line: line.line,
+        }
+    }
+}
+
+async fn instance_log_stream(
+    ctx: &Context,
+    challenge_id: String,
+    actor: String,
+    service: Option<String>,
+    tail_lines: Option<i32>,
+    follow: bool,
+) -> FieldResult<tonic::Streaming<crate::manager_api::InstanceLogLine>> {
+    ctx.require_role_min(UserRole::Admin)?;
+    let mut challenges_client = ctx.challenges_client();
+    Ok(challenges_client
+        .get_instance_logs(crate::manager_api::GetInstanceLogsRequest {
+            challenge_id,
+            actor,
+            service,
+            tail_lines: tail_lines.map(|n| n.max(1) as u32),
+            follow,
+        })
+        .await?
+        .into_inner())
+}
+
+/// The last lines logged by the containers of an actor's instance, optionally only those of one
+/// service
+pub async fn get_instance_logs(
+    ctx: &Context,
+    challenge_id: String,
+    actor: String,
+    service: Option<String>,
+    tail_lines: Option<i32>,
+) -> FieldResult<Vec<InstanceLogLine>> {
+    let lines: Vec<InstanceLogLine> =
+        instance_log_stream(ctx, challenge_id, actor, service, tail_lines, false)
+            .await?
+            .map_ok(InstanceLogLine::from)
+            .try_collect()
+            .await?;
+    Ok(lines)
+}
+
+/// Like [get_instance_logs], but keeps sending new lines while the containers are running
+pub async fn subscribe_instance_logs(
+    ctx: &Context,
+    challenge_id: String,
+    actor: String,
+    service: Option<String>,
+    tail_lines: Option<i32>,
+) -> FieldResult<InstanceLogStream> {
+    let lines = instance_log_stream(ctx, challenge_id, actor, service, tail_lines, true).await?;
+    Ok(Box::pin(lines.map(|line| {
+        line.map(InstanceLogLine::from).map_err(Into::into)
+    })))
+}
+
+/// Output of a command run in an instance
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct ExecResult {
+    pub stdout: String,
+    pub stderr: String,
+    /// Missing if the command didn't exit in time
+    pub exit_code: Option<i32>,
+}
+
+/// Runs a command in a container of an actor's instance and waits for it to exit
+pub async fn exec_in_instance(
+    ctx: &Context,
+    challenge_id: String,
+    actor: String,
+    command: Vec<String>,
+    service: Option<String>,
+    container: Option<String>,
+    stdin: Option<String>,
+) -> FieldResult<ExecResult> {
+    use crate::manager_api::{
+        ExecInstanceRequest, ExecStart, exec_instance_request, exec_instance_response,
+    };
+
+    ctx.require_role_min(UserRole::Admin)?;
+    let admin = ctx.require_authentication()?;
+    // Recorded before the command runs, so it can't run unrecorded
+    crate::graphql::handlers::audit_log::record(
+        &mut ctx.get_db_conn().await,
+        &admin,
+        AuditAction::InstanceExec,
+        None,
+        format!(
+            "Ran {:?} in the instance of challenge {} of {}",
+            command, challenge_id, actor
+        ),
+        None,
+    )
+    .await?;
+    let mut messages = vec![ExecInstanceRequest {
+        kind: Some(exec_instance_request::Kind::Start(ExecStart {
+            challenge_id,
+            actor,
+            service,
+            container,
+            command,
+            tty: false,
+        })),
+    }];
+    if let Some(stdin) = stdin {
+        messages.push(ExecInstanceRequest {
+            kind: Some(exec_instance_request::Kind::Stdin(stdin.into_bytes())),
+        });
+    }
+
+    let mut challenges_client = ctx.challenges_client();
+    let mut output = challenges_client
+        .exec_instance(futures_util::stream::iter(messages))
+        .await?
+        .into_inner();
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let mut exit_code = None;
+    let collect = async {
+        while let Some(message) = output.message().await? {
+            match message.kind {
+                Some(exec_instance_response::Kind::Stdout(data)) => {
+                    if stdout.len() < MAX_EXEC_OUTPUT {
+                        stdout.extend(data);
+                    }
+                }
+                Some(exec_instance_response::Kind::Stderr(data)) => {
+                    if stderr.len() < MAX_EXEC_OUTPUT {
+                        stderr.extend(data);
+                    }
+                }
+                Some(exec_instance_response::Kind::ExitCode(code)) => exit_code = Some(code),
+                None => {}
+            }
+        }
+        Ok::<_, tonic::Status>(())
+    };
+    // Dropping the stream on timeout kills the command
+    if let Ok(result) = tokio::time::timeout(EXEC_TIMEOUT, collect).await {
+        result?;
+    }
+
+    Ok(ExecResult {
+        stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&stderr).into_owned(),
+        exit_code,
+    })
+}
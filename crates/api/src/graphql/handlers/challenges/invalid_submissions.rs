@@ -2,16 +2,28 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use juniper::graphql_object;
+use std::collections::{HashMap, HashSet};
+
+use juniper::{FieldResult, GraphQLInputObject, GraphQLObject, graphql_object};
 
 use crate::{
-    db::models::{InvalidSubmission, User},
-    graphql::handlers::owned_resource::{HasActor, HasOwnerUserId},
+    db::models::{InvalidSubmission, User, UserRole},
+    db::schema::{invalid_submissions, teams, users},
+    graphql::{
+        Actor, Context,
+        handlers::owned_resource::{HasActor, HasOwnerUserId},
+    },
+    manager_api::{GetActorFlagRequest, GetActorFlagsRequest},
 };
 
 use diesel::prelude::*;
 use diesel_async::RunQueryDsl;
 
+/// Submissions scanned at most when filtering by near misses, which can't be done in the database
+const MAX_NEAR_MISS_SCAN: i64 = 5_000;
+/// Submissions this many edits away from the flag count as near misses, more for long flags
+const NEAR_MISS_MIN_DISTANCE: usize = 2;
+
 impl HasOwnerUserId for InvalidSubmission {
     fn user_id(&self) -> uuid::Uuid {
         self.user_id
@@ -34,6 +46,21 @@ impl InvalidSubmission {
         self.submitted_at.to_rfc3339()
     }
 
+    /// IP address the flag was submitted from (admin only)
+    pub fn ip_address(
+        &self,
+        ctx: &crate::graphql::Context,
+    ) -> juniper::FieldResult<Option<String>> {
+        ctx.require_role_min(UserRole::Admin)?;
+        Ok(self.ip_address.map(|ip| ip.addr().to_string()))
+    }
+
+    /// User agent the flag was submitted with (admin only)
+    pub fn user_agent(&self, ctx: &crate::graphql::Context) -> juniper::FieldResult<Option<&str>> {
+        ctx.require_role_min(UserRole::Admin)?;
+        Ok(self.user_agent.as_deref())
+    }
+
     pub async fn user(&self, ctx: &crate::graphql::Context) -> juniper::FieldResult<User> {
         use crate::db::schema::users::dsl::*;
         let user_record = users
@@ -58,3 +85,255 @@ impl InvalidSubmission {
             .ok_or_else(|| juniper::FieldError::new("Challenge not found", juniper::Value::null()))
     }
 }
+
+/// Criteria to narrow down invalid submissions, all of which must match
+#[derive(GraphQLInputObject, Debug, Default)]
+pub struct InvalidSubmissionFilter {
+    pub challenge_id: Option<String>,
+    /// Submissions by current members of this team
+    pub team_id: Option<String>,
+    pub user_id: Option<String>,
+    /// RFC 3339 timestamp
+    pub submitted_after: Option<String>,
+    /// RFC 3339 timestamp
+    pub submitted_before: Option<String>,
+    /// Only submissions that are (or are not) close to the correct flag
+    pub near_miss: Option<bool>,
+}
+
+/// An invalid submission compared to the flag the submitter should have found
+#[derive(GraphQLObject)]
+#[graphql(context = Context)]
+pub struct ReviewedSubmission {
+    pub submission: InvalidSubmission,
+    /// Edit distance to the correct flag, unknown if flags are checked by a script
+    pub flag_distance: Option<i32>,
+    /// Whether the submission looks like a typo of the correct flag
+    pub near_miss: bool,
+}
+
+#[derive(GraphQLObject)]
+#[graphql(context = Context)]
+pub struct InvalidSubmissionPage {
+    pub total_count: i32,
+    /// Whether only the newest submissions were searched for near misses, so there may be more
+    /// matching ones than `total_count`
+    pub truncated: bool,
+    pub submissions: Vec<ReviewedSubmission>,
+}
+
+/// Levenshtein distance between two strings, counted in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn is_near_miss(submitted: &str, flag: &str, distance: usize) -> bool {
+    submitted.trim().eq_ignore_ascii_case(flag)
+        || distance <= NEAR_MISS_MIN_DISTANCE.max(flag.chars().count() / 10)
+}
+
+fn parse_timestamp(timestamp: &str) -> FieldResult<chrono::DateTime<chrono::Utc>> {
+    Ok(chrono::DateTime::parse_from_rfc3339(timestamp)
+        .map_err(|e| {
+            juniper::FieldError::new(
+                format!("Invalid timestamp {timestamp}: {e}"),
+                juniper::Value::null(),
+            )
+        })?
+        .to_utc())
+}
+
+type FilteredSubmissions = diesel::dsl::IntoBoxed<
+    'static,
+    diesel::dsl::InnerJoin<
+        invalid_submissions::table,
+        diesel::dsl::LeftJoin<users::table, teams::table>,
+    >,
+    diesel::pg::Pg,
+>;
+
+fn filtered_submissions(filter: &InvalidSubmissionFilter) -> FieldResult<FilteredSubmissions> {
+    let mut query = invalid_submissions::table
+        .inner_join(users::table.left_join(teams::table))
+        .into_boxed();
+    if let Some(challenge_id) = &filter.challenge_id {
+        query = query.filter(invalid_submissions::challenge_id.eq(challenge_id.clone()));
+    }
+    if let Some(team_id) = &filter.team_id {
+        query = query.filter(users::team_id.eq(uuid::Uuid::parse_str(team_id)?));
+    }
+    if let Some(user_id) = &filter.user_id {
+        query = query.filter(invalid_submissions::user_id.eq(uuid::Uuid::parse_str(user_id)?));
+    }
+    if let Some(after) = &filter.submitted_after {
+        query = query.filter(invalid_submissions::submitted_at.ge(parse_timestamp(after)?));
+    }
+    if let Some(before) = &filter.submitted_before {
+        query = query.filter(invalid_submissions::submitted_at.lt(parse_timestamp(before)?));
+    }
+    Ok(query)
+}
+
+/// A submission with the username and the current team (ID and slug) of its submitter
+type SubmissionRow = (InvalidSubmission, String, Option<(uuid::Uuid, String)>);
+
+/// Compares submissions to the correct flag of their submitter, asking the manager for the flags
+/// of all challenges and actors at once.
+async fn review(ctx: &Context, rows: Vec<SubmissionRow>) -> FieldResult<Vec<ReviewedSubmission>> {
+    let rows = rows
+        .into_iter()
+        .map(|(submission, username, team)| {
+            let actor = match team {
+                Some((id, slug)) => Actor::Team { id, slug },
+                None => Actor::User {
+                    id: submission.user_id,
+                    username,
+                },
+            };
+            let key = (submission.challenge_id.clone(), actor.slug());
+            (submission, key)
+        })
+        .collect::<Vec<_>>();
+    let keys = rows
+        .iter()
+        .map(|(_, key)| key.clone())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let requests = keys
+        .iter()
+        .map(|(challenge_id, actor)| GetActorFlagRequest {
+            challenge_id: challenge_id.clone(),
+            actor: actor.clone(),
+        })
+        .collect();
+    let flags = ctx
+        .challenges_client()
+        .get_actor_flags(GetActorFlagsRequest { requests })
+        .await?
+        .into_inner()
+        .flags;
+    // Challenges may have been removed since, which just means there's no flag
+    let flags: HashMap<_, _> = keys
+        .into_iter()
+        .zip(flags.into_iter().map(|response| response.flag))
+        .collect();
+
+    let mut reviewed = Vec::with_capacity(rows.len());
+    for (submission, key) in rows {
+        let flag = flags.get(&key).cloned().flatten();
+        let distance = flag
+            .as_deref()
+            .map(|flag| edit_distance(&submission.submitted_flag, flag));
+        let near_miss = flag
+            .as_deref()
+            .zip(distance)
+            .is_some_and(|(flag, distance)| {
+                is_near_miss(&submission.submitted_flag, flag, distance)
+            });
+        reviewed.push(ReviewedSubmission {
+            submission,
+            flag_distance: distance.map(|d| d as i32),
+            near_miss,
+        });
+    }
+    Ok(reviewed)
+}
+
+/// Lists invalid flag submissions, newest first, to spot bruteforcing and typos (admin only).
+pub async fn get_invalid_submissions(
+    ctx: &Context,
+    filter: InvalidSubmissionFilter,
+    offset: i32,
+    limit: i32,
+) -> FieldResult<InvalidSubmissionPage> {
+    ctx.require_role_min(UserRole::Admin)?;
+
+    let offset = offset.max(0) as i64;
+    let limit = limit.clamp(1, 500) as i64;
+    let select = (
+        InvalidSubmission::as_select(),
+        users::username,
+        (teams::id, teams::slug).nullable(),
+    );
+    let conn = &mut ctx.get_db_conn().await;
+    let Some(near_miss) = filter.near_miss else {
+        let total_count = filtered_submissions(&filter)?
+            .count()
+            .get_result::<i64>(conn)
+            .await?;
+        let rows = filtered_submissions(&filter)?
+            .order(invalid_submissions::submitted_at.desc())
+            .offset(offset)
+            .limit(limit)
+            .select(select)
+            .load(conn)
+            .await?;
+        return Ok(InvalidSubmissionPage {
+            total_count: total_count as i32,
+            truncated: false,
+            submissions: review(ctx, rows).await?,
+        });
+    };
+
+    let rows = filtered_submissions(&filter)?
+        .order(invalid_submissions::submitted_at.desc())
+        .limit(MAX_NEAR_MISS_SCAN + 1)
+        .select(select)
+        .load::<SubmissionRow>(conn)
+        .await?;
+    let truncated = rows.len() as i64 > MAX_NEAR_MISS_SCAN;
+    let rows = rows.into_iter().take(MAX_NEAR_MISS_SCAN as usize).collect();
+    let matching = review(ctx, rows)
+        .await?
+        .into_iter()
+        .filter(|s| s.near_miss == near_miss)
+        .collect::<Vec<_>>();
+    Ok(InvalidSubmissionPage {
+        total_count: matching.len() as i32,
+        truncated,
+        submissions: matching
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_near_miss() {
+        assert_eq!(edit_distance("flag{abc}", "flag{abc}"), 0);
+        assert_eq!(edit_distance("flag{abd}", "flag{abc}"), 1);
+        assert_eq!(edit_distance("flg{abc}", "flag{abc}"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+
+        let flag = "flag{this_is_a_long_flag_with_many_chars}";
+        let typo = "flag{this_is_a_long_flag_with_mnay_chars}";
+        assert!(is_near_miss(typo, flag, edit_distance(typo, flag)));
+        let uppercase = " FLAG{THIS_IS_A_LONG_FLAG_WITH_MANY_CHARS}";
+        assert!(is_near_miss(
+            uppercase,
+            flag,
+            edit_distance(uppercase, flag)
+        ));
+        assert!(!is_near_miss(
+            "flag{guess}",
+            flag,
+            edit_distance("flag{guess}", flag)
+        ));
+    }
+}
@@ -0,0 +1,74 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::time::Duration;
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use tokio::task::JoinSet;
+
+use crate::{
+    graphql::BaseContext,
+    manager_api::{CollectGarbageRequest, challenges_service_client::ChallengesServiceClient},
+};
+
+/// How often the manager is told which actors still exist
+const COLLECT_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Slugs of all users and teams, as the manager knows them
+async fn actor_slugs(
+    ctx: &BaseContext,
+) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let conn = &mut ctx.db_pool.get().await?;
+    let usernames = crate::db::schema::users::table
+        .select(crate::db::schema::users::username)
+        .load::<String>(conn)
+        .await?;
+    let team_slugs = crate::db::schema::teams::table
+        .select(crate::db::schema::teams::slug)
+        .load::<String>(conn)
+        .await?;
+    Ok(usernames
+        .into_iter()
+        .map(|username| format!("user-{username}"))
+        .chain(team_slugs.into_iter().map(|slug| format!("team-{slug}")))
+        .collect())
+}
+
+async fn collect_orphaned_instances(
+    ctx: &BaseContext,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let actors = actor_slugs(ctx).await?;
+    if actors.is_empty() {
+        return Ok(());
+    }
+    let report = ChallengesServiceClient::new(ctx.grpc_client.clone())
+        .collect_garbage(CollectGarbageRequest { actors })
+        .await?
+        .into_inner();
+    for (reason, count) in [
+        ("missing_challenge", report.missing_challenge),
+        ("deleted_actor", report.deleted_actor),
+        ("force_finalized", report.force_finalized),
+    ] {
+        crate::metrics::INSTANCES_COLLECTED
+            .with_label_values(&[reason])
+            .inc_by(count as u64);
+    }
+    Ok(())
+}
+
+/// Starts the background task that has the manager delete instances of deleted users and teams,
+/// and of challenges removed from the repo.
+pub fn start_orphan_collector(tasks: &mut JoinSet<()>, ctx: BaseContext) {
+    tasks.spawn(async move {
+        let mut interval = tokio::time::interval(COLLECT_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = collect_orphaned_instances(&ctx).await {
+                tracing::error!("Failed to collect orphaned instances: {}", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,247 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+use diesel_async::{
+    AsyncConnection, AsyncPgConnection, RunQueryDsl, scoped_futures::ScopedFutureExt,
+};
+use juniper::{FieldResult, GraphQLObject};
+
+use crate::{
+    db::models::{AuditAction, InvalidSubmission, NewSolve, UserRole},
+    graphql::{Actor, Context, handlers::audit_log},
+    manager_api::CheckFlagRequest,
+};
+
+#[derive(GraphQLObject, Debug, Clone, Default)]
+pub struct RejudgeResult {
+    /// Invalid submissions that were checked again
+    pub checked: i32,
+    /// Submissions that are correct now and became new solves
+    pub new_solves: i32,
+    /// Solves that were moved to the time of an earlier submission that is correct now
+    pub earlier_solves: i32,
+}
+
+enum RejudgeOutcome {
+    NewSolve,
+    EarlierSolve,
+    AlreadySolved,
+}
+
+/// Turns a submission that turned out to be correct into a solve of its actor.
+///
+/// If the actor solved the challenge later on, that solve is moved to the submission instead.
+/// Playtest solves are kept apart from the ones after the release, like when submitting.
+async fn convert_to_solve(
+    conn: &mut AsyncPgConnection,
+    submission: InvalidSubmission,
+    playtest: bool,
+) -> QueryResult<RejudgeOutcome> {
+    use crate::db::schema::{invalid_submissions, solves};
+
+    let team_id = submission.team_id;
+    let mut existing = solves::table
+        .filter(solves::challenge_id.eq(&submission.challenge_id))
+        .filter(solves::playtest.eq(playtest))
+        .select((solves::id, solves::solved_at))
+        .into_boxed();
+    existing = match team_id {
+        Some(team_id) => existing.filter(solves::team_id.eq(team_id)),
+        None => existing
+            .filter(solves::user_id.eq(submission.user_id))
+            .filter(solves::team_id.is_null()),
+    };
+    let existing = existing
+        .first::<(uuid::Uuid, chrono::DateTime<chrono::Utc>)>(conn)
+        .await
+        .optional()?;
+
+    let outcome = match existing {
+        None => {
+            // The submitter may have solved it for another team since, which still counts
+            let inserted = diesel::insert_into(solves::table)
+                .values(NewSolve {
+                    user_id: submission.user_id,
+                    team_id,
+                    challenge_id: submission.challenge_id.clone(),
+                    submitted_flag: submission.submitted_flag.clone(),
+                    solved_at: submission.submitted_at,
+                    ip_address: submission.ip_address,
+                    user_agent: submission.user_agent.clone(),
+                    playtest,
+                })
+                .on_conflict_do_nothing()
+                .execute(conn)
+                .await?;
+            if inserted > 0 {
+                RejudgeOutcome::NewSolve
+            } else {
+                RejudgeOutcome::AlreadySolved
+            }
+        }
+        Some((solve_id, solved_at)) if solved_at > submission.submitted_at => {
+            // A user has one solve per challenge, so if the submitter's is another one (e.g. for
+            // a previous team), the team's solve only moves to the earlier time
+            let submitter_solve = solves::table
+                .filter(solves::challenge_id.eq(&submission.challenge_id))
+                .filter(solves::playtest.eq(playtest))
+                .filter(solves::user_id.eq(submission.user_id))
+                .filter(solves::id.ne(solve_id))
+                .select(solves::id)
+                .first::<uuid::Uuid>(conn)
+                .await
+                .optional()?;
+            let solve = solves::table.filter(solves::id.eq(solve_id));
+            if submitter_solve.is_some() {
+                diesel::update(solve)
+                    .set(solves::solved_at.eq(submission.submitted_at))
+                    .execute(conn)
+                    .await?;
+            } else {
+                diesel::update(solve)
+                    .set((
+                        solves::user_id.eq(submission.user_id),
+                        solves::submitted_flag.eq(&submission.submitted_flag),
+                        solves::solved_at.eq(submission.submitted_at),
+                        solves::ip_address.eq(submission.ip_address),
+                        solves::user_agent.eq(&submission.user_agent),
+                    ))
+                    .execute(conn)
+                    .await?;
+            }
+            RejudgeOutcome::EarlierSolve
+        }
+        Some(_) => RejudgeOutcome::AlreadySolved,
+    };
+    // The submission was correct after all
+    diesel::delete(invalid_submissions::table.filter(invalid_submissions::id.eq(submission.id)))
+        .execute(conn)
+        .await?;
+    Ok(outcome)
+}
+
+/// Checks all invalid submissions of a challenge again, e.g. after its flag was fixed, and turns
+/// those that are correct now into solves at the time they were submitted (admin only).
+///
+/// Submissions count for the team their submitter was in when submitting, like solves do.
+pub async fn rejudge_challenge(ctx: &Context, challenge_id: String) -> FieldResult<RejudgeResult> {
+    use crate::db::schema::{invalid_submissions, teams, users};
+
+    ctx.require_role_min(UserRole::Admin)?;
+    let admin = ctx.require_authentication()?;
+
+    let submissions = invalid_submissions::table
+        .inner_join(users::table)
+        .left_join(teams::table.on(invalid_submissions::team_id.eq(teams::id.nullable())))
+        .filter(invalid_submissions::challenge_id.eq(&challenge_id))
+        .order(invalid_submissions::submitted_at.asc())
+        .select((
+            InvalidSubmission::as_select(),
+            users::username,
+            (teams::id, teams::slug).nullable(),
+        ))
+        .load::<(InvalidSubmission, String, Option<(uuid::Uuid, String)>)>(
+            &mut ctx.get_db_conn().await,
+        )
+        .await?;
+
+    // Submissions before the release become playtest solves, like when they were made
+    let release_time = super::get_challenges(ctx)
+        .await?
+        .into_iter()
+        .find(|c| c.id == challenge_id)
+        .and_then(|c| c.release_time);
+
+    let checked = submissions.len() as i32;
+    let mut challenges_client = ctx.challenges_client();
+    // Bruteforcing produces many identical submissions
+    let mut verdicts: HashMap<(String, String), bool> = HashMap::new();
+    let mut correct_submissions = vec![];
+    for (submission, username, team) in submissions {
+        let actor = match team {
+            Some((id, slug)) => Actor::Team { id, slug },
+            None => Actor::User {
+                id: submission.user_id,
+                username,
+            },
+        };
+        let key = (actor.slug(), submission.submitted_flag.clone());
+        let correct = match verdicts.get(&key) {
+            Some(correct) => *correct,
+            None => {
+                let solved_challenge_id = challenges_client
+                    .check_flag(CheckFlagRequest {
+                        actor: key.0.clone(),
+                        challenge_id: Some(challenge_id.clone()),
+                        flag: key.1.clone(),
+                    })
+                    .await?
+                    .into_inner()
+                    .solved_challenge_id;
+                let correct = solved_challenge_id.as_ref() == Some(&challenge_id);
+                verdicts.insert(key, correct);
+                correct
+            }
+        };
+        if !correct {
+            continue;
+        }
+        let playtest = release_time.is_some_and(|release_time| {
+            i64::from(release_time) > submission.submitted_at.timestamp()
+        });
+        correct_submissions.push((submission, playtest));
+    }
+
+    // Either all submissions are converted or none, so a failed rejudge can simply be repeated
+    let (admin, challenge_id) = (&admin, &challenge_id);
+    let result = ctx
+        .get_db_conn()
+        .await
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                let mut result = RejudgeResult {
+                    checked,
+                    ..Default::default()
+                };
+                for (submission, playtest) in correct_submissions {
+                    match convert_to_solve(conn, submission, playtest).await? {
+                        RejudgeOutcome::NewSolve => result.new_solves += 1,
+                        RejudgeOutcome::EarlierSolve => result.earlier_solves += 1,
+                        RejudgeOutcome::AlreadySolved => {}
+                    }
+                }
+                audit_log::record(
+                    conn,
+                    admin,
+                    AuditAction::ChallengeRejudged,
+                    None,
+                    format!(
+                        "Rejudged challenge {}: {} submissions checked, {} new solves, {} earlier solves",
+                        challenge_id, result.checked, result.new_solves, result.earlier_solves
+                    ),
+                    None,
+                )
+                .await?;
+                Ok(result)
+            }
+            .scope_boxed()
+        })
+        .await?;
+    if result.new_solves > 0 || result.earlier_solves > 0 {
+        crate::graphql::handlers::scoreboard::points::request_recompute(Some(challenge_id.clone()));
+        super::invalidate_challenge_lists();
+    }
+    tracing::info!(
+        "Challenge {} rejudged by {}: {} submissions checked, {} new solves, {} earlier solves",
+        challenge_id,
+        admin.username,
+        result.checked,
+        result.new_solves,
+        result.earlier_solves
+    );
+    Ok(result)
+}
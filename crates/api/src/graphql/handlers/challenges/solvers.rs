@@ -0,0 +1,68 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use chrono::DateTime;
+use juniper::{FieldResult, GraphQLObject};
+
+use crate::graphql::{Context, handlers::scoreboard::get_scoreboard};
+
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct ChallengeSolver {
+    /// ID of the team, or of the user if they are not in a team
+    pub id: String,
+    pub name: String,
+    /// Actor slug, e.g. "team-foo" or "user-bar"
+    pub actor: String,
+    pub is_team: bool,
+    pub solved_at: String,
+    pub points: i32,
+}
+
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct ChallengeSolverPage {
+    /// Number of teams or users who solved the challenge
+    pub total_count: i32,
+    pub solvers: Vec<ChallengeSolver>,
+}
+
+/// Lists who solved a challenge, earliest first.
+///
+/// This is based on the scoreboard visible to the current user, so solves after a freeze are
+/// hidden the same way.
+pub async fn get_challenge_solvers(
+    ctx: &Context,
+    challenge_id: &str,
+    offset: i32,
+    limit: i32,
+) -> FieldResult<ChallengeSolverPage> {
+    let scoreboard = get_scoreboard(ctx).await?;
+    let mut solvers = scoreboard
+        .entries
+        .into_iter()
+        .filter_map(|entry| {
+            let solve = entry
+                .solves
+                .into_iter()
+                .find(|solve| solve.challenge_id == challenge_id)?;
+            Some(ChallengeSolver {
+                id: entry.id,
+                name: entry.name,
+                actor: entry.actor,
+                is_team: entry.is_team,
+                solved_at: solve.solved_at,
+                points: solve.points,
+            })
+        })
+        .collect::<Vec<_>>();
+    solvers.sort_by_key(|solver| DateTime::parse_from_rfc3339(&solver.solved_at).ok());
+
+    Ok(ChallengeSolverPage {
+        total_count: solvers.len() as i32,
+        solvers: solvers
+            .into_iter()
+            .skip(offset.max(0) as usize)
+            .take(limit.clamp(1, 500) as usize)
+            .collect(),
+    })
+}
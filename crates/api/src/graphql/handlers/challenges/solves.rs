@@ -20,6 +20,10 @@ impl HasOwnerUserId for Solve {
 
 #[graphql_object]
 impl Solve {
+    pub fn id(&self) -> String {
+        self.id.to_string()
+    }
+
     pub fn challenge_id(&self) -> &str {
         &self.challenge_id
     }
@@ -33,6 +37,26 @@ impl Solve {
         self.solved_at.to_rfc3339()
     }
 
+    /// Points awarded for this solve, null if they haven't been calculated yet
+    pub fn awarded_points(&self) -> Option<i32> {
+        self.awarded_points
+    }
+
+    /// IP address the flag was submitted from (admin only)
+    pub fn ip_address(
+        &self,
+        ctx: &crate::graphql::Context,
+    ) -> juniper::FieldResult<Option<String>> {
+        ctx.require_role_min(UserRole::Admin)?;
+        Ok(self.ip_address.map(|ip| ip.addr().to_string()))
+    }
+
+    /// User agent the flag was submitted with (admin only)
+    pub fn user_agent(&self, ctx: &crate::graphql::Context) -> juniper::FieldResult<Option<&str>> {
+        ctx.require_role_min(UserRole::Admin)?;
+        Ok(self.user_agent.as_deref())
+    }
+
     pub async fn user(&self, ctx: &crate::graphql::Context) -> juniper::FieldResult<User> {
         use crate::db::schema::users::dsl::*;
         let user_record = users
@@ -0,0 +1,107 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use diesel::{
+    prelude::*,
+    sql_types::{Array, BigInt, Inet, Timestamptz, Uuid},
+};
+use diesel_async::RunQueryDsl;
+use juniper::{FieldResult, graphql_object};
+
+use crate::{
+    db::models::{InvalidSubmission, Solve, User, UserRole},
+    graphql::Context,
+};
+
+/// Flag submissions (correct or not) made from a single IP address.
+#[derive(QueryableByName, Debug)]
+pub struct SubmissionIp {
+    #[diesel(sql_type = Inet)]
+    ip_address: ipnet::IpNet,
+    #[diesel(sql_type = Array<Uuid>)]
+    user_ids: Vec<uuid::Uuid>,
+    #[diesel(sql_type = BigInt)]
+    submissions: i64,
+    #[diesel(sql_type = Timestamptz)]
+    last_submission_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[graphql_object]
+impl SubmissionIp {
+    pub fn ip_address(&self) -> String {
+        self.ip_address.addr().to_string()
+    }
+
+    /// Number of different users who submitted flags from this IP
+    pub fn user_count(&self) -> i32 {
+        self.user_ids.len() as i32
+    }
+
+    pub fn submission_count(&self) -> i32 {
+        self.submissions as i32
+    }
+
+    pub fn last_submission_at(&self) -> String {
+        self.last_submission_at.to_rfc3339()
+    }
+
+    pub async fn users(&self, ctx: &Context) -> FieldResult<Vec<User>> {
+        use crate::db::schema::users::dsl::*;
+        let user_records = users
+            .filter(id.eq_any(&self.user_ids))
+            .order(username.asc())
+            .load::<User>(&mut ctx.get_db_conn().await)
+            .await?;
+        Ok(user_records)
+    }
+
+    pub async fn solves(&self, ctx: &Context) -> FieldResult<Vec<Solve>> {
+        use crate::db::schema::solves::dsl::*;
+        let solve_records = solves
+            .filter(ip_address.eq(self.ip_address))
+            .order(solved_at.asc())
+            .select(Solve::as_select())
+            .load::<Solve>(&mut ctx.get_db_conn().await)
+            .await?;
+        Ok(solve_records)
+    }
+
+    pub async fn invalid_submissions(&self, ctx: &Context) -> FieldResult<Vec<InvalidSubmission>> {
+        use crate::db::schema::invalid_submissions::dsl::*;
+        let submissions = invalid_submissions
+            .filter(ip_address.eq(self.ip_address))
+            .order(submitted_at.asc())
+            .select(InvalidSubmission::as_select())
+            .load::<InvalidSubmission>(&mut ctx.get_db_conn().await)
+            .await?;
+        Ok(submissions)
+    }
+}
+
+/// Lists the IPs flags were submitted from by at least `min_users` different users,
+/// which usually means accounts are shared or several players use one account.
+pub async fn get_submission_ips(ctx: &Context, min_users: i32) -> FieldResult<Vec<SubmissionIp>> {
+    ctx.require_role_min(UserRole::Admin)?;
+
+    let ips = diesel::sql_query(
+        "SELECT ip_address,
+            array_agg(DISTINCT user_id) AS user_ids,
+            COUNT(*) AS submissions,
+            MAX(submitted_at) AS last_submission_at
+        FROM (
+            SELECT ip_address, user_id, solved_at AS submitted_at
+            FROM solves WHERE ip_address IS NOT NULL
+            UNION ALL
+            SELECT ip_address, user_id, submitted_at
+            FROM invalid_submissions WHERE ip_address IS NOT NULL
+        ) s
+        GROUP BY ip_address
+        HAVING COUNT(DISTINCT user_id) >= $1
+        ORDER BY COUNT(DISTINCT user_id) DESC, submissions DESC",
+    )
+    .bind::<BigInt, _>(min_users.max(1) as i64)
+    .load::<SubmissionIp>(&mut ctx.get_db_conn().await)
+    .await?;
+    Ok(ips)
+}
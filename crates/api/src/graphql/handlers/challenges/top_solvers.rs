@@ -0,0 +1,95 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use chrono::{DateTime, Utc};
+use diesel::{
+    prelude::*,
+    sql_types::{BigInt, Bool, Integer, Nullable, Text, Timestamptz, Uuid},
+};
+use diesel_async::RunQueryDsl;
+use juniper::{FieldResult, GraphQLObject};
+
+use crate::graphql::{Context, handlers::scoreboard::visible_freeze_cutoff};
+
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct TopSolve {
+    /// 1 for the first blood
+    pub rank: i32,
+    /// ID of the team, or of the user if they are not in a team
+    pub id: String,
+    pub name: String,
+    /// Actor slug, e.g. "team-foo" or "user-bar"
+    pub actor: String,
+    pub is_team: bool,
+    pub solved_at: String,
+    /// Null while the scoreboard is frozen or if the points haven't been calculated yet
+    pub awarded_points: Option<i32>,
+}
+
+#[derive(QueryableByName)]
+struct TopSolveRow {
+    #[diesel(sql_type = Uuid)]
+    actor_id: uuid::Uuid,
+    #[diesel(sql_type = Text)]
+    name: String,
+    #[diesel(sql_type = Text)]
+    actor: String,
+    #[diesel(sql_type = Bool)]
+    is_team: bool,
+    #[diesel(sql_type = Timestamptz)]
+    solved_at: DateTime<Utc>,
+    #[diesel(sql_type = Nullable<Integer>)]
+    awarded_points: Option<i32>,
+}
+
+/// Returns the first `limit` teams or users to solve a challenge.
+pub async fn get_top_solvers(
+    ctx: &Context,
+    challenge_id: &str,
+    limit: i32,
+) -> FieldResult<Vec<TopSolve>> {
+    let frozen_at = visible_freeze_cutoff(ctx)
+        .await?
+        .and_then(|t| DateTime::from_timestamp(t, 0));
+
+    // Only the first solve of each team counts, like on the scoreboard
+    let rows = diesel::sql_query(
+        "SELECT actor_id, name, actor, is_team, solved_at, awarded_points FROM (
+            SELECT DISTINCT ON (COALESCE(s.team_id, u.id))
+                COALESCE(s.team_id, u.id) AS actor_id,
+                COALESCE(t.name, u.display_name) AS name,
+                CASE WHEN t.id IS NULL THEN 'user-' || u.username ELSE 'team-' || t.slug END AS actor,
+                t.id IS NOT NULL AS is_team,
+                s.solved_at,
+                s.awarded_points
+            FROM solves s
+            INNER JOIN users u ON u.id = s.user_id
+            LEFT JOIN teams t ON t.id = s.team_id
+            WHERE s.challenge_id = $1 AND ($2::timestamptz IS NULL OR s.solved_at <= $2)
+                AND NOT s.playtest AND NOT u.hidden AND NOT COALESCE(t.hidden OR t.disqualified, FALSE)
+            ORDER BY COALESCE(s.team_id, u.id), s.solved_at
+        ) first_solves
+        ORDER BY solved_at
+        LIMIT $3",
+    )
+    .bind::<Text, _>(challenge_id)
+    .bind::<Nullable<Timestamptz>, _>(frozen_at)
+    .bind::<BigInt, _>(limit.clamp(1, 100) as i64)
+    .load::<TopSolveRow>(&mut ctx.get_db_conn().await)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .enumerate()
+        .map(|(idx, row)| TopSolve {
+            rank: idx as i32 + 1,
+            id: row.actor_id.to_string(),
+            name: row.name,
+            actor: row.actor,
+            is_team: row.is_team,
+            solved_at: row.solved_at.to_rfc3339(),
+            awarded_points: row.awarded_points.filter(|_| frozen_at.is_none()),
+        })
+        .collect())
+}
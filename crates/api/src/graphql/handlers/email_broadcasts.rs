@@ -0,0 +1,476 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use diesel::{
+    prelude::*,
+    sql_types::{BigInt, Integer, Uuid},
+};
+use diesel_async::{AsyncConnection, RunQueryDsl, scoped_futures::ScopedFutureExt};
+use juniper::{FieldResult, GraphQLInputObject, GraphQLObject, graphql_object};
+use tokio::task::JoinSet;
+
+use crate::{
+    db::models::{
+        ApprovalStatus, EmailBroadcast, EmailDelivery, EmailDeliveryStatus, NewEmailBroadcast,
+        NewEmailDelivery, User, UserRole,
+    },
+    graphql::{BaseContext, Context},
+};
+
+type DbPool = diesel_async::pooled_connection::bb8::Pool<diesel_async::AsyncPgConnection>;
+
+/// Deliveries are retried with exponential backoff until this many attempts failed
+const MAX_ATTEMPTS: i32 = 5;
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(60);
+/// How often the worker looks for deliveries queued by other api replicas
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// Claimed deliveries are hidden from other workers for this long, so they are picked up again
+/// if the replica sending them stops
+const CLAIM_TIMEOUT: Duration = Duration::from_secs(600);
+const BATCH_SIZE: i64 = 50;
+/// Emails sent to each provider (recipient domain) per minute, unless email_rate_limit_per_provider is configured
+const DEFAULT_RATE_LIMIT: usize = 30;
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Wakes up the worker of this replica when a broadcast is queued
+static NEW_BROADCASTS: LazyLock<tokio::sync::Notify> = LazyLock::new(tokio::sync::Notify::new);
+
+/// Who receives a broadcast, all given criteria must match.
+///
+/// Only active, approved users with a verified email address receive broadcasts.
+#[derive(GraphQLInputObject, Debug, Default)]
+pub struct EmailAudience {
+    /// Only users with one of these roles
+    pub roles: Option<Vec<UserRole>>,
+    /// Only members of teams in this division
+    pub division: Option<String>,
+    /// Only members of these teams
+    pub team_ids: Option<Vec<String>>,
+}
+
+#[derive(GraphQLObject, Debug, Clone, Default)]
+pub struct EmailDeliveryCounts {
+    pub pending: i32,
+    pub sent: i32,
+    pub failed: i32,
+}
+
+#[graphql_object]
+impl EmailBroadcast {
+    pub fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    pub fn body_md(&self) -> &str {
+        &self.body_md
+    }
+
+    pub fn created_at(&self) -> String {
+        self.created_at.to_rfc3339()
+    }
+
+    /// The admin who sent the broadcast, unless their account was deleted
+    pub async fn created_by(&self, ctx: &Context) -> FieldResult<Option<User>> {
+        use crate::db::schema::users;
+        let Some(created_by) = self.created_by else {
+            return Ok(None);
+        };
+        let user = users::table
+            .filter(users::id.eq(created_by))
+            .first::<User>(&mut ctx.get_db_conn().await)
+            .await
+            .optional()?;
+        Ok(user)
+    }
+
+    pub async fn delivery_counts(&self, ctx: &Context) -> FieldResult<EmailDeliveryCounts> {
+        use crate::db::schema::email_deliveries;
+        let counts = email_deliveries::table
+            .filter(email_deliveries::broadcast_id.eq(self.id))
+            .group_by(email_deliveries::status)
+            .select((email_deliveries::status, diesel::dsl::count_star()))
+            .load::<(EmailDeliveryStatus, i64)>(&mut ctx.get_db_conn().await)
+            .await?;
+        let mut result = EmailDeliveryCounts::default();
+        for (status, count) in counts {
+            match status {
+                EmailDeliveryStatus::Pending => result.pending = count as i32,
+                EmailDeliveryStatus::Sent => result.sent = count as i32,
+                EmailDeliveryStatus::Failed => result.failed = count as i32,
+            }
+        }
+        Ok(result)
+    }
+
+    /// Deliveries of the broadcast, optionally only those with the given status
+    pub async fn deliveries(
+        &self,
+        ctx: &Context,
+        status: Option<EmailDeliveryStatus>,
+        #[graphql(default = 100)] limit: i32,
+    ) -> FieldResult<Vec<EmailDelivery>> {
+        use crate::db::schema::email_deliveries;
+        let mut query = email_deliveries::table
+            .filter(email_deliveries::broadcast_id.eq(self.id))
+            .order(email_deliveries::id.asc())
+            .limit(limit.clamp(1, 1000) as i64)
+            .select(EmailDelivery::as_select())
+            .into_boxed();
+        if let Some(status) = status {
+            query = query.filter(email_deliveries::status.eq(status));
+        }
+        let deliveries = query
+            .load::<EmailDelivery>(&mut ctx.get_db_conn().await)
+            .await?;
+        Ok(deliveries)
+    }
+}
+
+#[graphql_object]
+impl EmailDelivery {
+    pub fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    pub async fn user(&self, ctx: &Context) -> FieldResult<User> {
+        use crate::db::schema::users;
+        let user = users::table
+            .filter(users::id.eq(self.user_id))
+            .first::<User>(&mut ctx.get_db_conn().await)
+            .await?;
+        Ok(user)
+    }
+
+    pub fn status(&self) -> EmailDeliveryStatus {
+        self.status
+    }
+
+    pub fn attempts(&self) -> i32 {
+        self.attempts
+    }
+
+    /// Error of the last failed attempt
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn sent_at(&self) -> Option<String> {
+        self.sent_at.map(|t| t.to_rfc3339())
+    }
+}
+
+/// Queues an email to every user in the audience, which the email worker sends in the background.
+pub async fn broadcast_email(
+    ctx: &Context,
+    subject: String,
+    body_md: String,
+    audience: EmailAudience,
+) -> FieldResult<EmailBroadcast> {
+    use crate::db::schema::{email_broadcasts, email_deliveries, teams, users};
+
+    ctx.require_role_min(UserRole::Admin)?;
+    let admin = ctx.require_authentication()?;
+    if !crate::email::is_configured() {
+        return Err(juniper::FieldError::new(
+            "Email is not configured",
+            juniper::Value::null(),
+        ));
+    }
+    let subject = subject.trim().to_string();
+    if subject.is_empty() || body_md.trim().is_empty() {
+        return Err(juniper::FieldError::new(
+            "Subject and body must not be empty",
+            juniper::Value::null(),
+        ));
+    }
+
+    let mut query = users::table
+        .left_join(teams::table)
+        .filter(users::is_active.eq(true))
+        .filter(users::approval_status.eq(ApprovalStatus::Approved))
+        .filter(users::email_verified_at.is_not_null())
+        .select(users::id)
+        .into_boxed();
+    if let Some(roles) = audience.roles {
+        query = query.filter(users::role.eq_any(roles));
+    }
+    if let Some(division) = audience.division {
+        query = query.filter(teams::division.eq(division));
+    }
+    if let Some(team_ids) = audience.team_ids {
+        let team_ids = team_ids
+            .iter()
+            .map(|id| uuid::Uuid::parse_str(id))
+            .collect::<Result<Vec<_>, _>>()?;
+        query = query.filter(users::team_id.eq_any(team_ids));
+    }
+
+    let conn = &mut ctx.get_db_conn().await;
+    let recipients = query.load::<uuid::Uuid>(conn).await?;
+    if recipients.is_empty() {
+        return Err(juniper::FieldError::new(
+            "No users match the audience",
+            juniper::Value::null(),
+        ));
+    }
+    let recipient_count = recipients.len();
+    let broadcast = conn
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                let broadcast = diesel::insert_into(email_broadcasts::table)
+                    .values(NewEmailBroadcast {
+                        subject,
+                        body_md,
+                        created_by: Some(admin.user_id),
+                    })
+                    .returning(EmailBroadcast::as_returning())
+                    .get_result(conn)
+                    .await?;
+                diesel::insert_into(email_deliveries::table)
+                    .values(
+                        recipients
+                            .into_iter()
+                            .map(|user_id| NewEmailDelivery {
+                                broadcast_id: broadcast.id,
+                                user_id,
+                            })
+                            .collect::<Vec<_>>(),
+                    )
+                    .execute(conn)
+                    .await?;
+                Ok(broadcast)
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    tracing::info!(
+        "Email broadcast {} to {} users queued by {}",
+        broadcast.id,
+        recipient_count,
+        admin.username
+    );
+    NEW_BROADCASTS.notify_one();
+    Ok(broadcast)
+}
+
+pub async fn get_email_broadcasts(ctx: &Context) -> FieldResult<Vec<EmailBroadcast>> {
+    use crate::db::schema::email_broadcasts;
+
+    ctx.require_role_min(UserRole::Admin)?;
+    let broadcasts = email_broadcasts::table
+        .order(email_broadcasts::created_at.desc())
+        .select(EmailBroadcast::as_select())
+        .load::<EmailBroadcast>(&mut ctx.get_db_conn().await)
+        .await?;
+    Ok(broadcasts)
+}
+
+/// Limits how many emails are sent to each provider in a sliding window,
+/// so large broadcasts don't get the sender blocked.
+struct ProviderRateLimiter {
+    limit: usize,
+    sent: HashMap<String, VecDeque<Instant>>,
+}
+
+impl ProviderRateLimiter {
+    fn new(limit: usize) -> Self {
+        Self {
+            limit: limit.max(1),
+            sent: HashMap::new(),
+        }
+    }
+
+    /// Records an email to the provider if it is below the limit,
+    /// otherwise returns how long to wait until the next one can be sent.
+    fn try_acquire(&mut self, provider: &str, now: Instant) -> Result<(), Duration> {
+        let sent = self.sent.entry(provider.to_string()).or_default();
+        while sent
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= RATE_LIMIT_WINDOW)
+        {
+            sent.pop_front();
+        }
+        if sent.len() >= self.limit {
+            let oldest = sent.front().copied().unwrap_or(now);
+            return Err(RATE_LIMIT_WINDOW - now.duration_since(oldest));
+        }
+        sent.push_back(now);
+        Ok(())
+    }
+}
+
+#[derive(QueryableByName)]
+struct ClaimedDelivery {
+    #[diesel(sql_type = Uuid)]
+    id: uuid::Uuid,
+    #[diesel(sql_type = Integer)]
+    attempts: i32,
+}
+
+/// Sends one batch of due deliveries, returning how many were claimed.
+async fn send_pending(
+    db_pool: &DbPool,
+    limiter: &mut ProviderRateLimiter,
+) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    use crate::db::schema::{email_broadcasts, email_deliveries, users};
+
+    let mut conn = db_pool.get().await?;
+    // SKIP LOCKED lets the workers of several replicas claim different deliveries
+    let claimed = diesel::sql_query(
+        "UPDATE email_deliveries SET next_attempt_at = NOW() + $1 * INTERVAL '1 second'
+        WHERE id IN (
+            SELECT id FROM email_deliveries
+            WHERE status = 'PENDING' AND next_attempt_at <= NOW()
+            ORDER BY next_attempt_at
+            LIMIT $2
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING id, attempts",
+    )
+    .bind::<BigInt, _>(CLAIM_TIMEOUT.as_secs() as i64)
+    .bind::<BigInt, _>(BATCH_SIZE)
+    .load::<ClaimedDelivery>(&mut conn)
+    .await?;
+    if claimed.is_empty() {
+        return Ok(0);
+    }
+
+    let attempts = claimed
+        .iter()
+        .map(|d| (d.id, d.attempts))
+        .collect::<HashMap<_, _>>();
+    let deliveries = email_deliveries::table
+        .inner_join(users::table)
+        .inner_join(email_broadcasts::table)
+        .filter(email_deliveries::id.eq_any(attempts.keys()))
+        .select((
+            email_deliveries::id,
+            users::email,
+            email_broadcasts::subject,
+            email_broadcasts::body_md,
+        ))
+        .load::<(uuid::Uuid, String, String, String)>(&mut conn)
+        .await?;
+
+    for (delivery_id, email, subject, body_md) in deliveries {
+        let provider = email
+            .rsplit_once('@')
+            .map(|(_, domain)| domain.to_lowercase())
+            .unwrap_or_default();
+        if let Err(wait) = limiter.try_acquire(&provider, Instant::now()) {
+            diesel::update(email_deliveries::table.filter(email_deliveries::id.eq(delivery_id)))
+                .set(email_deliveries::next_attempt_at.eq(chrono::Utc::now() + wait))
+                .execute(&mut conn)
+                .await?;
+            continue;
+        }
+
+        let attempt = attempts[&delivery_id] + 1;
+        match crate::email::send_email(&email, &subject, body_md).await {
+            Ok(()) => {
+                diesel::update(
+                    email_deliveries::table.filter(email_deliveries::id.eq(delivery_id)),
+                )
+                .set((
+                    email_deliveries::status.eq(EmailDeliveryStatus::Sent),
+                    email_deliveries::attempts.eq(attempt),
+                    email_deliveries::error.eq::<Option<String>>(None),
+                    email_deliveries::sent_at.eq(Some(chrono::Utc::now())),
+                ))
+                .execute(&mut conn)
+                .await?;
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to send broadcast email {} (attempt {}/{}): {}",
+                    delivery_id,
+                    attempt,
+                    MAX_ATTEMPTS,
+                    e
+                );
+                let status = if attempt >= MAX_ATTEMPTS {
+                    EmailDeliveryStatus::Failed
+                } else {
+                    EmailDeliveryStatus::Pending
+                };
+                let retry_delay = RETRY_BASE_DELAY * 2u32.pow(attempt.clamp(1, 16) as u32 - 1);
+                diesel::update(
+                    email_deliveries::table.filter(email_deliveries::id.eq(delivery_id)),
+                )
+                .set((
+                    email_deliveries::status.eq(status),
+                    email_deliveries::attempts.eq(attempt),
+                    email_deliveries::error.eq(Some(e.to_string())),
+                    email_deliveries::next_attempt_at.eq(chrono::Utc::now() + retry_delay),
+                ))
+                .execute(&mut conn)
+                .await?;
+            }
+        }
+    }
+    Ok(claimed.len())
+}
+
+/// Starts the background task that sends queued broadcast emails.
+pub fn start_email_worker(tasks: &mut JoinSet<()>, ctx: BaseContext) {
+    if !crate::email::is_configured() {
+        return;
+    }
+    let rate_limit = ctx
+        .config
+        .email_rate_limit_per_provider
+        .unwrap_or(DEFAULT_RATE_LIMIT);
+
+    let db_pool = ctx.db_pool;
+    tasks.spawn(async move {
+        let mut limiter = ProviderRateLimiter::new(rate_limit);
+        loop {
+            match send_pending(&db_pool, &mut limiter).await {
+                // There may be more due deliveries
+                Ok(claimed) if claimed as i64 == BATCH_SIZE => continue,
+                Ok(_) => {}
+                Err(e) => tracing::error!("Failed to send broadcast emails: {}", e),
+            }
+            tokio::select! {
+                _ = NEW_BROADCASTS.notified() => {}
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limit_per_provider() {
+        let mut limiter = ProviderRateLimiter::new(2);
+        let start = Instant::now();
+        assert!(limiter.try_acquire("example.org", start).is_ok());
+        assert!(limiter.try_acquire("example.org", start).is_ok());
+        assert_eq!(
+            limiter.try_acquire("example.org", start + Duration::from_secs(20)),
+            Err(Duration::from_secs(40))
+        );
+        // Other providers have their own limit
+        assert!(limiter.try_acquire("example.com", start).is_ok());
+        assert!(
+            limiter
+                .try_acquire("example.org", start + RATE_LIMIT_WINDOW)
+                .is_ok()
+        );
+    }
+}
@@ -1,4 +1,8 @@
-use juniper::GraphQLObject;
+use std::{collections::HashMap, time::Duration};
+
+use juniper::{GraphQLObject, graphql_value};
+
+use crate::{db::models::UserRole, graphql::handlers::name_policy::NamePolicy};
 
 #[derive(GraphQLObject, Debug, Clone)]
 pub struct CtfCategory {
@@ -15,6 +19,13 @@ pub struct CtfDifficulty {
     pub color: Option<String>,
 }
 
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct CtfDivision {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+}
+
 #[derive(GraphQLObject, Debug, Clone)]
 pub struct EventConfig {
     pub event_name: String,
@@ -22,13 +33,44 @@ pub struct EventConfig {
     pub rules_md: String,
     pub start_time: i32,
     pub end_time: i32,
+    /// Whether the platform becomes a read-only archive once the event ended
+    pub archive_after_end: bool,
     pub use_teams: bool,
     pub registration_start_time: Option<i32>,
     pub registration_end_time: Option<i32>,
+    /// Whether registering requires an invite code
+    pub invite_only: bool,
     pub max_team_size: Option<i32>,
     pub scoreboard_freeze_time: Option<i32>,
+    pub scoreboard_reveal_time: Option<i32>,
+    /// Seconds to wait between two flag submissions for the same challenge
+    pub submission_cooldown: Option<i32>,
     pub categories: Vec<CtfCategory>,
     pub difficulties: Vec<CtfDifficulty>,
+    /// Brackets teams compete in, each with its own scoreboard. Empty if there are none.
+    pub divisions: Vec<CtfDivision>,
+    #[graphql(ignore)]
+    pub front_page_md_translations: HashMap<String, String>,
+    #[graphql(ignore)]
+    pub rules_md_translations: HashMap<String, String>,
+    /// Not public, so players can't look up which words are blocked
+    #[graphql(ignore)]
+    pub name_policy: NamePolicy,
+}
+
+impl EventConfig {
+    /// Uses the texts in the given locale, if they are translated into it.
+    pub fn localized(mut self, locale: Option<&str>) -> Self {
+        self.front_page_md = crate::i18n::localize(
+            &self.front_page_md,
+            &self.front_page_md_translations,
+            locale,
+        )
+        .to_string();
+        self.rules_md =
+            crate::i18n::localize(&self.rules_md, &self.rules_md_translations, locale).to_string();
+        self
+    }
 }
 
 pub async fn get_event_config(
@@ -36,8 +78,47 @@ pub async fn get_event_config(
 ) -> juniper::FieldResult<EventConfig> {
     // This does not require authentication, it is considered public information.
     // TODO: Should we allow private events where this info is restricted?
+    fetch_event_config(&context.base).await
+}
 
-    let mut client = context.repo_client();
+/// Forgets the cached event config, e.g. after the repository was synced.
+pub async fn invalidate_event_config() {
+    use cached::Cached;
+    FETCH_EVENT_CONFIG.lock().await.cache_clear();
+}
+
+/// Returns an error unless the event is currently running.
+///
+/// Testers, authors and admins are exempt so they can test challenges before and after the event.
+pub async fn require_event_running(context: &crate::graphql::Context) -> juniper::FieldResult<()> {
+    if context.role().is_some_and(|r| r >= UserRole::Tester) {
+        return Ok(());
+    }
+    let event_config = get_event_config(context).await?;
+    let now = chrono::Utc::now().timestamp();
+    if now < event_config.start_time as i64 {
+        return Err(juniper::FieldError::new(
+            "The event has not started yet",
+            graphql_value!({ "code": "EVENT_NOT_STARTED" }),
+        ));
+    }
+    if now > event_config.end_time as i64 {
+        return Err(juniper::FieldError::new(
+            "The event has ended",
+            graphql_value!({ "code": "EVENT_ENDED" }),
+        ));
+    }
+    Ok(())
+}
+
+// The event config only changes when the repository is synced, so it doesn't need to be fetched for every request
+#[cached::proc_macro::cached(time = 60, key = "()", convert = "{ }", result = true)]
+pub(crate) async fn fetch_event_config(
+    base: &crate::graphql::BaseContext,
+) -> juniper::FieldResult<EventConfig> {
+    let mut client = crate::manager_api::repository_service_client::RepositoryServiceClient::new(
+        base.grpc_client.clone(),
+    );
 
     let request = tonic::Request::new(crate::manager_api::GetEventConfigurationRequest {});
 
@@ -49,13 +130,19 @@ pub async fn get_event_config(
         event_name: config.event_name,
         front_page_md: config.front_page_md,
         rules_md: config.rules_md,
+        front_page_md_translations: config.front_page_md_translations,
+        rules_md_translations: config.rules_md_translations,
         start_time: config.start_time as i32,
         end_time: config.end_time as i32,
+        archive_after_end: config.archive_after_end,
         use_teams: config.use_teams,
         registration_start_time: config.registration_start_time.map(|t| t as i32),
         registration_end_time: config.registration_end_time.map(|t| t as i32),
+        invite_only: config.invite_only,
         max_team_size: config.max_team_size.map(|s| s as i32),
         scoreboard_freeze_time: config.scoreboard_freeze_time.map(|t| t as i32),
+        scoreboard_reveal_time: config.scoreboard_reveal_time.map(|t| t as i32),
+        submission_cooldown: config.submission_cooldown.map(|s| s as i32),
         categories: config
             .categories
             .into_iter()
@@ -75,5 +162,22 @@ pub async fn get_event_config(
                 color: d.color,
             })
             .collect(),
+        divisions: config
+            .divisions
+            .into_iter()
+            .map(|(id, d)| CtfDivision {
+                id,
+                name: d.name,
+                description: d.description,
+            })
+            .collect(),
+        name_policy: config
+            .name_policy
+            .map(|p| NamePolicy {
+                reserved_names: p.reserved_names,
+                blocked_words: p.blocked_words,
+                block_confusables: p.block_confusables,
+            })
+            .unwrap_or_default(),
     })
 }
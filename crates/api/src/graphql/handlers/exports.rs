@@ -0,0 +1,327 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use futures_util::TryStreamExt;
+use http_body_util::{BodyExt, StreamBody, combinators::UnsyncBoxBody};
+use hyper::body::{Bytes, Frame};
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use crate::{db::models::UserRole, graphql::Context};
+
+type DbPool = diesel_async::pooled_connection::bb8::Pool<diesel_async::AsyncPgConnection>;
+type ExportError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExportKind {
+    Solves,
+    Users,
+    Teams,
+    InvalidSubmissions,
+}
+
+#[derive(Serialize)]
+struct SolveRow {
+    id: uuid::Uuid,
+    challenge_id: String,
+    user_id: uuid::Uuid,
+    username: String,
+    team_slug: Option<String>,
+    submitted_flag: String,
+    solved_at: String,
+}
+
+#[derive(Serialize)]
+struct UserRow {
+    id: uuid::Uuid,
+    username: String,
+    display_name: String,
+    email: String,
+    role: UserRole,
+    team_id: Option<uuid::Uuid>,
+    is_active: bool,
+    email_verified_at: Option<String>,
+    created_at: String,
+}
+
+#[derive(Serialize)]
+struct TeamRow {
+    id: uuid::Uuid,
+    name: String,
+    slug: String,
+    created_at: String,
+}
+
+#[derive(Serialize)]
+struct InvalidSubmissionRow {
+    id: uuid::Uuid,
+    challenge_id: String,
+    user_id: uuid::Uuid,
+    username: String,
+    team_slug: Option<String>,
+    submitted_flag: String,
+    submitted_at: String,
+}
+
+/// Encodes rows one by one and sends them to the response body as they are produced.
+struct ExportWriter {
+    format: ExportFormat,
+    tx: mpsc::Sender<Result<Bytes, ExportError>>,
+    rows: usize,
+}
+
+impl ExportWriter {
+    fn new(format: ExportFormat, tx: mpsc::Sender<Result<Bytes, ExportError>>) -> Self {
+        Self {
+            format,
+            tx,
+            rows: 0,
+        }
+    }
+
+    async fn write<T: Serialize>(&mut self, row: &T) -> Result<(), ExportError> {
+        let chunk = match self.format {
+            ExportFormat::Csv => {
+                // The header is derived from the field names of the first row
+                let mut csv = csv::WriterBuilder::new()
+                    .has_headers(self.rows == 0)
+                    .from_writer(vec![]);
+                csv.serialize(row)?;
+                csv.into_inner()?
+            }
+            ExportFormat::Json => {
+                let mut chunk = if self.rows == 0 {
+                    b"[".to_vec()
+                } else {
+                    b",".to_vec()
+                };
+                serde_json::to_writer(&mut chunk, row)?;
+                chunk
+            }
+        };
+        self.rows += 1;
+        self.tx.send(Ok(Bytes::from(chunk))).await?;
+        Ok(())
+    }
+
+    async fn finish(self) -> Result<(), ExportError> {
+        if self.format == ExportFormat::Json {
+            let end: &'static [u8] = if self.rows == 0 { b"[]" } else { b"]" };
+            self.tx.send(Ok(Bytes::from_static(end))).await?;
+        }
+        Ok(())
+    }
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339()
+}
+
+async fn export_solves(db_pool: DbPool, mut writer: ExportWriter) -> Result<(), ExportError> {
+    use crate::db::schema::{solves, teams, users};
+
+    let mut conn = db_pool.get().await?;
+    let mut rows = solves::table
+        .inner_join(users::table.left_join(teams::table))
+        .select((
+            solves::id,
+            solves::challenge_id,
+            users::id,
+            users::username,
+            teams::slug.nullable(),
+            solves::submitted_flag,
+            solves::solved_at,
+        ))
+        .order(solves::solved_at.asc())
+        .load_stream::<(
+            uuid::Uuid,
+            String,
+            uuid::Uuid,
+            String,
+            Option<String>,
+            String,
+            DateTime<Utc>,
+        )>(&mut conn)
+        .await?;
+    while let Some((id, challenge_id, user_id, username, team_slug, submitted_flag, solved_at)) =
+        rows.try_next().await?
+    {
+        writer
+            .write(&SolveRow {
+                id,
+                challenge_id,
+                user_id,
+                username,
+                team_slug,
+                submitted_flag,
+                solved_at: format_time(solved_at),
+            })
+            .await?;
+    }
+    writer.finish().await
+}
+
+async fn export_users(db_pool: DbPool, mut writer: ExportWriter) -> Result<(), ExportError> {
+    use crate::db::models::User;
+    use crate::db::schema::users;
+
+    let mut conn = db_pool.get().await?;
+    let mut rows = users::table
+        .select(User::as_select())
+        .order(users::created_at.asc())
+        .load_stream::<User>(&mut conn)
+        .await?;
+    while let Some(user) = rows.try_next().await? {
+        writer
+            .write(&UserRow {
+                id: user.id,
+                username: user.username,
+                display_name: user.display_name,
+                email: user.email,
+                role: user.role,
+                team_id: user.team_id,
+                is_active: user.is_active,
+                email_verified_at: user.email_verified_at.map(format_time),
+                created_at: format_time(user.created_at),
+            })
+            .await?;
+    }
+    writer.finish().await
+}
+
+async fn export_teams(db_pool: DbPool, mut writer: ExportWriter) -> Result<(), ExportError> {
+    use crate::db::models::Team;
+    use crate::db::schema::teams;
+
+    let mut conn = db_pool.get().await?;
+    let mut rows = teams::table
+        .select(Team::as_select())
+        .order(teams::created_at.asc())
+        .load_stream::<Team>(&mut conn)
+        .await?;
+    while let Some(team) = rows.try_next().await? {
+        writer
+            .write(&TeamRow {
+                id: team.id,
+                name: team.name,
+                slug: team.slug,
+                created_at: format_time(team.created_at),
+            })
+            .await?;
+    }
+    writer.finish().await
+}
+
+async fn export_invalid_submissions(
+    db_pool: DbPool,
+    mut writer: ExportWriter,
+) -> Result<(), ExportError> {
+    use crate::db::schema::{invalid_submissions, teams, users};
+
+    let mut conn = db_pool.get().await?;
+    let mut rows = invalid_submissions::table
+        .inner_join(users::table.left_join(teams::table))
+        .select((
+            invalid_submissions::id,
+            invalid_submissions::challenge_id,
+            users::id,
+            users::username,
+            teams::slug.nullable(),
+            invalid_submissions::submitted_flag,
+            invalid_submissions::submitted_at,
+        ))
+        .order(invalid_submissions::submitted_at.asc())
+        .load_stream::<(
+            uuid::Uuid,
+            String,
+            uuid::Uuid,
+            String,
+            Option<String>,
+            String,
+            DateTime<Utc>,
+        )>(&mut conn)
+        .await?;
+    while let Some((id, challenge_id, user_id, username, team_slug, submitted_flag, submitted_at)) =
+        rows.try_next().await?
+    {
+        writer
+            .write(&InvalidSubmissionRow {
+                id,
+                challenge_id,
+                user_id,
+                username,
+                team_slug,
+                submitted_flag,
+                submitted_at: format_time(submitted_at),
+            })
+            .await?;
+    }
+    writer.finish().await
+}
+
+/// Streams an admin export, `export` being e.g. "solves.csv" or "users.json".
+///
+/// Returns the content type and the response body. The body fails if the export does, so the
+/// client notices the response is incomplete.
+pub async fn export_data(
+    ctx: Context,
+    export: &str,
+) -> Result<(&'static str, UnsyncBoxBody<Bytes, ExportError>), (u16, String)> {
+    match ctx.role() {
+        None => return Err((401, "Authentication required".to_string())),
+        Some(role) if role < UserRole::Admin => {
+            return Err((403, "Insufficient permissions".to_string()));
+        }
+        _ => {}
+    }
+
+    let (kind, format) = export
+        .rsplit_once('.')
+        .ok_or((400, "Missing export format".to_string()))?;
+    let kind = match kind {
+        "solves" => ExportKind::Solves,
+        "users" => ExportKind::Users,
+        "teams" => ExportKind::Teams,
+        "invalid-submissions" => ExportKind::InvalidSubmissions,
+        _ => return Err((404, format!("Unknown export: {}", kind))),
+    };
+    let (format, content_type) = match format {
+        "csv" => (ExportFormat::Csv, "text/csv"),
+        "json" => (ExportFormat::Json, "application/json"),
+        _ => return Err((400, format!("Unsupported export format: {}", format))),
+    };
+
+    let (tx, rx) = mpsc::channel(64);
+    let error_tx = tx.clone();
+    let writer = ExportWriter::new(format, tx);
+    let db_pool = ctx.base.db_pool.clone();
+    tokio::spawn(async move {
+        let result = match kind {
+            ExportKind::Solves => export_solves(db_pool, writer).await,
+            ExportKind::Users => export_users(db_pool, writer).await,
+            ExportKind::Teams => export_teams(db_pool, writer).await,
+            ExportKind::InvalidSubmissions => export_invalid_submissions(db_pool, writer).await,
+        };
+        if let Err(e) = result {
+            tracing::error!("Failed to export {:?}: {}", kind, e);
+            let _ = error_tx.send(Err(e)).await;
+        }
+    });
+
+    let stream = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    })
+    .map_ok(Frame::data);
+
+    Ok((content_type, StreamBody::new(stream).boxed_unsync()))
+}
@@ -0,0 +1,115 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use juniper::{FieldResult, GraphQLObject, graphql_object};
+
+use crate::{
+    db::models::{Job, JobStatus, UserRole},
+    graphql::Context,
+};
+
+#[derive(GraphQLObject, Debug, Clone, Default)]
+pub struct JobCounts {
+    pub pending: i32,
+    pub running: i32,
+    pub succeeded: i32,
+    pub failed: i32,
+}
+
+#[graphql_object]
+impl Job {
+    pub fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// JSON the job is run with
+    pub fn payload(&self) -> &str {
+        &self.payload
+    }
+
+    pub fn status(&self) -> JobStatus {
+        self.status
+    }
+
+    pub fn attempts(&self) -> i32 {
+        self.attempts
+    }
+
+    pub fn max_attempts(&self) -> i32 {
+        self.max_attempts
+    }
+
+    /// Error of the last failed attempt
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// When the job runs next if it is pending
+    pub fn run_at(&self) -> String {
+        self.run_at.to_rfc3339()
+    }
+
+    pub fn created_at(&self) -> String {
+        self.created_at.to_rfc3339()
+    }
+
+    pub fn finished_at(&self) -> Option<String> {
+        self.finished_at.map(|t| t.to_rfc3339())
+    }
+}
+
+/// Background jobs, newest first, optionally only those with the given status or kind (admin only).
+pub async fn get_jobs(
+    ctx: &Context,
+    status: Option<JobStatus>,
+    kind: Option<String>,
+    offset: i32,
+    limit: i32,
+) -> FieldResult<Vec<Job>> {
+    use crate::db::schema::jobs;
+
+    ctx.require_role_min(UserRole::Admin)?;
+    let mut query = jobs::table
+        .order(jobs::created_at.desc())
+        .select(Job::as_select())
+        .offset(offset.max(0) as i64)
+        .limit(limit.clamp(1, 500) as i64)
+        .into_boxed();
+    if let Some(status) = status {
+        query = query.filter(jobs::status.eq(status));
+    }
+    if let Some(kind) = kind {
+        query = query.filter(jobs::kind.eq(kind));
+    }
+    let jobs = query.load::<Job>(&mut ctx.get_db_conn().await).await?;
+    Ok(jobs)
+}
+
+/// Number of background jobs in each status (admin only).
+pub async fn get_job_counts(ctx: &Context) -> FieldResult<JobCounts> {
+    use crate::db::schema::jobs;
+
+    ctx.require_role_min(UserRole::Admin)?;
+    let counts = jobs::table
+        .group_by(jobs::status)
+        .select((jobs::status, diesel::dsl::count_star()))
+        .load::<(JobStatus, i64)>(&mut ctx.get_db_conn().await)
+        .await?;
+    let mut result = JobCounts::default();
+    for (status, count) in counts {
+        match status {
+            JobStatus::Pending => result.pending = count as i32,
+            JobStatus::Running => result.running = count as i32,
+            JobStatus::Succeeded => result.succeeded = count as i32,
+            JobStatus::Failed => result.failed = count as i32,
+        }
+    }
+    Ok(result)
+}
@@ -0,0 +1,211 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::time::Duration;
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use juniper::{
+    DefaultScalarValue, Definition, FieldResult, OperationType, SchemaType, Selection,
+    graphql_object, parser::parse_document_source,
+};
+use serde_json::{Value, json};
+
+use crate::{
+    db::models::{MaintenanceMode, UserRole},
+    graphql::{BaseContext, Context},
+};
+
+/// Mutations players can still run during maintenance, so staff can sign in to end it
+const ALLOWED_MUTATIONS: &[&str] = &["login", "refreshSession", "endSession"];
+
+#[graphql_object]
+impl MaintenanceMode {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Message to show to players while maintenance mode is enabled
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    pub fn updated_at(&self) -> String {
+        self.updated_at.to_rfc3339()
+    }
+}
+
+// Every request checks this, but it doesn't need to be exact, so replicas pick up changes within a few seconds
+#[cached::proc_macro::cached(time = 5, key = "()", convert = "{ }", result = true)]
+async fn fetch_maintenance_mode(base: &BaseContext) -> FieldResult<MaintenanceMode> {
+    use crate::db::schema::maintenance_mode::dsl::*;
+    let state = maintenance_mode
+        .select(MaintenanceMode::as_select())
+        .first::<MaintenanceMode>(&mut base.db_pool.get().await?)
+        .await?;
+    Ok(state)
+}
+
+pub async fn get_maintenance_mode(ctx: &Context) -> FieldResult<MaintenanceMode> {
+    // Public, so clients can show a banner
+    fetch_maintenance_mode(&ctx.base).await
+}
+
+pub async fn set_maintenance_mode(
+    ctx: &Context,
+    new_enabled: bool,
+    new_message: Option<String>,
+) -> FieldResult<MaintenanceMode> {
+    ctx.require_role_min(UserRole::Admin)?;
+    let admin = ctx.require_authentication()?;
+
+    use crate::db::schema::maintenance_mode;
+    let state = diesel::update(maintenance_mode::table)
+        .set((
+            maintenance_mode::enabled.eq(new_enabled),
+            maintenance_mode::message.eq(new_message),
+            maintenance_mode::updated_at.eq(chrono::Utc::now()),
+        ))
+        .returning(MaintenanceMode::as_returning())
+        .get_result(&mut ctx.get_db_conn().await)
+        .await?;
+
+    use cached::Cached;
+    FETCH_MAINTENANCE_MODE.lock().await.cache_clear();
+    tracing::info!(
+        "Maintenance mode {} by {}",
+        if new_enabled { "enabled" } else { "disabled" },
+        admin.username
+    );
+    Ok(state)
+}
+
+/// Whether a single GraphQL request runs a mutation that players can't use during maintenance.
+fn is_blocked_operation(
+    schema: &SchemaType<DefaultScalarValue>,
+    query: &str,
+    operation_name: Option<&str>,
+) -> bool {
+    // Invalid documents are rejected by juniper anyway
+    let Ok(document) = parse_document_source(query, schema) else {
+        return false;
+    };
+    let mut operations = document.iter().filter_map(|definition| match definition {
+        Definition::Operation(operation) => Some(&operation.item),
+        Definition::Fragment(_) => None,
+    });
+    let operation = match operation_name {
+        Some(operation_name) => operations
+            .find(|operation| operation.name.as_ref().map(|n| n.item) == Some(operation_name)),
+        None => operations.next(),
+    };
+    operation.is_some_and(|operation| {
+        operation.operation_type == OperationType::Mutation
+            && operation
+                .selection_set
+                .iter()
+                .any(|selection| match selection {
+                    Selection::Field(field) => !ALLOWED_MUTATIONS.contains(&field.item.name.item),
+                    // Fragments on the mutation root are unusual enough to just block them
+                    Selection::FragmentSpread(_) | Selection::InlineFragment(_) => true,
+                })
+    })
+}
+
+fn is_blocked_request(schema: &SchemaType<DefaultScalarValue>, request: &Value) -> bool {
+    let Some(query) = request.get("query").and_then(|query| query.as_str()) else {
+        return false;
+    };
+    let operation_name = request
+        .get("operationName")
+        .and_then(|operation_name| operation_name.as_str());
+    is_blocked_operation(schema, query, operation_name)
+}
+
+/// Checks a GraphQL request (GET query string or POST body, with persisted queries already
+/// resolved) against maintenance mode.
+///
+/// Returns the status code and body of the error response if it must not be executed.
+pub async fn check_maintenance(
+    ctx: &Context,
+    schema: &SchemaType<DefaultScalarValue>,
+    query_string: Option<&str>,
+    body: &[u8],
+) -> Option<(u16, String)> {
+    if ctx.role().is_some_and(|r| r >= UserRole::Author) {
+        return None;
+    }
+    let state = match fetch_maintenance_mode(&ctx.base).await {
+        Ok(state) => state,
+        Err(e) => {
+            tracing::error!("Failed to load maintenance mode: {:?}", e.message());
+            return None;
+        }
+    };
+    if !state.enabled {
+        return None;
+    }
+
+    let blocked = if body.is_empty() {
+        let params = form_urlencoded::parse(query_string.unwrap_or_default().as_bytes())
+            .map(|(key, value)| (key.into_owned(), Value::String(value.into_owned())))
+            .collect::<serde_json::Map<_, _>>();
+        is_blocked_request(schema, &Value::Object(params))
+    } else {
+        match serde_json::from_slice::<Value>(body) {
+            Ok(Value::Array(requests)) => requests
+                .iter()
+                .any(|request| is_blocked_request(schema, request)),
+            Ok(request) => is_blocked_request(schema, &request),
+            Err(_) => is_blocked_operation(schema, &String::from_utf8_lossy(body), None),
+        }
+    };
+    blocked.then(|| {
+        let message = state
+            .message
+            .unwrap_or_else(|| "The platform is currently under maintenance".to_string());
+        (
+            503,
+            json!({
+                "errors": [{ "message": message, "extensions": { "code": "MAINTENANCE" } }]
+            })
+            .to_string(),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphql::{Mutation, Query, Schema, Subscription};
+
+    #[test]
+    fn test_only_mutations_are_blocked() {
+        let schema = Schema::new(Query, Mutation, Subscription);
+        let schema = &schema.schema;
+
+        assert!(!is_blocked_operation(
+            schema,
+            "{ eventConfig { eventName } }",
+            None
+        ));
+        assert!(is_blocked_operation(
+            schema,
+            r#"mutation { submitFlag(challengeId: "a", flag: "b") }"#,
+            None
+        ));
+        assert!(!is_blocked_operation(
+            schema,
+            r#"mutation Login { login(username: "a", password: "b") { accessToken } }"#,
+            None
+        ));
+        // The operation that is actually executed counts
+        let document = r#"
+            query Q { eventConfig { eventName } }
+            mutation M { leaveTeam }
+        "#;
+        assert!(!is_blocked_operation(schema, document, Some("Q")));
+        assert!(is_blocked_operation(schema, document, Some("M")));
+    }
+}
@@ -2,10 +2,23 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
+pub mod archive;
+pub mod audit_log;
+pub mod avatars;
 pub mod challenges;
+pub mod email_broadcasts;
 pub mod event;
+pub mod exports;
+pub mod jobs;
+pub mod maintenance;
+pub mod name_policy;
+pub mod notifications;
 mod owned_resource;
 pub mod repo;
+pub mod scoreboard;
 pub mod sessions;
+pub mod stats;
 pub mod teams;
+pub mod tickets;
 pub mod users;
+pub mod webhooks;
@@ -0,0 +1,413 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use juniper::{FieldResult, graphql_value};
+
+use crate::graphql::Context;
+
+/// Names nobody can register, compared after folding
+const BUILTIN_RESERVED_NAMES: &[&str] = &[
+    "admin",
+    "administrator",
+    "root",
+    "system",
+    "staff",
+    "support",
+    "moderator",
+    "organizer",
+    "plfanzen",
+    "deleted user",
+    "anonymous",
+    // The actor the archive is browsed as without signing in
+    "visitor",
+    "null",
+    "undefined",
+];
+
+/// Unique constraints and indexes on names, with the kind of name they are for
+const NAME_CONSTRAINTS: &[(&str, NameKind)] = &[
+    ("users_username_key", NameKind::Username),
+    ("idx_users_username_lower", NameKind::Username),
+    ("idx_teams_name_lower", NameKind::TeamName),
+    ("teams_slug_key", NameKind::TeamSlug),
+    ("idx_teams_slug_lower", NameKind::TeamSlug),
+];
+
+/// Characters of other scripts that look like Latin letters, with the letter they look like
+const CONFUSABLES: &[(char, char)] = &[
+    ('а', 'a'),
+    ('в', 'b'),
+    ('е', 'e'),
+    ('ё', 'e'),
+    ('к', 'k'),
+    ('м', 'm'),
+    ('н', 'h'),
+    ('о', 'o'),
+    ('р', 'p'),
+    ('с', 'c'),
+    ('т', 't'),
+    ('у', 'y'),
+    ('х', 'x'),
+    ('і', 'i'),
+    ('ї', 'i'),
+    ('ј', 'j'),
+    ('ѕ', 's'),
+    ('ԁ', 'd'),
+    ('ԛ', 'q'),
+    ('ԝ', 'w'),
+    ('α', 'a'),
+    ('β', 'b'),
+    ('ε', 'e'),
+    ('ι', 'i'),
+    ('κ', 'k'),
+    ('ν', 'v'),
+    ('ο', 'o'),
+    ('ρ', 'p'),
+    ('τ', 't'),
+    ('υ', 'u'),
+    ('χ', 'x'),
+];
+
+/// Digits and symbols used in place of letters to get around filters
+const LEETSPEAK: &[(char, char)] = &[
+    ('0', 'o'),
+    ('1', 'i'),
+    ('3', 'e'),
+    ('4', 'a'),
+    ('5', 's'),
+    ('7', 't'),
+    ('@', 'a'),
+    ('$', 's'),
+];
+
+/// Kinds of names, each with its own length and charset rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameKind {
+    Username,
+    DisplayName,
+    TeamName,
+    TeamSlug,
+}
+
+impl NameKind {
+    fn label(self) -> &'static str {
+        match self {
+            NameKind::Username => "Username",
+            NameKind::DisplayName => "Display name",
+            NameKind::TeamName => "Team name",
+            NameKind::TeamSlug => "Team slug",
+        }
+    }
+
+    /// Minimum and maximum length in characters
+    fn length(self) -> (usize, usize) {
+        match self {
+            NameKind::Username => (3, 32),
+            NameKind::DisplayName | NameKind::TeamName => (1, 64),
+            NameKind::TeamSlug => (2, 32),
+        }
+    }
+
+    /// Usernames and slugs are part of the actor slug sent to the manager, so they are limited
+    /// to characters that are safe there
+    fn is_identifier(self) -> bool {
+        matches!(self, NameKind::Username | NameKind::TeamSlug)
+    }
+}
+
+/// Rules for names from the event config, on top of the built-in ones
+#[derive(Debug, Clone, Default)]
+pub struct NamePolicy {
+    pub reserved_names: Vec<String>,
+    pub blocked_words: Vec<String>,
+    /// Whether names mixing Latin letters with lookalikes from other scripts are rejected
+    pub block_confusables: bool,
+}
+
+/// Lowercases a name, maps lookalike characters to the letters they look like and drops
+/// separators, so "Ad_min" and "аdmin" (with a Cyrillic a) both become "admin".
+fn fold(name: &str) -> String {
+    name.chars()
+        .flat_map(char::to_lowercase)
+        .filter(|c| !c.is_whitespace() && !matches!(c, '_' | '-' | '.'))
+        .map(|c| {
+            CONFUSABLES
+                .iter()
+                .chain(LEETSPEAK)
+                .find(|(from, _)| *from == c)
+                .map_or(c, |(_, to)| *to)
+        })
+        .collect()
+}
+
+/// Zero-width and other invisible characters that make different names look the same
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}'
+            | '\u{034F}'
+            | '\u{200B}'..='\u{200F}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{FEFF}'
+    )
+}
+
+impl NamePolicy {
+    /// Checks a name against the rules for its kind, returning it without surrounding whitespace.
+    ///
+    /// Reserved names are only allowed for staff, e.g. so the first admin can be called admin.
+    pub fn check(
+        &self,
+        kind: NameKind,
+        name: &str,
+        allow_reserved: bool,
+    ) -> Result<String, String> {
+        let name = name.trim();
+        let (min, max) = kind.length();
+        let length = name.chars().count();
+        if length < min || length > max {
+            return Err(format!(
+                "{} must be between {} and {} characters long",
+                kind.label(),
+                min,
+                max
+            ));
+        }
+        if kind.is_identifier() {
+            if !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+            {
+                return Err(format!(
+                    "{} may only contain letters, digits, underscores and dashes",
+                    kind.label()
+                ));
+            }
+            if !name.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+                return Err(format!(
+                    "{} must start with a letter or digit",
+                    kind.label()
+                ));
+            }
+        } else if name.chars().any(|c| c.is_control() || is_invisible(c)) {
+            return Err(format!(
+                "{} must not contain control or invisible characters",
+                kind.label()
+            ));
+        }
+        if self.block_confusables
+            && name.chars().any(|c| c.is_ascii_alphabetic())
+            && name
+                .chars()
+                .flat_map(char::to_lowercase)
+                .any(|c| CONFUSABLES.iter().any(|(from, _)| *from == c))
+        {
+            return Err(format!(
+                "{} must not mix Latin letters with similar looking characters of other scripts",
+                kind.label()
+            ));
+        }
+
+        let folded = fold(name);
+        if !allow_reserved
+            && BUILTIN_RESERVED_NAMES
+                .iter()
+                .copied()
+                .chain(self.reserved_names.iter().map(String::as_str))
+                .any(|reserved| fold(reserved) == folded)
+        {
+            return Err(format!("{} is reserved", kind.label()));
+        }
+        if self
+            .blocked_words
+            .iter()
+            .map(|word| fold(word))
+            .any(|word| !word.is_empty() && folded.contains(&word))
+        {
+            return Err(format!(
+                "{} contains a word that is not allowed",
+                kind.label()
+            ));
+        }
+        Ok(name.to_string())
+    }
+}
+
+/// Checks a name against the built-in rules and those of the event config, and that no other
+/// user or team has it already, ignoring case.
+///
+/// Display names don't have to be unique. Returns the name without surrounding whitespace.
+pub async fn validate_name(
+    ctx: &Context,
+    kind: NameKind,
+    name: &str,
+    allow_reserved: bool,
+) -> FieldResult<String> {
+    use crate::db::schema::{teams, users};
+
+    // The event config is missing while the first admin registers, who is bound by the built-in rules only
+    let policy = crate::graphql::handlers::event::get_event_config(ctx)
+        .await
+        .map(|config| config.name_policy)
+        .unwrap_or_default();
+    let name = policy
+        .check(kind, name, allow_reserved)
+        .map_err(|e| juniper::FieldError::new(e, graphql_value!({ "code": "INVALID_NAME" })))?;
+
+    let lowercase = name.to_lowercase();
+    let conn = &mut ctx.get_db_conn().await;
+    let taken = match kind {
+        NameKind::Username => {
+            diesel::select(diesel::dsl::exists(
+                users::table.filter(
+                    diesel::dsl::sql::<diesel::sql_types::Bool>("LOWER(username) = ")
+                        .bind::<diesel::sql_types::Text, _>(&lowercase),
+                ),
+            ))
+            .get_result::<bool>(conn)
+            .await?
+        }
+        NameKind::TeamName => {
+            diesel::select(diesel::dsl::exists(
+                teams::table.filter(
+                    diesel::dsl::sql::<diesel::sql_types::Bool>("LOWER(name) = ")
+                        .bind::<diesel::sql_types::Text, _>(&lowercase),
+                ),
+            ))
+            .get_result::<bool>(conn)
+            .await?
+        }
+        NameKind::TeamSlug => {
+            diesel::select(diesel::dsl::exists(
+                teams::table.filter(
+                    diesel::dsl::sql::<diesel::sql_types::Bool>("LOWER(slug) = ")
+                        .bind::<diesel::sql_types::Text, _>(&lowercase),
+                ),
+            ))
+            .get_result::<bool>(conn)
+            .await?
+        }
+        NameKind::DisplayName => false,
+    };
+    if taken {
+        return Err(name_taken(kind));
+    }
+    Ok(name)
+}
+
+fn name_taken(kind: NameKind) -> juniper::FieldError {
+    juniper::FieldError::new(
+        format!("{} is already taken", kind.label()),
+        graphql_value!({ "code": "NAME_TAKEN" }),
+    )
+}
+
+/// Converts an error of inserting a name, returning the error of validate_name if someone else
+/// took the name after it was validated.
+pub fn map_name_taken(e: diesel::result::Error) -> juniper::FieldError {
+    if let diesel::result::Error::DatabaseError(
+        diesel::result::DatabaseErrorKind::UniqueViolation,
+        info,
+    ) = &e
+        && let Some((_, kind)) = NAME_CONSTRAINTS
+            .iter()
+            .find(|(constraint, _)| info.constraint_name() == Some(*constraint))
+    {
+        return name_taken(*kind);
+    }
+    e.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_length_and_charset() {
+        let policy = NamePolicy::default();
+        assert_eq!(
+            policy.check(NameKind::Username, " alice_1 ", false),
+            Ok("alice_1".to_string())
+        );
+        assert!(policy.check(NameKind::Username, "al", false).is_err());
+        assert!(
+            policy
+                .check(NameKind::Username, "alice bob", false)
+                .is_err()
+        );
+        assert!(policy.check(NameKind::Username, "-alice", false).is_err());
+        assert!(policy.check(NameKind::TeamSlug, "the-team", false).is_ok());
+        assert!(
+            policy
+                .check(NameKind::TeamName, "Die Plfanzen 🌱", false)
+                .is_ok()
+        );
+        assert!(
+            policy
+                .check(NameKind::TeamName, "Team\u{200B}", false)
+                .is_err()
+        );
+        assert!(policy.check(NameKind::DisplayName, "   ", false).is_err());
+    }
+
+    #[test]
+    fn test_reserved_names_are_folded() {
+        let policy = NamePolicy {
+            reserved_names: vec!["Orga Team".to_string()],
+            ..Default::default()
+        };
+        assert!(policy.check(NameKind::Username, "Ad_min", false).is_err());
+        assert!(policy.check(NameKind::TeamName, "аdmin", false).is_err());
+        assert!(policy.check(NameKind::Username, "r00t", false).is_err());
+        assert!(policy.check(NameKind::Username, "Visitor", false).is_err());
+        assert!(
+            policy
+                .check(NameKind::TeamName, "orga-team", false)
+                .is_err()
+        );
+        assert!(policy.check(NameKind::Username, "admins", false).is_ok());
+        assert!(policy.check(NameKind::Username, "admin", true).is_ok());
+    }
+
+    #[test]
+    fn test_blocked_words() {
+        let policy = NamePolicy {
+            blocked_words: vec!["badword".to_string()],
+            ..Default::default()
+        };
+        assert!(
+            policy
+                .check(NameKind::TeamName, "The B4dW0rd Team", false)
+                .is_err()
+        );
+        assert!(
+            policy
+                .check(NameKind::TeamName, "The Good Team", false)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_confusables() {
+        let policy = NamePolicy {
+            block_confusables: true,
+            ..Default::default()
+        };
+        // Cyrillic a among Latin letters
+        assert!(
+            policy
+                .check(NameKind::TeamName, "pаypаl hackers", false)
+                .is_err()
+        );
+        assert!(policy.check(NameKind::TeamName, "Плфанзен", false).is_ok());
+        assert!(
+            NamePolicy::default()
+                .check(NameKind::TeamName, "pаypаl hackers", false)
+                .is_ok()
+        );
+    }
+}
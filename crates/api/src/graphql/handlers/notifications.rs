@@ -0,0 +1,326 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::{collections::VecDeque, pin::Pin, sync::LazyLock, time::Duration};
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use futures_util::Stream;
+use juniper::{FieldResult, GraphQLObject};
+
+use crate::{
+    db::models::{NewNotification, NewNotificationRead, Notification, NotificationKind, UserRole},
+    graphql::Context,
+};
+
+type DbPool = diesel_async::pooled_connection::bb8::Pool<diesel_async::AsyncPgConnection>;
+pub type NotificationStream = Pin<Box<dyn Stream<Item = FieldResult<UserNotification>> + Send>>;
+
+/// How often subscriptions check for notifications created by other api replicas
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Wakes up the subscriptions of this replica when notifications are created
+static NEW_NOTIFICATIONS: LazyLock<tokio::sync::broadcast::Sender<()>> =
+    LazyLock::new(|| tokio::sync::broadcast::channel(16).0);
+
+/// A notification as seen by the current user
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct UserNotification {
+    pub id: String,
+    pub kind: NotificationKind,
+    pub title: String,
+    pub body: String,
+    /// The challenge the notification is about, if any
+    pub challenge_id: Option<String>,
+    pub created_at: String,
+    pub read: bool,
+}
+
+impl UserNotification {
+    fn new(notification: Notification, read_at: Option<DateTime<Utc>>) -> Self {
+        Self {
+            id: notification.id.to_string(),
+            kind: notification.kind,
+            title: notification.title,
+            body: notification.body,
+            challenge_id: notification.challenge_id,
+            created_at: notification.created_at.to_rfc3339(),
+            read: read_at.is_some(),
+        }
+    }
+}
+
+/// Stores notifications and wakes up subscriptions.
+pub(crate) async fn create_notifications(
+    db_pool: &DbPool,
+    new_notifications: Vec<NewNotification>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use crate::db::schema::notifications;
+
+    if new_notifications.is_empty() {
+        return Ok(());
+    }
+    diesel::insert_into(notifications::table)
+        .values(&new_notifications)
+        .execute(&mut db_pool.get().await?)
+        .await?;
+    // Nobody might be subscribed, which is fine
+    let _ = NEW_NOTIFICATIONS.send(());
+    Ok(())
+}
+
+/// Stores notifications in the background.
+pub(crate) fn create_notifications_in_background(
+    db_pool: &DbPool,
+    new_notifications: Vec<NewNotification>,
+) {
+    let db_pool = db_pool.clone();
+    tokio::spawn(async move {
+        if let Err(e) = create_notifications(&db_pool, new_notifications).await {
+            tracing::error!("Failed to create notifications: {}", e);
+        }
+    });
+}
+
+/// Loads the notifications of a user, newest first.
+///
+/// With `after`, the (created_at, id) of the last notification seen, only the ones following it
+/// are loaded, oldest first. Comparing the ID too keeps notifications created at the same time
+/// from being skipped when paging.
+async fn load_user_notifications(
+    db_pool: &DbPool,
+    uid: uuid::Uuid,
+    unread_only: bool,
+    after: Option<(DateTime<Utc>, uuid::Uuid)>,
+    limit: i64,
+) -> FieldResult<Vec<(Notification, Option<DateTime<Utc>>)>> {
+    use crate::db::schema::{notification_reads, notifications};
+
+    let mut query = notifications::table
+        .left_join(
+            notification_reads::table.on(notification_reads::notification_id
+                .eq(notifications::id)
+                .and(notification_reads::user_id.eq(uid))),
+        )
+        .filter(
+            notifications::user_id
+                .eq(uid)
+                .or(notifications::user_id.is_null()),
+        )
+        .filter(notifications::created_at.le(diesel::dsl::now))
+        .select((
+            Notification::as_select(),
+            notification_reads::read_at.nullable(),
+        ))
+        .limit(limit)
+        .into_boxed();
+    if unread_only {
+        query = query.filter(notification_reads::read_at.is_null());
+    }
+    query = match after {
+        Some((created_at, id)) => query
+            .filter(
+                notifications::created_at
+                    .gt(created_at)
+                    .or(notifications::created_at
+                        .eq(created_at)
+                        .and(notifications::id.gt(id))),
+            )
+            .order((notifications::created_at.asc(), notifications::id.asc())),
+        None => query.order(notifications::created_at.desc()),
+    };
+    let rows = query
+        .load::<(Notification, Option<DateTime<Utc>>)>(&mut db_pool.get().await?)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn get_notifications(
+    ctx: &Context,
+    unread_only: bool,
+    limit: i32,
+) -> FieldResult<Vec<UserNotification>> {
+    let user = ctx.require_authentication()?;
+    let rows = load_user_notifications(
+        &ctx.base.db_pool,
+        user.user_id,
+        unread_only,
+        None,
+        limit.clamp(1, 200) as i64,
+    )
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(notification, read_at)| UserNotification::new(notification, read_at))
+        .collect())
+}
+
+pub async fn get_unread_notification_count(ctx: &Context) -> FieldResult<i32> {
+    use crate::db::schema::{notification_reads, notifications};
+
+    let user = ctx.require_authentication()?;
+    let count = notifications::table
+        .left_join(
+            notification_reads::table.on(notification_reads::notification_id
+                .eq(notifications::id)
+                .and(notification_reads::user_id.eq(user.user_id))),
+        )
+        .filter(
+            notifications::user_id
+                .eq(user.user_id)
+                .or(notifications::user_id.is_null()),
+        )
+        .filter(notifications::created_at.le(diesel::dsl::now))
+        .filter(notification_reads::read_at.is_null())
+        .count()
+        .get_result::<i64>(&mut ctx.get_db_conn().await)
+        .await?;
+    Ok(count as i32)
+}
+
+/// Marks the given notifications (or all of them if `ids` is None) as read, returning how many
+/// notifications were newly marked.
+pub async fn mark_notifications_read(
+    ctx: &Context,
+    ids: Option<Vec<uuid::Uuid>>,
+) -> FieldResult<i32> {
+    use crate::db::schema::{notification_reads, notifications};
+
+    let user = ctx.require_authentication()?;
+    let conn = &mut ctx.get_db_conn().await;
+    let mut query = notifications::table
+        .filter(
+            notifications::user_id
+                .eq(user.user_id)
+                .or(notifications::user_id.is_null()),
+        )
+        .filter(notifications::created_at.le(diesel::dsl::now))
+        .select(notifications::id)
+        .into_boxed();
+    if let Some(ids) = ids {
+        query = query.filter(notifications::id.eq_any(ids));
+    }
+    let notification_ids = query.load::<uuid::Uuid>(conn).await?;
+
+    let marked = diesel::insert_into(notification_reads::table)
+        .values(
+            notification_ids
+                .into_iter()
+                .map(|notification_id| NewNotificationRead {
+                    notification_id,
+                    user_id: user.user_id,
+                })
+                .collect::<Vec<_>>(),
+        )
+        .on_conflict_do_nothing()
+        .execute(conn)
+        .await?;
+    Ok(marked as i32)
+}
+
+/// Sends a message from the organizers to a user, all members of a team, or everyone.
+pub async fn send_admin_message(
+    ctx: &Context,
+    title: String,
+    body: String,
+    target_user_id: Option<uuid::Uuid>,
+    target_team_id: Option<uuid::Uuid>,
+) -> FieldResult<bool> {
+    use crate::db::schema::users;
+
+    ctx.require_role_min(UserRole::Admin)?;
+    let recipients = match (target_user_id, target_team_id) {
+        (Some(_), Some(_)) => {
+            return Err(juniper::FieldError::new(
+                "Specify either a user or a team, not both",
+                juniper::Value::null(),
+            ));
+        }
+        (Some(target_user_id), None) => vec![Some(target_user_id)],
+        (None, Some(target_team_id)) => users::table
+            .filter(users::team_id.eq(target_team_id))
+            .select(users::id)
+            .load::<uuid::Uuid>(&mut ctx.get_db_conn().await)
+            .await?
+            .into_iter()
+            .map(Some)
+            .collect(),
+        (None, None) => vec![None],
+    };
+    if recipients.is_empty() {
+        return Err(juniper::FieldError::new(
+            "The team has no members",
+            juniper::Value::null(),
+        ));
+    }
+
+    let new_notifications = recipients
+        .into_iter()
+        .map(|recipient| NewNotification {
+            user_id: recipient,
+            kind: NotificationKind::AdminMessage,
+            title: title.clone(),
+            body: body.clone(),
+            challenge_id: None,
+            created_at: None,
+        })
+        .collect();
+    create_notifications(&ctx.base.db_pool, new_notifications).await?;
+    Ok(true)
+}
+
+struct SubscriptionState {
+    db_pool: DbPool,
+    user_id: uuid::Uuid,
+    /// created_at and ID of the last notification sent
+    last_seen: (DateTime<Utc>, uuid::Uuid),
+    pending: VecDeque<UserNotification>,
+    wakeup: tokio::sync::broadcast::Receiver<()>,
+}
+
+/// Streams notifications the current user receives from now on.
+pub fn subscribe_notifications(ctx: &Context) -> FieldResult<NotificationStream> {
+    let user = ctx.require_authentication()?;
+    let state = SubscriptionState {
+        db_pool: ctx.base.db_pool.clone(),
+        user_id: user.user_id,
+        last_seen: (Utc::now(), uuid::Uuid::max()),
+        pending: VecDeque::new(),
+        wakeup: NEW_NOTIFICATIONS.subscribe(),
+    };
+    Ok(Box::pin(futures_util::stream::unfold(
+        state,
+        |mut state| async move {
+            loop {
+                if let Some(notification) = state.pending.pop_front() {
+                    return Some((Ok(notification), state));
+                }
+                tokio::select! {
+                    _ = state.wakeup.recv() => {}
+                    _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                }
+                match load_user_notifications(
+                    &state.db_pool,
+                    state.user_id,
+                    false,
+                    Some(state.last_seen),
+                    100,
+                )
+                .await
+                {
+                    Ok(rows) => {
+                        for (notification, read_at) in rows {
+                            state.last_seen = (notification.created_at, notification.id);
+                            state
+                                .pending
+                                .push_back(UserNotification::new(notification, read_at));
+                        }
+                    }
+                    Err(e) => return Some((Err(e), state)),
+                }
+            }
+        },
+    )))
+}
@@ -2,8 +2,22 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
+pub mod git_webhook;
+pub mod release_waves;
+
+use std::collections::HashSet;
+
+use crate::db::models::{NewNotification, NotificationKind};
 use crate::graphql::Context;
-use juniper::GraphQLObject;
+use crate::manager_api::{Challenge, ListChallengesRequest};
+use juniper::{GraphQLEnum, GraphQLObject};
+
+#[derive(GraphQLObject)]
+pub struct SubmoduleStatus {
+    /// Path of the submodule in the challenge repository
+    pub path: String,
+    pub commit_hash: String,
+}
 
 #[derive(GraphQLObject)]
 pub struct SyncStatus {
@@ -13,9 +27,45 @@ pub struct SyncStatus {
     pub commit_timestamp: Option<i32>,
     pub commit_author: Option<String>,
     pub commit_title: Option<String>,
+    /// Commits checked out in the submodules of the repository, including nested ones
+    pub submodules: Vec<SubmoduleStatus>,
+    /// Commit or tag syncs deploy instead of the latest commit of the branch
+    pub pinned_revision: Option<String>,
     pub is_synced: bool,
 }
 
+impl From<Option<crate::manager_api::SyncStatus>> for SyncStatus {
+    fn from(status: Option<crate::manager_api::SyncStatus>) -> Self {
+        match status {
+            None => SyncStatus {
+                commit_hash: None,
+                commit_timestamp: None,
+                commit_author: None,
+                commit_title: None,
+                submodules: Vec::new(),
+                pinned_revision: None,
+                is_synced: false,
+            },
+            Some(status) => SyncStatus {
+                commit_hash: Some(status.commit_hash),
+                commit_timestamp: Some(status.commit_timestamp as i32),
+                commit_author: Some(status.commit_author),
+                commit_title: Some(status.commit_title),
+                submodules: status
+                    .submodules
+                    .into_iter()
+                    .map(|submodule| SubmoduleStatus {
+                        path: submodule.path,
+                        commit_hash: submodule.commit_hash,
+                    })
+                    .collect(),
+                pinned_revision: status.pinned_revision,
+                is_synced: true,
+            },
+        }
+    }
+}
+
 pub async fn get_sync_status(context: &Context) -> juniper::FieldResult<SyncStatus> {
     context.require_role_min(crate::db::models::UserRole::Author)?;
 
@@ -25,34 +75,200 @@ pub async fn get_sync_status(context: &Context) -> juniper::FieldResult<SyncStat
 
     let response = client.get_sync_status(request).await?;
 
-    let sync_status = response.into_inner().sync_status;
-
-    match sync_status {
-        None => Ok(SyncStatus {
-            commit_hash: None,
-            commit_timestamp: None,
-            commit_author: None,
-            commit_title: None,
-            is_synced: false,
-        }),
-        Some(status) => Ok(SyncStatus {
-            commit_hash: Some(status.commit_hash),
-            commit_timestamp: Some(status.commit_timestamp as i32),
-            commit_author: Some(status.commit_author),
-            commit_title: Some(status.commit_title),
-            is_synced: true,
-        }),
+    Ok(response.into_inner().sync_status.into())
+}
+
+#[derive(GraphQLEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildStatus {
+    /// The challenge only uses existing images
+    NotRequired,
+    Queued,
+    InProgress,
+    Success,
+    Failed,
+}
+
+impl From<crate::manager_api::BuildStatus> for BuildStatus {
+    fn from(status: crate::manager_api::BuildStatus) -> Self {
+        use crate::manager_api::BuildStatus as Status;
+        match status {
+            Status::NotRequired => BuildStatus::NotRequired,
+            Status::Queued => BuildStatus::Queued,
+            Status::InProgress => BuildStatus::InProgress,
+            Status::Success => BuildStatus::Success,
+            Status::Failed => BuildStatus::Failed,
+        }
     }
 }
 
+#[derive(GraphQLObject)]
+pub struct ChallengeBuild {
+    pub challenge_id: String,
+    pub status: BuildStatus,
+    /// Commit of the repository the challenge was built from
+    pub commit_hash: String,
+    /// End of the build log, or the error that prevented the build
+    pub log_excerpt: Option<String>,
+    pub updated_at: i32,
+}
+
+/// Build status of every challenge at the synced commit, failed builds first.
+pub async fn get_build_status(context: &Context) -> juniper::FieldResult<Vec<ChallengeBuild>> {
+    context.require_role_min(crate::db::models::UserRole::Author)?;
+
+    let response = context
+        .repo_client()
+        .get_build_status(tonic::Request::new(
+            crate::manager_api::GetBuildStatusRequest {},
+        ))
+        .await?
+        .into_inner();
+    let mut builds = response
+        .challenge_builds
+        .into_iter()
+        .map(|(challenge_id, build)| ChallengeBuild {
+            challenge_id,
+            status: build.status().into(),
+            commit_hash: build.commit_hash,
+            log_excerpt: build.log_excerpt,
+            updated_at: build.updated_at as i32,
+        })
+        .collect::<Vec<_>>();
+    builds.sort_by(|a, b| {
+        (b.status == BuildStatus::Failed)
+            .cmp(&(a.status == BuildStatus::Failed))
+            .then_with(|| a.challenge_id.cmp(&b.challenge_id))
+    });
+    Ok(builds)
+}
+
+/// Lists all challenges in the repository, including unreleased ones.
+async fn list_all_challenges(context: &Context) -> juniper::FieldResult<Vec<Challenge>> {
+    // Nobody is logged in for syncs triggered by webhooks, the IDs don't depend on the actor
+    let actor = context
+        .require_authentication()
+        .map(|user| user.actor().slug())
+        .unwrap_or_default();
+    let challenges = context
+        .challenges_client()
+        .list_challenges(ListChallengesRequest {
+            actor,
+            solved_challenges: Default::default(),
+            total_competitors: context.total_competitors as u64,
+            require_release: false,
+        })
+        .await?
+        .into_inner()
+        .challenges;
+    Ok(challenges)
+}
+
+type DbPool = diesel_async::pooled_connection::bb8::Pool<diesel_async::AsyncPgConnection>;
+
+/// Announces challenges at the time they are released.
+fn notify_new_challenges(db_pool: &DbPool, challenges: Vec<Challenge>) {
+    let notifications = challenges
+        .into_iter()
+        .map(|challenge| NewNotification {
+            user_id: None,
+            kind: NotificationKind::NewChallenge,
+            title: format!("New challenge: {}", challenge.name),
+            body: challenge.categories.join(", "),
+            created_at: challenge
+                .release_timestamp
+                .and_then(|ts| chrono::DateTime::from_timestamp(ts as i64, 0))
+                .filter(|release_time| *release_time > chrono::Utc::now()),
+            challenge_id: Some(challenge.id),
+        })
+        .collect();
+    super::notifications::create_notifications_in_background(db_pool, notifications);
+}
+
+/// Whether a challenge will be announced by [`release_waves`] when its wave is released
+fn is_in_upcoming_wave(challenge: &Challenge) -> bool {
+    challenge.release_wave.is_some()
+        && challenge
+            .release_timestamp
+            .is_some_and(|ts| ts as i64 > chrono::Utc::now().timestamp())
+}
+
 pub async fn sync_repository(context: &Context) -> juniper::FieldResult<bool> {
     context.require_role_min(crate::db::models::UserRole::Admin)?;
+    sync_and_announce(context).await?;
+    Ok(true)
+}
 
-    let mut client = context.repo_client();
+/// Deploys a commit or tag instead of the latest commit of the branch from now on. Without a
+/// revision, the latest commit is deployed again, or pinned if `promote_latest` is set.
+pub async fn set_repo_revision(
+    context: &Context,
+    revision: Option<String>,
+    promote_latest: bool,
+) -> juniper::FieldResult<SyncStatus> {
+    context.require_role_min(crate::db::models::UserRole::Admin)?;
+    let request = crate::manager_api::SetRepoRevisionRequest {
+        revision: revision.unwrap_or_default(),
+        promote_latest,
+    };
+    let status = announce_sync(context, async {
+        let response = context
+            .repo_client()
+            .set_repo_revision(tonic::Request::new(request))
+            .await?;
+        Ok(response.into_inner().sync_status)
+    })
+    .await?;
+    Ok(status.into())
+}
 
-    let request = tonic::Request::new(crate::manager_api::SyncChallengesRequest {});
+/// Syncs the repository and announces the challenges it added
+async fn sync_and_announce(context: &Context) -> juniper::FieldResult<()> {
+    announce_sync(context, async {
+        context
+            .repo_client()
+            .sync_challenges(tonic::Request::new(
+                crate::manager_api::SyncChallengesRequest {},
+            ))
+            .await?;
+        Ok(())
+    })
+    .await
+}
 
-    let _response = client.sync_challenges(request).await?;
+/// Held while syncing, so the challenges of one sync aren't announced by another one
+static SYNC_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
 
-    Ok(true)
+/// Runs a sync, then announces the challenges it added and drops what was cached from the
+/// previous commit
+async fn announce_sync<T>(
+    context: &Context,
+    sync: impl Future<Output = juniper::FieldResult<T>>,
+) -> juniper::FieldResult<T> {
+    let _sync_guard = SYNC_LOCK.lock().await;
+    // Without challenges before the first sync, everything would be announced as new
+    let previous_challenges = match list_all_challenges(context).await {
+        Ok(challenges) if !challenges.is_empty() => {
+            Some(challenges.into_iter().map(|c| c.id).collect::<HashSet<_>>())
+        }
+        _ => None,
+    };
+    let result = sync.await?;
+    if let Some(previous_challenges) = previous_challenges {
+        match list_all_challenges(context).await {
+            Ok(challenges) => notify_new_challenges(
+                &context.base.db_pool,
+                challenges
+                    .into_iter()
+                    .filter(|c| !previous_challenges.contains(&c.id) && !is_in_upcoming_wave(c))
+                    .collect(),
+            ),
+            Err(e) => tracing::error!("Failed to list challenges after sync: {:?}", e.message()),
+        }
+    }
+    super::event::invalidate_event_config().await;
+    super::challenges::invalidate_challenge_lists();
+    // Scoring of challenges might have changed
+    super::scoreboard::points::request_recompute(None);
+
+    Ok(result)
 }
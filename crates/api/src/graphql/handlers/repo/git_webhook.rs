@@ -0,0 +1,210 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Push webhooks of the challenge repository, which sync it so organizers don't have to after
+//! every commit.
+//!
+//! Webhooks of GitHub, Gitea, Forgejo and GitLab are verified with git_webhook_secret, without
+//! it the endpoint doesn't exist. Pushes to other branches than git_webhook_branch (or the one
+//! the manager syncs from) are ignored, and pushes in quick succession only lead to one sync.
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use hmac::{Hmac, Mac};
+use hyper::HeaderMap;
+use sha2::Sha256;
+
+use crate::{graphql::Context, manager_api::GetSyncStatusRequest};
+
+/// How long to wait for further pushes before syncing
+const DEBOUNCE: Duration = Duration::from_secs(10);
+
+/// Whether a sync is scheduled but hasn't started yet
+static SYNC_PENDING: AtomicBool = AtomicBool::new(false);
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn hmac_hex(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(body);
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+/// Whether the request was sent by a forge knowing the secret
+fn is_authentic(headers: &HeaderMap, body: &[u8], secret: &str) -> bool {
+    if let Some(signature) = header(headers, "x-hub-signature-256") {
+        // GitHub, and Gitea and Forgejo for compatibility
+        signature.strip_prefix("sha256=").is_some_and(|signature| {
+            constant_time_eq(signature.as_bytes(), hmac_hex(secret, body).as_bytes())
+        })
+    } else if let Some(signature) =
+        header(headers, "x-gitea-signature").or_else(|| header(headers, "x-forgejo-signature"))
+    {
+        constant_time_eq(signature.as_bytes(), hmac_hex(secret, body).as_bytes())
+    } else if let Some(token) = header(headers, "x-gitlab-token") {
+        // GitLab sends the secret itself
+        constant_time_eq(token.as_bytes(), secret.as_bytes())
+    } else {
+        false
+    }
+}
+
+/// Whether the webhook is about a push to the branch, if one is given
+fn is_push(headers: &HeaderMap, body: &[u8], branch: Option<&str>) -> bool {
+    let event = header(headers, "x-github-event")
+        .or_else(|| header(headers, "x-gitea-event"))
+        .or_else(|| header(headers, "x-forgejo-event"))
+        .or_else(|| header(headers, "x-gitlab-event"));
+    if !matches!(event, Some("push" | "Push Hook")) {
+        return false;
+    }
+    let Some(branch) = branch else {
+        return true;
+    };
+    serde_json::from_slice::<serde_json::Value>(body)
+        .ok()
+        .and_then(|payload| payload.get("ref")?.as_str().map(str::to_string))
+        .is_some_and(|git_ref| git_ref == format!("refs/heads/{}", branch))
+}
+
+/// Syncs the repository once no more pushes arrived for a while
+fn schedule_sync(ctx: Context) {
+    if SYNC_PENDING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tokio::spawn(async move {
+        tokio::time::sleep(DEBOUNCE).await;
+        // Pushes arriving during the sync schedule another one, which waits for this one
+        SYNC_PENDING.store(false, Ordering::SeqCst);
+        tracing::info!("Syncing the challenge repository after a push");
+        if let Err(e) = super::sync_and_announce(&ctx).await {
+            tracing::error!(
+                "Failed to sync the challenge repository after a push: {}",
+                e.message()
+            );
+        }
+    });
+}
+
+/// The branch the manager syncs from
+async fn synced_branch(ctx: &Context) -> Result<String, (u16, String)> {
+    let response = ctx
+        .repo_client()
+        .get_sync_status(GetSyncStatusRequest {})
+        .await
+        .map_err(|e| (503, format!("Failed to get the branch: {}", e.message())))?;
+    Ok(response.into_inner().branch)
+}
+
+pub async fn handle_git_webhook(
+    ctx: Context,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<&'static str, (u16, String)> {
+    let config = ctx.base.config.clone();
+    let Some(secret) = config
+        .git_webhook_secret
+        .as_deref()
+        .filter(|secret| !secret.is_empty())
+    else {
+        return Err((404, "Not found".to_string()));
+    };
+    if !is_authentic(headers, body, secret) {
+        return Err((401, "Invalid webhook signature".to_string()));
+    }
+    let branch = match config
+        .git_webhook_branch
+        .as_deref()
+        .filter(|branch| !branch.is_empty())
+    {
+        Some(branch) => branch.to_string(),
+        None => synced_branch(&ctx).await?,
+    };
+    if !is_push(headers, body, Some(&branch)) {
+        // Pings and pushes to other branches are acknowledged, so forges don't report failures
+        return Ok("Ignored");
+    }
+    schedule_sync(ctx);
+    Ok("Sync scheduled")
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::header::HeaderValue;
+
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| {
+                (
+                    hyper::header::HeaderName::from_static(name),
+                    HeaderValue::from_str(value).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_is_authentic() {
+        let body = br#"{"ref":"refs/heads/main"}"#;
+        let signature = hmac_hex("secret", body);
+        assert!(is_authentic(
+            &headers(&[("x-hub-signature-256", &format!("sha256={}", signature))]),
+            body,
+            "secret"
+        ));
+        assert!(is_authentic(
+            &headers(&[("x-gitea-signature", &signature)]),
+            body,
+            "secret"
+        ));
+        assert!(is_authentic(
+            &headers(&[("x-gitlab-token", "secret")]),
+            body,
+            "secret"
+        ));
+        assert!(!is_authentic(
+            &headers(&[("x-hub-signature-256", &format!("sha256={}", signature))]),
+            br#"{"ref":"refs/heads/evil"}"#,
+            "secret"
+        ));
+        assert!(!is_authentic(
+            &headers(&[("x-gitlab-token", "guess")]),
+            body,
+            "secret"
+        ));
+        assert!(!is_authentic(&HeaderMap::new(), body, "secret"));
+    }
+
+    #[test]
+    fn test_is_push() {
+        let body = br#"{"ref":"refs/heads/main"}"#;
+        let github_push = headers(&[("x-github-event", "push")]);
+        assert!(is_push(&github_push, body, Some("main")));
+        assert!(is_push(&github_push, body, None));
+        assert!(!is_push(&github_push, body, Some("dev")));
+        assert!(is_push(
+            &headers(&[("x-gitlab-event", "Push Hook")]),
+            body,
+            Some("main")
+        ));
+        assert!(!is_push(
+            &headers(&[("x-github-event", "ping")]),
+            body,
+            None
+        ));
+    }
+}
@@ -0,0 +1,109 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Announcements of the release waves of the event config, which the manager sends at the time
+//! they are released.
+//!
+//! Every api replica watches the releases to drop its cached challenge lists, but only the one
+//! recording a wave in the database first creates notifications and sends webhooks.
+
+use std::{collections::HashSet, time::Duration};
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use tokio::task::JoinSet;
+
+use crate::{
+    db::models::WebhookEvent,
+    graphql::BaseContext,
+    manager_api::{
+        ListChallengesRequest, ReleasedWave, WatchReleasesRequest,
+        challenges_service_client::ChallengesServiceClient,
+        repository_service_client::RepositoryServiceClient,
+    },
+};
+
+/// How long to wait before watching again after the connection to the manager broke
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+fn release_time(wave: &ReleasedWave) -> chrono::DateTime<chrono::Utc> {
+    chrono::DateTime::from_timestamp(wave.release_time as i64, 0).unwrap_or_default()
+}
+
+/// Records a wave as announced, false if another replica already did
+async fn record_wave(ctx: &BaseContext, wave: &ReleasedWave) -> Result<bool, Error> {
+    use crate::db::schema::released_waves;
+
+    let inserted = diesel::insert_into(released_waves::table)
+        .values((
+            released_waves::name.eq(&wave.name),
+            released_waves::release_time.eq(release_time(wave)),
+        ))
+        .on_conflict_do_nothing()
+        .execute(&mut ctx.db_pool.get().await?)
+        .await?;
+    Ok(inserted > 0)
+}
+
+async fn announce_wave(ctx: &BaseContext, wave: ReleasedWave) -> Result<(), Error> {
+    tracing::info!("Release wave {} was released", wave.name);
+    crate::graphql::handlers::challenges::invalidate_challenge_lists();
+    if !record_wave(ctx, &wave).await? {
+        return Ok(());
+    }
+    let challenge_ids: HashSet<&str> = wave.challenge_ids.iter().map(String::as_str).collect();
+    let challenges = ChallengesServiceClient::new(ctx.grpc_client.clone())
+        .list_challenges(ListChallengesRequest {
+            actor: String::new(),
+            solved_challenges: Default::default(),
+            total_competitors: 0,
+            require_release: false,
+        })
+        .await?
+        .into_inner()
+        .challenges
+        .into_iter()
+        .filter(|challenge| challenge_ids.contains(challenge.id.as_str()))
+        .collect();
+    // Challenges with a later release time of their own are announced at that time
+    super::notify_new_challenges(&ctx.db_pool, challenges);
+    crate::webhooks::dispatch(
+        &ctx.db_pool,
+        WebhookEvent::ChallengeRelease,
+        serde_json::json!({
+            "wave": wave.name,
+            "release_time": release_time(&wave).to_rfc3339(),
+            "challenge_ids": wave.challenge_ids,
+        }),
+    );
+    Ok(())
+}
+
+async fn watch_releases(ctx: &BaseContext) -> Result<(), Error> {
+    let mut releases = RepositoryServiceClient::new(ctx.grpc_client.clone())
+        .watch_releases(WatchReleasesRequest {})
+        .await?
+        .into_inner();
+    while let Some(wave) = releases.message().await? {
+        let name = wave.name.clone();
+        if let Err(e) = announce_wave(ctx, wave).await {
+            tracing::error!("Failed to announce release wave {}: {}", name, e);
+        }
+    }
+    Ok(())
+}
+
+/// Starts the background task that announces release waves when the manager releases them.
+pub fn start_release_watcher(tasks: &mut JoinSet<()>, ctx: BaseContext) {
+    tasks.spawn(async move {
+        loop {
+            if let Err(e) = watch_releases(&ctx).await {
+                tracing::warn!("Stopped watching release waves: {}", e);
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    });
+}
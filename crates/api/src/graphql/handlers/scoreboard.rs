@@ -0,0 +1,413 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+pub mod ctftime;
+pub mod hidden;
+pub mod penalties;
+pub mod points;
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, LazyLock, RwLock},
+};
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use juniper::GraphQLObject;
+
+use crate::{
+    db::models::UserRole,
+    graphql::{BaseContext, Context},
+    manager_api::{
+        CalculatePointsRequest, PointsQuery, SolvedChallenge,
+        challenges_service_client::ChallengesServiceClient,
+    },
+};
+
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct ScoreboardSolve {
+    pub challenge_id: String,
+    pub solved_at: String,
+    pub points: i32,
+}
+
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct ScoreboardEntry {
+    pub rank: i32,
+    /// ID of the team, or of the user if they are not in a team
+    pub id: String,
+    pub name: String,
+    /// Actor slug, e.g. "team-foo" or "user-bar"
+    pub actor: String,
+    pub is_team: bool,
+    /// Country code of the team, for flag icons
+    pub country_code: Option<String>,
+    pub affiliation: Option<String>,
+    /// Division of the team, if the event has divisions
+    pub division: Option<String>,
+    pub points: i32,
+    pub last_solve_at: Option<String>,
+    pub solves: Vec<ScoreboardSolve>,
+}
+
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct Scoreboard {
+    pub entries: Vec<ScoreboardEntry>,
+    /// Set if solves after this timestamp are hidden from the current user
+    pub frozen_at: Option<i32>,
+}
+
+/// Scoreboards precomputed by the points worker, so requests don't have to compute them.
+#[derive(Default)]
+struct ScoreboardSnapshot {
+    live: Option<Arc<Vec<ScoreboardEntry>>>,
+    /// The frozen scoreboard, with the freeze time it was computed for
+    frozen: Option<(i64, Arc<Vec<ScoreboardEntry>>)>,
+}
+
+static SNAPSHOT: LazyLock<RwLock<ScoreboardSnapshot>> = LazyLock::new(Default::default);
+
+/// Returns the precomputed scoreboard for the given freeze cutoff, if there is one.
+fn snapshot_entries(frozen_at: Option<i64>) -> Option<Vec<ScoreboardEntry>> {
+    let snapshot = SNAPSHOT.read().unwrap();
+    let entries = match frozen_at {
+        None => snapshot.live.as_ref(),
+        Some(frozen_at) => snapshot
+            .frozen
+            .as_ref()
+            .filter(|(cutoff, _)| *cutoff == frozen_at)
+            .map(|(_, entries)| entries),
+    };
+    entries.map(|entries| entries.as_ref().clone())
+}
+
+/// Recomputes the live scoreboard and, while the scoreboard is frozen, the frozen one.
+///
+/// Called by the points worker after it updated the points of new solves.
+pub(crate) async fn refresh_snapshot(base: &BaseContext) -> juniper::FieldResult<()> {
+    let event_config = super::event::fetch_event_config(base).await?;
+    let total_competitors = crate::graphql::count_competitors(&base.db_pool).await?;
+    let challs_client = ChallengesServiceClient::new(base.grpc_client.clone());
+
+    let live = compute_scoreboard(
+        &base.db_pool,
+        challs_client.clone(),
+        total_competitors,
+        None,
+    )
+    .await?;
+    let frozen_at = freeze_cutoff(
+        event_config.scoreboard_freeze_time.map(i64::from),
+        event_config.scoreboard_reveal_time.map(i64::from),
+        Utc::now().timestamp(),
+    );
+    let frozen = match frozen_at {
+        Some(frozen_at) => Some((
+            frozen_at,
+            Arc::new(
+                compute_scoreboard(
+                    &base.db_pool,
+                    challs_client,
+                    total_competitors,
+                    DateTime::from_timestamp(frozen_at, 0),
+                )
+                .await?,
+            ),
+        )),
+        None => None,
+    };
+
+    *SNAPSHOT.write().unwrap() = ScoreboardSnapshot {
+        live: Some(Arc::new(live)),
+        frozen,
+    };
+    Ok(())
+}
+
+/// Returns the timestamp after which solves must be hidden, if the scoreboard is currently frozen.
+pub fn freeze_cutoff(freeze_time: Option<i64>, reveal_time: Option<i64>, now: i64) -> Option<i64> {
+    let freeze_time = freeze_time?;
+    if now < freeze_time || reveal_time.is_some_and(|reveal| now >= reveal) {
+        return None;
+    }
+    Some(freeze_time)
+}
+
+/// Computes the full scoreboard, only taking solves up to `cutoff` into account.
+///
+/// The live scoreboard uses the points stored by the points worker, while a frozen one is calculated
+/// from the solve counts at the time of the freeze. This does not depend on a request context so it can also be used for feeds and background tasks.
+pub async fn compute_scoreboard(
+    db_pool: &diesel_async::pooled_connection::bb8::Pool<diesel_async::AsyncPgConnection>,
+    mut challs_client: ChallengesServiceClient<crate::graphql::GrpcChannel>,
+    total_competitors: i32,
+    cutoff: Option<DateTime<Utc>>,
+) -> juniper::FieldResult<Vec<ScoreboardEntry>> {
+    use crate::db::schema::{solves, teams, users};
+
+    let mut conn = db_pool.get().await?;
+    // Solves count for the team they were made for, even if the user switched teams since
+    let mut query = solves::table
+        .inner_join(users::table)
+        .left_join(teams::table.on(teams::id.nullable().eq(solves::team_id)))
+        .select((
+            solves::challenge_id,
+            solves::solved_at,
+            solves::awarded_points,
+            users::id,
+            users::username,
+            users::display_name,
+            teams::id.nullable(),
+            teams::name.nullable(),
+            teams::slug.nullable(),
+            teams::country_code.nullable(),
+            teams::affiliation.nullable(),
+            teams::division.nullable(),
+        ))
+        .filter(solves::playtest.eq(false))
+        .filter(users::hidden.eq(false))
+        .filter(
+            teams::hidden
+                .nullable()
+                .eq(false)
+                .or(teams::id.nullable().is_null()),
+        )
+        .filter(
+            teams::disqualified
+                .nullable()
+                .eq(false)
+                .or(teams::id.nullable().is_null()),
+        )
+        .order(solves::solved_at.asc())
+        .into_boxed();
+    if let Some(cutoff) = cutoff {
+        query = query.filter(solves::solved_at.le(cutoff));
+    }
+    #[allow(clippy::type_complexity)]
+    let rows: Vec<(
+        String,
+        DateTime<Utc>,
+        Option<i32>,
+        uuid::Uuid,
+        String,
+        String,
+        Option<uuid::Uuid>,
+        Option<String>,
+        Option<String>,
+        Option<String>,
+        Option<String>,
+        Option<String>,
+    )> = query.load(&mut conn).await?;
+    drop(conn);
+
+    let mut entries: HashMap<uuid::Uuid, ScoreboardEntry> = HashMap::new();
+    // Actors in the order they first solved each challenge
+    let mut solvers: HashMap<String, Vec<uuid::Uuid>> = HashMap::new();
+    // Solves whose points still need to be calculated
+    let mut pending: HashSet<(uuid::Uuid, String)> = HashSet::new();
+    for (
+        challenge_id,
+        solved_at,
+        awarded_points,
+        user_id,
+        username,
+        display_name,
+        team_id,
+        team_name,
+        team_slug,
+        country_code,
+        affiliation,
+        division,
+    ) in rows
+    {
+        let actor_id = team_id.unwrap_or(user_id);
+        let entry = entries.entry(actor_id).or_insert_with(|| ScoreboardEntry {
+            rank: 0,
+            id: actor_id.to_string(),
+            name: team_name.unwrap_or(display_name),
+            actor: match team_slug {
+                Some(slug) => format!("team-{slug}"),
+                None => format!("user-{username}"),
+            },
+            is_team: team_id.is_some(),
+            country_code,
+            affiliation,
+            division,
+            points: 0,
+            last_solve_at: None,
+            solves: vec![],
+        });
+        // Only the first solve of a team counts
+        if entry.solves.iter().any(|s| s.challenge_id == challenge_id) {
+            continue;
+        }
+        solvers
+            .entry(challenge_id.clone())
+            .or_default()
+            .push(actor_id);
+        let points = match awarded_points {
+            Some(points) if cutoff.is_none() => points,
+            _ => {
+                pending.insert((actor_id, challenge_id.clone()));
+                0
+            }
+        };
+        entry.last_solve_at = Some(solved_at.to_rfc3339());
+        entry.solves.push(ScoreboardSolve {
+            challenge_id,
+            solved_at: solved_at.to_rfc3339(),
+            points,
+        });
+    }
+
+    let mut entries: Vec<(uuid::Uuid, ScoreboardEntry)> = entries.into_iter().collect();
+    let mut queries = vec![];
+    for (actor_id, entry) in &entries {
+        for solve in &entry.solves {
+            if !pending.contains(&(*actor_id, solve.challenge_id.clone())) {
+                continue;
+            }
+            let challenge_solvers = &solvers[&solve.challenge_id];
+            let nth_solve = challenge_solvers
+                .iter()
+                .position(|id| id == actor_id)
+                .unwrap_or_default()
+                + 1;
+            queries.push(PointsQuery {
+                challenge_id: solve.challenge_id.clone(),
+                solve: Some(SolvedChallenge {
+                    actor_nth_solve: nth_solve as i32,
+                    total_solves: challenge_solvers.len() as i32,
+                }),
+            });
+        }
+    }
+    let points = if queries.is_empty() {
+        vec![]
+    } else {
+        challs_client
+            .calculate_points(CalculatePointsRequest {
+                queries,
+                total_competitors: total_competitors as u64,
+            })
+            .await?
+            .into_inner()
+            .points
+    };
+
+    // The response is in the same order as the queries
+    let mut points = points.into_iter();
+    for (actor_id, entry) in entries.iter_mut() {
+        for solve in entry.solves.iter_mut() {
+            if pending.contains(&(*actor_id, solve.challenge_id.clone())) {
+                solve.points = points.next().unwrap_or_default() as i32;
+            }
+            entry.points += solve.points;
+        }
+    }
+    let mut entries: Vec<ScoreboardEntry> = entries.into_iter().map(|(_, e)| e).collect();
+
+    // Higher score first, ties are broken by whoever reached the score first
+    entries.sort_by(|a, b| {
+        b.points
+            .cmp(&a.points)
+            .then_with(|| a.last_solve_at.cmp(&b.last_solve_at))
+    });
+    for (idx, entry) in entries.iter_mut().enumerate() {
+        entry.rank = idx as i32 + 1;
+    }
+
+    Ok(entries)
+}
+
+/// Returns the timestamp after which solves are hidden from the current user, if any.
+pub async fn visible_freeze_cutoff(context: &Context) -> juniper::FieldResult<Option<i64>> {
+    // Admins always see the live scoreboard
+    if context.role().is_some_and(|r| r >= UserRole::Admin) {
+        return Ok(None);
+    }
+    let event_config = super::event::get_event_config(context).await?;
+    Ok(freeze_cutoff(
+        event_config.scoreboard_freeze_time.map(i64::from),
+        event_config.scoreboard_reveal_time.map(i64::from),
+        Utc::now().timestamp(),
+    ))
+}
+
+async fn get_scoreboard_internal(context: &Context) -> juniper::FieldResult<Scoreboard> {
+    let frozen_at = visible_freeze_cutoff(context).await?;
+
+    // Until the points worker has computed a snapshot, the scoreboard is computed on demand
+    let entries = match snapshot_entries(frozen_at) {
+        Some(entries) => entries,
+        None => {
+            compute_scoreboard(
+                &context.base.db_pool,
+                context.challenges_client(),
+                context.total_competitors,
+                frozen_at.and_then(|t| DateTime::from_timestamp(t, 0)),
+            )
+            .await?
+        }
+    };
+
+    Ok(Scoreboard {
+        entries,
+        frozen_at: frozen_at.map(|t| t as i32),
+    })
+}
+
+/// Returns the scoreboard as visible to the current user, computed at most once per request.
+pub async fn get_scoreboard(context: &Context) -> juniper::FieldResult<Scoreboard> {
+    context
+        .scoreboard
+        .get_or_try_init(|| get_scoreboard_internal(context))
+        .await
+        .cloned()
+}
+
+/// Returns the scoreboard of a single division, with ranks within the division.
+pub async fn get_division_scoreboard(
+    context: &Context,
+    division: Option<String>,
+) -> juniper::FieldResult<Scoreboard> {
+    let mut scoreboard = get_scoreboard(context).await?;
+    if let Some(division) = division {
+        scoreboard
+            .entries
+            .retain(|e| e.division.as_ref() == Some(&division));
+        for (idx, entry) in scoreboard.entries.iter_mut().enumerate() {
+            entry.rank = idx as i32 + 1;
+        }
+    }
+    Ok(scoreboard)
+}
+
+/// Returns the scoreboard entry of a team or user (by their ID), if they have solved anything.
+pub async fn get_scoreboard_entry(
+    context: &Context,
+    actor_id: uuid::Uuid,
+) -> juniper::FieldResult<Option<ScoreboardEntry>> {
+    let actor_id = actor_id.to_string();
+    Ok(get_scoreboard(context)
+        .await?
+        .entries
+        .into_iter()
+        .find(|e| e.id == actor_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_freeze_cutoff() {
+        assert_eq!(freeze_cutoff(None, None, 100), None);
+        assert_eq!(freeze_cutoff(Some(50), None, 10), None);
+        assert_eq!(freeze_cutoff(Some(50), None, 100), Some(50));
+        assert_eq!(freeze_cutoff(Some(50), Some(200), 100), Some(50));
+        assert_eq!(freeze_cutoff(Some(50), Some(200), 200), None);
+    }
+}
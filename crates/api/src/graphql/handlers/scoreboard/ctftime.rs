@@ -0,0 +1,100 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::graphql::Context;
+
+#[derive(Serialize)]
+struct CtftimeTaskStats {
+    points: i32,
+    time: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CtftimeStanding {
+    pos: i32,
+    team: String,
+    score: i32,
+    task_stats: HashMap<String, CtftimeTaskStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_accept: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct CtftimeFeed {
+    tasks: Vec<String>,
+    standings: Vec<CtftimeStanding>,
+}
+
+/// Renders the public scoreboard in the CTFtime JSON feed format.
+///
+/// See https://ctftime.org/json-scoreboard-feed for the format.
+pub async fn ctftime_scoreboard(ctx: Context) -> Result<Vec<u8>, (u16, String)> {
+    let scoreboard = super::get_scoreboard(&ctx).await.map_err(|e| {
+        (
+            500,
+            format!("Failed to compute scoreboard: {}", e.message()),
+        )
+    })?;
+
+    let challenges = ctx
+        .challenges_client()
+        .list_challenges(crate::manager_api::ListChallengesRequest {
+            actor: String::new(),
+            solved_challenges: HashMap::new(),
+            total_competitors: ctx.total_competitors as u64,
+            require_release: true,
+        })
+        .await
+        .map_err(|e| (500, format!("Failed to list challenges: {}", e.message())))?
+        .into_inner()
+        .challenges;
+    let task_names: HashMap<String, String> =
+        challenges.into_iter().map(|c| (c.id, c.name)).collect();
+    let task_name = |id: &str| {
+        task_names
+            .get(id)
+            .cloned()
+            .unwrap_or_else(|| id.to_string())
+    };
+
+    let mut tasks: Vec<String> = task_names.values().cloned().collect();
+    tasks.sort();
+
+    let standings = scoreboard
+        .entries
+        .into_iter()
+        .map(|entry| {
+            let task_stats = entry
+                .solves
+                .iter()
+                .map(|solve| {
+                    (
+                        task_name(&solve.challenge_id),
+                        CtftimeTaskStats {
+                            points: solve.points,
+                            time: chrono::DateTime::parse_from_rfc3339(&solve.solved_at)
+                                .map(|t| t.timestamp())
+                                .unwrap_or_default(),
+                        },
+                    )
+                })
+                .collect::<HashMap<_, _>>();
+            CtftimeStanding {
+                pos: entry.rank,
+                team: entry.name,
+                score: entry.points,
+                last_accept: task_stats.values().map(|s| s.time).max(),
+                task_stats,
+            }
+        })
+        .collect();
+
+    serde_json::to_vec(&CtftimeFeed { tasks, standings })
+        .map_err(|e| (500, format!("Failed to serialize scoreboard: {}", e)))
+}
@@ -0,0 +1,74 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use juniper::FieldResult;
+
+use crate::{
+    db::models::{Team, User, UserRole},
+    graphql::Context,
+};
+
+/// Hides a user from the scoreboard and statistics, or shows them again (admin only).
+///
+/// Hidden users can still log in and solve challenges.
+pub async fn set_user_hidden(
+    ctx: &Context,
+    user_id: uuid::Uuid,
+    hidden: bool,
+) -> FieldResult<User> {
+    use crate::db::schema::users;
+
+    ctx.require_role_min(UserRole::Admin)?;
+    let admin = ctx.require_authentication()?;
+    let user = diesel::update(users::table.filter(users::id.eq(user_id)))
+        .set((
+            users::hidden.eq(hidden),
+            users::updated_at.eq(chrono::Utc::now()),
+        ))
+        .get_result::<User>(&mut ctx.get_db_conn().await)
+        .await
+        .optional()?
+        .ok_or_else(|| juniper::FieldError::new("User not found", juniper::Value::null()))?;
+    tracing::info!(
+        "User {} {} by {}",
+        user.username,
+        if hidden { "hidden" } else { "shown" },
+        admin.username
+    );
+    // Points depend on the number of visible solvers
+    super::points::request_recompute(None);
+    Ok(user)
+}
+
+/// Hides a team from the scoreboard and statistics, or shows it again (admin only).
+pub async fn set_team_hidden(
+    ctx: &Context,
+    team_id: uuid::Uuid,
+    hidden: bool,
+) -> FieldResult<Team> {
+    use crate::db::schema::teams;
+
+    ctx.require_role_min(UserRole::Admin)?;
+    let admin = ctx.require_authentication()?;
+    let team = diesel::update(teams::table.filter(teams::id.eq(team_id)))
+        .set((
+            teams::hidden.eq(hidden),
+            teams::updated_at.eq(chrono::Utc::now()),
+        ))
+        .returning(Team::as_returning())
+        .get_result(&mut ctx.get_db_conn().await)
+        .await
+        .optional()?
+        .ok_or_else(|| juniper::FieldError::new("Team not found", juniper::Value::null()))?;
+    tracing::info!(
+        "Team {} {} by {}",
+        team.slug,
+        if hidden { "hidden" } else { "shown" },
+        admin.username
+    );
+    super::points::request_recompute(None);
+    Ok(team)
+}
@@ -0,0 +1,240 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use diesel::prelude::*;
+use diesel_async::{AsyncConnection, RunQueryDsl, scoped_futures::ScopedFutureExt};
+use juniper::{FieldResult, graphql_value};
+
+use crate::{
+    db::models::{AuditAction, Solve, Team, UserRole},
+    graphql::{AuthenticatedUser, Context},
+};
+
+use super::super::audit_log;
+
+/// Updates points and the scoreboard right away instead of waiting for the points worker.
+async fn refresh_scoreboard(ctx: &Context, challenge_id: Option<String>) {
+    crate::graphql::handlers::challenges::invalidate_challenge_lists();
+    if let Err(e) = super::refresh_snapshot(&ctx.base).await {
+        tracing::error!("Failed to update scoreboard: {}", e.message());
+    }
+    // Removing a solve changes the points of everyone who solved the challenge after it
+    super::points::request_recompute(challenge_id);
+}
+
+/// Returns an error if the user's team was disqualified.
+pub async fn require_not_disqualified(ctx: &Context, user: &AuthenticatedUser) -> FieldResult<()> {
+    use crate::db::schema::teams;
+
+    let Some(team_id) = user.team_id else {
+        return Ok(());
+    };
+    let disqualified = teams::table
+        .filter(teams::id.eq(team_id))
+        .select(teams::disqualified)
+        .first::<bool>(&mut ctx.get_db_conn().await)
+        .await
+        .optional()?
+        .unwrap_or(false);
+    if disqualified {
+        return Err(juniper::FieldError::new(
+            "Your team was disqualified",
+            graphql_value!({ "code": "DISQUALIFIED" }),
+        ));
+    }
+    Ok(())
+}
+
+/// Removes a single solve, e.g. one made with a leaked flag (admin only).
+pub async fn revoke_solve(
+    ctx: &Context,
+    solve_id: uuid::Uuid,
+    reason: Option<String>,
+) -> FieldResult<Solve> {
+    use crate::db::schema::{solves, users};
+
+    ctx.require_role_min(UserRole::Admin)?;
+    let admin = ctx.require_authentication()?;
+
+    let solve = ctx
+        .get_db_conn()
+        .await
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            let admin = &admin;
+            async move {
+                let Some(solve) = diesel::delete(solves::table.filter(solves::id.eq(solve_id)))
+                    .returning(Solve::as_returning())
+                    .get_result(conn)
+                    .await
+                    .optional()?
+                else {
+                    return Ok(None);
+                };
+                let username = users::table
+                    .filter(users::id.eq(solve.user_id))
+                    .select(users::username)
+                    .first::<String>(conn)
+                    .await?;
+                audit_log::record(
+                    conn,
+                    admin,
+                    AuditAction::SolveRevoked,
+                    Some(solve.team_id.unwrap_or(solve.user_id)),
+                    format!(
+                        "Solve of {} by {} at {}",
+                        solve.challenge_id,
+                        username,
+                        solve.solved_at.to_rfc3339()
+                    ),
+                    reason,
+                )
+                .await?;
+                Ok(Some(solve))
+            }
+            .scope_boxed()
+        })
+        .await?
+        .ok_or_else(|| juniper::FieldError::new("Solve not found", juniper::Value::null()))?;
+
+    tracing::info!(
+        "Solve {} of {} revoked by {}",
+        solve.id,
+        solve.challenge_id,
+        admin.username
+    );
+    refresh_scoreboard(ctx, Some(solve.challenge_id.clone())).await;
+    Ok(solve)
+}
+
+/// Removes all solves credited to a team, so its score is zero (admin only).
+///
+/// The team stays on the scoreboard and can solve challenges again. Returns the number of removed
+/// solves.
+pub async fn zero_team_score(
+    ctx: &Context,
+    team_id: uuid::Uuid,
+    reason: Option<String>,
+) -> FieldResult<i32> {
+    use crate::db::schema::{solves, teams};
+
+    ctx.require_role_min(UserRole::Admin)?;
+    let admin = ctx.require_authentication()?;
+
+    let removed = ctx
+        .get_db_conn()
+        .await
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            let admin = &admin;
+            async move {
+                let Some(slug) = teams::table
+                    .filter(teams::id.eq(team_id))
+                    .select(teams::slug)
+                    .first::<String>(conn)
+                    .await
+                    .optional()?
+                else {
+                    return Ok(None);
+                };
+                // Solves count for the team they were made for, not the current team of the solver
+                let removed = diesel::delete(solves::table.filter(solves::team_id.eq(team_id)))
+                    .returning(solves::challenge_id)
+                    .get_results::<String>(conn)
+                    .await?;
+                audit_log::record(
+                    conn,
+                    admin,
+                    AuditAction::ScoreZeroed,
+                    Some(team_id),
+                    format!(
+                        "{} solves of team {} removed: {}",
+                        removed.len(),
+                        slug,
+                        removed.join(", ")
+                    ),
+                    reason,
+                )
+                .await?;
+                Ok(Some(removed.len()))
+            }
+            .scope_boxed()
+        })
+        .await?
+        .ok_or_else(|| juniper::FieldError::new("Team not found", juniper::Value::null()))?;
+
+    tracing::info!(
+        "Score of team {} zeroed by {}, {} solves removed",
+        team_id,
+        admin.username,
+        removed
+    );
+    refresh_scoreboard(ctx, None).await;
+    Ok(removed as i32)
+}
+
+/// Disqualifies a team, or lifts its disqualification (admin only).
+///
+/// Disqualified teams are left out of the scoreboard and can't submit flags, but keep their solves
+/// so they can be requalified.
+pub async fn set_team_disqualified(
+    ctx: &Context,
+    team_id: uuid::Uuid,
+    disqualified: bool,
+    reason: Option<String>,
+) -> FieldResult<Team> {
+    use crate::db::schema::teams;
+
+    ctx.require_role_min(UserRole::Admin)?;
+    let admin = ctx.require_authentication()?;
+
+    let team = ctx
+        .get_db_conn()
+        .await
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            let admin = &admin;
+            async move {
+                let Some(team) = diesel::update(teams::table.filter(teams::id.eq(team_id)))
+                    .set((
+                        teams::disqualified.eq(disqualified),
+                        teams::updated_at.eq(chrono::Utc::now()),
+                    ))
+                    .returning(Team::as_returning())
+                    .get_result(conn)
+                    .await
+                    .optional()?
+                else {
+                    return Ok(None);
+                };
+                audit_log::record(
+                    conn,
+                    admin,
+                    if disqualified {
+                        AuditAction::TeamDisqualified
+                    } else {
+                        AuditAction::TeamRequalified
+                    },
+                    Some(team.id),
+                    format!("Team {}", team.slug),
+                    reason,
+                )
+                .await?;
+                Ok(Some(team))
+            }
+            .scope_boxed()
+        })
+        .await?
+        .ok_or_else(|| juniper::FieldError::new("Team not found", juniper::Value::null()))?;
+
+    tracing::info!(
+        "Team {} {} by {}",
+        team.slug,
+        if disqualified {
+            "disqualified"
+        } else {
+            "requalified"
+        },
+        admin.username
+    );
+    refresh_scoreboard(ctx, None).await;
+    Ok(team)
+}
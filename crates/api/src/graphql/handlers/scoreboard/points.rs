@@ -0,0 +1,180 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+    time::Duration,
+};
+
+use diesel::prelude::*;
+use diesel_async::{AsyncConnection, RunQueryDsl, scoped_futures::ScopedFutureExt};
+use tokio::{sync::mpsc, task::JoinSet};
+
+use crate::{
+    graphql::BaseContext,
+    manager_api::{
+        CalculatePointsRequest, PointsQuery, SolvedChallenge,
+        challenges_service_client::ChallengesServiceClient,
+    },
+};
+
+type DbPool = diesel_async::pooled_connection::bb8::Pool<diesel_async::AsyncPgConnection>;
+
+/// How long to wait for more solves before recomputing, so bursts of solves are handled at once
+const DEBOUNCE: Duration = Duration::from_secs(2);
+/// Points also depend on the number of competitors, so everything is recomputed regularly
+const FULL_RECOMPUTE_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Challenges to recompute, None meaning all of them
+static RECOMPUTE_QUEUE: OnceLock<mpsc::UnboundedSender<Option<String>>> = OnceLock::new();
+
+/// Schedules recomputing the points of all solves of a challenge (or of all challenges if None).
+pub fn request_recompute(challenge_id: Option<String>) {
+    match RECOMPUTE_QUEUE.get() {
+        Some(queue) => {
+            let _ = queue.send(challenge_id);
+        }
+        None => tracing::warn!("Points worker is not running, points will not be updated"),
+    }
+}
+
+/// Recalculates and stores the points of every solve of the given challenges.
+async fn recompute_points(
+    db_pool: &DbPool,
+    mut challs_client: ChallengesServiceClient<crate::graphql::GrpcChannel>,
+    challenge_ids: Option<HashSet<String>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use crate::db::schema::{solves, teams, users};
+
+    let total_competitors = crate::graphql::count_competitors(db_pool)
+        .await
+        .map_err(|e| e.message().to_string())?;
+
+    let mut conn = db_pool.get().await?;
+    // Playtest solves and solves of hidden users and of hidden or disqualified teams don't count,
+    // so they don't lower the points of others. Solves stay with the team they were made for.
+    let mut query = solves::table
+        .inner_join(users::table)
+        .left_join(teams::table.on(teams::id.nullable().eq(solves::team_id)))
+        .select((solves::id, solves::challenge_id, users::id, solves::team_id))
+        .filter(solves::playtest.eq(false))
+        .filter(users::hidden.eq(false))
+        .filter(
+            teams::hidden
+                .nullable()
+                .eq(false)
+                .or(teams::id.nullable().is_null()),
+        )
+        .filter(
+            teams::disqualified
+                .nullable()
+                .eq(false)
+                .or(teams::id.nullable().is_null()),
+        )
+        .order(solves::solved_at.asc())
+        .into_boxed();
+    if let Some(challenge_ids) = &challenge_ids {
+        query = query.filter(solves::challenge_id.eq_any(challenge_ids));
+    }
+    let rows: Vec<(uuid::Uuid, String, uuid::Uuid, Option<uuid::Uuid>)> =
+        query.load(&mut conn).await?;
+
+    // Actors in the order they first solved each challenge
+    let mut solvers: HashMap<&str, Vec<uuid::Uuid>> = HashMap::new();
+    // Solves that count, the others were solved by a teammate before and are worth nothing
+    let mut counted = vec![];
+    let mut duplicates = vec![];
+    for (solve_id, challenge_id, user_id, team_id) in &rows {
+        let actor_id = team_id.unwrap_or(*user_id);
+        let challenge_solvers = solvers.entry(challenge_id).or_default();
+        if challenge_solvers.contains(&actor_id) {
+            duplicates.push(*solve_id);
+        } else {
+            challenge_solvers.push(actor_id);
+            counted.push((*solve_id, challenge_id.as_str(), challenge_solvers.len()));
+        }
+    }
+
+    let queries = counted
+        .iter()
+        .map(|(_, challenge_id, nth_solve)| PointsQuery {
+            challenge_id: challenge_id.to_string(),
+            solve: Some(SolvedChallenge {
+                actor_nth_solve: *nth_solve as i32,
+                total_solves: solvers[challenge_id].len() as i32,
+            }),
+        })
+        .collect();
+    let points = challs_client
+        .calculate_points(CalculatePointsRequest {
+            queries,
+            total_competitors: total_competitors as u64,
+        })
+        .await?
+        .into_inner()
+        .points;
+
+    // The response is in the same order as the queries
+    let updates: Vec<(uuid::Uuid, i32)> = counted
+        .iter()
+        .zip(points)
+        .map(|((solve_id, _, _), points)| (*solve_id, points as i32))
+        .chain(duplicates.into_iter().map(|solve_id| (solve_id, 0)))
+        .collect();
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        async move {
+            for (solve_id, points) in updates {
+                diesel::update(solves::table.filter(solves::id.eq(solve_id)))
+                    .set(solves::awarded_points.eq(Some(points)))
+                    .execute(conn)
+                    .await?;
+            }
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Starts the background task that keeps the stored points of all solves and the precomputed scoreboard up to date.
+pub fn start_points_worker(tasks: &mut JoinSet<()>, ctx: BaseContext) {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    if RECOMPUTE_QUEUE.set(tx).is_err() {
+        tracing::warn!("Points worker is already running");
+        return;
+    }
+
+    tasks.spawn(async move {
+        let mut full_recompute = tokio::time::interval(FULL_RECOMPUTE_INTERVAL);
+        loop {
+            let mut challenge_ids = tokio::select! {
+                _ = full_recompute.tick() => None,
+                Some(challenge_id) = rx.recv() => {
+                    tokio::time::sleep(DEBOUNCE).await;
+                    challenge_id.map(|id| HashSet::from([id]))
+                }
+                else => break,
+            };
+            while let Ok(challenge_id) = rx.try_recv() {
+                match (&mut challenge_ids, challenge_id) {
+                    (Some(ids), Some(challenge_id)) => {
+                        ids.insert(challenge_id);
+                    }
+                    _ => challenge_ids = None,
+                }
+            }
+
+            let challs_client = ChallengesServiceClient::new(ctx.grpc_client.clone());
+            if let Err(e) = recompute_points(&ctx.db_pool, challs_client, challenge_ids).await {
+                tracing::error!("Failed to recompute points: {}", e);
+            }
+            if let Err(e) = super::refresh_snapshot(&ctx).await {
+                tracing::error!("Failed to update scoreboard: {}", e.message());
+            }
+        }
+    });
+}
@@ -34,6 +34,17 @@ pub async fn create_session(
     key: &SigningKey,
 ) -> juniper::FieldResult<SessionCredentials> {
     let session_token = uuid::Uuid::now_v7().to_string();
+    let session = diesel::insert_into(crate::db::schema::sessions::table)
+        .values(crate::db::models::NewSession {
+            expires_at: chrono::Utc::now() + chrono::Duration::days(7),
+            user_agent: Some(ctx.get_user_agent().to_string()),
+            ip_address: Some(ctx.get_ip_net()),
+            session_token: session_token.clone(),
+            user_id: Some(uid),
+        })
+        .get_result::<crate::db::models::Session>(&mut ctx.get_db_conn().await)
+        .await?;
+
     let access_token = generate_jwt(
         &JwtPayload::new_with_duration(
             uid,
@@ -43,28 +54,13 @@ pub async fn create_session(
                 username,
                 team_id,
                 team_slug,
+                session_id: Some(session.id),
             },
             Duration::from_mins(10),
         ),
         key,
     )?;
 
-    let session = diesel::insert_into(crate::db::schema::sessions::table)
-        .values(crate::db::models::NewSession {
-            expires_at: chrono::Utc::now() + chrono::Duration::days(7),
-            user_agent: Some(ctx.get_user_agent().to_string()),
-            ip_address: Some(match ctx.get_ip() {
-                // These functions only return an Err() if prefix_len is too long, but the ones here are harcoded
-                // Unless the IP standard changes, this will not panic
-                std::net::IpAddr::V4(_) => ipnet::IpNet::new(*ctx.get_ip(), 32).unwrap(),
-                std::net::IpAddr::V6(_) => ipnet::IpNet::new(*ctx.get_ip(), 128).unwrap(),
-            }),
-            session_token: session_token.clone(),
-            user_id: Some(uid),
-        })
-        .get_result::<crate::db::models::Session>(&mut ctx.get_db_conn().await)
-        .await?;
-
     let refresh_token = generate_jwt(
         &JwtPayload::new_with_exp_ts(
             uid,
@@ -127,7 +123,8 @@ pub async fn refresh_session(
                 role: user.role,
                 username: user.username,
                 team_id: user.team_id,
-                team_slug: team.map(|t| t.name),
+                team_slug: team.map(|t| t.slug),
+                session_id: Some(current_session.id),
             },
             Duration::from_mins(10),
         ),
@@ -142,12 +139,7 @@ pub async fn refresh_session(
         crate::db::schema::sessions::session_token.eq(new_session_token.to_string()),
         crate::db::schema::sessions::expires_at.eq(chrono::Utc::now() + chrono::Duration::days(7)),
         crate::db::schema::sessions::user_agent.eq(Some(ctx.get_user_agent().to_string())),
-        crate::db::schema::sessions::ip_address.eq(Some(match ctx.get_ip() {
-            // These functions only return an Err() if prefix_len is too long, but the ones here are harcoded
-            // Unless the IP standard changes, this will not panic
-            std::net::IpAddr::V4(_) => ipnet::IpNet::new(*ctx.get_ip(), 32).unwrap(),
-            std::net::IpAddr::V6(_) => ipnet::IpNet::new(*ctx.get_ip(), 128).unwrap(),
-        })),
+        crate::db::schema::sessions::ip_address.eq(Some(ctx.get_ip_net())),
     ))
     .get_result::<crate::db::models::Session>(&mut con)
     .await?;
@@ -0,0 +1,422 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::collections::HashMap;
+
+use diesel::{
+    prelude::*,
+    sql_types::{Array, BigInt, Nullable, Text, Timestamptz, Uuid},
+};
+use diesel_async::RunQueryDsl;
+use juniper::{FieldResult, GraphQLObject};
+
+use crate::{
+    db::models::UserRole,
+    graphql::Context,
+    manager_api::{GetInstanceMetricsRequest, InstanceMetrics, ListInstancesRequest},
+};
+
+/// Number of users listed in EventStats.top_invalid_submitters
+const TOP_INVALID_SUBMITTERS: i64 = 10;
+
+/// Solves and invalid submissions of users and teams that aren't hidden, without playtest solves
+const VISIBLE_SUBMISSIONS: &str = "visible_users AS (
+        SELECT u.id FROM users u
+        LEFT JOIN teams t ON t.id = u.team_id
+        WHERE NOT u.hidden AND NOT COALESCE(t.hidden OR t.disqualified, FALSE)
+    ),
+    visible_solves AS (
+        SELECT s.* FROM solves s
+        INNER JOIN users u ON u.id = s.user_id
+        LEFT JOIN teams t ON t.id = s.team_id
+        WHERE NOT s.playtest AND NOT u.hidden AND NOT COALESCE(t.hidden OR t.disqualified, FALSE)
+    ),
+    visible_invalid_submissions AS (
+        SELECT * FROM invalid_submissions WHERE user_id IN (SELECT id FROM visible_users)
+    )";
+
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct ChallengeStats {
+    pub challenge_id: String,
+    pub name: String,
+    pub categories: Vec<String>,
+    pub solves: i32,
+    pub invalid_submissions: i32,
+    /// Share of submissions for this challenge that were correct, null if there were none
+    pub success_rate: Option<f64>,
+    pub active_instances: i32,
+}
+
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct CategoryStats {
+    pub category: String,
+    pub challenges: i32,
+    pub solves: i32,
+}
+
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct RegistrationCount {
+    /// Day in UTC, formatted as YYYY-MM-DD
+    pub date: String,
+    pub count: i32,
+}
+
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct InvalidSubmitter {
+    pub user_id: String,
+    pub username: String,
+    pub team_slug: Option<String>,
+    pub invalid_submissions: i32,
+}
+
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct EventStats {
+    pub total_solves: i32,
+    pub total_invalid_submissions: i32,
+    /// Share of all submissions that were correct, null if there were none
+    pub success_rate: Option<f64>,
+    pub active_instances: i32,
+    pub challenges: Vec<ChallengeStats>,
+    pub categories: Vec<CategoryStats>,
+    /// Registrations per day, oldest first
+    pub registrations: Vec<RegistrationCount>,
+    /// Users with the most incorrect flag submissions
+    pub top_invalid_submitters: Vec<InvalidSubmitter>,
+}
+
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct PodUsage {
+    pub pod: String,
+    pub cpu_millicores: i32,
+    /// Floating point, as GraphQL integers can't hold more than 2 GiB
+    pub memory_bytes: f64,
+}
+
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct InstanceUsage {
+    pub challenge_id: String,
+    pub actor: String,
+    pub instance_id: String,
+    pub cpu_millicores: i32,
+    pub memory_bytes: f64,
+    pub pods: Vec<PodUsage>,
+}
+
+#[derive(GraphQLObject, Debug, Clone, PartialEq)]
+pub struct ChallengeUsage {
+    pub challenge_id: String,
+    pub instances: i32,
+    pub cpu_millicores: i32,
+    pub memory_bytes: f64,
+}
+
+/// CPU and memory currently used by instances, as measured by metrics-server
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct ResourceUsage {
+    pub cpu_millicores: i32,
+    pub memory_bytes: f64,
+    /// Usage summed up per challenge, highest CPU usage first
+    pub challenges: Vec<ChallengeUsage>,
+    pub instances: Vec<InstanceUsage>,
+}
+
+#[derive(QueryableByName)]
+struct ChallengeCountsRow {
+    #[diesel(sql_type = Text)]
+    challenge_id: String,
+    #[diesel(sql_type = BigInt)]
+    solves: i64,
+    #[diesel(sql_type = BigInt)]
+    invalid_submissions: i64,
+}
+
+#[derive(QueryableByName)]
+struct CategoryCountsRow {
+    #[diesel(sql_type = Text)]
+    category: String,
+    #[diesel(sql_type = BigInt)]
+    challenges: i64,
+    #[diesel(sql_type = BigInt)]
+    solves: i64,
+}
+
+#[derive(QueryableByName)]
+struct RegistrationsRow {
+    #[diesel(sql_type = Timestamptz)]
+    day: chrono::DateTime<chrono::Utc>,
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+#[derive(QueryableByName)]
+struct InvalidSubmitterRow {
+    #[diesel(sql_type = Uuid)]
+    user_id: uuid::Uuid,
+    #[diesel(sql_type = Text)]
+    username: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    team_slug: Option<String>,
+    #[diesel(sql_type = BigInt)]
+    invalid_submissions: i64,
+}
+
+#[derive(QueryableByName)]
+struct TotalsRow {
+    #[diesel(sql_type = BigInt)]
+    solves: i64,
+    #[diesel(sql_type = BigInt)]
+    invalid_submissions: i64,
+}
+
+fn success_rate(solves: i64, invalid_submissions: i64) -> Option<f64> {
+    let total = solves + invalid_submissions;
+    (total > 0).then(|| solves as f64 / total as f64)
+}
+
+fn usage_per_challenge(instances: &[InstanceMetrics]) -> Vec<ChallengeUsage> {
+    let mut challenges: HashMap<&str, ChallengeUsage> = HashMap::new();
+    for instance in instances {
+        let usage = challenges
+            .entry(&instance.challenge_id)
+            .or_insert_with(|| ChallengeUsage {
+                challenge_id: instance.challenge_id.clone(),
+                instances: 0,
+                cpu_millicores: 0,
+                memory_bytes: 0.0,
+            });
+        usage.instances += 1;
+        usage.cpu_millicores += instance.cpu_millicores as i32;
+        usage.memory_bytes += instance.memory_bytes as f64;
+    }
+    let mut challenges = challenges.into_values().collect::<Vec<_>>();
+    challenges.sort_by(|a, b| {
+        b.cpu_millicores
+            .cmp(&a.cpu_millicores)
+            .then_with(|| a.challenge_id.cmp(&b.challenge_id))
+    });
+    challenges
+}
+
+pub async fn get_resource_usage(
+    ctx: &Context,
+    challenge_id: Option<String>,
+) -> FieldResult<ResourceUsage> {
+    ctx.require_role_min(UserRole::Admin)?;
+
+    let instances = ctx
+        .challenges_client()
+        .get_instance_metrics(GetInstanceMetricsRequest { challenge_id })
+        .await?
+        .into_inner()
+        .instances;
+    let challenges = usage_per_challenge(&instances);
+    Ok(ResourceUsage {
+        cpu_millicores: challenges.iter().map(|c| c.cpu_millicores).sum(),
+        memory_bytes: challenges.iter().map(|c| c.memory_bytes).sum(),
+        challenges,
+        instances: instances
+            .into_iter()
+            .map(|instance| InstanceUsage {
+                challenge_id: instance.challenge_id,
+                actor: instance.actor,
+                instance_id: instance.instance_id,
+                cpu_millicores: instance.cpu_millicores as i32,
+                memory_bytes: instance.memory_bytes as f64,
+                pods: instance
+                    .pods
+                    .into_iter()
+                    .map(|pod| PodUsage {
+                        pod: pod.pod,
+                        cpu_millicores: pod.cpu_millicores as i32,
+                        memory_bytes: pod.memory_bytes as f64,
+                    })
+                    .collect(),
+            })
+            .collect(),
+    })
+}
+
+pub async fn get_event_stats(ctx: &Context) -> FieldResult<EventStats> {
+    ctx.require_role_min(UserRole::Admin)?;
+
+    let challenges = crate::graphql::handlers::challenges::get_challenges(ctx).await?;
+    let instances = ctx
+        .challenges_client()
+        .list_instances(ListInstancesRequest { challenge_id: None })
+        .await?
+        .into_inner()
+        .instances;
+    let mut instance_counts: HashMap<String, i32> = HashMap::new();
+    for instance in instances.iter().filter(|i| !i.is_terminating) {
+        *instance_counts
+            .entry(instance.challenge_id.clone())
+            .or_default() += 1;
+    }
+
+    let challenge_ids = challenges.iter().map(|c| c.id.clone()).collect::<Vec<_>>();
+    // One row per (challenge, category) pair, passed to the database as two parallel arrays
+    let (category_challenge_ids, category_names): (Vec<String>, Vec<String>) = challenges
+        .iter()
+        .flat_map(|c| c.categories.iter().map(|cat| (c.id.clone(), cat.clone())))
+        .unzip();
+
+    let conn = &mut ctx.get_db_conn().await;
+    let challenge_counts = diesel::sql_query(format!(
+        "WITH {VISIBLE_SUBMISSIONS}
+        SELECT c.challenge_id,
+            COALESCE(s.solves, 0) AS solves,
+            COALESCE(i.invalid_submissions, 0) AS invalid_submissions
+        FROM unnest($1::text[]) AS c(challenge_id)
+        LEFT JOIN (
+            SELECT challenge_id, COUNT(*) AS solves FROM visible_solves GROUP BY challenge_id
+        ) s USING (challenge_id)
+        LEFT JOIN (
+            SELECT challenge_id, COUNT(*) AS invalid_submissions
+            FROM visible_invalid_submissions GROUP BY challenge_id
+        ) i USING (challenge_id)",
+    ))
+    .bind::<Array<Text>, _>(&challenge_ids)
+    .load::<ChallengeCountsRow>(conn)
+    .await?
+    .into_iter()
+    .map(|row| (row.challenge_id.clone(), row))
+    .collect::<HashMap<_, _>>();
+
+    let categories = diesel::sql_query(format!(
+        "WITH {VISIBLE_SUBMISSIONS}
+        SELECT c.category,
+            COUNT(DISTINCT c.challenge_id) AS challenges,
+            COALESCE(SUM(s.solves), 0)::BIGINT AS solves
+        FROM unnest($1::text[], $2::text[]) AS c(challenge_id, category)
+        LEFT JOIN (
+            SELECT challenge_id, COUNT(*) AS solves FROM visible_solves GROUP BY challenge_id
+        ) s USING (challenge_id)
+        GROUP BY c.category
+        ORDER BY c.category",
+    ))
+    .bind::<Array<Text>, _>(&category_challenge_ids)
+    .bind::<Array<Text>, _>(&category_names)
+    .load::<CategoryCountsRow>(conn)
+    .await?;
+
+    let registrations = diesel::sql_query(
+        "SELECT date_trunc('day', created_at, 'UTC') AS day, COUNT(*) AS count
+        FROM users
+        GROUP BY day
+        ORDER BY day",
+    )
+    .load::<RegistrationsRow>(conn)
+    .await?;
+
+    let top_invalid_submitters = diesel::sql_query(
+        "SELECT u.id AS user_id, u.username, t.slug AS team_slug, COUNT(*) AS invalid_submissions
+        FROM invalid_submissions i
+        INNER JOIN users u ON u.id = i.user_id
+        LEFT JOIN teams t ON t.id = u.team_id
+        WHERE NOT u.hidden AND NOT COALESCE(t.hidden OR t.disqualified, FALSE)
+        GROUP BY u.id, u.username, t.slug
+        ORDER BY invalid_submissions DESC, u.username
+        LIMIT $1",
+    )
+    .bind::<BigInt, _>(TOP_INVALID_SUBMITTERS)
+    .load::<InvalidSubmitterRow>(conn)
+    .await?;
+
+    let totals = diesel::sql_query(format!(
+        "WITH {VISIBLE_SUBMISSIONS}
+        SELECT (SELECT COUNT(*) FROM visible_solves) AS solves,
+            (SELECT COUNT(*) FROM visible_invalid_submissions) AS invalid_submissions",
+    ))
+    .get_result::<TotalsRow>(conn)
+    .await?;
+
+    Ok(EventStats {
+        total_solves: totals.solves as i32,
+        total_invalid_submissions: totals.invalid_submissions as i32,
+        success_rate: success_rate(totals.solves, totals.invalid_submissions),
+        active_instances: instance_counts.values().sum(),
+        challenges: challenges
+            .into_iter()
+            .map(|c| {
+                let (solves, invalid_submissions) = challenge_counts
+                    .get(&c.id)
+                    .map(|row| (row.solves, row.invalid_submissions))
+                    .unwrap_or_default();
+                ChallengeStats {
+                    active_instances: instance_counts.get(&c.id).copied().unwrap_or(0),
+                    challenge_id: c.id,
+                    name: c.name,
+                    categories: c.categories,
+                    solves: solves as i32,
+                    invalid_submissions: invalid_submissions as i32,
+                    success_rate: success_rate(solves, invalid_submissions),
+                }
+            })
+            .collect(),
+        categories: categories
+            .into_iter()
+            .map(|row| CategoryStats {
+                category: row.category,
+                challenges: row.challenges as i32,
+                solves: row.solves as i32,
+            })
+            .collect(),
+        registrations: registrations
+            .into_iter()
+            .map(|row| RegistrationCount {
+                date: row.day.format("%Y-%m-%d").to_string(),
+                count: row.count as i32,
+            })
+            .collect(),
+        top_invalid_submitters: top_invalid_submitters
+            .into_iter()
+            .map(|row| InvalidSubmitter {
+                user_id: row.user_id.to_string(),
+                username: row.username,
+                team_slug: row.team_slug,
+                invalid_submissions: row.invalid_submissions as i32,
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instance(challenge_id: &str, cpu_millicores: u64, memory_bytes: u64) -> InstanceMetrics {
+        InstanceMetrics {
+            challenge_id: challenge_id.to_string(),
+            cpu_millicores,
+            memory_bytes,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_usage_per_challenge() {
+        let usage = usage_per_challenge(&[
+            instance("web", 100, 1024),
+            instance("pwn", 500, 2048),
+            instance("web", 200, 1024),
+        ]);
+        assert_eq!(
+            usage,
+            vec![
+                ChallengeUsage {
+                    challenge_id: "pwn".to_string(),
+                    instances: 1,
+                    cpu_millicores: 500,
+                    memory_bytes: 2048.0,
+                },
+                ChallengeUsage {
+                    challenge_id: "web".to_string(),
+                    instances: 2,
+                    cpu_millicores: 300,
+                    memory_bytes: 2048.0,
+                },
+            ]
+        );
+    }
+}
@@ -4,7 +4,11 @@
 
 use juniper::graphql_object;
 
-use crate::db::models::{Team, User};
+use crate::db::models::{Team, TeamDetailsChangeset, User, UserRole};
+use crate::graphql::handlers::name_policy::{NameKind, map_name_taken, validate_name};
+use crate::graphql::handlers::scoreboard::{ScoreboardSolve, get_scoreboard_entry};
+use crate::graphql::handlers::users::email_preferences::{TeamEmailEvent, email_team_members};
+use crate::graphql::handlers::users::invite_codes::get_bound_division;
 
 use diesel::prelude::*;
 use diesel_async::RunQueryDsl;
@@ -23,6 +27,37 @@ impl Team {
         &self.slug
     }
 
+    pub fn avatar_url(&self, ctx: &crate::graphql::Context) -> Option<String> {
+        self.avatar_path
+            .as_deref()
+            .map(|path| ctx.base.storage.public_url(path))
+    }
+
+    /// ISO 3166-1 alpha-2 country code, e.g. "DE"
+    pub fn country_code(&self) -> Option<&str> {
+        self.country_code.as_deref()
+    }
+
+    /// University, company or club the team belongs to
+    pub fn affiliation(&self) -> Option<&str> {
+        self.affiliation.as_deref()
+    }
+
+    /// ID of the division the team competes in
+    pub fn division(&self) -> Option<&str> {
+        self.division.as_deref()
+    }
+
+    /// Whether the team's solves are left out of the scoreboard and statistics
+    pub fn hidden(&self) -> bool {
+        self.hidden
+    }
+
+    /// Whether the team was disqualified and left out of the scoreboard
+    pub fn disqualified(&self) -> bool {
+        self.disqualified
+    }
+
     pub fn join_code(&self, ctx: &crate::graphql::Context) -> juniper::FieldResult<Option<&str>> {
         if ctx.user.as_ref().is_some_and(|u| {
             u.role == crate::db::models::UserRole::Admin || u.team_id == Some(self.id)
@@ -37,12 +72,84 @@ impl Team {
     }
 
     pub async fn members(&self, ctx: &crate::graphql::Context) -> juniper::FieldResult<Vec<User>> {
-        use crate::db::schema::users::dsl::*;
-        let member_records = users
-            .filter(team_id.eq(self.id))
-            .load::<User>(&mut ctx.get_db_conn().await)
-            .await?;
-        Ok(member_records)
+        ctx.loaders.team_members.get(self.id).await
+    }
+
+    /// Current rank on the scoreboard, or null if the team has not solved anything yet
+    pub async fn rank(&self, ctx: &crate::graphql::Context) -> juniper::FieldResult<Option<i32>> {
+        Ok(get_scoreboard_entry(ctx, self.id).await?.map(|e| e.rank))
+    }
+
+    pub async fn score(&self, ctx: &crate::graphql::Context) -> juniper::FieldResult<i32> {
+        Ok(get_scoreboard_entry(ctx, self.id)
+            .await?
+            .map(|e| e.points)
+            .unwrap_or(0))
+    }
+
+    /// Solved challenges with their solve time and awarded points, respecting the scoreboard freeze
+    pub async fn solves(
+        &self,
+        ctx: &crate::graphql::Context,
+    ) -> juniper::FieldResult<Vec<ScoreboardSolve>> {
+        Ok(get_scoreboard_entry(ctx, self.id)
+            .await?
+            .map(|e| e.solves)
+            .unwrap_or_default())
+    }
+}
+
+/// Maximum length of a team's affiliation, in characters
+const MAX_AFFILIATION_LENGTH: usize = 100;
+
+/// Checks that a country code is made of two letters and returns it in uppercase.
+fn validate_country_code(code: &str) -> juniper::FieldResult<String> {
+    let code = code.trim();
+    if code.len() != 2 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(juniper::FieldError::new(
+            "Country code must be an ISO 3166-1 alpha-2 code, e.g. DE",
+            juniper::Value::null(),
+        ));
+    }
+    Ok(code.to_ascii_uppercase())
+}
+
+/// Trims the affiliation, treating an empty one as no affiliation.
+fn validate_affiliation(affiliation: &str) -> juniper::FieldResult<Option<String>> {
+    let affiliation = affiliation.trim();
+    if affiliation.chars().count() > MAX_AFFILIATION_LENGTH {
+        return Err(juniper::FieldError::new(
+            format!(
+                "Affiliation must be at most {} characters long",
+                MAX_AFFILIATION_LENGTH
+            ),
+            juniper::Value::null(),
+        ));
+    }
+    Ok((!affiliation.is_empty()).then(|| affiliation.to_string()))
+}
+
+/// Checks that the division exists in the event config.
+///
+/// If the event has divisions, every team has to choose one.
+async fn validate_division(
+    ctx: &crate::graphql::Context,
+    division: Option<String>,
+) -> juniper::FieldResult<Option<String>> {
+    let event_config = crate::graphql::handlers::event::get_event_config(ctx).await?;
+    match division {
+        None if !event_config.divisions.is_empty() => Err(juniper::FieldError::new(
+            "Please choose a division",
+            juniper::Value::null(),
+        )),
+        None => Ok(None),
+        Some(division) if event_config.divisions.iter().any(|d| d.id == division) => {
+            Ok(Some(division))
+        }
+        Some(division) => Err(juniper::FieldError::new(
+            format!("Unknown division {}", division),
+            juniper::Value::null(),
+        )),
     }
 }
 
@@ -68,6 +175,17 @@ pub async fn join_team_with_code(
             .first::<Team>(&mut ctx.get_db_conn().await)
             .await?
     };
+    if let Some(bound) = get_bound_division(ctx, current_user.user_id).await?
+        && team_record.division.as_ref() != Some(&bound)
+    {
+        return Err(juniper::FieldError::new(
+            format!(
+                "Your invite code only allows joining teams in the {} division",
+                bound
+            ),
+            juniper::Value::null(),
+        ));
+    }
 
     {
         use crate::db::schema::users::dsl::*;
@@ -77,6 +195,9 @@ pub async fn join_team_with_code(
             .execute(&mut ctx.get_db_conn().await)
             .await?;
     }
+    email_team_members(ctx, team_record.id, TeamEmailEvent::MemberJoined);
+    // A player joining a team is one competitor less, which changes dynamic points
+    crate::graphql::handlers::scoreboard::points::request_recompute(None);
 
     Ok(team_record)
 }
@@ -86,8 +207,32 @@ pub async fn create_team(
     name: String,
     slug: String,
     create_join_code: bool,
+    country_code: Option<String>,
+    affiliation: Option<String>,
+    division: Option<String>,
 ) -> juniper::FieldResult<Team> {
     let current_user = ctx.require_authentication()?;
+    let bound_division = get_bound_division(ctx, current_user.user_id).await?;
+    let division = match (division, bound_division) {
+        (Some(division), Some(bound)) if division != bound => {
+            return Err(juniper::FieldError::new(
+                format!(
+                    "Your invite code only allows teams in the {} division",
+                    bound
+                ),
+                juniper::Value::null(),
+            ));
+        }
+        (division, bound) => validate_division(ctx, division.or(bound)).await?,
+    };
+    let country_code = country_code
+        .as_deref()
+        .map(validate_country_code)
+        .transpose()?;
+    let affiliation = match affiliation {
+        Some(affiliation) => validate_affiliation(&affiliation)?,
+        None => None,
+    };
 
     if current_user.team_id.is_some() {
         return Err(juniper::FieldError::new(
@@ -95,6 +240,9 @@ pub async fn create_team(
             juniper::Value::null(),
         ));
     }
+    let is_staff = ctx.role().is_some_and(|r| r >= UserRole::Author);
+    let name = validate_name(ctx, NameKind::TeamName, &name, is_staff).await?;
+    let slug = validate_name(ctx, NameKind::TeamSlug, &slug, is_staff).await?;
 
     let new_team = crate::db::models::NewTeam {
         name,
@@ -107,6 +255,9 @@ pub async fn create_team(
         } else {
             None
         },
+        country_code,
+        affiliation,
+        division,
     };
 
     let inserted_team = {
@@ -116,7 +267,8 @@ pub async fn create_team(
             .values(&new_team)
             .returning(Team::as_returning())
             .get_result(&mut ctx.get_db_conn().await)
-            .await?
+            .await
+            .map_err(map_name_taken)?
     };
 
     {
@@ -127,6 +279,7 @@ pub async fn create_team(
             .execute(&mut ctx.get_db_conn().await)
             .await?;
     }
+    crate::graphql::handlers::scoreboard::points::request_recompute(None);
 
     Ok(inserted_team)
 }
@@ -166,8 +319,11 @@ pub async fn leave_team(ctx: &crate::graphql::Context) -> juniper::FieldResult<b
             diesel::delete(teams_dsl::teams.filter(teams_dsl::id.eq(team_id_val)))
                 .execute(&mut ctx.get_db_conn().await)
                 .await?;
+        } else {
+            email_team_members(ctx, team_id_val, TeamEmailEvent::MemberLeft);
         }
     }
+    crate::graphql::handlers::scoreboard::points::request_recompute(None);
 
     Ok(true)
 }
@@ -196,6 +352,87 @@ pub async fn enable_join_code(ctx: &crate::graphql::Context) -> juniper::FieldRe
     Ok(new_code)
 }
 
+/// Changes the details of the current user's team.
+///
+/// Outer None keeps a value, Some(None) removes it.
+pub async fn update_team(
+    ctx: &crate::graphql::Context,
+    country_code: Option<Option<String>>,
+    affiliation: Option<Option<String>>,
+) -> juniper::FieldResult<Team> {
+    let current_user = ctx.require_authentication()?;
+
+    let team_id_val = current_user
+        .team_id
+        .ok_or_else(|| juniper::FieldError::new("User is not in a team", juniper::Value::null()))?;
+
+    let changes = TeamDetailsChangeset {
+        country_code: country_code
+            .map(|code| code.as_deref().map(validate_country_code).transpose())
+            .transpose()?,
+        affiliation: affiliation
+            .map(|affiliation| {
+                Ok::<_, juniper::FieldError>(match affiliation {
+                    Some(affiliation) => validate_affiliation(&affiliation)?,
+                    None => None,
+                })
+            })
+            .transpose()?,
+        updated_at: chrono::Utc::now(),
+    };
+
+    use crate::db::schema::teams;
+
+    let team_record = diesel::update(teams::table.filter(teams::id.eq(team_id_val)))
+        .set(&changes)
+        .returning(Team::as_returning())
+        .get_result(&mut ctx.get_db_conn().await)
+        .await?;
+    // The scoreboard shows the country and affiliation of teams
+    crate::graphql::handlers::scoreboard::points::request_recompute(None);
+
+    Ok(team_record)
+}
+
+/// Moves a team to another division, e.g. if it turns out not to be eligible (admin only).
+pub async fn set_team_division(
+    ctx: &crate::graphql::Context,
+    team_id_val: uuid::Uuid,
+    division: Option<String>,
+) -> juniper::FieldResult<Team> {
+    use crate::db::schema::teams;
+
+    ctx.require_role_min(crate::db::models::UserRole::Admin)?;
+    let admin = ctx.require_authentication()?;
+    let event_config = crate::graphql::handlers::event::get_event_config(ctx).await?;
+    if let Some(division) = &division
+        && !event_config.divisions.iter().any(|d| &d.id == division)
+    {
+        return Err(juniper::FieldError::new(
+            format!("Unknown division {}", division),
+            juniper::Value::null(),
+        ));
+    }
+
+    let team_record = diesel::update(teams::table.filter(teams::id.eq(team_id_val)))
+        .set((
+            teams::division.eq(&division),
+            teams::updated_at.eq(chrono::Utc::now()),
+        ))
+        .returning(Team::as_returning())
+        .get_result(&mut ctx.get_db_conn().await)
+        .await?;
+    tracing::info!(
+        "Team {} moved to division {:?} by {}",
+        team_record.slug,
+        division,
+        admin.username
+    );
+    // The scoreboard snapshot contains the divisions of all teams
+    crate::graphql::handlers::scoreboard::points::request_recompute(None);
+    Ok(team_record)
+}
+
 pub async fn disable_join_code(ctx: &crate::graphql::Context) -> juniper::FieldResult<bool> {
     let current_user = ctx.require_authentication()?;
 
@@ -221,3 +458,19 @@ pub async fn get_teams(ctx: &crate::graphql::Context) -> juniper::FieldResult<Ve
 
     Ok(team_records)
 }
+
+pub async fn get_team_by_slug(
+    ctx: &crate::graphql::Context,
+    slug_input: String,
+) -> juniper::FieldResult<Option<Team>> {
+    use crate::db::schema::teams::dsl::*;
+
+    let team_record = teams
+        .filter(slug.eq(slug_input))
+        .select(Team::as_select())
+        .first::<Team>(&mut ctx.get_db_conn().await)
+        .await
+        .optional()?;
+
+    Ok(team_record)
+}
@@ -0,0 +1,407 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use diesel::prelude::*;
+use diesel_async::{AsyncConnection, RunQueryDsl, scoped_futures::ScopedFutureExt};
+use juniper::{FieldResult, graphql_object};
+
+use crate::{
+    db::models::{
+        NewNotification, NewTicket, NewTicketMessage, NotificationKind, Ticket, TicketMessage,
+        TicketStatus, User, UserRole, WebhookEvent,
+    },
+    graphql::{AuthenticatedUser, Context, handlers::challenges::is_listed_author},
+};
+
+const MAX_SUBJECT_LENGTH: usize = 200;
+const MAX_MESSAGE_LENGTH: usize = 10_000;
+
+#[graphql_object]
+impl Ticket {
+    pub fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// The challenge the ticket is about, if any
+    pub fn challenge_id(&self) -> Option<&str> {
+        self.challenge_id.as_deref()
+    }
+
+    pub fn status(&self) -> TicketStatus {
+        self.status
+    }
+
+    /// The player who opened the ticket
+    pub async fn user(&self, ctx: &Context) -> FieldResult<User> {
+        use crate::db::schema::users;
+        let user = users::table
+            .filter(users::id.eq(self.user_id))
+            .first::<User>(&mut ctx.get_db_conn().await)
+            .await?;
+        Ok(user)
+    }
+
+    /// Messages of the ticket, oldest first
+    pub async fn messages(&self, ctx: &Context) -> FieldResult<Vec<TicketMessage>> {
+        use crate::db::schema::ticket_messages;
+        let messages = ticket_messages::table
+            .filter(ticket_messages::ticket_id.eq(self.id))
+            .order(ticket_messages::created_at.asc())
+            .select(TicketMessage::as_select())
+            .load::<TicketMessage>(&mut ctx.get_db_conn().await)
+            .await?;
+        Ok(messages)
+    }
+
+    pub fn created_at(&self) -> String {
+        self.created_at.to_rfc3339()
+    }
+
+    pub fn updated_at(&self) -> String {
+        self.updated_at.to_rfc3339()
+    }
+}
+
+#[graphql_object]
+impl TicketMessage {
+    pub fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    /// None if the author's account was deleted
+    pub async fn author(&self, ctx: &Context) -> FieldResult<Option<User>> {
+        use crate::db::schema::users;
+        let Some(author_id) = self.author_id else {
+            return Ok(None);
+        };
+        let user = users::table
+            .filter(users::id.eq(author_id))
+            .first::<User>(&mut ctx.get_db_conn().await)
+            .await
+            .optional()?;
+        Ok(user)
+    }
+
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    pub fn created_at(&self) -> String {
+        self.created_at.to_rfc3339()
+    }
+}
+
+/// IDs of the challenges the user is listed as an author of.
+async fn authored_challenges(ctx: &Context, user: &AuthenticatedUser) -> FieldResult<Vec<String>> {
+    if user.role < UserRole::Author {
+        return Ok(vec![]);
+    }
+    let challenges = super::challenges::get_challenges(ctx).await?;
+    Ok(challenges
+        .into_iter()
+        .filter(|c| is_listed_author(&c.authors, &user.username))
+        .map(|c| c.id)
+        .collect())
+}
+
+/// Whether the user answers the ticket rather than having opened it
+async fn is_staff_for(
+    ctx: &Context,
+    user: &AuthenticatedUser,
+    ticket: &Ticket,
+) -> FieldResult<bool> {
+    if user.role >= UserRole::Admin {
+        return Ok(true);
+    }
+    let Some(challenge_id) = &ticket.challenge_id else {
+        return Ok(false);
+    };
+    Ok(authored_challenges(ctx, user).await?.contains(challenge_id))
+}
+
+/// Loads a ticket the current user may see, which are their own ones, those about challenges
+/// they authored, and all of them for admins.
+async fn load_ticket(ctx: &Context, ticket_id: uuid::Uuid) -> FieldResult<(Ticket, bool)> {
+    use crate::db::schema::tickets;
+
+    let user = ctx.require_authentication()?;
+    let ticket = tickets::table
+        .filter(tickets::id.eq(ticket_id))
+        .select(Ticket::as_select())
+        .first::<Ticket>(&mut ctx.get_db_conn().await)
+        .await
+        .optional()?;
+    let not_found = || juniper::FieldError::new("Ticket not found", juniper::Value::null());
+    let ticket = ticket.ok_or_else(not_found)?;
+    let is_staff = is_staff_for(ctx, &user, &ticket).await?;
+    if !is_staff && ticket.user_id != user.user_id {
+        return Err(not_found());
+    }
+    Ok((ticket, is_staff))
+}
+
+fn validate_message(body: &str) -> FieldResult<()> {
+    if body.trim().is_empty() || body.chars().count() > MAX_MESSAGE_LENGTH {
+        return Err(juniper::FieldError::new(
+            format!("Messages must have between 1 and {MAX_MESSAGE_LENGTH} characters"),
+            juniper::Value::null(),
+        ));
+    }
+    Ok(())
+}
+
+/// Notifies users about a ticket update, except the one who made it.
+fn notify(
+    ctx: &Context,
+    recipients: Vec<uuid::Uuid>,
+    sender: uuid::Uuid,
+    ticket: &Ticket,
+    title: String,
+    body: String,
+) {
+    let notifications = recipients
+        .into_iter()
+        .filter(|recipient| *recipient != sender)
+        .map(|recipient| NewNotification {
+            user_id: Some(recipient),
+            kind: NotificationKind::TicketUpdate,
+            title: title.clone(),
+            body: body.clone(),
+            challenge_id: ticket.challenge_id.clone(),
+            created_at: None,
+        })
+        .collect();
+    super::notifications::create_notifications_in_background(&ctx.base.db_pool, notifications);
+}
+
+pub async fn get_tickets(ctx: &Context, status: Option<TicketStatus>) -> FieldResult<Vec<Ticket>> {
+    use crate::db::schema::tickets;
+
+    let user = ctx.require_authentication()?;
+    let mut query = tickets::table
+        .order(tickets::updated_at.desc())
+        .select(Ticket::as_select())
+        .into_boxed();
+    if user.role < UserRole::Admin {
+        let authored = authored_challenges(ctx, &user).await?;
+        query = query.filter(
+            tickets::user_id
+                .eq(user.user_id)
+                .or(tickets::challenge_id.eq_any(authored)),
+        );
+    }
+    if let Some(status) = status {
+        query = query.filter(tickets::status.eq(status));
+    }
+    let tickets = query.load::<Ticket>(&mut ctx.get_db_conn().await).await?;
+    Ok(tickets)
+}
+
+pub async fn get_ticket(ctx: &Context, ticket_id: uuid::Uuid) -> FieldResult<Ticket> {
+    Ok(load_ticket(ctx, ticket_id).await?.0)
+}
+
+/// Opens a ticket, e.g. to report a broken challenge, and lets its authors know.
+pub async fn create_ticket(
+    ctx: &Context,
+    subject: String,
+    body: String,
+    challenge_id: Option<String>,
+) -> FieldResult<Ticket> {
+    use crate::db::schema::{ticket_messages, tickets, users};
+
+    let user = ctx.require_authentication()?;
+    let subject = subject.trim().to_string();
+    if subject.is_empty() || subject.chars().count() > MAX_SUBJECT_LENGTH {
+        return Err(juniper::FieldError::new(
+            format!("Subjects must have between 1 and {MAX_SUBJECT_LENGTH} characters"),
+            juniper::Value::null(),
+        ));
+    }
+    validate_message(&body)?;
+    let authors = match &challenge_id {
+        Some(challenge_id) => {
+            let challenge = super::challenges::get_challenges(ctx)
+                .await?
+                .into_iter()
+                .find(|c| &c.id == challenge_id)
+                .ok_or_else(|| {
+                    juniper::FieldError::new("Challenge not found", juniper::Value::null())
+                })?;
+            challenge.authors
+        }
+        None => vec![],
+    };
+
+    let conn = &mut ctx.get_db_conn().await;
+    let author_id = user.user_id;
+    let ticket = conn
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                let ticket = diesel::insert_into(tickets::table)
+                    .values(NewTicket {
+                        user_id: author_id,
+                        subject,
+                        challenge_id,
+                    })
+                    .returning(Ticket::as_returning())
+                    .get_result(conn)
+                    .await?;
+                diesel::insert_into(ticket_messages::table)
+                    .values(NewTicketMessage {
+                        ticket_id: ticket.id,
+                        author_id: Some(author_id),
+                        body,
+                    })
+                    .execute(conn)
+                    .await?;
+                Ok(ticket)
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    if !authors.is_empty() {
+        let author_ids = users::table
+            .filter(users::role.ge(UserRole::Author))
+            .select((users::id, users::username))
+            .load::<(uuid::Uuid, String)>(conn)
+            .await?
+            .into_iter()
+            .filter(|(_, username)| is_listed_author(&authors, username))
+            .map(|(id, _)| id)
+            .collect();
+        notify(
+            ctx,
+            author_ids,
+            user.user_id,
+            &ticket,
+            format!("New ticket: {}", ticket.subject),
+            format!("{} opened a ticket about your challenge.", user.username),
+        );
+    }
+    crate::webhooks::dispatch(
+        &ctx.base.db_pool,
+        WebhookEvent::TicketCreated,
+        serde_json::json!({
+            "ticket_id": ticket.id,
+            "subject": ticket.subject,
+            "challenge_id": ticket.challenge_id,
+            "user_id": user.user_id,
+            "username": user.username,
+        }),
+    );
+    Ok(ticket)
+}
+
+/// Adds a message to a ticket.
+///
+/// Answers by authors or admins mark the ticket as answered, replies by the player reopen it.
+pub async fn reply_to_ticket(
+    ctx: &Context,
+    ticket_id: uuid::Uuid,
+    body: String,
+) -> FieldResult<Ticket> {
+    use crate::db::schema::{ticket_messages, tickets};
+
+    let user = ctx.require_authentication()?;
+    validate_message(&body)?;
+    let (ticket, is_staff) = load_ticket(ctx, ticket_id).await?;
+    let status = if is_staff && ticket.user_id != user.user_id {
+        TicketStatus::Answered
+    } else {
+        TicketStatus::Open
+    };
+
+    let conn = &mut ctx.get_db_conn().await;
+    let author_id = user.user_id;
+    let ticket = conn
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                diesel::insert_into(ticket_messages::table)
+                    .values(NewTicketMessage {
+                        ticket_id,
+                        author_id: Some(author_id),
+                        body,
+                    })
+                    .execute(conn)
+                    .await?;
+                diesel::update(tickets::table.filter(tickets::id.eq(ticket_id)))
+                    .set((
+                        tickets::status.eq(status),
+                        tickets::updated_at.eq(chrono::Utc::now()),
+                    ))
+                    .returning(Ticket::as_returning())
+                    .get_result(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    // Everyone who took part in the conversation hears about the reply
+    let mut recipients = ticket_messages::table
+        .filter(ticket_messages::ticket_id.eq(ticket_id))
+        .filter(ticket_messages::author_id.is_not_null())
+        .select(ticket_messages::author_id.assume_not_null())
+        .distinct()
+        .load::<uuid::Uuid>(conn)
+        .await?;
+    if !recipients.contains(&ticket.user_id) {
+        recipients.push(ticket.user_id);
+    }
+    notify(
+        ctx,
+        recipients,
+        user.user_id,
+        &ticket,
+        format!("New reply to ticket: {}", ticket.subject),
+        format!("{} replied to the ticket.", user.username),
+    );
+    Ok(ticket)
+}
+
+/// Changes the status of a ticket. Players can only close or reopen their own tickets.
+pub async fn set_ticket_status(
+    ctx: &Context,
+    ticket_id: uuid::Uuid,
+    status: TicketStatus,
+) -> FieldResult<Ticket> {
+    use crate::db::schema::tickets;
+
+    let user = ctx.require_authentication()?;
+    let (ticket, is_staff) = load_ticket(ctx, ticket_id).await?;
+    if !is_staff && status == TicketStatus::Answered {
+        return Err(juniper::FieldError::new(
+            "Only challenge authors and admins can mark tickets as answered",
+            juniper::Value::null(),
+        ));
+    }
+    if ticket.status == status {
+        return Ok(ticket);
+    }
+    let ticket = diesel::update(tickets::table.filter(tickets::id.eq(ticket_id)))
+        .set((
+            tickets::status.eq(status),
+            tickets::updated_at.eq(chrono::Utc::now()),
+        ))
+        .returning(Ticket::as_returning())
+        .get_result(&mut ctx.get_db_conn().await)
+        .await?;
+    if status == TicketStatus::Closed {
+        notify(
+            ctx,
+            vec![ticket.user_id],
+            user.user_id,
+            &ticket,
+            format!("Ticket closed: {}", ticket.subject),
+            format!("{} closed the ticket.", user.username),
+        );
+    }
+    Ok(ticket)
+}
@@ -4,11 +4,11 @@
 
 use crate::{
     db::{
-        models::{NewUser, User},
+        models::{ApprovalStatus, NewUser, User},
         schema::users,
     },
     graphql::{
-        Context, captcha::verify_captcha_response, handlers::{event::get_event_config, sessions::SessionCredentials}
+        Context, captcha::verify_captcha_response, handlers::{event::get_event_config, name_policy::{NameKind, map_name_taken, validate_name}, sessions::SessionCredentials}
     },
 };
 use argon2::{
@@ -16,11 +16,18 @@ use argon2::{
     password_hash::{PasswordHasher, SaltString},
 };
 use diesel::prelude::*;
-use diesel_async::RunQueryDsl;
+use diesel_async::{AsyncConnection, RunQueryDsl, scoped_futures::ScopedFutureExt};
 use juniper::FieldResult;
 use rand_core::OsRng;
 
+pub mod approval;
+pub mod data_export;
+pub mod deletion;
 pub mod details;
+pub mod email_preferences;
+pub mod invite_codes;
+pub mod password_policy;
+pub mod roles;
 
 pub async fn create_user(
     username: String,
@@ -29,7 +36,9 @@ pub async fn create_user(
     context: &Context,
     captcha_challenge: Option<String>,
     captcha_response: Option<String>,
+    invite_code: Option<String>,
 ) -> FieldResult<bool> {
+    crate::graphql::handlers::archive::require_not_archived(context).await?;
     let passed_captcha = verify_captcha_response(&captcha_challenge.unwrap_or_default(), &captcha_response.unwrap_or_default()).await?;
     if !passed_captcha {
         return Err(juniper::FieldError::new(
@@ -47,6 +56,12 @@ pub async fn create_user(
     }
     match get_event_config(context).await {
         Ok(event_config) => {
+            if event_config.invite_only && invite_code.is_none() && user_count > 0 {
+                return Err(juniper::FieldError::new(
+                    "Registration requires an invite code",
+                    juniper::graphql_value!({ "code": "INVITE_CODE_REQUIRED" }),
+                ));
+            }
             if let Some(reg_start_time) = event_config.registration_start_time {
                 let now = chrono::Utc::now().timestamp();
                 if now < (reg_start_time as i64) {
@@ -76,6 +91,16 @@ pub async fn create_user(
         }
     }
 
+    // The first user becomes admin and may take a reserved name like admin
+    let username = validate_name(
+        context,
+        NameKind::Username,
+        &username,
+        role == crate::db::models::UserRole::Admin,
+    )
+    .await?;
+    password_policy::check_password(&context.base.config, &password, &[&username, &email]).await?;
+
     let argon2 = Argon2::default();
     let salt = SaltString::generate(&mut OsRng);
 
@@ -91,14 +116,57 @@ pub async fn create_user(
         // TODO: implement email verification
         is_active: true,
         team_id: None,
+        // Without email, users couldn't be told about their approval, so they are approved right away
+        approval_status: if role == crate::db::models::UserRole::Admin
+            || !crate::email::is_configured()
+        {
+            ApprovalStatus::Approved
+        } else {
+            ApprovalStatus::Pending
+        },
+        invite_code_id: None,
     };
 
-    diesel::insert_into(users::table)
-        .values(&new_user)
-        .returning(User::as_returning())
-        .execute(&mut context.get_db_conn().await)
+    let user = context
+        .get_db_conn()
+        .await
+        .transaction::<_, juniper::FieldError, _>(|conn| {
+            async move {
+                let mut new_user = new_user;
+                if let Some(invite_code) = invite_code {
+                    let invite_code = invite_codes::redeem_invite_code(conn, &invite_code).await?;
+                    new_user.invite_code_id = Some(invite_code.id);
+                    if new_user.role != crate::db::models::UserRole::Admin {
+                        new_user.role = invite_code.role.unwrap_or(new_user.role);
+                    }
+                    // Admins already vouched for users they invited
+                    new_user.approval_status = ApprovalStatus::Approved;
+                }
+                let user = diesel::insert_into(users::table)
+                    .values(&new_user)
+                    .returning(User::as_returning())
+                    .get_result(conn)
+                    .await
+                    .map_err(map_name_taken)?;
+                Ok(user)
+            }
+            .scope_boxed()
+        })
         .await?;
 
+    crate::webhooks::dispatch(
+        &context.base.db_pool,
+        crate::db::models::WebhookEvent::Registration,
+        serde_json::json!({
+            "user_id": user.id,
+            "username": user.username,
+            "approval_status": user.approval_status,
+            "created_at": user.created_at.to_rfc3339(),
+        }),
+    );
+    // Approved players count as competitors right away
+    crate::graphql::handlers::scoreboard::points::request_recompute(None);
+
     Ok(true)
 }
 
@@ -106,7 +174,21 @@ pub async fn login_user(
     username: String,
     password: String,
     context: &Context,
+    captcha_challenge: Option<String>,
+    captcha_response: Option<String>,
 ) -> juniper::FieldResult<SessionCredentials> {
+    if context.base.config.captcha_on_login
+        && !verify_captcha_response(
+            &captcha_challenge.unwrap_or_default(),
+            &captcha_response.unwrap_or_default(),
+        )
+        .await?
+    {
+        return Err(juniper::FieldError::new(
+            "CAPTCHA verification failed",
+            juniper::Value::null(),
+        ));
+    }
     let user_and_team: Option<(User, Option<crate::db::models::Team>)> =
         crate::db::schema::users::table
             .filter(crate::db::schema::users::username.eq(&username))
@@ -123,37 +205,148 @@ pub async fn login_user(
             .first(&mut context.get_db_conn().await)
             .await
             .optional()?;
-    match user_and_team {
-        Some((user, team)) => {
-            let parsed_hash = argon2::PasswordHash::new(&user.password_hash)?;
-            if Argon2::default()
-                .verify_password(password.as_bytes(), &parsed_hash)
-                .is_ok()
-            {
-                let signing_key = context.get_signing_key();
-                let session_credentials = crate::graphql::handlers::sessions::create_session(
-                    context,
-                    user.id,
-                    user.role,
-                    user.username,
-                    user.team_id,
-                    team.map(|t| t.name),
-                    signing_key,
-                )
-                .await?;
-                Ok(session_credentials)
-            } else {
-                Err(juniper::FieldError::new(
-                    "Invalid username or password",
-                    juniper::Value::null(),
-                ))
-            }
-        }
-        None => Err(juniper::FieldError::new(
+    let Some((user, team)) = user_and_team else {
+        return Err(juniper::FieldError::new(
             "User not found",
             juniper::Value::null(),
-        )),
+        ));
+    };
+    let parsed_hash = argon2::PasswordHash::new(&user.password_hash)?;
+    if Argon2::default()
+        .verify_password(password.as_bytes(), &parsed_hash)
+        .is_err()
+    {
+        return Err(juniper::FieldError::new(
+            "Invalid username or password",
+            juniper::Value::null(),
+        ));
+    }
+    // Only reported after the password matched, so the status of an account isn't revealed to
+    // anyone who knows its username
+    if !user.is_active {
+        return Err(juniper::FieldError::new(
+            "Account is disabled",
+            juniper::Value::null(),
+        ));
+    }
+    match user.approval_status {
+        ApprovalStatus::Pending => {
+            return Err(juniper::FieldError::new(
+                "Account is awaiting approval by an admin",
+                juniper::Value::null(),
+            ));
+        }
+        ApprovalStatus::Rejected => {
+            return Err(juniper::FieldError::new(
+                "Registration was rejected",
+                juniper::Value::null(),
+            ));
+        }
+        _ => {}
+    }
+    let signing_key = context.get_signing_key();
+    crate::graphql::handlers::sessions::create_session(
+        context,
+        user.id,
+        user.role,
+        user.username,
+        user.team_id,
+        team.map(|t| t.slug),
+        signing_key,
+    )
+    .await
+}
+
+/// Changes the password of the current user and signs out all of their other sessions.
+pub async fn change_password(
+    context: &Context,
+    old_password: String,
+    new_password: String,
+) -> FieldResult<bool> {
+    use crate::db::schema::sessions;
+
+    let current_user = context.require_authentication()?;
+    let conn = &mut context.get_db_conn().await;
+    let user = users::table
+        .filter(users::id.eq(current_user.user_id))
+        .filter(users::is_active.eq(true))
+        .first::<User>(conn)
+        .await?;
+
+    let argon2 = Argon2::default();
+    let parsed_hash = argon2::PasswordHash::new(&user.password_hash)?;
+    if argon2
+        .verify_password(old_password.as_bytes(), &parsed_hash)
+        .is_err()
+    {
+        return Err(juniper::FieldError::new(
+            "Invalid password",
+            juniper::Value::null(),
+        ));
     }
+
+    password_policy::check_password(
+        &context.base.config,
+        &new_password,
+        &[&user.username, &user.email],
+    )
+    .await?;
+
+    // Always hash with the current default parameters, even if the old hash used different ones
+    let salt = SaltString::generate(&mut OsRng);
+    let new_hash = argon2
+        .hash_password(new_password.as_bytes(), &salt)?
+        .to_string();
+
+    let user_id = user.id;
+    let current_session = current_user.session_id;
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        async move {
+            diesel::update(users::table.filter(users::id.eq(user_id)))
+                .set((
+                    users::password_hash.eq(new_hash),
+                    users::updated_at.eq(chrono::Utc::now()),
+                ))
+                .execute(conn)
+                .await?;
+            let user_sessions = sessions::table.filter(sessions::user_id.eq(user_id));
+            match current_session {
+                Some(current_session) => {
+                    diesel::delete(user_sessions.filter(sessions::id.ne(current_session)))
+                        .execute(conn)
+                        .await?;
+                }
+                // Older access tokens don't contain the session id, so all sessions are revoked
+                None => {
+                    diesel::delete(user_sessions).execute(conn).await?;
+                }
+            }
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await?;
+
+    Ok(true)
+}
+
+/// Changes the name of the current user shown on the scoreboard and to team members.
+pub async fn update_display_name(context: &Context, display_name: String) -> FieldResult<User> {
+    let current_user = context.require_authentication()?;
+    let is_staff = context
+        .role()
+        .is_some_and(|r| r >= crate::db::models::UserRole::Author);
+    let display_name =
+        validate_name(context, NameKind::DisplayName, &display_name, is_staff).await?;
+    let user = diesel::update(users::table.filter(users::id.eq(current_user.user_id)))
+        .set((
+            users::display_name.eq(display_name),
+            users::updated_at.eq(chrono::Utc::now()),
+        ))
+        .returning(User::as_returning())
+        .get_result(&mut context.get_db_conn().await)
+        .await?;
+    Ok(user)
 }
 
 pub async fn get_all_users(context: &Context) -> juniper::FieldResult<Vec<User>> {
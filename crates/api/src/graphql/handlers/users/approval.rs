@@ -0,0 +1,84 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use juniper::FieldResult;
+
+use crate::{
+    db::models::{ApprovalStatus, User, UserRole},
+    graphql::Context,
+};
+
+/// Lists all registrations that are waiting for an admin to approve them, oldest first.
+pub async fn get_pending_users(ctx: &Context) -> FieldResult<Vec<User>> {
+    ctx.require_role_min(UserRole::Admin)?;
+    use crate::db::schema::users::dsl::*;
+
+    let pending = users
+        .filter(approval_status.eq(ApprovalStatus::Pending))
+        .order(created_at.asc())
+        .load::<User>(&mut ctx.get_db_conn().await)
+        .await?;
+    Ok(pending)
+}
+
+async fn set_approval_status(
+    ctx: &Context,
+    user_id: uuid::Uuid,
+    status: ApprovalStatus,
+) -> FieldResult<User> {
+    use crate::db::schema::users::dsl::*;
+
+    let user = diesel::update(users.filter(id.eq(user_id)).filter(is_active.eq(true)))
+        .set((
+            approval_status.eq(status),
+            updated_at.eq(chrono::Utc::now()),
+        ))
+        .get_result::<User>(&mut ctx.get_db_conn().await)
+        .await
+        .optional()?;
+    // Only approved players count as competitors
+    crate::graphql::handlers::scoreboard::points::request_recompute(None);
+    user.ok_or_else(|| juniper::FieldError::new("User not found", juniper::Value::null()))
+}
+
+pub async fn approve_user(ctx: &Context, user_id: uuid::Uuid) -> FieldResult<User> {
+    ctx.require_role_min(UserRole::Admin)?;
+    let user = set_approval_status(ctx, user_id, ApprovalStatus::Approved).await?;
+
+    if crate::email::is_configured() {
+        let email = user.email.clone();
+        let body = format!(
+            "Hi {},\n\nyour account has been approved, you can now log in.\n",
+            user.display_name
+        );
+        tokio::spawn(async move {
+            if let Err(e) =
+                crate::email::send_email(&email, "Your account has been approved", body).await
+            {
+                tracing::warn!("Failed to send approval email to {}: {}", email, e);
+            }
+        });
+    }
+    Ok(user)
+}
+
+pub async fn reject_user(ctx: &Context, user_id: uuid::Uuid) -> FieldResult<User> {
+    ctx.require_role_min(UserRole::Admin)?;
+    if ctx.require_authentication()?.user_id == user_id {
+        return Err(juniper::FieldError::new(
+            "You cannot reject your own account",
+            juniper::Value::null(),
+        ));
+    }
+    let user = set_approval_status(ctx, user_id, ApprovalStatus::Rejected).await?;
+
+    // Also sign out the user in case they were approved before
+    use crate::db::schema::sessions;
+    diesel::delete(sessions::table.filter(sessions::user_id.eq(user.id)))
+        .execute(&mut ctx.get_db_conn().await)
+        .await?;
+    Ok(user)
+}
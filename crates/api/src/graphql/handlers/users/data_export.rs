@@ -0,0 +1,304 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use diesel::prelude::*;
+use diesel_async::{
+    AsyncConnection, AsyncPgConnection, RunQueryDsl, scoped_futures::ScopedFutureExt,
+};
+use juniper::{FieldResult, graphql_object};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    db::models::{
+        DataExport, DataExportStatus, EmailPreferences, InvalidSubmission, NewDataExport, Session,
+        Solve, Team, User,
+    },
+    graphql::{BaseContext, Context},
+    jobs::{JobError, JobFuture},
+};
+
+/// Kind of the jobs generating data exports
+pub const DATA_EXPORT_JOB: &str = "data_export";
+/// Exports still pending after this long count as failed, e.g. because every attempt of their job
+/// failed
+const EXPORT_TIMEOUT: chrono::TimeDelta = chrono::TimeDelta::hours(2);
+
+#[derive(Serialize, Deserialize)]
+struct DataExportJob {
+    export_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+}
+
+#[graphql_object]
+impl DataExport {
+    pub fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    pub fn status(&self) -> DataExportStatus {
+        self.status
+    }
+
+    pub fn created_at(&self) -> String {
+        self.created_at.to_rfc3339()
+    }
+
+    pub fn completed_at(&self) -> Option<String> {
+        self.completed_at.map(|t| t.to_rfc3339())
+    }
+
+    /// URL to download the export from, once it is completed
+    pub fn download_url(&self) -> Option<String> {
+        (self.status == DataExportStatus::Completed).then(|| format!("/data-export/{}", self.id))
+    }
+}
+
+/// Collects all personal data stored about a user into a JSON document.
+async fn build_data_export(
+    ctx: &BaseContext,
+    uid: uuid::Uuid,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    use crate::db::schema::{
+        email_preferences, invalid_submissions, sessions, solves, teams, users,
+    };
+
+    let mut conn = ctx.db_pool.get().await?;
+    let user = users::table
+        .filter(users::id.eq(uid))
+        .first::<User>(&mut conn)
+        .await?;
+    let team = match user.team_id {
+        Some(team_id) => teams::table
+            .filter(teams::id.eq(team_id))
+            .select(Team::as_select())
+            .first::<Team>(&mut conn)
+            .await
+            .optional()?,
+        None => None,
+    };
+    let user_email_preferences = email_preferences::table
+        .filter(email_preferences::user_id.eq(uid))
+        .select(EmailPreferences::as_select())
+        .first::<EmailPreferences>(&mut conn)
+        .await
+        .optional()?;
+    let user_sessions = sessions::table
+        .filter(sessions::user_id.eq(uid))
+        .select(Session::as_select())
+        .load::<Session>(&mut conn)
+        .await?;
+    let user_solves = solves::table
+        .filter(solves::user_id.eq(uid))
+        .select(Solve::as_select())
+        .load::<Solve>(&mut conn)
+        .await?;
+    let user_invalid_submissions = invalid_submissions::table
+        .filter(invalid_submissions::user_id.eq(uid))
+        .select(InvalidSubmission::as_select())
+        .load::<InvalidSubmission>(&mut conn)
+        .await?;
+
+    let export = json!({
+        "generated_at": chrono::Utc::now().to_rfc3339(),
+        "profile": {
+            "id": user.id,
+            "username": user.username,
+            "display_name": user.display_name,
+            "email": user.email,
+            "role": user.role,
+            "created_at": user.created_at.to_rfc3339(),
+            "updated_at": user.updated_at.to_rfc3339(),
+            "email_verified_at": user.email_verified_at.map(|t| t.to_rfc3339()),
+            "avatar_url": user.avatar_path.as_deref().map(|path| ctx.storage.public_url(path)),
+        },
+        "team": team.map(|t| json!({
+            "id": t.id,
+            "name": t.name,
+            "slug": t.slug,
+            "country_code": t.country_code,
+            "affiliation": t.affiliation,
+            "division": t.division,
+        })),
+        "email_preferences": user_email_preferences.map(|p| json!({
+            "team_member_joined": p.team_member_joined,
+            "team_member_left": p.team_member_left,
+            "updated_at": p.updated_at.to_rfc3339(),
+        })),
+        "sessions": user_sessions.into_iter().map(|s| json!({
+            "created_at": s.created_at.to_rfc3339(),
+            "expires_at": s.expires_at.to_rfc3339(),
+            "user_agent": s.user_agent,
+            "ip_address": s.ip_address.map(|ip| ip.addr().to_string()),
+        })).collect::<Vec<_>>(),
+        "solves": user_solves.into_iter().map(|s| json!({
+            "challenge_id": s.challenge_id,
+            "submitted_flag": s.submitted_flag,
+            "solved_at": s.solved_at.to_rfc3339(),
+            "ip_address": s.ip_address.map(|ip| ip.addr().to_string()),
+            "user_agent": s.user_agent,
+        })).collect::<Vec<_>>(),
+        "invalid_submissions": user_invalid_submissions.into_iter().map(|s| json!({
+            "challenge_id": s.challenge_id,
+            "submitted_flag": s.submitted_flag,
+            "submitted_at": s.submitted_at.to_rfc3339(),
+            "ip_address": s.ip_address.map(|ip| ip.addr().to_string()),
+            "user_agent": s.user_agent,
+        })).collect::<Vec<_>>(),
+    });
+
+    Ok(serde_json::to_vec_pretty(&export)?)
+}
+
+async fn generate_data_export(
+    ctx: &BaseContext,
+    export_id: uuid::Uuid,
+    uid: uuid::Uuid,
+) -> Result<(), JobError> {
+    let export_path = format!("data-exports/{}/{}.json", uid, export_id);
+    let data = build_data_export(ctx, uid).await?;
+    ctx.storage.put_file(&export_path, data).await?;
+
+    use crate::db::schema::data_exports::dsl::*;
+    // An export that timed out stays failed, one whose user was deleted in the meantime is gone
+    let updated = diesel::update(
+        data_exports
+            .filter(id.eq(export_id))
+            .filter(status.eq(DataExportStatus::Pending)),
+    )
+    .set((
+        status.eq(DataExportStatus::Completed),
+        file_path.eq(Some(export_path)),
+        completed_at.eq(Some(chrono::Utc::now())),
+    ))
+    .execute(&mut ctx.db_pool.get().await?)
+    .await?;
+    if updated == 0 {
+        ctx.storage.delete_file(&export_path).await?;
+    }
+    Ok(())
+}
+
+/// Generates the data export of a job enqueued by request_data_export. Failed exports are
+/// retried by the job queue.
+pub fn run_data_export_job(ctx: BaseContext, payload: serde_json::Value) -> JobFuture {
+    Box::pin(async move {
+        let job: DataExportJob = serde_json::from_value(payload)?;
+        generate_data_export(&ctx, job.export_id, job.user_id).await
+    })
+}
+
+/// Marks the exports of a user as failed that are pending for longer than EXPORT_TIMEOUT.
+async fn fail_stale_exports(conn: &mut AsyncPgConnection, uid: uuid::Uuid) -> QueryResult<()> {
+    use crate::db::schema::data_exports::dsl::*;
+
+    let now = chrono::Utc::now();
+    diesel::update(
+        data_exports
+            .filter(user_id.eq(uid))
+            .filter(status.eq(DataExportStatus::Pending))
+            .filter(created_at.lt(now - EXPORT_TIMEOUT)),
+    )
+    .set((
+        status.eq(DataExportStatus::Failed),
+        completed_at.eq(Some(now)),
+    ))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Queues generating an export of all personal data of the current user.
+pub async fn request_data_export(ctx: &Context) -> FieldResult<DataExport> {
+    let current_user = ctx.require_authentication()?;
+
+    use crate::db::schema::data_exports::dsl::*;
+    let conn = &mut ctx.get_db_conn().await;
+    fail_stale_exports(conn, current_user.user_id).await?;
+
+    // Don't start another export while one is still being generated
+    let pending = data_exports
+        .filter(user_id.eq(current_user.user_id))
+        .filter(status.eq(DataExportStatus::Pending))
+        .select(DataExport::as_select())
+        .first::<DataExport>(conn)
+        .await
+        .optional()?;
+    if let Some(pending) = pending {
+        return Ok(pending);
+    }
+
+    let uid = current_user.user_id;
+    let export = conn
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                let export = diesel::insert_into(data_exports)
+                    .values(NewDataExport { user_id: uid })
+                    .returning(DataExport::as_returning())
+                    .get_result(conn)
+                    .await?;
+                let job = DataExportJob {
+                    export_id: export.id,
+                    user_id: uid,
+                };
+                crate::jobs::enqueue(
+                    conn,
+                    DATA_EXPORT_JOB,
+                    serde_json::to_value(job).expect("Job payload is serializable"),
+                )
+                .await?;
+                Ok(export)
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    Ok(export)
+}
+
+pub async fn get_my_data_exports(ctx: &Context) -> FieldResult<Vec<DataExport>> {
+    let current_user = ctx.require_authentication()?;
+
+    use crate::db::schema::data_exports::dsl::*;
+    let conn = &mut ctx.get_db_conn().await;
+    fail_stale_exports(conn, current_user.user_id).await?;
+    let exports = data_exports
+        .filter(user_id.eq(current_user.user_id))
+        .order(created_at.desc())
+        .select(DataExport::as_select())
+        .load::<DataExport>(conn)
+        .await?;
+    Ok(exports)
+}
+
+pub async fn download_data_export(
+    ctx: Context,
+    export_id: String,
+) -> Result<Vec<u8>, (u16, String)> {
+    let current_user = ctx
+        .require_authentication()
+        .map_err(|e| (401, format!("Authentication required: {:?}", e)))?;
+    let export_id =
+        uuid::Uuid::parse_str(&export_id).map_err(|_| (400, "Invalid export ID".to_string()))?;
+
+    use crate::db::schema::data_exports::dsl::*;
+    let export = data_exports
+        .filter(id.eq(export_id))
+        .filter(user_id.eq(current_user.user_id))
+        .select(DataExport::as_select())
+        .first::<DataExport>(&mut ctx.get_db_conn().await)
+        .await
+        .optional()
+        .map_err(|e| (500, format!("Failed to load data export: {}", e)))?
+        .ok_or((404, "Data export not found".to_string()))?;
+
+    let Some(path) = export.file_path else {
+        return Err((404, "Data export is not available".to_string()));
+    };
+    ctx.base
+        .storage
+        .get_file(&path)
+        .await
+        .map_err(|e| (500, format!("Failed to retrieve data export: {}", e)))
+}
@@ -0,0 +1,171 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use argon2::{Argon2, PasswordVerifier};
+use diesel::prelude::*;
+use diesel_async::{AsyncConnection, RunQueryDsl, scoped_futures::ScopedFutureExt};
+use juniper::FieldResult;
+
+use crate::{
+    db::models::{User, UserRole},
+    graphql::Context,
+};
+
+/// Removes all personal data of a user.
+///
+/// The user row itself is kept (anonymized and deactivated) so that their solves still count
+/// for their team and the scoreboard stays consistent. Invalid submissions, sessions and data
+/// exports (including their files) are deleted, the IP addresses and user agents of the solves
+/// removed.
+async fn anonymize_user(ctx: &Context, user: &User) -> FieldResult<()> {
+    use crate::db::schema::{data_exports, invalid_submissions, sessions, solves, teams, users};
+
+    let user_id = user.id;
+    let anonymous_name = format!("deleted-{}", user_id.simple());
+    let mut conn = ctx.get_db_conn().await;
+    let export_files = conn
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                diesel::delete(sessions::table.filter(sessions::user_id.eq(user_id)))
+                    .execute(conn)
+                    .await?;
+                diesel::delete(
+                    invalid_submissions::table.filter(invalid_submissions::user_id.eq(user_id)),
+                )
+                .execute(conn)
+                .await?;
+                let export_files: Vec<Option<String>> =
+                    diesel::delete(data_exports::table.filter(data_exports::user_id.eq(user_id)))
+                        .returning(data_exports::file_path)
+                        .get_results(conn)
+                        .await?;
+                // The solves stay for the team, but not where they were submitted from
+                diesel::update(solves::table.filter(solves::user_id.eq(user_id)))
+                    .set((
+                        solves::ip_address.eq::<Option<ipnet::IpNet>>(None),
+                        solves::user_agent.eq::<Option<String>>(None),
+                    ))
+                    .execute(conn)
+                    .await?;
+                diesel::update(users::table.filter(users::id.eq(user_id)))
+                    .set((
+                        users::username.eq(&anonymous_name),
+                        users::display_name.eq("Deleted user"),
+                        users::email.eq(format!("{anonymous_name}@deleted.invalid")),
+                        // Not a valid PHC string, so logging in is impossible
+                        users::password_hash.eq("!"),
+                        users::is_active.eq(false),
+                        users::avatar_path.eq::<Option<String>>(None),
+                        users::updated_at.eq(chrono::Utc::now()),
+                    ))
+                    .execute(conn)
+                    .await?;
+
+                // Like when leaving a team, the team is deleted once nobody else is in it. Teams with
+                // solves are kept, deleting them would take the solves off the scoreboard.
+                if let Some(team_id) = user.team_id {
+                    let other_members: i64 = users::table
+                        .filter(users::team_id.eq(team_id))
+                        .filter(users::id.ne(user_id))
+                        .count()
+                        .get_result(conn)
+                        .await?;
+                    let team_solves: i64 = solves::table
+                        .filter(solves::team_id.eq(team_id))
+                        .count()
+                        .get_result(conn)
+                        .await?;
+                    if other_members == 0 && team_solves == 0 {
+                        diesel::delete(teams::table.filter(teams::id.eq(team_id)))
+                            .execute(conn)
+                            .await?;
+                    }
+                }
+                Ok(export_files)
+            }
+            .scope_boxed()
+        })
+        .await?;
+    // The user no longer counts as a competitor and their team may be gone
+    crate::graphql::handlers::scoreboard::points::request_recompute(None);
+
+    if let Some(avatar_path) = &user.avatar_path
+        && let Err(e) = ctx.base.storage.delete_file(avatar_path).await
+    {
+        tracing::warn!("Failed to delete avatar of deleted user {}: {}", user_id, e);
+    }
+    // Exports that are still being generated delete their file once they see the row is gone
+    for path in export_files.into_iter().flatten() {
+        if let Err(e) = ctx.base.storage.delete_file(&path).await {
+            tracing::warn!(
+                "Failed to delete data export of deleted user {}: {}",
+                user_id,
+                e
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn get_user(ctx: &Context, user_id: uuid::Uuid) -> FieldResult<User> {
+    use crate::db::schema::users::dsl::*;
+
+    let user = users
+        .filter(id.eq(user_id))
+        .filter(is_active.eq(true))
+        .first::<User>(&mut ctx.get_db_conn().await)
+        .await
+        .optional()?;
+    user.ok_or_else(|| juniper::FieldError::new("User not found", juniper::Value::null()))
+}
+
+async fn ensure_not_last_admin(ctx: &Context, user: &User) -> FieldResult<()> {
+    if user.role != UserRole::Admin {
+        return Ok(());
+    }
+    use crate::db::schema::users::dsl::*;
+
+    let admin_count: i64 = users
+        .filter(role.eq(UserRole::Admin))
+        .filter(is_active.eq(true))
+        .count()
+        .get_result(&mut ctx.get_db_conn().await)
+        .await?;
+    if admin_count <= 1 {
+        return Err(juniper::FieldError::new(
+            "The last admin account cannot be deleted",
+            juniper::Value::null(),
+        ));
+    }
+    Ok(())
+}
+
+pub async fn delete_account(ctx: &Context, password: String) -> FieldResult<bool> {
+    let current_user = ctx.require_authentication()?;
+    let user = get_user(ctx, current_user.user_id).await?;
+
+    let parsed_hash = argon2::PasswordHash::new(&user.password_hash)?;
+    if Argon2::default()
+        .verify_password(password.as_bytes(), &parsed_hash)
+        .is_err()
+    {
+        return Err(juniper::FieldError::new(
+            "Invalid password",
+            juniper::Value::null(),
+        ));
+    }
+    ensure_not_last_admin(ctx, &user).await?;
+
+    anonymize_user(ctx, &user).await?;
+    Ok(true)
+}
+
+pub async fn delete_user(ctx: &Context, user_id: uuid::Uuid) -> FieldResult<bool> {
+    ctx.require_role_min(UserRole::Admin)?;
+    let user = get_user(ctx, user_id).await?;
+    ensure_not_last_admin(ctx, &user).await?;
+
+    anonymize_user(ctx, &user).await?;
+    Ok(true)
+}
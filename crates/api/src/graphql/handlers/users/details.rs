@@ -4,7 +4,7 @@
 
 use juniper::{FieldResult, graphql_object};
 
-use crate::db::models::{User, UserRole};
+use crate::db::models::{ApprovalStatus, User, UserRole};
 use crate::graphql::Context;
 
 use diesel::prelude::*;
@@ -20,6 +20,17 @@ impl User {
         &self.username
     }
 
+    /// Name shown on the scoreboard, the username unless the user changed it
+    pub fn display_name(&self) -> &str {
+        &self.display_name
+    }
+
+    pub fn avatar_url(&self, ctx: &Context) -> Option<String> {
+        self.avatar_path
+            .as_deref()
+            .map(|path| ctx.base.storage.public_url(path))
+    }
+
     pub fn email(&self, ctx: &Context) -> FieldResult<String> {
         if ctx
             .user
@@ -39,6 +50,15 @@ impl User {
         self.role
     }
 
+    pub fn approval_status(&self) -> ApprovalStatus {
+        self.approval_status
+    }
+
+    /// Whether the user's solves are left out of the scoreboard and statistics
+    pub fn hidden(&self) -> bool {
+        self.hidden
+    }
+
     pub async fn invalid_submissions_count(&self, ctx: &Context) -> FieldResult<i32> {
         ctx.require_role_min(UserRole::Author)?;
         use crate::db::schema::invalid_submissions::dsl::*;
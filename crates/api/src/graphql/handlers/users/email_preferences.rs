@@ -0,0 +1,171 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use juniper::{FieldResult, graphql_object};
+
+use crate::{db::models::EmailPreferences, graphql::Context};
+
+type DbPool = diesel_async::pooled_connection::bb8::Pool<diesel_async::AsyncPgConnection>;
+
+#[graphql_object]
+impl EmailPreferences {
+    /// Email me when someone joins my team
+    pub fn team_member_joined(&self) -> bool {
+        self.team_member_joined
+    }
+
+    /// Email me when someone leaves my team
+    pub fn team_member_left(&self) -> bool {
+        self.team_member_left
+    }
+}
+
+impl EmailPreferences {
+    fn defaults(user_id: uuid::Uuid) -> Self {
+        Self {
+            user_id,
+            team_member_joined: true,
+            team_member_left: true,
+            updated_at: chrono::Utc::now(),
+        }
+    }
+}
+
+/// Team events members can receive emails about
+#[derive(Debug, Clone, Copy)]
+pub enum TeamEmailEvent {
+    MemberJoined,
+    MemberLeft,
+}
+
+pub async fn get_email_preferences(ctx: &Context) -> FieldResult<EmailPreferences> {
+    use crate::db::schema::email_preferences;
+
+    let user = ctx.require_authentication()?;
+    let preferences = email_preferences::table
+        .filter(email_preferences::user_id.eq(user.user_id))
+        .select(EmailPreferences::as_select())
+        .first::<EmailPreferences>(&mut ctx.get_db_conn().await)
+        .await
+        .optional()?;
+    Ok(preferences.unwrap_or_else(|| EmailPreferences::defaults(user.user_id)))
+}
+
+pub async fn update_email_preferences(
+    ctx: &Context,
+    team_member_joined: Option<bool>,
+    team_member_left: Option<bool>,
+) -> FieldResult<EmailPreferences> {
+    use crate::db::schema::email_preferences;
+
+    let mut preferences = get_email_preferences(ctx).await?;
+    if let Some(team_member_joined) = team_member_joined {
+        preferences.team_member_joined = team_member_joined;
+    }
+    if let Some(team_member_left) = team_member_left {
+        preferences.team_member_left = team_member_left;
+    }
+    preferences.updated_at = chrono::Utc::now();
+
+    let preferences = diesel::insert_into(email_preferences::table)
+        .values(&preferences)
+        .on_conflict(email_preferences::user_id)
+        .do_update()
+        .set(&preferences)
+        .returning(EmailPreferences::as_returning())
+        .get_result(&mut ctx.get_db_conn().await)
+        .await?;
+    Ok(preferences)
+}
+
+async fn send_team_emails(
+    db_pool: &DbPool,
+    team_id: uuid::Uuid,
+    actor_id: uuid::Uuid,
+    event: TeamEmailEvent,
+    actor_name: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use crate::db::schema::{email_preferences, teams, users};
+
+    let mut conn = db_pool.get().await?;
+    // The team is deleted when its last member leaves
+    let Some(team_name) = teams::table
+        .filter(teams::id.eq(team_id))
+        .select(teams::name)
+        .first::<String>(&mut conn)
+        .await
+        .optional()?
+    else {
+        return Ok(());
+    };
+    let query = users::table
+        .left_join(email_preferences::table)
+        .filter(users::team_id.eq(team_id))
+        .filter(users::id.ne(actor_id))
+        .filter(users::is_active.eq(true))
+        .select((users::email, users::display_name))
+        .into_boxed();
+    let query = match event {
+        TeamEmailEvent::MemberJoined => query.filter(
+            email_preferences::team_member_joined
+                .is_null()
+                .or(email_preferences::team_member_joined.eq(true)),
+        ),
+        TeamEmailEvent::MemberLeft => query.filter(
+            email_preferences::team_member_left
+                .is_null()
+                .or(email_preferences::team_member_left.eq(true)),
+        ),
+    };
+    let recipients = query.load::<(String, String)>(&mut conn).await?;
+    drop(conn);
+
+    let (subject, message) = match event {
+        TeamEmailEvent::MemberJoined => (
+            format!("{} joined {}", actor_name, team_name),
+            format!("{} has joined your team {}.", actor_name, team_name),
+        ),
+        TeamEmailEvent::MemberLeft => (
+            format!("{} left {}", actor_name, team_name),
+            format!("{} has left your team {}.", actor_name, team_name),
+        ),
+    };
+    for (email, display_name) in recipients {
+        let body = format!(
+            "Hi {},\n\n{}\n\nYou can turn off these emails in your account settings.\n",
+            display_name, message
+        );
+        if let Err(e) = crate::email::send_email(&email, &subject, body).await {
+            tracing::warn!("Failed to send team email to {}: {}", email, e);
+        }
+    }
+    Ok(())
+}
+
+/// Emails the other members of a team about a change, in the background.
+///
+/// Members who turned off emails for the event are skipped.
+pub fn email_team_members(ctx: &Context, team_id: uuid::Uuid, event: TeamEmailEvent) {
+    if !crate::email::is_configured() {
+        return;
+    }
+    let Some(user) = ctx.user.clone() else {
+        return;
+    };
+    let db_pool = ctx.base.db_pool.clone();
+    tokio::spawn(async move {
+        if let Err(e) =
+            send_team_emails(&db_pool, team_id, user.user_id, event, &user.username).await
+        {
+            tracing::error!(
+                "Failed to send {:?} emails for team {}: {}",
+                event,
+                team_id,
+                e
+            );
+        }
+    });
+}
@@ -0,0 +1,184 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use juniper::{FieldResult, graphql_object, graphql_value};
+
+use crate::{
+    db::models::{InviteCode, NewInviteCode, UserRole},
+    graphql::Context,
+};
+
+#[graphql_object]
+impl InviteCode {
+    pub fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    pub fn max_uses(&self) -> i32 {
+        self.max_uses
+    }
+
+    pub fn uses(&self) -> i32 {
+        self.uses
+    }
+
+    /// Role users registering with the code get, players if null
+    pub fn role(&self) -> Option<UserRole> {
+        self.role
+    }
+
+    /// Division the teams of users registering with the code compete in
+    pub fn division(&self) -> Option<&str> {
+        self.division.as_deref()
+    }
+
+    pub fn expires_at(&self) -> Option<String> {
+        self.expires_at.map(|t| t.to_rfc3339())
+    }
+
+    pub fn created_at(&self) -> String {
+        self.created_at.to_rfc3339()
+    }
+}
+
+/// Generates a random code that is easy to type.
+fn generate_code() -> String {
+    use rand::Rng;
+    const ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+    let mut rng = rand::thread_rng();
+    (0..12)
+        .map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char)
+        .collect()
+}
+
+/// Uses up one use of an invite code, failing if it doesn't exist, expired or was used up.
+///
+/// Meant to be called in the same transaction as the user is created in.
+pub(crate) async fn redeem_invite_code(
+    conn: &mut AsyncPgConnection,
+    code: &str,
+) -> FieldResult<InviteCode> {
+    use crate::db::schema::invite_codes;
+
+    let invite_code = diesel::update(
+        invite_codes::table
+            .filter(invite_codes::code.eq(code.trim().to_uppercase()))
+            .filter(invite_codes::uses.lt(invite_codes::max_uses))
+            .filter(
+                invite_codes::expires_at
+                    .is_null()
+                    .or(invite_codes::expires_at.gt(diesel::dsl::now)),
+            ),
+    )
+    .set(invite_codes::uses.eq(invite_codes::uses + 1))
+    .returning(InviteCode::as_returning())
+    .get_result(conn)
+    .await
+    .optional()?;
+    invite_code.ok_or_else(|| {
+        juniper::FieldError::new(
+            "This invite code is invalid or was already used",
+            graphql_value!({ "code": "INVALID_INVITE_CODE" }),
+        )
+    })
+}
+
+/// The division the user's invite code binds their team to, if any.
+pub(crate) async fn get_bound_division(
+    ctx: &Context,
+    user_id: uuid::Uuid,
+) -> FieldResult<Option<String>> {
+    use crate::db::schema::{invite_codes, users};
+
+    let division = users::table
+        .inner_join(invite_codes::table.on(users::invite_code_id.eq(invite_codes::id.nullable())))
+        .filter(users::id.eq(user_id))
+        .select(invite_codes::division)
+        .first::<Option<String>>(&mut ctx.get_db_conn().await)
+        .await
+        .optional()?;
+    Ok(division.flatten())
+}
+
+pub async fn get_invite_codes(ctx: &Context) -> FieldResult<Vec<InviteCode>> {
+    use crate::db::schema::invite_codes;
+
+    ctx.require_role_min(UserRole::Admin)?;
+    let codes = invite_codes::table
+        .order(invite_codes::created_at.desc())
+        .select(InviteCode::as_select())
+        .load::<InviteCode>(&mut ctx.get_db_conn().await)
+        .await?;
+    Ok(codes)
+}
+
+/// Creates an invite code that can be used for `max_uses` registrations (admin only).
+pub async fn create_invite_code(
+    ctx: &Context,
+    max_uses: i32,
+    role: Option<UserRole>,
+    division: Option<String>,
+    expires_at: Option<String>,
+) -> FieldResult<InviteCode> {
+    use crate::db::schema::invite_codes;
+
+    ctx.require_role_min(UserRole::Admin)?;
+    let admin = ctx.require_authentication()?;
+    if max_uses < 1 {
+        return Err(juniper::FieldError::new(
+            "Invite codes must be usable at least once",
+            juniper::Value::null(),
+        ));
+    }
+    if let Some(division) = &division {
+        let event_config = crate::graphql::handlers::event::get_event_config(ctx).await?;
+        if !event_config.divisions.iter().any(|d| &d.id == division) {
+            return Err(juniper::FieldError::new(
+                format!("Unknown division {}", division),
+                juniper::Value::null(),
+            ));
+        }
+    }
+    let expires_at = expires_at
+        .map(|t| chrono::DateTime::parse_from_rfc3339(&t).map(|t| t.to_utc()))
+        .transpose()?;
+
+    let invite_code = diesel::insert_into(invite_codes::table)
+        .values(NewInviteCode {
+            code: generate_code(),
+            max_uses,
+            role,
+            division,
+            expires_at,
+            created_by: Some(admin.user_id),
+        })
+        .returning(InviteCode::as_returning())
+        .get_result(&mut ctx.get_db_conn().await)
+        .await?;
+    tracing::info!(
+        "Invite code for {} uses created by {}",
+        invite_code.max_uses,
+        admin.username
+    );
+    Ok(invite_code)
+}
+
+/// Deletes an invite code so it can't be used anymore (admin only).
+///
+/// Users who registered with it keep their role, but their teams are no longer bound to its division.
+pub async fn delete_invite_code(ctx: &Context, invite_code_id: uuid::Uuid) -> FieldResult<bool> {
+    use crate::db::schema::invite_codes;
+
+    ctx.require_role_min(UserRole::Admin)?;
+    let deleted = diesel::delete(invite_codes::table.filter(invite_codes::id.eq(invite_code_id)))
+        .execute(&mut ctx.get_db_conn().await)
+        .await?;
+    Ok(deleted > 0)
+}
@@ -0,0 +1,221 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::{sync::LazyLock, time::Duration};
+
+use juniper::{FieldResult, graphql_value};
+use sha1::{Digest, Sha1};
+
+use crate::config::Config;
+
+const MIN_LENGTH: usize = 8;
+/// Required strength unless password_min_entropy_bits is configured
+const DEFAULT_MIN_ENTROPY_BITS: f64 = 40.0;
+/// Bits a word from the dictionary is worth, roughly one guess out of a list of a thousand words
+const DICTIONARY_WORD_BITS: f64 = 10.0;
+/// Bits a character is worth if it repeats or continues a sequence, e.g. the b in "ab"
+const PREDICTABLE_CHAR_BITS: f64 = 1.0;
+
+/// Passwords and parts of passwords that are tried first when guessing
+const COMMON_PASSWORDS: &[&str] = &[
+    "password", "passwort", "123456", "qwerty", "qwertz", "azerty", "asdf", "yxcv", "zxcv",
+    "letmein", "welcome", "monkey", "dragon", "football", "baseball", "master", "shadow",
+    "sunshine", "princess", "iloveyou", "trustno1", "superman", "batman", "hello", "freedom",
+    "whatever", "secret", "admin", "login", "flag", "ctf", "hacker", "hallo", "geheim", "summer",
+    "winter", "spring", "autumn", "love", "pass",
+];
+
+static HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .expect("Failed to create breach check HTTP client")
+});
+
+/// Lowercases the password and replaces digits and symbols commonly used in place of letters.
+fn fold(password: &str) -> Vec<char> {
+    password
+        .chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            '0' => 'o',
+            '1' | '!' => 'i',
+            '3' => 'e',
+            '4' | '@' => 'a',
+            '5' | '$' => 's',
+            '7' => 't',
+            c => c,
+        })
+        .collect()
+}
+
+/// Number of characters an attacker would have to try for each position
+fn charset_size(password: &str) -> u32 {
+    let mut size = 0;
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        size += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        size += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        size += 10;
+    }
+    if password
+        .chars()
+        .any(|c| c.is_ascii_punctuation() || c == ' ')
+    {
+        size += 33;
+    }
+    if password.chars().any(|c| !c.is_ascii()) {
+        size += 100;
+    }
+    size.max(1)
+}
+
+/// Estimates how many bits of entropy a password has, in the spirit of zxcvbn.
+///
+/// Common passwords and the user's own details (e.g. their username) count as a single
+/// dictionary word, repeated characters and sequences like "abc" or "123" barely count.
+pub fn estimate_entropy(password: &str, user_inputs: &[&str]) -> f64 {
+    let bits_per_char = (charset_size(password) as f64).log2();
+    let password = fold(password);
+    let dictionary = COMMON_PASSWORDS
+        .iter()
+        .copied()
+        .chain(user_inputs.iter().copied().filter(|input| input.len() >= 3))
+        .map(fold)
+        .collect::<Vec<_>>();
+
+    let mut bits = 0.0;
+    let mut previous: Option<char> = None;
+    let mut i = 0;
+    while i < password.len() {
+        if let Some(word) = dictionary
+            .iter()
+            .filter(|word| password[i..].starts_with(word))
+            .max_by_key(|word| word.len())
+        {
+            bits += DICTIONARY_WORD_BITS;
+            i += word.len();
+            previous = None;
+            continue;
+        }
+        let c = password[i];
+        let predictable = previous.is_some_and(|p| (c as i64 - p as i64).abs() <= 1);
+        bits += if predictable {
+            PREDICTABLE_CHAR_BITS
+        } else {
+            bits_per_char
+        };
+        previous = Some(c);
+        i += 1;
+    }
+    bits
+}
+
+/// Whether the password is in the HaveIBeenPwned database.
+///
+/// Only the first five characters of the SHA-1 hash are sent (k-anonymity), the response is
+/// padded so its size doesn't give away the prefix either.
+async fn is_breached(password: &str) -> Result<bool, reqwest::Error> {
+    let hash = format!("{:X}", Sha1::digest(password.as_bytes()));
+    let (prefix, suffix) = hash.split_at(5);
+    let response = HTTP_CLIENT
+        .get(format!("https://api.pwnedpasswords.com/range/{prefix}"))
+        .header("Add-Padding", "true")
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    Ok(response.lines().any(|line| {
+        line.split_once(':').is_some_and(|(candidate, count)| {
+            candidate.eq_ignore_ascii_case(suffix) && count.trim() != "0"
+        })
+    }))
+}
+
+/// Checks that a new password is long and strong enough and, if enabled, that it wasn't leaked.
+///
+/// `user_inputs` are details of the user like their username and email, which make a weak
+/// password if it is based on them. New passwords are only checked against the HaveIBeenPwned
+/// database if password_breach_check is enabled.
+pub async fn check_password(
+    config: &Config,
+    password: &str,
+    user_inputs: &[&str],
+) -> FieldResult<()> {
+    if password.chars().count() < MIN_LENGTH {
+        return Err(juniper::FieldError::new(
+            format!("Password must be at least {} characters long", MIN_LENGTH),
+            graphql_value!({ "code": "WEAK_PASSWORD" }),
+        ));
+    }
+    let min_entropy_bits = config
+        .password_min_entropy_bits
+        .unwrap_or(DEFAULT_MIN_ENTROPY_BITS);
+    if estimate_entropy(password, user_inputs) < min_entropy_bits {
+        return Err(juniper::FieldError::new(
+            "Password is too easy to guess, try a longer one or a few unrelated words",
+            graphql_value!({ "code": "WEAK_PASSWORD" }),
+        ));
+    }
+    if config.password_breach_check {
+        match is_breached(password).await {
+            Ok(true) => {
+                return Err(juniper::FieldError::new(
+                    "This password appeared in a data breach, please choose another one",
+                    graphql_value!({ "code": "BREACHED_PASSWORD" }),
+                ));
+            }
+            Ok(false) => {}
+            // Registration shouldn't depend on a third-party service being available
+            Err(e) => tracing::warn!("Failed to check password against HaveIBeenPwned: {}", e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weak_passwords() {
+        for password in [
+            "password",
+            "P@ssw0rd123",
+            "aaaaaaaaaaaa",
+            "abcdefgh12345678",
+            "qwertyqwerty",
+        ] {
+            assert!(
+                estimate_entropy(password, &[]) < DEFAULT_MIN_ENTROPY_BITS,
+                "{password} should be weak"
+            );
+        }
+    }
+
+    #[test]
+    fn test_user_inputs_are_weak() {
+        let password = "plfanzenfan2026";
+        assert!(estimate_entropy(password, &[]) >= DEFAULT_MIN_ENTROPY_BITS);
+        assert!(estimate_entropy(password, &["plfanzenfan"]) < DEFAULT_MIN_ENTROPY_BITS);
+    }
+
+    #[test]
+    fn test_strong_passwords() {
+        for password in [
+            "correct horse battery staple",
+            "k7#Qm2!vXz9p",
+            "Tr0ub4dor&3xyzw",
+        ] {
+            assert!(
+                estimate_entropy(password, &[]) >= DEFAULT_MIN_ENTROPY_BITS,
+                "{password} should be strong"
+            );
+        }
+    }
+}
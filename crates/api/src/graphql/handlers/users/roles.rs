@@ -0,0 +1,50 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use juniper::FieldResult;
+
+use crate::{
+    db::models::{User, UserRole},
+    graphql::Context,
+};
+
+/// Changes the role of a user, e.g. to make them a tester (admin only).
+///
+/// The new role applies once the user's access token is refreshed.
+pub async fn set_user_role(
+    ctx: &Context,
+    user_id: uuid::Uuid,
+    new_role: UserRole,
+) -> FieldResult<User> {
+    use crate::db::schema::users;
+
+    ctx.require_role_min(UserRole::Admin)?;
+    let admin = ctx.require_authentication()?;
+    if admin.user_id == user_id {
+        return Err(juniper::FieldError::new(
+            "You cannot change your own role",
+            juniper::Value::null(),
+        ));
+    }
+    let user = diesel::update(users::table.filter(users::id.eq(user_id)))
+        .set((
+            users::role.eq(new_role),
+            users::updated_at.eq(chrono::Utc::now()),
+        ))
+        .get_result::<User>(&mut ctx.get_db_conn().await)
+        .await
+        .optional()?
+        .ok_or_else(|| juniper::FieldError::new("User not found", juniper::Value::null()))?;
+    tracing::info!(
+        "Role of {} set to {:?} by {}",
+        user.username,
+        new_role,
+        admin.username
+    );
+    // Only players are on the scoreboard
+    crate::graphql::handlers::scoreboard::points::request_recompute(None);
+    Ok(user)
+}
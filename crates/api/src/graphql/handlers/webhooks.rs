@@ -0,0 +1,214 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use juniper::{FieldResult, graphql_object};
+
+use crate::{
+    db::models::{NewWebhook, UserRole, Webhook, WebhookDelivery, WebhookEvent},
+    graphql::Context,
+};
+
+#[graphql_object]
+impl Webhook {
+    pub fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Secret used to sign the payloads (X-Plfanzen-Signature header)
+    pub fn secret(&self) -> &str {
+        &self.secret
+    }
+
+    pub fn events(&self) -> &[WebhookEvent] {
+        &self.events
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.is_active
+    }
+
+    pub fn created_at(&self) -> String {
+        self.created_at.to_rfc3339()
+    }
+
+    /// Most recent deliveries, newest first
+    pub async fn deliveries(
+        &self,
+        ctx: &Context,
+        #[graphql(default = 50)] limit: i32,
+    ) -> FieldResult<Vec<WebhookDelivery>> {
+        use crate::db::schema::webhook_deliveries::dsl::*;
+        let deliveries = webhook_deliveries
+            .filter(webhook_id.eq(self.id))
+            .order(created_at.desc())
+            .limit(limit.clamp(1, 500) as i64)
+            .select(WebhookDelivery::as_select())
+            .load::<WebhookDelivery>(&mut ctx.get_db_conn().await)
+            .await?;
+        Ok(deliveries)
+    }
+}
+
+#[graphql_object]
+impl WebhookDelivery {
+    pub fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    pub fn event(&self) -> WebhookEvent {
+        self.event
+    }
+
+    /// The JSON body that was sent
+    pub fn payload(&self) -> &str {
+        &self.payload
+    }
+
+    pub fn attempts(&self) -> i32 {
+        self.attempts
+    }
+
+    /// HTTP status of the last attempt, if the receiver responded
+    pub fn response_status(&self) -> Option<i32> {
+        self.response_status
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn created_at(&self) -> String {
+        self.created_at.to_rfc3339()
+    }
+
+    pub fn delivered_at(&self) -> Option<String> {
+        self.delivered_at.map(|t| t.to_rfc3339())
+    }
+}
+
+/// Removes duplicate events, keeping the order
+fn unique_events(events: Vec<WebhookEvent>) -> Vec<WebhookEvent> {
+    let mut unique = Vec::with_capacity(events.len());
+    for event in events {
+        if !unique.contains(&event) {
+            unique.push(event);
+        }
+    }
+    unique
+}
+
+fn validate_webhook(url: &str, events: &[WebhookEvent]) -> FieldResult<()> {
+    let parsed = reqwest::Url::parse(url)
+        .map_err(|_| juniper::FieldError::new("Invalid webhook URL", juniper::Value::null()))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(juniper::FieldError::new(
+            "Webhook URL must use http or https",
+            juniper::Value::null(),
+        ));
+    }
+    if events.is_empty() {
+        return Err(juniper::FieldError::new(
+            "A webhook needs at least one event",
+            juniper::Value::null(),
+        ));
+    }
+    Ok(())
+}
+
+pub async fn get_webhooks(ctx: &Context) -> FieldResult<Vec<Webhook>> {
+    ctx.require_role_min(UserRole::Admin)?;
+
+    use crate::db::schema::webhooks::dsl::*;
+    let result = webhooks
+        .order(created_at.asc())
+        .select(Webhook::as_select())
+        .load::<Webhook>(&mut ctx.get_db_conn().await)
+        .await?;
+    Ok(result)
+}
+
+pub async fn create_webhook(
+    ctx: &Context,
+    url: String,
+    events: Vec<WebhookEvent>,
+) -> FieldResult<Webhook> {
+    ctx.require_role_min(UserRole::Admin)?;
+    let events = unique_events(events);
+    validate_webhook(&url, &events)?;
+
+    use crate::db::schema::webhooks;
+    let webhook = diesel::insert_into(webhooks::table)
+        .values(NewWebhook {
+            url,
+            secret: crate::webhooks::generate_secret(),
+            events,
+        })
+        .returning(Webhook::as_returning())
+        .get_result(&mut ctx.get_db_conn().await)
+        .await?;
+    Ok(webhook)
+}
+
+pub async fn update_webhook(
+    ctx: &Context,
+    webhook_id: uuid::Uuid,
+    new_url: Option<String>,
+    new_events: Option<Vec<WebhookEvent>>,
+    active: Option<bool>,
+    rotate_secret: bool,
+) -> FieldResult<Webhook> {
+    ctx.require_role_min(UserRole::Admin)?;
+
+    use crate::db::schema::webhooks::dsl::*;
+    let conn = &mut ctx.get_db_conn().await;
+    let mut webhook = webhooks
+        .filter(id.eq(webhook_id))
+        .select(Webhook::as_select())
+        .first::<Webhook>(conn)
+        .await
+        .optional()?
+        .ok_or_else(|| juniper::FieldError::new("Webhook not found", juniper::Value::null()))?;
+
+    if let Some(new_url) = new_url {
+        webhook.url = new_url;
+    }
+    if let Some(new_events) = new_events {
+        webhook.events = unique_events(new_events);
+    }
+    validate_webhook(&webhook.url, &webhook.events)?;
+    if let Some(active) = active {
+        webhook.is_active = active;
+    }
+    if rotate_secret {
+        webhook.secret = crate::webhooks::generate_secret();
+    }
+
+    let webhook = diesel::update(webhooks.filter(id.eq(webhook_id)))
+        .set((
+            url.eq(webhook.url),
+            events.eq(webhook.events),
+            is_active.eq(webhook.is_active),
+            secret.eq(webhook.secret),
+        ))
+        .returning(Webhook::as_returning())
+        .get_result(conn)
+        .await?;
+    Ok(webhook)
+}
+
+pub async fn delete_webhook(ctx: &Context, webhook_id: uuid::Uuid) -> FieldResult<bool> {
+    ctx.require_role_min(UserRole::Admin)?;
+
+    use crate::db::schema::webhooks::dsl::*;
+    let deleted = diesel::delete(webhooks.filter(id.eq(webhook_id)))
+        .execute(&mut ctx.get_db_conn().await)
+        .await?;
+    Ok(deleted > 0)
+}
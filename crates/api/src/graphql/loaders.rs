@@ -0,0 +1,265 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    hash::Hash,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use juniper::FieldResult;
+use tokio::sync::OnceCell;
+
+use crate::{db::models::User, graphql::AuthenticatedUser};
+
+type DbPool = diesel_async::pooled_connection::bb8::Pool<diesel_async::AsyncPgConnection>;
+type BatchFuture<K, V> = Pin<Box<dyn Future<Output = FieldResult<HashMap<K, V>>> + Send>>;
+type BatchFn<K, V> = Box<dyn Fn(Vec<K>) -> BatchFuture<K, V> + Send + Sync>;
+
+struct Batch<K, V> {
+    keys: Mutex<Vec<K>>,
+    result: OnceCell<FieldResult<HashMap<K, V>>>,
+}
+
+struct LoaderState<K, V> {
+    loaded: HashMap<K, V>,
+    /// The batch new keys are added to, until one of its loads starts fetching it
+    pending: Option<Arc<Batch<K, V>>>,
+}
+
+/// Collects the keys requested by fields that are resolved together (e.g. the same field of every
+/// item in a list) and loads them with a single call of the batch function.
+///
+/// Keys missing from the batch result get the default value. Results are kept for the lifetime of
+/// the loader, which is a single request.
+pub struct BatchLoader<K, V> {
+    load_batch: BatchFn<K, V>,
+    state: Mutex<LoaderState<K, V>>,
+}
+
+impl<K, V> BatchLoader<K, V>
+where
+    K: Eq + Hash + Clone + Send + 'static,
+    V: Clone + Default + Send + 'static,
+{
+    pub fn new<F, Fut>(load_batch: F) -> Self
+    where
+        F: Fn(Vec<K>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = FieldResult<HashMap<K, V>>> + Send + 'static,
+    {
+        Self {
+            load_batch: Box::new(move |keys| Box::pin(load_batch(keys))),
+            state: Mutex::new(LoaderState {
+                loaded: HashMap::new(),
+                pending: None,
+            }),
+        }
+    }
+
+    pub async fn get(&self, key: K) -> FieldResult<V> {
+        let batch = {
+            let mut state = self.state.lock().unwrap();
+            if let Some(value) = state.loaded.get(&key) {
+                return Ok(value.clone());
+            }
+            let batch = state
+                .pending
+                .get_or_insert_with(|| {
+                    Arc::new(Batch {
+                        keys: Mutex::new(vec![]),
+                        result: OnceCell::new(),
+                    })
+                })
+                .clone();
+            batch.keys.lock().unwrap().push(key.clone());
+            batch
+        };
+
+        // juniper polls sibling fields concurrently, so this lets them add their keys before the batch is sent
+        tokio::task::yield_now().await;
+
+        {
+            let mut state = self.state.lock().unwrap();
+            if state
+                .pending
+                .as_ref()
+                .is_some_and(|pending| Arc::ptr_eq(pending, &batch))
+            {
+                state.pending = None;
+            }
+        }
+        let result = batch
+            .result
+            .get_or_init(|| {
+                let keys = std::mem::take(&mut *batch.keys.lock().unwrap());
+                let keys = keys.into_iter().collect::<HashSet<_>>();
+                (self.load_batch)(keys.into_iter().collect())
+            })
+            .await;
+
+        let values = result.as_ref().map_err(Clone::clone)?;
+        let value = values.get(&key).cloned().unwrap_or_default();
+        self.state.lock().unwrap().loaded.insert(key, value.clone());
+        Ok(value)
+    }
+}
+
+/// Per-request loaders for fields which would otherwise run one query per object.
+pub struct Loaders {
+    /// Number of solves of each challenge
+    pub challenge_solve_counts: BatchLoader<String, i64>,
+    /// Whether the current user (or their team) solved each challenge
+    pub solved_challenges: BatchLoader<String, bool>,
+    /// Members of each team
+    pub team_members: BatchLoader<uuid::Uuid, Vec<User>>,
+}
+
+impl Loaders {
+    pub fn new(db_pool: DbPool, user: Option<&AuthenticatedUser>) -> Self {
+        let actor = user.map(|u| (u.user_id, u.team_id));
+        Self {
+            challenge_solve_counts: BatchLoader::new({
+                let db_pool = db_pool.clone();
+                move |challenge_ids| load_challenge_solve_counts(db_pool.clone(), challenge_ids)
+            }),
+            solved_challenges: BatchLoader::new({
+                let db_pool = db_pool.clone();
+                move |challenge_ids| load_solved_challenges(db_pool.clone(), actor, challenge_ids)
+            }),
+            team_members: BatchLoader::new(move |team_ids| {
+                load_team_members(db_pool.clone(), team_ids)
+            }),
+        }
+    }
+}
+
+async fn load_challenge_solve_counts(
+    db_pool: DbPool,
+    challenge_ids: Vec<String>,
+) -> FieldResult<HashMap<String, i64>> {
+    use crate::db::schema::{solves, teams, users};
+
+    // Hidden users and teams and playtest solves are left out, like on the scoreboard
+    let counts = solves::table
+        .inner_join(users::table)
+        .left_join(teams::table.on(teams::id.nullable().eq(solves::team_id)))
+        .filter(solves::challenge_id.eq_any(&challenge_ids))
+        .filter(solves::playtest.eq(false))
+        .filter(users::hidden.eq(false))
+        .filter(
+            teams::hidden
+                .nullable()
+                .eq(false)
+                .or(teams::id.nullable().is_null()),
+        )
+        .filter(
+            teams::disqualified
+                .nullable()
+                .eq(false)
+                .or(teams::id.nullable().is_null()),
+        )
+        .group_by(solves::challenge_id)
+        .select((solves::challenge_id, diesel::dsl::count_star()))
+        .load::<(String, i64)>(&mut db_pool.get().await?)
+        .await?;
+    Ok(counts.into_iter().collect())
+}
+
+async fn load_solved_challenges(
+    db_pool: DbPool,
+    actor: Option<(uuid::Uuid, Option<uuid::Uuid>)>,
+    challenge_ids: Vec<String>,
+) -> FieldResult<HashMap<String, bool>> {
+    use crate::db::schema::solves;
+
+    let Some((current_user_id, current_team_id)) = actor else {
+        return Ok(HashMap::new());
+    };
+    let conn = &mut db_pool.get().await?;
+    let query = solves::table
+        .filter(solves::challenge_id.eq_any(&challenge_ids))
+        .select(solves::challenge_id)
+        .distinct()
+        .into_boxed();
+    let solved = match current_team_id {
+        Some(current_team_id) => {
+            query
+                .filter(solves::team_id.eq(current_team_id))
+                .load::<String>(conn)
+                .await?
+        }
+        None => {
+            query
+                .filter(solves::user_id.eq(current_user_id))
+                .load::<String>(conn)
+                .await?
+        }
+    };
+    Ok(solved.into_iter().map(|id| (id, true)).collect())
+}
+
+async fn load_team_members(
+    db_pool: DbPool,
+    team_ids: Vec<uuid::Uuid>,
+) -> FieldResult<HashMap<uuid::Uuid, Vec<User>>> {
+    use crate::db::schema::users::dsl::*;
+
+    let members = users
+        .filter(team_id.eq_any(&team_ids))
+        .load::<User>(&mut db_pool.get().await?)
+        .await?;
+    let mut result: HashMap<uuid::Uuid, Vec<User>> = HashMap::new();
+    for member in members {
+        if let Some(member_team_id) = member.team_id {
+            result.entry(member_team_id).or_default().push(member);
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use juniper::FieldResult;
+
+    use super::BatchLoader;
+
+    #[tokio::test]
+    async fn test_concurrent_loads_are_batched() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let loader = BatchLoader::new({
+            let calls = calls.clone();
+            move |keys: Vec<i32>| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    Ok(keys
+                        .into_iter()
+                        .filter(|k| *k < 100)
+                        .map(|k| (k, k * 2))
+                        .collect())
+                }
+            }
+        });
+
+        let values = futures_util::future::join_all((0..10).map(|k| loader.get(k % 5))).await;
+        let values = values.into_iter().collect::<FieldResult<Vec<_>>>().unwrap();
+        assert_eq!(values, vec![0, 2, 4, 6, 8, 0, 2, 4, 6, 8]);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Cached values don't trigger another batch
+        assert_eq!(loader.get(3).await.unwrap(), 6);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        // Missing keys get the default value
+        assert_eq!(loader.get(200).await.unwrap(), 0);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
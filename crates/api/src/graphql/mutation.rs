@@ -19,10 +19,20 @@ impl Mutation {
         context: &Context,
         username: String,
         password: String,
+        captcha_challenge: Option<String>,
+        captcha_response: Option<String>,
     ) -> FieldResult<SessionCredentials> {
-        handlers::users::login_user(username, password, context).await
+        handlers::users::login_user(
+            username,
+            password,
+            context,
+            captcha_challenge,
+            captcha_response,
+        )
+        .await
     }
 
+    /// Register a new user. An invite code is required if the event is invite-only.
     async fn create_user(
         context: &Context,
         username: String,
@@ -30,6 +40,7 @@ impl Mutation {
         password: String,
         captcha_challenge: Option<String>,
         captcha_response: Option<String>,
+        invite_code: Option<String>,
     ) -> FieldResult<bool> {
         handlers::users::create_user(
             username,
@@ -38,10 +49,234 @@ impl Mutation {
             context,
             captcha_challenge,
             captcha_response,
+            invite_code,
         )
         .await
     }
 
+    /// Change the password of the current user. All other sessions are signed out.
+    async fn change_password(
+        context: &Context,
+        old_password: String,
+        new_password: String,
+    ) -> FieldResult<bool> {
+        handlers::users::change_password(context, old_password, new_password).await
+    }
+
+    /// Change the name of the current user shown on the scoreboard.
+    async fn update_display_name(
+        context: &Context,
+        display_name: String,
+    ) -> FieldResult<crate::db::models::User> {
+        handlers::users::update_display_name(context, display_name).await
+    }
+
+    /// Permanently delete the current account. Solves are kept anonymously for the scoreboard.
+    async fn delete_account(context: &Context, password: String) -> FieldResult<bool> {
+        handlers::users::deletion::delete_account(context, password).await
+    }
+
+    /// Approve a pending registration and notify the user by email (admin only).
+    async fn approve_user(
+        context: &Context,
+        user_id: String,
+    ) -> FieldResult<crate::db::models::User> {
+        let user_id = uuid::Uuid::parse_str(&user_id)?;
+        handlers::users::approval::approve_user(context, user_id).await
+    }
+
+    /// Reject a registration, the user won't be able to log in (admin only).
+    async fn reject_user(
+        context: &Context,
+        user_id: String,
+    ) -> FieldResult<crate::db::models::User> {
+        let user_id = uuid::Uuid::parse_str(&user_id)?;
+        handlers::users::approval::reject_user(context, user_id).await
+    }
+
+    /// Change the role of a user, e.g. to let them playtest unreleased challenges as a tester
+    /// (admin only). It applies once their access token is refreshed.
+    async fn set_user_role(
+        context: &Context,
+        user_id: String,
+        role: crate::db::models::UserRole,
+    ) -> FieldResult<crate::db::models::User> {
+        let user_id = uuid::Uuid::parse_str(&user_id)?;
+        handlers::users::roles::set_user_role(context, user_id, role).await
+    }
+
+    /// Hide a user from the scoreboard and statistics, e.g. a test account (admin only).
+    /// They can still log in and solve challenges.
+    async fn set_user_hidden(
+        context: &Context,
+        user_id: String,
+        hidden: bool,
+    ) -> FieldResult<crate::db::models::User> {
+        let user_id = uuid::Uuid::parse_str(&user_id)?;
+        handlers::scoreboard::hidden::set_user_hidden(context, user_id, hidden).await
+    }
+
+    /// Hide a team from the scoreboard and statistics, e.g. the organizers (admin only).
+    async fn set_team_hidden(
+        context: &Context,
+        team_id: String,
+        hidden: bool,
+    ) -> FieldResult<crate::db::models::Team> {
+        let team_id = uuid::Uuid::parse_str(&team_id)?;
+        handlers::scoreboard::hidden::set_team_hidden(context, team_id, hidden).await
+    }
+
+    /// Remove a single solve, e.g. one made with a leaked flag (admin only).
+    async fn revoke_solve(
+        context: &Context,
+        solve_id: String,
+        reason: Option<String>,
+    ) -> FieldResult<crate::db::models::Solve> {
+        let solve_id = uuid::Uuid::parse_str(&solve_id)?;
+        handlers::scoreboard::penalties::revoke_solve(context, solve_id, reason).await
+    }
+
+    /// Remove all solves of a team so its score is zero, returning how many were removed (admin only).
+    async fn zero_team_score(
+        context: &Context,
+        team_id: String,
+        reason: Option<String>,
+    ) -> FieldResult<i32> {
+        let team_id = uuid::Uuid::parse_str(&team_id)?;
+        handlers::scoreboard::penalties::zero_team_score(context, team_id, reason).await
+    }
+
+    /// Disqualify a team for cheating, or lift its disqualification (admin only).
+    /// Disqualified teams are left out of the scoreboard and can't submit flags.
+    async fn set_team_disqualified(
+        context: &Context,
+        team_id: String,
+        disqualified: bool,
+        reason: Option<String>,
+    ) -> FieldResult<crate::db::models::Team> {
+        let team_id = uuid::Uuid::parse_str(&team_id)?;
+        handlers::scoreboard::penalties::set_team_disqualified(
+            context,
+            team_id,
+            disqualified,
+            reason,
+        )
+        .await
+    }
+
+    /// Email all verified users, or only those in the audience (admin only).
+    ///
+    /// The emails are sent in the background, the returned broadcast tracks their delivery.
+    async fn broadcast_email(
+        context: &Context,
+        subject: String,
+        body_md: String,
+        audience: Option<handlers::email_broadcasts::EmailAudience>,
+    ) -> FieldResult<crate::db::models::EmailBroadcast> {
+        handlers::email_broadcasts::broadcast_email(
+            context,
+            subject,
+            body_md,
+            audience.unwrap_or_default(),
+        )
+        .await
+    }
+
+    /// Open a support ticket, e.g. to report a broken challenge.
+    async fn create_ticket(
+        context: &Context,
+        subject: String,
+        body: String,
+        challenge_id: Option<String>,
+    ) -> FieldResult<crate::db::models::Ticket> {
+        handlers::tickets::create_ticket(context, subject, body, challenge_id).await
+    }
+
+    /// Add a message to a ticket.
+    async fn reply_to_ticket(
+        context: &Context,
+        ticket_id: String,
+        body: String,
+    ) -> FieldResult<crate::db::models::Ticket> {
+        let ticket_id = uuid::Uuid::parse_str(&ticket_id)?;
+        handlers::tickets::reply_to_ticket(context, ticket_id, body).await
+    }
+
+    async fn set_ticket_status(
+        context: &Context,
+        ticket_id: String,
+        status: crate::db::models::TicketStatus,
+    ) -> FieldResult<crate::db::models::Ticket> {
+        let ticket_id = uuid::Uuid::parse_str(&ticket_id)?;
+        handlers::tickets::set_ticket_status(context, ticket_id, status).await
+    }
+
+    /// Create a code users can register with, e.g. if the event is invite-only (admin only).
+    async fn create_invite_code(
+        context: &Context,
+        #[graphql(default = 1)] max_uses: i32,
+        role: Option<crate::db::models::UserRole>,
+        division: Option<String>,
+        expires_at: Option<String>,
+    ) -> FieldResult<crate::db::models::InviteCode> {
+        handlers::users::invite_codes::create_invite_code(
+            context, max_uses, role, division, expires_at,
+        )
+        .await
+    }
+
+    async fn delete_invite_code(context: &Context, invite_code_id: String) -> FieldResult<bool> {
+        let invite_code_id = uuid::Uuid::parse_str(&invite_code_id)?;
+        handlers::users::invite_codes::delete_invite_code(context, invite_code_id).await
+    }
+
+    /// Permanently delete a user's account (admin only).
+    async fn delete_user(context: &Context, user_id: String) -> FieldResult<bool> {
+        let user_id = uuid::Uuid::parse_str(&user_id)?;
+        handlers::users::deletion::delete_user(context, user_id).await
+    }
+
+    /// Start generating a JSON export of all personal data stored about the current user.
+    async fn export_my_data(context: &Context) -> FieldResult<crate::db::models::DataExport> {
+        handlers::users::data_export::request_data_export(context).await
+    }
+
+    /// Register a URL that receives signed JSON payloads for the given events (admin only).
+    async fn create_webhook(
+        context: &Context,
+        url: String,
+        events: Vec<crate::db::models::WebhookEvent>,
+    ) -> FieldResult<crate::db::models::Webhook> {
+        handlers::webhooks::create_webhook(context, url, events).await
+    }
+
+    /// Change a webhook, optionally generating a new secret (admin only).
+    async fn update_webhook(
+        context: &Context,
+        webhook_id: String,
+        url: Option<String>,
+        events: Option<Vec<crate::db::models::WebhookEvent>>,
+        is_active: Option<bool>,
+        #[graphql(default = false)] rotate_secret: bool,
+    ) -> FieldResult<crate::db::models::Webhook> {
+        let webhook_id = uuid::Uuid::parse_str(&webhook_id)?;
+        handlers::webhooks::update_webhook(
+            context,
+            webhook_id,
+            url,
+            events,
+            is_active,
+            rotate_secret,
+        )
+        .await
+    }
+
+    /// Delete a webhook and its delivery log (admin only).
+    async fn delete_webhook(context: &Context, webhook_id: String) -> FieldResult<bool> {
+        let webhook_id = uuid::Uuid::parse_str(&webhook_id)?;
+        handlers::webhooks::delete_webhook(context, webhook_id).await
+    }
+
     async fn refresh_session(
         context: &Context,
         refresh_token: String,
@@ -53,10 +288,82 @@ impl Mutation {
         handlers::sessions::end_session(context, refresh_token).await
     }
 
+    /// Enable or disable maintenance mode, which blocks mutations from players (admin only).
+    async fn set_maintenance_mode(
+        context: &Context,
+        enabled: bool,
+        message: Option<String>,
+    ) -> FieldResult<crate::db::models::MaintenanceMode> {
+        handlers::maintenance::set_maintenance_mode(context, enabled, message).await
+    }
+
+    /// Turn the platform into a read-only archive, or back (admin only).
+    ///
+    /// While archived, nobody but admins can register, start instances or submit flags.
+    async fn set_archive_mode(
+        context: &Context,
+        enabled: bool,
+    ) -> FieldResult<crate::db::models::ArchiveMode> {
+        handlers::archive::set_archive_mode(context, enabled).await
+    }
+
+    /// Change which emails the current user wants to receive. Omitted preferences are kept.
+    async fn update_email_preferences(
+        context: &Context,
+        team_member_joined: Option<bool>,
+        team_member_left: Option<bool>,
+    ) -> FieldResult<crate::db::models::EmailPreferences> {
+        handlers::users::email_preferences::update_email_preferences(
+            context,
+            team_member_joined,
+            team_member_left,
+        )
+        .await
+    }
+
+    /// Mark notifications as read, or all of them if no ids are given.
+    /// Returns how many notifications were newly marked as read.
+    async fn mark_notifications_read(
+        context: &Context,
+        ids: Option<Vec<String>>,
+    ) -> FieldResult<i32> {
+        let ids = ids
+            .map(|ids| {
+                ids.iter()
+                    .map(|id| uuid::Uuid::parse_str(id))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+        handlers::notifications::mark_notifications_read(context, ids).await
+    }
+
+    /// Send a notification to a user, all members of a team, or everyone (admin only).
+    async fn send_notification(
+        context: &Context,
+        title: String,
+        #[graphql(default = String::new())] body: String,
+        user_id: Option<String>,
+        team_id: Option<String>,
+    ) -> FieldResult<bool> {
+        let user_id = user_id.map(|id| uuid::Uuid::parse_str(&id)).transpose()?;
+        let team_id = team_id.map(|id| uuid::Uuid::parse_str(&id)).transpose()?;
+        handlers::notifications::send_admin_message(context, title, body, user_id, team_id).await
+    }
+
     async fn sync_repo(context: &Context) -> FieldResult<bool> {
         handlers::repo::sync_repository(context).await
     }
 
+    /// Deploy a commit or tag instead of the latest commit of the branch (admin only). Without a
+    /// revision, the branch is followed again, or its latest commit is pinned with promoteLatest.
+    async fn set_repo_revision(
+        context: &Context,
+        revision: Option<String>,
+        #[graphql(default = false)] promote_latest: bool,
+    ) -> FieldResult<handlers::repo::SyncStatus> {
+        handlers::repo::set_repo_revision(context, revision, promote_latest).await
+    }
+
     async fn launch_challenge_instance(
         context: &Context,
         challenge_id: String,
@@ -68,6 +375,45 @@ impl Mutation {
         handlers::challenges::instances::stop_challenge_instance(context, challenge_id).await
     }
 
+    /// Postpones the deletion of your instance of a challenge, returns when it expires now.
+    async fn extend_challenge_instance(
+        context: &Context,
+        challenge_id: String,
+    ) -> FieldResult<String> {
+        handlers::challenges::instances::extend_challenge_instance(context, challenge_id).await
+    }
+
+    /// Recreates the containers of your instance of a challenge, its connection info stays the same.
+    async fn restart_challenge_instance(
+        context: &Context,
+        challenge_id: String,
+    ) -> FieldResult<bool> {
+        handlers::challenges::instances::restart_challenge_instance(context, challenge_id).await
+    }
+
+    /// Runs a command in a container of a team's instance, for debugging (admin only).
+    /// Commands are killed after 30 seconds.
+    async fn exec_in_instance(
+        context: &Context,
+        challenge_id: String,
+        actor: String,
+        command: Vec<String>,
+        service: Option<String>,
+        container: Option<String>,
+        stdin: Option<String>,
+    ) -> FieldResult<handlers::challenges::instances::ExecResult> {
+        handlers::challenges::instances::exec_in_instance(
+            context,
+            challenge_id,
+            actor,
+            command,
+            service,
+            container,
+            stdin,
+        )
+        .await
+    }
+
     /// Returns the ID of the solved challenge if the flag is correct, or null otherwise.
     async fn submit_flag(
         context: &Context,
@@ -77,6 +423,26 @@ impl Mutation {
         handlers::challenges::flags::submit_flag(context, challenge_id, flag).await
     }
 
+    /// Allow a team, or a user without a team, to submit flags for a challenge again after
+    /// using up its attempts (admin only).
+    async fn reset_attempts(
+        context: &Context,
+        challenge_id: String,
+        actor_id: String,
+    ) -> FieldResult<bool> {
+        let actor_id = uuid::Uuid::parse_str(&actor_id)?;
+        handlers::challenges::attempts::reset_attempts(context, challenge_id, actor_id).await
+    }
+
+    /// Check all wrong submissions of a challenge again, e.g. after its flag was fixed, and turn
+    /// those that are correct now into solves at their original time (admin only).
+    async fn rejudge_challenge(
+        context: &Context,
+        challenge_id: String,
+    ) -> FieldResult<handlers::challenges::rejudge::RejudgeResult> {
+        handlers::challenges::rejudge::rejudge_challenge(context, challenge_id).await
+    }
+
     async fn join_team_with_code(
         context: &Context,
         join_code_input: String,
@@ -89,8 +455,40 @@ impl Mutation {
         name: String,
         slug: String,
         create_join_code: bool,
+        country_code: Option<String>,
+        affiliation: Option<String>,
+        division: Option<String>,
     ) -> FieldResult<crate::db::models::Team> {
-        handlers::teams::create_team(context, name, slug, create_join_code).await
+        handlers::teams::create_team(
+            context,
+            name,
+            slug,
+            create_join_code,
+            country_code,
+            affiliation,
+            division,
+        )
+        .await
+    }
+
+    /// Move a team to another division, or remove it from all divisions with null (admin only).
+    async fn set_team_division(
+        context: &Context,
+        team_id: String,
+        division: Option<String>,
+    ) -> FieldResult<crate::db::models::Team> {
+        let team_id = uuid::Uuid::parse_str(&team_id)?;
+        handlers::teams::set_team_division(context, team_id, division).await
+    }
+
+    /// Change the details of the current user's team. Omitted fields are kept, null removes them.
+    async fn update_team(
+        context: &Context,
+        country_code: juniper::Nullable<String>,
+        affiliation: juniper::Nullable<String>,
+    ) -> FieldResult<crate::db::models::Team> {
+        handlers::teams::update_team(context, country_code.explicit(), affiliation.explicit())
+            .await
     }
 
     async fn leave_team(context: &Context) -> FieldResult<bool> {
@@ -104,4 +502,20 @@ impl Mutation {
     async fn disable_join_code(context: &Context) -> FieldResult<bool> {
         handlers::teams::disable_join_code(context).await
     }
+
+    /// Upload a new avatar (PNG, JPEG, WebP or GIF, base64 encoded) for the current user.
+    async fn upload_avatar(
+        context: &Context,
+        image_base64: String,
+    ) -> FieldResult<crate::db::models::User> {
+        handlers::avatars::upload_avatar(context, image_base64).await
+    }
+
+    /// Upload a new avatar (PNG, JPEG, WebP or GIF, base64 encoded) for the current user's team.
+    async fn upload_team_avatar(
+        context: &Context,
+        image_base64: String,
+    ) -> FieldResult<crate::db::models::Team> {
+        handlers::avatars::upload_team_avatar(context, image_base64).await
+    }
 }
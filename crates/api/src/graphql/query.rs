@@ -23,16 +23,43 @@ impl Query {
         crate::graphql::handlers::repo::get_sync_status(context).await
     }
 
+    /// Build status of every challenge at the synced commit, failed builds first (authors only)
+    async fn build_status(
+        context: &Context,
+    ) -> juniper::FieldResult<Vec<crate::graphql::handlers::repo::ChallengeBuild>> {
+        crate::graphql::handlers::repo::get_build_status(context).await
+    }
+
+    /// Event settings, with texts in the given locale if they are translated into it
     async fn event_config(
         context: &Context,
+        locale: Option<String>,
     ) -> juniper::FieldResult<crate::graphql::handlers::event::EventConfig> {
-        crate::graphql::handlers::event::get_event_config(context).await
+        crate::graphql::handlers::event::get_event_config(context)
+            .await
+            .map(|config| config.localized(locale.as_deref()))
     }
 
+    /// Challenges of the current user. Filters are combined, text search covers the name and description.
+    ///
+    /// Descriptions are in the given locale if the challenge is translated into it.
     async fn challenges(
         context: &Context,
+        category: Option<String>,
+        difficulty: Option<String>,
+        solved: Option<bool>,
+        search: Option<String>,
+        sort: Option<crate::graphql::handlers::challenges::ChallengeSort>,
+        locale: Option<String>,
     ) -> juniper::FieldResult<Vec<crate::graphql::handlers::challenges::CtfChallengeMetadata>> {
-        crate::graphql::handlers::challenges::get_challenges(context).await
+        let filter = crate::graphql::handlers::challenges::ChallengeFilter {
+            category,
+            difficulty,
+            solved,
+            search,
+        };
+        crate::graphql::handlers::challenges::get_filtered_challenges(context, filter, sort, locale)
+            .await
     }
 
     async fn users(context: &Context) -> juniper::FieldResult<Vec<crate::db::models::User>> {
@@ -43,6 +70,19 @@ impl Query {
         crate::graphql::handlers::users::get_current_user(context).await
     }
 
+    /// Registrations waiting for approval (admin only)
+    async fn pending_users(
+        context: &Context,
+    ) -> juniper::FieldResult<Vec<crate::db::models::User>> {
+        crate::graphql::handlers::users::approval::get_pending_users(context).await
+    }
+
+    async fn my_data_exports(
+        context: &Context,
+    ) -> juniper::FieldResult<Vec<crate::db::models::DataExport>> {
+        crate::graphql::handlers::users::data_export::get_my_data_exports(context).await
+    }
+
     async fn user_by_id(
         context: &Context,
         user_id: String,
@@ -58,7 +98,192 @@ impl Query {
     async fn teams(context: &Context) -> juniper::FieldResult<Vec<crate::db::models::Team>> {
         crate::graphql::handlers::teams::get_teams(context).await
     }
+
+    async fn team(
+        context: &Context,
+        slug: String,
+    ) -> juniper::FieldResult<Option<crate::db::models::Team>> {
+        crate::graphql::handlers::teams::get_team_by_slug(context, slug).await
+    }
     
+    /// The scoreboard, or only the teams of one division with their rank in it
+    async fn scoreboard(
+        context: &Context,
+        division: Option<String>,
+    ) -> juniper::FieldResult<crate::graphql::handlers::scoreboard::Scoreboard> {
+        crate::graphql::handlers::scoreboard::get_division_scoreboard(context, division).await
+    }
+
+    async fn maintenance_mode(
+        context: &Context,
+    ) -> juniper::FieldResult<crate::db::models::MaintenanceMode> {
+        crate::graphql::handlers::maintenance::get_maintenance_mode(context).await
+    }
+
+    /// Whether the platform is a read-only archive of a past event
+    async fn archive_mode(
+        context: &Context,
+    ) -> juniper::FieldResult<crate::db::models::ArchiveMode> {
+        crate::graphql::handlers::archive::get_archive_mode(context).await
+    }
+
+    /// Notifications of the current user, newest first
+    async fn notifications(
+        context: &Context,
+        #[graphql(default = false)] unread_only: bool,
+        #[graphql(default = 50)] limit: i32,
+    ) -> juniper::FieldResult<Vec<crate::graphql::handlers::notifications::UserNotification>> {
+        crate::graphql::handlers::notifications::get_notifications(context, unread_only, limit)
+            .await
+    }
+
+    async fn unread_notification_count(context: &Context) -> juniper::FieldResult<i32> {
+        crate::graphql::handlers::notifications::get_unread_notification_count(context).await
+    }
+
+    /// Which emails the current user wants to receive
+    async fn email_preferences(
+        context: &Context,
+    ) -> juniper::FieldResult<crate::db::models::EmailPreferences> {
+        crate::graphql::handlers::users::email_preferences::get_email_preferences(context).await
+    }
+
+    /// All configured webhooks (admin only)
+    async fn webhooks(context: &Context) -> juniper::FieldResult<Vec<crate::db::models::Webhook>> {
+        crate::graphql::handlers::webhooks::get_webhooks(context).await
+    }
+
+    /// Admin actions against teams and users, newest first, optionally only those against one
+    /// team or user (admin only)
+    async fn audit_log(
+        context: &Context,
+        target_id: Option<String>,
+        #[graphql(default = 0)] offset: i32,
+        #[graphql(default = 100)] limit: i32,
+    ) -> juniper::FieldResult<Vec<crate::db::models::AuditLogEntry>> {
+        let target_id = target_id
+            .map(|id| uuid::Uuid::parse_str(&id))
+            .transpose()?;
+        crate::graphql::handlers::audit_log::get_audit_log(context, target_id, offset, limit).await
+    }
+
+    /// Submissions of flags that were issued to another team or user (admin only)
+    async fn cheating_incidents(
+        context: &Context,
+    ) -> juniper::FieldResult<Vec<crate::db::models::CheatingIncident>> {
+        crate::graphql::handlers::challenges::cheating::get_cheating_incidents(context).await
+    }
+
+    /// Support tickets of the current user, about challenges they authored, or all of them for admins
+    async fn tickets(
+        context: &Context,
+        status: Option<crate::db::models::TicketStatus>,
+    ) -> juniper::FieldResult<Vec<crate::db::models::Ticket>> {
+        crate::graphql::handlers::tickets::get_tickets(context, status).await
+    }
+
+    async fn ticket(
+        context: &Context,
+        id: String,
+    ) -> juniper::FieldResult<crate::db::models::Ticket> {
+        let id = uuid::Uuid::parse_str(&id)?;
+        crate::graphql::handlers::tickets::get_ticket(context, id).await
+    }
+
+    /// Codes users can register with (admin only)
+    async fn invite_codes(
+        context: &Context,
+    ) -> juniper::FieldResult<Vec<crate::db::models::InviteCode>> {
+        crate::graphql::handlers::users::invite_codes::get_invite_codes(context).await
+    }
+
+    /// Emails sent to users by admins, newest first (admin only)
+    async fn email_broadcasts(
+        context: &Context,
+    ) -> juniper::FieldResult<Vec<crate::db::models::EmailBroadcast>> {
+        crate::graphql::handlers::email_broadcasts::get_email_broadcasts(context).await
+    }
+
+    /// IPs flags were submitted from by several users, to investigate shared accounts (admin only)
+    async fn submission_ips(
+        context: &Context,
+        #[graphql(default = 2)] min_users: i32,
+    ) -> juniper::FieldResult<Vec<crate::graphql::handlers::challenges::submission_ips::SubmissionIp>>
+    {
+        crate::graphql::handlers::challenges::submission_ips::get_submission_ips(context, min_users)
+            .await
+    }
+
+    /// Incorrect flag submissions, newest first, to spot bruteforcing and typos (admin only)
+    async fn invalid_submissions(
+        context: &Context,
+        filter: Option<crate::graphql::handlers::challenges::invalid_submissions::InvalidSubmissionFilter>,
+        #[graphql(default = 0)] offset: i32,
+        #[graphql(default = 50)] limit: i32,
+    ) -> juniper::FieldResult<
+        crate::graphql::handlers::challenges::invalid_submissions::InvalidSubmissionPage,
+    > {
+        crate::graphql::handlers::challenges::invalid_submissions::get_invalid_submissions(
+            context,
+            filter.unwrap_or_default(),
+            offset,
+            limit,
+        )
+        .await
+    }
+
+    /// Background jobs, newest first, optionally only those with the given status or kind (admin only)
+    async fn jobs(
+        context: &Context,
+        status: Option<crate::db::models::JobStatus>,
+        kind: Option<String>,
+        #[graphql(default = 0)] offset: i32,
+        #[graphql(default = 100)] limit: i32,
+    ) -> juniper::FieldResult<Vec<crate::db::models::Job>> {
+        crate::graphql::handlers::jobs::get_jobs(context, status, kind, offset, limit).await
+    }
+
+    /// Number of background jobs in each status (admin only)
+    async fn job_counts(
+        context: &Context,
+    ) -> juniper::FieldResult<crate::graphql::handlers::jobs::JobCounts> {
+        crate::graphql::handlers::jobs::get_job_counts(context).await
+    }
+
+    /// Aggregated statistics about solves, submissions, registrations and instances (admin only)
+    async fn event_stats(
+        context: &Context,
+    ) -> juniper::FieldResult<crate::graphql::handlers::stats::EventStats> {
+        crate::graphql::handlers::stats::get_event_stats(context).await
+    }
+
+    /// CPU and memory currently used by instances, per instance and per challenge (admin only)
+    async fn resource_usage(
+        context: &Context,
+        challenge_id: Option<String>,
+    ) -> juniper::FieldResult<crate::graphql::handlers::stats::ResourceUsage> {
+        crate::graphql::handlers::stats::get_resource_usage(context, challenge_id).await
+    }
+
+    /// The last lines logged by the containers of an actor's instance (admin only)
+    async fn instance_logs(
+        context: &Context,
+        challenge_id: String,
+        actor: String,
+        service: Option<String>,
+        tail_lines: Option<i32>,
+    ) -> juniper::FieldResult<Vec<crate::graphql::handlers::challenges::instances::InstanceLogLine>>
+    {
+        crate::graphql::handlers::challenges::instances::get_instance_logs(
+            context,
+            challenge_id,
+            actor,
+            service,
+            tail_lines,
+        )
+        .await
+    }
+
     async fn captcha(
         context: &Context,
     ) -> juniper::FieldResult<CaptchaChallenge> {
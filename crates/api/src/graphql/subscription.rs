@@ -0,0 +1,37 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use juniper::{FieldResult, graphql_subscription};
+
+use super::Context;
+use super::handlers::challenges::instances::InstanceLogStream;
+use super::handlers::notifications::NotificationStream;
+
+pub struct Subscription;
+
+#[graphql_subscription(context = Context)]
+impl Subscription {
+    /// Notifications the current user receives while subscribed
+    async fn notifications(context: &Context) -> FieldResult<NotificationStream> {
+        super::handlers::notifications::subscribe_notifications(context)
+    }
+
+    /// Logs of the containers of an actor's instance as they are written (admin only)
+    async fn instance_logs(
+        context: &Context,
+        challenge_id: String,
+        actor: String,
+        service: Option<String>,
+        tail_lines: Option<i32>,
+    ) -> FieldResult<InstanceLogStream> {
+        super::handlers::challenges::instances::subscribe_instance_logs(
+            context,
+            challenge_id,
+            actor,
+            service,
+            tail_lines,
+        )
+        .await
+    }
+}
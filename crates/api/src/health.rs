@@ -0,0 +1,62 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::time::Duration;
+
+use diesel_async::RunQueryDsl;
+use serde_json::json;
+
+use crate::{
+    graphql::BaseContext,
+    manager_api::{GetSyncStatusRequest, repository_service_client::RepositoryServiceClient},
+};
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+async fn check_database(ctx: &BaseContext) -> Result<(), String> {
+    let mut conn = ctx.db_pool.get().await.map_err(|e| e.to_string())?;
+    diesel::sql_query("SELECT 1")
+        .execute(&mut conn)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+async fn check_manager(ctx: &BaseContext) -> Result<(), String> {
+    RepositoryServiceClient::new(ctx.grpc_client.clone())
+        .get_sync_status(GetSyncStatusRequest {})
+        .await
+        .map_err(|e| e.message().to_string())?;
+    Ok(())
+}
+
+async fn with_timeout(check: impl Future<Output = Result<(), String>>) -> Result<(), String> {
+    tokio::time::timeout(CHECK_TIMEOUT, check)
+        .await
+        .unwrap_or_else(|_| Err("Timed out".to_string()))
+}
+
+/// Checks whether the database and the manager are reachable.
+///
+/// Returns the HTTP status code and a JSON body describing the state of each dependency.
+pub async fn readiness(ctx: &BaseContext) -> (u16, String) {
+    let (database, manager) = tokio::join!(
+        with_timeout(check_database(ctx)),
+        with_timeout(check_manager(ctx))
+    );
+    let status = if database.is_ok() && manager.is_ok() {
+        200
+    } else {
+        503
+    };
+    let describe = |result: Result<(), String>| match result {
+        Ok(()) => json!({ "status": "ok" }),
+        Err(e) => json!({ "status": "error", "error": e }),
+    };
+    let body = json!({
+        "database": describe(database),
+        "manager": describe(manager),
+    });
+    (status, body.to_string())
+}
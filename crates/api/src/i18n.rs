@@ -0,0 +1,52 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::collections::HashMap;
+
+/// Picks the translation of a text for the requested locale.
+///
+/// Locales match case-insensitively and regional variants fall back to the language
+/// (e.g. "de-AT" to "de"). Without a matching translation, the default text is used.
+pub fn localize<'a>(
+    default: &'a str,
+    translations: &'a HashMap<String, String>,
+    locale: Option<&str>,
+) -> &'a str {
+    let Some(locale) = locale.map(|l| l.replace('_', "-")) else {
+        return default;
+    };
+    let find = |wanted: &str| {
+        translations
+            .iter()
+            .find(|(l, _)| l.replace('_', "-").eq_ignore_ascii_case(wanted))
+            .map(|(_, text)| text.as_str())
+    };
+    find(&locale)
+        .or_else(|| {
+            locale
+                .split_once('-')
+                .and_then(|(language, _)| find(language))
+        })
+        .unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_localize_falls_back() {
+        let translations = HashMap::from([
+            ("en".to_string(), "Hello".to_string()),
+            ("de".to_string(), "Hallo".to_string()),
+            ("pt-BR".to_string(), "Olá".to_string()),
+        ]);
+        assert_eq!(localize("Hello", &translations, None), "Hello");
+        assert_eq!(localize("Hello", &translations, Some("DE")), "Hallo");
+        assert_eq!(localize("Hello", &translations, Some("de-AT")), "Hallo");
+        assert_eq!(localize("Hello", &translations, Some("pt_br")), "Olá");
+        assert_eq!(localize("Hello", &translations, Some("fr")), "Hello");
+        assert_eq!(localize("Plain", &HashMap::new(), Some("de")), "Plain");
+    }
+}
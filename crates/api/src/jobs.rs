@@ -0,0 +1,260 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Postgres-backed queue for work that happens in the background, e.g. sending emails or
+//! recomputing scores.
+//!
+//! Handlers are registered per job kind with [`register`] before [`start_worker`] is called.
+//! Jobs are stored in the database, so they survive restarts and are shared by all api replicas.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{LazyLock, RwLock},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use diesel::{
+    prelude::*,
+    sql_types::{BigInt, Integer, Text, Uuid},
+};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use tokio::task::JoinSet;
+
+use crate::{
+    db::models::{JobStatus, NewJob},
+    graphql::BaseContext,
+};
+
+type DbPool = diesel_async::pooled_connection::bb8::Pool<diesel_async::AsyncPgConnection>;
+
+pub type JobError = Box<dyn std::error::Error + Send + Sync>;
+pub type JobFuture = Pin<Box<dyn Future<Output = Result<(), JobError>> + Send>>;
+/// Runs a job with its payload, a failed job is retried until it ran `max_attempts` times
+pub type JobHandler = fn(BaseContext, serde_json::Value) -> JobFuture;
+
+/// Attempts of a job unless another number is given when enqueuing it
+pub const DEFAULT_MAX_ATTEMPTS: i32 = 5;
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(30);
+/// Retries are never delayed longer than this
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(6 * 60 * 60);
+/// How often the worker looks for jobs enqueued by other api replicas or scheduled for later
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// Running jobs are picked up again by other workers after this long, so they are run again if
+/// the replica running them stops
+const CLAIM_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+/// Jobs run concurrently by each worker
+const BATCH_SIZE: i64 = 10;
+
+static HANDLERS: LazyLock<RwLock<HashMap<&'static str, JobHandler>>> =
+    LazyLock::new(Default::default);
+
+/// Wakes up the worker of this replica when a job is enqueued
+static NEW_JOBS: LazyLock<tokio::sync::Notify> = LazyLock::new(tokio::sync::Notify::new);
+
+/// Sets the handler jobs of a kind are run with.
+pub fn register(kind: &'static str, handler: JobHandler) {
+    HANDLERS
+        .write()
+        .expect("Job handlers lock poisoned")
+        .insert(kind, handler);
+}
+
+fn handler(kind: &str) -> Option<JobHandler> {
+    HANDLERS
+        .read()
+        .expect("Job handlers lock poisoned")
+        .get(kind)
+        .copied()
+}
+
+/// Enqueues a job to run as soon as a worker is free.
+///
+/// Takes a connection so the job can be enqueued in the same transaction as the change it
+/// belongs to. Jobs enqueued in a transaction are only picked up once it is committed.
+pub async fn enqueue(
+    conn: &mut AsyncPgConnection,
+    kind: &str,
+    payload: serde_json::Value,
+) -> QueryResult<uuid::Uuid> {
+    enqueue_at(conn, kind, payload, Utc::now(), DEFAULT_MAX_ATTEMPTS).await
+}
+
+/// Enqueues a job to run at the given time, or as soon as possible if it has passed.
+pub async fn enqueue_at(
+    conn: &mut AsyncPgConnection,
+    kind: &str,
+    payload: serde_json::Value,
+    run_at: DateTime<Utc>,
+    max_attempts: i32,
+) -> QueryResult<uuid::Uuid> {
+    use crate::db::schema::jobs;
+
+    let id = diesel::insert_into(jobs::table)
+        .values(NewJob {
+            kind: kind.to_string(),
+            payload: payload.to_string(),
+            max_attempts: max_attempts.max(1),
+            run_at,
+        })
+        .returning(jobs::id)
+        .get_result(conn)
+        .await?;
+    if run_at <= Utc::now() {
+        NEW_JOBS.notify_one();
+    }
+    Ok(id)
+}
+
+/// How long to wait before running a job again after its nth attempt failed
+fn retry_delay(attempt: i32) -> Duration {
+    RETRY_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt.clamp(1, 32) as u32 - 1))
+        .min(MAX_RETRY_DELAY)
+}
+
+#[derive(QueryableByName)]
+struct ClaimedJob {
+    #[diesel(sql_type = Uuid)]
+    id: uuid::Uuid,
+    #[diesel(sql_type = Text)]
+    kind: String,
+    #[diesel(sql_type = Text)]
+    payload: String,
+    #[diesel(sql_type = Integer)]
+    attempts: i32,
+    #[diesel(sql_type = Integer)]
+    max_attempts: i32,
+}
+
+/// Runs a job, turning a panic of its handler into an error.
+async fn run(ctx: BaseContext, job: &ClaimedJob) -> Result<(), String> {
+    let Some(handler) = handler(&job.kind) else {
+        return Err(format!("No handler for jobs of kind {}", job.kind));
+    };
+    let payload = serde_json::from_str(&job.payload).map_err(|e| e.to_string())?;
+    match tokio::spawn(handler(ctx, payload)).await {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(e) if e.is_panic() => Err("Job panicked".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Runs a claimed job and records its outcome.
+async fn run_and_record(ctx: BaseContext, job: ClaimedJob) -> Result<(), JobError> {
+    use crate::db::schema::jobs;
+
+    let result = run(ctx.clone(), &job).await;
+    let mut conn = ctx.db_pool.get().await?;
+    match result {
+        Ok(()) => {
+            diesel::update(jobs::table.filter(jobs::id.eq(job.id)))
+                .set((
+                    jobs::status.eq(JobStatus::Succeeded),
+                    jobs::error.eq::<Option<String>>(None),
+                    jobs::locked_until.eq::<Option<DateTime<Utc>>>(None),
+                    jobs::finished_at.eq(Some(Utc::now())),
+                ))
+                .execute(&mut conn)
+                .await?;
+        }
+        Err(e) => {
+            tracing::warn!(
+                "Job {} ({}) failed (attempt {}/{}): {}",
+                job.id,
+                job.kind,
+                job.attempts,
+                job.max_attempts,
+                e
+            );
+            let (status, finished_at) = if job.attempts >= job.max_attempts {
+                (JobStatus::Failed, Some(Utc::now()))
+            } else {
+                (JobStatus::Pending, None)
+            };
+            diesel::update(jobs::table.filter(jobs::id.eq(job.id)))
+                .set((
+                    jobs::status.eq(status),
+                    jobs::error.eq(Some(e)),
+                    jobs::run_at.eq(Utc::now() + retry_delay(job.attempts)),
+                    jobs::locked_until.eq::<Option<DateTime<Utc>>>(None),
+                    jobs::finished_at.eq(finished_at),
+                ))
+                .execute(&mut conn)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs one batch of due jobs, returning how many were claimed.
+async fn run_pending(ctx: &BaseContext) -> Result<usize, JobError> {
+    let claimed = {
+        let mut conn = ctx.db_pool.get().await?;
+        // SKIP LOCKED lets the workers of several replicas claim different jobs
+        diesel::sql_query(
+            "UPDATE jobs SET status = 'RUNNING', attempts = attempts + 1,
+                locked_until = NOW() + $1 * INTERVAL '1 second'
+            WHERE id IN (
+                SELECT id FROM jobs
+                WHERE (status = 'PENDING' AND run_at <= NOW())
+                    OR (status = 'RUNNING' AND locked_until <= NOW())
+                ORDER BY run_at
+                LIMIT $2
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, kind, payload, attempts, max_attempts",
+        )
+        .bind::<BigInt, _>(CLAIM_TIMEOUT.as_secs() as i64)
+        .bind::<BigInt, _>(BATCH_SIZE)
+        .load::<ClaimedJob>(&mut conn)
+        .await?
+    };
+    let count = claimed.len();
+    let results = futures_util::future::join_all(
+        claimed
+            .into_iter()
+            .map(|job| run_and_record(ctx.clone(), job)),
+    )
+    .await;
+    for result in results {
+        if let Err(e) = result {
+            tracing::error!("Failed to record job result: {}", e);
+        }
+    }
+    Ok(count)
+}
+
+/// Starts the background task that runs queued jobs.
+pub fn start_worker(tasks: &mut JoinSet<()>, ctx: BaseContext) {
+    tasks.spawn(async move {
+        loop {
+            match run_pending(&ctx).await {
+                // There may be more due jobs
+                Ok(claimed) if claimed as i64 == BATCH_SIZE => continue,
+                Ok(_) => {}
+                Err(e) => tracing::error!("Failed to run jobs: {}", e),
+            }
+            tokio::select! {
+                _ = NEW_JOBS.notified() => {}
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1), Duration::from_secs(30));
+        assert_eq!(retry_delay(2), Duration::from_secs(60));
+        assert_eq!(retry_delay(4), Duration::from_secs(240));
+        assert_eq!(retry_delay(100), MAX_RETRY_DELAY);
+    }
+}
@@ -1,6 +1,19 @@
+pub mod config;
 pub mod db;
+pub mod error_reporting;
 pub mod graphql;
+pub mod health;
+pub mod i18n;
+pub mod jobs;
+pub mod metrics;
+pub mod persisted_queries;
+pub mod sse;
 pub mod discord;
+pub mod email;
+pub mod storage;
+pub mod telemetry;
+pub mod tls;
+pub mod webhooks;
 
 pub mod manager_api {
     tonic::include_proto!("plfanzen_ctf");
@@ -2,88 +2,124 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use std::{convert::Infallible, error::Error, net::SocketAddr, sync::Arc};
+use std::{convert::Infallible, error::Error, sync::Arc, time::Duration};
 
 use diesel::Connection;
 use diesel_async::pooled_connection::AsyncDieselConnectionManager;
 use ed25519_dalek::SigningKey;
-use http_body_util::Full;
-use hyper::{Method, Response, StatusCode, body::Bytes, service::service_fn};
-use hyper_util::rt::{TokioExecutor, TokioIo};
-use juniper::{EmptySubscription, RootNode};
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited, combinators::UnsyncBoxBody};
+use hyper::{Method, Request, Response, StatusCode, body::Bytes, service::service_fn};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::graceful::GracefulShutdown,
+};
+use juniper::RootNode;
 use juniper_hyper::{graphiql, graphql, playground};
 use slugify::slugify;
 use tokio::net::TcpListener;
+use tracing::Instrument;
 
-use plfanzen_api::db;
-use plfanzen_api::graphql::{self, AuthenticatedUser, Context, Mutation, Query, Schema};
+use plfanzen_api::config::{self, Config};
+use plfanzen_api::graphql::{
+    self, AuthenticatedUser, Context, Mutation, Query, Schema, Subscription,
+};
+use plfanzen_api::{
+    db, error_reporting, health, jobs, metrics, persisted_queries, sse, telemetry, tls, webhooks,
+};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
-    // Set RUST_LOG to debug
-    unsafe {
-        std::env::set_var("RUST_LOG", "debug");
-    }
-    tracing_subscriber::fmt::init();
     rustls::crypto::aws_lc_rs::default_provider()
         .install_default()
         .expect("Failed to set AWS-LC-RS as default TLS provider");
 
+    let args = match config::CliArgs::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}");
+            eprintln!("Usage: plfanzen-api [--config <file>] [--check-config]");
+            std::process::exit(2);
+        }
+    };
+    // Loaded before logging is set up, as it configures where traces are exported to
+    let config_file = Config::find_file(args.config_file.as_deref());
+    let config = Config::load(config_file.as_deref())?;
+    let tracer_provider = telemetry::init_tracing(config.otel_exporter_otlp_endpoint.as_deref());
+    if let Some(config_file) = &config_file {
+        tracing::info!("Loaded configuration from {}", config_file.display());
+    }
+    if let Err(e) = config.validate() {
+        tracing::error!("{e}");
+        std::process::exit(1);
+    }
+    let tls_acceptor = tls::load_tls_acceptor(&config).expect("Failed to load TLS certificate");
+    if let Some(allowlist_file) = &config.graphql_allowlist_file {
+        persisted_queries::load_allowlist(allowlist_file)?;
+    }
+    if args.check_config {
+        if config.signing_key_file.exists() {
+            let keypair_json = std::fs::read_to_string(&config.signing_key_file)?;
+            serde_json::from_str::<SigningKey>(&keypair_json)?;
+        }
+        tracing::info!("Configuration is valid");
+        return Ok(());
+    }
+    error_reporting::init(
+        config.sentry_dsn.as_deref(),
+        config.sentry_environment.clone(),
+    );
+
     // This is required so the bot is shown as online on Discord
-    // Check if the DISCORD_TOKEN env var is set
-    if std::env::var("DISCORD_TOKEN").is_err() {
-        tracing::warn!(
-            "DISCORD_TOKEN environment variable is not set; Discord bot will not be started."
-        );
-    } else {
+    if let Some(token) = config.discord_token.clone() {
         let _bot_task = tokio::spawn(async move {
-            plfanzen_api::discord::run_new_client().await.unwrap();
+            plfanzen_api::discord::run_new_client(&token).await.unwrap();
         });
+    } else {
+        tracing::warn!("discord_token is not set; Discord bot will not be started.");
     }
 
-    for var in &[
-        "EMAIL_SMTP_SERVER",
-        "EMAIL_SMTP_USERNAME",
-        "EMAIL_SMTP_PASSWORD",
-        "EMAIL_FROM_ADDRESS",
-    ] {
-        if std::env::var(var).is_err() {
-            tracing::warn!(
-                "Environment variable {var} is not set; users will be approved automatically!"
-            );
-        }
+    plfanzen_api::email::init(&config);
+    if !plfanzen_api::email::is_configured() {
+        tracing::warn!(
+            "Email is not configured (email_smtp_server, email_smtp_username, email_smtp_password and email_from_address); users will be approved automatically!"
+        );
     }
+    graphql::init_captcha(&config);
+    let storage = plfanzen_api::storage::Storage::from_config(&config)?;
 
-    let root_node: Arc<Schema> = Arc::new(RootNode::new(Query, Mutation, EmptySubscription::new()));
+    let root_node: Arc<Schema> = Arc::new(RootNode::new(Query, Mutation, Subscription));
 
-    let addr = SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 0], 3000));
+    let addr = config.listen_addr;
     let listener = TcpListener::bind(addr).await?;
 
-    let key_file = std::env::var("SIGNING_KEY_FILE").unwrap_or_else(|_| "key.json".to_string());
-    let key_file = std::path::Path::new(&key_file);
+    let key_file = config.signing_key_file.as_path();
     if !key_file.exists() {
         let mut csprng = rand::rngs::OsRng;
         let signing_key: SigningKey = SigningKey::generate(&mut csprng);
         let keypair_json = serde_json::to_string_pretty(&signing_key)?;
         std::fs::write(key_file, keypair_json)?;
-        tracing::info!("Generated new signing key and saved to key.json");
+        tracing::info!(
+            "Generated new signing key and saved to {}",
+            key_file.display()
+        );
     }
     let keypair_json = std::fs::read_to_string(key_file)?;
     let signing_key: SigningKey = serde_json::from_str(&keypair_json)?;
 
-    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let database_url = config.database_url().to_string();
     {
         let mut pg_connection = diesel::pg::PgConnection::establish(&database_url)
             .expect("Failed to connect to database for migrations");
         db::run_migrations(&mut pg_connection).expect("Failed to run database migrations");
     }
     let ctx = graphql::BaseContext {
-        grpc_client: tonic::transport::Channel::from_shared(
-            std::env::var("MANAGER_ENDPOINT").expect("No manager endpoint set"),
-        )
-        .expect("Invalid manager endpoint URL")
-        .connect()
-        .await?,
+        grpc_client: tonic::service::interceptor::InterceptedService::new(
+            tonic::transport::Channel::from_shared(config.manager_endpoint().to_string())
+                .expect("Invalid manager endpoint URL")
+                .connect()
+                .await?,
+            telemetry::TraceContextInterceptor,
+        ),
         db_pool: {
             let manager =
                 AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(database_url);
@@ -93,233 +129,581 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
                 .expect("Failed to create DB connection pool")
         },
         keypair: signing_key,
+        config: std::sync::Arc::new(config.clone()),
+        storage,
     };
-    tracing::info!("Listening on http://{addr}");
+    let mut background_tasks = tokio::task::JoinSet::new();
+    graphql::start_points_worker(&mut background_tasks, ctx.clone());
+    graphql::start_email_worker(&mut background_tasks, ctx.clone());
+    graphql::start_orphan_collector(&mut background_tasks, ctx.clone());
+    graphql::start_release_watcher(&mut background_tasks, ctx.clone());
+    metrics::start_session_counter(&mut background_tasks, ctx.clone());
+    jobs::register(graphql::DATA_EXPORT_JOB, graphql::run_data_export_job);
+    jobs::register(webhooks::DELIVERY_JOB, webhooks::run_delivery_job);
+    jobs::start_worker(&mut background_tasks, ctx.clone());
+    if tls_acceptor.is_some() {
+        tracing::info!("Listening on https://{addr}");
+    } else {
+        tracing::info!("Listening on http://{addr}");
+    }
+    let graceful = GracefulShutdown::new();
+    let mut shutdown = std::pin::pin!(shutdown_signal());
     loop {
-        let (stream, remote_addr) = listener.accept().await?;
-
-        let io = TokioIo::new(stream);
+        let (stream, remote_addr) = tokio::select! {
+            conn = listener.accept() => conn?,
+            _ = &mut shutdown => break,
+        };
 
+        let watcher = graceful.watcher();
         let root_node = root_node.clone();
         let ctx = ctx.clone();
+        let tls_acceptor = tls_acceptor.clone();
 
         tokio::spawn(async move {
             let root_node = root_node.clone();
             let ctx = ctx.clone();
 
-            if let Err(e) = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new())
-                .serve_connection(
-                    io,
-                    service_fn(move |req| {
-                        let root_node = root_node.clone();
-                        let mut remote_ip = remote_addr.ip();
+            // The handshake happens here so a slow client doesn't block accepting other connections
+            let stream: Box<dyn tls::ClientStream> = match tls_acceptor {
+                Some(acceptor) => match acceptor.accept(stream).await {
+                    Ok(stream) => Box::new(stream),
+                    Err(e) => {
+                        tracing::debug!("TLS handshake with {remote_addr} failed: {e}");
+                        return;
+                    }
+                },
+                None => Box::new(stream),
+            };
+            let io = TokioIo::new(stream);
 
-                        let is_private = match remote_ip {
-                            std::net::IpAddr::V4(ipv4) => ipv4.is_private(),
-                            std::net::IpAddr::V6(ipv6) => ipv6.is_unique_local(),
-                        };
+            let builder = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new());
+            let conn = builder.serve_connection(
+                io,
+                service_fn(move |req: Request<hyper::body::Incoming>| {
+                    let root_node = root_node.clone();
+                    let mut remote_ip = remote_addr.ip();
 
-                        if is_private
-                            && let Some(xff) = req.headers().get("x-forwarded-for")
-                            && let Ok(xff_str) = xff.to_str()
-                        {
-                            for ip_str in xff_str.split(',') {
-                                if let Ok(ip) = ip_str.trim().parse::<std::net::IpAddr>() {
-                                    let is_private = match ip {
-                                        std::net::IpAddr::V4(ipv4) => ipv4.is_private(),
-                                        std::net::IpAddr::V6(ipv6) => ipv6.is_unique_local(),
-                                    };
-                                    if !is_private {
-                                        remote_ip = ip;
-                                        break;
-                                    }
+                    let is_private = match remote_ip {
+                        std::net::IpAddr::V4(ipv4) => ipv4.is_private(),
+                        std::net::IpAddr::V6(ipv6) => ipv6.is_unique_local(),
+                    };
+
+                    if is_private
+                        && let Some(xff) = req.headers().get("x-forwarded-for")
+                        && let Ok(xff_str) = xff.to_str()
+                    {
+                        for ip_str in xff_str.split(',') {
+                            if let Ok(ip) = ip_str.trim().parse::<std::net::IpAddr>() {
+                                let is_private = match ip {
+                                    std::net::IpAddr::V4(ipv4) => ipv4.is_private(),
+                                    std::net::IpAddr::V6(ipv6) => ipv6.is_unique_local(),
+                                };
+                                if !is_private {
+                                    remote_ip = ip;
+                                    break;
                                 }
                             }
                         }
+                    }
 
-                        let auth = req.headers().get("authorization").and_then(|auth_header| {
-                            let auth_str = auth_header.to_str().ok()?;
-                            if auth_str.starts_with("Bearer ") {
-                                Some(auth_str.trim_start_matches("Bearer ").to_string())
-                            } else {
-                                None
-                            }
+                    let auth = req.headers().get("authorization").and_then(|auth_header| {
+                        let auth_str = auth_header.to_str().ok()?;
+                        if auth_str.starts_with("Bearer ") {
+                            Some(auth_str.trim_start_matches("Bearer ").to_string())
+                        } else {
+                            None
+                        }
+                    });
+                    let user_details = auth
+                        .and_then(|token| {
+                            graphql::auth::parse_and_validate_jwt::<graphql::auth::AuthJwtPayload>(
+                                &token,
+                                &ctx.keypair.verifying_key(),
+                            )
+                            .ok()
+                        })
+                        .map(|jwt| AuthenticatedUser {
+                            role: jwt.custom_fields.role,
+                            username: jwt.custom_fields.username,
+                            team_slug: jwt.custom_fields.team_slug,
+                            user_id: jwt.sub,
+                            team_id: jwt.custom_fields.team_id,
+                            session_id: jwt.custom_fields.session_id,
                         });
-                        let user_details = auth
-                            .and_then(|token| {
-                                graphql::auth::parse_and_validate_jwt::<
-                                        graphql::auth::AuthJwtPayload,
-                                    >(
-                                        &token, &ctx.keypair.verifying_key()
-                                    )
-                                    .ok()
-                            })
-                            .map(|jwt| AuthenticatedUser {
-                                role: jwt.custom_fields.role,
-                                username: jwt.custom_fields.username,
-                                team_slug: jwt.custom_fields.team_slug,
-                                user_id: jwt.sub,
-                                team_id: jwt.custom_fields.team_id,
-                            });
 
-                        let ctx = ctx.clone();
-                        async move {
-                            let ctx = Context::new(
-                                ctx,
-                                remote_ip,
-                                req.headers()
-                                    .get("user-agent")
-                                    .and_then(|ua| ua.to_str().ok())
-                                    .unwrap_or("unknown")
-                                    .to_string(),
-                                user_details,
-                            )
-                            .await;
-                            Ok::<_, Infallible>(match (req.method(), req.uri().path()) {
-                                (&Method::GET, "/graphql") | (&Method::POST, "/graphql") => {
-                                    tokio::time::timeout(
-                                        std::time::Duration::from_secs(30),
-                                        graphql(root_node, Arc::new(ctx), req),
-                                    )
-                                    .await
-                                    .map(|resp| {
-                                        resp.map(|body| {
-                                            Full::new(Bytes::copy_from_slice(body.as_bytes()))
-                                        })
-                                    })
-                                    .unwrap_or_else(|_| {
-                                        let mut resp = Response::new(Full::new(Bytes::from(
-                                            "Request timed out",
-                                        )));
-                                        *resp.status_mut() = StatusCode::GATEWAY_TIMEOUT;
+                    let ctx = ctx.clone();
+                    let span = tracing::info_span!(
+                        "http_request",
+                        method = %req.method(),
+                        path = %req.uri().path(),
+                    );
+                    async move {
+                        // Probes and metrics are answered before anything else, they don't need a request context
+                        if req.method() == Method::GET
+                            && matches!(req.uri().path(), "/healthz" | "/readyz" | "/metrics")
+                        {
+                            let (status_code, content_type, body) = match req.uri().path() {
+                                // Liveness only depends on the server itself, so a database outage doesn't restart all replicas
+                                "/healthz" => {
+                                    (200, "application/json", r#"{"status":"ok"}"#.to_string())
+                                }
+                                "/readyz" => {
+                                    let (status_code, body) = health::readiness(&ctx).await;
+                                    (status_code, "application/json", body)
+                                }
+                                _ if !metrics::is_authorized(&ctx, req.headers()) => {
+                                    (404, "text/plain", "Not found".to_string())
+                                }
+                                _ => match metrics::render(&ctx) {
+                                    Ok(body) => (200, "text/plain; version=0.0.4", body),
+                                    Err((status_code, message)) => {
+                                        (status_code, "text/plain", message)
+                                    }
+                                },
+                            };
+                            let mut resp = Response::new(boxed(Full::new(Bytes::from(body))));
+                            resp.headers_mut().insert(
+                                hyper::header::CONTENT_TYPE,
+                                hyper::header::HeaderValue::from_static(content_type),
+                            );
+                            *resp.status_mut() = StatusCode::from_u16(status_code)
+                                .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+                            return Ok::<_, Infallible>(resp);
+                        }
+                        let ctx = Context::new(
+                            ctx,
+                            remote_ip,
+                            req.headers()
+                                .get("user-agent")
+                                .and_then(|ua| ua.to_str().ok())
+                                .unwrap_or("unknown")
+                                .to_string(),
+                            user_details,
+                        )
+                        .await;
+                        if req.method() == Method::GET
+                            && let Some(export) = req.uri().path().strip_prefix("/admin/export/")
+                        {
+                            return Ok::<_, Infallible>(
+                                match graphql::export_data(ctx, export).await {
+                                    Ok((content_type, body)) => {
+                                        let mut resp = Response::new(body);
+                                        resp.headers_mut().insert(
+                                            hyper::header::CONTENT_TYPE,
+                                            hyper::header::HeaderValue::from_static(content_type),
+                                        );
+                                        resp
+                                    }
+                                    Err((status_code, message)) => {
+                                        let mut resp =
+                                            Response::new(boxed(Full::new(Bytes::from(message))));
+                                        *resp.status_mut() = StatusCode::from_u16(status_code)
+                                            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
                                         resp
+                                    }
+                                },
+                            );
+                        }
+                        Ok(match (req.method(), req.uri().path()) {
+                            (&Method::GET, "/graphql") | (&Method::POST, "/graphql") => {
+                                // The body is read here so the operation name can be recorded
+                                // and persisted queries can be resolved
+                                let (mut parts, body) = req.into_parts();
+                                let Some(mut body) = read_body(body).await else {
+                                    return Ok(payload_too_large());
+                                };
+                                let operation = metrics::graphql_operation_name(
+                                    parts.uri.query(),
+                                    &body,
+                                    persisted_queries::allowlisted_operations(),
+                                );
+                                metrics::GRAPHQL_REQUESTS
+                                    .with_label_values(&[&operation])
+                                    .inc();
+                                let timer = metrics::GRAPHQL_REQUEST_DURATION
+                                    .with_label_values(&[&operation])
+                                    .start_timer();
+
+                                let authenticated = ctx.is_authenticated();
+                                let resolved = if parts.method == Method::GET {
+                                    persisted_queries::resolve_query_string(
+                                        parts.uri.query().unwrap_or_default(),
+                                        authenticated,
+                                    )
+                                    .map(|query| {
+                                        if let Ok(uri) =
+                                            format!("{}?{}", parts.uri.path(), query).parse()
+                                        {
+                                            parts.uri = uri;
+                                        }
                                     })
+                                } else {
+                                    persisted_queries::resolve_body(&body, authenticated).map(
+                                        |resolved| {
+                                            if let Some(resolved) = resolved {
+                                                body = Bytes::from(resolved);
+                                            }
+                                        },
+                                    )
+                                };
+                                if let Err(e) = resolved {
+                                    let mut resp = Response::new(Full::new(Bytes::from(e.body)));
+                                    resp.headers_mut().insert(
+                                        hyper::header::CONTENT_TYPE,
+                                        hyper::header::HeaderValue::from_static("application/json"),
+                                    );
+                                    *resp.status_mut() = StatusCode::from_u16(e.status_code)
+                                        .unwrap_or(StatusCode::BAD_REQUEST);
+                                    return Ok(resp.map(boxed));
                                 }
-                                (&Method::OPTIONS, _) => {
-                                    let mut resp = Response::new(Full::new(Bytes::new()));
-                                    *resp.status_mut() = StatusCode::NO_CONTENT;
+                                if let Some((status_code, message)) = graphql::check_maintenance(
+                                    &ctx,
+                                    &root_node.schema,
+                                    parts.uri.query(),
+                                    &body,
+                                )
+                                .await
+                                {
+                                    let mut resp = Response::new(Full::new(Bytes::from(message)));
+                                    resp.headers_mut().insert(
+                                        hyper::header::CONTENT_TYPE,
+                                        hyper::header::HeaderValue::from_static("application/json"),
+                                    );
+                                    *resp.status_mut() = StatusCode::from_u16(status_code)
+                                        .unwrap_or(StatusCode::SERVICE_UNAVAILABLE);
+                                    return Ok(resp.map(boxed));
+                                }
+                                if parts.method == Method::POST
+                                    && sse::accepts_event_stream(&parts.headers)
+                                {
+                                    // Subscriptions stay open as long as the client wants, so
+                                    // neither the timeout nor the duration metric apply
+                                    timer.stop_and_discard();
+                                    return Ok(sse::serve(root_node, ctx, &body).map(boxed));
+                                }
+                                let mut request_context = ctx.error_report_context();
+                                request_context["method"] = parts.method.as_str().into();
+                                request_context["path"] = parts.uri.path().into();
+                                let req = Request::from_parts(parts, Full::new(body));
+                                tokio::time::timeout(
+                                    std::time::Duration::from_secs(30),
+                                    error_reporting::track_failed_queries(graphql(
+                                        root_node,
+                                        Arc::new(ctx),
+                                        req,
+                                    )),
+                                )
+                                .await
+                                .map(|(resp, failed_queries)| {
+                                    error_reporting::capture_graphql_errors(
+                                        &operation,
+                                        resp.body(),
+                                        &failed_queries,
+                                        request_context,
+                                    );
+                                    resp.map(|body| {
+                                        Full::new(Bytes::copy_from_slice(body.as_bytes()))
+                                    })
+                                })
+                                .unwrap_or_else(|_| {
+                                    let mut resp =
+                                        Response::new(Full::new(Bytes::from("Request timed out")));
+                                    *resp.status_mut() = StatusCode::GATEWAY_TIMEOUT;
                                     resp
+                                })
+                            }
+                            (&Method::POST, "/git-webhook") => {
+                                let (parts, body) = req.into_parts();
+                                let Some(body) = read_body(body).await else {
+                                    return Ok(payload_too_large());
+                                };
+                                match graphql::handle_git_webhook(ctx, &parts.headers, &body).await
+                                {
+                                    Ok(message) => Response::new(Full::new(Bytes::from(message))),
+                                    Err((status_code, message)) => {
+                                        let mut resp =
+                                            Response::new(Full::new(Bytes::from(message)));
+                                        *resp.status_mut() = StatusCode::from_u16(status_code)
+                                            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+                                        resp
+                                    }
                                 }
-                                (&Method::GET, "/graphiql") => graphiql("/graphql", None)
-                                    .await
-                                    .map(|body| Full::new(Bytes::from(body))),
-                                (&Method::GET, "/playground") => playground("/graphql", None)
-                                    .await
-                                    .map(|body| Full::new(Bytes::from(body))),
-                                (&Method::GET, path) => {
-                                    if path.starts_with("/export-challenge/") {
-                                        let challenge_id = path
-                                            .trim_start_matches("/export-challenge/")
-                                            .to_string();
-                                        let challenge_slug = slugify!(&challenge_id);
-                                        match graphql::export_challenge(ctx, challenge_id.clone())
-                                            .await
-                                        {
-                                            Ok(archive_data) => {
-                                                let mut resp = Response::new(Full::new(
-                                                    Bytes::from(archive_data),
-                                                ));
-                                                resp.headers_mut().insert(
-                                                    hyper::header::CONTENT_TYPE,
-                                                    hyper::header::HeaderValue::from_static(
-                                                        "application/gzip",
-                                                    ),
-                                                );
-                                                let filename = format!("{}.tar.gz", challenge_slug);
-                                                resp.headers_mut().insert(
-                                                    hyper::header::CONTENT_DISPOSITION,
-                                                    hyper::header::HeaderValue::from_str(&format!(
-                                                        "attachment; filename=\"{}\"",
-                                                        filename
-                                                    ))
-                                                    .unwrap(),
-                                                );
-                                                resp
-                                            }
-                                            Err((status_code, message)) => {
-                                                let mut resp =
-                                                    Response::new(Full::new(Bytes::from(message)));
-                                                *resp.status_mut() = StatusCode::from_u16(
-                                                    status_code,
-                                                )
+                            }
+                            (&Method::OPTIONS, _) => {
+                                let mut resp = Response::new(Full::new(Bytes::new()));
+                                *resp.status_mut() = StatusCode::NO_CONTENT;
+                                resp
+                            }
+                            (&Method::GET, "/graphiql") => graphiql("/graphql", None)
+                                .await
+                                .map(|body| Full::new(Bytes::from(body))),
+                            (&Method::GET, "/playground") => playground("/graphql", None)
+                                .await
+                                .map(|body| Full::new(Bytes::from(body))),
+                            (&Method::GET, "/ctftime/scoreboard") => {
+                                match graphql::ctftime_scoreboard(ctx).await {
+                                    Ok(feed) => {
+                                        let mut resp = Response::new(Full::new(Bytes::from(feed)));
+                                        resp.headers_mut().insert(
+                                            hyper::header::CONTENT_TYPE,
+                                            hyper::header::HeaderValue::from_static(
+                                                "application/json",
+                                            ),
+                                        );
+                                        resp
+                                    }
+                                    Err((status_code, message)) => {
+                                        let mut resp =
+                                            Response::new(Full::new(Bytes::from(message)));
+                                        *resp.status_mut() = StatusCode::from_u16(status_code)
+                                            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+                                        resp
+                                    }
+                                }
+                            }
+                            (&Method::GET, path) => {
+                                if path.starts_with("/export-challenge/") {
+                                    let challenge_id =
+                                        path.trim_start_matches("/export-challenge/").to_string();
+                                    let challenge_slug = slugify!(&challenge_id);
+                                    match graphql::export_challenge(ctx, challenge_id.clone()).await
+                                    {
+                                        Ok(archive_data) => {
+                                            let mut resp =
+                                                Response::new(Full::new(Bytes::from(archive_data)));
+                                            resp.headers_mut().insert(
+                                                hyper::header::CONTENT_TYPE,
+                                                hyper::header::HeaderValue::from_static(
+                                                    "application/gzip",
+                                                ),
+                                            );
+                                            let filename = format!("{}.tar.gz", challenge_slug);
+                                            resp.headers_mut().insert(
+                                                hyper::header::CONTENT_DISPOSITION,
+                                                hyper::header::HeaderValue::from_str(&format!(
+                                                    "attachment; filename=\"{}\"",
+                                                    filename
+                                                ))
+                                                .unwrap(),
+                                            );
+                                            resp
+                                        }
+                                        Err((status_code, message)) => {
+                                            let mut resp =
+                                                Response::new(Full::new(Bytes::from(message)));
+                                            *resp.status_mut() = StatusCode::from_u16(status_code)
                                                 .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
-                                                resp
-                                            }
+                                            resp
                                         }
-                                    } else if path.starts_with("/retrieve-file/") {
-                                        let parts: Vec<&str> = path
-                                            .trim_start_matches("/retrieve-file/")
-                                            .splitn(2, '/')
-                                            .collect();
-                                        if parts.len() != 2 {
-                                            let mut resp = Response::new(Full::new(Bytes::from(
-                                                "Invalid request",
-                                            )));
-                                            *resp.status_mut() = StatusCode::BAD_REQUEST;
-                                            return Ok(resp);
+                                    }
+                                } else if let Some(avatar_path) = path.strip_prefix('/')
+                                    && avatar_path.starts_with("avatars/")
+                                {
+                                    match graphql::retrieve_avatar(&ctx, avatar_path).await {
+                                        Ok(image_data) => {
+                                            let mut resp =
+                                                Response::new(Full::new(Bytes::from(image_data)));
+                                            resp.headers_mut().insert(
+                                                hyper::header::CONTENT_TYPE,
+                                                hyper::header::HeaderValue::from_static(
+                                                    "image/png",
+                                                ),
+                                            );
+                                            // Avatar paths change on every upload
+                                            resp.headers_mut().insert(
+                                                hyper::header::CACHE_CONTROL,
+                                                hyper::header::HeaderValue::from_static(
+                                                    "public, max-age=31536000, immutable",
+                                                ),
+                                            );
+                                            resp
+                                        }
+                                        Err((status_code, message)) => {
+                                            let mut resp =
+                                                Response::new(Full::new(Bytes::from(message)));
+                                            *resp.status_mut() = StatusCode::from_u16(status_code)
+                                                .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+                                            resp
                                         }
-                                        let challenge_id = parts[0].to_string();
-                                        let filename = parts[1].to_string();
-                                        match graphql::retrieve_file(
-                                            ctx,
-                                            challenge_id.clone(),
-                                            filename.clone(),
-                                        )
+                                    }
+                                } else if let Some(export_id) = path.strip_prefix("/data-export/") {
+                                    match graphql::download_data_export(ctx, export_id.to_string())
                                         .await
-                                        {
-                                            Ok(file_data) => {
-                                                let mut resp = Response::new(Full::new(
-                                                    Bytes::from(file_data),
-                                                ));
-                                                resp.headers_mut().insert(
-                                                    hyper::header::CONTENT_TYPE,
-                                                    hyper::header::HeaderValue::from_static(
-                                                        "application/octet-stream",
-                                                    ),
-                                                );
-                                                let file_slug = slugify!(&filename);
-                                                let content_disposition = format!(
-                                                    "attachment; filename=\"{}\"",
-                                                    file_slug
-                                                );
-                                                resp.headers_mut().insert(
-                                                    hyper::header::CONTENT_DISPOSITION,
-                                                    hyper::header::HeaderValue::from_str(
-                                                        &content_disposition,
-                                                    )
-                                                    .unwrap(),
-                                                );
-                                                resp
-                                            }
-                                            Err((status_code, message)) => {
-                                                let mut resp =
-                                                    Response::new(Full::new(Bytes::from(message)));
-                                                *resp.status_mut() = StatusCode::from_u16(
-                                                    status_code,
+                                    {
+                                        Ok(export_data) => {
+                                            let mut resp =
+                                                Response::new(Full::new(Bytes::from(export_data)));
+                                            resp.headers_mut().insert(
+                                                hyper::header::CONTENT_TYPE,
+                                                hyper::header::HeaderValue::from_static(
+                                                    "application/json",
+                                                ),
+                                            );
+                                            resp.headers_mut().insert(
+                                                hyper::header::CONTENT_DISPOSITION,
+                                                hyper::header::HeaderValue::from_static(
+                                                    "attachment; filename=\"data-export.json\"",
+                                                ),
+                                            );
+                                            resp
+                                        }
+                                        Err((status_code, message)) => {
+                                            let mut resp =
+                                                Response::new(Full::new(Bytes::from(message)));
+                                            *resp.status_mut() = StatusCode::from_u16(status_code)
+                                                .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+                                            resp
+                                        }
+                                    }
+                                } else if path.starts_with("/retrieve-file/") {
+                                    let parts: Vec<&str> = path
+                                        .trim_start_matches("/retrieve-file/")
+                                        .splitn(2, '/')
+                                        .collect();
+                                    if parts.len() != 2 {
+                                        let mut resp = Response::new(Full::new(Bytes::from(
+                                            "Invalid request",
+                                        )));
+                                        *resp.status_mut() = StatusCode::BAD_REQUEST;
+                                        return Ok(resp.map(boxed));
+                                    }
+                                    let challenge_id = parts[0].to_string();
+                                    let filename = parts[1].to_string();
+                                    match graphql::retrieve_file(
+                                        ctx,
+                                        challenge_id.clone(),
+                                        filename.clone(),
+                                    )
+                                    .await
+                                    {
+                                        Ok(file_data) => {
+                                            let mut resp =
+                                                Response::new(Full::new(Bytes::from(file_data)));
+                                            resp.headers_mut().insert(
+                                                hyper::header::CONTENT_TYPE,
+                                                hyper::header::HeaderValue::from_static(
+                                                    "application/octet-stream",
+                                                ),
+                                            );
+                                            let file_slug = slugify!(&filename);
+                                            let content_disposition =
+                                                format!("attachment; filename=\"{}\"", file_slug);
+                                            resp.headers_mut().insert(
+                                                hyper::header::CONTENT_DISPOSITION,
+                                                hyper::header::HeaderValue::from_str(
+                                                    &content_disposition,
                                                 )
+                                                .unwrap(),
+                                            );
+                                            resp
+                                        }
+                                        Err((status_code, message)) => {
+                                            let mut resp =
+                                                Response::new(Full::new(Bytes::from(message)));
+                                            *resp.status_mut() = StatusCode::from_u16(status_code)
                                                 .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
-                                                resp
-                                            }
+                                            resp
                                         }
-                                    } else {
-                                        let mut resp = Response::new(Full::new(Bytes::new()));
-                                        *resp.status_mut() = StatusCode::NOT_FOUND;
-                                        resp
                                     }
-                                }
-                                _ => {
+                                } else {
                                     let mut resp = Response::new(Full::new(Bytes::new()));
-                                    *resp.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+                                    *resp.status_mut() = StatusCode::NOT_FOUND;
                                     resp
                                 }
-                            })
+                            }
+                            _ => {
+                                let mut resp = Response::new(Full::new(Bytes::new()));
+                                *resp.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+                                resp
+                            }
                         }
-                    }),
-                )
-                .await
-            {
+                        .map(boxed))
+                    }
+                    .instrument(span)
+                }),
+            );
+            if let Err(e) = watcher.watch(conn).await {
                 tracing::error!("Error serving connection: {e}");
             }
         });
     }
+
+    // Stop accepting connections, then let the active ones finish their requests
+    drop(listener);
+    tracing::info!("Shutting down, waiting for active connections to finish");
+    tokio::select! {
+        _ = graceful.shutdown() => tracing::info!("All connections closed"),
+        _ = tokio::time::sleep(Duration::from_secs(config.shutdown_timeout)) => {
+            tracing::warn!("Timed out waiting for connections to close");
+        }
+    }
+
+    // The database pool closes its connections once the last context holding it is dropped, so the
+    // background tasks are stopped before dropping ours
+    background_tasks.shutdown().await;
+    drop(ctx);
+    if let Some(tracer_provider) = tracer_provider
+        && let Err(e) = tracer_provider.shutdown()
+    {
+        tracing::warn!("Failed to flush traces: {e}");
+    }
+    Ok(())
+}
+
+/// Largest request body accepted, GraphQL requests can contain base64 encoded avatar uploads
+const MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+/// Reads a request body, None if it is larger than MAX_BODY_SIZE.
+async fn read_body(body: hyper::body::Incoming) -> Option<Bytes> {
+    match Limited::new(body, MAX_BODY_SIZE).collect().await {
+        Ok(body) => Some(body.to_bytes()),
+        Err(e) if e.is::<LengthLimitError>() => None,
+        // A body that couldn't be read is treated like an empty one
+        Err(_) => Some(Bytes::new()),
+    }
+}
+
+/// Body of all responses, only streamed exports can fail
+type ResponseBody = UnsyncBoxBody<Bytes, Box<dyn Error + Send + Sync>>;
+
+fn boxed(
+    body: impl hyper::body::Body<Data = Bytes, Error = Infallible> + Send + 'static,
+) -> ResponseBody {
+    body.map_err(|e| match e {}).boxed_unsync()
+}
+
+fn payload_too_large() -> Response<ResponseBody> {
+    let mut resp = Response::new(boxed(Full::new(Bytes::from("Request body too large"))));
+    *resp.status_mut() = StatusCode::PAYLOAD_TOO_LARGE;
+    resp
+}
+
+/// Resolves on SIGINT or SIGTERM.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl+C: {e}");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::info!("Received shutdown signal");
 }
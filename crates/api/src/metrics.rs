@@ -0,0 +1,200 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::{collections::HashSet, sync::LazyLock, time::Duration};
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+use sha2::{Digest, Sha256};
+use tokio::task::JoinSet;
+
+use crate::graphql::BaseContext;
+
+static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+pub static GRAPHQL_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(
+        Opts::new("graphql_requests_total", "Number of GraphQL requests"),
+        &["operation"],
+    ))
+});
+
+pub static GRAPHQL_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register(HistogramVec::new(
+        HistogramOpts::new(
+            "graphql_request_duration_seconds",
+            "Time taken to respond to GraphQL requests",
+        ),
+        &["operation"],
+    ))
+});
+
+pub static FLAG_SUBMISSIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(
+        Opts::new("flag_submissions_total", "Number of submitted flags"),
+        &["result"],
+    ))
+});
+
+pub static INSTANCES_COLLECTED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(
+        Opts::new(
+            "instances_garbage_collected_total",
+            "Number of orphaned instances cleaned up by the manager",
+        ),
+        &["reason"],
+    ))
+});
+
+static DB_POOL_CONNECTIONS: LazyLock<IntGauge> = LazyLock::new(|| {
+    register(IntGauge::new(
+        "db_pool_connections",
+        "Number of open database connections",
+    ))
+});
+
+static DB_POOL_IDLE_CONNECTIONS: LazyLock<IntGauge> = LazyLock::new(|| {
+    register(IntGauge::new(
+        "db_pool_idle_connections",
+        "Number of idle database connections",
+    ))
+});
+
+static ACTIVE_SESSIONS: LazyLock<IntGauge> = LazyLock::new(|| {
+    register(IntGauge::new(
+        "active_sessions",
+        "Number of sessions that have not expired yet",
+    ))
+});
+
+fn register<T: prometheus::core::Collector + Clone + 'static>(metric: prometheus::Result<T>) -> T {
+    let metric = metric.expect("Invalid metric definition");
+    REGISTRY
+        .register(Box::new(metric.clone()))
+        .expect("Metric registered twice");
+    metric
+}
+
+/// Extracts the operation name of a GraphQL request for use as a metric label.
+///
+/// Only the names of `known_operations` are used, others are counted as "other", so clients
+/// can't create arbitrary labels.
+pub fn graphql_operation_name(
+    query_string: Option<&str>,
+    body: &[u8],
+    known_operations: &HashSet<String>,
+) -> String {
+    let name = match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(serde_json::Value::Array(_)) => return "batch".to_string(),
+        Ok(request) => request
+            .get("operationName")
+            .and_then(|name| name.as_str())
+            .map(|name| name.to_string()),
+        Err(_) => query_string.and_then(|query| {
+            query
+                .split('&')
+                .find_map(|param| param.strip_prefix("operationName="))
+                .map(|name| name.to_string())
+        }),
+    };
+    match name {
+        Some(name) if known_operations.contains(&name) => name,
+        Some(_) => "other".to_string(),
+        None => "anonymous".to_string(),
+    }
+}
+
+/// How often the number of active sessions is counted
+const SESSION_COUNT_INTERVAL: Duration = Duration::from_secs(60);
+
+async fn count_active_sessions(
+    ctx: &BaseContext,
+) -> Result<i64, Box<dyn std::error::Error + Send + Sync>> {
+    use crate::db::schema::sessions::dsl::*;
+
+    let mut conn = ctx.db_pool.get().await?;
+    Ok(sessions
+        .filter(expires_at.gt(chrono::Utc::now()))
+        .count()
+        .get_result(&mut conn)
+        .await?)
+}
+
+/// Starts the background task keeping the active_sessions gauge up to date, so scrapes don't
+/// query the database.
+pub fn start_session_counter(tasks: &mut JoinSet<()>, ctx: BaseContext) {
+    tasks.spawn(async move {
+        let mut interval = tokio::time::interval(SESSION_COUNT_INTERVAL);
+        loop {
+            interval.tick().await;
+            match count_active_sessions(&ctx).await {
+                Ok(active) => ACTIVE_SESSIONS.set(active),
+                Err(e) => tracing::error!("Failed to count active sessions: {}", e),
+            }
+        }
+    });
+}
+
+/// Whether a request to /metrics carries the metrics_token as bearer token.
+///
+/// Without a configured token, metrics are not served at all.
+pub fn is_authorized(ctx: &BaseContext, headers: &hyper::HeaderMap) -> bool {
+    let Some(token) = ctx.config.metrics_token.as_deref() else {
+        return false;
+    };
+    let given = headers
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    // Comparing the hashes doesn't reveal how much of the token matched through timing
+    Sha256::digest(given.as_bytes()) == Sha256::digest(token.as_bytes())
+}
+
+/// Renders all metrics in the Prometheus text format.
+pub fn render(ctx: &BaseContext) -> Result<String, (u16, String)> {
+    let pool_state = ctx.db_pool.state();
+    DB_POOL_CONNECTIONS.set(pool_state.connections as i64);
+    DB_POOL_IDLE_CONNECTIONS.set(pool_state.idle_connections as i64);
+
+    let mut buffer = vec![];
+    TextEncoder::new()
+        .encode(&REGISTRY.gather(), &mut buffer)
+        .map_err(|e| (500, format!("Failed to encode metrics: {}", e)))?;
+    String::from_utf8(buffer).map_err(|e| (500, e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_graphql_operation_name() {
+        let known = HashSet::from(["Me".to_string()]);
+        assert_eq!(
+            graphql_operation_name(
+                None,
+                br#"{"query":"{me{id}}","operationName":"Me"}"#,
+                &known
+            ),
+            "Me"
+        );
+        assert_eq!(
+            graphql_operation_name(None, br#"{"query":"{me{id}}"}"#, &known),
+            "anonymous"
+        );
+        assert_eq!(
+            graphql_operation_name(None, br#"{"operationName":"Random123"}"#, &known),
+            "other"
+        );
+        assert_eq!(graphql_operation_name(None, b"[]", &known), "batch");
+        assert_eq!(
+            graphql_operation_name(Some("query=%7Bme%7D&operationName=Me"), b"", &known),
+            "Me"
+        );
+    }
+}
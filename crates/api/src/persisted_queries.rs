@@ -0,0 +1,279 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{LazyLock, OnceLock},
+};
+
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+/// Queries registered by clients through automatic persisted queries, by their SHA-256 hash
+static QUERY_CACHE: LazyLock<moka::sync::Cache<String, String>> =
+    LazyLock::new(|| moka::sync::Cache::new(10_000));
+
+/// Queries that anonymous users may run, set by [`load_allowlist`]. Without it, any query is
+/// allowed.
+struct Allowlist {
+    queries: HashMap<String, String>,
+    /// Names of the operations defined in the queries
+    operations: HashSet<String>,
+}
+
+static ALLOWLIST: OnceLock<Allowlist> = OnceLock::new();
+
+#[derive(Error, Debug)]
+pub enum AllowlistError {
+    #[error("Failed to read GraphQL allow-list {0}: {1}")]
+    Read(PathBuf, std::io::Error),
+    #[error("Failed to parse GraphQL allow-list {0}: {1}")]
+    Parse(PathBuf, serde_json::Error),
+}
+
+/// Restricts anonymous users to the queries in the allow-list at `path` (graphql_allowlist_file).
+///
+/// The file is a JSON object mapping the SHA-256 hash of each query to the query, as generated
+/// by most persisted document tooling.
+pub fn load_allowlist(path: &Path) -> Result<(), AllowlistError> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|e| AllowlistError::Read(path.to_path_buf(), e))?;
+    let queries: HashMap<String, String> = serde_json::from_str(&contents)
+        .map_err(|e| AllowlistError::Parse(path.to_path_buf(), e))?;
+    tracing::info!(
+        "Only {} allow-listed queries can be used by anonymous users",
+        queries.len()
+    );
+    let operations = queries
+        .values()
+        .flat_map(|query| operation_names(query))
+        .collect();
+    let _ = ALLOWLIST.set(Allowlist {
+        queries,
+        operations,
+    });
+    Ok(())
+}
+
+/// Names of the operations defined in a GraphQL document, like `Me` in `query Me { me { id } }`
+fn operation_names(document: &str) -> Vec<String> {
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut names = Vec::new();
+    let mut rest = document;
+    while let Some(start) = rest.find(is_name) {
+        rest = &rest[start..];
+        let (word, after) = rest.split_at(rest.find(|c| !is_name(c)).unwrap_or(rest.len()));
+        rest = after;
+        if matches!(word, "query" | "mutation" | "subscription") {
+            let after = after.trim_start();
+            let name = &after[..after.find(|c| !is_name(c)).unwrap_or(after.len())];
+            if !name.is_empty() {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// Operation names of the allow-list, empty if there is none.
+pub fn allowlisted_operations() -> &'static HashSet<String> {
+    static NO_OPERATIONS: LazyLock<HashSet<String>> = LazyLock::new(HashSet::new);
+    ALLOWLIST
+        .get()
+        .map_or(&NO_OPERATIONS, |allowlist| &allowlist.operations)
+}
+
+/// An error response to send instead of executing the request
+pub struct PersistedQueryError {
+    pub status_code: u16,
+    pub body: String,
+}
+
+impl PersistedQueryError {
+    fn new(status_code: u16, message: &str, code: &str) -> Self {
+        Self {
+            status_code,
+            body: json!({
+                "errors": [{ "message": message, "extensions": { "code": code } }]
+            })
+            .to_string(),
+        }
+    }
+}
+
+fn sha256_hex(query: &str) -> String {
+    format!("{:x}", Sha256::digest(query.as_bytes()))
+}
+
+/// Fills in the query of a single request that only contains a persisted query hash,
+/// and checks it against the allow-list.
+fn resolve_request(request: &mut Value, authenticated: bool) -> Result<(), PersistedQueryError> {
+    let Some(request) = request.as_object_mut() else {
+        return Ok(());
+    };
+    let hash = request
+        .get("extensions")
+        .and_then(|extensions| extensions.get("persistedQuery"))
+        .and_then(|persisted_query| persisted_query.get("sha256Hash"))
+        .and_then(|hash| hash.as_str())
+        .map(|hash| hash.to_lowercase());
+    let query = request
+        .get("query")
+        .and_then(|query| query.as_str())
+        .map(|query| query.to_string());
+
+    let query = match (hash, query) {
+        (Some(hash), Some(query)) => {
+            if sha256_hex(&query) != hash {
+                return Err(PersistedQueryError::new(
+                    400,
+                    "provided sha does not match query",
+                    "PERSISTED_QUERY_HASH_MISMATCH",
+                ));
+            }
+            QUERY_CACHE.insert(hash, query.clone());
+            query
+        }
+        (Some(hash), None) => {
+            let query = ALLOWLIST
+                .get()
+                .and_then(|allowlist| allowlist.queries.get(&hash).cloned())
+                .or_else(|| QUERY_CACHE.get(&hash));
+            let Some(query) = query else {
+                // Clients retry with the full query when receiving this exact message
+                return Err(PersistedQueryError::new(
+                    200,
+                    "PersistedQueryNotFound",
+                    "PERSISTED_QUERY_NOT_FOUND",
+                ));
+            };
+            request.insert("query".to_string(), Value::String(query.clone()));
+            query
+        }
+        (None, Some(query)) => query,
+        (None, None) => return Ok(()),
+    };
+
+    if !authenticated
+        && let Some(allowlist) = ALLOWLIST.get()
+        && !allowlist.queries.contains_key(&sha256_hex(&query))
+    {
+        return Err(PersistedQueryError::new(
+            403,
+            "This query is not allowed",
+            "QUERY_NOT_ALLOWED",
+        ));
+    }
+    Ok(())
+}
+
+/// Resolves persisted queries in a POST body, which may contain a single request or a batch.
+pub fn resolve_body(
+    body: &[u8],
+    authenticated: bool,
+) -> Result<Option<Vec<u8>>, PersistedQueryError> {
+    let Ok(mut request) = serde_json::from_slice::<Value>(body) else {
+        // Not JSON, so either the query itself (application/graphql) or invalid, which juniper rejects.
+        // It still has to pass the allow-list.
+        let mut request = json!({ "query": String::from_utf8_lossy(body) });
+        resolve_request(&mut request, authenticated)?;
+        return Ok(None);
+    };
+    match &mut request {
+        Value::Array(requests) => {
+            for request in requests {
+                resolve_request(request, authenticated)?;
+            }
+        }
+        request => resolve_request(request, authenticated)?,
+    }
+    Ok(Some(serde_json::to_vec(&request).unwrap_or_default()))
+}
+
+/// Resolves a persisted query sent as GET parameters, returning the new query string.
+pub fn resolve_query_string(
+    query_string: &str,
+    authenticated: bool,
+) -> Result<String, PersistedQueryError> {
+    let mut params: Vec<(String, String)> = form_urlencoded::parse(query_string.as_bytes())
+        .into_owned()
+        .collect();
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+    };
+    let mut request = json!({});
+    if let Some(query) = param("query") {
+        request["query"] = Value::String(query);
+    }
+    if let Some(extensions) = param("extensions").and_then(|e| serde_json::from_str(&e).ok()) {
+        request["extensions"] = extensions;
+    }
+    resolve_request(&mut request, authenticated)?;
+
+    if let Some(query) = request.get("query").and_then(|query| query.as_str()) {
+        params.retain(|(key, _)| key != "query");
+        params.push(("query".to_string(), query.to_string()));
+    }
+    Ok(form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(params)
+        .finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_automatic_persisted_query() {
+        let query = "{ isAuthenticated }";
+        let hash = sha256_hex(query);
+        let hash_only = json!({
+            "extensions": { "persistedQuery": { "version": 1, "sha256Hash": hash } }
+        })
+        .to_string();
+
+        let err = resolve_body(hash_only.as_bytes(), false).err().unwrap();
+        assert!(err.body.contains("PersistedQueryNotFound"));
+
+        let with_query = json!({
+            "query": query,
+            "extensions": { "persistedQuery": { "version": 1, "sha256Hash": hash } }
+        })
+        .to_string();
+        assert!(resolve_body(with_query.as_bytes(), false).is_ok());
+
+        let resolved = resolve_body(hash_only.as_bytes(), false)
+            .ok()
+            .flatten()
+            .unwrap();
+        let resolved: Value = serde_json::from_slice(&resolved).unwrap();
+        assert_eq!(resolved["query"], query);
+    }
+
+    #[test]
+    fn test_operation_names() {
+        assert_eq!(
+            operation_names("query Me { me { id } }\nmutation Logout($all: Boolean) { logout }"),
+            ["Me", "Logout"]
+        );
+        assert!(operation_names("{ isAuthenticated }").is_empty());
+        assert!(operation_names("query { me { id } }").is_empty());
+    }
+
+    #[test]
+    fn test_hash_mismatch() {
+        let body = json!({
+            "query": "{ isAuthenticated }",
+            "extensions": { "persistedQuery": { "version": 1, "sha256Hash": "abc" } }
+        })
+        .to_string();
+        let err = resolve_body(body.as_bytes(), false).err().unwrap();
+        assert_eq!(err.status_code, 400);
+    }
+}
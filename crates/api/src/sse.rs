@@ -0,0 +1,126 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! GraphQL subscriptions over server-sent events, following the "distinct connections mode" of
+//! the GraphQL over SSE protocol: the client POSTs a single subscription with
+//! `Accept: text/event-stream` and receives a `next` event per result, then `complete`.
+
+use std::{convert::Infallible, sync::Arc, time::Duration};
+
+use futures_util::StreamExt;
+use http_body_util::{BodyExt, Full, StreamBody, combinators::UnsyncBoxBody};
+use hyper::{HeaderMap, Response, StatusCode, body::Bytes, body::Frame};
+use juniper::{
+    Value,
+    http::{GraphQLRequest, GraphQLResponse},
+};
+use tokio::sync::mpsc;
+
+use crate::graphql::{Context, Schema};
+
+/// Comments are sent this often so proxies don't close idle streams
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Whether the client asked for an event stream instead of a single JSON response
+pub fn accepts_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(hyper::header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.contains("text/event-stream"))
+}
+
+fn event(name: &str, response: &GraphQLResponse) -> Bytes {
+    let data = serde_json::to_string(response).unwrap_or_default();
+    Bytes::from(format!("event: {name}\ndata: {data}\n\n"))
+}
+
+/// Runs a subscription, sending its results until the stream ends or the client disconnects.
+async fn run_subscription(
+    schema: Arc<Schema>,
+    ctx: Arc<Context>,
+    request: GraphQLRequest,
+    events: mpsc::Sender<Bytes>,
+) {
+    let mut streams = match juniper::http::resolve_into_stream(&request, &schema, &ctx).await {
+        Ok((value, errors)) if errors.is_empty() => match value.into_object() {
+            Some(fields) => fields.into_iter().collect::<Vec<_>>(),
+            None => Vec::new(),
+        },
+        Ok((_, errors)) => {
+            let response = GraphQLResponse::from_result(Ok((Value::null(), errors)));
+            let _ = events.send(event("next", &response)).await;
+            Vec::new()
+        }
+        Err(e) => {
+            let response = GraphQLResponse::from_result(Err(e));
+            let _ = events.send(event("next", &response)).await;
+            Vec::new()
+        }
+    };
+
+    // Subscriptions have exactly one root field
+    if let Some((field_name, Value::Scalar(mut stream))) = streams.pop() {
+        let mut keep_alive = tokio::time::interval(KEEP_ALIVE_INTERVAL);
+        loop {
+            let message = tokio::select! {
+                item = stream.next() => match item {
+                    Some(Ok(value)) => {
+                        let data = [(field_name.clone(), value)].into_iter().collect();
+                        event("next", &GraphQLResponse::from_result(Ok((Value::Object(data), vec![]))))
+                    }
+                    Some(Err(e)) => {
+                        event("next", &GraphQLResponse::from_result(Ok((Value::null(), vec![e]))))
+                    }
+                    None => break,
+                },
+                _ = keep_alive.tick() => Bytes::from_static(b":\n\n"),
+            };
+            if events.send(message).await.is_err() {
+                // The client disconnected
+                return;
+            }
+        }
+    }
+    let _ = events
+        .send(Bytes::from_static(b"event: complete\ndata:\n\n"))
+        .await;
+}
+
+/// Handles a POST /graphql request that accepts `text/event-stream`.
+pub fn serve(
+    schema: Arc<Schema>,
+    ctx: Context,
+    body: &[u8],
+) -> Response<UnsyncBoxBody<Bytes, Infallible>> {
+    let request = match serde_json::from_slice::<GraphQLRequest>(body) {
+        Ok(request) => request,
+        Err(e) => {
+            let mut resp = Response::new(
+                Full::new(Bytes::from(format!("Invalid GraphQL request: {e}"))).boxed_unsync(),
+            );
+            *resp.status_mut() = StatusCode::BAD_REQUEST;
+            return resp;
+        }
+    };
+
+    let (tx, rx) = mpsc::channel::<Bytes>(16);
+    tokio::spawn(run_subscription(schema, Arc::new(ctx), request, tx));
+    let frames = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv()
+            .await
+            .map(|bytes| (Ok::<_, Infallible>(Frame::data(bytes)), rx))
+    });
+
+    let mut resp = Response::new(StreamBody::new(frames).boxed_unsync());
+    resp.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("text/event-stream"),
+    );
+    resp.headers_mut().insert(
+        hyper::header::CACHE_CONTROL,
+        hyper::header::HeaderValue::from_static("no-cache"),
+    );
+    resp
+}
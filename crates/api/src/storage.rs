@@ -0,0 +1,88 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::{path::PathBuf, sync::Arc};
+
+use object_store::{ObjectStore, PutPayload, path::Path};
+use thiserror::Error;
+
+use crate::config::{Config, StorageBackend};
+
+#[derive(Error, Debug)]
+pub enum StorageError {
+    #[error("Failed to create storage directory {0}: {1}")]
+    CreateDir(PathBuf, std::io::Error),
+    #[error("Failed to configure storage: {0}")]
+    Configure(#[from] object_store::Error),
+}
+
+/// Storage for user-uploaded files, either a local directory or an S3 bucket.
+#[derive(Debug, Clone)]
+pub struct Storage {
+    store: Arc<dyn ObjectStore>,
+    /// Base URL files are served from, empty if they are served by the api itself
+    public_url: String,
+}
+
+impl Storage {
+    /// Sets up the storage_backend of the config, which has been checked by
+    /// [`Config::validate`].
+    ///
+    /// S3 credentials and the region are taken from the usual AWS_* variables.
+    pub fn from_config(config: &Config) -> Result<Self, StorageError> {
+        let store: Arc<dyn ObjectStore> = match config.storage_backend {
+            StorageBackend::S3 => {
+                let bucket = config
+                    .s3_bucket
+                    .as_deref()
+                    .expect("s3_bucket is checked by validate()");
+                tracing::info!("Using S3 bucket {bucket} for file storage");
+                Arc::new(
+                    object_store::aws::AmazonS3Builder::from_env()
+                        .with_bucket_name(bucket)
+                        .build()?,
+                )
+            }
+            StorageBackend::Local => {
+                let dir = &config.storage_dir;
+                std::fs::create_dir_all(dir)
+                    .map_err(|e| StorageError::CreateDir(dir.clone(), e))?;
+                tracing::info!("Using local directory {} for file storage", dir.display());
+                Arc::new(object_store::local::LocalFileSystem::new_with_prefix(dir)?)
+            }
+        };
+        Ok(Storage {
+            store,
+            public_url: config
+                .storage_public_url
+                .as_deref()
+                .unwrap_or_default()
+                .trim_end_matches('/')
+                .to_string(),
+        })
+    }
+
+    pub async fn put_file(&self, path: &str, data: Vec<u8>) -> Result<(), object_store::Error> {
+        self.store
+            .put(&Path::parse(path)?, PutPayload::from(data))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_file(&self, path: &str) -> Result<Vec<u8>, object_store::Error> {
+        let result = self.store.get(&Path::parse(path)?).await?;
+        Ok(result.bytes().await?.to_vec())
+    }
+
+    pub async fn delete_file(&self, path: &str) -> Result<(), object_store::Error> {
+        self.store.delete(&Path::parse(path)?).await
+    }
+
+    /// Returns the URL under which a stored file can be downloaded.
+    ///
+    /// By default, files are served by the api itself, set storage_public_url to serve them from a CDN or bucket instead.
+    pub fn public_url(&self, path: &str) -> String {
+        format!("{}/{}", self.public_url, path)
+    }
+}
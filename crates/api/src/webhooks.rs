@@ -0,0 +1,212 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::{sync::LazyLock, time::Duration};
+
+use diesel::prelude::*;
+use diesel_async::{AsyncConnection, RunQueryDsl, scoped_futures::ScopedFutureExt};
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+
+use crate::{
+    db::models::{NewWebhookDelivery, Webhook, WebhookDelivery, WebhookEvent},
+    graphql::BaseContext,
+    jobs::{JobError, JobFuture},
+};
+
+type DbPool = diesel_async::pooled_connection::bb8::Pool<diesel_async::AsyncPgConnection>;
+
+/// Kind of the jobs sending webhook deliveries
+pub const DELIVERY_JOB: &str = "webhook_delivery";
+/// Deliveries are retried by the job queue until this many attempts failed
+const MAX_ATTEMPTS: i32 = 5;
+
+static HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("Failed to create webhook HTTP client")
+});
+
+/// Signs a payload the way receivers are expected to verify it:
+/// HMAC-SHA256 of "<timestamp>.<body>" with the webhook secret, hex-encoded.
+pub fn sign_payload(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+/// Name of an event as used in payloads and the X-Plfanzen-Event header
+fn event_name(event: WebhookEvent) -> &'static str {
+    match event {
+        WebhookEvent::Solve => "SOLVE",
+        WebhookEvent::FirstBlood => "FIRST_BLOOD",
+        WebhookEvent::Registration => "REGISTRATION",
+        WebhookEvent::InstanceStart => "INSTANCE_START",
+        WebhookEvent::TicketCreated => "TICKET_CREATED",
+        WebhookEvent::ChallengeRelease => "CHALLENGE_RELEASE",
+    }
+}
+
+/// Generates a new random webhook secret.
+pub fn generate_secret() -> String {
+    use rand::RngCore;
+    let mut buf = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut buf);
+    buf.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Sends an event to all active webhooks subscribed to it, in the background.
+pub fn dispatch(db_pool: &DbPool, event: WebhookEvent, data: serde_json::Value) {
+    let db_pool = db_pool.clone();
+    tokio::spawn(async move {
+        if let Err(e) = dispatch_internal(db_pool, event, data).await {
+            tracing::error!("Failed to dispatch {:?} webhooks: {}", event, e);
+        }
+    });
+}
+
+async fn dispatch_internal(
+    db_pool: DbPool,
+    event: WebhookEvent,
+    data: serde_json::Value,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use crate::db::schema::{webhook_deliveries, webhooks};
+
+    let mut conn = db_pool.get().await?;
+    let subscribed = webhooks::table
+        .filter(webhooks::is_active.eq(true))
+        .select(Webhook::as_select())
+        .load::<Webhook>(&mut conn)
+        .await?
+        .into_iter()
+        .filter(|w| w.events.contains(&event));
+
+    for webhook in subscribed {
+        let delivery_id = uuid::Uuid::now_v7();
+        let payload = json!({
+            "id": delivery_id,
+            "event": event_name(event),
+            "created_at": chrono::Utc::now().to_rfc3339(),
+            "data": data,
+        })
+        .to_string();
+        // The delivery is sent by a job, so it is retried even if this replica stops
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                diesel::insert_into(webhook_deliveries::table)
+                    .values(NewWebhookDelivery {
+                        id: delivery_id,
+                        webhook_id: webhook.id,
+                        event,
+                        payload,
+                    })
+                    .execute(conn)
+                    .await?;
+                crate::jobs::enqueue_at(
+                    conn,
+                    DELIVERY_JOB,
+                    json!({ "delivery_id": delivery_id }),
+                    chrono::Utc::now(),
+                    MAX_ATTEMPTS,
+                )
+                .await?;
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await?;
+    }
+    Ok(())
+}
+
+/// Sends a delivery enqueued by dispatch, failing the job if the receiver didn't accept it.
+pub fn run_delivery_job(ctx: BaseContext, payload: serde_json::Value) -> JobFuture {
+    Box::pin(async move {
+        let delivery_id = serde_json::from_value(payload["delivery_id"].clone())?;
+        deliver(&ctx.db_pool, delivery_id).await
+    })
+}
+
+/// Sends the payload of a delivery to its webhook and records the attempt.
+async fn deliver(db_pool: &DbPool, delivery_id: uuid::Uuid) -> Result<(), JobError> {
+    use crate::db::schema::{webhook_deliveries, webhooks};
+
+    let mut conn = db_pool.get().await?;
+    let Some((delivery, webhook)) = webhook_deliveries::table
+        .inner_join(webhooks::table)
+        .filter(webhook_deliveries::id.eq(delivery_id))
+        .select((WebhookDelivery::as_select(), Webhook::as_select()))
+        .first::<(WebhookDelivery, Webhook)>(&mut conn)
+        .await
+        .optional()?
+    else {
+        // The webhook was deleted since
+        return Ok(());
+    };
+    if delivery.delivered_at.is_some() || !webhook.is_active {
+        return Ok(());
+    }
+
+    let timestamp = chrono::Utc::now().timestamp();
+    let result = HTTP_CLIENT
+        .post(&webhook.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Plfanzen-Event", event_name(delivery.event))
+        .header("X-Plfanzen-Delivery", delivery_id.to_string())
+        .header("X-Plfanzen-Timestamp", timestamp.to_string())
+        .header(
+            "X-Plfanzen-Signature",
+            format!(
+                "sha256={}",
+                sign_payload(&webhook.secret, timestamp, &delivery.payload)
+            ),
+        )
+        .body(delivery.payload)
+        .send()
+        .await;
+    let (status, delivery_error) = match result {
+        Ok(resp) if resp.status().is_success() => (Some(resp.status().as_u16() as i32), None),
+        Ok(resp) => (
+            Some(resp.status().as_u16() as i32),
+            Some(format!("Receiver responded with {}", resp.status())),
+        ),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    let delivered = delivery_error.is_none();
+
+    diesel::update(webhook_deliveries::table.filter(webhook_deliveries::id.eq(delivery_id)))
+        .set((
+            webhook_deliveries::attempts.eq(webhook_deliveries::attempts + 1),
+            webhook_deliveries::response_status.eq(status),
+            webhook_deliveries::error.eq(&delivery_error),
+            webhook_deliveries::delivered_at.eq(delivered.then(chrono::Utc::now)),
+        ))
+        .execute(&mut conn)
+        .await?;
+
+    match delivery_error {
+        None => Ok(()),
+        Some(e) => Err(format!("Delivery to {} failed: {}", webhook.url, e).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_payload() {
+        // echo -n '1700000000.{"a":1}' | openssl dgst -sha256 -hmac secret
+        assert_eq!(
+            sign_payload("secret", 1700000000, r#"{"a":1}"#),
+            "49f24e537407743fa4a0242bb63b94b9a47ee99cbbe071ccd8a22550ae411686"
+        );
+    }
+}
@@ -0,0 +1,84 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use serde_json::json;
+use tonic::codegen::{BoxFuture, Context, Poll, Service, http};
+
+use plfanzen_error_reporting::{capture, is_enabled};
+
+/// Starts reporting panics and internal errors of gRPC requests if a DSN is configured.
+pub fn init(dsn: Option<&str>, environment: Option<String>) {
+    plfanzen_error_reporting::init("plfanzen-manager", dsn, environment);
+}
+
+/// gRPC status codes that mean a request failed because of the manager, not the caller
+const INTERNAL_STATUS_CODES: [tonic::Code; 4] = [
+    tonic::Code::Internal,
+    tonic::Code::Unknown,
+    tonic::Code::Unavailable,
+    tonic::Code::DataLoss,
+];
+
+/// Reports gRPC requests answered with an internal error.
+///
+/// Errors returned by the services are sent as trailers-only responses, so the status is already in
+/// the response headers.
+#[derive(Clone)]
+pub struct ReportInternalErrors<S> {
+    inner: S,
+}
+
+/// Adds [`ReportInternalErrors`] to the gRPC server.
+#[derive(Clone)]
+pub struct ReportInternalErrorsLayer;
+
+impl<S> tower_layer::Layer<S> for ReportInternalErrorsLayer {
+    type Service = ReportInternalErrors<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ReportInternalErrors { inner }
+    }
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for ReportInternalErrors<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<ReqBody>) -> Self::Future {
+        let path = request.uri().path().to_string();
+        let traceparent = request
+            .headers()
+            .get("traceparent")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let response = response.await?;
+            if is_enabled()
+                && let Some(status) = tonic::Status::from_header_map(response.headers())
+                && INTERNAL_STATUS_CODES.contains(&status.code())
+            {
+                capture(
+                    "grpc",
+                    status.message(),
+                    json!({
+                        "path": path,
+                        "code": status.code().description(),
+                        "traceparent": traceparent,
+                    }),
+                );
+            }
+            Ok(response)
+        })
+    }
+}
@@ -2,27 +2,92 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
+use futures_util::StreamExt;
 use tonic::Response;
 
 use crate::grpc::api::{
-    Challenge, CheckFlagRequest, CheckFlagResponse, ConnectionInfo, ExportChallengeRequest,
-    ExportChallengeResponse, GetChallengeInstanceStatusRequest, GetChallengeInstanceStatusResponse,
-    ListChallengesRequest, ListChallengesResponse, Protocol, RetrieveFileRequest,
+    CalculatePointsRequest, CalculatePointsResponse, Challenge, ChallengeFile, CheckFlagRequest,
+    CheckFlagResponse, CollectGarbageRequest, CollectGarbageResponse, ConnectionInfo,
+    ExecInstanceRequest, ExecInstanceResponse, ExportChallengeRequest, ExportChallengeResponse,
+    ExtendChallengeInstanceRequest, ExtendChallengeInstanceResponse, GetActorFlagRequest,
+    GetActorFlagResponse, GetActorFlagsRequest, GetActorFlagsResponse,
+    GetChallengeInstanceStatusRequest, GetChallengeInstanceStatusResponse, GetInstanceLogsRequest,
+    GetInstanceMetricsRequest, GetInstanceMetricsResponse, InstanceFailure, InstanceLogLine,
+    InstanceMetrics, InstanceProgress, InstanceSummary, ListChallengesRequest,
+    ListChallengesResponse, ListInstancesRequest, ListInstancesResponse, PodMetrics, Protocol,
+    RestartChallengeInstanceRequest, RestartChallengeInstanceResponse, RetrieveFileRequest,
     RetrieveFileResponse, StartChallengeInstanceRequest, StartChallengeInstanceResponse,
-    StopChallengeInstanceRequest, StopChallengeInstanceResponse,
+    StopChallengeInstanceRequest, StopChallengeInstanceResponse, ValidateChallengeRequest,
+    ValidateChallengeResponse, ValidationIssue, ValidationSeverity, exec_instance_request,
+    exec_instance_response,
 };
-use crate::instances::{InstanceState, full_instance_ns};
+use crate::instances::admission::{
+    AdmissionQueue, QueuedStart, admission_control_enabled, challenge_requests, cluster_capacity,
+};
+use crate::instances::diagnostics::diagnose_instance;
+use crate::instances::exec;
+use crate::instances::priority::InstancePriority;
+use crate::instances::progress::{DeployStage, InstanceStage, instance_progress, set_deploy_stage};
+use crate::instances::shared::{SHARED_ACTOR, SHARED_INSTANCE_ID, challenge_revision};
+use crate::instances::{
+    InstanceState, PrepareInstanceError, full_instance_ns, lifetime::LifetimeConfig,
+};
+use crate::repo::builds::{BuildState, BuildStore};
 use crate::repo::challenges::loader::tera::render_dir_recursively;
-use crate::repo::challenges::loader::{load_challenge_from_repo, load_challenges_from_repo};
+use crate::repo::challenges::loader::{
+    load_challenge_from_repo, load_challenge_instance_from_repo, load_challenges_from_repo,
+};
+use crate::repo::challenges::metadata::{DeploymentMode, FlagValidator};
+use crate::repo::challenges::packages::PackageStore;
+use crate::repo::challenges::validate::{Severity, validate_challenge};
 use crate::repo::challenges::vm::HasVms;
 
 use super::api::challenges_service_server::ChallengesService;
+
+fn prepare_error_status(challenge_id: &str, e: PrepareInstanceError) -> tonic::Status {
+    match e {
+        PrepareInstanceError::TooManyPending | PrepareInstanceError::QuotaExceeded(_) => {
+            tonic::Status::resource_exhausted(e.to_string())
+        }
+        PrepareInstanceError::AlreadyRunning => tonic::Status::already_exists(e.to_string()),
+        e => tonic::Status::internal(format!(
+            "Failed to start challenge instance for challenge {}: {}",
+            challenge_id, e
+        )),
+    }
+}
+
+fn proto_stage(stage: InstanceStage) -> crate::grpc::api::InstanceStage {
+    use crate::grpc::api::InstanceStage as Stage;
+    match stage {
+        InstanceStage::NamespaceCreated => Stage::NamespaceCreated,
+        InstanceStage::CreatingResources => Stage::CreatingResources,
+        InstanceStage::Scheduling => Stage::Scheduling,
+        InstanceStage::PullingImages => Stage::PullingImages,
+        InstanceStage::Starting => Stage::Starting,
+        InstanceStage::Ready => Stage::Ready,
+    }
+}
+
+/// How often queued instances are checked for free capacity
+const ADMISSION_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Challenges loaded at the same time to look up the flags of many actors
+const ACTOR_FLAG_CONCURRENCY: usize = 16;
+
+#[derive(Clone)]
 pub struct ChallengeManager {
     pub repo_dir: PathBuf,
     pub kube_client: kube::Client,
+    pub builds: Arc<BuildStore>,
+    pub admission_queue: Arc<AdmissionQueue>,
+    /// Attachments packed during syncs, if a store is configured
+    pub packages: Option<Arc<PackageStore>>,
 }
 
 fn get_connection_details(
@@ -118,8 +183,389 @@ fn get_connection_details(
     connection_info
 }
 
+impl ChallengeManager {
+    /// Loads a challenge and checks that an instance of it can be started, returning it and
+    /// whether it uses built images
+    async fn load_startable_challenge(
+        &self,
+        challenge_id: &str,
+        actor: &str,
+        require_release: bool,
+    ) -> Result<(crate::repo::challenges::loader::Challenge, bool), tonic::Status> {
+        if !challenge_id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        {
+            return Err(tonic::Status::invalid_argument(
+                "challenge_id contains invalid characters",
+            ));
+        }
+        let challenge = load_challenge_from_repo(&self.repo_dir, challenge_id, actor, false)
+            .await
+            .map_err(|e| {
+                tonic::Status::internal(format!(
+                    "Failed to load challenge {} from repo: {}",
+                    challenge_id, e
+                ))
+            })?;
+
+        let requires_build = challenge
+            .compose
+            .services
+            .values()
+            .any(|svc| svc.build.is_some());
+        if requires_build {
+            let build = crate::repo::get_head_commit_info(&self.repo_dir).and_then(|commit_info| {
+                self.builds
+                    .for_commit(&commit_info.hash)
+                    .remove(challenge_id)
+            });
+            match build.map(|build| build.state) {
+                Some(BuildState::Succeeded) => {}
+                Some(BuildState::Failed) => {
+                    return Err(tonic::Status::failed_precondition(format!(
+                        "Images of challenge {} failed to build",
+                        challenge_id
+                    )));
+                }
+                _ => {
+                    return Err(tonic::Status::unavailable(format!(
+                        "Images of challenge {} are still being built",
+                        challenge_id
+                    )));
+                }
+            }
+        }
+
+        if challenge.compose.services.is_empty() && challenge.compose.get_vms().is_empty() {
+            return Err(tonic::Status::failed_precondition(format!(
+                "Challenge {} has no services to start",
+                challenge_id
+            )));
+        }
+
+        if require_release {
+            let now = chrono::Utc::now().timestamp() as u64;
+            if let Some(release_time) = challenge.metadata.release_time
+                && now < release_time
+            {
+                return Err(tonic::Status::failed_precondition(format!(
+                    "Challenge {} has not been released yet",
+                    challenge_id
+                )));
+            }
+        }
+
+        Ok((challenge, requires_build))
+    }
+
+    /// Assigns a pooled instance of the challenge to the actor, None if the challenge isn't
+    /// pooled or its pool is empty
+    async fn claim_pooled_instance(
+        &self,
+        challenge: &crate::repo::challenges::loader::Challenge,
+        challenge_id: &str,
+        actor: &str,
+    ) -> Result<Option<StartChallengeInstanceResponse>, tonic::Status> {
+        let chall_dir = self.repo_dir.join("challs").join(challenge_id);
+        // Instances pooled before the challenge stopped being poolable aren't handed out
+        if challenge.metadata.pool_size.is_none()
+            || crate::instances::pool::check_poolable(challenge, &chall_dir).is_err()
+        {
+            return Ok(None);
+        }
+        let _start = crate::instances::START_LOCK.lock().await;
+        crate::instances::check_can_start(&self.kube_client, challenge_id, actor)
+            .await
+            .map_err(|e| prepare_error_status(challenge_id, e))?;
+        let revision = challenge_revision(&chall_dir).map_err(|e| {
+            tonic::Status::internal(format!("Failed to hash challenge {}: {}", challenge_id, e))
+        })?;
+        let instance_id = crate::instances::pool::claim_instance(
+            &self.kube_client,
+            challenge_id,
+            actor,
+            &revision,
+        )
+        .await
+        .map_err(|e| {
+            tonic::Status::internal(format!(
+                "Failed to claim pooled instance of challenge {}: {}",
+                challenge_id, e
+            ))
+        })?;
+        let Some(instance_id) = instance_id else {
+            return Ok(None);
+        };
+        let connection_info = get_connection_details(challenge, challenge_id, &instance_id, actor);
+        Ok(Some(StartChallengeInstanceResponse {
+            instance_id,
+            connection_info,
+            queue_position: None,
+        }))
+    }
+
+    /// The flag of the challenge as rendered for the actor, None if it is checked by a script
+    async fn actor_flag(
+        &self,
+        challenge_id: &str,
+        actor: &str,
+    ) -> Result<Option<String>, tonic::Status> {
+        let challenge = load_challenge_from_repo(&self.repo_dir, challenge_id, actor, false)
+            .await
+            .map_err(|e| {
+                tonic::Status::internal(format!(
+                    "Failed to load challenge {} from repo: {}",
+                    challenge_id, e
+                ))
+            })?;
+        challenge
+            .metadata
+            .actor_flag(challenge_id, actor)
+            .map_err(|e| {
+                tonic::Status::internal(format!(
+                    "Failed to derive flag of challenge {}: {}",
+                    challenge_id, e
+                ))
+            })
+    }
+
+    /// Creates the namespace of a new instance and deploys the challenge into it
+    async fn deploy_instance(
+        &self,
+        challenge_id: &str,
+        actor: &str,
+        requires_build: bool,
+        priority: InstancePriority,
+    ) -> Result<StartChallengeInstanceResponse, tonic::Status> {
+        let instance_id =
+            crate::instances::prepare_instance(&self.kube_client, challenge_id, actor)
+                .await
+                .map_err(|e| prepare_error_status(challenge_id, e))?;
+        // Loaded again, now that the template variables of the instance are known
+        let mut challenge =
+            load_challenge_instance_from_repo(&self.repo_dir, challenge_id, actor, &instance_id)
+                .await
+                .map_err(|e| {
+                    tonic::Status::failed_precondition(format!(
+                        "Failed to load challenge {} for instance {}: {}",
+                        challenge_id, instance_id, e
+                    ))
+                })?;
+        if requires_build {
+            crate::repo::image_builder::use_built_images(
+                &mut challenge.compose,
+                &self.repo_dir.join("challs").join(challenge_id),
+                challenge_id,
+                crate::repo::get_head_commit(&self.repo_dir).as_deref(),
+            )
+            .map_err(tonic::Status::failed_precondition)?;
+        }
+        let flag = challenge
+            .metadata
+            .actor_flag(challenge_id, actor)
+            .map_err(|e| {
+                tonic::Status::internal(format!(
+                    "Failed to derive flag of challenge {}: {}",
+                    challenge_id, e
+                ))
+            })?;
+        let connection_info = get_connection_details(&challenge, challenge_id, &instance_id, actor);
+
+        let working_dir = tempfile::tempdir().map_err(|e| {
+            tonic::Status::internal(format!(
+                "Failed to create temporary working directory: {}",
+                e
+            ))
+        })?;
+
+        render_dir_recursively(
+            &self.repo_dir.join("challs").join(challenge_id),
+            working_dir.path(),
+            actor,
+            false,
+        )
+        .map_err(|e| {
+            tonic::Status::internal(format!(
+                "Failed to render challenge templates for challenge {}: {}",
+                challenge_id, e
+            ))
+        })?;
+
+        self.record_deploy_stage(challenge_id, &instance_id, DeployStage::CreatingResources)
+            .await;
+        crate::instances::deploy::deploy_challenge(
+            &self.kube_client,
+            &full_instance_ns(challenge_id, &instance_id),
+            challenge,
+            &std::env::var("EXPOSED_DOMAIN").unwrap_or("localhost".to_string()),
+            working_dir.path(),
+            actor,
+            &instance_id,
+            flag.as_deref(),
+            priority,
+        )
+        .await
+        .map_err(|e| {
+            tonic::Status::internal(format!(
+                "Failed to deploy challenge instance for challenge {}: {}",
+                challenge_id, e
+            ))
+        })?;
+        self.record_deploy_stage(challenge_id, &instance_id, DeployStage::Deployed)
+            .await;
+        Ok(StartChallengeInstanceResponse {
+            instance_id,
+            connection_info,
+            queue_position: None,
+        })
+    }
+
+    /// Only shown to players, so a failure to record it doesn't fail the start
+    async fn record_deploy_stage(&self, challenge_id: &str, instance_id: &str, stage: DeployStage) {
+        if let Err(e) = set_deploy_stage(&self.kube_client, challenge_id, instance_id, stage).await
+        {
+            tracing::warn!(
+                "Failed to record deploy stage of instance {}: {}",
+                instance_id,
+                e
+            );
+        }
+    }
+
+    /// Starts queued instances for as long as the next one fits into the cluster
+    async fn admit_queued_instances(&self) {
+        let mut capacity = match cluster_capacity(&self.kube_client).await {
+            Ok(capacity) => capacity,
+            Err(e) => {
+                tracing::error!("Failed to get cluster capacity: {}", e);
+                return;
+            }
+        };
+        while let Some(next) = self.admission_queue.front() {
+            let (challenge, requires_build) = match self
+                .load_startable_challenge(&next.challenge_id, &next.actor, next.require_release)
+                .await
+            {
+                Ok(challenge) => challenge,
+                Err(e) => {
+                    tracing::warn!(
+                        "Dropping queued instance of challenge {} for {}: {}",
+                        next.challenge_id,
+                        next.actor,
+                        e.message()
+                    );
+                    self.admission_queue.remove(&next.challenge_id, &next.actor);
+                    continue;
+                }
+            };
+            let requests = challenge_requests(&challenge);
+            // It would block everyone behind it forever
+            if !requests.fits_in(&capacity.total) {
+                tracing::warn!(
+                    "Dropping queued instance of challenge {} for {}, it needs more resources than the cluster has",
+                    next.challenge_id,
+                    next.actor
+                );
+                self.admission_queue.remove(&next.challenge_id, &next.actor);
+                continue;
+            }
+            // Later instances have to wait even if they'd fit, so large ones aren't starved
+            if !requests.fits_in(&capacity.free) {
+                return;
+            }
+            self.admission_queue.remove(&next.challenge_id, &next.actor);
+            match self
+                .deploy_instance(
+                    &next.challenge_id,
+                    &next.actor,
+                    requires_build,
+                    InstancePriority::for_start(next.require_release),
+                )
+                .await
+            {
+                // Its pods might not be listed yet, so they are subtracted here
+                Ok(_) => capacity.free.reserve(requests),
+                Err(e) => tracing::error!(
+                    "Failed to start queued instance of challenge {} for {}: {}",
+                    next.challenge_id,
+                    next.actor,
+                    e.message()
+                ),
+            }
+        }
+    }
+
+    /// Status of the shared deployment of a challenge, None if it isn't a shared challenge
+    async fn shared_instance_status(
+        &self,
+        challenge_id: &str,
+    ) -> Result<Option<GetChallengeInstanceStatusResponse>, tonic::Status> {
+        let api: kube::Api<k8s_openapi::api::core::v1::Namespace> =
+            kube::Api::all(self.kube_client.clone());
+        let shared_ns = api
+            .get_opt(&full_instance_ns(challenge_id, SHARED_INSTANCE_ID))
+            .await
+            .map_err(|e| {
+                tonic::Status::internal(format!(
+                    "Failed to look up shared deployment of challenge {}: {}",
+                    challenge_id, e
+                ))
+            })?;
+        if shared_ns.is_none_or(|ns| ns.metadata.deletion_timestamp.is_some()) {
+            return Ok(None);
+        }
+        let challenge = load_challenge_from_repo(&self.repo_dir, challenge_id, SHARED_ACTOR, false)
+            .await
+            .map_err(|e| {
+                tonic::Status::internal(format!(
+                    "Failed to load challenge {} from repo: {}",
+                    challenge_id, e
+                ))
+            })?;
+        Ok(Some(GetChallengeInstanceStatusResponse {
+            is_deployed: true,
+            is_ready: crate::instances::is_instance_running(
+                &self.kube_client,
+                challenge_id,
+                SHARED_INSTANCE_ID,
+            )
+            .await,
+            connection_info: get_connection_details(
+                &challenge,
+                challenge_id,
+                SHARED_INSTANCE_ID,
+                SHARED_ACTOR,
+            ),
+            expires_at: None,
+            queue_position: None,
+            failure_reason: None,
+            failure_hint: None,
+            progress: None,
+        }))
+    }
+
+    /// Periodically starts queued instances in the background
+    pub fn spawn_admission_worker(self) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(ADMISSION_INTERVAL);
+            loop {
+                interval.tick().await;
+                self.admit_queued_instances().await;
+            }
+        });
+    }
+}
+
 #[tonic::async_trait]
 impl ChallengesService for ChallengeManager {
+    type GetInstanceLogsStream = std::pin::Pin<
+        Box<dyn futures_util::Stream<Item = Result<InstanceLogLine, tonic::Status>> + Send>,
+    >;
+    type ExecInstanceStream = std::pin::Pin<
+        Box<dyn futures_util::Stream<Item = Result<ExecInstanceResponse, tonic::Status>> + Send>,
+    >;
+
     /// ListChallenges returns a list of all available challenges.
     async fn list_challenges(
         &self,
@@ -133,6 +579,7 @@ impl ChallengesService for ChallengeManager {
         let event_config = crate::repo::EventConfig::try_load_from_repo(&self.repo_dir)
             .await
             .map_err(|e| tonic::Status::internal(format!("Failed to load event config: {}", e)))?;
+        let packages = self.packages.as_deref();
         let mut out_challenges = vec![];
         for (id, chall) in challenges {
             if request.require_release {
@@ -143,6 +590,12 @@ impl ChallengesService for ChallengeManager {
                     continue;
                 }
             }
+            let shared = chall.metadata.deployment == DeploymentMode::Shared;
+            let shared_connection_info = if shared {
+                get_connection_details(&chall, &id, SHARED_INSTANCE_ID, SHARED_ACTOR)
+            } else {
+                vec![]
+            };
             let solve_info = request.solved_challenges.get(&id);
             let points = event_config
                 .calculate_points(
@@ -164,20 +617,52 @@ impl ChallengesService for ChallengeManager {
                         id, e
                     ))
                 })?;
+            let release_wave = event_config
+                .release_wave(&id)
+                .map(|(wave_name, _)| wave_name.to_string());
             out_challenges.push(Challenge {
-                id,
+                id: id.clone(),
                 name: chall.metadata.name,
-                description: chall.metadata.description_md,
+                description: chall.metadata.description_md.default_text().to_string(),
+                description_translations: chall.metadata.description_md.translations(),
                 release_timestamp: chall.metadata.release_time,
+                release_wave,
                 end_timestamp: chall.metadata.end_time,
+                max_attempts: chall.metadata.max_attempts,
+                playtest: chall.metadata.playtest,
                 categories: chall.metadata.categories,
                 authors: chall.metadata.authors,
                 attachments: chall.metadata.attachments,
-                can_start: !chall.compose.services.is_empty()
-                    || !chall.compose.get_vms().is_empty(),
+                files: chall
+                    .files
+                    .into_iter()
+                    .map(|file| ChallengeFile {
+                        url: packages
+                            .and_then(|store| store.attachment_url(&id, &file.name, &file.sha256))
+                            .unwrap_or_default(),
+                        name: file.name,
+                        size: file.size,
+                        sha256: file.sha256,
+                    })
+                    .collect(),
+                source: packages
+                    .filter(|_| chall.metadata.auto_publish_src)
+                    .and_then(|store| {
+                        let source = store.get(&id)?.source?;
+                        Some(ChallengeFile {
+                            url: store.url(&id, &source),
+                            name: source.name,
+                            size: source.size,
+                            sha256: source.sha256,
+                        })
+                    }),
+                can_start: !shared
+                    && (!chall.compose.services.is_empty() || !chall.compose.get_vms().is_empty()),
                 points,
                 difficulty: chall.metadata.difficulty,
                 can_export: chall.metadata.auto_publish_src,
+                shared,
+                shared_connection_info,
             });
         }
         let response = ListChallengesResponse {
@@ -186,110 +671,138 @@ impl ChallengesService for ChallengeManager {
         Ok(tonic::Response::new(response))
     }
 
-    /// StartChallengeInstance starts a new instance of the specified challenge for the given team.
-    async fn start_challenge_instance(
+    /// CalculatePoints returns the points awarded for a batch of solves, e.g. to build a scoreboard.
+    async fn calculate_points(
         &self,
-        request: tonic::Request<StartChallengeInstanceRequest>,
-    ) -> Result<tonic::Response<StartChallengeInstanceResponse>, tonic::Status> {
+        request: tonic::Request<CalculatePointsRequest>,
+    ) -> Result<tonic::Response<CalculatePointsResponse>, tonic::Status> {
         let request = request.into_inner();
-        if !request
-            .challenge_id
-            .chars()
-            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
-        {
-            return Err(tonic::Status::invalid_argument(
-                "challenge_id contains invalid characters",
-            ));
-        }
-        let challenge =
-            load_challenge_from_repo(&self.repo_dir, &request.challenge_id, &request.actor, false)
+        // Points only depend on the metadata, so the actor used for rendering does not matter here
+        let challenges = load_challenges_from_repo(&self.repo_dir, "", false)
+            .await
+            .map_err(|e| tonic::Status::internal(format!("Failed to load challenges: {}", e)))?;
+
+        let event_config = crate::repo::EventConfig::try_load_from_repo(&self.repo_dir)
+            .await
+            .map_err(|e| tonic::Status::internal(format!("Failed to load event config: {}", e)))?;
+        let mut points = Vec::with_capacity(request.queries.len());
+        for query in request.queries {
+            let Some(chall) = challenges.get(&query.challenge_id) else {
+                // Challenges that were removed from the repo no longer award points
+                points.push(0);
+                continue;
+            };
+            let solve = query.solve.unwrap_or_default();
+            let chall_points = event_config
+                .calculate_points(
+                    &chall.metadata,
+                    solve.total_solves as u32,
+                    solve.actor_nth_solve as u32,
+                    request.total_competitors as u32,
+                )
                 .await
                 .map_err(|e| {
                     tonic::Status::internal(format!(
-                        "Failed to load challenge {} from repo: {}",
-                        request.challenge_id, e
+                        "Failed to calculate points for challenge {}: {}",
+                        query.challenge_id, e
                     ))
                 })?;
+            points.push(chall_points);
+        }
+        Ok(tonic::Response::new(CalculatePointsResponse { points }))
+    }
 
-        if challenge.compose.services.is_empty() && challenge.compose.get_vms().is_empty() {
-            return Err(tonic::Status::failed_precondition(format!(
-                "Challenge {} has no services to start",
-                request.challenge_id
-            )));
+    /// StartChallengeInstance starts a new instance of the specified challenge for the given team.
+    async fn start_challenge_instance(
+        &self,
+        request: tonic::Request<StartChallengeInstanceRequest>,
+    ) -> Result<tonic::Response<StartChallengeInstanceResponse>, tonic::Status> {
+        let request = request.into_inner();
+        let (challenge, requires_build) = self
+            .load_startable_challenge(
+                &request.challenge_id,
+                &request.actor,
+                request.require_release,
+            )
+            .await?;
+
+        if challenge.metadata.deployment == DeploymentMode::Shared {
+            // Everyone connects to the deployment the manager keeps running
+            return Ok(Response::new(StartChallengeInstanceResponse {
+                instance_id: SHARED_INSTANCE_ID.to_string(),
+                connection_info: get_connection_details(
+                    &challenge,
+                    &request.challenge_id,
+                    SHARED_INSTANCE_ID,
+                    SHARED_ACTOR,
+                ),
+                queue_position: None,
+            }));
         }
 
-        if request.require_release {
-            let now = chrono::Utc::now().timestamp() as u64;
-            if let Some(release_time) = challenge.metadata.release_time
-                && now < release_time
+        if let Some(response) = self
+            .claim_pooled_instance(&challenge, &request.challenge_id, &request.actor)
+            .await?
+        {
+            return Ok(Response::new(response));
+        }
+
+        if admission_control_enabled() {
+            let queued = |queue_position: usize| {
+                Response::new(StartChallengeInstanceResponse {
+                    instance_id: String::new(),
+                    connection_info: vec![],
+                    queue_position: Some(queue_position as u32),
+                })
+            };
+            if let Some(position) = self
+                .admission_queue
+                .position(&request.challenge_id, &request.actor)
             {
+                return Ok(queued(position));
+            }
+            let capacity = cluster_capacity(&self.kube_client).await.map_err(|e| {
+                tonic::Status::internal(format!("Failed to get cluster capacity: {}", e))
+            })?;
+            let requests = challenge_requests(&challenge);
+            if !requests.fits_in(&capacity.total) {
                 return Err(tonic::Status::failed_precondition(format!(
-                    "Challenge {} has not been released yet",
+                    "Challenge {} needs more resources than the cluster has",
                     request.challenge_id
                 )));
             }
+            if !self.admission_queue.is_empty() || !requests.fits_in(&capacity.free) {
+                // Queueing an instance the actor can't have would only block others
+                if crate::instances::get_instances(
+                    &self.kube_client,
+                    &request.challenge_id,
+                    &request.actor,
+                )
+                .await
+                .values()
+                .any(|state| *state != InstanceState::Terminating)
+                {
+                    return Err(prepare_error_status(
+                        &request.challenge_id,
+                        PrepareInstanceError::AlreadyRunning,
+                    ));
+                }
+                return Ok(queued(self.admission_queue.enqueue(QueuedStart {
+                    challenge_id: request.challenge_id,
+                    actor: request.actor,
+                    require_release: request.require_release,
+                })));
+            }
         }
 
-        let instance_id = crate::instances::prepare_instance(
-            &self.kube_client,
+        self.deploy_instance(
             &request.challenge_id,
             &request.actor,
+            requires_build,
+            InstancePriority::for_start(request.require_release),
         )
         .await
-        .map_err(|e| {
-            tonic::Status::internal(format!(
-                "Failed to start challenge instance for challenge {}: {}",
-                request.challenge_id, e
-            ))
-        })?;
-        let connection_info = get_connection_details(
-            &challenge,
-            &request.challenge_id,
-            &instance_id,
-            &request.actor,
-        );
-
-        let working_dir = tempfile::tempdir().map_err(|e| {
-            tonic::Status::internal(format!(
-                "Failed to create temporary working directory: {}",
-                e
-            ))
-        })?;
-
-        render_dir_recursively(
-            &self.repo_dir.join("challs").join(&request.challenge_id),
-            working_dir.path(),
-            &request.actor,
-            false,
-        )
-        .map_err(|e| {
-            tonic::Status::internal(format!(
-                "Failed to render challenge templates for challenge {}: {}",
-                request.challenge_id, e
-            ))
-        })?;
-
-        crate::instances::deploy::deploy_challenge(
-            &self.kube_client,
-            &full_instance_ns(&request.challenge_id, &instance_id),
-            challenge,
-            &std::env::var("EXPOSED_DOMAIN").unwrap_or("localhost".to_string()),
-            working_dir.path(),
-            &request.actor,
-            &instance_id,
-        )
-        .await
-        .map_err(|e| {
-            tonic::Status::internal(format!(
-                "Failed to deploy challenge instance for challenge {}: {}",
-                request.challenge_id, e
-            ))
-        })?;
-        let response = StartChallengeInstanceResponse {
-            instance_id,
-            connection_info,
-        };
-        Ok(Response::new(response))
+        .map(Response::new)
     }
 
     /// StopChallengeInstance stops the specified challenge instance for the given team.
@@ -304,7 +817,9 @@ impl ChallengesService for ChallengeManager {
             &request.actor,
         )
         .await;
-        let mut success = false;
+        let mut success = self
+            .admission_queue
+            .remove(&request.challenge_id, &request.actor);
         for (instance_name, state) in instances {
             if state == InstanceState::Terminating {
                 continue;
@@ -343,10 +858,21 @@ impl ChallengesService for ChallengeManager {
         .filter(|(_, state)| *state != InstanceState::Terminating)
         .collect::<HashMap<_, _>>();
         if instances.is_empty() {
+            if let Some(status) = self.shared_instance_status(&request.challenge_id).await? {
+                return Ok(Response::new(status));
+            }
             return Ok(Response::new(GetChallengeInstanceStatusResponse {
                 is_deployed: false,
                 is_ready: false,
                 connection_info: vec![],
+                expires_at: None,
+                queue_position: self
+                    .admission_queue
+                    .position(&request.challenge_id, &request.actor)
+                    .map(|position| position as u32),
+                failure_reason: None,
+                failure_hint: None,
+                progress: None,
             }));
         }
         // For simplicity, we assume only one instance per challenge per actor
@@ -367,19 +893,271 @@ impl ChallengesService for ChallengeManager {
             &instance_id,
             &request.actor,
         );
+        let expires_at = crate::instances::lifetime::instance_expiry(
+            &self.kube_client,
+            &request.challenge_id,
+            &instance_id,
+        )
+        .await;
+        let failure = if is_ready {
+            None
+        } else {
+            diagnose_instance(&self.kube_client, &request.challenge_id, &instance_id)
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::warn!("Failed to diagnose instance {}: {}", instance_id, e);
+                    None
+                })
+        };
+        let progress = if is_ready {
+            None
+        } else {
+            instance_progress(&self.kube_client, &request.challenge_id, &instance_id)
+                .await
+                .inspect_err(|e| {
+                    tracing::warn!("Failed to get progress of instance {}: {}", instance_id, e)
+                })
+                .ok()
+        };
         Ok(Response::new(GetChallengeInstanceStatusResponse {
             is_deployed: true,
             is_ready,
             connection_info,
+            expires_at: expires_at.map(|t| t.timestamp()),
+            queue_position: None,
+            failure_hint: failure.as_ref().map(|failure| failure.hint().to_string()),
+            failure_reason: failure.map(|failure| InstanceFailure {
+                reason: failure.reason,
+                message: failure.message,
+                pod: failure.pod,
+                container: failure.container,
+                events: failure.events,
+            }),
+            progress: progress.map(|progress| InstanceProgress {
+                stage: proto_stage(progress.stage) as i32,
+                ready_pods: progress.ready_pods,
+                total_pods: progress.total_pods,
+            }),
+        }))
+    }
+
+    /// ExtendChallengeInstance postpones the expiry of the given team's instance.
+    async fn extend_challenge_instance(
+        &self,
+        request: tonic::Request<ExtendChallengeInstanceRequest>,
+    ) -> Result<tonic::Response<ExtendChallengeInstanceResponse>, tonic::Status> {
+        let request = request.into_inner();
+        let Some(config) = LifetimeConfig::from_env() else {
+            return Err(tonic::Status::failed_precondition(
+                "Instances do not expire",
+            ));
+        };
+        let instance_id = crate::instances::get_instances(
+            &self.kube_client,
+            &request.challenge_id,
+            &request.actor,
+        )
+        .await
+        .into_iter()
+        .find(|(_, state)| *state != InstanceState::Terminating)
+        .map(|(instance_id, _)| instance_id)
+        .ok_or_else(|| {
+            tonic::Status::not_found(format!(
+                "No running instance of challenge {}",
+                request.challenge_id
+            ))
+        })?;
+        let expires_at = crate::instances::lifetime::extend_instance(
+            &self.kube_client,
+            &config,
+            &request.challenge_id,
+            &request.actor,
+            &instance_id,
+        )
+        .await
+        .map_err(|e| {
+            tonic::Status::failed_precondition(format!(
+                "Failed to extend instance {} of challenge {}: {}",
+                instance_id, request.challenge_id, e
+            ))
+        })?;
+        Ok(Response::new(ExtendChallengeInstanceResponse {
+            expires_at: expires_at.timestamp(),
+        }))
+    }
+
+    /// RestartChallengeInstance recreates the pods of the given team's instance.
+    async fn restart_challenge_instance(
+        &self,
+        request: tonic::Request<RestartChallengeInstanceRequest>,
+    ) -> Result<tonic::Response<RestartChallengeInstanceResponse>, tonic::Status> {
+        let request = request.into_inner();
+        let instance_id = crate::instances::get_instances(
+            &self.kube_client,
+            &request.challenge_id,
+            &request.actor,
+        )
+        .await
+        .into_iter()
+        .find(|(_, state)| *state != InstanceState::Terminating)
+        .map(|(instance_id, _)| instance_id)
+        .ok_or_else(|| {
+            tonic::Status::not_found(format!(
+                "No running instance of challenge {}",
+                request.challenge_id
+            ))
+        })?;
+        crate::instances::restart_instance(
+            &self.kube_client,
+            &request.challenge_id,
+            &request.actor,
+            &instance_id,
+        )
+        .await
+        .map_err(|e| {
+            tonic::Status::internal(format!(
+                "Failed to restart instance {} of challenge {}: {}",
+                instance_id, request.challenge_id, e
+            ))
+        })?;
+        Ok(Response::new(RestartChallengeInstanceResponse {
+            success: true,
         }))
     }
 
+    /// GetInstanceLogs streams the logs of the containers in the given team's instance.
+    async fn get_instance_logs(
+        &self,
+        request: tonic::Request<GetInstanceLogsRequest>,
+    ) -> Result<tonic::Response<Self::GetInstanceLogsStream>, tonic::Status> {
+        let request = request.into_inner();
+        let instance_id = crate::instances::get_instances(
+            &self.kube_client,
+            &request.challenge_id,
+            &request.actor,
+        )
+        .await
+        .into_iter()
+        .find(|(_, state)| *state != InstanceState::Terminating)
+        .map(|(instance_id, _)| instance_id)
+        .ok_or_else(|| {
+            tonic::Status::not_found(format!(
+                "No running instance of challenge {}",
+                request.challenge_id
+            ))
+        })?;
+        let logs = crate::instances::logs::instance_logs(
+            &self.kube_client,
+            &request.challenge_id,
+            &instance_id,
+            request.service.as_deref(),
+            request.tail_lines.map(i64::from),
+            request.follow,
+        )
+        .await
+        .map_err(|e| {
+            tonic::Status::internal(format!(
+                "Failed to get logs of instance {} of challenge {}: {}",
+                instance_id, request.challenge_id, e
+            ))
+        })?;
+        Ok(Response::new(Box::pin(logs.map(|line| {
+            line.map(|line| InstanceLogLine {
+                pod: line.pod,
+                container: line.container,
+                line: line.line,
+            })
+            .map_err(|e| tonic::Status::internal(format!("Failed to read logs: {}", e)))
+        }))))
+    }
+
+    /// ExecInstance runs a command in a container of the given team's instance.
+    async fn exec_instance(
+        &self,
+        request: tonic::Request<tonic::Streaming<ExecInstanceRequest>>,
+    ) -> Result<tonic::Response<Self::ExecInstanceStream>, tonic::Status> {
+        let mut input = request.into_inner();
+        let Some(exec_instance_request::Kind::Start(start)) =
+            input.message().await?.and_then(|message| message.kind)
+        else {
+            return Err(tonic::Status::invalid_argument(
+                "The first message has to start a command",
+            ));
+        };
+        if start.command.is_empty() {
+            return Err(tonic::Status::invalid_argument("No command given"));
+        }
+        let instance_id =
+            crate::instances::get_instances(&self.kube_client, &start.challenge_id, &start.actor)
+                .await
+                .into_iter()
+                .find(|(_, state)| *state != InstanceState::Terminating)
+                .map(|(instance_id, _)| instance_id)
+                .ok_or_else(|| {
+                    tonic::Status::not_found(format!(
+                        "No running instance of challenge {}",
+                        start.challenge_id
+                    ))
+                })?;
+        let process = exec::exec_in_instance(
+            &self.kube_client,
+            &start.challenge_id,
+            &instance_id,
+            start.service.as_deref(),
+            start.container.as_deref(),
+            start.command,
+            start.tty,
+        )
+        .await
+        .map_err(|e| match e {
+            exec::ExecError::NoPod => tonic::Status::not_found(format!(
+                "No running pod in instance {} of challenge {}",
+                instance_id, start.challenge_id
+            )),
+            exec::ExecError::Kube(e) => tonic::Status::internal(format!(
+                "Failed to run command in instance {} of challenge {}: {}",
+                instance_id, start.challenge_id, e
+            )),
+        })?;
+
+        // Input ends when the client closes its side of the stream or it breaks
+        let input = input
+            .take_while(|message| std::future::ready(message.is_ok()))
+            .filter_map(|message| {
+                std::future::ready(match message.ok()?.kind? {
+                    exec_instance_request::Kind::Stdin(data) => Some(exec::ExecInput::Stdin(data)),
+                    exec_instance_request::Kind::Resize(size) => Some(exec::ExecInput::Resize {
+                        width: size.width.min(u16::MAX as u32) as u16,
+                        height: size.height.min(u16::MAX as u32) as u16,
+                    }),
+                    exec_instance_request::Kind::Start(_) => None,
+                })
+            });
+        let (output_tx, output_rx) = tokio::sync::mpsc::channel(32);
+        tokio::spawn(exec::bridge(process, input, output_tx));
+        let output = futures_util::stream::unfold(output_rx, |mut output_rx| async move {
+            output_rx.recv().await.map(|output| (output, output_rx))
+        })
+        .map(|output| {
+            Ok(ExecInstanceResponse {
+                kind: Some(match output {
+                    exec::ExecOutput::Stdout(data) => exec_instance_response::Kind::Stdout(data),
+                    exec::ExecOutput::Stderr(data) => exec_instance_response::Kind::Stderr(data),
+                    exec::ExecOutput::Exit(code) => exec_instance_response::Kind::ExitCode(code),
+                }),
+            })
+        });
+        Ok(Response::new(Box::pin(output)))
+    }
+
     /// CheckFlag verifies if the provided flag is correct for the specified challenge and team.
     async fn check_flag(
         &self,
         request: tonic::Request<CheckFlagRequest>,
     ) -> Result<tonic::Response<CheckFlagResponse>, tonic::Status> {
         let request = request.into_inner();
+        // Oracles only get submissions meant for their challenge
+        let check_all = request.challenge_id.is_none();
         let challenges = if let Some(challenge_id) = request.challenge_id {
             let challenge =
                 load_challenge_from_repo(&self.repo_dir, &challenge_id, &request.actor, false)
@@ -399,12 +1177,19 @@ impl ChallengesService for ChallengeManager {
         let mut solved_challenge_id = None;
         let total_challs = challenges.len();
         for (challenge_id, chall) in challenges {
-            match chall.metadata.check_flag(&request.flag).map_err(|e| {
-                tonic::Status::internal(format!(
-                    "Failed to check flag for challenge {}: {}",
-                    challenge_id, e
-                ))
-            }) {
+            if check_all && matches!(chall.metadata.flag_validator, FlagValidator::Oracle { .. }) {
+                continue;
+            }
+            match chall
+                .metadata
+                .check_flag(&challenge_id, &request.actor, &request.flag)
+                .await
+                .map_err(|e| {
+                    tonic::Status::internal(format!(
+                        "Failed to check flag for challenge {}: {}",
+                        challenge_id, e
+                    ))
+                }) {
                 Ok(true) => {
                     solved_challenge_id = Some(challenge_id);
                     break;
@@ -531,4 +1316,150 @@ impl ChallengesService for ChallengeManager {
             file_content,
         }))
     }
+
+    async fn list_instances(
+        &self,
+        request: tonic::Request<ListInstancesRequest>,
+    ) -> Result<tonic::Response<ListInstancesResponse>, tonic::Status> {
+        let request = request.into_inner();
+        let instances = crate::instances::list_all_instances(
+            &self.kube_client,
+            request.challenge_id.as_deref(),
+        )
+        .await
+        .map_err(|e| tonic::Status::internal(format!("Failed to list instances: {}", e)))?;
+        Ok(Response::new(ListInstancesResponse {
+            instances: instances
+                .into_iter()
+                .map(|instance| InstanceSummary {
+                    challenge_id: instance.challenge_id,
+                    actor: instance.actor_id,
+                    instance_id: instance.instance_id,
+                    is_terminating: instance.is_terminating,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn get_instance_metrics(
+        &self,
+        request: tonic::Request<GetInstanceMetricsRequest>,
+    ) -> Result<tonic::Response<GetInstanceMetricsResponse>, tonic::Status> {
+        let request = request.into_inner();
+        let usage = crate::instances::metrics::instance_usage(
+            &self.kube_client,
+            request.challenge_id.as_deref(),
+        )
+        .await
+        .map_err(|e| match e {
+            kube::Error::Api(e) if e.code == 404 => {
+                tonic::Status::unavailable("metrics-server is not installed in the cluster")
+            }
+            e => tonic::Status::internal(format!("Failed to get instance metrics: {}", e)),
+        })?;
+        Ok(Response::new(GetInstanceMetricsResponse {
+            instances: usage
+                .into_iter()
+                .map(|instance| {
+                    let total = instance.total();
+                    InstanceMetrics {
+                        challenge_id: instance.instance.challenge_id,
+                        actor: instance.instance.actor_id,
+                        instance_id: instance.instance.instance_id,
+                        cpu_millicores: total.cpu_millis.max(0) as u64,
+                        memory_bytes: total.memory_bytes.max(0) as u64,
+                        pods: instance
+                            .pods
+                            .into_iter()
+                            .map(|pod| PodMetrics {
+                                pod: pod.pod,
+                                cpu_millicores: pod.usage.cpu_millis.max(0) as u64,
+                                memory_bytes: pod.usage.memory_bytes.max(0) as u64,
+                            })
+                            .collect(),
+                    }
+                })
+                .collect(),
+        }))
+    }
+
+    async fn collect_garbage(
+        &self,
+        request: tonic::Request<CollectGarbageRequest>,
+    ) -> Result<tonic::Response<CollectGarbageResponse>, tonic::Status> {
+        let request = request.into_inner();
+        // An empty list more likely means the caller failed to load them than that there are none
+        let actors = (!request.actors.is_empty())
+            .then(|| request.actors.into_iter().collect::<HashSet<_>>());
+        let report = crate::instances::gc::collect_garbage(
+            &self.kube_client,
+            &self.repo_dir,
+            actors.as_ref(),
+        )
+        .await
+        .map_err(|e| {
+            tonic::Status::internal(format!("Failed to collect orphaned instances: {}", e))
+        })?;
+        Ok(Response::new(CollectGarbageResponse {
+            missing_challenge: report.missing_challenge,
+            deleted_actor: report.deleted_actor,
+            force_finalized: report.force_finalized,
+        }))
+    }
+
+    async fn get_actor_flag(
+        &self,
+        request: tonic::Request<GetActorFlagRequest>,
+    ) -> Result<tonic::Response<GetActorFlagResponse>, tonic::Status> {
+        let request = request.into_inner();
+        let flag = self
+            .actor_flag(&request.challenge_id, &request.actor)
+            .await?;
+        Ok(Response::new(GetActorFlagResponse { flag }))
+    }
+
+    async fn get_actor_flags(
+        &self,
+        request: tonic::Request<GetActorFlagsRequest>,
+    ) -> Result<tonic::Response<GetActorFlagsResponse>, tonic::Status> {
+        let requests = request.into_inner().requests;
+        let flags = futures_util::stream::iter(&requests)
+            .map(|request| self.actor_flag(&request.challenge_id, &request.actor))
+            .buffered(ACTOR_FLAG_CONCURRENCY)
+            .collect::<Vec<_>>()
+            .await;
+        Ok(Response::new(GetActorFlagsResponse {
+            flags: flags
+                .into_iter()
+                .map(|flag| GetActorFlagResponse {
+                    flag: flag.ok().flatten(),
+                })
+                .collect(),
+        }))
+    }
+
+    async fn validate_challenge(
+        &self,
+        request: tonic::Request<ValidateChallengeRequest>,
+    ) -> Result<tonic::Response<ValidateChallengeResponse>, tonic::Status> {
+        let request = request.into_inner();
+        let issues = validate_challenge(&self.repo_dir, &request.challenge_id).await;
+        Ok(Response::new(ValidateChallengeResponse {
+            valid: issues.iter().all(|issue| issue.severity != Severity::Error),
+            issues: issues
+                .into_iter()
+                .map(|issue| ValidationIssue {
+                    severity: match issue.severity {
+                        Severity::Error => ValidationSeverity::Error,
+                        Severity::Warning => ValidationSeverity::Warning,
+                    }
+                    .into(),
+                    message: issue.message,
+                    file: None,
+                    line: None,
+                    column: None,
+                })
+                .collect(),
+        }))
+    }
 }
@@ -2,15 +2,28 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use std::path::PathBuf;
+use std::{path::PathBuf, pin::Pin, sync::Arc};
+
+use futures_util::{Stream, StreamExt};
 
 use crate::{
     grpc::api::{
-        EventConfiguration, GetBuildStatusRequest, GetBuildStatusResponse,
-        GetEventConfigurationRequest, GetSyncStatusRequest, GetSyncStatusResponse,
-        SyncChallengesRequest, SyncChallengesResponse, SyncStatus,
+        BuildStatus, ChallengeBuild, EventConfiguration, GetBuildStatusRequest,
+        GetBuildStatusResponse, GetEventConfigurationRequest, GetSyncStatusRequest,
+        GetSyncStatusResponse, ReleasedWave, SetRepoRevisionRequest, SetRepoRevisionResponse,
+        SubmoduleStatus, SyncChallengesRequest, SyncChallengesResponse, SyncStatus,
+        ValidateEventConfigRequest, ValidateEventConfigResponse, ValidationIssue,
+        ValidationSeverity, WatchReleasesRequest,
+    },
+    repo::{
+        CommitInfo, EventConfig, GitAuth, SparseCheckout,
+        builds::{BuildState, BuildStore},
+        challenges::{packages::PackageStore, validate::Severity},
+        event_config::validate::validate_event_config,
+        image_builder::ImageBuilder,
+        release_waves::{self, ReleaseScheduler},
+        revision::RevisionPin,
     },
-    repo::EventConfig,
 };
 
 use super::api::repository_service_server::RepositoryService;
@@ -18,29 +31,130 @@ pub struct RepoManager {
     pub repo_dir: PathBuf,
     pub git_url: String,
     pub git_branch: String,
+    /// Credentials for private repositories
+    pub git_auth: GitAuth,
+    /// Only these paths are checked out, if configured
+    pub sparse_checkout: Option<SparseCheckout>,
+    /// Syncs check out this revision instead of the tip of the branch, if one is pinned
+    pub revision_pin: RevisionPin,
+    pub builds: Arc<BuildStore>,
+    /// Builds images of services with a build section, if a registry is configured
+    pub image_builder: Option<Arc<ImageBuilder>>,
+    /// Attachments are packed after every sync, if a store is configured
+    pub packages: Option<Arc<PackageStore>>,
+    /// Releases the waves of the event config, which might change with every sync
+    pub release_scheduler: Arc<ReleaseScheduler>,
+    /// Held while syncing, so two syncs don't check out into the same directory at once
+    pub sync_lock: tokio::sync::Mutex<()>,
+}
+
+impl From<BuildState> for BuildStatus {
+    fn from(state: BuildState) -> Self {
+        match state {
+            BuildState::NotRequired => BuildStatus::NotRequired,
+            BuildState::Queued => BuildStatus::Queued,
+            BuildState::InProgress => BuildStatus::InProgress,
+            BuildState::Succeeded => BuildStatus::Success,
+            BuildState::Failed => BuildStatus::Failed,
+        }
+    }
+}
+
+impl From<release_waves::ReleasedWave> for ReleasedWave {
+    fn from(wave: release_waves::ReleasedWave) -> Self {
+        ReleasedWave {
+            name: wave.name,
+            release_time: wave.release_time.timestamp().max(0) as u64,
+            challenge_ids: wave.challenge_ids,
+        }
+    }
+}
+
+impl From<CommitInfo> for SyncStatus {
+    fn from(commit_info: CommitInfo) -> Self {
+        SyncStatus {
+            commit_hash: commit_info.hash,
+            commit_timestamp: commit_info.timestamp,
+            commit_author: commit_info.author,
+            commit_title: commit_info.title,
+            submodules: commit_info
+                .submodules
+                .into_iter()
+                .map(|submodule| SubmoduleStatus {
+                    path: submodule.path,
+                    commit_hash: submodule.hash,
+                })
+                .collect(),
+            pinned_revision: None,
+        }
+    }
+}
+
+impl RepoManager {
+    fn sync_status(&self, commit_info: CommitInfo) -> SyncStatus {
+        SyncStatus {
+            pinned_revision: self.revision_pin.get(),
+            ..commit_info.into()
+        }
+    }
+
+    /// Checks out the revision (or the tip of the branch) and starts building and packing its
+    /// challenges
+    async fn sync(&self, revision: Option<&str>) -> Result<CommitInfo, tonic::Status> {
+        let _sync_guard = self.sync_lock.lock().await;
+        crate::repo::sync_repo(
+            &self.repo_dir,
+            &self.git_url,
+            &self.git_branch,
+            revision,
+            &self.git_auth,
+            self.sparse_checkout.as_ref(),
+        )
+        .await
+        .map_err(|e| tonic::Status::internal(format!("Failed to sync repository: {}", e)))?;
+        crate::repo::challenges::loader::cache::invalidate(&self.repo_dir);
+        self.release_scheduler.reload();
+        let commit_info = crate::repo::get_head_commit_info(&self.repo_dir).ok_or_else(|| {
+            tonic::Status::internal("Failed to get head commit info after syncing")
+        })?;
+        self.builds.retain_commit(&commit_info.hash);
+        // Building every challenge takes a while, GetBuildStatus shows the progress
+        let builds = self.builds.clone();
+        let image_builder = self.image_builder.clone();
+        let packages = self.packages.clone();
+        let repo_dir = self.repo_dir.clone();
+        let commit = commit_info.hash.clone();
+        tokio::spawn(async move {
+            if let Some(packages) = packages
+                && let Err(e) = packages.pack_challenges(&repo_dir).await
+            {
+                tracing::error!("Failed to pack challenges of commit {}: {}", commit, e);
+            }
+            if let Err(e) = builds
+                .build_challenges(&repo_dir, &commit, image_builder.as_deref())
+                .await
+            {
+                tracing::error!("Failed to build challenges of commit {}: {}", commit, e);
+            }
+        });
+        Ok(commit_info)
+    }
 }
 
 #[tonic::async_trait]
 impl RepositoryService for RepoManager {
+    type WatchReleasesStream =
+        Pin<Box<dyn Stream<Item = Result<ReleasedWave, tonic::Status>> + Send>>;
+
     /// SyncChallenges pulls the latest changes from the remote challenge repository.
     async fn sync_challenges(
         &self,
         _request: tonic::Request<SyncChallengesRequest>,
     ) -> Result<tonic::Response<SyncChallengesResponse>, tonic::Status> {
-        crate::repo::sync_repo(&self.repo_dir, &self.git_url, &self.git_branch)
-            .await
-            .map_err(|e| tonic::Status::internal(format!("Failed to sync repository: {}", e)))?;
-        let commit_info = crate::repo::get_head_commit_info(&self.repo_dir).ok_or_else(|| {
-            tonic::Status::internal("Failed to get head commit info after syncing")
-        })?;
+        let commit_info = self.sync(self.revision_pin.get().as_deref()).await?;
         Ok(tonic::Response::new(SyncChallengesResponse {
             success: true,
-            sync_status: Some(SyncStatus {
-                commit_hash: commit_info.hash,
-                commit_timestamp: commit_info.timestamp,
-                commit_author: commit_info.author,
-                commit_title: commit_info.title,
-            }),
+            sync_status: Some(self.sync_status(commit_info)),
         }))
     }
 
@@ -49,7 +163,32 @@ impl RepositoryService for RepoManager {
         &self,
         _request: tonic::Request<GetBuildStatusRequest>,
     ) -> Result<tonic::Response<GetBuildStatusResponse>, tonic::Status> {
-        todo!()
+        let Some(commit_info) = crate::repo::get_head_commit_info(&self.repo_dir) else {
+            return Ok(tonic::Response::new(GetBuildStatusResponse::default()));
+        };
+        let challenge_builds = self
+            .builds
+            .for_commit(&commit_info.hash)
+            .into_iter()
+            .map(|(challenge_id, record)| {
+                (
+                    challenge_id,
+                    ChallengeBuild {
+                        status: BuildStatus::from(record.state).into(),
+                        commit_hash: commit_info.hash.clone(),
+                        log_excerpt: record.log_excerpt,
+                        updated_at: record.updated_at.timestamp() as u64,
+                    },
+                )
+            })
+            .collect::<std::collections::HashMap<_, _>>();
+        Ok(tonic::Response::new(GetBuildStatusResponse {
+            challenge_build_statuses: challenge_builds
+                .iter()
+                .map(|(challenge_id, build)| (challenge_id.clone(), build.status))
+                .collect(),
+            challenge_builds,
+        }))
     }
 
     /// GetEventConfiguration retrieves the event configuration from the repository.
@@ -67,15 +206,21 @@ impl RepositoryService for RepoManager {
             })?;
         Ok(tonic::Response::new(EventConfiguration {
             event_name: config.event_name,
-            front_page_md: config.front_page_md,
-            rules_md: config.rules_md,
+            front_page_md: config.front_page_md.default_text().to_string(),
+            front_page_md_translations: config.front_page_md.translations(),
+            rules_md: config.rules_md.default_text().to_string(),
+            rules_md_translations: config.rules_md.translations(),
             start_time: config.start_time.timestamp() as u64,
             end_time: config.end_time.timestamp() as u64,
             use_teams: config.use_teams,
             max_team_size: config.max_team_size,
             scoreboard_freeze_time: config.scoreboard_freeze_time.map(|t| t.timestamp() as u64),
+            scoreboard_reveal_time: config.scoreboard_reveal_time.map(|t| t.timestamp() as u64),
+            submission_cooldown: config.submission_cooldown,
             registration_start_time: config.registration_start_time.map(|t| t.timestamp() as u64),
             registration_end_time: config.registration_end_time.map(|t| t.timestamp() as u64),
+            invite_only: config.invite_only,
+            archive_after_end: config.archive_after_end,
             categories: config
                 .categories
                 .into_iter()
@@ -103,6 +248,24 @@ impl RepositoryService for RepoManager {
                     )
                 })
                 .collect(),
+            divisions: config
+                .divisions
+                .into_iter()
+                .map(|(k, v)| {
+                    (
+                        k,
+                        crate::grpc::api::CtfDivision {
+                            name: v.name,
+                            description: v.description,
+                        },
+                    )
+                })
+                .collect(),
+            name_policy: Some(crate::grpc::api::NamePolicy {
+                reserved_names: config.name_policy.reserved_names,
+                blocked_words: config.name_policy.blocked_words,
+                block_confusables: config.name_policy.block_confusables,
+            }),
         }))
     }
 
@@ -110,13 +273,86 @@ impl RepositoryService for RepoManager {
         &self,
         _request: tonic::Request<GetSyncStatusRequest>,
     ) -> Result<tonic::Response<GetSyncStatusResponse>, tonic::Status> {
-        let sync_status =
-            crate::repo::get_head_commit_info(&self.repo_dir).map(|commit_info| SyncStatus {
-                commit_hash: commit_info.hash,
-                commit_timestamp: commit_info.timestamp,
-                commit_author: commit_info.author,
-                commit_title: commit_info.title,
-            });
-        Ok(tonic::Response::new(GetSyncStatusResponse { sync_status }))
+        let sync_status = crate::repo::get_head_commit_info(&self.repo_dir)
+            .map(|commit_info| self.sync_status(commit_info));
+        Ok(tonic::Response::new(GetSyncStatusResponse {
+            sync_status,
+            branch: self.git_branch.clone(),
+        }))
+    }
+
+    /// SetRepoRevision syncs the given revision and keeps deploying it on further syncs.
+    async fn set_repo_revision(
+        &self,
+        request: tonic::Request<SetRepoRevisionRequest>,
+    ) -> Result<tonic::Response<SetRepoRevisionResponse>, tonic::Status> {
+        let request = request.into_inner();
+        let revision = request.revision.trim();
+        let (commit_info, pinned) = if request.promote_latest {
+            let commit_info = self.sync(None).await?;
+            let commit = commit_info.hash.clone();
+            (commit_info, Some(commit))
+        } else if revision.is_empty() {
+            (self.sync(None).await?, None)
+        } else {
+            (self.sync(Some(revision)).await?, Some(revision.to_string()))
+        };
+        // Only pinned once it was checked out, so a typo doesn't break further syncs
+        self.revision_pin.set(pinned).map_err(|e| {
+            tonic::Status::internal(format!("Failed to store the pinned revision: {}", e))
+        })?;
+        Ok(tonic::Response::new(SetRepoRevisionResponse {
+            sync_status: Some(self.sync_status(commit_info)),
+        }))
+    }
+
+    /// WatchReleases streams the release waves of the event config when they are released.
+    async fn watch_releases(
+        &self,
+        _request: tonic::Request<WatchReleasesRequest>,
+    ) -> Result<tonic::Response<Self::WatchReleasesStream>, tonic::Status> {
+        let (recent, receiver) = self.release_scheduler.watch().await;
+        let released = futures_util::stream::unfold(receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(wave) => return Some((wave, receiver)),
+                    // The missed waves are gone, but the client still gets the next ones
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        });
+        Ok(tonic::Response::new(Box::pin(
+            futures_util::stream::iter(recent)
+                .chain(released)
+                .map(|wave| Ok(wave.into())),
+        )))
+    }
+
+    /// ValidateEventConfig checks event.yml of the current checkout.
+    async fn validate_event_config(
+        &self,
+        _request: tonic::Request<ValidateEventConfigRequest>,
+    ) -> Result<tonic::Response<ValidateEventConfigResponse>, tonic::Status> {
+        let diagnostics = validate_event_config(&self.repo_dir).await;
+        Ok(tonic::Response::new(ValidateEventConfigResponse {
+            valid: diagnostics
+                .iter()
+                .all(|diagnostic| diagnostic.severity != Severity::Error),
+            issues: diagnostics
+                .into_iter()
+                .map(|diagnostic| ValidationIssue {
+                    severity: match diagnostic.severity {
+                        Severity::Error => ValidationSeverity::Error,
+                        Severity::Warning => ValidationSeverity::Warning,
+                    }
+                    .into(),
+                    message: diagnostic.message,
+                    file: Some(diagnostic.file),
+                    line: diagnostic.line.map(|line| line as u32),
+                    column: diagnostic.column.map(|column| column as u32),
+                })
+                .collect(),
+        }))
     }
 }
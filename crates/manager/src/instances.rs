@@ -5,9 +5,25 @@
 use k8s_openapi::api::core::v1::{Namespace, Pod};
 use kube::{Api, Client, api::ListParams};
 use rand::Rng;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use thiserror::Error;
 
+pub mod admission;
 pub mod deploy;
+pub mod diagnostics;
+pub mod exec;
+pub mod gc;
+pub mod lifetime;
+pub mod logs;
+pub mod metrics;
+pub mod pool;
+pub mod priority;
+pub mod progress;
+pub mod shared;
+
+/// Length of the actor hash at the start of instance ids
+const ACTOR_HASH_LEN: usize = 8;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum InstanceState {
@@ -25,23 +41,31 @@ pub async fn is_instance_running(
         kube_client.clone(),
         full_instance_ns(challenge_id, instance_id).as_str(),
     );
-    // Check if all pods are running, if not (or there are none), return false
+    // Check if all pods are ready, if not (or there are none), return false.
+    // Pods with a healthcheck only become ready once it succeeds.
     let lp = ListParams::default();
     let pod_list = api.list(&lp).await.expect("Failed to list pods");
     if pod_list.items.is_empty() {
         return false;
     }
     for pod in pod_list {
-        if let Some(status) = pod.status {
-            if let Some(phase) = status.phase {
-                if phase != "Running" && phase != "Succeeded" {
+        let Some(status) = pod.status else {
+            return false;
+        };
+        match status.phase.as_deref() {
+            // Pods that ran to completion never become ready again
+            Some("Succeeded") => {}
+            Some("Running") => {
+                let ready = status.conditions.is_some_and(|conditions| {
+                    conditions
+                        .iter()
+                        .any(|c| c.type_ == "Ready" && c.status == "True")
+                });
+                if !ready {
                     return false;
                 }
-            } else {
-                return false;
             }
-        } else {
-            return false;
+            _ => return false,
         }
     }
     true
@@ -58,7 +82,11 @@ pub async fn get_instances(
     let ns_list = api.list(&lp).await.expect("Failed to list namespaces");
     let mut instances = HashMap::new();
     for ns in ns_list {
-        if let Some(name) = ns.metadata.name {
+        if !is_owned_by(&ns, challenge_id, actor_id) {
+            continue;
+        }
+        if let Some(name) = &ns.metadata.name {
+            let instance_id = instance_id_from_ns(challenge_id, name);
             let state = if ns.metadata.deletion_timestamp.is_some()
                 || ns
                     .status
@@ -66,51 +94,204 @@ pub async fn get_instances(
                     .is_some_and(|s| s.phase.as_deref() == Some("Terminating"))
             {
                 InstanceState::Terminating
-            } else if is_instance_running(
-                kube_client,
-                challenge_id,
-                name.strip_prefix(format!("challenge-{}-instance-", challenge_id).as_str())
-                    .unwrap_or(&name),
-            )
-            .await
-            {
+            } else if is_instance_running(kube_client, challenge_id, instance_id).await {
                 InstanceState::Running
             } else {
                 InstanceState::Creating
             };
-            let name = name
-                .strip_prefix(format!("challenge-{}-instance-", challenge_id).as_str())
-                .unwrap_or(&name)
-                .to_string();
-            instances.insert(name, state);
+            instances.insert(instance_id.to_string(), state);
         }
     }
     instances
 }
 
-pub async fn prepare_instance(
+/// An instance of any challenge, as found in the cluster.
+#[derive(Debug, Clone)]
+pub struct InstanceSummary {
+    pub challenge_id: String,
+    pub actor_id: String,
+    pub instance_id: String,
+    pub is_terminating: bool,
+}
+
+/// Lists the instances of all challenges (or only the given one) without checking their pods.
+pub async fn list_all_instances(
+    kube_client: &Client,
+    challenge_id: Option<&str>,
+) -> Result<Vec<InstanceSummary>, kube::Error> {
+    let api: Api<Namespace> = Api::all(kube_client.clone());
+    let selector = match challenge_id {
+        Some(challenge_id) => format!("challenge_id={},actor_id", challenge_id),
+        None => "challenge_id,actor_id".to_string(),
+    };
+    let ns_list = api.list(&ListParams::default().labels(&selector)).await?;
+    let mut instances = Vec::new();
+    for ns in ns_list {
+        let (Some(name), Some(labels)) = (ns.metadata.name, ns.metadata.labels) else {
+            continue;
+        };
+        let (Some(challenge_id), Some(actor_id)) =
+            (labels.get("challenge_id"), labels.get("actor_id"))
+        else {
+            continue;
+        };
+        let is_terminating = ns.metadata.deletion_timestamp.is_some()
+            || ns
+                .status
+                .as_ref()
+                .is_some_and(|s| s.phase.as_deref() == Some("Terminating"));
+        instances.push(InstanceSummary {
+            instance_id: instance_id_from_ns(challenge_id, &name).to_string(),
+            challenge_id: challenge_id.clone(),
+            actor_id: actor_id.clone(),
+            is_terminating,
+        });
+    }
+    Ok(instances)
+}
+
+#[derive(Debug, Error)]
+pub enum PrepareInstanceError {
+    #[error("Too many pending instances")]
+    TooManyPending,
+    #[error("An instance is already running/creating")]
+    AlreadyRunning,
+    #[error("Only {0} instances may run at the same time, stop one of your other instances first")]
+    QuotaExceeded(usize),
+    #[error(transparent)]
+    Kube(#[from] kube::Error),
+}
+
+/// How many instances an actor may have across all challenges, from MAX_INSTANCES_PER_ACTOR.
+/// Unlimited if unset.
+fn max_instances_per_actor() -> Option<usize> {
+    std::env::var("MAX_INSTANCES_PER_ACTOR")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|max| *max > 0)
+}
+
+/// Instances of the actor across all challenges, not counting those being deleted
+pub async fn count_actor_instances(
     kube_client: &Client,
-    challenge_id: &str,
     actor_id: &str,
-) -> Result<String, Box<dyn std::error::Error>> {
+) -> Result<usize, kube::Error> {
     let api: Api<Namespace> = Api::all(kube_client.clone());
+    let lp = ListParams::default().labels(&format!("challenge_id,actor_id={}", actor_id));
+    Ok(api
+        .list(&lp)
+        .await?
+        .into_iter()
+        .filter(|ns| {
+            ns.metadata.deletion_timestamp.is_none()
+                && ns
+                    .status
+                    .as_ref()
+                    .is_none_or(|s| s.phase.as_deref() != Some("Terminating"))
+        })
+        .count())
+}
+
+fn random_hex(len: usize) -> String {
+    (0..len)
+        .map(|_| format!("{:x}", rand::rng().random_range(0..16)))
+        .collect()
+}
+
+/// Id of an instance which doesn't belong to an actor yet
+pub fn random_instance_id() -> String {
+    random_hex(12)
+}
+
+/// Short hash of an actor, which the ids of its instances start with. Actors are hashed as they
+/// can be longer than namespace names allow, and contain characters they don't.
+pub fn actor_hash(actor_id: &str) -> String {
+    let hash = format!("{:x}", Sha256::digest(actor_id.as_bytes()));
+    hash[..ACTOR_HASH_LEN].to_string()
+}
+
+/// Id of a new instance of the actor
+pub fn new_instance_id(actor_id: &str) -> String {
+    format!("{}-{}", actor_hash(actor_id), random_hex(6))
+}
+
+/// The id of the instance in an instance namespace
+pub fn instance_id_from_ns<'a>(challenge_id: &str, ns_name: &'a str) -> &'a str {
+    ns_name
+        .strip_prefix(format!("challenge-{}-instance-", challenge_id).as_str())
+        .unwrap_or(ns_name)
+}
+
+/// Whether an instance namespace belongs to the actor.
+///
+/// Instances started before their ids contained the hash of their actor, and pooled instances
+/// which were only assigned to the actor later, are identified by their label alone.
+pub fn is_owned_by(ns: &Namespace, challenge_id: &str, actor_id: &str) -> bool {
+    let labeled = ns
+        .metadata
+        .labels
+        .as_ref()
+        .and_then(|l| l.get("actor_id"))
+        .is_some_and(|label| label == actor_id);
+    let Some(name) = &ns.metadata.name else {
+        return false;
+    };
+    match instance_id_from_ns(challenge_id, name).split_once('-') {
+        Some((hash, _)) => labeled && hash == actor_hash(actor_id),
+        None => labeled,
+    }
+}
+
+/// Held from checking that an actor may get a new instance until its namespace is created or a
+/// pooled one claimed, so concurrent starts can't all pass the checks before any of them counts
+pub static START_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Checks that the actor may get a new instance of the challenge. Hold [`START_LOCK`] until the
+/// instance exists.
+pub async fn check_can_start(
+    kube_client: &Client,
+    challenge_id: &str,
+    actor_id: &str,
+) -> Result<(), PrepareInstanceError> {
     // Ensure we have at most 5 instances
     let instances = get_instances(kube_client, challenge_id, actor_id).await;
     if instances.len() >= 5 {
-        return Err("Too many pending instances".into());
+        return Err(PrepareInstanceError::TooManyPending);
     }
     // If we have one or more running instances, return an error
     if instances
         .values()
         .any(|state| matches!(state, InstanceState::Running | InstanceState::Creating))
     {
-        return Err("An instance is already running/creating".into());
+        return Err(PrepareInstanceError::AlreadyRunning);
     }
+    if let Some(max) = max_instances_per_actor()
+        && count_actor_instances(kube_client, actor_id).await? >= max
+    {
+        return Err(PrepareInstanceError::QuotaExceeded(max));
+    }
+    Ok(())
+}
+
+#[tracing::instrument(skip(kube_client))]
+pub async fn prepare_instance(
+    kube_client: &Client,
+    challenge_id: &str,
+    actor_id: &str,
+) -> Result<String, PrepareInstanceError> {
+    let api: Api<Namespace> = Api::all(kube_client.clone());
+    let _start = START_LOCK.lock().await;
+    check_can_start(kube_client, challenge_id, actor_id).await?;
+    let mut annotations =
+        lifetime::initial_annotations(lifetime::LifetimeConfig::from_env().as_ref())
+            .unwrap_or_default();
+    annotations.insert(
+        progress::DEPLOY_STAGE_ANNOTATION.to_string(),
+        progress::DeployStage::NamespaceCreated.as_str().to_string(),
+    );
     // This will never cause an infinite loop because we check the number of existing instances above
     loop {
-        let instance_suffix: String = (0..12)
-            .map(|_| format!("{:x}", rand::rng().random_range(0..16)))
-            .collect();
+        let instance_suffix = new_instance_id(actor_id);
         let instance_name = full_instance_ns(challenge_id, &instance_suffix);
         if api.get_opt(&instance_name).await?.is_some() {
             continue;
@@ -127,6 +308,7 @@ pub async fn prepare_instance(
                     .cloned()
                     .collect(),
                 ),
+                annotations: Some(annotations.clone()),
                 ..Default::default()
             },
             ..Default::default()
@@ -140,6 +322,7 @@ pub fn full_instance_ns(challenge_id: &str, instance_id: &str) -> String {
     format!("challenge-{}-instance-{}", challenge_id, instance_id)
 }
 
+#[tracing::instrument(skip(kube_client))]
 pub async fn delete_instance(
     kube_client: &Client,
     challenge_id: &str,
@@ -149,10 +332,64 @@ pub async fn delete_instance(
     let api: Api<Namespace> = Api::all(kube_client.clone());
     let instance_ns = full_instance_ns(challenge_id, instance_id);
     let ns = api.get(&instance_ns).await?;
-    if ns.metadata.labels.as_ref().and_then(|l| l.get("actor_id")) != Some(&actor_id.to_string()) {
+    if !is_owned_by(&ns, challenge_id, actor_id) {
         return Err("Instance does not belong to actor".into());
     }
     api.delete(&instance_ns, &kube::api::DeleteParams::default())
         .await?;
     Ok(())
 }
+
+/// Deletes all pods of an instance, so their controllers recreate them from scratch. The
+/// namespace and with it the hostnames and volumes of the instance stay the same.
+#[tracing::instrument(skip(kube_client))]
+pub async fn restart_instance(
+    kube_client: &Client,
+    challenge_id: &str,
+    actor_id: &str,
+    instance_id: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let api: Api<Namespace> = Api::all(kube_client.clone());
+    let instance_ns = full_instance_ns(challenge_id, instance_id);
+    let ns = api.get(&instance_ns).await?;
+    if !is_owned_by(&ns, challenge_id, actor_id) {
+        return Err("Instance does not belong to actor".into());
+    }
+    let pod_api: Api<Pod> = Api::namespaced(kube_client.clone(), &instance_ns);
+    pod_api
+        .delete_collection(&kube::api::DeleteParams::default(), &ListParams::default())
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ns(name: &str, actor_id: &str) -> Namespace {
+        Namespace {
+            metadata: kube::api::ObjectMeta {
+                name: Some(name.to_string()),
+                labels: Some([("actor_id".to_string(), actor_id.to_string())].into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_instance_ownership() {
+        let instance_id = new_instance_id("team-foo");
+        assert!(instance_id.starts_with(&actor_hash("team-foo")));
+        let name = full_instance_ns("web", &instance_id);
+        assert_eq!(instance_id_from_ns("web", &name), instance_id);
+        assert!(is_owned_by(&ns(&name, "team-foo"), "web", "team-foo"));
+        // The label alone isn't enough once the id contains the actor
+        assert!(!is_owned_by(&ns(&name, "team-bar"), "web", "team-bar"));
+        assert!(!is_owned_by(&ns(&name, "team-foo"), "web", "team-bar"));
+
+        let legacy = full_instance_ns("web", &random_instance_id());
+        assert!(is_owned_by(&ns(&legacy, "team-foo"), "web", "team-foo"));
+        assert!(!is_owned_by(&ns(&legacy, "team-foo"), "web", "team-bar"));
+    }
+}
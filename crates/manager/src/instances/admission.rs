@@ -0,0 +1,274 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Queueing instance starts while the cluster doesn't have the resources to run them.
+//!
+//! Enabled by setting ADMISSION_CONTROL=true. Starts are queued in order and started once the
+//! resources the pods of the instance request are free on the schedulable nodes. The queue is
+//! only kept in memory, so it is lost if the manager restarts.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Mutex,
+};
+
+use k8s_openapi::{
+    api::core::v1::{Node, Pod},
+    apimachinery::pkg::api::resource::Quantity,
+};
+use kube::{Api, Client, api::ListParams};
+
+use crate::repo::challenges::{compose::service::AsDeployment, loader::Challenge, vm::HasVms};
+
+pub fn admission_control_enabled() -> bool {
+    std::env::var("ADMISSION_CONTROL").is_ok_and(|v| v == "true" || v == "1")
+}
+
+/// Parses a Kubernetes quantity like "500m", "2" or "1Gi" into its base unit, cores or bytes
+pub fn parse_quantity(quantity: &str) -> Option<f64> {
+    let quantity = quantity.trim();
+    let split = quantity
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(quantity.len());
+    let (number, suffix) = quantity.split_at(split);
+    // Exponents like 1e3 are part of the number
+    if suffix.starts_with(['e', 'E']) && suffix[1..].parse::<i32>().is_ok() {
+        return quantity.parse().ok();
+    }
+    let factor = match suffix {
+        "" => 1.0,
+        "n" => 1e-9,
+        "u" => 1e-6,
+        "m" => 1e-3,
+        "k" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        "T" => 1e12,
+        "P" => 1e15,
+        "E" => 1e18,
+        "Ki" => 1024.0,
+        "Mi" => 1024.0_f64.powi(2),
+        "Gi" => 1024.0_f64.powi(3),
+        "Ti" => 1024.0_f64.powi(4),
+        "Pi" => 1024.0_f64.powi(5),
+        "Ei" => 1024.0_f64.powi(6),
+        _ => return None,
+    };
+    number.parse::<f64>().ok().map(|n| n * factor)
+}
+
+/// CPU and memory, as requested by pods or free on nodes
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Resources {
+    pub cpu_millis: i64,
+    pub memory_bytes: i64,
+}
+
+impl Resources {
+    pub fn from_map(map: &BTreeMap<String, Quantity>) -> Self {
+        let get = |name: &str| {
+            map.get(name)
+                .and_then(|q| parse_quantity(&q.0))
+                .unwrap_or_default()
+        };
+        Resources {
+            cpu_millis: (get("cpu") * 1000.0).ceil() as i64,
+            memory_bytes: get("memory").ceil() as i64,
+        }
+    }
+
+    fn add(&mut self, other: Resources, times: i64) {
+        self.cpu_millis += other.cpu_millis * times;
+        self.memory_bytes += other.memory_bytes * times;
+    }
+
+    /// Takes the requests of an instance that was just started off the free resources
+    pub fn reserve(&mut self, requests: Resources) {
+        self.add(requests, -1);
+    }
+
+    pub fn fits_in(&self, free: &Resources) -> bool {
+        self.cpu_millis <= free.cpu_millis && self.memory_bytes <= free.memory_bytes
+    }
+}
+
+/// Resources the pods and virtual machines of a challenge request
+pub fn challenge_requests(challenge: &Challenge) -> Resources {
+    let mut requests = Resources::default();
+    for svc in challenge.compose.services.values() {
+        requests.add(
+            Resources::from_map(&svc.pod_resource_requests()),
+            svc.replicas() as i64,
+        );
+    }
+    for vm in challenge.compose.get_vms().values() {
+        requests.add(
+            Resources {
+                cpu_millis: vm.cpu_cores as i64 * 1000,
+                memory_bytes: parse_quantity(&vm.memory).unwrap_or_default().ceil() as i64,
+            },
+            1,
+        );
+    }
+    requests
+}
+
+/// Resources of the pod spec, limits count as requests where no request is set
+fn pod_requests(pod: &Pod) -> Resources {
+    let mut requests = Resources::default();
+    let Some(spec) = &pod.spec else {
+        return requests;
+    };
+    for container in &spec.containers {
+        let Some(resources) = &container.resources else {
+            continue;
+        };
+        let mut merged = resources.limits.clone().unwrap_or_default();
+        merged.extend(resources.requests.clone().unwrap_or_default());
+        requests.add(Resources::from_map(&merged), 1);
+    }
+    requests
+}
+
+/// Resources of the nodes pods can be scheduled on
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Capacity {
+    /// Allocatable on the nodes, instances requesting more can never run
+    pub total: Resources,
+    /// Left after subtracting the requests of all pods which haven't finished yet
+    pub free: Resources,
+}
+
+/// Total and free resources of the cluster
+pub async fn cluster_capacity(kube_client: &Client) -> Result<Capacity, kube::Error> {
+    let nodes: Api<Node> = Api::all(kube_client.clone());
+    let mut total = Resources::default();
+    for node in nodes.list(&ListParams::default()).await? {
+        let schedulable = !node
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.unschedulable)
+            .unwrap_or(false);
+        let ready = node
+            .status
+            .as_ref()
+            .and_then(|status| status.conditions.as_ref())
+            .is_some_and(|conditions| {
+                conditions
+                    .iter()
+                    .any(|c| c.type_ == "Ready" && c.status == "True")
+            });
+        if let (true, true, Some(allocatable)) = (
+            schedulable,
+            ready,
+            node.status.as_ref().and_then(|s| s.allocatable.as_ref()),
+        ) {
+            total.add(Resources::from_map(allocatable), 1);
+        }
+    }
+    let mut free = total;
+    let pods: Api<Pod> = Api::all(kube_client.clone());
+    let lp = ListParams::default().fields("status.phase!=Succeeded,status.phase!=Failed");
+    for pod in pods.list(&lp).await? {
+        free.add(pod_requests(&pod), -1);
+    }
+    Ok(Capacity { total, free })
+}
+
+/// An instance start waiting for resources
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedStart {
+    pub challenge_id: String,
+    pub actor: String,
+    pub require_release: bool,
+}
+
+/// Instance starts in the order they were requested
+#[derive(Default)]
+pub struct AdmissionQueue {
+    queue: Mutex<VecDeque<QueuedStart>>,
+}
+
+impl AdmissionQueue {
+    pub fn is_empty(&self) -> bool {
+        self.queue
+            .lock()
+            .expect("Admission queue lock poisoned")
+            .is_empty()
+    }
+
+    /// Position of the actor's start of a challenge, starting at 1 for the next one
+    pub fn position(&self, challenge_id: &str, actor: &str) -> Option<usize> {
+        self.queue
+            .lock()
+            .expect("Admission queue lock poisoned")
+            .iter()
+            .position(|start| start.challenge_id == challenge_id && start.actor == actor)
+            .map(|i| i + 1)
+    }
+
+    /// Queues a start unless it is queued already, returning its position
+    pub fn enqueue(&self, start: QueuedStart) -> usize {
+        let mut queue = self.queue.lock().expect("Admission queue lock poisoned");
+        if let Some(i) = queue
+            .iter()
+            .position(|s| s.challenge_id == start.challenge_id && s.actor == start.actor)
+        {
+            return i + 1;
+        }
+        queue.push_back(start);
+        queue.len()
+    }
+
+    pub fn front(&self) -> Option<QueuedStart> {
+        self.queue
+            .lock()
+            .expect("Admission queue lock poisoned")
+            .front()
+            .cloned()
+    }
+
+    /// Removes the actor's start of a challenge, returning whether it was queued
+    pub fn remove(&self, challenge_id: &str, actor: &str) -> bool {
+        let mut queue = self.queue.lock().expect("Admission queue lock poisoned");
+        let len = queue.len();
+        queue.retain(|start| start.challenge_id != challenge_id || start.actor != actor);
+        queue.len() != len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quantity() {
+        assert_eq!(parse_quantity("500m"), Some(0.5));
+        assert_eq!(parse_quantity("2"), Some(2.0));
+        assert_eq!(parse_quantity("1Gi"), Some(1073741824.0));
+        assert_eq!(parse_quantity("128M"), Some(128e6));
+        assert_eq!(parse_quantity("1e3"), Some(1000.0));
+        assert_eq!(parse_quantity("1x"), None);
+    }
+
+    #[test]
+    fn test_queue_positions() {
+        let queue = AdmissionQueue::default();
+        let start = |challenge_id: &str, actor: &str| QueuedStart {
+            challenge_id: challenge_id.to_string(),
+            actor: actor.to_string(),
+            require_release: true,
+        };
+        assert_eq!(queue.enqueue(start("web", "team-a")), 1);
+        assert_eq!(queue.enqueue(start("web", "team-b")), 2);
+        assert_eq!(queue.enqueue(start("pwn", "team-a")), 3);
+        // Starting again doesn't lose the place in the queue
+        assert_eq!(queue.enqueue(start("web", "team-b")), 2);
+
+        assert!(queue.remove("web", "team-a"));
+        assert_eq!(queue.position("pwn", "team-a"), Some(2));
+        assert_eq!(queue.front(), Some(start("web", "team-b")));
+        assert!(!queue.remove("web", "team-a"));
+    }
+}
@@ -2,34 +2,66 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use std::path::Path;
+use std::{collections::BTreeSet, path::Path};
 
 use compose_spec::Resource;
-use k8s_openapi::api::{apps::v1::Deployment, core::v1::PersistentVolumeClaim};
+use k8s_openapi::api::{
+    apps::v1::{Deployment, StatefulSet},
+    core::v1::{LimitRange, PersistentVolumeClaim, ResourceQuota, Secret, Service},
+    policy::v1::PodDisruptionBudget,
+};
 use kube::{Api, Client};
 
+use crate::instances::priority::InstancePriority;
+
 use crate::repo::challenges::{
     compose::{
         service::{
             AsDeployment, AsExternalService, AsIngress, AsService, AsSshGateway,
             ComposeServiceError, HasLabels,
         },
-        volume::{AsPvc, default_size_pvc, get_pvc},
+        volume::{AsPvc, DATA_PVC_NAME, default_size_pvc, get_pvc, named_volumes},
     },
+    flag::{NO_FLAG_ERROR, flag_secret, inject_into_user_data, uses_flag},
     loader::Challenge,
-    vm::HasVms,
+    quota::{QuotaConfig, quota_objects},
+    vm::{Disk, HasVms},
 };
 
-pub async fn deploy_challenge(
-    kube_client: &Client,
-    challenge_ns: &str,
+/// Everything created in the namespace of an instance
+pub struct InstanceObjects {
+    pub quota: Option<(ResourceQuota, Option<LimitRange>)>,
+    pub flag_secret: Option<Secret>,
+    pub deployments: Vec<Deployment>,
+    pub stateful_sets: Vec<StatefulSet>,
+    pub pdbs: Vec<PodDisruptionBudget>,
+    pub svcs: Vec<Service>,
+    pub ingressroutes: Vec<k8s_crds_traefik::IngressRoute>,
+    pub ingressroutestcp: Vec<k8s_crds_traefik::IngressRouteTCP>,
+    pub pvcs: Vec<PersistentVolumeClaim>,
+    pub sshgateways: Vec<crate::ssh::SSHGateway>,
+    pub kube_virt_vms: Vec<k8s_crds_kube_virt::VirtualMachine>,
+    pub policies: Vec<k8s_crds_cilium::CiliumNetworkPolicy>,
+}
+
+/// Converts a challenge to the objects of an instance without creating them
+pub fn instance_objects(
     challenge: Challenge,
+    challenge_ns: &str,
     exposed_domain: &str,
     working_dir: &Path,
     actor: &str,
     instance_id: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
+    flag: Option<&str>,
+    priority: InstancePriority,
+) -> Result<InstanceObjects, Box<dyn std::error::Error>> {
+    if flag.is_none() && challenge.compose.services.values().any(uses_flag) {
+        return Err(ComposeServiceError::Other(NO_FLAG_ERROR.to_string()).into());
+    }
+
     let policies = crate::repo::challenges::compose::service::networking::get_policies(&challenge);
+    let quota = quota_objects(&challenge, QuotaConfig::from_env());
+    let priority_class = priority.class_name();
 
     let requires_data_pvc = challenge
         .compose
@@ -37,17 +69,68 @@ pub async fn deploy_challenge(
         .values()
         .any(|svc| svc.requires_data_pvc());
 
+    // Named volumes used by services without being declared get a claim of the default size
+    let undeclared_volumes = challenge
+        .compose
+        .services
+        .values()
+        .flat_map(named_volumes)
+        .filter(|source| !challenge.compose.volumes.contains_key(source))
+        .collect::<BTreeSet<_>>();
+
     let mut deployments = Vec::new();
+    let mut stateful_sets = Vec::new();
+    let mut pdbs = Vec::new();
     let mut svcs = Vec::new();
     let mut ingressroutes = Vec::new();
     let mut ingressroutestcp = Vec::new();
     let mut sshgateways = Vec::new();
+    // Volumes of stateful services are created by their StatefulSet, one per replica
+    let mut stateful_volumes = BTreeSet::new();
+    let mut shared_volumes = BTreeSet::new();
 
     let vms = challenge.compose.get_vms();
 
     for (svc_id, svc) in challenge.compose.services {
         let labels = svc.get_labels(&svc_id.to_string());
-        deployments.push(svc.as_deployment(svc_id.to_string(), working_dir));
+        if svc.is_stateful() {
+            let volume_claim_templates = named_volumes(&svc)
+                .into_iter()
+                .map(|name| {
+                    let template = match challenge.compose.volumes.get(&name) {
+                        Some(Some(Resource::External { .. })) => {
+                            return Err(ComposeServiceError::ExternalVolume);
+                        }
+                        Some(Some(Resource::Compose(volume))) => volume.as_pvc(name.to_string()),
+                        _ => default_size_pvc(name.to_string()),
+                    };
+                    stateful_volumes.insert(name);
+                    Ok(template)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut stateful_set =
+                svc.as_stateful_set(svc_id.to_string(), working_dir, volume_claim_templates)?;
+            if let Some(pod_spec) = stateful_set
+                .spec
+                .as_mut()
+                .and_then(|spec| spec.template.spec.as_mut())
+            {
+                pod_spec.priority_class_name = priority_class.clone();
+            }
+            stateful_sets.push(stateful_set);
+        } else {
+            shared_volumes.extend(named_volumes(&svc));
+            let mut deployment = svc.as_deployment(svc_id.to_string(), working_dir)?;
+            if let Some(pod_spec) = deployment
+                .spec
+                .as_mut()
+                .and_then(|spec| spec.template.spec.as_mut())
+            {
+                pod_spec.priority_class_name = priority_class.clone();
+            }
+            deployments.push(deployment);
+        }
+        pdbs.extend(svc.as_pdb(svc_id.to_string()));
         svcs.push(svc.as_internal_svc(svc_id.to_string()));
         if let Some(external_svc) = svc.as_proxied_svc(svc_id.to_string(), Some(labels.clone()))? {
             svcs.push(external_svc);
@@ -65,11 +148,33 @@ pub async fn deploy_challenge(
         sshgateways.extend(svc.as_ssh_gateways(svc_id.to_string(), Some(ssh_password))?);
     }
 
+    if let Some(volume) = stateful_volumes.intersection(&shared_volumes).next() {
+        return Err(ComposeServiceError::Other(format!(
+            "Volume {} is used by a stateful service and can't be shared with other services",
+            volume
+        ))
+        .into());
+    }
+
     let mut kube_virt_vms: Vec<k8s_crds_kube_virt::VirtualMachine> = Vec::new();
 
-    for (vm_id, vm) in vms {
+    for (vm_id, mut vm) in vms {
+        for disk in &mut vm.disks {
+            if let Disk::CloudInit {
+                cloud_init_user_data_base64,
+            } = disk
+            {
+                *cloud_init_user_data_base64 =
+                    inject_into_user_data(cloud_init_user_data_base64, flag)
+                        .map_err(ComposeServiceError::Other)?;
+            }
+        }
         let labels = vm.get_labels(&vm_id.to_string());
-        kube_virt_vms.push(vm.as_kube_virt(vm_id.to_string()));
+        let mut kube_virt_vm = vm.as_kube_virt(vm_id.to_string());
+        if let Some(vm_spec) = kube_virt_vm.spec.template.spec.as_mut() {
+            vm_spec.priority_class_name = priority_class.clone();
+        }
+        kube_virt_vms.push(kube_virt_vm);
         svcs.push(vm.as_internal_svc(vm_id.to_string()));
         if let Some(external_svc) = vm.as_proxied_svc(vm_id.to_string(), Some(labels.clone()))? {
             svcs.push(external_svc);
@@ -91,6 +196,7 @@ pub async fn deploy_challenge(
         .compose
         .volumes
         .into_iter()
+        .filter(|(vol_id, _)| !stateful_volumes.contains(vol_id))
         .map(|(vol_id, vol)| match vol {
             Some(Resource::External { .. }) => Err(()),
             Some(Resource::Compose(volume)) => Ok(volume.as_pvc(vol_id.to_string())),
@@ -99,26 +205,114 @@ pub async fn deploy_challenge(
         .collect::<Result<Vec<_>, _>>()
         .map_err(|_| ComposeServiceError::ExternalVolume)?;
 
+    pvcs.extend(
+        undeclared_volumes
+            .difference(&stateful_volumes)
+            .map(|name| default_size_pvc(name.to_string())),
+    );
+
     if requires_data_pvc {
-        if let Some(data_pvc_size) = &challenge.metadata.data_pvc_size {
-            pvcs.push(get_pvc(
-                "plfanzen_internal_ctf_data".to_string(),
-                data_pvc_size.to_string(),
-            ));
-        } else {
-            pvcs.push(default_size_pvc("plfanzen_internal_ctf_data".to_string()));
+        pvcs.push(get_pvc(
+            DATA_PVC_NAME.to_string(),
+            challenge
+                .metadata
+                .data_pvc_size
+                .clone()
+                .unwrap_or_else(|| "1Gi".to_string()),
+            challenge.metadata.data_pvc_storage_class.clone(),
+        ));
+    }
+
+    Ok(InstanceObjects {
+        quota,
+        flag_secret: flag.map(flag_secret),
+        deployments,
+        stateful_sets,
+        pdbs,
+        svcs,
+        ingressroutes,
+        ingressroutestcp,
+        pvcs,
+        sshgateways,
+        kube_virt_vms,
+        policies,
+    })
+}
+
+#[tracing::instrument(skip(kube_client, challenge, working_dir, flag))]
+pub async fn deploy_challenge(
+    kube_client: &Client,
+    challenge_ns: &str,
+    challenge: Challenge,
+    exposed_domain: &str,
+    working_dir: &Path,
+    actor: &str,
+    instance_id: &str,
+    flag: Option<&str>,
+    priority: InstancePriority,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let InstanceObjects {
+        quota,
+        flag_secret,
+        deployments,
+        stateful_sets,
+        pdbs,
+        svcs,
+        ingressroutes,
+        ingressroutestcp,
+        pvcs,
+        sshgateways,
+        kube_virt_vms,
+        policies,
+    } = instance_objects(
+        challenge,
+        challenge_ns,
+        exposed_domain,
+        working_dir,
+        actor,
+        instance_id,
+        flag,
+        priority,
+    )?;
+
+    // Created before any pods, which are only checked against them when they are created
+    if let Some((quota, limit_range)) = quota {
+        let quota_api: Api<ResourceQuota> = Api::namespaced(kube_client.clone(), challenge_ns);
+        quota_api.create(&Default::default(), &quota).await?;
+        if let Some(limit_range) = limit_range {
+            let limit_range_api: Api<LimitRange> =
+                Api::namespaced(kube_client.clone(), challenge_ns);
+            limit_range_api
+                .create(&Default::default(), &limit_range)
+                .await?;
         }
     }
 
+    // Created first, pods referencing it can't start without it
+    if let Some(flag_secret) = flag_secret {
+        let secret_api: Api<Secret> = Api::namespaced(kube_client.clone(), challenge_ns);
+        secret_api.create(&Default::default(), &flag_secret).await?;
+    }
+
     let deployment_api: Api<Deployment> = Api::namespaced(kube_client.clone(), challenge_ns);
     for deployment in deployments {
-        let deployment = deployment?;
         deployment_api
             .create(&Default::default(), &deployment)
             .await?;
     }
-    let service_api: Api<k8s_openapi::api::core::v1::Service> =
+    let stateful_set_api: Api<StatefulSet> =
         Api::namespaced(deployment_api.into_client(), challenge_ns);
+    for stateful_set in stateful_sets {
+        stateful_set_api
+            .create(&Default::default(), &stateful_set)
+            .await?;
+    }
+    let pdb_api: Api<PodDisruptionBudget> =
+        Api::namespaced(stateful_set_api.into_client(), challenge_ns);
+    for pdb in pdbs {
+        pdb_api.create(&Default::default(), &pdb).await?;
+    }
+    let service_api: Api<Service> = Api::namespaced(pdb_api.into_client(), challenge_ns);
     for service in svcs {
         service_api.create(&Default::default(), &service).await?;
     }
@@ -0,0 +1,266 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Figuring out why an instance doesn't become ready, from the state of its pods and the events
+//! in its namespace.
+//!
+//! The details are meant for organizers, as they contain image and pod names. Players only get a
+//! hint derived from the reason.
+
+use k8s_openapi::api::core::v1::{Event, Pod};
+use kube::{Api, Client, api::ListParams};
+
+use crate::instances::full_instance_ns;
+
+/// Waiting reasons of containers which won't resolve by waiting longer
+const FAILED_WAITING_REASONS: &[&str] = &[
+    "ImagePullBackOff",
+    "ErrImagePull",
+    "InvalidImageName",
+    "CrashLoopBackOff",
+    "CreateContainerConfigError",
+    "CreateContainerError",
+    "RunContainerError",
+];
+/// How many of the latest warning events are included
+const MAX_EVENTS: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstanceFailure {
+    /// Machine readable cause, like `ImagePullBackOff`, `OOMKilled` or `Unschedulable`
+    pub reason: String,
+    pub message: String,
+    pub pod: String,
+    pub container: Option<String>,
+    /// The latest warning events of the namespace, oldest first
+    pub events: Vec<String>,
+}
+
+impl InstanceFailure {
+    /// An explanation which is safe to show to players
+    pub fn hint(&self) -> &'static str {
+        match self.reason.as_str() {
+            "ImagePullBackOff" | "ErrImagePull" | "InvalidImageName" => {
+                "The images of this challenge could not be downloaded, please contact the organizers"
+            }
+            "OOMKilled" => {
+                "A service of this challenge ran out of memory, try restarting the instance"
+            }
+            "CrashLoopBackOff" | "RunContainerError" | "Error" => {
+                "A service of this challenge keeps crashing, try restarting the instance or contact the organizers"
+            }
+            "Unschedulable" => {
+                "The cluster has no room for this instance right now, it starts once resources are free"
+            }
+            _ => "This instance failed to start, please contact the organizers",
+        }
+    }
+}
+
+/// Why a pod isn't running, if it failed in a way that waiting won't fix
+fn pod_failure(pod: &Pod) -> Option<InstanceFailure> {
+    let pod_name = pod.metadata.name.clone().unwrap_or_default();
+    let status = pod.status.as_ref()?;
+    let failure = |reason: &str, message: Option<&String>, container: Option<&str>| {
+        Some(InstanceFailure {
+            reason: reason.to_string(),
+            message: message.cloned().unwrap_or_default(),
+            pod: pod_name.clone(),
+            container: container.map(str::to_string),
+            events: Vec::new(),
+        })
+    };
+
+    if status.phase.as_deref() == Some("Failed") {
+        return failure(
+            status.reason.as_deref().unwrap_or("Failed"),
+            status.message.as_ref(),
+            None,
+        );
+    }
+    let unschedulable = status.conditions.iter().flatten().find(|c| {
+        c.type_ == "PodScheduled"
+            && c.status == "False"
+            && c.reason.as_deref() == Some("Unschedulable")
+    });
+    if let Some(condition) = unschedulable {
+        return failure("Unschedulable", condition.message.as_ref(), None);
+    }
+    let containers = status
+        .init_container_statuses
+        .iter()
+        .flatten()
+        .chain(status.container_statuses.iter().flatten());
+    for container in containers {
+        let Some(waiting) = container.state.as_ref().and_then(|s| s.waiting.as_ref()) else {
+            continue;
+        };
+        let Some(reason) = waiting
+            .reason
+            .as_deref()
+            .filter(|reason| FAILED_WAITING_REASONS.contains(reason))
+        else {
+            continue;
+        };
+        // Crash loops caused by the memory limit are the most common, and the easiest to fix
+        let oom_killed = container
+            .last_state
+            .as_ref()
+            .and_then(|s| s.terminated.as_ref())
+            .filter(|terminated| terminated.reason.as_deref() == Some("OOMKilled"));
+        if reason == "CrashLoopBackOff"
+            && let Some(terminated) = oom_killed
+        {
+            return failure(
+                "OOMKilled",
+                terminated.message.as_ref(),
+                Some(&container.name),
+            );
+        }
+        return failure(reason, waiting.message.as_ref(), Some(&container.name));
+    }
+    None
+}
+
+/// The latest warning events in a namespace, formatted for humans
+async fn warning_events(api: &Api<Event>) -> Result<Vec<String>, kube::Error> {
+    let mut events = api
+        .list(&ListParams::default().fields("type=Warning"))
+        .await?
+        .items;
+    events.sort_by_key(|event| {
+        event
+            .last_timestamp
+            .as_ref()
+            .map(|t| t.0)
+            .or_else(|| event.event_time.as_ref().map(|t| t.0))
+    });
+    let skip = events.len().saturating_sub(MAX_EVENTS);
+    Ok(events
+        .into_iter()
+        .skip(skip)
+        .map(|event| {
+            format!(
+                "{} {}: {}",
+                event.involved_object.name.unwrap_or_default(),
+                event.reason.unwrap_or_default(),
+                event.message.unwrap_or_default()
+            )
+        })
+        .collect())
+}
+
+/// Why an instance that isn't ready failed, None if it looks like it's still starting
+#[tracing::instrument(skip(kube_client))]
+pub async fn diagnose_instance(
+    kube_client: &Client,
+    challenge_id: &str,
+    instance_id: &str,
+) -> Result<Option<InstanceFailure>, kube::Error> {
+    let ns = full_instance_ns(challenge_id, instance_id);
+    let pods: Api<Pod> = Api::namespaced(kube_client.clone(), &ns);
+    let Some(mut failure) = pods
+        .list(&ListParams::default())
+        .await?
+        .iter()
+        .find_map(pod_failure)
+    else {
+        return Ok(None);
+    };
+    failure.events = warning_events(&Api::namespaced(kube_client.clone(), &ns)).await?;
+    Ok(Some(failure))
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::api::core::v1::{
+        ContainerState, ContainerStateTerminated, ContainerStateWaiting, ContainerStatus,
+        PodCondition, PodStatus,
+    };
+
+    use super::*;
+
+    fn pod(status: PodStatus) -> Pod {
+        Pod {
+            metadata: kube::api::ObjectMeta {
+                name: Some("web-0".to_string()),
+                ..Default::default()
+            },
+            status: Some(status),
+            ..Default::default()
+        }
+    }
+
+    fn waiting_container(reason: &str, last_terminated: Option<&str>) -> ContainerStatus {
+        ContainerStatus {
+            name: "web".to_string(),
+            state: Some(ContainerState {
+                waiting: Some(ContainerStateWaiting {
+                    reason: Some(reason.to_string()),
+                    message: Some("details".to_string()),
+                }),
+                ..Default::default()
+            }),
+            last_state: last_terminated.map(|reason| ContainerState {
+                terminated: Some(ContainerStateTerminated {
+                    reason: Some(reason.to_string()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_starting_pod_is_not_a_failure() {
+        let status = PodStatus {
+            phase: Some("Pending".to_string()),
+            container_statuses: Some(vec![waiting_container("ContainerCreating", None)]),
+            ..Default::default()
+        };
+        assert_eq!(pod_failure(&pod(status)), None);
+    }
+
+    #[test]
+    fn test_waiting_reasons() {
+        let status = PodStatus {
+            phase: Some("Pending".to_string()),
+            container_statuses: Some(vec![waiting_container("ImagePullBackOff", None)]),
+            ..Default::default()
+        };
+        let failure = pod_failure(&pod(status)).unwrap();
+        assert_eq!(failure.reason, "ImagePullBackOff");
+        assert_eq!(failure.container.as_deref(), Some("web"));
+        assert_eq!(failure.pod, "web-0");
+
+        let status = PodStatus {
+            phase: Some("Running".to_string()),
+            container_statuses: Some(vec![waiting_container(
+                "CrashLoopBackOff",
+                Some("OOMKilled"),
+            )]),
+            ..Default::default()
+        };
+        assert_eq!(pod_failure(&pod(status)).unwrap().reason, "OOMKilled");
+    }
+
+    #[test]
+    fn test_unschedulable() {
+        let status = PodStatus {
+            phase: Some("Pending".to_string()),
+            conditions: Some(vec![PodCondition {
+                type_: "PodScheduled".to_string(),
+                status: "False".to_string(),
+                reason: Some("Unschedulable".to_string()),
+                message: Some("0/3 nodes are available: 3 Insufficient memory.".to_string()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        let failure = pod_failure(&pod(status)).unwrap();
+        assert_eq!(failure.reason, "Unschedulable");
+        assert!(!failure.hint().contains("nodes"));
+    }
+}
@@ -0,0 +1,220 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Running commands in the containers of an instance, so organizers can inspect instances
+//! without access to the cluster.
+
+use futures_util::{Stream, StreamExt};
+use k8s_openapi::{api::core::v1::Pod, apimachinery::pkg::apis::meta::v1::Status};
+use kube::{
+    Api, Client,
+    api::{AttachParams, AttachedProcess, ListParams, TerminalSize},
+};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    sync::mpsc,
+};
+
+use crate::instances::full_instance_ns;
+
+#[derive(Debug, Error)]
+pub enum ExecError {
+    #[error("No running pod found")]
+    NoPod,
+    #[error(transparent)]
+    Kube(#[from] kube::Error),
+}
+
+pub enum ExecInput {
+    Stdin(Vec<u8>),
+    Resize { width: u16, height: u16 },
+}
+
+pub enum ExecOutput {
+    Stdout(Vec<u8>),
+    Stderr(Vec<u8>),
+    Exit(i32),
+}
+
+/// Exit code of a finished command, from the status the API server sends once it exits
+pub fn exit_code(status: &Status) -> Option<i32> {
+    if status.status.as_deref() == Some("Success") {
+        return Some(0);
+    }
+    status
+        .details
+        .as_ref()?
+        .causes
+        .as_ref()?
+        .iter()
+        .find(|cause| cause.reason.as_deref() == Some("ExitCode"))?
+        .message
+        .as_ref()?
+        .parse()
+        .ok()
+}
+
+/// Starts a command in the first running pod of an instance (or of one of its compose services),
+/// in the given container or the default container of the pod.
+///
+/// With `tty`, a terminal is allocated and stderr is written to stdout.
+#[tracing::instrument(skip(kube_client))]
+pub async fn exec_in_instance(
+    kube_client: &Client,
+    challenge_id: &str,
+    instance_id: &str,
+    service: Option<&str>,
+    container: Option<&str>,
+    command: Vec<String>,
+    tty: bool,
+) -> Result<AttachedProcess, ExecError> {
+    let api: Api<Pod> = Api::namespaced(
+        kube_client.clone(),
+        &full_instance_ns(challenge_id, instance_id),
+    );
+    let lp = match service {
+        Some(service) => ListParams::default().labels(&format!("compose-service-id={}", service)),
+        None => ListParams::default(),
+    }
+    .fields("status.phase=Running");
+    let pod_name = api
+        .list(&lp)
+        .await?
+        .into_iter()
+        .find_map(|pod| pod.metadata.name)
+        .ok_or(ExecError::NoPod)?;
+    let mut ap = AttachParams::default()
+        .stdin(true)
+        .stdout(true)
+        .stderr(!tty)
+        .tty(tty);
+    if let Some(container) = container {
+        ap = ap.container(container);
+    }
+    tracing::info!("Running {:?} in pod {}", command, pod_name);
+    Ok(api.exec(&pod_name, command, &ap).await?)
+}
+
+async fn forward_output(
+    mut reader: impl AsyncRead + Unpin,
+    output: &mpsc::Sender<ExecOutput>,
+    wrap: fn(Vec<u8>) -> ExecOutput,
+) {
+    let mut buf = vec![0; 8192];
+    loop {
+        match reader.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                if output.send(wrap(buf[..n].to_vec())).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Passes input to the process and its output to `output` until it exits, ending with its exit
+/// code. The process is killed if the receiver of the output goes away.
+pub async fn bridge(
+    mut process: AttachedProcess,
+    input: impl Stream<Item = ExecInput> + Send + 'static,
+    output: mpsc::Sender<ExecOutput>,
+) {
+    let status = process.take_status();
+    let mut stdin = process.stdin();
+    let mut terminal_size = process.terminal_size();
+    let input_task = tokio::spawn(async move {
+        let mut input = Box::pin(input);
+        while let Some(input) = input.next().await {
+            match input {
+                ExecInput::Stdin(data) => {
+                    if let Some(stdin) = stdin.as_mut()
+                        && stdin.write_all(&data).await.is_err()
+                    {
+                        break;
+                    }
+                }
+                ExecInput::Resize { width, height } => {
+                    if let Some(terminal_size) = terminal_size.as_mut() {
+                        let _ = terminal_size.try_send(TerminalSize { width, height });
+                    }
+                }
+            }
+        }
+        // Lets commands reading stdin finish once the client is done
+        if let Some(mut stdin) = stdin {
+            let _ = stdin.shutdown().await;
+        }
+    });
+
+    let stdout = process.stdout();
+    let stderr = process.stderr();
+    let forward = async {
+        tokio::join!(
+            async {
+                if let Some(stdout) = stdout {
+                    forward_output(stdout, &output, ExecOutput::Stdout).await;
+                }
+            },
+            async {
+                if let Some(stderr) = stderr {
+                    forward_output(stderr, &output, ExecOutput::Stderr).await;
+                }
+            },
+        )
+    };
+    // A command without output would otherwise keep running after the client went away
+    let closed = tokio::select! {
+        _ = forward => output.is_closed(),
+        _ = output.closed() => true,
+    };
+    input_task.abort();
+    if closed {
+        process.abort();
+        return;
+    }
+    if let Some(status) = status
+        && let Some(code) = status.await.as_ref().and_then(exit_code)
+    {
+        let _ = output.send(ExecOutput::Exit(code)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::{StatusCause, StatusDetails};
+
+    use super::*;
+
+    #[test]
+    fn test_exit_code() {
+        let success = Status {
+            status: Some("Success".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(exit_code(&success), Some(0));
+
+        let failure = Status {
+            status: Some("Failure".to_string()),
+            reason: Some("NonZeroExitCode".to_string()),
+            details: Some(StatusDetails {
+                causes: Some(vec![StatusCause {
+                    reason: Some("ExitCode".to_string()),
+                    message: Some("127".to_string()),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(exit_code(&failure), Some(127));
+
+        let unknown = Status {
+            status: Some("Failure".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(exit_code(&unknown), None);
+    }
+}
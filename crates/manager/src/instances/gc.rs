@@ -0,0 +1,234 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Cleaning up instances nothing else would delete anymore: those of challenges which were
+//! removed from the repo, those of actors which no longer exist, and namespaces which are stuck
+//! terminating.
+//!
+//! The manager doesn't know which actors exist, so they are only checked when the api passes
+//! them to the CollectGarbage RPC. Namespaces stuck terminating are usually held up by finalizers
+//! of their pods, volume claims or virtual machines, which are removed once the namespace has
+//! been terminating for a while. Everything in an instance namespace belongs to the instance, so
+//! nothing else depends on them.
+
+use std::{
+    collections::HashSet,
+    fmt::Debug,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{TimeDelta, Utc};
+use k8s_openapi::api::core::v1::{Namespace, PersistentVolumeClaim, Pod};
+use kube::{
+    Api, Client, Resource,
+    api::{DeleteParams, ListParams, Patch, PatchParams},
+};
+use serde::de::DeserializeOwned;
+
+use crate::repo::get_head_commit_info;
+
+/// How often the manager looks for garbage on its own
+const GC_INTERVAL: Duration = Duration::from_secs(300);
+/// How long a namespace may be terminating before the finalizers of its objects are removed
+const STUCK_AFTER: TimeDelta = TimeDelta::minutes(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Orphan {
+    MissingChallenge,
+    DeletedActor,
+}
+
+/// Number of instance namespaces cleaned up by one run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GarbageReport {
+    pub missing_challenge: u32,
+    pub deleted_actor: u32,
+    /// Namespaces stuck terminating whose objects had their finalizers removed
+    pub force_finalized: u32,
+}
+
+/// Challenges in the repo, None if the repo hasn't been cloned yet
+fn existing_challenges(repo_dir: &Path) -> std::io::Result<Option<HashSet<String>>> {
+    let challenges_dir = repo_dir.join("challs");
+    if get_head_commit_info(repo_dir).is_none() || !challenges_dir.is_dir() {
+        return Ok(None);
+    }
+    let mut challenges = HashSet::new();
+    for entry in std::fs::read_dir(challenges_dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            challenges.insert(path.file_name().unwrap().to_string_lossy().to_string());
+        }
+    }
+    Ok(Some(challenges))
+}
+
+/// Why an instance namespace should be deleted, if it should be
+fn orphan_reason(
+    ns: &Namespace,
+    challenges: Option<&HashSet<String>>,
+    actors: Option<&HashSet<String>>,
+) -> Option<Orphan> {
+    let labels = ns.metadata.labels.as_ref()?;
+    let challenge_id = labels.get("challenge_id")?;
+    if challenges.is_some_and(|challenges| !challenges.contains(challenge_id)) {
+        return Some(Orphan::MissingChallenge);
+    }
+    // Shared instances and unclaimed pooled ones don't have an actor
+    let actor_id = labels.get("actor_id")?;
+    if actors.is_some_and(|actors| !actors.contains(actor_id)) {
+        return Some(Orphan::DeletedActor);
+    }
+    None
+}
+
+/// Removes the finalizers of all objects of a kind which have any, returning how many there were
+async fn remove_finalizers<K>(api: Api<K>) -> Result<u32, kube::Error>
+where
+    K: Resource + Clone + DeserializeOwned + Debug,
+{
+    let patch = serde_json::json!({ "metadata": { "finalizers": null } });
+    let mut removed = 0;
+    for object in api.list(&ListParams::default()).await? {
+        let meta = object.meta();
+        let (Some(name), Some(finalizers)) = (&meta.name, &meta.finalizers) else {
+            continue;
+        };
+        if finalizers.is_empty() {
+            continue;
+        }
+        tracing::warn!("Removing finalizers {:?} of {}", finalizers, name);
+        api.patch(name, &PatchParams::default(), &Patch::Merge(&patch))
+            .await?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// Removes the finalizers holding up the deletion of an instance namespace, returning whether
+/// there were any
+async fn force_finalize(kube_client: &Client, ns: &str) -> Result<bool, kube::Error> {
+    let removed = remove_finalizers::<Pod>(Api::namespaced(kube_client.clone(), ns)).await?
+        + remove_finalizers::<PersistentVolumeClaim>(Api::namespaced(kube_client.clone(), ns))
+            .await?
+        + remove_finalizers::<k8s_crds_kube_virt::VirtualMachine>(Api::namespaced(
+            kube_client.clone(),
+            ns,
+        ))
+        .await?
+        + remove_finalizers::<k8s_crds_kube_virt::VirtualMachineInstance>(Api::namespaced(
+            kube_client.clone(),
+            ns,
+        ))
+        .await?;
+    Ok(removed > 0)
+}
+
+/// Deletes orphaned instances and unblocks namespaces stuck terminating. Actors are only checked
+/// if `actors` is given.
+pub async fn collect_garbage(
+    kube_client: &Client,
+    repo_dir: &Path,
+    actors: Option<&HashSet<String>>,
+) -> Result<GarbageReport, Box<dyn std::error::Error>> {
+    let challenges = existing_challenges(repo_dir)?;
+    let api: Api<Namespace> = Api::all(kube_client.clone());
+    let now = Utc::now();
+    let mut report = GarbageReport::default();
+    for ns in api
+        .list(&ListParams::default().labels("challenge_id"))
+        .await?
+    {
+        let Some(name) = ns.metadata.name.clone() else {
+            continue;
+        };
+        if let Some(deleted_at) = &ns.metadata.deletion_timestamp {
+            if now - deleted_at.0 > STUCK_AFTER && force_finalize(kube_client, &name).await? {
+                tracing::warn!("Force finalized instance namespace {}", name);
+                report.force_finalized += 1;
+            }
+            continue;
+        }
+        let Some(reason) = orphan_reason(&ns, challenges.as_ref(), actors) else {
+            continue;
+        };
+        tracing::info!("Deleting orphaned instance {} ({:?})", name, reason);
+        api.delete(&name, &DeleteParams::default()).await?;
+        match reason {
+            Orphan::MissingChallenge => report.missing_challenge += 1,
+            Orphan::DeletedActor => report.deleted_actor += 1,
+        }
+    }
+    Ok(report)
+}
+
+/// Periodically collects garbage in the background, without checking actors
+pub fn spawn_collector(kube_client: Client, repo_dir: PathBuf) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(GC_INTERVAL);
+        loop {
+            interval.tick().await;
+            match collect_garbage(&kube_client, &repo_dir, None).await {
+                Ok(report) if report != GarbageReport::default() => {
+                    tracing::info!("Collected orphaned instances: {:?}", report);
+                }
+                Ok(_) => {}
+                Err(e) => tracing::error!("Failed to collect orphaned instances: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ns(labels: &[(&str, &str)]) -> Namespace {
+        Namespace {
+            metadata: kube::api::ObjectMeta {
+                labels: Some(
+                    labels
+                        .iter()
+                        .map(|(k, v)| (k.to_string(), v.to_string()))
+                        .collect(),
+                ),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_orphan_reason() {
+        let challenges = HashSet::from(["web".to_string()]);
+        let actors = HashSet::from(["team-foo".to_string()]);
+        let owned = ns(&[("challenge_id", "web"), ("actor_id", "team-foo")]);
+        assert_eq!(
+            orphan_reason(&owned, Some(&challenges), Some(&actors)),
+            None
+        );
+
+        let removed = ns(&[("challenge_id", "pwn"), ("actor_id", "team-foo")]);
+        assert_eq!(
+            orphan_reason(&removed, Some(&challenges), Some(&actors)),
+            Some(Orphan::MissingChallenge)
+        );
+        // Nothing is known about the challenges before the repo is cloned
+        assert_eq!(orphan_reason(&removed, None, Some(&actors)), None);
+
+        let deleted = ns(&[("challenge_id", "web"), ("actor_id", "team-bar")]);
+        assert_eq!(
+            orphan_reason(&deleted, Some(&challenges), Some(&actors)),
+            Some(Orphan::DeletedActor)
+        );
+        assert_eq!(orphan_reason(&deleted, Some(&challenges), None), None);
+
+        let shared = ns(&[("challenge_id", "web"), ("shared", "true")]);
+        assert_eq!(
+            orphan_reason(&shared, Some(&challenges), Some(&actors)),
+            None
+        );
+    }
+}
@@ -0,0 +1,225 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Instances are deleted once they expire, unless their actor extends them in time.
+//!
+//! The expiry is stored as a unix timestamp in an annotation of the instance namespace.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use k8s_openapi::api::core::v1::Namespace;
+use kube::{
+    Api, Client,
+    api::{ListParams, Patch, PatchParams},
+};
+
+use crate::instances::{full_instance_ns, is_owned_by};
+
+pub const EXPIRES_AT_ANNOTATION: &str = "expires_at";
+/// When a pooled instance was assigned to its actor, lifetimes count from then instead of from
+/// the creation of the namespace
+pub const STARTED_AT_ANNOTATION: &str = "started_at";
+/// How often expired instances are looked for
+const REAP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long instances live, from INSTANCE_LIFETIME_SECS. Instances live forever if it isn't set.
+#[derive(Debug, Clone, Copy)]
+pub struct LifetimeConfig {
+    /// Lifetime of a new instance
+    pub lifetime: TimeDelta,
+    /// Time added by an extension, INSTANCE_EXTENSION_SECS (defaults to the lifetime)
+    pub extension: TimeDelta,
+    /// Total lifetime extensions can't go beyond, INSTANCE_MAX_LIFETIME_SECS (defaults to four
+    /// times the lifetime)
+    pub max_lifetime: TimeDelta,
+}
+
+fn secs_from_env(name: &str) -> Option<i64> {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|secs| *secs > 0)
+}
+
+impl LifetimeConfig {
+    pub fn from_env() -> Option<Self> {
+        let lifetime = secs_from_env("INSTANCE_LIFETIME_SECS")?;
+        Some(LifetimeConfig {
+            lifetime: TimeDelta::seconds(lifetime),
+            extension: TimeDelta::seconds(
+                secs_from_env("INSTANCE_EXTENSION_SECS").unwrap_or(lifetime),
+            ),
+            max_lifetime: TimeDelta::seconds(
+                secs_from_env("INSTANCE_MAX_LIFETIME_SECS").unwrap_or(lifetime * 4),
+            ),
+        })
+    }
+
+    /// The new expiry of an instance created at `created_at`, extended at `now`.
+    ///
+    /// Extensions count from now (or the current expiry, if that's later) and are capped at the
+    /// maximum lifetime. Fails if the instance can't live any longer.
+    pub fn extended_expiry(
+        &self,
+        created_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Result<DateTime<Utc>, String> {
+        let new_expiry = (expires_at.max(now) + self.extension).min(created_at + self.max_lifetime);
+        if new_expiry <= expires_at {
+            return Err("The instance has reached its maximum lifetime".to_string());
+        }
+        Ok(new_expiry)
+    }
+}
+
+/// Annotations of a new instance namespace
+pub fn initial_annotations(config: Option<&LifetimeConfig>) -> Option<BTreeMap<String, String>> {
+    config.map(|config| {
+        BTreeMap::from([(
+            EXPIRES_AT_ANNOTATION.to_string(),
+            (Utc::now() + config.lifetime).timestamp().to_string(),
+        )])
+    })
+}
+
+/// When the instance in the namespace expires, if it does
+pub fn expires_at(ns: &Namespace) -> Option<DateTime<Utc>> {
+    ns.metadata
+        .annotations
+        .as_ref()?
+        .get(EXPIRES_AT_ANNOTATION)?
+        .parse::<i64>()
+        .ok()
+        .and_then(|ts| DateTime::from_timestamp(ts, 0))
+}
+
+/// When an instance expires, None if it doesn't or can't be found
+pub async fn instance_expiry(
+    kube_client: &Client,
+    challenge_id: &str,
+    instance_id: &str,
+) -> Option<DateTime<Utc>> {
+    let api: Api<Namespace> = Api::all(kube_client.clone());
+    let ns = api
+        .get_opt(&full_instance_ns(challenge_id, instance_id))
+        .await
+        .ok()??;
+    expires_at(&ns)
+}
+
+/// Extends the actor's instance of a challenge, returning its new expiry
+#[tracing::instrument(skip(kube_client))]
+pub async fn extend_instance(
+    kube_client: &Client,
+    config: &LifetimeConfig,
+    challenge_id: &str,
+    actor_id: &str,
+    instance_id: &str,
+) -> Result<DateTime<Utc>, Box<dyn std::error::Error>> {
+    let api: Api<Namespace> = Api::all(kube_client.clone());
+    let instance_ns = full_instance_ns(challenge_id, instance_id);
+    let ns = api.get(&instance_ns).await?;
+    if !is_owned_by(&ns, challenge_id, actor_id) {
+        return Err("Instance does not belong to actor".into());
+    }
+    let created_at = ns
+        .metadata
+        .annotations
+        .as_ref()
+        .and_then(|a| a.get(STARTED_AT_ANNOTATION))
+        .and_then(|ts| ts.parse::<i64>().ok())
+        .and_then(|ts| DateTime::from_timestamp(ts, 0))
+        .or_else(|| ns.metadata.creation_timestamp.as_ref().map(|ts| ts.0))
+        .ok_or("Instance has no creation time")?;
+    // Instances started before lifetimes were configured live forever
+    let current_expiry = expires_at(&ns).ok_or("The instance does not expire")?;
+    let new_expiry = config.extended_expiry(created_at, current_expiry, Utc::now())?;
+    let patch = serde_json::json!({
+        "metadata": {
+            "annotations": {
+                EXPIRES_AT_ANNOTATION: new_expiry.timestamp().to_string(),
+            },
+        },
+    });
+    api.patch(&instance_ns, &PatchParams::default(), &Patch::Merge(&patch))
+        .await?;
+    Ok(new_expiry)
+}
+
+/// Deletes instances which expired
+pub async fn reap_expired_instances(kube_client: &Client) -> Result<(), kube::Error> {
+    let api: Api<Namespace> = Api::all(kube_client.clone());
+    let now = Utc::now();
+    let ns_list = api
+        .list(&ListParams::default().labels("challenge_id,actor_id"))
+        .await?;
+    for ns in ns_list {
+        if ns.metadata.deletion_timestamp.is_some()
+            || expires_at(&ns).is_none_or(|expires_at| expires_at > now)
+        {
+            continue;
+        }
+        let Some(name) = ns.metadata.name else {
+            continue;
+        };
+        tracing::info!("Deleting expired instance {}", name);
+        if let Err(e) = api.delete(&name, &kube::api::DeleteParams::default()).await {
+            tracing::error!("Failed to delete expired instance {}: {}", name, e);
+        }
+    }
+    Ok(())
+}
+
+/// Periodically deletes expired instances in the background
+pub fn spawn_reaper(kube_client: Client) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(REAP_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = reap_expired_instances(&kube_client).await {
+                tracing::error!("Failed to look for expired instances: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: LifetimeConfig = LifetimeConfig {
+        lifetime: TimeDelta::minutes(30),
+        extension: TimeDelta::minutes(30),
+        max_lifetime: TimeDelta::minutes(90),
+    };
+
+    #[test]
+    fn test_extension_counts_from_expiry() {
+        let created_at = DateTime::from_timestamp(0, 0).unwrap();
+        let expires_at = created_at + TimeDelta::minutes(30);
+        let now = created_at + TimeDelta::minutes(20);
+        assert_eq!(
+            CONFIG.extended_expiry(created_at, expires_at, now),
+            Ok(created_at + TimeDelta::minutes(60))
+        );
+    }
+
+    #[test]
+    fn test_extension_is_capped() {
+        let created_at = DateTime::from_timestamp(0, 0).unwrap();
+        let expires_at = created_at + TimeDelta::minutes(80);
+        let now = created_at + TimeDelta::minutes(70);
+        assert_eq!(
+            CONFIG.extended_expiry(created_at, expires_at, now),
+            Ok(created_at + TimeDelta::minutes(90))
+        );
+        assert!(
+            CONFIG
+                .extended_expiry(created_at, created_at + TimeDelta::minutes(90), now)
+                .is_err()
+        );
+    }
+}
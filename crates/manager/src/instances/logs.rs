@@ -0,0 +1,77 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Logs of the containers of an instance, for organizers debugging broken challenges.
+
+use std::pin::Pin;
+
+use futures_util::{AsyncBufReadExt, Stream, StreamExt};
+use k8s_openapi::api::core::v1::Pod;
+use kube::{
+    Api, Client,
+    api::{ListParams, LogParams},
+};
+
+use crate::instances::full_instance_ns;
+
+/// Lines per container if the request doesn't say
+pub const DEFAULT_TAIL_LINES: i64 = 100;
+const MAX_TAIL_LINES: i64 = 10_000;
+
+pub struct LogLine {
+    pub pod: String,
+    pub container: String,
+    pub line: String,
+}
+
+pub type LogStream = Pin<Box<dyn Stream<Item = std::io::Result<LogLine>> + Send>>;
+
+/// Streams the last lines of all containers of an instance (or only those of one compose
+/// service), interleaved as they arrive. With `follow`, the stream continues with new lines until
+/// the containers stop.
+#[tracing::instrument(skip(kube_client))]
+pub async fn instance_logs(
+    kube_client: &Client,
+    challenge_id: &str,
+    instance_id: &str,
+    service: Option<&str>,
+    tail_lines: Option<i64>,
+    follow: bool,
+) -> Result<LogStream, kube::Error> {
+    let api: Api<Pod> = Api::namespaced(
+        kube_client.clone(),
+        &full_instance_ns(challenge_id, instance_id),
+    );
+    let lp = match service {
+        Some(service) => ListParams::default().labels(&format!("compose-service-id={}", service)),
+        None => ListParams::default(),
+    };
+    let tail_lines = tail_lines
+        .unwrap_or(DEFAULT_TAIL_LINES)
+        .clamp(1, MAX_TAIL_LINES);
+    let mut streams: Vec<LogStream> = Vec::new();
+    for pod in api.list(&lp).await? {
+        let (Some(pod_name), Some(spec)) = (pod.metadata.name, pod.spec) else {
+            continue;
+        };
+        for container in spec.containers {
+            let log_params = LogParams {
+                container: Some(container.name.clone()),
+                follow,
+                tail_lines: Some(tail_lines),
+                ..Default::default()
+            };
+            let lines = api.log_stream(&pod_name, &log_params).await?.lines();
+            let pod_name = pod_name.clone();
+            streams.push(Box::pin(lines.map(move |line| {
+                line.map(|line| LogLine {
+                    pod: pod_name.clone(),
+                    container: container.name.clone(),
+                    line,
+                })
+            })));
+        }
+    }
+    Ok(Box::pin(futures_util::stream::select_all(streams)))
+}
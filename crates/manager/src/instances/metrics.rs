@@ -0,0 +1,131 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! CPU and memory the pods of instances currently use, as measured by metrics-server.
+
+use std::collections::{BTreeMap, HashMap};
+
+use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+use kube::{
+    Api, Client,
+    api::{ApiResource, DynamicObject, GroupVersionKind, ListParams},
+};
+use serde::Deserialize;
+
+use crate::instances::{
+    InstanceSummary, admission::Resources, full_instance_ns, list_all_instances,
+};
+
+#[derive(Debug, Deserialize)]
+struct ContainerMetrics {
+    #[serde(default)]
+    usage: BTreeMap<String, Quantity>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PodUsage {
+    pub pod: String,
+    pub usage: Resources,
+}
+
+#[derive(Debug, Clone)]
+pub struct InstanceUsage {
+    pub instance: InstanceSummary,
+    pub pods: Vec<PodUsage>,
+}
+
+impl InstanceUsage {
+    pub fn total(&self) -> Resources {
+        self.pods
+            .iter()
+            .fold(Resources::default(), |total, pod| Resources {
+                cpu_millis: total.cpu_millis + pod.usage.cpu_millis,
+                memory_bytes: total.memory_bytes + pod.usage.memory_bytes,
+            })
+    }
+}
+
+fn pod_metrics_resource() -> ApiResource {
+    ApiResource::from_gvk_with_plural(
+        &GroupVersionKind::gvk("metrics.k8s.io", "v1beta1", "PodMetrics"),
+        "pods",
+    )
+}
+
+/// Usage of all containers of a PodMetrics object together
+fn pod_usage(metrics: &DynamicObject) -> Option<PodUsage> {
+    let containers: Vec<ContainerMetrics> =
+        serde_json::from_value(metrics.data.get("containers")?.clone()).ok()?;
+    let mut usage = Resources::default();
+    for container in containers {
+        let container_usage = Resources::from_map(&container.usage);
+        usage.cpu_millis += container_usage.cpu_millis;
+        usage.memory_bytes += container_usage.memory_bytes;
+    }
+    Some(PodUsage {
+        pod: metrics.metadata.name.clone()?,
+        usage,
+    })
+}
+
+/// Usage of the pods of all running instances (or only those of the given challenge). Pods
+/// metrics-server hasn't measured yet are missing.
+pub async fn instance_usage(
+    kube_client: &Client,
+    challenge_id: Option<&str>,
+) -> Result<Vec<InstanceUsage>, kube::Error> {
+    let instances = list_all_instances(kube_client, challenge_id).await?;
+    // One request for the whole cluster is cheaper than one per instance namespace
+    let api: Api<DynamicObject> = Api::all_with(kube_client.clone(), &pod_metrics_resource());
+    let mut pods_by_ns: HashMap<String, Vec<PodUsage>> = HashMap::new();
+    for metrics in api.list(&ListParams::default()).await? {
+        if let Some(ns) = metrics.metadata.namespace.clone()
+            && let Some(usage) = pod_usage(&metrics)
+        {
+            pods_by_ns.entry(ns).or_default().push(usage);
+        }
+    }
+    Ok(instances
+        .into_iter()
+        .filter(|instance| !instance.is_terminating)
+        .map(|instance| {
+            let ns = full_instance_ns(&instance.challenge_id, &instance.instance_id);
+            InstanceUsage {
+                pods: pods_by_ns.remove(&ns).unwrap_or_default(),
+                instance,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pod_usage() {
+        let metrics: DynamicObject = serde_json::from_value(serde_json::json!({
+            "apiVersion": "metrics.k8s.io/v1beta1",
+            "kind": "PodMetrics",
+            "metadata": { "name": "web-0", "namespace": "challenge-web-instance-abc" },
+            "timestamp": "2026-10-17T12:00:00Z",
+            "window": "15s",
+            "containers": [
+                { "name": "web", "usage": { "cpu": "250m", "memory": "64Mi" } },
+                { "name": "db", "usage": { "cpu": "1500000n", "memory": "1024Ki" } }
+            ]
+        }))
+        .unwrap();
+        assert_eq!(
+            pod_usage(&metrics),
+            Some(PodUsage {
+                pod: "web-0".to_string(),
+                usage: Resources {
+                    cpu_millis: 252,
+                    memory_bytes: 65 * 1024 * 1024,
+                },
+            })
+        );
+    }
+}
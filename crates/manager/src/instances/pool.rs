@@ -0,0 +1,253 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Instances started ahead of time for challenges with a `pool_size`, so players don't have to
+//! wait for images to be pulled and services to become ready.
+//!
+//! Unclaimed instances are labeled `pooled` instead of having an actor. Claiming one gives it the
+//! label of the actor, after which it is an instance like any other.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use k8s_openapi::api::core::v1::Namespace;
+use kube::{
+    Api, Client,
+    api::{DeleteParams, ListParams, Patch, PatchParams},
+};
+
+use crate::{
+    instances::{
+        full_instance_ns, instance_id_from_ns, is_instance_running, lifetime, random_instance_id,
+        shared::{REVISION_ANNOTATION, challenge_revision, deploy_unowned},
+    },
+    repo::{
+        builds::BuildStore,
+        challenges::{
+            loader::{Challenge, load_challenges_from_repo},
+            metadata::{DeploymentMode, FlagValidator},
+            packages::is_static,
+            vm::HasVms,
+        },
+    },
+};
+
+/// Pooled instances are rendered for this actor
+pub const POOL_ACTOR: &str = "pool";
+const POOLED_LABEL: &str = "pooled";
+/// How often pools are refilled
+const REPLENISH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Pooled instances are rendered before anyone claims them, so nothing in them may depend on the
+/// actor: no templates, no per-actor flags and no SSH gateways, whose passwords are derived from
+/// the actor
+pub fn check_poolable(challenge: &Challenge, chall_dir: &Path) -> Result<(), String> {
+    if !is_static(chall_dir).map_err(|e| format!("Failed to read challenge files: {}", e))? {
+        return Err("Challenges with templates can't be pooled".to_string());
+    }
+    if matches!(
+        challenge.metadata.flag_validator,
+        FlagValidator::Dynamic { .. }
+    ) {
+        return Err("Challenges with dynamic flags can't be pooled".to_string());
+    }
+    let uses_ssh_gateway = challenge
+        .compose
+        .services
+        .values()
+        .map(|svc| svc.ports.clone())
+        .chain(challenge.compose.get_vms().into_values().map(|vm| vm.ports))
+        .flat_map(compose_spec::service::ports::into_long_iter)
+        .any(|port| {
+            port.app_protocol
+                .as_deref()
+                .is_some_and(|proto| proto.eq_ignore_ascii_case("ssh"))
+                && port.extensions.contains_key("x-username")
+                && port.extensions.contains_key("x-password")
+        });
+    if uses_ssh_gateway {
+        return Err("Challenges with SSH gateways can't be pooled".to_string());
+    }
+    Ok(())
+}
+
+fn revision_of(ns: &Namespace) -> Option<&String> {
+    ns.metadata.annotations.as_ref()?.get(REVISION_ANNOTATION)
+}
+
+/// Pooled instances nobody claimed yet, of all challenges or only the given one
+async fn unclaimed_instances(
+    kube_client: &Client,
+    challenge_id: Option<&str>,
+) -> Result<Vec<Namespace>, kube::Error> {
+    let api: Api<Namespace> = Api::all(kube_client.clone());
+    let selector = match challenge_id {
+        Some(challenge_id) => format!("challenge_id={},{}=true", challenge_id, POOLED_LABEL),
+        None => format!("challenge_id,{}=true", POOLED_LABEL),
+    };
+    Ok(api
+        .list(&ListParams::default().labels(&selector))
+        .await?
+        .into_iter()
+        .filter(|ns| ns.metadata.deletion_timestamp.is_none())
+        .collect())
+}
+
+/// Assigns an unclaimed instance of the current revision of a challenge to the actor, preferring
+/// ready ones. Returns None if the pool is empty.
+#[tracing::instrument(skip(kube_client))]
+pub async fn claim_instance(
+    kube_client: &Client,
+    challenge_id: &str,
+    actor_id: &str,
+    revision: &str,
+) -> Result<Option<String>, kube::Error> {
+    let api: Api<Namespace> = Api::all(kube_client.clone());
+    let mut candidates = Vec::new();
+    for ns in unclaimed_instances(kube_client, Some(challenge_id)).await? {
+        if revision_of(&ns).map(String::as_str) != Some(revision) {
+            continue;
+        }
+        let (Some(name), Some(resource_version)) = (ns.metadata.name, ns.metadata.resource_version)
+        else {
+            continue;
+        };
+        let instance_id = instance_id_from_ns(challenge_id, &name).to_string();
+        let ready = is_instance_running(kube_client, challenge_id, &instance_id).await;
+        candidates.push((ready, instance_id, resource_version));
+    }
+    candidates.sort_by_key(|(ready, _, _)| !*ready);
+
+    let mut annotations =
+        lifetime::initial_annotations(lifetime::LifetimeConfig::from_env().as_ref())
+            .unwrap_or_default();
+    annotations.insert(
+        lifetime::STARTED_AT_ANNOTATION.to_string(),
+        chrono::Utc::now().timestamp().to_string(),
+    );
+    for (_, instance_id, resource_version) in candidates {
+        // The resource version makes the patch fail if someone else claimed the instance first
+        let patch = serde_json::json!({
+            "metadata": {
+                "resourceVersion": resource_version,
+                "labels": {
+                    "actor_id": actor_id,
+                    POOLED_LABEL: null,
+                },
+                "annotations": annotations,
+            },
+        });
+        match api
+            .patch(
+                &full_instance_ns(challenge_id, &instance_id),
+                &PatchParams::default(),
+                &Patch::Merge(&patch),
+            )
+            .await
+        {
+            Ok(_) => return Ok(Some(instance_id)),
+            Err(kube::Error::Api(e)) if e.code == 409 => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(None)
+}
+
+/// Starts instances until every pool is full, replacing those of outdated revisions and deleting
+/// the pools of challenges that are gone or no longer pooled
+pub async fn replenish_pools(
+    kube_client: &Client,
+    repo_dir: &Path,
+    builds: &BuildStore,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let api: Api<Namespace> = Api::all(kube_client.clone());
+    let mut unclaimed: HashMap<String, Vec<Namespace>> = HashMap::new();
+    for ns in unclaimed_instances(kube_client, None).await? {
+        if let Some(challenge_id) = ns
+            .metadata
+            .labels
+            .as_ref()
+            .and_then(|labels| labels.get("challenge_id"))
+        {
+            unclaimed.entry(challenge_id.clone()).or_default().push(ns);
+        }
+    }
+
+    let challenges = load_challenges_from_repo(repo_dir, POOL_ACTOR, false).await?;
+    for (challenge_id, challenge) in challenges {
+        let Some(pool_size) = challenge
+            .metadata
+            .pool_size
+            .filter(|_| challenge.metadata.deployment == DeploymentMode::Instanced)
+        else {
+            continue;
+        };
+        let chall_dir = repo_dir.join("challs").join(&challenge_id);
+        if let Err(e) = check_poolable(&challenge, &chall_dir) {
+            tracing::warn!("Not pooling challenge {}: {}", challenge_id, e);
+            continue;
+        }
+        let revision = challenge_revision(&chall_dir)?;
+        let mut pooled = 0;
+        for ns in unclaimed.remove(&challenge_id).unwrap_or_default() {
+            if revision_of(&ns) == Some(&revision) {
+                pooled += 1;
+            } else if let Some(name) = ns.metadata.name {
+                tracing::info!("Deleting outdated pooled instance {}", name);
+                api.delete(&name, &DeleteParams::default()).await?;
+            }
+        }
+        for _ in pooled..pool_size {
+            match deploy_unowned(
+                kube_client,
+                repo_dir,
+                builds,
+                &challenge_id,
+                &random_instance_id(),
+                POOL_ACTOR,
+                [(POOLED_LABEL.to_string(), "true".to_string())].into(),
+                &revision,
+            )
+            .await
+            {
+                Ok(true) => {}
+                // The images aren't built yet
+                Ok(false) => break,
+                Err(e) => {
+                    tracing::error!(
+                        "Failed to start pooled instance of challenge {}: {}",
+                        challenge_id,
+                        e
+                    );
+                    break;
+                }
+            }
+        }
+    }
+
+    for ns in unclaimed.into_values().flatten() {
+        if let Some(name) = ns.metadata.name {
+            tracing::info!("Deleting pooled instance {}", name);
+            api.delete(&name, &DeleteParams::default()).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Periodically refills the pools in the background
+pub fn spawn_replenisher(kube_client: Client, repo_dir: PathBuf, builds: Arc<BuildStore>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(REPLENISH_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = replenish_pools(&kube_client, &repo_dir, &builds).await {
+                tracing::error!("Failed to replenish instance pools: {}", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,95 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Priority classes of the pods of instances, so the scheduler prefers the platform over
+//! instances, and instances of organizers testing challenges over those of players.
+//!
+//! The classes are taken from INSTANCE_PRIORITY_CLASS and TEST_INSTANCE_PRIORITY_CLASS. With
+//! MANAGE_PRIORITY_CLASSES=true, the manager creates classes below the default priority of 0 for
+//! both instead, so pods without a class (like the platform) preempt instances when the cluster
+//! is full. Instances never preempt each other.
+
+use k8s_openapi::api::scheduling::v1::PriorityClass;
+use kube::{
+    Api, Client,
+    api::{Patch, PatchParams},
+};
+
+const MANAGED_INSTANCE_CLASS: &str = "plfanzen-instance";
+const MANAGED_TEST_INSTANCE_CLASS: &str = "plfanzen-test-instance";
+
+/// Who an instance was started for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstancePriority {
+    Player,
+    /// Started by someone with access to the challenge before its release
+    Test,
+}
+
+impl InstancePriority {
+    /// Starts which don't need the challenge to be released come from organizers and authors
+    pub fn for_start(require_release: bool) -> Self {
+        if require_release {
+            InstancePriority::Player
+        } else {
+            InstancePriority::Test
+        }
+    }
+
+    /// Name of the priority class of the pods, None if none is configured
+    pub fn class_name(self) -> Option<String> {
+        let (var, managed) = match self {
+            InstancePriority::Player => ("INSTANCE_PRIORITY_CLASS", MANAGED_INSTANCE_CLASS),
+            InstancePriority::Test => ("TEST_INSTANCE_PRIORITY_CLASS", MANAGED_TEST_INSTANCE_CLASS),
+        };
+        std::env::var(var)
+            .ok()
+            .filter(|name| !name.is_empty())
+            .or_else(|| manage_priority_classes().then(|| managed.to_string()))
+    }
+}
+
+pub fn manage_priority_classes() -> bool {
+    std::env::var("MANAGE_PRIORITY_CLASSES").is_ok_and(|v| v == "true" || v == "1")
+}
+
+fn managed_class(name: &str, value: i32, description: &str) -> PriorityClass {
+    PriorityClass {
+        metadata: kube::api::ObjectMeta {
+            name: Some(name.to_string()),
+            ..Default::default()
+        },
+        value,
+        description: Some(description.to_string()),
+        preemption_policy: Some("Never".to_string()),
+        global_default: Some(false),
+    }
+}
+
+/// Creates or updates the priority classes of instances
+pub async fn ensure_priority_classes(kube_client: &Client) -> Result<(), kube::Error> {
+    let api: Api<PriorityClass> = Api::all(kube_client.clone());
+    let classes = [
+        managed_class(
+            MANAGED_INSTANCE_CLASS,
+            -100,
+            "Challenge instances of players",
+        ),
+        managed_class(
+            MANAGED_TEST_INSTANCE_CLASS,
+            -50,
+            "Challenge instances of organizers and authors",
+        ),
+    ];
+    for class in classes {
+        let name = class.metadata.name.clone().unwrap_or_default();
+        api.patch(
+            &name,
+            &PatchParams::apply("plfanzen-manager").force(),
+            &Patch::Apply(&class),
+        )
+        .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,257 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! How far a starting instance got, so players don't have to stare at a spinner for minutes.
+//!
+//! Until all objects of an instance are created, the step is stored in an annotation of its
+//! namespace. After that, the progress is derived from the state of its pods.
+
+use k8s_openapi::api::core::v1::{Namespace, Pod};
+use kube::{
+    Api, Client,
+    api::{ListParams, Patch, PatchParams},
+};
+
+use crate::instances::full_instance_ns;
+
+pub const DEPLOY_STAGE_ANNOTATION: &str = "deploy_stage";
+
+/// Steps of a start before pods exist
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeployStage {
+    NamespaceCreated,
+    /// The deployments, services, routes and network policies are being created
+    CreatingResources,
+    /// Everything is created, the pods are starting
+    Deployed,
+}
+
+impl DeployStage {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeployStage::NamespaceCreated => "namespace_created",
+            DeployStage::CreatingResources => "creating_resources",
+            DeployStage::Deployed => "deployed",
+        }
+    }
+
+    /// Instances created before stages were recorded are deployed already
+    fn of(ns: &Namespace) -> Self {
+        match ns
+            .metadata
+            .annotations
+            .as_ref()
+            .and_then(|a| a.get(DEPLOY_STAGE_ANNOTATION))
+            .map(String::as_str)
+        {
+            Some("namespace_created") => DeployStage::NamespaceCreated,
+            Some("creating_resources") => DeployStage::CreatingResources,
+            _ => DeployStage::Deployed,
+        }
+    }
+}
+
+/// What a starting instance is currently waiting for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstanceStage {
+    NamespaceCreated,
+    CreatingResources,
+    /// Pods are waiting for a node
+    Scheduling,
+    /// Containers are being created, which mostly means their images are being pulled
+    PullingImages,
+    /// Containers are running, but not ready yet
+    Starting,
+    Ready,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstanceProgress {
+    pub stage: InstanceStage,
+    pub ready_pods: u32,
+    pub total_pods: u32,
+}
+
+/// Records the step a start reached
+pub async fn set_deploy_stage(
+    kube_client: &Client,
+    challenge_id: &str,
+    instance_id: &str,
+    stage: DeployStage,
+) -> Result<(), kube::Error> {
+    let api: Api<Namespace> = Api::all(kube_client.clone());
+    let patch = serde_json::json!({
+        "metadata": {
+            "annotations": {
+                DEPLOY_STAGE_ANNOTATION: stage.as_str(),
+            },
+        },
+    });
+    api.patch(
+        &full_instance_ns(challenge_id, instance_id),
+        &PatchParams::default(),
+        &Patch::Merge(&patch),
+    )
+    .await?;
+    Ok(())
+}
+
+fn is_ready(pod: &Pod) -> bool {
+    let Some(status) = &pod.status else {
+        return false;
+    };
+    // Pods that ran to completion never become ready again
+    status.phase.as_deref() == Some("Succeeded")
+        || status
+            .conditions
+            .iter()
+            .flatten()
+            .any(|c| c.type_ == "Ready" && c.status == "True")
+}
+
+fn is_scheduled(pod: &Pod) -> bool {
+    pod.status
+        .as_ref()
+        .and_then(|status| status.conditions.as_ref())
+        .is_some_and(|conditions| {
+            conditions
+                .iter()
+                .any(|c| c.type_ == "PodScheduled" && c.status == "True")
+        })
+}
+
+fn is_creating_containers(pod: &Pod) -> bool {
+    let Some(status) = &pod.status else {
+        return false;
+    };
+    status
+        .init_container_statuses
+        .iter()
+        .flatten()
+        .chain(status.container_statuses.iter().flatten())
+        .filter_map(|c| c.state.as_ref()?.waiting.as_ref()?.reason.as_deref())
+        .any(|reason| {
+            matches!(
+                reason,
+                "ContainerCreating" | "PodInitializing" | "ErrImagePull"
+            )
+        })
+}
+
+/// Progress of an instance whose resources are all created, from its pods. The earliest stage
+/// any pod is in counts.
+fn pod_progress(pods: &[Pod]) -> InstanceProgress {
+    let ready_pods = pods.iter().filter(|pod| is_ready(pod)).count() as u32;
+    let stage = if pods.iter().any(|pod| !is_scheduled(pod)) || pods.is_empty() {
+        InstanceStage::Scheduling
+    } else if pods.iter().any(is_creating_containers) {
+        InstanceStage::PullingImages
+    } else if ready_pods < pods.len() as u32 {
+        InstanceStage::Starting
+    } else {
+        InstanceStage::Ready
+    };
+    InstanceProgress {
+        stage,
+        ready_pods,
+        total_pods: pods.len() as u32,
+    }
+}
+
+#[tracing::instrument(skip(kube_client))]
+pub async fn instance_progress(
+    kube_client: &Client,
+    challenge_id: &str,
+    instance_id: &str,
+) -> Result<InstanceProgress, kube::Error> {
+    let ns_name = full_instance_ns(challenge_id, instance_id);
+    let ns = Api::<Namespace>::all(kube_client.clone())
+        .get(&ns_name)
+        .await?;
+    let stage = match DeployStage::of(&ns) {
+        DeployStage::NamespaceCreated => Some(InstanceStage::NamespaceCreated),
+        DeployStage::CreatingResources => Some(InstanceStage::CreatingResources),
+        DeployStage::Deployed => None,
+    };
+    let pods = Api::<Pod>::namespaced(kube_client.clone(), &ns_name)
+        .list(&ListParams::default())
+        .await?
+        .items;
+    let progress = pod_progress(&pods);
+    Ok(match stage {
+        Some(stage) => InstanceProgress { stage, ..progress },
+        None => progress,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::api::core::v1::{
+        ContainerState, ContainerStateWaiting, ContainerStatus, PodCondition, PodStatus,
+    };
+
+    use super::*;
+
+    fn pod(conditions: &[(&str, &str)], waiting: Option<&str>) -> Pod {
+        Pod {
+            status: Some(PodStatus {
+                phase: Some("Pending".to_string()),
+                conditions: Some(
+                    conditions
+                        .iter()
+                        .map(|(type_, status)| PodCondition {
+                            type_: type_.to_string(),
+                            status: status.to_string(),
+                            ..Default::default()
+                        })
+                        .collect(),
+                ),
+                container_statuses: waiting.map(|reason| {
+                    vec![ContainerStatus {
+                        name: "web".to_string(),
+                        state: Some(ContainerState {
+                            waiting: Some(ContainerStateWaiting {
+                                reason: Some(reason.to_string()),
+                                message: None,
+                            }),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    }]
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pod_progress() {
+        let unscheduled = pod(&[("PodScheduled", "False")], None);
+        let pulling = pod(&[("PodScheduled", "True")], Some("ContainerCreating"));
+        let starting = pod(&[("PodScheduled", "True"), ("Ready", "False")], None);
+        let ready = pod(&[("PodScheduled", "True"), ("Ready", "True")], None);
+
+        assert_eq!(
+            pod_progress(&[unscheduled, ready.clone()]).stage,
+            InstanceStage::Scheduling
+        );
+        assert_eq!(
+            pod_progress(&[pulling, starting.clone()]).stage,
+            InstanceStage::PullingImages
+        );
+        assert_eq!(
+            pod_progress(&[starting, ready.clone()]),
+            InstanceProgress {
+                stage: InstanceStage::Starting,
+                ready_pods: 1,
+                total_pods: 2,
+            }
+        );
+        assert_eq!(
+            pod_progress(&[ready.clone(), ready]).stage,
+            InstanceStage::Ready
+        );
+    }
+}
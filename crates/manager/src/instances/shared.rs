@@ -0,0 +1,244 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Challenges with `deployment: shared` run once for everyone instead of per actor.
+//!
+//! Their deployment lives in an instance namespace without an actor, so it is never counted,
+//! extended or reaped like the instances of players. It is redeployed whenever the files of the
+//! challenge change.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use k8s_openapi::api::core::v1::Namespace;
+use kube::{
+    Api, Client,
+    api::{DeleteParams, ListParams, PostParams},
+};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    instances::{deploy::deploy_challenge, full_instance_ns, priority::InstancePriority},
+    repo::{
+        builds::{BuildState, BuildStore},
+        challenges::{
+            loader::{load_challenge_instance_from_repo, load_challenges_from_repo, tera},
+            metadata::{DeploymentMode, FlagValidator},
+        },
+        get_head_commit_info,
+        image_builder::{collect_files, use_built_images},
+    },
+};
+
+/// Instance ID of shared deployments, it can't clash with the random hex IDs of instances
+pub const SHARED_INSTANCE_ID: &str = "shared";
+/// Shared challenges are rendered for this actor
+pub const SHARED_ACTOR: &str = "shared";
+/// Hash of the challenge files the deployment was created from
+pub const REVISION_ANNOTATION: &str = "revision";
+/// How often shared deployments are compared to the repo
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Hash of all files of a challenge
+pub fn challenge_revision(chall_dir: &Path) -> std::io::Result<String> {
+    let mut files = Vec::new();
+    collect_files(chall_dir, chall_dir, &mut files)?;
+    files.sort();
+    let mut hasher = Sha256::new();
+    for file in files {
+        hasher.update(file.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(std::fs::read(chall_dir.join(&file))?);
+        hasher.update([0]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Deploys a challenge into a new instance namespace that doesn't belong to an actor, rendered
+/// for `actor`. Returns false if the images of the challenge aren't built yet.
+#[tracing::instrument(skip(kube_client, repo_dir, builds, labels))]
+pub async fn deploy_unowned(
+    kube_client: &Client,
+    repo_dir: &Path,
+    builds: &BuildStore,
+    challenge_id: &str,
+    instance_id: &str,
+    actor: &str,
+    labels: BTreeMap<String, String>,
+    revision: &str,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let chall_dir = repo_dir.join("challs").join(challenge_id);
+    let mut challenge =
+        load_challenge_instance_from_repo(repo_dir, challenge_id, actor, instance_id).await?;
+    if matches!(
+        challenge.metadata.flag_validator,
+        FlagValidator::Dynamic { .. }
+    ) {
+        return Err("Challenges without an actor can't have a flag per actor".into());
+    }
+    if challenge
+        .compose
+        .services
+        .values()
+        .any(|svc| svc.build.is_some())
+    {
+        let commit = get_head_commit_info(repo_dir).map(|commit_info| commit_info.hash);
+        let state = commit
+            .as_ref()
+            .and_then(|commit| builds.for_commit(commit).remove(challenge_id))
+            .map(|build| build.state);
+        if state != Some(BuildState::Succeeded) {
+            tracing::debug!("Images are not built yet, not deploying");
+            return Ok(false);
+        }
+        use_built_images(
+            &mut challenge.compose,
+            &chall_dir,
+            challenge_id,
+            commit.as_deref(),
+        )?;
+    }
+    let flag = challenge.metadata.actor_flag(challenge_id, actor)?;
+
+    let api: Api<Namespace> = Api::all(kube_client.clone());
+    let instance_ns = full_instance_ns(challenge_id, instance_id);
+    let mut labels = labels;
+    labels.insert("challenge_id".to_string(), challenge_id.to_string());
+    let ns = Namespace {
+        metadata: kube::api::ObjectMeta {
+            name: Some(instance_ns.clone()),
+            labels: Some(labels),
+            annotations: Some([(REVISION_ANNOTATION.to_string(), revision.to_string())].into()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    api.create(&PostParams::default(), &ns).await?;
+
+    let working_dir = tempfile::tempdir()?;
+    let deployed = async {
+        tera::render_dir_recursively(&chall_dir, working_dir.path(), actor, false)?;
+        deploy_challenge(
+            kube_client,
+            &instance_ns,
+            challenge,
+            &std::env::var("EXPOSED_DOMAIN").unwrap_or("localhost".to_string()),
+            working_dir.path(),
+            actor,
+            instance_id,
+            flag.as_deref(),
+            InstancePriority::Player,
+        )
+        .await
+    }
+    .await
+    .map_err(|e| e.to_string());
+    if let Err(e) = deployed {
+        // Tried again from scratch in the next round
+        api.delete(&instance_ns, &DeleteParams::default()).await?;
+        return Err(e.into());
+    }
+    Ok(true)
+}
+
+/// Deploys shared challenges that aren't running yet, redeploys changed ones and deletes the
+/// deployments of challenges that are gone, no longer shared or not released yet
+pub async fn reconcile_shared_challenges(
+    kube_client: &Client,
+    repo_dir: &Path,
+    builds: &BuildStore,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let api: Api<Namespace> = Api::all(kube_client.clone());
+    let mut deployed = api
+        .list(&ListParams::default().labels("challenge_id,shared=true"))
+        .await?
+        .into_iter()
+        .filter_map(|ns| {
+            let challenge_id = ns.metadata.labels.as_ref()?.get("challenge_id")?.clone();
+            Some((challenge_id, ns))
+        })
+        .collect::<HashMap<_, _>>();
+
+    // Loaded with their release waves, so challenges in a later wave aren't deployed early
+    let challenges = load_challenges_from_repo(repo_dir, SHARED_ACTOR, false).await?;
+    let now = chrono::Utc::now().timestamp().max(0) as u64;
+    for (challenge_id, challenge) in challenges {
+        if challenge.metadata.deployment != DeploymentMode::Shared {
+            continue;
+        }
+        // Its hostname is predictable, so it only runs once the challenge is released. A
+        // deployment left over from an earlier release time is deleted below.
+        if let Some(release_time) = challenge.metadata.release_time
+            && release_time > now
+        {
+            continue;
+        }
+        let revision = challenge_revision(&repo_dir.join("challs").join(&challenge_id))?;
+        match deployed.remove(&challenge_id) {
+            // Deployed again once the old namespace is gone
+            Some(ns) if ns.metadata.deletion_timestamp.is_some() => {}
+            Some(ns)
+                if ns
+                    .metadata
+                    .annotations
+                    .as_ref()
+                    .and_then(|a| a.get(REVISION_ANNOTATION))
+                    == Some(&revision) => {}
+            Some(_) => {
+                tracing::info!("Challenge {} changed, redeploying it", challenge_id);
+                api.delete(
+                    &full_instance_ns(&challenge_id, SHARED_INSTANCE_ID),
+                    &DeleteParams::default(),
+                )
+                .await?;
+            }
+            None => {
+                if let Err(e) = deploy_unowned(
+                    kube_client,
+                    repo_dir,
+                    builds,
+                    &challenge_id,
+                    SHARED_INSTANCE_ID,
+                    SHARED_ACTOR,
+                    [("shared".to_string(), "true".to_string())].into(),
+                    &revision,
+                )
+                .await
+                {
+                    tracing::error!("Failed to deploy shared challenge {}: {}", challenge_id, e);
+                }
+            }
+        }
+    }
+
+    for (challenge_id, ns) in deployed {
+        if ns.metadata.deletion_timestamp.is_some() {
+            continue;
+        }
+        tracing::info!("Deleting shared deployment of challenge {}", challenge_id);
+        api.delete(
+            &full_instance_ns(&challenge_id, SHARED_INSTANCE_ID),
+            &DeleteParams::default(),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Periodically reconciles shared challenges in the background
+pub fn spawn_reconciler(kube_client: Client, repo_dir: PathBuf, builds: Arc<BuildStore>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RECONCILE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = reconcile_shared_challenges(&kube_client, &repo_dir, &builds).await {
+                tracing::error!("Failed to reconcile shared challenges: {}", e);
+            }
+        }
+    });
+}
@@ -2,13 +2,224 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use std::{cell::RefCell, rc::Rc};
+//! The JS runtime for scripts of the challenge repository, like points_fn and
+//! flag_validation_fn.
+//!
+//! Scripts run for requests like CheckFlag and ListChallenges, so they are limited in what they
+//! can use: loops, recursion and buffers are capped by the engine. Boa can't stop a script from
+//! the outside though, so scripts run in worker processes of the manager, and a worker whose
+//! script doesn't finish within [`SCRIPT_TIMEOUT`] is killed.
+
+use std::{
+    cell::RefCell,
+    io::{BufRead, Write},
+    process::Stdio,
+    rc::Rc,
+    sync::Mutex,
+    time::Duration,
+};
 
 use boa_engine::{
     JsError, JsNativeError, JsObject, JsResult, JsString, Module,
+    context::HostHooks,
+    gc::{Finalize, Trace},
     module::{ModuleLoader, Referrer},
 };
-use boa_runtime::RuntimeExtension;
+use boa_runtime::{ConsoleState, Logger, RuntimeExtension, extensions::ConsoleExtension};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, ChildStdout},
+    sync::Semaphore,
+};
+
+use crate::repo::{challenges::metadata, event_config};
+
+/// Iterations of a single loop
+const LOOP_ITERATION_LIMIT: u64 = 1_000_000;
+/// Depth of nested function calls
+const RECURSION_LIMIT: usize = 256;
+/// Size of an ArrayBuffer, in bytes
+const MAX_BUFFER_SIZE: u64 = 16 * 1024 * 1024;
+/// How long a script may take once a worker picked it up
+const SCRIPT_TIMEOUT: Duration = Duration::from_secs(2);
+/// First argument of the manager when it is started as a script worker
+const WORKER_MODE: &str = "script-worker";
+
+/// Scripts running at the same time, each in its own worker
+static SCRIPT_WORKERS: Semaphore = Semaphore::const_new(8);
+/// Workers that finished their last script in time, waiting for the next one
+static IDLE_WORKERS: Mutex<Vec<Worker>> = Mutex::new(Vec::new());
+
+#[derive(Error, Debug)]
+pub enum ScriptError {
+    #[error("Script did not finish within {0:?}")]
+    Timeout(Duration),
+    #[error("Script exceeded a runtime limit: {0}")]
+    LimitExceeded(String),
+    #[error("{0}")]
+    Failed(String),
+    #[error("Script worker failed: {0}")]
+    Worker(#[from] std::io::Error),
+}
+
+impl ScriptError {
+    fn from_error(error: Box<dyn std::error::Error>) -> Self {
+        match error.downcast_ref::<JsError>() {
+            Some(js_error)
+                if js_error
+                    .as_native()
+                    .is_some_and(|native| native.is_runtime_limit()) =>
+            {
+                ScriptError::LimitExceeded(js_error.to_string())
+            }
+            _ => ScriptError::Failed(error.to_string()),
+        }
+    }
+}
+
+/// A script with its inputs, sent to a worker
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ScriptJob {
+    /// points_fn of the event config
+    Points {
+        points_fn: String,
+        challenge_metadata: Box<metadata::CtfChallengeMetadata>,
+        total_solves: u32,
+        solve_index: u32,
+        total_competitors: u32,
+    },
+    /// flag_validation_fn of a challenge
+    FlagValidation {
+        flag_validation_fn: String,
+        input_flag: String,
+    },
+}
+
+impl ScriptJob {
+    fn run(
+        self,
+        engine: &mut boa_engine::Context,
+    ) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+        match self {
+            ScriptJob::Points {
+                points_fn,
+                challenge_metadata,
+                total_solves,
+                solve_index,
+                total_competitors,
+            } => Ok(event_config::run_points_fn(
+                engine,
+                &points_fn,
+                &challenge_metadata,
+                total_solves,
+                solve_index,
+                total_competitors,
+            )?
+            .into()),
+            ScriptJob::FlagValidation {
+                flag_validation_fn,
+                input_flag,
+            } => Ok(
+                metadata::run_flag_validation_fn(engine, &flag_validation_fn, &input_flag)?.into(),
+            ),
+        }
+    }
+}
+
+/// What a worker sends back for a job
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+enum ScriptResult {
+    Done(serde_json::Value),
+    LimitExceeded(String),
+    Failed(String),
+}
+
+/// Runs a job in a new context
+fn run_job(job: ScriptJob) -> ScriptResult {
+    match job.run(&mut create_boa_context()) {
+        Ok(value) => ScriptResult::Done(value),
+        Err(e) => match ScriptError::from_error(e) {
+            ScriptError::LimitExceeded(message) => ScriptResult::LimitExceeded(message),
+            e => ScriptResult::Failed(e.to_string()),
+        },
+    }
+}
+
+/// Whether the manager was started as a script worker
+pub fn is_worker() -> bool {
+    std::env::args().nth(1).as_deref() == Some(WORKER_MODE)
+}
+
+/// Runs the jobs read from stdin, one JSON object per line, and writes their results to stdout
+/// in the same way. Returns the exit code.
+pub fn run_worker() -> i32 {
+    let mut stdout = std::io::stdout().lock();
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            return 1;
+        };
+        let result = match serde_json::from_str(&line) {
+            Ok(job) => run_job(job),
+            Err(e) => ScriptResult::Failed(format!("Invalid script job: {}", e)),
+        };
+        let written = serde_json::to_writer(&mut stdout, &result)
+            .map_err(std::io::Error::from)
+            .and_then(|_| stdout.write_all(b"\n"))
+            .and_then(|_| stdout.flush());
+        if written.is_err() {
+            return 1;
+        }
+    }
+    0
+}
+
+/// A worker process, killed when it is dropped
+struct Worker {
+    _process: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Worker {
+    fn spawn() -> std::io::Result<Self> {
+        let mut process = tokio::process::Command::new(std::env::current_exe()?)
+            .arg(WORKER_MODE)
+            // Scripts have no business with the secrets of the manager
+            .env_clear()
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdin = process.stdin.take().expect("stdin of the worker is piped");
+        let stdout = process
+            .stdout
+            .take()
+            .expect("stdout of the worker is piped");
+        Ok(Worker {
+            _process: process,
+            stdin,
+            stdout: BufReader::new(stdout),
+        })
+    }
+
+    async fn run(&mut self, job: &[u8]) -> std::io::Result<ScriptResult> {
+        self.stdin.write_all(job).await?;
+        self.stdin.write_all(b"\n").await?;
+        self.stdin.flush().await?;
+        let mut line = String::new();
+        if self.stdout.read_line(&mut line).await? == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "Script worker exited",
+            ));
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+}
 
 struct DummyLoader;
 
@@ -34,14 +245,76 @@ impl ModuleLoader for DummyLoader {
     }
 }
 
+struct LimitedHooks;
+
+impl HostHooks for LimitedHooks {
+    fn max_buffer_size(&self, _context: &mut boa_engine::Context) -> u64 {
+        MAX_BUFFER_SIZE
+    }
+}
+
+/// Writes the console of scripts to stderr, stdout of workers is for their results
+#[derive(Debug)]
+struct StderrLogger;
+
+impl Finalize for StderrLogger {}
+
+// SAFETY: StderrLogger contains no garbage collected values
+unsafe impl Trace for StderrLogger {
+    boa_engine::gc::empty_trace!();
+}
+
+impl Logger for StderrLogger {
+    fn log(
+        &self,
+        msg: String,
+        state: &ConsoleState,
+        _context: &mut boa_engine::Context,
+    ) -> JsResult<()> {
+        let indent = state.indent();
+        writeln!(std::io::stderr(), "{msg:>indent$}").map_err(JsError::from_rust)
+    }
+
+    fn info(
+        &self,
+        msg: String,
+        state: &ConsoleState,
+        context: &mut boa_engine::Context,
+    ) -> JsResult<()> {
+        self.log(msg, state, context)
+    }
+
+    fn warn(
+        &self,
+        msg: String,
+        state: &ConsoleState,
+        context: &mut boa_engine::Context,
+    ) -> JsResult<()> {
+        self.log(msg, state, context)
+    }
+
+    fn error(
+        &self,
+        msg: String,
+        state: &ConsoleState,
+        context: &mut boa_engine::Context,
+    ) -> JsResult<()> {
+        self.log(msg, state, context)
+    }
+}
+
 pub fn create_boa_context() -> boa_engine::Context {
     let mut ctx = boa_engine::Context::builder()
         .module_loader(Rc::new(DummyLoader))
+        .host_hooks(Rc::new(LimitedHooks))
         .build()
         .unwrap();
+    let limits = ctx.runtime_limits_mut();
+    limits.set_loop_iteration_limit(LOOP_ITERATION_LIMIT);
+    limits.set_recursion_limit(RECURSION_LIMIT);
 
     (
-        boa_runtime::extensions::ConsoleExtension::default(),
+        ConsoleExtension(StderrLogger),
         boa_runtime::extensions::EncodingExtension,
         // boa_runtime::extensions::MicrotaskExtension,
         boa_runtime::extensions::TimeoutExtension,
@@ -54,3 +327,66 @@ pub fn create_boa_context() -> boa_engine::Context {
 
     ctx
 }
+
+/// Runs a job in a worker, killing the worker if the script doesn't finish within
+/// [`SCRIPT_TIMEOUT`]
+pub async fn run_script<T: DeserializeOwned>(job: ScriptJob) -> Result<T, ScriptError> {
+    let job = serde_json::to_vec(&job).map_err(|e| ScriptError::Failed(e.to_string()))?;
+    let _permit = SCRIPT_WORKERS
+        .acquire()
+        .await
+        .expect("Script semaphore closed");
+    let idle = IDLE_WORKERS.lock().unwrap().pop();
+    let mut worker = match idle {
+        Some(worker) => worker,
+        None => Worker::spawn()?,
+    };
+    // A worker that timed out or failed is dropped, which kills it
+    let result = tokio::time::timeout(SCRIPT_TIMEOUT, worker.run(&job))
+        .await
+        .map_err(|_| ScriptError::Timeout(SCRIPT_TIMEOUT))??;
+    IDLE_WORKERS.lock().unwrap().push(worker);
+    match result {
+        ScriptResult::Done(value) => {
+            serde_json::from_value(value).map_err(|e| ScriptError::Failed(e.to_string()))
+        }
+        ScriptResult::LimitExceeded(message) => Err(ScriptError::LimitExceeded(message)),
+        ScriptResult::Failed(message) => Err(ScriptError::Failed(message)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(body: &str) -> ScriptResult {
+        run_job(ScriptJob::FlagValidation {
+            flag_validation_fn: format!("setFlagValidationFunction((flag) => {{ {} }})", body),
+            input_flag: "flag{test}".to_string(),
+        })
+    }
+
+    #[test]
+    fn test_run_job() {
+        assert!(matches!(
+            validate("return flag === 'flag{test}';"),
+            ScriptResult::Done(serde_json::Value::Bool(true))
+        ));
+        assert!(matches!(
+            validate("throw new Error('nope');"),
+            ScriptResult::Failed(_)
+        ));
+        assert!(matches!(
+            validate("while (true) {}"),
+            ScriptResult::LimitExceeded(_)
+        ));
+        assert!(matches!(
+            validate("function f() { return f(); } return f();"),
+            ScriptResult::LimitExceeded(_)
+        ));
+        assert!(matches!(
+            validate("new ArrayBuffer(1024 * 1024 * 1024); return true;"),
+            ScriptResult::Failed(_)
+        ));
+    }
+}
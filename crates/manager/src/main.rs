@@ -2,38 +2,115 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
-use crate::grpc::{
-    ChallengeManager, ChallengesServiceServer, RepoManager, RepositoryServiceServer,
+use crate::{
+    grpc::{ChallengeManager, ChallengesServiceServer, RepoManager, RepositoryServiceServer},
+    repo::builds::BuildStore,
 };
 
+mod error_reporting;
 mod grpc;
 mod instances;
 mod js;
 mod repo;
 mod ssh;
+mod telemetry;
 mod utils;
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    if js::is_worker() {
+        std::process::exit(js::run_worker());
+    }
+    if let Some(args) = repo::challenges::validate::cli_args() {
+        std::process::exit(repo::challenges::validate::run_cli(args).await);
+    }
+    let _tracer_provider = telemetry::init_tracing();
     rustls::crypto::aws_lc_rs::default_provider().install_default().expect("Failed to set AWS-LC-RS as default TLS provider");
+    error_reporting::init(
+        std::env::var("SENTRY_DSN").ok().as_deref(),
+        std::env::var("SENTRY_ENVIRONMENT").ok(),
+    );
     let kube_client = kube::Client::try_default()
         .await
         .expect("Failed to create kube client");
+    let repo_dir = PathBuf::from(std::env::var("REPO_DIR").unwrap_or_else(|_| "/data/repo".into()));
+    let git_url = std::env::var("GIT_URL").expect("GIT_URL must be set");
+    let git_branch = std::env::var("GIT_BRANCH").expect("GIT_BRANCH must be set");
+    let builds: Arc<BuildStore> = Default::default();
+    let image_builder = repo::image_builder::BuildConfig::from_env().map(|config| {
+        Arc::new(repo::image_builder::ImageBuilder {
+            kube_client: kube_client.clone(),
+            config,
+        })
+    });
+    if instances::lifetime::LifetimeConfig::from_env().is_some() {
+        instances::lifetime::spawn_reaper(kube_client.clone());
+    }
+    if instances::priority::manage_priority_classes()
+        && let Err(e) = instances::priority::ensure_priority_classes(&kube_client).await
+    {
+        tracing::error!("Failed to create priority classes of instances: {}", e);
+    }
+    instances::shared::spawn_reconciler(kube_client.clone(), repo_dir.clone(), builds.clone());
+    instances::pool::spawn_replenisher(kube_client.clone(), repo_dir.clone(), builds.clone());
+    instances::gc::spawn_collector(kube_client.clone(), repo_dir.clone());
+    let packages = repo::challenges::packages::PackageStore::from_env().map(Arc::new);
+    if let Some(packages) = &packages {
+        packages.clone().spawn_release_packer(repo_dir.clone());
+    }
     let challenge_manager = ChallengeManager {
-        repo_dir: PathBuf::from(std::env::var("REPO_DIR").unwrap_or_else(|_| "/data/repo".into())),
+        repo_dir: repo_dir.clone(),
         kube_client,
+        builds: builds.clone(),
+        admission_queue: Default::default(),
+        packages: packages.clone(),
     };
+    if instances::admission::admission_control_enabled() {
+        challenge_manager.clone().spawn_admission_worker();
+    }
+    let revision_pin = repo::revision::RevisionPin::load(&repo_dir);
+    let release_scheduler = repo::release_waves::ReleaseScheduler::spawn(repo_dir.clone());
     let repo_manager = RepoManager {
-        repo_dir: PathBuf::from(std::env::var("REPO_DIR").unwrap_or_else(|_| "/data/repo".into())),
-        git_url: std::env::var("GIT_URL").expect("GIT_URL must be set"),
-        git_branch: std::env::var("GIT_BRANCH").expect("GIT_BRANCH must be set"),
+        repo_dir,
+        git_url,
+        git_branch,
+        git_auth: repo::GitAuth::from_env().expect("Failed to read git credentials"),
+        sparse_checkout: repo::SparseCheckout::from_env(),
+        revision_pin,
+        builds,
+        image_builder,
+        packages,
+        release_scheduler,
+        sync_lock: Default::default(),
     };
+    // Build states are only kept in memory, so the challenges of the current commit are checked again
+    // (and packed, in case the repo was synced while no package store was configured)
+    if let Some(commit_info) = repo::get_head_commit_info(&repo_manager.repo_dir) {
+        let builds = repo_manager.builds.clone();
+        let image_builder = repo_manager.image_builder.clone();
+        let packages = repo_manager.packages.clone();
+        let repo_dir = repo_manager.repo_dir.clone();
+        tokio::spawn(async move {
+            if let Some(packages) = packages
+                && let Err(e) = packages.pack_challenges(&repo_dir).await
+            {
+                tracing::error!("Failed to pack challenges: {}", e);
+            }
+            if let Err(e) = builds
+                .build_challenges(&repo_dir, &commit_info.hash, image_builder.as_deref())
+                .await
+            {
+                tracing::error!("Failed to build challenges: {}", e);
+            }
+        });
+    }
     let addr = "[::]:50051".parse().unwrap();
     println!("Plfanzen manager listening on {}", addr);
     tonic::transport::Server::builder()
+        .trace_fn(telemetry::grpc_request_span)
+        .layer(error_reporting::ReportInternalErrorsLayer)
         .add_service(ChallengesServiceServer::new(challenge_manager))
         .add_service(RepositoryServiceServer::new(repo_manager))
         .serve(addr)
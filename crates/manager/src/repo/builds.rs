@@ -0,0 +1,200 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::{collections::HashMap, path::Path, sync::Mutex};
+
+use chrono::{DateTime, Utc};
+
+use crate::repo::{
+    challenges::loader::load_challenge_from_dir,
+    image_builder::{ImageBuildSpec, ImageBuilder},
+};
+
+/// Logs are cut to this many bytes from the end, the last lines usually contain the error
+const MAX_LOG_EXCERPT_LEN: usize = 4000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildState {
+    /// The challenge only uses existing images
+    NotRequired,
+    Queued,
+    InProgress,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildRecord {
+    pub state: BuildState,
+    /// End of the build log, or the error that prevented the build
+    pub log_excerpt: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Build states of challenges, keyed by challenge ID and the commit they were built from.
+///
+/// Only kept in memory, every sync checks all challenges of the new commit again anyway.
+#[derive(Default)]
+pub struct BuildStore {
+    records: Mutex<HashMap<(String, String), BuildRecord>>,
+}
+
+/// Keeps the end of a log, cut at a line break if possible.
+fn excerpt(log: &str) -> String {
+    let log = log.trim_end();
+    if log.len() <= MAX_LOG_EXCERPT_LEN {
+        return log.to_string();
+    }
+    let mut start = log.len() - MAX_LOG_EXCERPT_LEN;
+    while !log.is_char_boundary(start) {
+        start += 1;
+    }
+    let tail = &log[start..];
+    match tail.split_once('\n') {
+        Some((_, rest)) if !rest.is_empty() => rest.to_string(),
+        _ => tail.to_string(),
+    }
+}
+
+impl BuildStore {
+    pub fn set(&self, challenge_id: &str, commit: &str, state: BuildState, log: Option<&str>) {
+        self.records
+            .lock()
+            .expect("Build store lock poisoned")
+            .insert(
+                (challenge_id.to_string(), commit.to_string()),
+                BuildRecord {
+                    state,
+                    log_excerpt: log.map(excerpt),
+                    updated_at: Utc::now(),
+                },
+            );
+    }
+
+    /// Build states of all challenges at the given commit
+    pub fn for_commit(&self, commit: &str) -> HashMap<String, BuildRecord> {
+        self.records
+            .lock()
+            .expect("Build store lock poisoned")
+            .iter()
+            .filter(|((_, c), _)| c == commit)
+            .map(|((challenge_id, _), record)| (challenge_id.clone(), record.clone()))
+            .collect()
+    }
+
+    /// Forgets the states of commits other than the given one, e.g. after a sync.
+    pub fn retain_commit(&self, commit: &str) {
+        self.records
+            .lock()
+            .expect("Build store lock poisoned")
+            .retain(|(_, c), _| c == commit);
+    }
+
+    /// Checks every challenge of the repository at the given commit and builds the images of
+    /// services with a build section, recording whether each challenge can be deployed.
+    ///
+    /// Without an image builder, challenges that need one fail.
+    pub async fn build_challenges(
+        &self,
+        repo_dir: &Path,
+        commit: &str,
+        builder: Option<&ImageBuilder>,
+    ) -> std::io::Result<()> {
+        let challenges_dir = repo_dir.join("challs");
+        if !challenges_dir.is_dir() {
+            return Ok(());
+        }
+        let mut challenge_dirs = Vec::new();
+        for entry in std::fs::read_dir(challenges_dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                let challenge_id = path.file_name().unwrap().to_string_lossy().to_string();
+                self.set(&challenge_id, commit, BuildState::Queued, None);
+                challenge_dirs.push((challenge_id, path));
+            }
+        }
+        // One challenge after another, so a sync doesn't flood the cluster with build jobs
+        for (challenge_id, path) in challenge_dirs {
+            self.set(&challenge_id, commit, BuildState::InProgress, None);
+            let (state, log) = self.build_challenge(&challenge_id, &path, builder).await;
+            if state == BuildState::Failed {
+                tracing::warn!("Challenge {} failed to build", challenge_id);
+            }
+            self.set(&challenge_id, commit, state, log.as_deref());
+        }
+        Ok(())
+    }
+
+    async fn build_challenge(
+        &self,
+        challenge_id: &str,
+        path: &Path,
+        builder: Option<&ImageBuilder>,
+    ) -> (BuildState, Option<String>) {
+        let challenge = match load_challenge_from_dir(path, "", false).await {
+            Ok(challenge) => challenge,
+            Err(e) => return (BuildState::Failed, Some(e.to_string())),
+        };
+        let mut specs = Vec::new();
+        for (svc_id, svc) in &challenge.compose.services {
+            match ImageBuildSpec::from_service(svc, path) {
+                Ok(Some(spec)) => specs.push((svc_id.to_string(), spec)),
+                Ok(None) => {}
+                Err(e) => return (BuildState::Failed, Some(format!("{}: {}", svc_id, e))),
+            }
+        }
+        if specs.is_empty() {
+            return (BuildState::NotRequired, None);
+        }
+        let Some(builder) = builder else {
+            return (
+                BuildState::Failed,
+                Some("Building images is not enabled, set BUILD_REGISTRY".to_string()),
+            );
+        };
+        let mut logs = Vec::new();
+        for (svc_id, spec) in specs {
+            match builder.build(path, challenge_id, &svc_id, &spec).await {
+                Ok((tag, log)) => logs.push(format!("==> {} built as {}\n{}", svc_id, tag, log)),
+                Err(log) => {
+                    logs.push(format!("==> {} failed to build\n{}", svc_id, log));
+                    return (BuildState::Failed, Some(logs.join("\n")));
+                }
+            }
+        }
+        (BuildState::Succeeded, Some(logs.join("\n")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_are_per_commit() {
+        let store = BuildStore::default();
+        store.set("web", "aaa", BuildState::Failed, Some("error"));
+        store.set("web", "bbb", BuildState::Succeeded, None);
+        store.set("pwn", "bbb", BuildState::InProgress, None);
+
+        let builds = store.for_commit("bbb");
+        assert_eq!(builds.len(), 2);
+        assert_eq!(builds["web"].state, BuildState::Succeeded);
+
+        store.retain_commit("bbb");
+        assert!(store.for_commit("aaa").is_empty());
+    }
+
+    #[test]
+    fn test_excerpt_keeps_end_of_log() {
+        let log = (0..1000)
+            .map(|i| format!("line {i}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let tail = excerpt(&log);
+        assert!(tail.len() <= MAX_LOG_EXCERPT_LEN);
+        assert!(tail.starts_with("line "));
+        assert!(tail.ends_with("line 999"));
+    }
+}
@@ -11,6 +11,7 @@ use crate::repo::challenges::{compose::service::networking::HasNetworkPolicy, vm
 mod deployment;
 mod ingress;
 pub mod networking;
+pub mod scheduling;
 mod service;
 mod ssh;
 
@@ -51,6 +52,26 @@ pub trait AsDeployment {
         working_dir: &Path,
     ) -> Result<k8s_openapi::api::apps::v1::Deployment, ComposeServiceError>;
     fn requires_data_pvc(&self) -> bool;
+    /// Whether the service sets `x-ctf-stateful: true` and is deployed as a StatefulSet
+    fn is_stateful(&self) -> bool;
+    /// Builds a StatefulSet, the named volumes of the service are created from the given claim
+    /// templates for each replica instead of being shared.
+    fn as_stateful_set(
+        &self,
+        id: String,
+        working_dir: &Path,
+        volume_claim_templates: Vec<k8s_openapi::api::core::v1::PersistentVolumeClaim>,
+    ) -> Result<k8s_openapi::api::apps::v1::StatefulSet, ComposeServiceError>;
+    /// Resources a single pod of the service requests, limits count as requests where no request
+    /// is set, like Kubernetes does
+    fn pod_resource_requests(
+        &self,
+    ) -> BTreeMap<String, k8s_openapi::apimachinery::pkg::api::resource::Quantity>;
+    /// Number of pods the service runs
+    fn replicas(&self) -> i32;
+    /// A PodDisruptionBudget letting node drains evict only one pod of the service at a time,
+    /// None if the service runs a single replica
+    fn as_pdb(&self, id: String) -> Option<k8s_openapi::api::policy::v1::PodDisruptionBudget>;
 }
 
 pub trait AsService {
@@ -3,6 +3,7 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
 mod container;
+mod devices;
 mod environment;
 mod security;
 mod validation;
@@ -10,9 +11,12 @@ mod volumes;
 
 use std::path::Path;
 
+use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
 use kube::api::ObjectMeta;
 
-use crate::repo::challenges::compose::service::{AsDeployment, ComposeServiceError, HasLabels};
+use crate::repo::challenges::compose::service::{
+    AsDeployment, ComposeServiceError, HasLabels, networking, scheduling::HasScheduling,
+};
 
 impl AsDeployment for compose_spec::Service {
     fn as_deployment(
@@ -70,6 +74,53 @@ impl AsDeployment for compose_spec::Service {
         })
     }
 
+    fn is_stateful(&self) -> bool {
+        self.extensions
+            .get("x-ctf-stateful")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+    }
+
+    fn as_stateful_set(
+        &self,
+        id: String,
+        working_dir: &Path,
+        volume_claim_templates: Vec<k8s_openapi::api::core::v1::PersistentVolumeClaim>,
+    ) -> Result<k8s_openapi::api::apps::v1::StatefulSet, ComposeServiceError> {
+        let deployment = self.as_deployment(id.clone(), working_dir)?;
+        let spec = deployment.spec.unwrap_or_default();
+        let mut template = spec.template;
+        // The claims of the templates are mounted by name, they must not be pod volumes as well
+        if let Some(volumes) = template
+            .spec
+            .as_mut()
+            .and_then(|pod_spec| pod_spec.volumes.as_mut())
+        {
+            volumes.retain(|volume| {
+                !volume_claim_templates
+                    .iter()
+                    .any(|claim| claim.metadata.name.as_ref() == Some(&volume.name))
+            });
+        }
+        Ok(k8s_openapi::api::apps::v1::StatefulSet {
+            metadata: deployment.metadata,
+            spec: Some(k8s_openapi::api::apps::v1::StatefulSetSpec {
+                replicas: spec.replicas,
+                selector: spec.selector,
+                // The internal service of each compose service is headless
+                service_name: Some(id),
+                template,
+                volume_claim_templates: if volume_claim_templates.is_empty() {
+                    None
+                } else {
+                    Some(volume_claim_templates)
+                },
+                ..Default::default()
+            }),
+            status: None,
+        })
+    }
+
     fn requires_data_pvc(&self) -> bool {
         for vol in compose_spec::service::volumes::into_long_iter(self.volumes.clone()) {
             if let compose_spec::service::volumes::Mount::Bind(b) = vol {
@@ -81,6 +132,47 @@ impl AsDeployment for compose_spec::Service {
         }
         false
     }
+
+    fn pod_resource_requests(&self) -> std::collections::BTreeMap<String, Quantity> {
+        let Ok(Some(resources)) = container::build_resource_requirements(self) else {
+            return Default::default();
+        };
+        let mut requests = resources.limits.unwrap_or_default();
+        requests.extend(resources.requests.unwrap_or_default());
+        requests
+    }
+
+    fn replicas(&self) -> i32 {
+        calculate_replicas(self).ok().flatten().unwrap_or(1)
+    }
+
+    fn as_pdb(&self, id: String) -> Option<k8s_openapi::api::policy::v1::PodDisruptionBudget> {
+        // Draining the node of a single pod takes the service down either way, a budget would
+        // only block the drain
+        if self.replicas() <= 1 {
+            return None;
+        }
+        Some(k8s_openapi::api::policy::v1::PodDisruptionBudget {
+            metadata: ObjectMeta {
+                name: Some(id.clone()),
+                labels: Some(self.get_labels(&id)),
+                ..Default::default()
+            },
+            spec: Some(k8s_openapi::api::policy::v1::PodDisruptionBudgetSpec {
+                max_unavailable: Some(
+                    k8s_openapi::apimachinery::pkg::util::intstr::IntOrString::Int(1),
+                ),
+                selector: Some(
+                    k8s_openapi::apimachinery::pkg::apis::meta::v1::LabelSelector {
+                        match_labels: Some([("compose-service-id".to_string(), id)].into()),
+                        ..Default::default()
+                    },
+                ),
+                ..Default::default()
+            }),
+            status: None,
+        })
+    }
 }
 
 fn calculate_replicas(svc: &compose_spec::Service) -> Result<Option<i32>, ComposeServiceError> {
@@ -124,6 +216,13 @@ fn build_pod_labels(
     [("compose-service-id".to_string(), id.to_string())]
         .iter()
         .cloned()
+        // Selected by the network policies of services sharing a network with this one
+        .chain(
+            networking::attached_networks(svc)
+                .unwrap_or_default()
+                .iter()
+                .map(|network| (networking::network_label(network), "true".to_string())),
+        )
         .chain(
             svc.labels
                 .clone()
@@ -161,10 +260,26 @@ fn build_pod_spec(
     let volume_mounts = volumes::build_volume_mounts(svc)?;
     let security_context = security::build_container_security_context(svc)?;
     let container = container::build_container_spec(svc, id, env, volume_mounts, security_context)?;
+    let mut init_containers = container::build_init_containers(svc).unwrap_or_default();
+    init_containers.extend(container::build_dependency_gates(svc)?);
+    let scheduling = svc.get_scheduling()?;
+    let uses_gpu = container
+        .resources
+        .as_ref()
+        .and_then(|resources| resources.limits.as_ref())
+        .is_some_and(devices::uses_gpu);
+    let mut node_selector = if uses_gpu {
+        devices::gpu_node_selector()
+    } else {
+        Default::default()
+    };
+    node_selector.extend(scheduling.node_selector().unwrap_or_default());
 
     Ok(k8s_openapi::api::core::v1::PodSpec {
         runtime_class_name: if svc.privileged || !svc.cap_add.is_empty() {
             Some("kata".to_string())
+        } else if uses_gpu && svc.runtime.is_none() {
+            devices::gpu_runtime_class()
         } else {
             svc.runtime.clone()
         },
@@ -181,10 +296,21 @@ fn build_pod_spec(
             // Otherwise, stop_signal can not be used
             name: "linux".to_string(),
         }),
-        init_containers: container::build_init_containers(svc),
+        init_containers: if init_containers.is_empty() {
+            None
+        } else {
+            Some(init_containers)
+        },
         enable_service_links: Some(false),
         automount_service_account_token: Some(false),
         security_context: security::build_pod_security_context(svc),
+        node_selector: if node_selector.is_empty() {
+            None
+        } else {
+            Some(node_selector)
+        },
+        tolerations: scheduling.tolerations(),
+        affinity: scheduling.affinity,
         containers: vec![container],
         ..Default::default()
     })
@@ -242,3 +368,27 @@ fn build_dns_config(
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pdb_only_for_multiple_replicas() {
+        let single: compose_spec::Service = serde_yaml::from_str("image: web\n").unwrap();
+        assert!(single.as_pdb("web".to_string()).is_none());
+
+        let replicated: compose_spec::Service =
+            serde_yaml::from_str("image: web\ndeploy:\n  replicas: 3\n").unwrap();
+        let pdb = replicated.as_pdb("web".to_string()).unwrap();
+        let spec = pdb.spec.unwrap();
+        assert_eq!(
+            spec.max_unavailable,
+            Some(k8s_openapi::apimachinery::pkg::util::intstr::IntOrString::Int(1))
+        );
+        assert_eq!(
+            spec.selector.unwrap().match_labels.unwrap()["compose-service-id"],
+            "web"
+        );
+    }
+}
@@ -2,7 +2,13 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use crate::{repo::challenges::compose::service::ComposeServiceError, utils::split_with_quotes};
+use crate::{
+    repo::challenges::{
+        compose::service::{ComposeServiceError, deployment::devices},
+        quota::ContainerDefaults,
+    },
+    utils::split_with_quotes,
+};
 
 /// Builds the container spec from compose service configuration
 pub fn build_container_spec(
@@ -29,13 +35,15 @@ pub fn build_container_spec(
                 stop_signal: Some(signal.clone()),
                 ..Default::default()
             }),
-        resources: build_resource_requirements(svc),
+        resources: build_resource_requirements(svc)?,
         ports: build_container_ports(svc),
         security_context,
         command: build_command(svc),
         args: build_args(svc),
         env: Some(env),
         volume_mounts: Some(volume_mounts),
+        readiness_probe: build_probe(svc, false),
+        liveness_probe: build_probe(svc, true),
         ..Default::default()
     })
 }
@@ -49,9 +57,18 @@ fn convert_pull_policy(p: &compose_spec::service::PullPolicy) -> String {
     }
 }
 
-fn build_resource_requirements(
+/// Resources of the container, with the defaults of the manager for resources the service doesn't
+/// set anything for. Services can opt out of the defaults with `x-ctf-default-resources: false`.
+pub fn build_resource_requirements(
     svc: &compose_spec::Service,
-) -> Option<k8s_openapi::api::core::v1::ResourceRequirements> {
+) -> Result<Option<k8s_openapi::api::core::v1::ResourceRequirements>, ComposeServiceError> {
+    resource_requirements(svc, &ContainerDefaults::from_env())
+}
+
+fn resource_requirements(
+    svc: &compose_spec::Service,
+    defaults: &ContainerDefaults,
+) -> Result<Option<k8s_openapi::api::core::v1::ResourceRequirements>, ComposeServiceError> {
     let mut requests = std::collections::BTreeMap::new();
     let mut limits = std::collections::BTreeMap::new();
 
@@ -82,7 +99,28 @@ fn build_resource_requirements(
         );
     }
 
-    if requests.is_empty() && limits.is_empty() {
+    let use_defaults = svc
+        .extensions
+        .get("x-ctf-default-resources")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true);
+    if use_defaults {
+        for resource in ["cpu", "memory"] {
+            if requests.contains_key(resource) || limits.contains_key(resource) {
+                continue;
+            }
+            if let Some(request) = defaults.requests.get(resource) {
+                requests.insert(resource.to_string(), request.clone());
+            }
+            if let Some(limit) = defaults.limits.get(resource) {
+                limits.insert(resource.to_string(), limit.clone());
+            }
+        }
+    }
+
+    limits.extend(devices::extended_resources(svc)?);
+
+    Ok(if requests.is_empty() && limits.is_empty() {
         None
     } else {
         Some(k8s_openapi::api::core::v1::ResourceRequirements {
@@ -98,7 +136,7 @@ fn build_resource_requirements(
             },
             ..Default::default()
         })
-    }
+    })
 }
 
 fn build_container_ports(
@@ -126,6 +164,64 @@ fn build_container_ports(
     }
 }
 
+/// Translates the compose healthcheck into a probe, using the Docker defaults for unset values.
+///
+/// The liveness probe restarts the container once the check failed `retries` times in a row,
+/// like Docker marks it unhealthy, failures during `start_period` are not counted. The readiness
+/// probe starts right away, so the instance becomes ready with the first successful check.
+fn build_probe(
+    svc: &compose_spec::Service,
+    liveness: bool,
+) -> Option<k8s_openapi::api::core::v1::Probe> {
+    let compose_spec::service::Healthcheck::Command(healthcheck) = svc.healthcheck.as_ref()? else {
+        return None;
+    };
+    let command = match healthcheck.test.as_ref()? {
+        compose_spec::service::healthcheck::Test::Command(command) => command.clone(),
+        compose_spec::service::healthcheck::Test::ShellCommand(command) => {
+            vec!["/bin/sh".to_string(), "-c".to_string(), command.clone()]
+        }
+    };
+    if command.is_empty() {
+        return None;
+    }
+    let seconds =
+        |duration: std::time::Duration| duration.as_secs().clamp(1, i32::MAX as u64) as i32;
+    let interval = healthcheck
+        .interval
+        .unwrap_or(std::time::Duration::from_secs(30));
+    Some(k8s_openapi::api::core::v1::Probe {
+        exec: Some(k8s_openapi::api::core::v1::ExecAction {
+            command: Some(command),
+        }),
+        period_seconds: Some(if liveness {
+            seconds(interval)
+        } else {
+            // Don't wait a whole interval for the instance to become ready
+            seconds(
+                healthcheck
+                    .start_interval
+                    .unwrap_or(std::time::Duration::from_secs(5))
+                    .min(interval),
+            )
+        }),
+        timeout_seconds: Some(seconds(
+            healthcheck
+                .timeout
+                .unwrap_or(std::time::Duration::from_secs(30)),
+        )),
+        failure_threshold: Some(healthcheck.retries.unwrap_or(3).clamp(1, i32::MAX as u64) as i32),
+        initial_delay_seconds: if liveness {
+            healthcheck
+                .start_period
+                .map(|period| period.as_secs().min(i32::MAX as u64) as i32)
+        } else {
+            None
+        },
+        ..Default::default()
+    })
+}
+
 fn build_command(svc: &compose_spec::Service) -> Option<Vec<String>> {
     if svc.init {
         // When init is true, wrap with tini
@@ -178,6 +274,58 @@ fn build_args(svc: &compose_spec::Service) -> Option<Vec<String>> {
     }
 }
 
+/// Image of the init containers waiting for dependencies, only needs a shell and nslookup
+const DEPENDENCY_GATE_IMAGE: &str = "busybox:1.37";
+
+/// Builds init containers that wait until the services listed in depends_on are ready.
+///
+/// The internal services are headless, so their names only resolve once a pod behind them is
+/// ready. That is as soon as it runs, or once its healthcheck passed if it has one, which covers
+/// both service_started and service_healthy.
+pub fn build_dependency_gates(
+    svc: &compose_spec::Service,
+) -> Result<Vec<k8s_openapi::api::core::v1::Container>, ComposeServiceError> {
+    let dependencies: Vec<String> = match &svc.depends_on {
+        compose_spec::ShortOrLong::Short(services) => {
+            services.iter().map(|id| id.to_string()).collect()
+        }
+        compose_spec::ShortOrLong::Long(services) => {
+            let mut dependencies = Vec::new();
+            for (id, dependency) in services {
+                if dependency.condition
+                    == compose_spec::service::Condition::ServiceCompletedSuccessfully
+                {
+                    return Err(ComposeServiceError::PropertyNotSupported(format!(
+                        "depends_on.{}.condition: service_completed_successfully",
+                        id
+                    )));
+                }
+                // Optional dependencies don't hold up the start
+                if dependency.required {
+                    dependencies.push(id.to_string());
+                }
+            }
+            dependencies
+        }
+    };
+    Ok(dependencies
+        .into_iter()
+        .map(|dependency| k8s_openapi::api::core::v1::Container {
+            name: format!("wait-for-{}", dependency),
+            image: Some(DEPENDENCY_GATE_IMAGE.to_string()),
+            command: Some(vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                format!(
+                    "until nslookup {0} > /dev/null 2>&1; do echo 'Waiting for {0}'; sleep 2; done",
+                    dependency
+                ),
+            ]),
+            ..Default::default()
+        })
+        .collect())
+}
+
 /// Builds init containers for tini installation if needed
 pub fn build_init_containers(
     svc: &compose_spec::Service,
@@ -203,3 +351,83 @@ pub fn build_init_containers(
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_healthcheck_probes() {
+        let svc: compose_spec::Service = serde_yaml::from_str(
+            "image: nginx\nhealthcheck:\n  test: curl -f http://localhost\n  interval: 10s\n  retries: 5\n  start_period: 1m\n",
+        )
+        .unwrap();
+        let liveness = build_probe(&svc, true).unwrap();
+        assert_eq!(
+            liveness.exec.unwrap().command.unwrap(),
+            vec!["/bin/sh", "-c", "curl -f http://localhost"]
+        );
+        assert_eq!(liveness.period_seconds, Some(10));
+        assert_eq!(liveness.timeout_seconds, Some(30));
+        assert_eq!(liveness.failure_threshold, Some(5));
+        assert_eq!(liveness.initial_delay_seconds, Some(60));
+
+        let readiness = build_probe(&svc, false).unwrap();
+        assert_eq!(readiness.period_seconds, Some(5));
+        assert_eq!(readiness.initial_delay_seconds, None);
+    }
+
+    #[test]
+    fn test_default_resources() {
+        use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+
+        let defaults = ContainerDefaults {
+            requests: [("cpu".to_string(), Quantity("100m".to_string()))].into(),
+            limits: [
+                ("cpu".to_string(), Quantity("1".to_string())),
+                ("memory".to_string(), Quantity("256Mi".to_string())),
+            ]
+            .into(),
+        };
+        let svc: compose_spec::Service =
+            serde_yaml::from_str("image: app\nmem_limit: 1g\n").unwrap();
+        let resources = resource_requirements(&svc, &defaults).unwrap().unwrap();
+        let limits = resources.limits.unwrap();
+        assert_eq!(limits["cpu"], Quantity("1".to_string()));
+        assert_ne!(limits["memory"], Quantity("256Mi".to_string()));
+        assert_eq!(
+            resources.requests.unwrap()["cpu"],
+            Quantity("100m".to_string())
+        );
+
+        let svc: compose_spec::Service =
+            serde_yaml::from_str("image: app\nx-ctf-default-resources: false\n").unwrap();
+        assert_eq!(resource_requirements(&svc, &defaults).unwrap(), None);
+    }
+
+    #[test]
+    fn test_dependency_gates() {
+        let svc: compose_spec::Service = serde_yaml::from_str(
+            "image: app\ndepends_on:\n  db:\n    condition: service_healthy\n  cache:\n    condition: service_started\n    required: false\n",
+        )
+        .unwrap();
+        let gates = build_dependency_gates(&svc).unwrap();
+        assert_eq!(gates.len(), 1);
+        assert_eq!(gates[0].name, "wait-for-db");
+
+        let svc: compose_spec::Service = serde_yaml::from_str(
+            "image: app\ndepends_on:\n  migrate:\n    condition: service_completed_successfully\n",
+        )
+        .unwrap();
+        assert!(build_dependency_gates(&svc).is_err());
+    }
+
+    #[test]
+    fn test_disabled_healthcheck() {
+        let svc: compose_spec::Service =
+            serde_yaml::from_str("image: nginx\nhealthcheck:\n  disable: true\n").unwrap();
+        assert!(build_probe(&svc, true).is_none());
+        let svc: compose_spec::Service = serde_yaml::from_str("image: nginx\n").unwrap();
+        assert!(build_probe(&svc, false).is_none());
+    }
+}
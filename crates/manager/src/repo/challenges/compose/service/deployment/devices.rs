@@ -0,0 +1,148 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! GPUs and other extended resources, from `deploy.resources.reservations.devices` or the
+//! `x-ctf-resources` extension, which maps resource names to counts:
+//!
+//! ```yaml
+//! x-ctf-resources:
+//!   nvidia.com/gpu: 1
+//! ```
+//!
+//! Pods using GPUs get the runtime class from GPU_RUNTIME_CLASS and the node selector from
+//! GPU_NODE_SELECTOR (like `nvidia.com/gpu.present=true`), if set.
+
+use std::collections::BTreeMap;
+
+use compose_spec::service::deploy::resources::{Capability, Count};
+use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+
+use crate::repo::challenges::compose::service::ComposeServiceError;
+
+/// Kubernetes resource of the GPUs of a compose device driver
+fn gpu_resource(driver: Option<&str>) -> Result<&'static str, ComposeServiceError> {
+    match driver {
+        None | Some("nvidia") => Ok("nvidia.com/gpu"),
+        Some("amd") => Ok("amd.com/gpu"),
+        Some("intel") => Ok("gpu.intel.com/i915"),
+        Some(driver) => Err(ComposeServiceError::Other(format!(
+            "Unknown GPU driver {}, request its resource with x-ctf-resources instead",
+            driver
+        ))),
+    }
+}
+
+/// Extended resources the container needs, as limits. Kubernetes doesn't allow overcommitting
+/// them, so the requests are always the same.
+pub fn extended_resources(
+    svc: &compose_spec::Service,
+) -> Result<BTreeMap<String, Quantity>, ComposeServiceError> {
+    let mut counts: BTreeMap<String, u64> = BTreeMap::new();
+    let devices = svc
+        .deploy
+        .iter()
+        .filter_map(|deploy| deploy.resources.as_ref()?.reservations.as_ref())
+        .flat_map(|reservations| &reservations.devices);
+    for device in devices {
+        if !device.capabilities.contains(&Capability::Gpu) {
+            return Err(ComposeServiceError::PropertyNotSupported(
+                "deploy.resources.reservations.devices without the gpu capability".to_string(),
+            ));
+        }
+        if !device.device_ids.is_empty() {
+            return Err(ComposeServiceError::PropertyNotSupported(
+                "deploy.resources.reservations.devices.device_ids".to_string(),
+            ));
+        }
+        let count = match device.count {
+            Some(Count::Integer(count)) => count,
+            // Compose reserves all GPUs by default, which Kubernetes can't do
+            Some(Count::All) | None => {
+                return Err(ComposeServiceError::Other(
+                    "GPU devices need a count, reserving all GPUs is not supported".to_string(),
+                ));
+            }
+        };
+        *counts
+            .entry(gpu_resource(device.driver.as_deref())?.to_string())
+            .or_default() += count;
+    }
+
+    if let Some(value) = svc.extensions.get("x-ctf-resources") {
+        let resources: BTreeMap<String, u64> =
+            serde_yaml::from_value(value.clone()).map_err(|e| {
+                ComposeServiceError::Other(format!("Failed to parse x-ctf-resources: {}", e))
+            })?;
+        for (resource, count) in resources {
+            if matches!(resource.as_str(), "cpu" | "memory") {
+                return Err(ComposeServiceError::Other(format!(
+                    "Set {} with the compose properties instead of x-ctf-resources",
+                    resource
+                )));
+            }
+            *counts.entry(resource).or_default() += count;
+        }
+    }
+
+    Ok(counts
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(resource, count)| (resource, Quantity(count.to_string())))
+        .collect())
+}
+
+/// Whether any of the resources are GPUs
+pub fn uses_gpu(resources: &BTreeMap<String, Quantity>) -> bool {
+    resources
+        .keys()
+        .any(|resource| resource.ends_with("/gpu") || resource == "gpu.intel.com/i915")
+}
+
+pub fn gpu_runtime_class() -> Option<String> {
+    std::env::var("GPU_RUNTIME_CLASS")
+        .ok()
+        .filter(|class| !class.is_empty())
+}
+
+/// Labels of the nodes with GPUs, from GPU_NODE_SELECTOR as comma separated `key=value` pairs
+pub fn gpu_node_selector() -> BTreeMap<String, String> {
+    std::env::var("GPU_NODE_SELECTOR")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            Some((key.trim().to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gpu_devices() {
+        let svc: compose_spec::Service = serde_yaml::from_str(
+            "image: ml\ndeploy:\n  resources:\n    reservations:\n      devices:\n        - capabilities: [gpu]\n          driver: nvidia\n          count: 2\nx-ctf-resources:\n  example.com/fpga: 1\n",
+        )
+        .unwrap();
+        let resources = extended_resources(&svc).unwrap();
+        assert_eq!(resources["nvidia.com/gpu"], Quantity("2".to_string()));
+        assert_eq!(resources["example.com/fpga"], Quantity("1".to_string()));
+        assert!(uses_gpu(&resources));
+    }
+
+    #[test]
+    fn test_unsupported_devices() {
+        let all_gpus: compose_spec::Service = serde_yaml::from_str(
+            "image: ml\ndeploy:\n  resources:\n    reservations:\n      devices:\n        - capabilities: [gpu]\n",
+        )
+        .unwrap();
+        assert!(extended_resources(&all_gpus).is_err());
+
+        let cpu: compose_spec::Service =
+            serde_yaml::from_str("image: ml\nx-ctf-resources:\n  cpu: 4\n").unwrap();
+        assert!(extended_resources(&cpu).is_err());
+    }
+}
@@ -4,7 +4,7 @@
 
 use std::path::Path;
 
-use crate::repo::challenges::compose::service::ComposeServiceError;
+use crate::repo::challenges::{compose::service::ComposeServiceError, flag::inject_into_env};
 
 /// Processes environment variables from compose service configuration.
 /// Returns a vector of Kubernetes EnvVar objects.
@@ -29,6 +29,8 @@ pub fn process_environment(
         process_env_files(&mut env, env_file, working_dir)?;
     }
 
+    inject_into_env(&mut env);
+
     Ok(env)
 }
 
@@ -2,7 +2,9 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use crate::repo::challenges::compose::service::ComposeServiceError;
+use crate::repo::challenges::compose::service::{
+    ComposeServiceError, networking::attached_networks,
+};
 
 macro_rules! ensure_option_none {
     ($field:expr) => {
@@ -43,7 +45,7 @@ pub fn ensure_only_supported(svc: &compose_spec::Service) -> Result<(), ComposeS
     ensure_option_none!(svc.memswap_limit);
     ensure_option_none!(svc.pid);
     ensure_option_none!(svc.pids_limit);
-    ensure_option_none!(svc.network_config);
+    attached_networks(svc)?;
     ensure_option_none!(svc.mac_address);
     ensure_false!(svc.oom_kill_disable);
     ensure_option_none!(svc.oom_score_adj);
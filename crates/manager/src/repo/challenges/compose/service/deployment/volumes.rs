@@ -4,7 +4,13 @@
 
 use slugify::slugify;
 
-use crate::repo::challenges::compose::service::ComposeServiceError;
+use crate::repo::challenges::{
+    compose::{
+        service::ComposeServiceError,
+        volume::{DATA_PVC_NAME, pvc_name},
+    },
+    flag::flag_file_volume,
+};
 
 /// Builds Kubernetes volumes from compose service configuration
 pub fn build_volumes(
@@ -53,6 +59,10 @@ pub fn build_volumes(
         });
     }
 
+    if let Some((flag_volume, _)) = flag_file_volume(svc) {
+        volumes.push(flag_volume);
+    }
+
     Ok(volumes)
 }
 
@@ -67,10 +77,10 @@ fn convert_volume(
                 .ok_or(ComposeServiceError::AnonymousVolume)?
                 .clone();
             Ok(k8s_openapi::api::core::v1::Volume {
-                name: vol_name.to_string(),
+                name: pvc_name(&vol_name.to_string()),
                 persistent_volume_claim: Some(
                     k8s_openapi::api::core::v1::PersistentVolumeClaimVolumeSource {
-                        claim_name: vol_name.to_string(),
+                        claim_name: pvc_name(&vol_name.to_string()),
                         ..Default::default()
                     },
                 ),
@@ -88,7 +98,7 @@ fn convert_volume(
                 name: slugify!(&b.common.target.as_inner().to_string_lossy()),
                 persistent_volume_claim: Some(
                     k8s_openapi::api::core::v1::PersistentVolumeClaimVolumeSource {
-                        claim_name: "plfanzen_internal_ctf_data".to_string(),
+                        claim_name: DATA_PVC_NAME.to_string(),
                         ..Default::default()
                     },
                 ),
@@ -142,6 +152,10 @@ pub fn build_volume_mounts(
         });
     }
 
+    if let Some((_, flag_mount)) = flag_file_volume(svc) {
+        mounts.push(flag_mount);
+    }
+
     Ok(mounts)
 }
 
@@ -156,7 +170,7 @@ fn convert_volume_mount(
                 .ok_or(ComposeServiceError::AnonymousVolume)?
                 .clone();
             Ok(k8s_openapi::api::core::v1::VolumeMount {
-                name: vol_name.to_string(),
+                name: pvc_name(&vol_name.to_string()),
                 mount_path: volume
                     .common
                     .target
@@ -167,9 +181,22 @@ fn convert_volume_mount(
             })
         }
         compose_spec::service::volumes::Mount::Bind(b) => {
+            // Each bind source is its own directory on the data claim
+            let sub_path = b
+                .source
+                .as_inner()
+                .strip_prefix("./data/")
+                .map(|path| path.to_string_lossy().trim_end_matches('/').to_string())
+                .unwrap_or_default();
             Ok(k8s_openapi::api::core::v1::VolumeMount {
                 name: slugify!(&b.common.target.as_inner().to_string_lossy()),
                 mount_path: b.common.target.as_inner().to_string_lossy().to_string(),
+                sub_path: if sub_path.is_empty() {
+                    None
+                } else {
+                    Some(sub_path)
+                },
+                read_only: b.common.read_only.then_some(true),
                 ..Default::default()
             })
         }
@@ -4,15 +4,21 @@ use std::collections::BTreeMap;
 
 use serde::{Deserialize, Serialize};
 
-use crate::repo::challenges::{loader::Challenge, vm::{HasVms, VirtualMachine}};
+use crate::repo::challenges::{
+    compose::service::ComposeServiceError,
+    loader::Challenge,
+    vm::{HasVms, VirtualMachine},
+};
 
-#[derive(Serialize, Deserialize, Default, PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(Serialize, Deserialize, Default, PartialEq, Eq, Clone, Debug)]
 pub enum OtherParty {
     Challenge,
     Cluster,
     ClusterDns,
     #[default]
     World,
+    /// Services of the challenge attached to the given compose network
+    Network(String),
 }
 
 #[derive(Serialize, Deserialize, Default, PartialEq, Eq, Clone, Copy, Debug)]
@@ -92,24 +98,108 @@ impl Default for OutgoingNetworkPolicy {
     }
 }
 
+/// Label of the pods of services attached to a compose network
+pub fn network_label(network: &str) -> String {
+    format!("network-{}", network)
+}
+
+/// Names of the compose networks a service is attached to.
+///
+/// Only plain attachments are supported, network modes and per-network settings like aliases
+/// or static addresses have no equivalent in the cluster.
+pub fn attached_networks(svc: &compose_spec::Service) -> Result<Vec<String>, ComposeServiceError> {
+    match &svc.network_config {
+        None => Ok(Vec::new()),
+        Some(compose_spec::service::NetworkConfig::NetworkMode(_)) => Err(
+            ComposeServiceError::PropertyNotSupported("network_mode".to_string()),
+        ),
+        Some(compose_spec::service::NetworkConfig::Networks(compose_spec::ShortOrLong::Short(
+            networks,
+        ))) => Ok(networks.iter().map(|network| network.to_string()).collect()),
+        Some(compose_spec::service::NetworkConfig::Networks(compose_spec::ShortOrLong::Long(
+            networks,
+        ))) => networks
+            .iter()
+            .map(|(network, config)| {
+                if config.as_ref().is_some_and(|config| !config.is_empty()) {
+                    return Err(ComposeServiceError::PropertyNotSupported(format!(
+                        "networks.{}",
+                        network
+                    )));
+                }
+                Ok(network.to_string())
+            })
+            .collect(),
+    }
+}
+
+/// Policy of a service attached to compose networks: it can only talk to services sharing one
+/// of its networks. Ports it publishes can still be reached from the cluster, which is where
+/// the ingress proxy connects from.
+fn network_scoped_policy(svc: &compose_spec::Service, networks: &[String]) -> NetworkPolicy {
+    let published_ports = compose_spec::service::ports::into_long_iter(svc.ports.clone())
+        .map(|port| PortRule {
+            port: port.target,
+            protocols: vec![match port.protocol {
+                Some(compose_spec::service::ports::Protocol::Udp) => Protocol::UDP,
+                _ => Protocol::TCP,
+            }],
+        })
+        .collect::<Vec<_>>();
+    let mut incoming = networks
+        .iter()
+        .map(|network| NetworkPolicyRule {
+            other_party: OtherParty::Network(network.clone()),
+            ports: None,
+        })
+        .collect::<Vec<_>>();
+    if !published_ports.is_empty() {
+        incoming.push(NetworkPolicyRule {
+            other_party: OtherParty::Cluster,
+            ports: Some(published_ports.clone()),
+        });
+        incoming.push(NetworkPolicyRule {
+            other_party: OtherParty::World,
+            ports: Some(published_ports),
+        });
+    }
+    let mut outgoing = OutgoingNetworkPolicy::default();
+    outgoing
+        .rules
+        .retain(|rule| rule.other_party != OtherParty::Challenge);
+    outgoing
+        .rules
+        .extend(networks.iter().map(|network| NetworkPolicyRule {
+            other_party: OtherParty::Network(network.clone()),
+            ports: None,
+        }));
+    NetworkPolicy {
+        incoming: IncomingNetworkPolicy { rules: incoming },
+        outgoing,
+    }
+}
+
 pub trait HasNetworkPolicy {
     fn get_network_policy(&self) -> Option<NetworkPolicy>;
 }
 
 impl HasNetworkPolicy for compose_spec::service::Service {
     fn get_network_policy(&self) -> Option<NetworkPolicy> {
-        self.extensions.get("x-ctf-network-policy").and_then(|v| {
-            match serde_yaml::from_value::<NetworkPolicy>(v.clone()) {
+        if let Some(v) = self.extensions.get("x-ctf-network-policy") {
+            return match serde_yaml::from_value::<NetworkPolicy>(v.clone()) {
                 Ok(policy) => Some(policy),
                 Err(err) => {
-                    tracing::error!(
-                        "Failed to parse x-ctf-network-policy for service: {}",
-                        err
-                    );
+                    tracing::error!("Failed to parse x-ctf-network-policy for service: {}", err);
                     None
                 }
-            }
-        })
+            };
+        }
+        // Services without networks keep the policy of the challenge
+        let networks = attached_networks(self).ok()?;
+        if networks.is_empty() {
+            return None;
+        }
+        Some(network_scoped_policy(self, &networks))
     }
 }
 
@@ -171,3 +261,51 @@ pub fn get_policies(challenge: &Challenge) -> Vec<k8s_crds_cilium::CiliumNetwork
 
     policies
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_scoped_policy() {
+        let svc: compose_spec::Service = serde_yaml::from_str(
+            "image: web\nports:\n  - 80:8080\nnetworks:\n  - frontend\n  - internal\n",
+        )
+        .unwrap();
+        assert_eq!(
+            attached_networks(&svc).unwrap(),
+            vec!["frontend", "internal"]
+        );
+        let policy = svc.get_network_policy().unwrap();
+        assert!(
+            policy
+                .incoming
+                .rules
+                .iter()
+                .any(|rule| rule.other_party == OtherParty::Network("internal".to_string()))
+        );
+        let cluster_rule = policy
+            .incoming
+            .rules
+            .iter()
+            .find(|rule| rule.other_party == OtherParty::Cluster)
+            .unwrap();
+        assert_eq!(cluster_rule.ports.as_ref().unwrap()[0].port, 8080);
+        assert!(
+            !policy
+                .outgoing
+                .rules
+                .iter()
+                .any(|rule| rule.other_party == OtherParty::Challenge)
+        );
+
+        let svc: compose_spec::Service =
+            serde_yaml::from_str("image: db\nnetworks:\n  internal:\n").unwrap();
+        let policy = svc.get_network_policy().unwrap();
+        assert_eq!(policy.incoming.rules.len(), 1);
+
+        let svc: compose_spec::Service =
+            serde_yaml::from_str("image: db\nnetwork_mode: host\n").unwrap();
+        assert!(attached_networks(&svc).is_err());
+    }
+}
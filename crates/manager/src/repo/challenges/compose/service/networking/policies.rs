@@ -25,6 +25,18 @@ impl super::NetworkPolicy {
                             OtherParty::World => Some(vec!["world".to_string()]),
                             _ => None,
                         },
+                        from_endpoints: match &rule.other_party {
+                            OtherParty::Network(network) => {
+                                Some(vec![CiliumNetworkPolicyIngressFromEndpoints {
+                                    match_labels: Some(BTreeMap::from([(
+                                        super::network_label(network),
+                                        "true".to_string(),
+                                    )])),
+                                    match_expressions: None,
+                                }])
+                            }
+                            _ => None,
+                        },
                         to_ports: rule.ports.as_ref().map(|ports| {
                             ports
                                 .iter()
@@ -107,7 +119,7 @@ impl super::NetworkPolicy {
                             to_endpoints: Some(vec![CiliumNetworkPolicyEgressToEndpoints {
                                 match_labels: Some({
                                     let mut labels = BTreeMap::new();
-                                    match rule.other_party {
+                                    match &rule.other_party {
                                         OtherParty::Challenge => {
                                             labels
                                                 .insert("app".to_string(), "challenge".to_string());
@@ -121,6 +133,12 @@ impl super::NetworkPolicy {
                                         OtherParty::World => {
                                             labels.insert("world".to_string(), "true".to_string());
                                         }
+                                        OtherParty::Network(network) => {
+                                            labels.insert(
+                                                super::network_label(network),
+                                                "true".to_string(),
+                                            );
+                                        }
                                         OtherParty::ClusterDns => {}
                                     }
                                     labels
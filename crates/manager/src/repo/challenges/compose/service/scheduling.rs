@@ -0,0 +1,87 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Pinning services and virtual machines to nodes, with the `x-ctf-scheduling` extension of a
+//! service or the `scheduling` field of a virtual machine:
+//!
+//! ```yaml
+//! x-ctf-scheduling:
+//!   node_selector:
+//!     pool: pwn
+//!   tolerations:
+//!     - key: dedicated
+//!       operator: Equal
+//!       value: pwn
+//!       effect: NoSchedule
+//! ```
+//!
+//! Tolerations and the affinity are written like in a Kubernetes PodSpec.
+
+use std::collections::BTreeMap;
+
+use k8s_openapi::api::core::v1::{Affinity, Toleration};
+use serde::{Deserialize, Serialize};
+
+use crate::repo::challenges::compose::service::ComposeServiceError;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Scheduling {
+    #[serde(default)]
+    pub node_selector: BTreeMap<String, String>,
+    #[serde(default)]
+    pub tolerations: Vec<Toleration>,
+    #[serde(default)]
+    pub affinity: Option<Affinity>,
+}
+
+impl Scheduling {
+    pub fn node_selector(&self) -> Option<BTreeMap<String, String>> {
+        (!self.node_selector.is_empty()).then(|| self.node_selector.clone())
+    }
+
+    pub fn tolerations(&self) -> Option<Vec<Toleration>> {
+        (!self.tolerations.is_empty()).then(|| self.tolerations.clone())
+    }
+}
+
+pub trait HasScheduling {
+    fn get_scheduling(&self) -> Result<Scheduling, ComposeServiceError>;
+}
+
+impl HasScheduling for compose_spec::Service {
+    fn get_scheduling(&self) -> Result<Scheduling, ComposeServiceError> {
+        let Some(value) = self.extensions.get("x-ctf-scheduling") else {
+            return Ok(Scheduling::default());
+        };
+        serde_yaml::from_value(value.clone()).map_err(|e| {
+            ComposeServiceError::Other(format!("Failed to parse x-ctf-scheduling: {}", e))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scheduling() {
+        let svc: compose_spec::Service = serde_yaml::from_str(
+            "image: pwn\nx-ctf-scheduling:\n  node_selector:\n    pool: pwn\n  tolerations:\n    - key: dedicated\n      operator: Equal\n      value: pwn\n      effect: NoSchedule\n      tolerationSeconds: 60\n",
+        )
+        .unwrap();
+        let scheduling = svc.get_scheduling().unwrap();
+        assert_eq!(
+            scheduling.node_selector(),
+            Some(BTreeMap::from([("pool".to_string(), "pwn".to_string())]))
+        );
+        let tolerations = scheduling.tolerations().unwrap();
+        assert_eq!(tolerations[0].key.as_deref(), Some("dedicated"));
+        assert_eq!(tolerations[0].toleration_seconds, Some(60));
+        assert_eq!(scheduling.affinity, None);
+
+        let svc: compose_spec::Service =
+            serde_yaml::from_str("image: pwn\nx-ctf-scheduling:\n  tolerations: yes\n").unwrap();
+        assert!(svc.get_scheduling().is_err());
+    }
+}
@@ -1,8 +1,40 @@
+/// Claim the ./data/ bind mounts of all services are stored on
+pub const DATA_PVC_NAME: &str = "plfanzen-internal-ctf-data";
+
+/// Turns a compose volume name into a valid claim name, compose allows underscores and
+/// uppercase letters, Kubernetes doesn't.
+pub fn pvc_name(volume: &str) -> String {
+    volume
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' | '-' => c,
+            'A'..='Z' => c.to_ascii_lowercase(),
+            _ => '-',
+        })
+        .collect::<String>()
+        .trim_matches('-')
+        .to_string()
+}
+
+/// Names of the named volumes a service mounts
+pub fn named_volumes(svc: &compose_spec::Service) -> Vec<compose_spec::Identifier> {
+    compose_spec::service::volumes::into_long_iter(svc.volumes.clone())
+        .filter_map(|mount| match mount {
+            compose_spec::service::volumes::Mount::Volume(volume) => volume.source,
+            _ => None,
+        })
+        .collect()
+}
+
 pub trait AsPvc {
     fn as_pvc(&self, id: String) -> k8s_openapi::api::core::v1::PersistentVolumeClaim;
 }
 
-pub fn get_pvc(name: String, size: String) -> k8s_openapi::api::core::v1::PersistentVolumeClaim {
+pub fn get_pvc(
+    name: String,
+    size: String,
+    storage_class: Option<String>,
+) -> k8s_openapi::api::core::v1::PersistentVolumeClaim {
     k8s_openapi::api::core::v1::PersistentVolumeClaim {
         metadata: k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta {
             name: Some(name),
@@ -22,6 +54,7 @@ pub fn get_pvc(name: String, size: String) -> k8s_openapi::api::core::v1::Persis
                 ),
                 ..Default::default()
             }),
+            storage_class_name: storage_class,
             ..Default::default()
         }),
         ..Default::default()
@@ -30,16 +63,46 @@ pub fn get_pvc(name: String, size: String) -> k8s_openapi::api::core::v1::Persis
 impl AsPvc for compose_spec::Volume {
     fn as_pvc(&self, id: String) -> k8s_openapi::api::core::v1::PersistentVolumeClaim {
         get_pvc(
-            id,
+            pvc_name(&id),
             self.extensions
                 .get("x-size")
                 .and_then(|v| v.as_str())
                 .unwrap_or("1Gi")
                 .to_string(),
+            self.extensions
+                .get("x-storage-class")
+                .and_then(|v| v.as_str())
+                .map(str::to_string),
         )
     }
 }
 
 pub fn default_size_pvc(id: String) -> k8s_openapi::api::core::v1::PersistentVolumeClaim {
-    get_pvc(id, "1Gi".to_string())
+    get_pvc(pvc_name(&id), "1Gi".to_string(), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pvc_name() {
+        assert_eq!(pvc_name("db_data"), "db-data");
+        assert_eq!(pvc_name("Uploads"), "uploads");
+        assert_eq!(pvc_name("_cache"), "cache");
+    }
+
+    #[test]
+    fn test_volume_extensions() {
+        let volume: compose_spec::Volume =
+            serde_yaml::from_str("x-size: 5Gi\nx-storage-class: fast-ssd\n").unwrap();
+        let pvc = volume.as_pvc("db_data".to_string());
+        assert_eq!(pvc.metadata.name.as_deref(), Some("db-data"));
+        let spec = pvc.spec.unwrap();
+        assert_eq!(spec.storage_class_name.as_deref(), Some("fast-ssd"));
+        assert_eq!(
+            spec.resources.unwrap().requests.unwrap()["storage"].0,
+            "5Gi"
+        );
+    }
 }
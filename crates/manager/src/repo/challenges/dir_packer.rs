@@ -65,8 +65,9 @@ pub fn safe_pack_challenge(source_dir: &Path) -> Result<Vec<u8>, Box<dyn std::er
                         relative_path,
                         new_compose_content.as_bytes(),
                     )?;
+                } else {
+                    archive.append_path_with_name(path, relative_path)?;
                 }
-                archive.append_path_with_name(path, relative_path)?;
             } else if path.is_dir() {
                 // This does not append the files inside the directory, just the directory itself
                 archive.append_dir(relative_path, path)?;
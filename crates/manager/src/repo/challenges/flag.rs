@@ -0,0 +1,175 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Hands the flag of the actor to their instance at deployment time, so it doesn't have to be
+//! part of the images.
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use k8s_openapi::api::core::v1::{
+    EnvVar, EnvVarSource, Secret, SecretKeySelector, SecretVolumeSource, Volume, VolumeMount,
+};
+use kube::api::ObjectMeta;
+
+/// Replaced with the flag in environment variables and cloud-init user data
+pub const FLAG_PLACEHOLDER: &str = "${FLAG}";
+pub const FLAG_SECRET_NAME: &str = "plfanzen-internal-ctf-flag";
+const FLAG_SECRET_KEY: &str = "flag";
+/// Kubernetes only expands $(VAR) references, so the placeholder points to this variable
+const FLAG_ENV_NAME: &str = "PLFANZEN_INTERNAL_FLAG";
+const FLAG_VOLUME_NAME: &str = "plfanzen-internal-ctf-flag";
+pub const NO_FLAG_ERROR: &str =
+    "The flag can't be injected, it is checked by a script instead of being a fixed value";
+
+/// The secret of an instance holding its flag
+pub fn flag_secret(flag: &str) -> Secret {
+    Secret {
+        metadata: ObjectMeta {
+            name: Some(FLAG_SECRET_NAME.to_string()),
+            ..Default::default()
+        },
+        string_data: Some([(FLAG_SECRET_KEY.to_string(), flag.to_string())].into()),
+        ..Default::default()
+    }
+}
+
+/// Path the flag is mounted at as a file, from the x-ctf-flag-file extension
+pub fn flag_file(svc: &compose_spec::Service) -> Option<String> {
+    svc.extensions
+        .get("x-ctf-flag-file")
+        .and_then(|v| v.as_str())
+        .map(|path| path.to_string())
+}
+
+/// Whether a service gets the flag through its environment or as a file
+pub fn uses_flag(svc: &compose_spec::Service) -> bool {
+    flag_file(svc).is_some()
+        || svc.environment.clone().into_map().is_ok_and(|env| {
+            env.values()
+                .flatten()
+                .any(|v| v.to_string().contains(FLAG_PLACEHOLDER))
+        })
+}
+
+/// Points `${FLAG}` in environment variables to the flag secret.
+///
+/// The flag itself is never part of the pod spec, Kubernetes expands it when the container starts.
+pub fn inject_into_env(env: &mut Vec<EnvVar>) {
+    let mut injected = false;
+    for var in env.iter_mut() {
+        if let Some(value) = var.value.as_mut()
+            && value.contains(FLAG_PLACEHOLDER)
+        {
+            *value = value.replace(FLAG_PLACEHOLDER, &format!("$({})", FLAG_ENV_NAME));
+            injected = true;
+        }
+    }
+    if injected {
+        // Variables can only reference those defined before them
+        env.insert(
+            0,
+            EnvVar {
+                name: FLAG_ENV_NAME.to_string(),
+                value_from: Some(EnvVarSource {
+                    secret_key_ref: Some(SecretKeySelector {
+                        name: FLAG_SECRET_NAME.to_string(),
+                        key: FLAG_SECRET_KEY.to_string(),
+                        optional: Some(false),
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        );
+    }
+}
+
+/// Volume and mount of the flag file, if the service wants one
+pub fn flag_file_volume(svc: &compose_spec::Service) -> Option<(Volume, VolumeMount)> {
+    let path = flag_file(svc)?;
+    Some((
+        Volume {
+            name: FLAG_VOLUME_NAME.to_string(),
+            secret: Some(SecretVolumeSource {
+                secret_name: Some(FLAG_SECRET_NAME.to_string()),
+                default_mode: Some(0o444),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        VolumeMount {
+            name: FLAG_VOLUME_NAME.to_string(),
+            mount_path: path,
+            sub_path: Some(FLAG_SECRET_KEY.to_string()),
+            read_only: Some(true),
+            ..Default::default()
+        },
+    ))
+}
+
+/// Replaces `${FLAG}` in base64 encoded cloud-init user data.
+///
+/// Fails if the user data needs a flag, but the challenge doesn't have one.
+pub fn inject_into_user_data(user_data_base64: &str, flag: Option<&str>) -> Result<String, String> {
+    let user_data = BASE64_STANDARD
+        .decode(user_data_base64)
+        .map_err(|e| format!("Invalid cloud-init user data: {}", e))?;
+    let user_data = String::from_utf8(user_data)
+        .map_err(|e| format!("Cloud-init user data is not UTF-8: {}", e))?;
+    if !user_data.contains(FLAG_PLACEHOLDER) {
+        return Ok(user_data_base64.to_string());
+    }
+    let flag = flag.ok_or_else(|| NO_FLAG_ERROR.to_string())?;
+    Ok(BASE64_STANDARD.encode(user_data.replace(FLAG_PLACEHOLDER, flag)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_references_secret() {
+        let mut env = vec![
+            EnvVar {
+                name: "GREETING".to_string(),
+                value: Some("hello".to_string()),
+                ..Default::default()
+            },
+            EnvVar {
+                name: "FLAG".to_string(),
+                value: Some("The flag is ${FLAG}".to_string()),
+                ..Default::default()
+            },
+        ];
+        inject_into_env(&mut env);
+        assert_eq!(env.len(), 3);
+        assert_eq!(env[0].name, FLAG_ENV_NAME);
+        assert!(env[0].value_from.is_some());
+        assert_eq!(
+            env[2].value.as_deref(),
+            Some("The flag is $(PLFANZEN_INTERNAL_FLAG)")
+        );
+    }
+
+    #[test]
+    fn test_env_without_placeholder() {
+        let mut env = vec![EnvVar {
+            name: "GREETING".to_string(),
+            value: Some("hello".to_string()),
+            ..Default::default()
+        }];
+        inject_into_env(&mut env);
+        assert_eq!(env.len(), 1);
+    }
+
+    #[test]
+    fn test_user_data() {
+        let user_data = BASE64_STANDARD.encode("#cloud-config\nwrite_files:\n- content: ${FLAG}\n");
+        let injected = inject_into_user_data(&user_data, Some("flag{test}")).unwrap();
+        assert_eq!(
+            BASE64_STANDARD.decode(injected).unwrap(),
+            b"#cloud-config\nwrite_files:\n- content: flag{test}\n"
+        );
+        assert!(inject_into_user_data(&user_data, None).is_err());
+    }
+}
@@ -4,35 +4,99 @@
 
 use std::collections::HashMap;
 
-use crate::repo::challenges::{dir_packer::safe_pack_challenge, metadata::CtfChallengeMetadata};
+use crate::repo::{
+    EventConfig,
+    challenges::{
+        dir_packer::safe_pack_challenge,
+        metadata::{CtfChallengeMetadata, FlagValidator},
+        oracle,
+    },
+};
+use sha2::{Digest, Sha256};
 use tempfile::TempDir;
 
+pub mod cache;
+pub mod template;
 pub mod tera;
 
+#[derive(Clone)]
 pub struct Challenge {
     pub metadata: CtfChallengeMetadata,
     pub compose: compose_spec::Compose,
     pub export: Option<Vec<u8>>,
+    /// Attachments as rendered for the actor
+    pub files: Vec<AttachmentFile>,
+}
+
+#[derive(Clone)]
+pub struct AttachmentFile {
+    pub name: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// Describes the attachments listed in the metadata, skipping (and logging) missing ones.
+fn describe_attachments(
+    rendered_dir: &std::path::Path,
+    attachments: &[String],
+) -> Vec<AttachmentFile> {
+    let Ok(rendered_dir) = std::fs::canonicalize(rendered_dir) else {
+        return Vec::new();
+    };
+    attachments
+        .iter()
+        .filter_map(|name| {
+            let data = std::fs::canonicalize(rendered_dir.join(name))
+                .ok()
+                .filter(|path| path.starts_with(&rendered_dir))
+                .and_then(|path| std::fs::read(path).ok());
+            let Some(data) = data else {
+                tracing::warn!(
+                    "Attachment {} does not exist in {}",
+                    name,
+                    rendered_dir.to_string_lossy()
+                );
+                return None;
+            };
+            Some(AttachmentFile {
+                name: name.clone(),
+                size: data.len() as u64,
+                sha256: format!("{:x}", Sha256::digest(&data)),
+            })
+        })
+        .collect()
 }
 
 pub async fn load_challenge_from_dir(
     chall_dir: &std::path::Path,
     actor: &str,
     is_export: bool,
+) -> Result<Challenge, Box<dyn std::error::Error>> {
+    load_challenge(chall_dir, actor, is_export, None).await
+}
+
+/// Loads a challenge, replacing the template variables of docker-compose.yml (see [`template`])
+/// if it is loaded for an instance.
+async fn load_challenge(
+    chall_dir: &std::path::Path,
+    actor: &str,
+    is_export: bool,
+    instance_id: Option<&str>,
 ) -> Result<Challenge, Box<dyn std::error::Error>> {
     // Process the challenge to a new temp dir
     let temp_dir = TempDir::new()?;
     let tmp_path = temp_dir.path().to_path_buf();
     let source_chall_dir = chall_dir.to_path_buf();
-    let actor = actor.to_string();
+    let render_actor = actor.to_string();
     tokio::task::spawn_blocking(move || {
-        tera::render_dir_recursively(&source_chall_dir, &tmp_path, &actor, is_export).map_err(|e| {
-            format!(
-                "Failed to render challenge directory {}: {}",
-                source_chall_dir.to_string_lossy(),
-                e
-            )
-        })
+        tera::render_dir_recursively(&source_chall_dir, &tmp_path, &render_actor, is_export)
+            .map_err(|e| {
+                format!(
+                    "Failed to render challenge directory {}: {}",
+                    source_chall_dir.to_string_lossy(),
+                    e
+                )
+            })
     })
     .await??;
     // Load docker-compose.yml from the temp dir
@@ -44,7 +108,7 @@ pub async fn load_challenge_from_dir(
             e
         )
     })?;
-    let mut compose: compose_spec::Compose =
+    let mut compose_value: serde_yaml::Value =
         serde_yaml::from_str(&compose_content).map_err(|e| {
             format!(
                 "Failed to parse docker-compose.yml from {}: {}",
@@ -52,10 +116,9 @@ pub async fn load_challenge_from_dir(
                 e
             )
         })?;
-    let metadata = serde_yaml::from_value(
-        compose
-            .extensions
-            .shift_remove("x-ctf-metadata")
+    let metadata: CtfChallengeMetadata = serde_yaml::from_value(
+        compose_value
+            .get("x-ctf-metadata")
             .ok_or(format!(
                 "Missing ctf-metadata extension in docker-compose.yml at {}",
                 compose_path.to_string_lossy()
@@ -69,9 +132,47 @@ pub async fn load_challenge_from_dir(
             e
         )
     })?;
+    if let Some(instance_id) = instance_id {
+        let challenge_id = chall_dir
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let flag = metadata.actor_flag(&challenge_id, actor)?;
+        let oracle_key = match metadata.flag_validator {
+            FlagValidator::Oracle { .. } => Some(oracle::oracle_key(&challenge_id)?),
+            _ => None,
+        };
+        template::render_compose(
+            &mut compose_value,
+            &template::InstanceVars {
+                flag: flag.as_deref(),
+                instance_id,
+                actor,
+                oracle_key: oracle_key.as_deref(),
+            },
+        )
+        .map_err(|e| {
+            format!(
+                "Failed to render docker-compose.yml at {}: {}",
+                compose_path.to_string_lossy(),
+                e
+            )
+        })?;
+    }
+    let mut compose: compose_spec::Compose =
+        serde_yaml::from_value(compose_value).map_err(|e| {
+            format!(
+                "Failed to parse docker-compose.yml from {}: {}",
+                compose_path.to_string_lossy(),
+                e
+            )
+        })?;
+    compose.extensions.shift_remove("x-ctf-metadata");
+    let files = describe_attachments(temp_dir.path(), &metadata.attachments);
     Ok(Challenge {
         metadata,
         compose,
+        files,
         export: if is_export {
             Some(safe_pack_challenge(temp_dir.path()).map_err(move |e| {
                 format!(
@@ -89,6 +190,11 @@ pub async fn load_challenge_from_dir(
     })
 }
 
+/// The event config, if it can be loaded, to apply its release waves to challenges
+async fn load_event_config(repo_path: &std::path::Path) -> Option<EventConfig> {
+    EventConfig::try_load_from_repo(repo_path).await.ok()
+}
+
 pub async fn load_challenges_from_repo(
     repo_path: &std::path::Path,
     actor: &str,
@@ -96,6 +202,7 @@ pub async fn load_challenges_from_repo(
 ) -> Result<HashMap<String, Challenge>, Box<dyn std::error::Error>> {
     let challenges_dir = repo_path.join("challs");
     let mut challenges = HashMap::new();
+    let event_config = load_event_config(repo_path).await;
 
     if challenges_dir.is_dir() {
         for entry in std::fs::read_dir(challenges_dir)? {
@@ -104,12 +211,18 @@ pub async fn load_challenges_from_repo(
             if !path.is_dir() {
                 continue;
             }
-            match load_challenge_from_dir(&path, actor, is_export).await {
-                Ok(challenge) => {
-                    challenges.insert(
-                        path.file_name().unwrap().to_string_lossy().to_string(),
-                        challenge,
-                    );
+            let challenge_id = path.file_name().unwrap().to_string_lossy().to_string();
+            let challenge = if is_export {
+                load_challenge_from_dir(&path, actor, is_export).await
+            } else {
+                cache::load_cached_challenge(repo_path, &challenge_id, actor).await
+            };
+            match challenge {
+                Ok(mut challenge) => {
+                    if let Some(event_config) = &event_config {
+                        event_config.apply_release_wave(&challenge_id, &mut challenge.metadata);
+                    }
+                    challenges.insert(challenge_id, challenge);
                 }
                 Err(e) => {
                     tracing::warn!(
@@ -130,7 +243,30 @@ pub async fn load_challenge_from_repo(
     challenge_id: &str,
     actor: &str,
     is_export: bool,
+) -> Result<Challenge, Box<dyn std::error::Error>> {
+    let mut challenge = if is_export {
+        let challenge_dir = repo_path.join("challs").join(challenge_id);
+        load_challenge_from_dir(&challenge_dir, actor, is_export).await?
+    } else {
+        cache::load_cached_challenge(repo_path, challenge_id, actor).await?
+    };
+    if let Some(event_config) = load_event_config(repo_path).await {
+        event_config.apply_release_wave(challenge_id, &mut challenge.metadata);
+    }
+    Ok(challenge)
+}
+
+/// Loads a challenge for a new instance, see [`template`] for the variables available to it
+pub async fn load_challenge_instance_from_repo(
+    repo_path: &std::path::Path,
+    challenge_id: &str,
+    actor: &str,
+    instance_id: &str,
 ) -> Result<Challenge, Box<dyn std::error::Error>> {
     let challenge_dir = repo_path.join("challs").join(challenge_id);
-    load_challenge_from_dir(&challenge_dir, actor, is_export).await
+    let mut challenge = load_challenge(&challenge_dir, actor, false, Some(instance_id)).await?;
+    if let Some(event_config) = load_event_config(repo_path).await {
+        event_config.apply_release_wave(challenge_id, &mut challenge.metadata);
+    }
+    Ok(challenge)
 }
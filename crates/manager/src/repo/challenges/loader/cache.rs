@@ -0,0 +1,135 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Challenges loaded from the checked out commit, so listing challenges or checking flags doesn't
+//! render and parse every docker-compose.yml again.
+//!
+//! Challenges are kept per actor, except for those without templates, which are the same for
+//! everyone. Failures are kept as well, so a broken challenge is only loaded once per commit.
+//! Everything is dropped when HEAD moves or the repository is synced.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+};
+
+use crate::repo::{
+    challenges::{
+        loader::{Challenge, load_challenge_from_dir},
+        packages::is_static,
+    },
+    get_head_commit,
+};
+
+static CACHE: LazyLock<Mutex<HashMap<PathBuf, RepoCache>>> = LazyLock::new(Default::default);
+
+#[derive(Default)]
+struct RepoCache {
+    commit: String,
+    /// Whether a challenge is rendered the same for every actor
+    is_static: HashMap<String, bool>,
+    /// Results by challenge and actor, which is empty for static challenges
+    challenges: HashMap<(String, String), Result<Challenge, String>>,
+}
+
+/// Runs `f` on the cache of a repository, emptying it first if it was filled for another commit
+fn with_repo_cache<T>(repo_path: &Path, commit: &str, f: impl FnOnce(&mut RepoCache) -> T) -> T {
+    let mut cache = CACHE.lock().expect("Challenge cache lock poisoned");
+    let repo_cache = cache.entry(repo_path.to_path_buf()).or_default();
+    if repo_cache.commit != commit {
+        *repo_cache = RepoCache {
+            commit: commit.to_string(),
+            ..Default::default()
+        };
+    }
+    f(repo_cache)
+}
+
+/// Loads a challenge of the repository for an actor, without export
+pub async fn load_cached_challenge(
+    repo_path: &Path,
+    challenge_id: &str,
+    actor: &str,
+) -> Result<Challenge, Box<dyn std::error::Error>> {
+    let chall_dir = repo_path.join("challs").join(challenge_id);
+    // Not a checkout, e.g. a directory being validated
+    let Some(commit) = get_head_commit(repo_path) else {
+        return load_challenge_from_dir(&chall_dir, actor, false).await;
+    };
+    let cached_is_static = with_repo_cache(repo_path, &commit, |cache| {
+        cache.is_static.get(challenge_id).copied()
+    });
+    let challenge_is_static = match cached_is_static {
+        Some(challenge_is_static) => challenge_is_static,
+        None => {
+            let challenge_is_static = is_static(&chall_dir)?;
+            with_repo_cache(repo_path, &commit, |cache| {
+                cache
+                    .is_static
+                    .insert(challenge_id.to_string(), challenge_is_static)
+            });
+            challenge_is_static
+        }
+    };
+    let key = (
+        challenge_id.to_string(),
+        if challenge_is_static {
+            String::new()
+        } else {
+            actor.to_string()
+        },
+    );
+    if let Some(cached) = with_repo_cache(repo_path, &commit, |cache| {
+        cache.challenges.get(&key).cloned()
+    }) {
+        return cached.map_err(Into::into);
+    }
+    let result = load_challenge_from_dir(&chall_dir, actor, false)
+        .await
+        .map_err(|e| e.to_string());
+    with_repo_cache(repo_path, &commit, |cache| {
+        cache.challenges.insert(key, result.clone())
+    });
+    result.map_err(Into::into)
+}
+
+/// Drops the challenges of a repository, e.g. after it was synced
+pub fn invalidate(repo_path: &Path) {
+    CACHE
+        .lock()
+        .expect("Challenge cache lock poisoned")
+        .remove(repo_path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_commit_empties_cache() {
+        let repo_path = Path::new("/nonexistent/cache-test");
+        with_repo_cache(repo_path, "a", |cache| {
+            cache.is_static.insert("web".to_string(), true);
+            cache.challenges.insert(
+                ("web".to_string(), String::new()),
+                Err("broken".to_string()),
+            );
+        });
+        assert_eq!(
+            with_repo_cache(repo_path, "a", |cache| cache.challenges.len()),
+            1
+        );
+        assert!(with_repo_cache(repo_path, "b", |cache| {
+            cache.is_static.is_empty() && cache.challenges.is_empty()
+        }));
+
+        with_repo_cache(repo_path, "b", |cache| {
+            cache.is_static.insert("web".to_string(), true);
+        });
+        invalidate(repo_path);
+        let is_empty = with_repo_cache(repo_path, "b", |cache| cache.is_static.is_empty());
+        assert!(is_empty);
+    }
+}
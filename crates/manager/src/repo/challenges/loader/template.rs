@@ -0,0 +1,248 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Per-instance variables in docker-compose.yml, e.g. `{{instance_id}}` or `{{random_hex 16}}`.
+//! Challenges checked by a flag oracle also get `{{oracle_key}}`, which its requests are signed
+//! with.
+//!
+//! Variables are replaced in the string values of the parsed file, so their values can never
+//! change the structure of the YAML, no matter which characters they contain. Anything else in
+//! double braces, like Go templates in healthchecks or `{{secret}}` in a command, is left as is.
+
+use std::collections::HashMap;
+
+use rand::Rng;
+use thiserror::Error;
+
+/// Longest value random_hex may generate
+const MAX_RANDOM_HEX_LEN: usize = 256;
+/// Names of the variables, only expressions starting with one of them are replaced
+const VARIABLES: &[&str] = &["flag", "instance_id", "actor", "oracle_key", "random_hex"];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TemplateError {
+    #[error("Invalid template expression {0}: {1}")]
+    InvalidExpression(String, String),
+    #[error("Template uses {{{{flag}}}}, but the flag is checked by a script")]
+    NoFlag,
+    #[error("Template uses {{{{oracle_key}}}}, but the flag is not checked by an oracle")]
+    NoOracleKey,
+    #[error("Unterminated template expression in {0}")]
+    Unterminated(String),
+}
+
+/// Values of a single instance
+pub struct InstanceVars<'a> {
+    pub flag: Option<&'a str>,
+    pub instance_id: &'a str,
+    pub actor: &'a str,
+    pub oracle_key: Option<&'a str>,
+}
+
+/// Expressions are only those starting with the name of a variable
+fn is_expression(inner: &str) -> bool {
+    inner
+        .split_whitespace()
+        .next()
+        .is_some_and(|name| VARIABLES.contains(&name))
+}
+
+struct Renderer<'a> {
+    vars: &'a InstanceVars<'a>,
+    /// Generated values by length, so services can share e.g. a database password
+    random: HashMap<usize, String>,
+}
+
+impl Renderer<'_> {
+    fn eval(&mut self, expression: &str) -> Result<String, TemplateError> {
+        let mut parts = expression.split_whitespace();
+        let name = parts.next().unwrap_or_default();
+        let args = parts.collect::<Vec<_>>();
+        let no_args = |value: &str| {
+            if args.is_empty() {
+                Ok(value.to_string())
+            } else {
+                Err(TemplateError::InvalidExpression(
+                    expression.to_string(),
+                    format!("{} takes no arguments", name),
+                ))
+            }
+        };
+        match name {
+            "flag" => no_args(self.vars.flag.ok_or(TemplateError::NoFlag)?),
+            "instance_id" => no_args(self.vars.instance_id),
+            "actor" => no_args(self.vars.actor),
+            "oracle_key" => no_args(self.vars.oracle_key.ok_or(TemplateError::NoOracleKey)?),
+            "random_hex" => {
+                let len = match args.as_slice() {
+                    [len] => len.parse::<usize>().ok(),
+                    _ => None,
+                }
+                .filter(|len| (1..=MAX_RANDOM_HEX_LEN).contains(len))
+                .ok_or_else(|| {
+                    TemplateError::InvalidExpression(
+                        expression.to_string(),
+                        format!("random_hex takes a length from 1 to {}", MAX_RANDOM_HEX_LEN),
+                    )
+                })?;
+                Ok(self
+                    .random
+                    .entry(len)
+                    .or_insert_with(|| {
+                        (0..len)
+                            .map(|_| format!("{:x}", rand::rng().random_range(0..16)))
+                            .collect()
+                    })
+                    .clone())
+            }
+            _ => unreachable!("{} is not a variable", name),
+        }
+    }
+
+    fn render_str(&mut self, input: &str) -> Result<String, TemplateError> {
+        let mut output = String::with_capacity(input.len());
+        let mut rest = input;
+        while let Some(start) = rest.find("{{") {
+            output.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            let Some(end) = after.find("}}") else {
+                if is_expression(after) {
+                    return Err(TemplateError::Unterminated(input.to_string()));
+                }
+                rest = &rest[start..];
+                break;
+            };
+            let inner = &after[..end];
+            if is_expression(inner) {
+                output.push_str(&self.eval(inner.trim())?);
+            } else {
+                output.push_str(&rest[start..start + 2 + end + 2]);
+            }
+            rest = &after[end + 2..];
+        }
+        output.push_str(rest);
+        Ok(output)
+    }
+
+    fn render_value(&mut self, value: &mut serde_yaml::Value) -> Result<(), TemplateError> {
+        match value {
+            serde_yaml::Value::String(s) => *s = self.render_str(s)?,
+            serde_yaml::Value::Sequence(seq) => {
+                for item in seq {
+                    self.render_value(item)?;
+                }
+            }
+            serde_yaml::Value::Mapping(map) => {
+                for (_, item) in map.iter_mut() {
+                    self.render_value(item)?;
+                }
+            }
+            serde_yaml::Value::Tagged(tagged) => self.render_value(&mut tagged.value)?,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Replaces the template expressions in the string values of a docker-compose.yml.
+///
+/// The x-ctf-metadata extension is left as is, the flag comes from there.
+pub fn render_compose(
+    compose: &mut serde_yaml::Value,
+    vars: &InstanceVars,
+) -> Result<(), TemplateError> {
+    let mut renderer = Renderer {
+        vars,
+        random: HashMap::new(),
+    };
+    match compose {
+        serde_yaml::Value::Mapping(map) => {
+            for (key, value) in map.iter_mut() {
+                if key.as_str() != Some("x-ctf-metadata") {
+                    renderer.render_value(value)?;
+                }
+            }
+            Ok(())
+        }
+        other => renderer.render_value(other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VARS: InstanceVars = InstanceVars {
+        flag: Some("flag{\"quoted\": yes}"),
+        instance_id: "0123456789ab",
+        actor: "team-1",
+        oracle_key: None,
+    };
+
+    fn render(yaml: &str, vars: &InstanceVars) -> Result<serde_yaml::Value, TemplateError> {
+        let mut value = serde_yaml::from_str(yaml).unwrap();
+        render_compose(&mut value, vars)?;
+        Ok(value)
+    }
+
+    #[test]
+    fn test_variables() {
+        let value = render(
+            "services:\n  web:\n    environment:\n      FLAG: \"{{flag}}\"\n      ID: web-{{ instance_id }}-{{actor}}\n    healthcheck:\n      test: [\"CMD\", \"docker\", \"inspect\", \"{{.State}}\"]\n",
+            &VARS,
+        )
+        .unwrap();
+        let web = &value["services"]["web"];
+        assert_eq!(web["environment"]["FLAG"], "flag{\"quoted\": yes}");
+        assert_eq!(web["environment"]["ID"], "web-0123456789ab-team-1");
+        assert_eq!(web["healthcheck"]["test"][3], "{{.State}}");
+    }
+
+    #[test]
+    fn test_random_hex_is_shared() {
+        let value = render(
+            "services:\n  db:\n    environment:\n      PASSWORD: \"{{random_hex 16}}\"\n  web:\n    environment:\n      DB_PASSWORD: \"{{random_hex 16}}\"\n",
+            &VARS,
+        )
+        .unwrap();
+        let password = value["services"]["db"]["environment"]["PASSWORD"]
+            .as_str()
+            .unwrap();
+        assert_eq!(password.len(), 16);
+        assert!(password.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(
+            value["services"]["web"]["environment"]["DB_PASSWORD"],
+            password
+        );
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        assert!(render("a: \"{{random_hex 100000}}\"", &VARS).is_err());
+        assert!(render("a: \"{{actor 1}}\"", &VARS).is_err());
+        assert!(render("a: \"{{actor\"", &VARS).is_err());
+        let no_flag = InstanceVars { flag: None, ..VARS };
+        assert_eq!(
+            render("a: \"{{flag}}\"", &no_flag),
+            Err(TemplateError::NoFlag)
+        );
+    }
+
+    #[test]
+    fn test_other_braces_are_untouched() {
+        let value = render(
+            "a: \"{{secret}} {{ Name }} {{flagged}} {{.State}}\"\nb: \"echo {{\"\n",
+            &VARS,
+        )
+        .unwrap();
+        assert_eq!(value["a"], "{{secret}} {{ Name }} {{flagged}} {{.State}}");
+        assert_eq!(value["b"], "echo {{");
+    }
+
+    #[test]
+    fn test_metadata_is_untouched() {
+        let value = render("x-ctf-metadata:\n  flag: \"{{flag}}\"\n", &VARS).unwrap();
+        assert_eq!(value["x-ctf-metadata"]["flag"], "{{flag}}");
+    }
+}
@@ -13,7 +13,10 @@ use serde::{Deserialize, Serialize};
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
 
-use crate::js::create_boa_context;
+use crate::{
+    js::{ScriptError, ScriptJob, run_script},
+    repo::{LocalizedMarkdown, challenges::oracle::FlagOracle},
+};
 
 fn json_into_js(
     value: &serde_json::Value,
@@ -22,6 +25,15 @@ fn json_into_js(
     JsValue::from_json(value, context)
 }
 
+/// Scripts see a string, or an object of strings by locale for translated texts
+fn localized_into_js(
+    value: &LocalizedMarkdown,
+    context: &mut boa_engine::Context,
+) -> boa_engine::JsResult<boa_engine::JsValue> {
+    let value = serde_json::to_value(value).expect("Strings can always be serialized");
+    JsValue::from_json(&value, context)
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum FlagValidator {
@@ -32,6 +44,111 @@ pub enum FlagValidator {
         /// JS code that runs setFlagValidationFunction((flag) => boolean)
         flag_validation_fn: String,
     },
+    /// Every actor gets their own flag, derived from HMAC_SECRET_KEY, the challenge and the actor
+    Dynamic {
+        dynamic_flag: DynamicFlag,
+    },
+    /// Submissions are forwarded to a service of the challenge, which decides if they are correct
+    Oracle {
+        flag_oracle: FlagOracle,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DynamicFlag {
+    /// Text before the braces, e.g. "flag" for flag{...}
+    #[serde(default = "default_flag_prefix")]
+    pub prefix: String,
+    /// Number of hex characters between the braces
+    #[serde(default = "default_flag_length")]
+    pub length: usize,
+}
+
+fn default_flag_prefix() -> String {
+    "flag".to_string()
+}
+
+fn default_flag_length() -> usize {
+    32
+}
+
+impl DynamicFlag {
+    /// Derives the flag of an actor. Fails without HMAC_SECRET_KEY, anyone could compute the
+    /// flags otherwise.
+    pub fn derive(&self, challenge_id: &str, actor: &str) -> Result<String, String> {
+        let hmac_key = std::env::var("HMAC_SECRET_KEY")
+            .map_err(|_| "HMAC_SECRET_KEY must be set for dynamic flags".to_string())?;
+        Ok(self.derive_with_key(hmac_key.as_bytes(), challenge_id, actor))
+    }
+
+    fn derive_with_key(&self, hmac_key: &[u8], challenge_id: &str, actor: &str) -> String {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(hmac_key).expect("HMAC can take key of any size");
+        mac.update(b"flag");
+        // Separators keep ("ab", "c") and ("a", "bc") apart
+        mac.update(&[0]);
+        mac.update(challenge_id.as_bytes());
+        mac.update(&[0]);
+        mac.update(actor.as_bytes());
+        let hex_str = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        format!(
+            "{}{{{}}}",
+            self.prefix,
+            &hex_str[..self.length.clamp(8, hex_str.len())]
+        )
+    }
+}
+
+/// How a challenge with services is deployed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeploymentMode {
+    /// Every actor starts their own instance
+    #[default]
+    Instanced,
+    /// A single deployment everyone connects to, kept running by the manager
+    Shared,
+}
+
+/// Differences between a submission and the flag that are forgiven, all off by default
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FlagNormalization {
+    /// Compare flags ignoring upper and lower case
+    #[serde(default)]
+    pub case_insensitive: bool,
+    /// Ignore whitespace around the submission, e.g. a trailing newline
+    #[serde(default)]
+    pub trim_whitespace: bool,
+    /// Only compare what's between the braces, so "flag{x}", "CTF{x}" and "x" are all the same
+    #[serde(default)]
+    pub strip_wrapper: bool,
+}
+
+impl FlagNormalization {
+    pub fn normalize(&self, flag: &str) -> String {
+        let mut flag = if self.trim_whitespace {
+            flag.trim()
+        } else {
+            flag
+        };
+        if self.strip_wrapper
+            && let Some(start) = flag.find('{')
+            && flag.ends_with('}')
+            && !flag[..start].contains(char::is_whitespace)
+        {
+            flag = &flag[start + 1..flag.len() - 1];
+        }
+        if self.case_insensitive {
+            flag.to_lowercase()
+        } else {
+            flag.to_string()
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, TryIntoJs)]
@@ -40,11 +157,24 @@ pub struct CtfChallengeMetadata {
     pub name: String,
     /// Authors of the challenge
     pub authors: Vec<String>,
-    /// Description of the challenge in Markdown format
-    pub description_md: String,
+    /// Description of the challenge in Markdown format, optionally per locale
+    #[boa(into_js_with = "localized_into_js")]
+    pub description_md: LocalizedMarkdown,
     #[serde(flatten)]
     #[boa(skip)]
     pub flag_validator: FlagValidator,
+    /// Applied to both the submission and the flag before comparing them, validation scripts only
+    /// get the normalized submission
+    #[serde(default)]
+    #[boa(skip)]
+    pub flag_normalization: FlagNormalization,
+    #[serde(default)]
+    #[boa(skip)]
+    pub deployment: DeploymentMode,
+    /// Instances kept running ahead of time, so starting one only takes assigning it. Pooled
+    /// instances are rendered for the actor `pool`, as the actor who gets them isn't known yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool_size: Option<u32>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub categories: Vec<String>,
     // Path to attached files
@@ -54,68 +184,134 @@ pub struct CtfChallengeMetadata {
     pub release_time: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub end_time: Option<u64>,
+    /// Wrong submissions a team (or user without a team) may make before being locked out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_attempts: Option<u32>,
+    /// Lets testers access the challenge before its release to playtest it
+    #[serde(default)]
+    pub playtest: bool,
     /// Whether to automatically expose source code + docker images + docker-compose for this challenge
     #[serde(default)]
     pub auto_publish_src: bool,
     pub difficulty: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub data_pvc_size: Option<String>,
+    /// Storage class of the claim ./data/ bind mounts are stored on, the cluster default if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_pvc_storage_class: Option<String>,
     #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
     #[boa(into_js_with = "json_into_js")]
     pub additional_metadata: serde_json::Value,
 }
 
+/// Runs flag_validation_fn for a submitted flag, in a script worker
+pub fn run_flag_validation_fn(
+    engine: &mut boa_engine::Context,
+    flag_validation_fn: &str,
+    input_flag: &str,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let flag_fn: Rc<Mutex<Option<JsFunction>>> = Rc::new(Mutex::new(None));
+    let flag_fn_clone = flag_fn.clone();
+    engine
+        .register_global_builtin_callable(js_string!("setFlagValidationFunction"), 1, unsafe {
+            NativeFunction::from_closure(move |_this, args, _ctx| {
+                let fn_obj = args.first().and_then(|v| v.as_object());
+                if let Some(obj) = fn_obj {
+                    let Some(func) = JsFunction::from_object(obj) else {
+                        return Err(JsError::from(JsNativeError::typ().with_message(
+                            "setPointsFn expects a function as its first argument",
+                        )));
+                    };
+                    let mut lock = flag_fn_clone.lock().unwrap();
+                    *lock = Some(func);
+                } else {
+                    return Err(JsError::from(JsNativeError::typ().with_message(
+                        "setPointsFn expects a function as its first argument",
+                    )));
+                }
+                Ok(JsValue::undefined())
+            })
+        })
+        .expect("Failed to register setFlagValidationFunction");
+    engine.eval(Source::from_bytes(flag_validation_fn))?;
+    let flag_validation_function = {
+        let mut lock = flag_fn.lock().unwrap();
+        lock.take().ok_or("Flag validation function not set")?
+    };
+    let result = flag_validation_function.call(
+        &JsValue::undefined(),
+        &[js_value!(js_string!(input_flag))],
+        engine,
+    )?;
+    let success = result
+        .as_boolean()
+        .ok_or("Flag validation function did not return a boolean")?;
+    Ok(success)
+}
+
+/// Checks a submitted flag with flag_validation_fn
+async fn check_flag_with_script(
+    flag_validation_fn: String,
+    input_flag: String,
+) -> Result<bool, ScriptError> {
+    run_script(ScriptJob::FlagValidation {
+        flag_validation_fn,
+        input_flag,
+    })
+    .await
+}
+
 impl CtfChallengeMetadata {
-    pub fn check_flag(&self, input_flag: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    pub async fn check_flag(
+        &self,
+        challenge_id: &str,
+        actor: &str,
+        input_flag: &str,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let input_flag = self.flag_normalization.normalize(input_flag);
+        if let FlagValidator::Oracle { flag_oracle } = &self.flag_validator {
+            return Ok(flag_oracle.check(challenge_id, actor, &input_flag).await?);
+        }
+        if let FlagValidator::JsFunction { flag_validation_fn } = &self.flag_validator {
+            return Ok(check_flag_with_script(flag_validation_fn.clone(), input_flag).await?);
+        }
+        self.check_flag_locally(challenge_id, actor, input_flag)
+    }
+
+    fn check_flag_locally(
+        &self,
+        challenge_id: &str,
+        actor: &str,
+        input_flag: String,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let normalization = &self.flag_normalization;
         match &self.flag_validator {
-            FlagValidator::String { flag } => Ok(flag == input_flag),
-            FlagValidator::JsFunction { flag_validation_fn } => {
-                let mut engine = create_boa_context();
-                let flag_fn: Rc<Mutex<Option<JsFunction>>> = Rc::new(Mutex::new(None));
-                let flag_fn_clone = flag_fn.clone();
-                engine
-                    .register_global_builtin_callable(
-                        js_string!("setFlagValidationFunction"),
-                        1,
-                        unsafe {
-                            NativeFunction::from_closure(move |_this, args, _ctx| {
-                                let fn_obj = args.first().and_then(|v| v.as_object());
-                                if let Some(obj) = fn_obj {
-                                    let Some(func) = JsFunction::from_object(obj) else {
-                                        return Err(JsError::from(JsNativeError::typ().with_message(
-                                        "setPointsFn expects a function as its first argument",
-                                    )));
-                                    };
-                                    let mut lock = flag_fn_clone.lock().unwrap();
-                                    *lock = Some(func);
-                                } else {
-                                    return Err(JsError::from(JsNativeError::typ().with_message(
-                                        "setPointsFn expects a function as its first argument",
-                                    )));
-                                }
-                                Ok(JsValue::undefined())
-                            })
-                        },
-                    )
-                    .expect("Failed to register setFlagValidationFunction");
-                engine.eval(Source::from_bytes(&flag_validation_fn))?;
-                let flag_validation_function = {
-                    let mut lock = flag_fn.lock().unwrap();
-                    lock.take().ok_or("Flag validation function not set")?
-                };
-                let result = flag_validation_function.call(
-                    &JsValue::undefined(),
-                    &[js_value!(js_string!(input_flag))],
-                    &mut engine,
-                )?;
-                let success = result
-                    .as_boolean()
-                    .ok_or("Flag validation function did not return a boolean")?;
-                Ok(success)
+            FlagValidator::String { flag } => Ok(normalization.normalize(flag) == input_flag),
+            FlagValidator::Dynamic { dynamic_flag } => Ok(normalization
+                .normalize(&dynamic_flag.derive(challenge_id, actor)?)
+                == input_flag),
+            FlagValidator::Oracle { .. } => unreachable!("Flag oracles are asked in check_flag"),
+            FlagValidator::JsFunction { .. } => {
+                unreachable!("Flag validation scripts are run in check_flag")
             }
         }
     }
 
+    /// The flag the actor has to submit, unless it is validated by a script
+    pub fn actor_flag(
+        &self,
+        challenge_id: &str,
+        actor: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        Ok(match &self.flag_validator {
+            FlagValidator::String { flag } => Some(flag.clone()),
+            FlagValidator::Dynamic { dynamic_flag } => {
+                Some(dynamic_flag.derive(challenge_id, actor)?)
+            }
+            FlagValidator::JsFunction { .. } | FlagValidator::Oracle { .. } => None,
+        })
+    }
+
     pub fn get_password(&self, actor: &str, instance_id: &str, password_id: &str) -> String {
         let hmac_key = if let Ok(env_key) = std::env::var("HMAC_SECRET_KEY") {
             env_key.into_bytes()
@@ -128,6 +324,10 @@ impl CtfChallengeMetadata {
                 FlagValidator::JsFunction {
                     ref flag_validation_fn,
                 } => flag_validation_fn.clone().into_bytes(),
+                FlagValidator::Dynamic { ref dynamic_flag } => {
+                    dynamic_flag.prefix.clone().into_bytes()
+                }
+                FlagValidator::Oracle { ref flag_oracle } => flag_oracle.url.clone().into_bytes(),
             }
         };
         let mut mac =
@@ -144,3 +344,63 @@ impl CtfChallengeMetadata {
         hex_str[..16].to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dynamic_flags_differ_per_actor() {
+        let dynamic_flag: DynamicFlag = serde_yaml::from_str("prefix: CTF").unwrap();
+        let flag = dynamic_flag.derive_with_key(b"secret", "web", "team-a");
+        assert!(flag.starts_with("CTF{") && flag.ends_with('}'));
+        assert_eq!(flag.len(), "CTF{}".len() + 32);
+        assert_eq!(
+            flag,
+            dynamic_flag.derive_with_key(b"secret", "web", "team-a")
+        );
+        assert_ne!(
+            flag,
+            dynamic_flag.derive_with_key(b"secret", "web", "team-b")
+        );
+        assert_ne!(
+            flag,
+            dynamic_flag.derive_with_key(b"secret", "pwn", "team-a")
+        );
+        assert_ne!(
+            flag,
+            dynamic_flag.derive_with_key(b"other", "web", "team-a")
+        );
+    }
+
+    #[test]
+    fn test_flag_normalization() {
+        let normalization = FlagNormalization {
+            case_insensitive: true,
+            trim_whitespace: true,
+            strip_wrapper: true,
+        };
+        for flag in ["flag{S3cret}", " CTF{s3cret}\n", "s3cret"] {
+            assert_eq!(normalization.normalize(flag), "s3cret");
+        }
+        assert_eq!(
+            FlagNormalization::default().normalize(" flag{S3cret}"),
+            " flag{S3cret}"
+        );
+        // Braces in the middle of a sentence are not a wrapper
+        assert_eq!(normalization.normalize("not a {flag}"), "not a {flag}");
+    }
+
+    #[test]
+    fn test_flag_validator_variants() {
+        let validator: FlagValidator =
+            serde_yaml::from_str("dynamic_flag:\n  length: 16\n").unwrap();
+        let FlagValidator::Dynamic { dynamic_flag } = validator else {
+            panic!("Expected a dynamic flag");
+        };
+        assert_eq!(dynamic_flag.prefix, "flag");
+        assert_eq!(dynamic_flag.length, 16);
+        let validator: FlagValidator = serde_yaml::from_str("flag: flag{static}").unwrap();
+        assert!(matches!(validator, FlagValidator::String { .. }));
+    }
+}
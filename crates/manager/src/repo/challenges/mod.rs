@@ -4,6 +4,11 @@
 
 pub mod compose;
 pub mod dir_packer;
+pub mod flag;
 pub mod loader;
 pub mod metadata;
+pub mod oracle;
+pub mod packages;
+pub mod quota;
+pub mod validate;
 pub mod vm;
@@ -0,0 +1,192 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Checking flags with a service of the challenge author, for flags that can't be compared to a
+//! fixed value, e.g. proofs of work or flags that depend on the state of an instance.
+//!
+//! The oracle has to be a service of the challenge's shared instance. Requests are signed with a
+//! key derived from HMAC_SECRET_KEY for the challenge alone, which compose files of the challenge
+//! get as `{{oracle_key}}`. Oracles never see HMAC_SECRET_KEY, so they can't derive the flags or
+//! passwords of other challenges.
+
+use std::{sync::LazyLock, time::Duration};
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::instances::{full_instance_ns, shared::SHARED_INSTANCE_ID};
+
+/// Header with the hex encoded HMAC-SHA256 of the request body, keyed with the oracle key
+pub const SIGNATURE_HEADER: &str = "X-Plfanzen-Signature";
+/// Oracles may take this long to answer unless the challenge allows more
+const DEFAULT_TIMEOUT_SECS: u64 = 5;
+const MAX_TIMEOUT_SECS: u64 = 30;
+
+static HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("Failed to create flag oracle HTTP client")
+});
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FlagOracle {
+    /// URL of a service of the shared instance submissions are POSTed to, e.g.
+    /// http://checker.challenge-my-challenge-instance-shared.svc.cluster.local/check
+    pub url: String,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_timeout_secs() -> u64 {
+    DEFAULT_TIMEOUT_SECS
+}
+
+/// What the oracle receives, the timestamp lets it reject replayed requests
+#[derive(Serialize)]
+struct OracleRequest<'a> {
+    challenge_id: &'a str,
+    actor: &'a str,
+    flag: &'a str,
+    timestamp: i64,
+}
+
+#[derive(Deserialize)]
+struct OracleVerdict {
+    correct: bool,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Signs a request body so the oracle can tell it comes from the manager
+pub fn sign(oracle_key: &[u8], body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(oracle_key).expect("HMAC can take key of any size");
+    mac.update(body);
+    hex(&mac.finalize().into_bytes())
+}
+
+fn derive_oracle_key(hmac_key: &[u8], challenge_id: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(hmac_key).expect("HMAC can take key of any size");
+    mac.update(b"oracle");
+    mac.update(&[0]);
+    mac.update(challenge_id.as_bytes());
+    hex(&mac.finalize().into_bytes())
+}
+
+/// The key requests to the oracle of a challenge are signed with, hex encoded
+pub fn oracle_key(challenge_id: &str) -> Result<String, String> {
+    let hmac_key = std::env::var("HMAC_SECRET_KEY")
+        .map_err(|_| "HMAC_SECRET_KEY must be set for flag oracles".to_string())?;
+    Ok(derive_oracle_key(hmac_key.as_bytes(), challenge_id))
+}
+
+/// Submissions must not leave the challenge, so only services in the namespace of its shared
+/// instance are allowed
+fn is_challenge_service(url: &reqwest::Url, challenge_id: &str) -> bool {
+    let namespace = full_instance_ns(challenge_id, SHARED_INSTANCE_ID);
+    matches!(url.scheme(), "http" | "https")
+        && url
+            .host_str()
+            .and_then(|host| {
+                host.strip_suffix(".svc.cluster.local")
+                    .or_else(|| host.strip_suffix(".svc"))
+            })
+            .and_then(|name| name.split_once('.'))
+            .is_some_and(|(service, service_ns)| !service.is_empty() && service_ns == namespace)
+}
+
+impl FlagOracle {
+    /// Forwards a submission to the oracle and returns its verdict
+    pub async fn check(&self, challenge_id: &str, actor: &str, flag: &str) -> Result<bool, String> {
+        let url = reqwest::Url::parse(&self.url)
+            .map_err(|e| format!("Invalid flag oracle URL {}: {}", self.url, e))?;
+        if !is_challenge_service(&url, challenge_id) {
+            return Err(format!(
+                "Flag oracle URL {} is not a service of the shared instance of {}",
+                self.url, challenge_id
+            ));
+        }
+        let oracle_key = oracle_key(challenge_id)?;
+        let body = serde_json::to_vec(&OracleRequest {
+            challenge_id,
+            actor,
+            flag,
+            timestamp: chrono::Utc::now().timestamp(),
+        })
+        .map_err(|e| e.to_string())?;
+        let response = HTTP_CLIENT
+            .post(url)
+            .timeout(Duration::from_secs(
+                self.timeout_secs.clamp(1, MAX_TIMEOUT_SECS),
+            ))
+            .header("Content-Type", "application/json")
+            .header(SIGNATURE_HEADER, sign(oracle_key.as_bytes(), &body))
+            .body(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("Flag oracle request failed: {}", e))?
+            .bytes()
+            .await
+            .map_err(|e| format!("Failed to read flag oracle response: {}", e))?;
+        serde_json::from_slice::<OracleVerdict>(&response)
+            .map(|verdict| verdict.correct)
+            .map_err(|e| format!("Invalid flag oracle response: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_challenge_services() {
+        for (url, allowed) in [
+            (
+                "http://checker.challenge-web-instance-shared.svc.cluster.local/check",
+                true,
+            ),
+            (
+                "http://checker.challenge-web-instance-shared.svc:8080",
+                true,
+            ),
+            (
+                "http://checker.challenge-pwn-instance-shared.svc/check",
+                false,
+            ),
+            ("http://kubernetes.default.svc/api", false),
+            ("http://challenge-web-instance-shared.svc/check", false),
+            ("https://example.com/check", false),
+            ("http://10.0.0.1/check", false),
+            ("file:///etc/passwd", false),
+        ] {
+            assert_eq!(
+                is_challenge_service(&reqwest::Url::parse(url).unwrap(), "web"),
+                allowed,
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_oracle_key_per_challenge() {
+        let key = derive_oracle_key(b"secret", "web");
+        assert_eq!(key.len(), 64);
+        assert_eq!(key, derive_oracle_key(b"secret", "web"));
+        assert_ne!(key, derive_oracle_key(b"secret", "pwn"));
+        assert_ne!(key, derive_oracle_key(b"other", "web"));
+    }
+
+    #[test]
+    fn test_signature() {
+        let signature = sign(b"secret", br#"{"flag":"flag{x}"}"#);
+        assert_eq!(signature.len(), 64);
+        assert_eq!(signature, sign(b"secret", br#"{"flag":"flag{x}"}"#));
+        assert_ne!(signature, sign(b"other", br#"{"flag":"flag{x}"}"#));
+    }
+}
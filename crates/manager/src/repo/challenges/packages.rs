@@ -0,0 +1,392 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Attachments and source bundles packed when the repository is synced, so players can download
+//! them from a static file server instead of through the manager.
+//!
+//! Files are stored below ATTACHMENT_DIR as `{challenge_id}/{sha256}/{file name}`, which
+//! ATTACHMENT_BASE_URL has to serve (e.g. a web server or a bucket mounted at that directory).
+//! Only challenges without templates can be packed, the files of the others are rendered for
+//! every actor and keep being served by RetrieveFile and ExportChallenge.
+//!
+//! Challenges whose directory didn't change since they were last packed aren't packed again.
+//! Unreleased challenges are only packed at their release time, as anything below ATTACHMENT_DIR
+//! can be downloaded. For the same reason, the manifest of the packages is stored next to it
+//! instead of inside, as `{ATTACHMENT_DIR}.manifest.json`.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::Notify;
+
+use crate::{
+    instances::shared::challenge_revision,
+    repo::challenges::{
+        dir_packer::safe_pack_challenge,
+        loader::{load_challenge_from_dir, load_challenges_from_repo},
+    },
+};
+
+/// Name of the manifest inside ATTACHMENT_DIR before it was moved out, removed when packing
+const LEGACY_MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackedFile {
+    pub name: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// Everything packed for a challenge
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChallengePackage {
+    /// Hash of the challenge directory it was packed from, see [`challenge_revision`]
+    #[serde(default)]
+    pub revision: String,
+    pub files: Vec<PackedFile>,
+    /// The sanitized source, if the challenge publishes it
+    pub source: Option<PackedFile>,
+}
+
+pub struct PackageStore {
+    dir: PathBuf,
+    base_url: String,
+    /// Packages of the synced commit, also stored in the manifest to survive restarts
+    packages: Mutex<HashMap<String, ChallengePackage>>,
+    /// When the next challenge that wasn't packed yet is released, as a Unix timestamp
+    next_release: Mutex<Option<u64>>,
+    repacked: Notify,
+}
+
+/// Whether the challenge is rendered the same for every actor
+pub fn is_static(chall_dir: &Path) -> std::io::Result<bool> {
+    for entry in std::fs::read_dir(chall_dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if !is_static(&path)? {
+                return Ok(false);
+            }
+        } else if path.extension().and_then(|s| s.to_str()) == Some("plftera") {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// The manifest is stored next to the served directory, it lists every challenge, including the
+/// hashes that make up the URLs of their files
+fn manifest_path(dir: &Path) -> PathBuf {
+    let mut file_name = dir.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".manifest.json");
+    dir.with_file_name(file_name)
+}
+
+/// Name a file is stored and served under, attachments may be in subdirectories
+fn stored_name(name: &str) -> &str {
+    Path::new(name)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(name)
+}
+
+impl PackageStore {
+    /// None unless both ATTACHMENT_DIR and ATTACHMENT_BASE_URL are set
+    pub fn from_env() -> Option<Self> {
+        let dir = std::env::var("ATTACHMENT_DIR")
+            .ok()
+            .filter(|d| !d.is_empty())?;
+        let base_url = std::env::var("ATTACHMENT_BASE_URL")
+            .ok()
+            .filter(|u| !u.is_empty())?;
+        Some(Self::new(
+            PathBuf::from(dir),
+            base_url.trim_end_matches('/').to_string(),
+        ))
+    }
+
+    fn new(dir: PathBuf, base_url: String) -> Self {
+        let packages = std::fs::read(manifest_path(&dir))
+            .ok()
+            .and_then(|manifest| serde_json::from_slice(&manifest).ok())
+            .unwrap_or_default();
+        PackageStore {
+            dir,
+            base_url,
+            packages: Mutex::new(packages),
+            next_release: Mutex::new(None),
+            repacked: Notify::new(),
+        }
+    }
+
+    pub fn get(&self, challenge_id: &str) -> Option<ChallengePackage> {
+        self.packages
+            .lock()
+            .expect("Package store lock poisoned")
+            .get(challenge_id)
+            .cloned()
+    }
+
+    pub fn url(&self, challenge_id: &str, file: &PackedFile) -> String {
+        format!(
+            "{}/{}/{}/{}",
+            self.base_url,
+            challenge_id,
+            file.sha256,
+            stored_name(&file.name)
+        )
+    }
+
+    /// URL of an attachment as it was rendered for an actor, if that exact file was packed
+    pub fn attachment_url(&self, challenge_id: &str, name: &str, sha256: &str) -> Option<String> {
+        let package = self.get(challenge_id)?;
+        let file = package
+            .files
+            .iter()
+            .find(|file| file.name == name && file.sha256 == sha256)?;
+        Some(self.url(challenge_id, file))
+    }
+
+    fn path(&self, challenge_id: &str, file: &PackedFile) -> PathBuf {
+        self.dir
+            .join(challenge_id)
+            .join(&file.sha256)
+            .join(stored_name(&file.name))
+    }
+
+    /// Whether all files of a package are still stored
+    fn is_stored(&self, challenge_id: &str, package: &ChallengePackage) -> bool {
+        package
+            .files
+            .iter()
+            .chain(&package.source)
+            .all(|file| self.path(challenge_id, file).is_file())
+    }
+
+    fn store(&self, challenge_id: &str, name: &str, data: &[u8]) -> std::io::Result<PackedFile> {
+        let file = PackedFile {
+            name: name.to_string(),
+            size: data.len() as u64,
+            sha256: format!("{:x}", Sha256::digest(data)),
+        };
+        let path = self.path(challenge_id, &file);
+        // Files are stored by their hash, so one that exists already has the same content
+        if !path.is_file() {
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(path, data)?;
+        }
+        Ok(file)
+    }
+
+    async fn pack_challenge(
+        &self,
+        challenge_id: &str,
+        chall_dir: &Path,
+    ) -> Result<Option<ChallengePackage>, Box<dyn std::error::Error>> {
+        if !is_static(chall_dir)? {
+            return Ok(None);
+        }
+        let revision = challenge_revision(chall_dir)?;
+        if let Some(package) = self.get(challenge_id)
+            && package.revision == revision
+            && self.is_stored(challenge_id, &package)
+        {
+            return Ok(Some(package));
+        }
+        let challenge = load_challenge_from_dir(chall_dir, "", false).await?;
+        let chall_dir = std::fs::canonicalize(chall_dir)?;
+        let mut package = ChallengePackage {
+            revision,
+            ..Default::default()
+        };
+        for name in &challenge.metadata.attachments {
+            let path = std::fs::canonicalize(chall_dir.join(name))?;
+            if !path.starts_with(&chall_dir) {
+                return Err(format!("Attachment {} is outside of the challenge", name).into());
+            }
+            package
+                .files
+                .push(self.store(challenge_id, name, &std::fs::read(path)?)?);
+        }
+        if challenge.metadata.auto_publish_src {
+            let bundle = safe_pack_challenge(&chall_dir)?;
+            package.source =
+                Some(self.store(challenge_id, &format!("{}.tar.gz", challenge_id), &bundle)?);
+        }
+        Ok(Some(package))
+    }
+
+    /// Packs every released challenge of the repository which changed since the previous sync,
+    /// replacing its packages. Challenges that fail to pack are logged and skipped.
+    pub async fn pack_challenges(&self, repo_dir: &Path) -> std::io::Result<()> {
+        // Loaded with their release waves, to know which ones are released
+        let challenges = load_challenges_from_repo(repo_dir, "", false)
+            .await
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        let now = chrono::Utc::now().timestamp().max(0) as u64;
+        let mut packages = HashMap::new();
+        let mut next_release = None;
+        for (challenge_id, challenge) in challenges {
+            if let Some(release_time) = challenge.metadata.release_time
+                && release_time > now
+            {
+                next_release =
+                    Some(next_release.map_or(release_time, |next: u64| next.min(release_time)));
+                continue;
+            }
+            let path = repo_dir.join("challs").join(&challenge_id);
+            match self.pack_challenge(&challenge_id, &path).await {
+                Ok(Some(package)) => {
+                    packages.insert(challenge_id, package);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::warn!("Failed to pack challenge {}: {}", challenge_id, e);
+                }
+            }
+        }
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(
+            manifest_path(&self.dir),
+            serde_json::to_vec(&packages).map_err(std::io::Error::other)?,
+        )?;
+        match std::fs::remove_file(self.dir.join(LEGACY_MANIFEST_FILE)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        *self.packages.lock().expect("Package store lock poisoned") = packages;
+        *self
+            .next_release
+            .lock()
+            .expect("Package store lock poisoned") = next_release;
+        self.repacked.notify_waiters();
+        Ok(())
+    }
+
+    /// Starts packing challenges of the repository checked out at `repo_dir` when they are
+    /// released
+    pub fn spawn_release_packer(self: Arc<Self>, repo_dir: PathBuf) {
+        tokio::spawn(async move {
+            loop {
+                // Waiting for a repack starts before reading its result, so none is missed
+                let repacked = self.repacked.notified();
+                let mut repacked = std::pin::pin!(repacked);
+                repacked.as_mut().enable();
+                let next_release = *self
+                    .next_release
+                    .lock()
+                    .expect("Package store lock poisoned");
+                let Some(next_release) = next_release else {
+                    repacked.await;
+                    continue;
+                };
+                let now = chrono::Utc::now().timestamp().max(0) as u64;
+                let delay = std::time::Duration::from_secs(next_release.saturating_sub(now));
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {
+                        if let Err(e) = self.pack_challenges(&repo_dir).await {
+                            tracing::error!("Failed to pack released challenges: {}", e);
+                            // Not tried again before the next sync
+                            *self.next_release.lock().expect("Package store lock poisoned") = None;
+                        }
+                    }
+                    _ = repacked => {}
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_static() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("dist")).unwrap();
+        std::fs::write(dir.path().join("docker-compose.yml"), "").unwrap();
+        std::fs::write(dir.path().join("dist/chall.zip"), "").unwrap();
+        assert!(is_static(dir.path()).unwrap());
+
+        std::fs::write(dir.path().join("dist/flag.txt.plftera"), "").unwrap();
+        assert!(!is_static(dir.path()).unwrap());
+    }
+
+    #[test]
+    fn test_url() {
+        let store = PackageStore::new(
+            PathBuf::from("/data/attachments"),
+            "https://files.example.com".to_string(),
+        );
+        *store.packages.lock().unwrap() = HashMap::from([(
+            "web".to_string(),
+            ChallengePackage {
+                revision: "rev".to_string(),
+                files: vec![PackedFile {
+                    name: "dist/chall.zip".to_string(),
+                    size: 3,
+                    sha256: "abc".to_string(),
+                }],
+                source: None,
+            },
+        )]);
+        assert_eq!(
+            store
+                .attachment_url("web", "dist/chall.zip", "abc")
+                .as_deref(),
+            Some("https://files.example.com/web/abc/chall.zip")
+        );
+        // Rendered differently for the actor than when it was packed
+        assert_eq!(store.attachment_url("web", "dist/chall.zip", "def"), None);
+    }
+
+    #[test]
+    fn test_manifest_is_not_served() {
+        assert_eq!(
+            manifest_path(Path::new("/data/attachments")),
+            Path::new("/data/attachments.manifest.json")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pack_unchanged() {
+        let challenge = tempfile::tempdir().unwrap();
+        std::fs::write(challenge.path().join("chall.zip"), "zip").unwrap();
+        let store_dir = tempfile::tempdir().unwrap();
+        let store = PackageStore::new(
+            store_dir.path().join("attachments"),
+            "https://files.example.com".to_string(),
+        );
+        let file = PackedFile {
+            name: "chall.zip".to_string(),
+            size: 3,
+            sha256: format!("{:x}", Sha256::digest(b"zip")),
+        };
+        let packed = ChallengePackage {
+            revision: challenge_revision(challenge.path()).unwrap(),
+            files: vec![file.clone()],
+            source: None,
+        };
+        store.store("web", &file.name, b"zip").unwrap();
+        store
+            .packages
+            .lock()
+            .unwrap()
+            .insert("web".to_string(), packed.clone());
+        // There is no docker-compose.yml to load, so this only succeeds if nothing is repacked
+        assert_eq!(
+            store.pack_challenge("web", challenge.path()).await.unwrap(),
+            Some(packed.clone())
+        );
+
+        std::fs::remove_file(store.path("web", &file)).unwrap();
+        assert!(!store.is_stored("web", &packed));
+        assert!(store.pack_challenge("web", challenge.path()).await.is_err());
+    }
+}
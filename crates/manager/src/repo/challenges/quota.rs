@@ -0,0 +1,240 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Limits on the total resources of an instance, so one broken challenge can't take over the
+//! cluster.
+//!
+//! The limits are enforced by a ResourceQuota in the instance namespace. The defaults come from
+//! INSTANCE_QUOTA_CPU, INSTANCE_QUOTA_MEMORY, INSTANCE_QUOTA_PODS and INSTANCE_QUOTA_STORAGE, and
+//! challenges can override them with the `x-ctf-resource-quota` extension. No quota is created if
+//! none of them are set.
+
+use std::collections::BTreeMap;
+
+use k8s_openapi::{
+    api::core::v1::{LimitRange, LimitRangeItem, LimitRangeSpec, ResourceQuota, ResourceQuotaSpec},
+    apimachinery::pkg::api::resource::Quantity,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::repo::challenges::{loader::Challenge, vm::HasVms};
+
+pub const QUOTA_NAME: &str = "instance-quota";
+/// Resources of containers which don't set any, if the quota covers them
+const DEFAULT_CPU_REQUEST: &str = "100m";
+const DEFAULT_MEMORY_REQUEST: &str = "128Mi";
+const DEFAULT_CPU_LIMIT: &str = "500m";
+const DEFAULT_MEMORY_LIMIT: &str = "512Mi";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaConfig {
+    /// CPU all pods of an instance may use together, like "2" or "1500m"
+    #[serde(default)]
+    pub cpu: Option<String>,
+    /// Memory all pods of an instance may use together, like "4Gi"
+    #[serde(default)]
+    pub memory: Option<String>,
+    /// Number of pods an instance may have
+    #[serde(default)]
+    pub pods: Option<u32>,
+    /// Storage all volume claims of an instance may request together
+    #[serde(default)]
+    pub storage: Option<String>,
+}
+
+impl QuotaConfig {
+    pub fn from_env() -> Self {
+        QuotaConfig {
+            cpu: std::env::var("INSTANCE_QUOTA_CPU").ok(),
+            memory: std::env::var("INSTANCE_QUOTA_MEMORY").ok(),
+            pods: std::env::var("INSTANCE_QUOTA_PODS")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+            storage: std::env::var("INSTANCE_QUOTA_STORAGE").ok(),
+        }
+    }
+
+    /// The values set in `overrides` replace those of self
+    pub fn merge(self, overrides: QuotaConfig) -> Self {
+        QuotaConfig {
+            cpu: overrides.cpu.or(self.cpu),
+            memory: overrides.memory.or(self.memory),
+            pods: overrides.pods.or(self.pods),
+            storage: overrides.storage.or(self.storage),
+        }
+    }
+
+    fn hard(&self, with_limits: bool) -> BTreeMap<String, Quantity> {
+        let mut hard = BTreeMap::new();
+        for (resource, value) in [("cpu", &self.cpu), ("memory", &self.memory)] {
+            let Some(value) = value else {
+                continue;
+            };
+            hard.insert(format!("requests.{}", resource), Quantity(value.clone()));
+            if with_limits {
+                hard.insert(format!("limits.{}", resource), Quantity(value.clone()));
+            }
+        }
+        if let Some(pods) = self.pods {
+            hard.insert("pods".to_string(), Quantity(pods.to_string()));
+        }
+        if let Some(storage) = &self.storage {
+            hard.insert("requests.storage".to_string(), Quantity(storage.clone()));
+        }
+        hard
+    }
+}
+
+pub trait HasResourceQuota {
+    fn get_resource_quota(&self) -> QuotaConfig;
+}
+
+impl HasResourceQuota for compose_spec::Compose {
+    fn get_resource_quota(&self) -> QuotaConfig {
+        self.extensions
+            .get("x-ctf-resource-quota")
+            .and_then(|v| match serde_yaml::from_value::<QuotaConfig>(v.clone()) {
+                Ok(config) => Some(config),
+                Err(err) => {
+                    tracing::error!("Failed to parse x-ctf-resource-quota: {}", err);
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Defaults for containers which don't set their own resources, from
+/// INSTANCE_DEFAULT_{CPU,MEMORY}_{REQUEST,LIMIT}. Resources without a variable set have no default.
+#[derive(Debug, Clone, Default)]
+pub struct ContainerDefaults {
+    pub requests: BTreeMap<String, Quantity>,
+    pub limits: BTreeMap<String, Quantity>,
+}
+
+impl ContainerDefaults {
+    pub fn from_env() -> Self {
+        let from_env = |kind: &str| -> BTreeMap<String, Quantity> {
+            ["cpu", "memory"]
+                .into_iter()
+                .filter_map(|resource| {
+                    let var = format!("INSTANCE_DEFAULT_{}_{}", resource.to_uppercase(), kind);
+                    Some((resource.to_string(), Quantity(std::env::var(var).ok()?)))
+                })
+                .collect()
+        };
+        ContainerDefaults {
+            requests: from_env("REQUEST"),
+            limits: from_env("LIMIT"),
+        }
+    }
+}
+
+fn with_fallbacks(
+    configured: &BTreeMap<String, Quantity>,
+    cpu: &str,
+    memory: &str,
+) -> BTreeMap<String, Quantity> {
+    let mut quantities = BTreeMap::from([
+        ("cpu".to_string(), Quantity(cpu.to_string())),
+        ("memory".to_string(), Quantity(memory.to_string())),
+    ]);
+    quantities.extend(configured.clone());
+    quantities
+}
+
+/// The ResourceQuota of an instance of the challenge, and the LimitRange giving containers
+/// without resources the defaults the quota requires them to have.
+///
+/// KubeVirt doesn't set limits on the pods of virtual machines, and default limits could be below
+/// the memory of the machine. For challenges with virtual machines, only requests are limited.
+pub fn quota_objects(
+    challenge: &Challenge,
+    defaults: QuotaConfig,
+) -> Option<(ResourceQuota, Option<LimitRange>)> {
+    let config = defaults.merge(challenge.compose.get_resource_quota());
+    let with_limits = challenge.compose.get_vms().is_empty();
+    let hard = config.hard(with_limits);
+    if hard.is_empty() {
+        return None;
+    }
+    let metadata = kube::api::ObjectMeta {
+        name: Some(QUOTA_NAME.to_string()),
+        ..Default::default()
+    };
+    let quota = ResourceQuota {
+        metadata: metadata.clone(),
+        spec: Some(ResourceQuotaSpec {
+            hard: Some(hard),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    // Configured defaults are already set on the containers, unless they opted out of them
+    let container_defaults = ContainerDefaults::from_env();
+    let limit_range = (config.cpu.is_some() || config.memory.is_some()).then(|| LimitRange {
+        metadata,
+        spec: Some(LimitRangeSpec {
+            limits: vec![LimitRangeItem {
+                type_: "Container".to_string(),
+                default_request: Some(with_fallbacks(
+                    &container_defaults.requests,
+                    DEFAULT_CPU_REQUEST,
+                    DEFAULT_MEMORY_REQUEST,
+                )),
+                default: with_limits.then(|| {
+                    with_fallbacks(
+                        &container_defaults.limits,
+                        DEFAULT_CPU_LIMIT,
+                        DEFAULT_MEMORY_LIMIT,
+                    )
+                }),
+                ..Default::default()
+            }],
+        }),
+    });
+    Some((quota, limit_range))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let defaults = QuotaConfig {
+            cpu: Some("2".to_string()),
+            memory: Some("4Gi".to_string()),
+            pods: Some(10),
+            storage: None,
+        };
+        let overrides: QuotaConfig = serde_yaml::from_str("memory: 16Gi\nstorage: 20Gi\n").unwrap();
+        assert_eq!(
+            defaults.merge(overrides),
+            QuotaConfig {
+                cpu: Some("2".to_string()),
+                memory: Some("16Gi".to_string()),
+                pods: Some(10),
+                storage: Some("20Gi".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_hard() {
+        let config = QuotaConfig {
+            cpu: Some("2".to_string()),
+            pods: Some(10),
+            ..Default::default()
+        };
+        let hard = config.hard(true);
+        assert_eq!(hard.get("requests.cpu"), Some(&Quantity("2".to_string())));
+        assert_eq!(hard.get("limits.cpu"), Some(&Quantity("2".to_string())));
+        assert_eq!(hard.get("pods"), Some(&Quantity("10".to_string())));
+        assert!(!hard.contains_key("requests.memory"));
+
+        assert!(!config.hard(false).contains_key("limits.cpu"));
+        assert!(QuotaConfig::default().hard(true).is_empty());
+    }
+}
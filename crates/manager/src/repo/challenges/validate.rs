@@ -0,0 +1,314 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Dry runs of everything the manager does with a challenge, without deploying it, so authors
+//! and CI find mistakes before a sync.
+//!
+//! Besides the ValidateChallenge RPC, the manager validates challenges of a local checkout when
+//! it is run as `plfanzen-manager validate [REPO_DIR] [CHALLENGE_ID...]` (or through a link
+//! named `plfanzen-validate`), exiting with 1 if any challenge has errors. Without challenge IDs,
+//! event.yml is validated as well.
+
+use std::path::Path;
+
+use crate::{
+    instances::{deploy::instance_objects, full_instance_ns, priority::InstancePriority},
+    repo::{
+        EventConfig,
+        challenges::{
+            loader::{
+                Challenge, load_challenge_from_dir, load_challenge_instance_from_repo,
+                tera::render_dir_recursively,
+            },
+            metadata::FlagValidator,
+        },
+        event_config::validate::validate_event_config,
+        image_builder::{BuildConfig, use_built_images},
+    },
+};
+
+/// Actor and instance the challenge is rendered for
+pub const VALIDATION_ACTOR: &str = "team-validation";
+const VALIDATION_INSTANCE_ID: &str = "validation";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The challenge can't be listed, started or solved like this
+    Error,
+    /// Probably not intended, but works
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Default)]
+struct Issues(Vec<Issue>);
+
+impl Issues {
+    fn error(&mut self, message: impl Into<String>) {
+        self.0.push(Issue {
+            severity: Severity::Error,
+            message: message.into(),
+        });
+    }
+
+    fn warning(&mut self, message: impl Into<String>) {
+        self.0.push(Issue {
+            severity: Severity::Warning,
+            message: message.into(),
+        });
+    }
+}
+
+/// Checks the timestamps and attachments of a loaded challenge
+fn check_metadata(challenge: &Challenge, issues: &mut Issues) {
+    let metadata = &challenge.metadata;
+    if let (Some(release_time), Some(end_time)) = (metadata.release_time, metadata.end_time)
+        && end_time <= release_time
+    {
+        issues.error("The end time is not after the release time");
+    }
+    for attachment in &metadata.attachments {
+        if !challenge.files.iter().any(|file| &file.name == attachment) {
+            issues.error(format!("Attachment {} does not exist", attachment));
+        }
+    }
+}
+
+/// Checks that the flag an actor gets is accepted, and that validation scripts run
+async fn check_flag(challenge_id: &str, challenge: &Challenge, issues: &mut Issues) {
+    let metadata = &challenge.metadata;
+    let flag = match metadata.actor_flag(challenge_id, VALIDATION_ACTOR) {
+        Ok(flag) => flag,
+        Err(e) => {
+            issues.error(format!("Failed to derive the flag: {}", e));
+            return;
+        }
+    };
+    let submission = match (&metadata.flag_validator, flag) {
+        (_, Some(flag)) => flag,
+        (FlagValidator::JsFunction { .. }, None) => "flag{validation}".to_string(),
+        // Asking an oracle would need the instance it runs in
+        (_, None) => return,
+    };
+    match metadata
+        .check_flag(challenge_id, VALIDATION_ACTOR, &submission)
+        .await
+    {
+        Ok(false) if !matches!(metadata.flag_validator, FlagValidator::JsFunction { .. }) => {
+            issues.error("The flag is rejected when it is submitted");
+        }
+        Ok(_) => {}
+        Err(e) => issues.error(format!("Failed to check a flag: {}", e)),
+    }
+}
+
+/// Converts the challenge to the objects of an instance, like a start would
+fn check_conversion(
+    chall_dir: &Path,
+    challenge_id: &str,
+    mut challenge: Challenge,
+    issues: &mut Issues,
+) {
+    if BuildConfig::from_env().is_some() {
+        if let Err(e) = use_built_images(&mut challenge.compose, chall_dir, challenge_id, None) {
+            issues.error(e);
+            return;
+        }
+    } else {
+        for (svc_id, svc) in challenge.compose.services.iter_mut() {
+            if svc.build.take().is_some() {
+                issues.warning(format!(
+                    "Service {} has a build section, which can only be deployed with BUILD_REGISTRY",
+                    svc_id
+                ));
+                svc.image = compose_spec::service::Image::parse(format!(
+                    "validation/{}",
+                    svc_id.to_string().to_lowercase()
+                ))
+                .ok();
+            }
+        }
+    }
+    let flag = challenge
+        .metadata
+        .actor_flag(challenge_id, VALIDATION_ACTOR)
+        .ok()
+        .flatten();
+    let working_dir = match tempfile::tempdir() {
+        Ok(working_dir) => working_dir,
+        Err(e) => {
+            issues.error(format!("Failed to create a working directory: {}", e));
+            return;
+        }
+    };
+    if let Err(e) = render_dir_recursively(chall_dir, working_dir.path(), VALIDATION_ACTOR, false) {
+        issues.error(format!("Failed to render the challenge: {}", e));
+        return;
+    }
+    if let Err(e) = instance_objects(
+        challenge,
+        &full_instance_ns(challenge_id, VALIDATION_INSTANCE_ID),
+        &std::env::var("EXPOSED_DOMAIN").unwrap_or("localhost".to_string()),
+        working_dir.path(),
+        VALIDATION_ACTOR,
+        VALIDATION_INSTANCE_ID,
+        flag.as_deref(),
+        InstancePriority::Player,
+    ) {
+        issues.error(format!("Failed to convert the challenge: {}", e));
+    }
+}
+
+/// Checks that the event config knows the category and difficulty, and that points can be
+/// calculated
+async fn check_points(repo_dir: &Path, challenge: &Challenge, issues: &mut Issues) {
+    let event_config = match EventConfig::try_load_from_repo(repo_dir).await {
+        Ok(event_config) => event_config,
+        Err(e) => {
+            issues.error(format!("Failed to load the event config: {}", e));
+            return;
+        }
+    };
+    let metadata = &challenge.metadata;
+    for category in &metadata.categories {
+        if !event_config.categories.contains_key(category) {
+            issues.warning(format!("Category {} is not in the event config", category));
+        }
+    }
+    if !event_config.difficulties.contains_key(&metadata.difficulty) {
+        issues.warning(format!(
+            "Difficulty {} is not in the event config",
+            metadata.difficulty
+        ));
+    }
+    // The first solve of a single competitor and the last one of many, the extremes of most
+    // scoring functions
+    for (total_solves, nth_solve, competitors) in [(1, 1, 1), (100, 100, 100)] {
+        if let Err(e) = event_config
+            .calculate_points(metadata, total_solves, nth_solve, competitors)
+            .await
+        {
+            issues.error(format!("Failed to calculate points: {}", e));
+            return;
+        }
+    }
+}
+
+/// Runs every check on a challenge of the repository
+pub async fn validate_challenge(repo_dir: &Path, challenge_id: &str) -> Vec<Issue> {
+    let mut issues = Issues::default();
+    let chall_dir = repo_dir.join("challs").join(challenge_id);
+    if !chall_dir.join("docker-compose.yml").is_file() {
+        issues.error(format!("Challenge {} does not exist", challenge_id));
+        return issues.0;
+    }
+    let challenge = match load_challenge_instance_from_repo(
+        repo_dir,
+        challenge_id,
+        VALIDATION_ACTOR,
+        VALIDATION_INSTANCE_ID,
+    )
+    .await
+    {
+        Ok(challenge) => challenge,
+        Err(e) => {
+            issues.error(format!("Failed to load the challenge: {}", e));
+            return issues.0;
+        }
+    };
+    check_metadata(&challenge, &mut issues);
+    check_flag(challenge_id, &challenge, &mut issues).await;
+    check_points(repo_dir, &challenge, &mut issues).await;
+    if challenge.metadata.auto_publish_src
+        && let Err(e) = load_challenge_from_dir(&chall_dir, VALIDATION_ACTOR, true).await
+    {
+        issues.error(format!("Failed to export the challenge: {}", e));
+    }
+    check_conversion(&chall_dir, challenge_id, challenge, &mut issues);
+    issues.0
+}
+
+/// Arguments after the mode, if the manager was started to validate challenges
+pub fn cli_args() -> Option<Vec<String>> {
+    let mut args = std::env::args();
+    let program = args.next()?;
+    if Path::new(&program).file_name().and_then(|n| n.to_str()) == Some("plfanzen-validate") {
+        return Some(args.collect());
+    }
+    match args.next().as_deref() {
+        Some("validate") => Some(args.collect()),
+        _ => None,
+    }
+}
+
+/// Validates the given challenges (or all of them) of the repository in the first argument,
+/// printing the issues. Returns the exit code.
+pub async fn run_cli(args: Vec<String>) -> i32 {
+    let mut args = args.into_iter();
+    let repo_dir = args.next().unwrap_or_else(|| ".".to_string());
+    let repo_dir = Path::new(&repo_dir);
+    let mut challenge_ids: Vec<String> = args.collect();
+    let mut errors = 0;
+    if challenge_ids.is_empty() {
+        let diagnostics = validate_event_config(repo_dir).await;
+        if diagnostics.is_empty() {
+            println!("event.yml: ok");
+        }
+        for diagnostic in diagnostics {
+            if diagnostic.severity == Severity::Error {
+                errors += 1;
+            }
+            println!("{}", diagnostic);
+        }
+        let Ok(entries) = std::fs::read_dir(repo_dir.join("challs")) else {
+            eprintln!("{} has no challs directory", repo_dir.to_string_lossy());
+            return 1;
+        };
+        challenge_ids = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect();
+        challenge_ids.sort();
+    }
+    for challenge_id in challenge_ids {
+        let issues = validate_challenge(repo_dir, &challenge_id).await;
+        if issues.is_empty() {
+            println!("{}: ok", challenge_id);
+        }
+        for issue in issues {
+            let severity = match issue.severity {
+                Severity::Error => {
+                    errors += 1;
+                    "error"
+                }
+                Severity::Warning => "warning",
+            };
+            println!("{}: {}: {}", challenge_id, severity, issue.message);
+        }
+    }
+    if errors > 0 { 1 } else { 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_validate_missing_challenge() {
+        let repo = tempfile::tempdir().unwrap();
+        assert_eq!(
+            validate_challenge(repo.path(), "web").await,
+            vec![Issue {
+                severity: Severity::Error,
+                message: "Challenge web does not exist".to_string(),
+            }]
+        );
+    }
+}
@@ -7,6 +7,7 @@ use std::collections::BTreeMap;
 use serde::{Deserialize, Serialize};
 
 use crate::repo::challenges::compose::service::networking::NetworkPolicy;
+use crate::repo::challenges::compose::service::scheduling::Scheduling;
 use crate::repo::challenges::compose::service::{AsService, HasPorts};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -26,6 +27,8 @@ pub struct VirtualMachine {
     #[serde(default)]
     pub ports: compose_spec::service::ports::Ports,
     pub network_policy: Option<NetworkPolicy>,
+    #[serde(default)]
+    pub scheduling: Scheduling,
 }
 
 impl HasPorts for VirtualMachine {
@@ -126,6 +129,9 @@ impl VirtualMachine {
                                 },
                             }
                         }).collect()),
+                        node_selector: self.scheduling.node_selector(),
+                        tolerations: self.scheduling.tolerations().and_then(convert_crd_field),
+                        affinity: self.scheduling.affinity.as_ref().and_then(convert_crd_field),
                         ..Default::default()
                     }),
                     ..Default::default()
@@ -137,6 +143,14 @@ impl VirtualMachine {
     }
 }
 
+/// The KubeVirt CRD has its own copies of the Kubernetes types, with the same fields
+fn convert_crd_field<T: Serialize, U: serde::de::DeserializeOwned>(value: T) -> Option<U> {
+    serde_json::to_value(value)
+        .and_then(serde_json::from_value)
+        .inspect_err(|e| tracing::error!("Failed to convert scheduling of virtual machine: {}", e))
+        .ok()
+}
+
 impl AsService for VirtualMachine {
     fn as_internal_svc(&self, id: String) -> k8s_openapi::api::core::v1::Service {
         k8s_openapi::api::core::v1::Service {
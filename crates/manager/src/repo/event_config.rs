@@ -2,7 +2,7 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::rc::Rc;
 use std::sync::Mutex;
 
@@ -10,9 +10,17 @@ use boa_engine::value::TryIntoJs;
 use boa_engine::{JsError, JsNativeError, JsValue};
 use serde::{Deserialize, Serialize};
 
-use crate::{js::create_boa_context, repo::challenges::metadata::CtfChallengeMetadata};
+use crate::{
+    js::{ScriptJob, run_script},
+    repo::{LocalizedMarkdown, challenges::metadata::CtfChallengeMetadata},
+};
 use boa_engine::{NativeFunction, Source, js_string, js_value, object::builtins::JsFunction};
 
+pub mod scoring;
+pub mod validate;
+
+use scoring::Scoring;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CtfCategory {
     pub name: String,
@@ -26,24 +34,75 @@ pub struct CtfDifficulty {
     pub color: Option<String>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CtfDivision {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct NamePolicy {
+    // Names nobody can register in addition to the built-in ones like admin, compared ignoring
+    // case, separators and lookalike characters
+    #[serde(default)]
+    pub reserved_names: Vec<String>,
+    // Words usernames, display names and team names must not contain, e.g. profanity
+    #[serde(default)]
+    pub blocked_words: Vec<String>,
+    // Whether names mixing Latin letters with lookalikes from other scripts are rejected
+    #[serde(default)]
+    pub block_confusables: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReleaseWave {
+    pub release_time: chrono::DateTime<chrono::Utc>,
+    // IDs of the challenges released in this wave
+    pub challenges: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EventConfig {
     pub event_name: String,
-    pub front_page_md: String,
-    pub rules_md: String,
+    pub front_page_md: LocalizedMarkdown,
+    pub rules_md: LocalizedMarkdown,
     pub start_time: chrono::DateTime<chrono::Utc>,
     pub end_time: chrono::DateTime<chrono::Utc>,
+    // Whether the platform becomes a read-only archive once the event ended
+    #[serde(default)]
+    pub archive_after_end: bool,
     pub use_teams: bool,
     pub registration_start_time: Option<chrono::DateTime<chrono::Utc>>,
     pub registration_end_time: Option<chrono::DateTime<chrono::Utc>>,
+    // Whether users need an invite code created by an admin to register
+    #[serde(default)]
+    pub invite_only: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_team_size: Option<u32>,
     pub scoreboard_freeze_time: Option<chrono::DateTime<chrono::Utc>>,
+    // When the frozen scoreboard is revealed again, usually some time after end_time
+    pub scoreboard_reveal_time: Option<chrono::DateTime<chrono::Utc>>,
+    // Seconds a team (or user) has to wait between two flag submissions for the same challenge
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub submission_cooldown: Option<u32>,
     // JS code that calls setPointsFn((challengeMetadata, currentSolves, solveIndex) => points);
     #[serde(skip_serializing_if = "Option::is_none")]
     pub points_fn: Option<String>,
+    // Built-in scoring formula, used unless there is a points_fn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scoring: Option<Scoring>,
     pub categories: HashMap<String, CtfCategory>,
     pub difficulties: HashMap<String, CtfDifficulty>,
+    // Brackets teams choose from when they are created, e.g. student, open or onsite
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub divisions: HashMap<String, CtfDivision>,
+    // Additional rules for usernames, display names and team names
+    #[serde(default)]
+    pub name_policy: NamePolicy,
+    // Challenges released together, by wave name. A challenge in a wave is released at the time
+    // of the wave, or its own release_time if that is later.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub release_waves: BTreeMap<String, ReleaseWave>,
 }
 
 impl EventConfig {
@@ -56,6 +115,23 @@ impl EventConfig {
         Ok(config)
     }
 
+    /// The wave a challenge is released in, the last one if it is in several
+    pub fn release_wave(&self, challenge_id: &str) -> Option<(&str, &ReleaseWave)> {
+        self.release_waves
+            .iter()
+            .filter(|(_, wave)| wave.challenges.iter().any(|id| id == challenge_id))
+            .max_by_key(|(_, wave)| wave.release_time)
+            .map(|(name, wave)| (name.as_str(), wave))
+    }
+
+    /// Delays the release of a challenge to its release wave
+    pub fn apply_release_wave(&self, challenge_id: &str, metadata: &mut CtfChallengeMetadata) {
+        if let Some((_, wave)) = self.release_wave(challenge_id) {
+            let wave_time = wave.release_time.timestamp().max(0) as u64;
+            metadata.release_time = Some(metadata.release_time.unwrap_or(0).max(wave_time));
+        }
+    }
+
     pub async fn calculate_points(
         &self,
         challenge_metadata: &CtfChallengeMetadata,
@@ -64,57 +140,76 @@ impl EventConfig {
         total_competitors: u32,
     ) -> Result<u32, Box<dyn std::error::Error>> {
         if let Some(points_fn) = &self.points_fn {
-            // Use boa to execute the JS function
-            let mut engine = create_boa_context();
-            let flag_fn: Rc<Mutex<Option<JsFunction>>> = Rc::new(Mutex::new(None));
-            let flag_fn_clone = flag_fn.clone();
-            engine
-                .register_global_builtin_callable(js_string!("setPointsFn"), 1, unsafe {
-                    NativeFunction::from_closure(move |_this, args, _ctx| {
-                        let fn_obj = args.first().and_then(|v| v.as_object());
-                        if let Some(obj) = fn_obj {
-                            let Some(func) = JsFunction::from_object(obj) else {
-                                return Err(JsError::from(JsNativeError::typ().with_message(
-                                    "setPointsFn expects a function as its first argument",
-                                )));
-                            };
-                            let mut lock = flag_fn_clone.lock().unwrap();
-                            *lock = Some(func);
-                        } else {
-                            return Err(JsError::from(JsNativeError::typ().with_message(
-                                "setPointsFn expects a function as its first argument",
-                            )));
-                        }
-                        Ok(JsValue::undefined())
-                    })
-                })
-                .expect("Failed to register setPointsFn");
-            engine.eval(Source::from_bytes(&points_fn))?;
-            let points_function = {
-                let mut lock = flag_fn.lock().unwrap();
-                lock.take().ok_or("Points function not set")?
-            };
-            let challenge_metadata_js = challenge_metadata.try_into_js(&mut engine)?;
-            let total_solves_js = js_value!(total_solves);
-            let solve_index_js = js_value!(solve_index);
-            let total_competitors_js = js_value!(total_competitors);
-            let result = points_function.call(
-                &JsValue::undefined(),
-                &[
-                    challenge_metadata_js,
-                    total_solves_js,
-                    solve_index_js,
-                    total_competitors_js,
-                ],
-                &mut engine,
-            )?;
-            let points = result
-                .as_i32()
-                .ok_or("Points function did not return a number")?;
-            Ok(points as u32)
+            Ok(run_script(ScriptJob::Points {
+                points_fn: points_fn.clone(),
+                challenge_metadata: Box::new(challenge_metadata.clone()),
+                total_solves,
+                solve_index,
+                total_competitors,
+            })
+            .await?)
+        } else if let Some(scoring) = &self.scoring {
+            Ok(scoring.points(total_solves, solve_index))
         } else {
             // Default points calculation
             Ok(100)
         }
     }
 }
+
+/// Runs points_fn for a challenge, in a script worker
+pub fn run_points_fn(
+    engine: &mut boa_engine::Context,
+    points_fn: &str,
+    challenge_metadata: &CtfChallengeMetadata,
+    total_solves: u32,
+    solve_index: u32,
+    total_competitors: u32,
+) -> Result<u32, Box<dyn std::error::Error>> {
+    let flag_fn: Rc<Mutex<Option<JsFunction>>> = Rc::new(Mutex::new(None));
+    let flag_fn_clone = flag_fn.clone();
+    engine
+        .register_global_builtin_callable(js_string!("setPointsFn"), 1, unsafe {
+            NativeFunction::from_closure(move |_this, args, _ctx| {
+                let fn_obj = args.first().and_then(|v| v.as_object());
+                if let Some(obj) = fn_obj {
+                    let Some(func) = JsFunction::from_object(obj) else {
+                        return Err(JsError::from(JsNativeError::typ().with_message(
+                            "setPointsFn expects a function as its first argument",
+                        )));
+                    };
+                    let mut lock = flag_fn_clone.lock().unwrap();
+                    *lock = Some(func);
+                } else {
+                    return Err(JsError::from(JsNativeError::typ().with_message(
+                        "setPointsFn expects a function as its first argument",
+                    )));
+                }
+                Ok(JsValue::undefined())
+            })
+        })
+        .expect("Failed to register setPointsFn");
+    engine.eval(Source::from_bytes(points_fn))?;
+    let points_function = {
+        let mut lock = flag_fn.lock().unwrap();
+        lock.take().ok_or("Points function not set")?
+    };
+    let challenge_metadata_js = challenge_metadata.try_into_js(engine)?;
+    let total_solves_js = js_value!(total_solves);
+    let solve_index_js = js_value!(solve_index);
+    let total_competitors_js = js_value!(total_competitors);
+    let result = points_function.call(
+        &JsValue::undefined(),
+        &[
+            challenge_metadata_js,
+            total_solves_js,
+            solve_index_js,
+            total_competitors_js,
+        ],
+        engine,
+    )?;
+    let points = result
+        .as_i32()
+        .ok_or("Points function did not return a number")?;
+    Ok(points as u32)
+}
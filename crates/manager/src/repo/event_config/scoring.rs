@@ -0,0 +1,140 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Scoring formulas that can be configured in event.yml instead of writing a points_fn, e.g.
+//!
+//! ```yaml
+//! scoring:
+//!   formula: logarithmic
+//!   maximum: 500
+//!   minimum: 100
+//!   solves_to_minimum: 50
+//!   first_blood_bonus: [30, 20, 10]
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "formula", rename_all = "snake_case")]
+pub enum ScoringFormula {
+    /// The same points no matter how many solved the challenge
+    Static { points: u32 },
+    /// Loses `points_per_solve` with every solve after the first, down to `minimum`
+    LinearDecay {
+        maximum: u32,
+        minimum: u32,
+        points_per_solve: u32,
+    },
+    /// Decreases with the logarithm of the solves, from `maximum` for the first solve to
+    /// `minimum` at `solves_to_minimum` solves
+    Logarithmic {
+        maximum: u32,
+        minimum: u32,
+        solves_to_minimum: u32,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Scoring {
+    #[serde(flatten)]
+    pub formula: ScoringFormula,
+    /// Extra points for the first solvers of a challenge, starting with the first blood
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub first_blood_bonus: Vec<u32>,
+}
+
+impl ScoringFormula {
+    /// The points of a challenge solved `total_solves` times, or that nobody solved yet
+    pub fn points(&self, total_solves: u32) -> u32 {
+        let solves = total_solves.max(1);
+        match *self {
+            ScoringFormula::Static { points } => points,
+            ScoringFormula::LinearDecay {
+                maximum,
+                minimum,
+                points_per_solve,
+            } => maximum
+                .saturating_sub(points_per_solve.saturating_mul(solves - 1))
+                .max(minimum),
+            ScoringFormula::Logarithmic {
+                maximum,
+                minimum,
+                solves_to_minimum,
+            } => {
+                if solves >= solves_to_minimum {
+                    return minimum;
+                }
+                let progress = (solves as f64).ln() / (solves_to_minimum as f64).ln();
+                let points = maximum as f64 - (maximum as f64 - minimum as f64) * progress;
+                points.round() as u32
+            }
+        }
+    }
+
+    /// The range of points the formula awards, to validate the config
+    pub fn bounds(&self) -> (u32, u32) {
+        match *self {
+            ScoringFormula::Static { points } => (points, points),
+            ScoringFormula::LinearDecay {
+                maximum, minimum, ..
+            }
+            | ScoringFormula::Logarithmic {
+                maximum, minimum, ..
+            } => (minimum, maximum),
+        }
+    }
+}
+
+impl Scoring {
+    /// The points of a solve, with the bonus if it is among the first ones (`solve_index`
+    /// starting at 1, 0 if the challenge wasn't solved)
+    pub fn points(&self, total_solves: u32, solve_index: u32) -> u32 {
+        let bonus = solve_index
+            .checked_sub(1)
+            .and_then(|index| self.first_blood_bonus.get(index as usize))
+            .copied()
+            .unwrap_or(0);
+        self.formula.points(total_solves).saturating_add(bonus)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formulas() {
+        let linear = ScoringFormula::LinearDecay {
+            maximum: 500,
+            minimum: 100,
+            points_per_solve: 25,
+        };
+        assert_eq!(linear.points(0), 500);
+        assert_eq!(linear.points(1), 500);
+        assert_eq!(linear.points(5), 400);
+        assert_eq!(linear.points(1000), 100);
+
+        let logarithmic = ScoringFormula::Logarithmic {
+            maximum: 500,
+            minimum: 100,
+            solves_to_minimum: 100,
+        };
+        assert_eq!(logarithmic.points(1), 500);
+        assert_eq!(logarithmic.points(10), 300);
+        assert_eq!(logarithmic.points(100), 100);
+        assert_eq!(logarithmic.points(200), 100);
+    }
+
+    #[test]
+    fn test_scoring_from_yaml() {
+        let scoring: Scoring =
+            serde_yaml::from_str("formula: static\npoints: 200\nfirst_blood_bonus: [30, 10]\n")
+                .unwrap();
+        assert_eq!(scoring.formula, ScoringFormula::Static { points: 200 });
+        assert_eq!(scoring.points(3, 0), 200);
+        assert_eq!(scoring.points(3, 1), 230);
+        assert_eq!(scoring.points(3, 2), 210);
+        assert_eq!(scoring.points(3, 3), 200);
+    }
+}
@@ -0,0 +1,455 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Lint of event.yml for CI, checking what parsing it doesn't: times in the wrong order, keys
+//! that are ignored, scoring scripts that fail, and categories, difficulties and challenges that
+//! are referenced, but don't exist.
+//!
+//! Diagnostics point at the key they are about. Besides the ValidateEventConfig RPC, they are
+//! printed by `plfanzen-manager validate` when it validates the whole repository.
+
+use std::{fmt, path::Path};
+
+use crate::repo::{
+    EventConfig,
+    challenges::{
+        loader::{Challenge, load_challenge_from_dir},
+        validate::{Severity, VALIDATION_ACTOR},
+    },
+};
+
+const EVENT_CONFIG_FILE: &str = "event.yml";
+
+/// Top-level keys of [`EventConfig`], as serde ignores misspelled ones
+const KNOWN_KEYS: &[&str] = &[
+    "event_name",
+    "front_page_md",
+    "rules_md",
+    "start_time",
+    "end_time",
+    "archive_after_end",
+    "use_teams",
+    "registration_start_time",
+    "registration_end_time",
+    "invite_only",
+    "max_team_size",
+    "scoreboard_freeze_time",
+    "scoreboard_reveal_time",
+    "submission_cooldown",
+    "points_fn",
+    "scoring",
+    "categories",
+    "difficulties",
+    "divisions",
+    "name_policy",
+    "release_waves",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Path of the file relative to the repository
+    pub file: String,
+    /// Position of the key the diagnostic is about, starting at 1, if it was found
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+/// Formatted like compiler messages, which CI systems turn into annotations
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+            if let Some(column) = self.column {
+                write!(f, ":{}", column)?;
+            }
+        }
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, ": {}: {}", severity, self.message)
+    }
+}
+
+/// Line and column of a key in a YAML document, following nested block mappings
+fn locate_key(text: &str, key_path: &[&str]) -> Option<(usize, usize)> {
+    let mut depth = 0;
+    // Indentation of the last key found, its children are indented further
+    let mut parent_indent: Option<usize> = None;
+    // Indentation of the keys in the current block, set by its first line
+    let mut child_indent: Option<usize> = Some(0);
+    for (index, line) in text.lines().enumerate() {
+        let content = line.trim_start();
+        if content.is_empty() || content.starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - content.len();
+        if parent_indent.is_some_and(|parent_indent| indent <= parent_indent) {
+            return None;
+        }
+        if *child_indent.get_or_insert(indent) != indent {
+            continue;
+        }
+        let Some((key, _)) = content.split_once(':') else {
+            continue;
+        };
+        if key.trim().trim_matches(['"', '\'']) == *key_path.get(depth)? {
+            depth += 1;
+            if depth == key_path.len() {
+                return Some((index + 1, indent + 1));
+            }
+            parent_indent = Some(indent);
+            child_indent = None;
+        }
+    }
+    None
+}
+
+/// A file of the repository, to point diagnostics at its keys
+struct Source {
+    file: String,
+    text: String,
+}
+
+#[derive(Default)]
+struct Diagnostics(Vec<Diagnostic>);
+
+impl Diagnostics {
+    fn add(
+        &mut self,
+        severity: Severity,
+        file: &str,
+        position: Option<(usize, usize)>,
+        message: String,
+    ) {
+        self.0.push(Diagnostic {
+            severity,
+            file: file.to_string(),
+            line: position.map(|(line, _)| line),
+            column: position.map(|(_, column)| column),
+            message,
+        });
+    }
+
+    fn error(&mut self, source: &Source, key_path: &[&str], message: impl Into<String>) {
+        let position = locate_key(&source.text, key_path);
+        self.add(Severity::Error, &source.file, position, message.into());
+    }
+
+    fn warning(&mut self, source: &Source, key_path: &[&str], message: impl Into<String>) {
+        let position = locate_key(&source.text, key_path);
+        self.add(Severity::Warning, &source.file, position, message.into());
+    }
+}
+
+struct LoadedChallenge {
+    id: String,
+    source: Source,
+    challenge: Challenge,
+}
+
+fn check_keys(event: &Source, diagnostics: &mut Diagnostics) {
+    let Ok(serde_yaml::Value::Mapping(mapping)) = serde_yaml::from_str(&event.text) else {
+        return;
+    };
+    for key in mapping.keys().filter_map(|key| key.as_str()) {
+        if !KNOWN_KEYS.contains(&key) {
+            diagnostics.warning(event, &[key], format!("Unknown key {} is ignored", key));
+        }
+    }
+}
+
+fn check_times(event: &Source, config: &EventConfig, diagnostics: &mut Diagnostics) {
+    if config.end_time <= config.start_time {
+        diagnostics.error(event, &["end_time"], "end_time is not after start_time");
+    }
+    if let (Some(start), Some(end)) = (config.registration_start_time, config.registration_end_time)
+        && end <= start
+    {
+        diagnostics.error(
+            event,
+            &["registration_end_time"],
+            "registration_end_time is not after registration_start_time",
+        );
+    }
+    if let Some(freeze) = config.scoreboard_freeze_time {
+        if freeze < config.start_time || freeze > config.end_time {
+            diagnostics.warning(
+                event,
+                &["scoreboard_freeze_time"],
+                "scoreboard_freeze_time is not during the event",
+            );
+        }
+        if let Some(reveal) = config.scoreboard_reveal_time
+            && reveal <= freeze
+        {
+            diagnostics.error(
+                event,
+                &["scoreboard_reveal_time"],
+                "scoreboard_reveal_time is not after scoreboard_freeze_time",
+            );
+        }
+    }
+    for (name, wave) in &config.release_waves {
+        if wave.release_time < config.start_time || wave.release_time >= config.end_time {
+            diagnostics.warning(
+                event,
+                &["release_waves", name, "release_time"],
+                format!("Release wave {} is not released during the event", name),
+            );
+        }
+    }
+}
+
+fn check_scoring(event: &Source, config: &EventConfig, diagnostics: &mut Diagnostics) {
+    let Some(scoring) = &config.scoring else {
+        return;
+    };
+    if config.points_fn.is_some() {
+        diagnostics.warning(
+            event,
+            &["scoring"],
+            "scoring is ignored, as points_fn is used instead",
+        );
+    }
+    let (minimum, maximum) = scoring.formula.bounds();
+    if minimum > maximum {
+        diagnostics.error(
+            event,
+            &["scoring", "minimum"],
+            "The minimum of the scoring formula is above its maximum",
+        );
+    }
+}
+
+/// Loads the challenges of the repository, pointing out the ones that can't be loaded
+async fn load_challenges(repo_dir: &Path, diagnostics: &mut Diagnostics) -> Vec<LoadedChallenge> {
+    let Ok(entries) = std::fs::read_dir(repo_dir.join("challs")) else {
+        return Vec::new();
+    };
+    let mut ids: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join("docker-compose.yml").is_file())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect();
+    ids.sort();
+    let mut challenges = Vec::new();
+    for id in ids {
+        let chall_dir = repo_dir.join("challs").join(&id);
+        let source = Source {
+            file: format!("challs/{}/docker-compose.yml", id),
+            text: std::fs::read_to_string(chall_dir.join("docker-compose.yml")).unwrap_or_default(),
+        };
+        match load_challenge_from_dir(&chall_dir, VALIDATION_ACTOR, false).await {
+            Ok(challenge) => challenges.push(LoadedChallenge {
+                id,
+                source,
+                challenge,
+            }),
+            Err(e) => diagnostics.warning(
+                &source,
+                &["x-ctf-metadata"],
+                format!(
+                    "Challenge {} can't be loaded, so it isn't checked against the event config: {}",
+                    id, e
+                ),
+            ),
+        }
+    }
+    challenges
+}
+
+/// Checks that what challenges and release waves reference exists
+fn check_references(
+    repo_dir: &Path,
+    event: &Source,
+    config: &EventConfig,
+    challenges: &[LoadedChallenge],
+    diagnostics: &mut Diagnostics,
+) {
+    for loaded in challenges {
+        let metadata = &loaded.challenge.metadata;
+        for category in &metadata.categories {
+            if !config.categories.contains_key(category) {
+                diagnostics.error(
+                    &loaded.source,
+                    &["x-ctf-metadata", "categories"],
+                    format!(
+                        "Category {} of challenge {} is not in {}",
+                        category, loaded.id, EVENT_CONFIG_FILE
+                    ),
+                );
+            }
+        }
+        if !config.difficulties.contains_key(&metadata.difficulty) {
+            diagnostics.error(
+                &loaded.source,
+                &["x-ctf-metadata", "difficulty"],
+                format!(
+                    "Difficulty {} of challenge {} is not in {}",
+                    metadata.difficulty, loaded.id, EVENT_CONFIG_FILE
+                ),
+            );
+        }
+    }
+    for (name, wave) in &config.release_waves {
+        for challenge_id in &wave.challenges {
+            let chall_dir = repo_dir.join("challs").join(challenge_id);
+            if !chall_dir.join("docker-compose.yml").is_file() {
+                diagnostics.error(
+                    event,
+                    &["release_waves", name, "challenges"],
+                    format!(
+                        "Challenge {} of release wave {} does not exist",
+                        challenge_id, name
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Runs points_fn for every challenge with the extremes of most scoring functions, the first
+/// solve of a single competitor and the last one of many
+async fn check_points_fn(
+    event: &Source,
+    config: &EventConfig,
+    challenges: &[LoadedChallenge],
+    diagnostics: &mut Diagnostics,
+) {
+    if config.points_fn.is_none() {
+        return;
+    }
+    for loaded in challenges {
+        for (total_solves, nth_solve, competitors) in [(1, 1, 1), (100, 100, 100)] {
+            let message = match config
+                .calculate_points(
+                    &loaded.challenge.metadata,
+                    total_solves,
+                    nth_solve,
+                    competitors,
+                )
+                .await
+            {
+                Ok(_) => continue,
+                Err(e) => format!("points_fn fails for challenge {}: {}", loaded.id, e),
+            };
+            // The script is the same for every challenge, one failure is enough
+            diagnostics.error(event, &["points_fn"], message);
+            return;
+        }
+    }
+}
+
+/// Runs every check on the event config of the repository
+pub async fn validate_event_config(repo_dir: &Path) -> Vec<Diagnostic> {
+    let mut diagnostics = Diagnostics::default();
+    let text = match std::fs::read_to_string(repo_dir.join(EVENT_CONFIG_FILE)) {
+        Ok(text) => text,
+        Err(e) => {
+            diagnostics.add(
+                Severity::Error,
+                EVENT_CONFIG_FILE,
+                None,
+                format!("Failed to read the event config: {}", e),
+            );
+            return diagnostics.0;
+        }
+    };
+    let event = Source {
+        file: EVENT_CONFIG_FILE.to_string(),
+        text,
+    };
+    // Missing fields and values of the wrong type
+    let config: EventConfig = match serde_yaml::from_str(&event.text) {
+        Ok(config) => config,
+        Err(e) => {
+            let position = e
+                .location()
+                .map(|location| (location.line(), location.column()));
+            diagnostics.add(Severity::Error, &event.file, position, e.to_string());
+            return diagnostics.0;
+        }
+    };
+    check_keys(&event, &mut diagnostics);
+    check_times(&event, &config, &mut diagnostics);
+    check_scoring(&event, &config, &mut diagnostics);
+    let challenges = load_challenges(repo_dir, &mut diagnostics).await;
+    check_references(repo_dir, &event, &config, &challenges, &mut diagnostics);
+    check_points_fn(&event, &config, &challenges, &mut diagnostics).await;
+    diagnostics.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENT_YML: &str = "\
+event_name: Test CTF
+front_page_md: Welcome
+rules_md: Be nice
+# Ends before it starts
+start_time: 2026-05-02T00:00:00Z
+end_time: 2026-05-01T00:00:00Z
+use_teams: true
+strat_time: 2026-05-01T00:00:00Z
+categories:
+  web:
+    name: Web
+difficulties:
+  easy:
+    name: Easy
+release_waves:
+  first:
+    release_time: 2026-05-02T12:00:00Z
+    challenges:
+      - missing
+";
+
+    #[test]
+    fn test_locate_key() {
+        assert_eq!(locate_key(EVENT_YML, &["end_time"]), Some((6, 1)));
+        assert_eq!(
+            locate_key(EVENT_YML, &["categories", "web", "name"]),
+            Some((11, 5))
+        );
+        assert_eq!(
+            locate_key(EVENT_YML, &["release_waves", "first", "challenges"]),
+            Some((18, 5))
+        );
+        // Only in another block, or nested deeper
+        assert_eq!(locate_key(EVENT_YML, &["categories", "easy"]), None);
+        assert_eq!(locate_key(EVENT_YML, &["categories", "name"]), None);
+        assert_eq!(locate_key(EVENT_YML, &["name"]), None);
+    }
+
+    #[tokio::test]
+    async fn test_validate_event_config() {
+        let repo = tempfile::tempdir().unwrap();
+        std::fs::write(repo.path().join(EVENT_CONFIG_FILE), EVENT_YML).unwrap();
+        let diagnostics: Vec<String> = validate_event_config(repo.path())
+            .await
+            .iter()
+            .map(Diagnostic::to_string)
+            .collect();
+        assert_eq!(
+            diagnostics,
+            [
+                "event.yml:8:1: warning: Unknown key strat_time is ignored",
+                "event.yml:6:1: error: end_time is not after start_time",
+                "event.yml:17:5: warning: Release wave first is not released during the event",
+                "event.yml:18:5: error: Challenge missing of release wave first does not exist",
+            ]
+        );
+
+        std::fs::write(repo.path().join(EVENT_CONFIG_FILE), "event_name: [1]\n").unwrap();
+        let diagnostics = validate_event_config(repo.path()).await;
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].line, Some(1));
+    }
+}
@@ -5,11 +5,13 @@
 use std::num::NonZeroU32;
 use std::path::{Path, PathBuf};
 
-use gix::bstr::BStr;
-use gix::prepare_clone;
+use gix::bstr::{BStr, ByteSlice};
+use gix::sec::trust::DefaultForLevel;
 use tempfile::TempDir;
 use thiserror::Error;
 
+mod lfs;
+
 #[derive(Error, Debug)]
 pub enum GitError {
     #[error("Failed to join Tokio task: {0}")]
@@ -23,33 +25,425 @@ pub enum GitError {
     #[error("Git clone failed: {0}")]
     GitClone(#[from] gix::clone::Error),
     #[error("Git checkout failed: {0}")]
-    GitCheckout(#[from] gix::clone::checkout::main_worktree::Error),
+    GitCheckout(String),
     #[error("Git URL error: {0}")]
     GitUrl(#[from] gix::url::parse::Error),
     #[error("Target directory already exists and is not empty: {0}")]
     DirExists(String),
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("Git submodule error: {0}")]
+    Submodule(String),
+    #[error("Revision {0} does not exist")]
+    RevisionNotFound(String),
+    #[error("Git LFS error: {0}")]
+    Lfs(String),
     #[error("Other Git error: {0}")]
     Other(String),
 }
 
-pub async fn clone(repo_url: gix::Url, branch: &str, target: PathBuf) -> Result<(), GitError> {
-    tracing::info!("Cloning {repo_url:?} into {target:?}...");
-    let rspec = format!("refs/heads/{}", branch);
-    tokio::task::spawn_blocking(move || {
-        let prepare_clone = prepare_clone(repo_url, target)?;
-        let (mut prepare_checkout, _) = prepare_clone
-            .with_ref_name(Some(&rspec))?
-            .with_shallow(gix::remote::fetch::Shallow::DepthAtRemote(
+/// Credentials for private challenge repositories.
+///
+/// HTTPS URLs use GIT_TOKEN (or the contents of GIT_TOKEN_FILE) as password and GIT_USERNAME
+/// (default "git") as user name, which works for access tokens of GitHub, GitLab and Gitea. SSH
+/// URLs use the deploy key in GIT_SSH_KEY_FILE, which ssh only accepts if nobody else can read it.
+/// The host key is checked against GIT_SSH_KNOWN_HOSTS_FILE if set, and trusted on first use
+/// otherwise.
+///
+/// The token is only sent to the host of GIT_URL, not to the hosts of submodules or LFS servers
+/// elsewhere.
+#[derive(Clone, Default)]
+pub struct GitAuth {
+    /// User name and token
+    https: Option<(String, String)>,
+    /// Host of the challenge repository, the only one the token is sent to
+    host: Option<String>,
+    ssh_command: Option<String>,
+}
+
+fn non_empty_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// Quotes an argument for the shell the ssh command is run in
+fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+fn ssh_command(key_file: &Path, known_hosts_file: Option<&Path>) -> String {
+    let mut command = format!(
+        "ssh -i {} -o IdentitiesOnly=yes -o BatchMode=yes",
+        shell_quote(&key_file.to_string_lossy())
+    );
+    match known_hosts_file {
+        Some(known_hosts_file) => command.push_str(&format!(
+            " -o UserKnownHostsFile={} -o StrictHostKeyChecking=yes",
+            shell_quote(&known_hosts_file.to_string_lossy())
+        )),
+        None => command.push_str(" -o StrictHostKeyChecking=accept-new"),
+    }
+    command
+}
+
+impl GitAuth {
+    pub fn from_env() -> Result<Self, GitError> {
+        let token = match non_empty_var("GIT_TOKEN_FILE") {
+            Some(token_file) => Some(std::fs::read_to_string(token_file)?.trim().to_string()),
+            None => non_empty_var("GIT_TOKEN"),
+        };
+        let username = non_empty_var("GIT_USERNAME").unwrap_or_else(|| "git".to_string());
+        let ssh_command = non_empty_var("GIT_SSH_KEY_FILE").map(|key_file| {
+            ssh_command(
+                Path::new(&key_file),
+                non_empty_var("GIT_SSH_KNOWN_HOSTS_FILE")
+                    .as_deref()
+                    .map(Path::new),
+            )
+        });
+        let host = non_empty_var("GIT_URL")
+            .and_then(|url| gix::Url::from_bytes(BStr::new(&url)).ok())
+            .and_then(|url| url.host().map(str::to_string));
+        Ok(GitAuth {
+            https: token
+                .filter(|token| !token.is_empty())
+                .map(|token| (username, token)),
+            host,
+            ssh_command,
+        })
+    }
+
+    /// User name and token for a URL, if it is on the host of the challenge repository
+    fn https_for(&self, url: &gix::Url) -> Option<(String, String)> {
+        let host = self.host.as_deref()?;
+        url.host()
+            .is_some_and(|url_host| url_host.eq_ignore_ascii_case(host))
+            .then(|| self.https.clone())
+            .flatten()
+    }
+}
+
+/// Paths of the repository to check out, so assets next to the challenges of a monorepo don't
+/// take up disk space in the worktree. Only the worktree is reduced, fetching still downloads
+/// every file of the synced commit.
+///
+/// GIT_SPARSE_PATHS is a comma-separated list of glob patterns like `challs/**,event.yml`, in
+/// which `*` doesn't match `/`, but `**` does. A pattern matching a directory includes all of it.
+/// Submodules and Git LFS files are only downloaded if they are included.
+///
+/// This is not a partial clone: gix can't fetch left out objects on demand, so the objects of
+/// excluded paths stay in the pack of the clone, only their checkout is skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseCheckout {
+    patterns: Vec<String>,
+}
+
+impl SparseCheckout {
+    /// None if GIT_SPARSE_PATHS is unset, to check out everything. Setting it doesn't reduce how
+    /// much is downloaded, only what is checked out.
+    pub fn from_env() -> Option<Self> {
+        let patterns: Vec<String> = std::env::var("GIT_SPARSE_PATHS")
+            .ok()?
+            .split(',')
+            .map(|pattern| pattern.trim().trim_matches('/').to_string())
+            .filter(|pattern| !pattern.is_empty())
+            .collect();
+        (!patterns.is_empty()).then_some(SparseCheckout { patterns })
+    }
+
+    /// Whether the path or a directory containing it matches a pattern
+    fn includes(&self, path: &BStr) -> bool {
+        path.find_iter("/")
+            .map(|end| &path[..end])
+            .chain([path])
+            .any(|prefix| {
+                self.patterns.iter().any(|pattern| {
+                    gix::glob::wildmatch(
+                        pattern.as_bytes().as_bstr(),
+                        prefix,
+                        gix::glob::wildmatch::Mode::NO_MATCH_SLASH_LITERAL,
+                    )
+                })
+            })
+    }
+}
+
+/// Checks out HEAD of a fresh clone like PrepareCheckout::main_worktree(), which only supports
+/// branches. Paths excluded from the sparse checkout are marked to be skipped in the index, so
+/// they aren't seen as deleted.
+fn checkout_head(repo: &gix::Repository, sparse: Option<&SparseCheckout>) -> Result<(), GitError> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| GitError::GitCheckout("The repository has no worktree".to_string()))?;
+    let tree = repo
+        .head_tree_id()
+        .map_err(|e| GitError::GitCheckout(e.to_string()))?;
+    let mut index = repo
+        .index_from_tree(&tree)
+        .map_err(|e| GitError::GitCheckout(e.to_string()))?;
+    for (entry, path) in index.entries_mut_with_paths() {
+        if sparse.is_some_and(|sparse| !sparse.includes(path)) {
+            // Skipping the worktree is an extended flag, which needs index version 3
+            entry.flags |=
+                gix::index::entry::Flags::SKIP_WORKTREE | gix::index::entry::Flags::EXTENDED;
+        }
+    }
+    let mut opts = repo
+        .checkout_options(gix::worktree::stack::state::attributes::Source::IdMapping)
+        .map_err(|e| GitError::GitCheckout(e.to_string()))?;
+    opts.destination_is_initially_empty = true;
+    gix::worktree::state::checkout(
+        &mut index,
+        workdir,
+        repo.objects.clone().into_arc()?,
+        &gix::progress::Discard,
+        &gix::progress::Discard,
+        &gix::interrupt::IS_INTERRUPTED,
+        opts,
+    )
+    .map_err(|e| GitError::GitCheckout(e.to_string()))?;
+    index
+        .write(Default::default())
+        .map_err(|e| GitError::GitCheckout(e.to_string()))?;
+    Ok(())
+}
+
+/// Like gix::prepare_clone(), but with the credentials of the repository
+fn prepare_clone(
+    repo_url: gix::Url,
+    target: &Path,
+    auth: &GitAuth,
+) -> Result<gix::clone::PrepareFetch, GitError> {
+    let mut open_opts = gix::open::Options::default_for_level(gix::sec::Trust::Full);
+    open_opts.permissions.config.git_binary = true;
+    if let Some(ssh_command) = &auth.ssh_command {
+        open_opts = open_opts.config_overrides([format!("core.sshCommand={}", ssh_command)]);
+    }
+    let mut prepare_clone = gix::clone::PrepareFetch::new(
+        repo_url,
+        target,
+        gix::create::Kind::WithWorktree,
+        gix::create::Options::default(),
+        open_opts,
+    )?;
+    if let Some((username, password)) = auth.https_for(&repo_url) {
+        prepare_clone = prepare_clone.configure_connection(move |connection| {
+            let (username, password) = (username.clone(), password.clone());
+            connection.set_credentials(move |action| match action {
+                gix::credentials::helper::Action::Get(context) => {
+                    Ok(Some(gix::credentials::protocol::Outcome {
+                        identity: gix::sec::identity::Account {
+                            username: username.clone(),
+                            password: password.clone(),
+                            oauth_refresh_token: None,
+                        },
+                        next: context.into(),
+                    }))
+                }
+                // The credentials aren't stored anywhere, so there is nothing to approve or reject
+                gix::credentials::helper::Action::Store(_)
+                | gix::credentials::helper::Action::Erase(_) => Ok(None),
+            });
+            Ok(())
+        });
+    }
+    Ok(prepare_clone)
+}
+
+/// Resolves the URL of a submodule, which may be relative to the URL of its superproject
+fn submodule_url(superproject_url: &gix::Url, url: gix::Url) -> gix::Url {
+    let Some(mut relative) = (url.scheme == gix::url::Scheme::File)
+        .then(|| url.path.to_str().ok())
+        .flatten()
+        .filter(|path| path.starts_with("./") || path.starts_with("../"))
+    else {
+        return url;
+    };
+    let mut path = superproject_url
+        .path
+        .to_str_lossy()
+        .trim_end_matches('/')
+        .to_string();
+    loop {
+        if let Some(rest) = relative.strip_prefix("./") {
+            relative = rest;
+        } else if let Some(rest) = relative.strip_prefix("../") {
+            path.truncate(path.rfind('/').unwrap_or(0));
+            relative = rest;
+        } else {
+            break;
+        }
+    }
+    let mut resolved = superproject_url.clone();
+    // Paths of scp-like URLs are relative to the home directory
+    resolved.path = if path.is_empty() && !superproject_url.path.starts_with(b"/") {
+        relative.into()
+    } else {
+        format!("{}/{}", path, relative).into()
+    };
+    resolved
+}
+
+/// Clones a repository and checks out `revision` (a commit or tag), or else the tip of `branch`
+/// (or of the default branch)
+fn clone_revision(
+    repo_url: &gix::Url,
+    branch: Option<&str>,
+    revision: Option<&str>,
+    target: &Path,
+    auth: &GitAuth,
+    sparse: Option<&SparseCheckout>,
+) -> Result<gix::Repository, GitError> {
+    let rspec = branch.map(|branch| format!("refs/heads/{}", branch));
+    // The revision is usually the tip of the branch, otherwise the whole history and the tags are
+    // needed
+    for is_shallow in [true, false] {
+        let mut prepare_clone =
+            prepare_clone(repo_url.clone(), target, auth)?.with_ref_name(rspec.as_deref())?;
+        prepare_clone = if is_shallow {
+            prepare_clone.with_shallow(gix::remote::fetch::Shallow::DepthAtRemote(
                 NonZeroU32::new(1).unwrap(),
             ))
+        } else {
+            prepare_clone.configure_remote(|remote| {
+                Ok(remote
+                    .with_refspecs(["+refs/tags/*:refs/tags/*"], gix::remote::Direction::Fetch)?)
+            })
+        };
+        let (prepare_checkout, _) = prepare_clone
             .fetch_then_checkout(gix::progress::Discard, &gix::interrupt::IS_INTERRUPTED)?;
-        let _ = prepare_checkout
-            .main_worktree(gix::progress::Discard, &gix::interrupt::IS_INTERRUPTED)?;
-        Ok(())
+        if let Some(revision) = revision {
+            let commit = prepare_checkout
+                .repo()
+                .rev_parse_single(revision)
+                .ok()
+                .and_then(|id| id.object().ok()?.peel_to_commit().ok())
+                .map(|commit| commit.id);
+            // Dropping the checkout removes the clone again
+            let Some(commit) = commit else {
+                continue;
+            };
+            prepare_checkout
+                .repo()
+                .reference(
+                    "HEAD",
+                    commit,
+                    gix::refs::transaction::PreviousValue::Any,
+                    format!("checkout: {}", revision),
+                )
+                .map_err(|e| GitError::GitCheckout(e.to_string()))?;
+        }
+        // Unlike the prepared checkout, the persisted repository isn't removed on errors
+        let repo = prepare_checkout.persist();
+        if let Err(e) = checkout_head(&repo, sparse) {
+            std::fs::remove_dir_all(target).ok();
+            return Err(e);
+        }
+        return Ok(repo);
+    }
+    Err(GitError::RevisionNotFound(
+        revision.unwrap_or_default().to_string(),
+    ))
+}
+
+/// Clones the submodules of a checkout recursively, like `git submodule update --init --recursive`.
+/// The sparse checkout only applies to the submodules of the repository itself.
+fn update_submodules(
+    repo: &gix::Repository,
+    repo_url: &gix::Url,
+    auth: &GitAuth,
+    sparse: Option<&SparseCheckout>,
+    checkouts: &mut Vec<(PathBuf, gix::Url)>,
+) -> Result<(), GitError> {
+    let Some(submodules) = repo
+        .submodules()
+        .map_err(|e| GitError::Submodule(e.to_string()))?
+    else {
+        return Ok(());
+    };
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| GitError::Submodule("The repository has no worktree".to_string()))?;
+    for submodule in submodules {
+        let name = submodule.name().to_string();
+        if let Ok(Some(gix::submodule::config::Update::None)) = submodule.update() {
+            continue;
+        }
+        let path = submodule
+            .path()
+            .map_err(|e| GitError::Submodule(e.to_string()))?;
+        if sparse.is_some_and(|sparse| !sparse.includes(path.as_ref())) {
+            continue;
+        }
+        let Some(commit) = submodule
+            .head_id()
+            .map_err(|e| GitError::Submodule(format!("{}: {}", name, e)))?
+        else {
+            // Only in .gitmodules, but not committed
+            continue;
+        };
+        let url = submodule_url(
+            repo_url,
+            submodule
+                .url()
+                .map_err(|e| GitError::Submodule(e.to_string()))?,
+        );
+        // Cloning from the filesystem of the manager would let authors read it
+        if url.scheme == gix::url::Scheme::File && repo_url.scheme != gix::url::Scheme::File {
+            return Err(GitError::Submodule(format!(
+                "{} is not a remote URL",
+                url.to_bstring()
+            )));
+        }
+        let target = workdir.join(gix::path::from_bstr(path));
+        tracing::info!("Cloning submodule {} into {:?}...", name, target);
+        let submodule_repo =
+            clone_revision(&url, None, Some(&commit.to_string()), &target, auth, None)?;
+        update_submodules(&submodule_repo, &url, auth, None, checkouts)?;
+        checkouts.push((target, url));
+    }
+    Ok(())
+}
+
+/// Clones the branch of a repository with its submodules, checking out the pinned revision
+/// instead of the tip of the branch if one is given
+pub async fn clone(
+    repo_url: gix::Url,
+    branch: &str,
+    revision: Option<&str>,
+    target: PathBuf,
+    auth: &GitAuth,
+    sparse: Option<&SparseCheckout>,
+) -> Result<(), GitError> {
+    tracing::info!("Cloning {repo_url:?} into {target:?}...");
+    let branch = branch.to_string();
+    let revision = revision.map(str::to_string);
+    let clone_auth = auth.clone();
+    let sparse = sparse.cloned();
+    let checkouts = tokio::task::spawn_blocking(move || {
+        let repo = clone_revision(
+            &repo_url,
+            Some(&branch),
+            revision.as_deref(),
+            &target,
+            &clone_auth,
+            sparse.as_ref(),
+        )?;
+        let mut checkouts = Vec::new();
+        update_submodules(
+            &repo,
+            &repo_url,
+            &clone_auth,
+            sparse.as_ref(),
+            &mut checkouts,
+        )?;
+        checkouts.push((target, repo_url));
+        Ok::<_, GitError>(checkouts)
     })
-    .await?
+    .await??;
+    for (workdir, url) in checkouts {
+        lfs::fetch_objects(&workdir, &url, auth).await?;
+    }
+    Ok(())
 }
 
 pub fn copy_dir_recursively<'a>(
@@ -72,7 +466,14 @@ pub fn copy_dir_recursively<'a>(
     })
 }
 
-pub async fn sync_repo(repo_dir: &Path, git_url: &str, git_branch: &str) -> Result<(), GitError> {
+pub async fn sync_repo(
+    repo_dir: &Path,
+    git_url: &str,
+    git_branch: &str,
+    revision: Option<&str>,
+    auth: &GitAuth,
+    sparse: Option<&SparseCheckout>,
+) -> Result<(), GitError> {
     if repo_dir.join(".git").exists() {
         // Repo already exists, re-clone to a tmp dir and then replace
         let temp_dir = TempDir::new()?;
@@ -80,7 +481,10 @@ pub async fn sync_repo(repo_dir: &Path, git_url: &str, git_branch: &str) -> Resu
         clone(
             gix::Url::from_bytes(BStr::new(git_url))?,
             git_branch,
+            revision,
             temp_path.join("repo"),
+            auth,
+            sparse,
         )
         .await?;
         let temp_repo_path = temp_path.join("repo");
@@ -107,15 +511,62 @@ pub async fn sync_repo(repo_dir: &Path, git_url: &str, git_branch: &str) -> Resu
             }
         }
         let url = gix::Url::from_bytes(BStr::new(git_url))?;
-        clone(url, git_branch, repo_dir.to_path_buf()).await
+        clone(
+            url,
+            git_branch,
+            revision,
+            repo_dir.to_path_buf(),
+            auth,
+            sparse,
+        )
+        .await
     }
 }
 
+pub struct SubmoduleInfo {
+    /// Path in the repository, including the paths of the submodules it is nested in
+    pub path: String,
+    pub hash: String,
+}
+
 pub struct CommitInfo {
     pub hash: String,
     pub timestamp: u64,
     pub author: String,
     pub title: String,
+    pub submodules: Vec<SubmoduleInfo>,
+}
+
+/// Commits checked out in the submodules of a repository, recursively
+fn submodule_heads(repo: &gix::Repository, prefix: &Path, heads: &mut Vec<SubmoduleInfo>) {
+    let (Ok(Some(submodules)), Some(workdir)) = (repo.submodules(), repo.workdir()) else {
+        return;
+    };
+    for submodule in submodules {
+        let Ok(path) = submodule.path() else {
+            continue;
+        };
+        let path = gix::path::from_bstr(path).into_owned();
+        // Not cloned if its update mode is none
+        let Ok(submodule_repo) = gix::open(workdir.join(&path)) else {
+            continue;
+        };
+        let Ok(head) = submodule_repo.head_id() else {
+            continue;
+        };
+        let path = prefix.join(path);
+        heads.push(SubmoduleInfo {
+            path: path.to_string_lossy().to_string(),
+            hash: head.to_string(),
+        });
+        submodule_heads(&submodule_repo, &path, heads);
+    }
+}
+
+/// Hash of the checked out commit, without the details of [`get_head_commit_info`]
+pub fn get_head_commit(repo_dir: &Path) -> Option<String> {
+    let repo = gix::open(repo_dir).ok()?;
+    Some(repo.head_id().ok()?.to_string())
 }
 
 pub fn get_head_commit_info(repo_dir: &std::path::Path) -> Option<CommitInfo> {
@@ -129,11 +580,14 @@ pub fn get_head_commit_info(repo_dir: &std::path::Path) -> Option<CommitInfo> {
         .message()
         .map(|m| m.title.to_string())
         .unwrap_or_default();
+    let mut submodules = Vec::new();
+    submodule_heads(&repo, Path::new(""), &mut submodules);
     Some(CommitInfo {
         hash,
         timestamp,
         author,
         title,
+        submodules,
     })
 }
 
@@ -147,7 +601,15 @@ mod tests {
         let repo_path = temp_dir.path().join("test_repo");
         let git_url = "https://github.com/octocat/Hello-World.git";
         let git_branch = "master";
-        let result = sync_repo(&repo_path, git_url, git_branch).await;
+        let result = sync_repo(
+            &repo_path,
+            git_url,
+            git_branch,
+            None,
+            &GitAuth::default(),
+            None,
+        )
+        .await;
         assert!(result.is_ok());
         assert!(repo_path.exists());
         // Ensure a file called README exists in the cloned repo with the content "Hello World!\n"
@@ -164,4 +626,81 @@ mod tests {
         );
         assert_eq!(commit_info.timestamp, 1331075210); // 2012-03-06 15:06:50 UTC-0800
     }
+
+    #[test]
+    fn test_sparse_checkout_includes() {
+        let sparse = SparseCheckout {
+            patterns: vec!["challs/*".to_string(), "event.yml".to_string()],
+        };
+        assert!(sparse.includes("event.yml".into()));
+        assert!(sparse.includes("challs/web".into()));
+        assert!(sparse.includes("challs/web/dist/chall.zip".into()));
+        assert!(!sparse.includes("challs".into()));
+        assert!(!sparse.includes("assets/video.mp4".into()));
+        assert!(!sparse.includes("event.yml.bak".into()));
+    }
+
+    #[test]
+    fn test_token_only_for_repo_host() {
+        let auth = GitAuth {
+            https: Some(("git".to_string(), "token".to_string())),
+            host: Some("git.example.com".to_string()),
+            ssh_command: None,
+        };
+        let url = |url: &str| gix::Url::from_bytes(BStr::new(url)).unwrap();
+        assert!(
+            auth.https_for(&url("https://git.example.com/ctf/web.git"))
+                .is_some()
+        );
+        assert!(
+            auth.https_for(&url("https://evil.example.com/ctf/web.git"))
+                .is_none()
+        );
+        assert!(auth.https_for(&url("/srv/git/web.git")).is_none());
+    }
+
+    #[test]
+    fn test_submodule_url() {
+        let https =
+            gix::Url::from_bytes(BStr::new("https://git.example.com/ctf/challs.git")).unwrap();
+        let resolve = |superproject: &gix::Url, url: &str| {
+            submodule_url(superproject, gix::Url::from_bytes(BStr::new(url)).unwrap())
+                .to_bstring()
+                .to_string()
+        };
+        assert_eq!(
+            resolve(&https, "../web.git"),
+            "https://git.example.com/ctf/web.git"
+        );
+        assert_eq!(
+            resolve(&https, "./pwn.git"),
+            "https://git.example.com/ctf/challs.git/pwn.git"
+        );
+        assert_eq!(
+            resolve(&https, "https://github.com/octocat/Hello-World.git"),
+            "https://github.com/octocat/Hello-World.git"
+        );
+        let ssh = gix::Url::from_bytes(BStr::new("git@git.example.com:ctf/challs.git")).unwrap();
+        assert_eq!(
+            resolve(&ssh, "../../other/web.git"),
+            "git@git.example.com:other/web.git"
+        );
+    }
+
+    #[test]
+    fn test_ssh_command() {
+        assert_eq!(
+            ssh_command(Path::new("/secrets/deploy key"), None),
+            "ssh -i '/secrets/deploy key' -o IdentitiesOnly=yes -o BatchMode=yes \
+             -o StrictHostKeyChecking=accept-new"
+        );
+        assert_eq!(
+            ssh_command(
+                Path::new("/secrets/key"),
+                Some(Path::new("/secrets/it's known"))
+            ),
+            "ssh -i '/secrets/key' -o IdentitiesOnly=yes -o BatchMode=yes \
+             -o UserKnownHostsFile='/secrets/it'\\''s known' -o StrictHostKeyChecking=yes"
+        );
+    }
 }
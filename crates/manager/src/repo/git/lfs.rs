@@ -0,0 +1,427 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Git LFS, which gix doesn't support, so big files like disk images are checked out as pointers.
+//!
+//! After a clone, the pointers in the worktree are replaced with the objects downloaded through
+//! the batch API of the remote. HTTPS remotes are authenticated with the token of [`GitAuth`] if
+//! they are on the host of the challenge repository, SSH remotes through `git-lfs-authenticate`
+//! with the deploy key.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+
+use super::{GitAuth, GitError, shell_quote};
+
+const POINTER_VERSION: &str = "version https://git-lfs.github.com/spec/v1";
+/// Pointers are smaller than this, so bigger files aren't read
+const MAX_POINTER_SIZE: u64 = 1024;
+const LFS_MEDIA_TYPE: &str = "application/vnd.git-lfs+json";
+/// Objects requested at once, servers commonly accept up to 100
+const BATCH_SIZE: usize = 100;
+
+static HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .connect_timeout(std::time::Duration::from_secs(30))
+        // Applies between reads, so large objects can still take their time
+        .read_timeout(std::time::Duration::from_secs(60))
+        .build()
+        .expect("Failed to create Git LFS HTTP client")
+});
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+struct LfsObject {
+    oid: String,
+    size: u64,
+}
+
+#[derive(Serialize)]
+struct BatchRequest<'a> {
+    operation: &'static str,
+    transfers: [&'static str; 1],
+    objects: &'a [LfsObject],
+}
+
+#[derive(Deserialize)]
+struct BatchResponse {
+    objects: Vec<BatchObject>,
+}
+
+#[derive(Deserialize)]
+struct BatchObject {
+    oid: String,
+    #[serde(default)]
+    actions: Option<BatchActions>,
+    #[serde(default)]
+    error: Option<BatchError>,
+}
+
+#[derive(Deserialize)]
+struct BatchActions {
+    download: Option<Action>,
+}
+
+#[derive(Deserialize)]
+struct BatchError {
+    code: u16,
+    message: String,
+}
+
+/// A URL to send a request to, also returned by `git-lfs-authenticate`
+#[derive(Deserialize, Debug, Clone, Default)]
+struct Action {
+    href: String,
+    #[serde(default)]
+    header: HashMap<String, String>,
+}
+
+/// Parses an LFS pointer, None if the file is a regular one
+fn parse_pointer(data: &[u8]) -> Option<LfsObject> {
+    let mut lines = std::str::from_utf8(data).ok()?.lines();
+    if lines.next()? != POINTER_VERSION {
+        return None;
+    }
+    let mut oid = None;
+    let mut size = None;
+    for line in lines {
+        if let Some(value) = line.strip_prefix("oid sha256:") {
+            oid = Some(value);
+        } else if let Some(value) = line.strip_prefix("size ") {
+            size = value.parse().ok();
+        }
+    }
+    let oid = oid.filter(|oid| {
+        oid.len() == 64
+            && oid
+                .bytes()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    })?;
+    Some(LfsObject {
+        oid: oid.to_string(),
+        size: size?,
+    })
+}
+
+/// Finds the pointers in a worktree, skipping nested repositories like submodules
+fn find_pointers(
+    dir: &Path,
+    pointers: &mut HashMap<LfsObject, Vec<PathBuf>>,
+) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if entry.file_name() != ".git" && !path.join(".git").exists() {
+                find_pointers(&path, pointers)?;
+            }
+        } else if file_type.is_file()
+            && entry.metadata()?.len() < MAX_POINTER_SIZE
+            && let Some(object) = parse_pointer(&std::fs::read(&path)?)
+        {
+            pointers.entry(object).or_default().push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The LFS endpoint of a remote over HTTPS, as described in
+/// https://github.com/git-lfs/git-lfs/blob/main/docs/api/server-discovery.md
+fn https_endpoint(url: &gix::Url) -> Option<String> {
+    let scheme = match url.scheme {
+        gix::url::Scheme::Http => "http",
+        gix::url::Scheme::Https | gix::url::Scheme::Ssh => "https",
+        _ => return None,
+    };
+    let host = url.host()?;
+    let port = match (&url.scheme, url.port) {
+        (gix::url::Scheme::Http | gix::url::Scheme::Https, Some(port)) => format!(":{}", port),
+        _ => String::new(),
+    };
+    let path = url.path.to_string();
+    let path = path.trim_matches('/');
+    let suffix = if path.ends_with(".git") { "" } else { ".git" };
+    Some(format!(
+        "{}://{}{}/{}{}/info/lfs",
+        scheme, host, port, path, suffix
+    ))
+}
+
+/// Asks the SSH server of the remote for the LFS endpoint and credentials
+fn ssh_authenticate(url: &gix::Url, auth: &GitAuth) -> Result<Action, GitError> {
+    let (Some(host), Some(path)) = (url.host_argument_safe(), url.path_argument_safe()) else {
+        return Err(GitError::Lfs(format!(
+            "{} is not a valid SSH URL",
+            url.to_bstring()
+        )));
+    };
+    let destination = match url.user_argument_safe() {
+        Some(user) => format!("{}@{}", user, host),
+        None => host.to_string(),
+    };
+    let mut command = auth
+        .ssh_command
+        .clone()
+        .unwrap_or_else(|| "ssh -o BatchMode=yes".to_string());
+    if let Some(port) = url.port {
+        command.push_str(&format!(" -p {}", port));
+    }
+    command.push_str(&format!(
+        " {} git-lfs-authenticate {} download",
+        shell_quote(&destination),
+        shell_quote(&path.to_string())
+    ));
+    let output = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .output()?;
+    if !output.status.success() {
+        return Err(GitError::Lfs(format!(
+            "git-lfs-authenticate failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    serde_json::from_slice(&output.stdout)
+        .map_err(|e| GitError::Lfs(format!("Invalid git-lfs-authenticate response: {}", e)))
+}
+
+struct LfsServer {
+    endpoint: Action,
+    basic_auth: Option<(String, String)>,
+}
+
+impl LfsServer {
+    async fn discover(url: &gix::Url, auth: &GitAuth) -> Result<Self, GitError> {
+        let no_endpoint = || {
+            GitError::Lfs(format!(
+                "{} has no LFS server, only HTTP(S) and SSH remotes are supported",
+                url.to_bstring()
+            ))
+        };
+        if url.scheme == gix::url::Scheme::Ssh {
+            let ssh_url = url.clone();
+            let ssh_auth = auth.clone();
+            let authenticated =
+                tokio::task::spawn_blocking(move || ssh_authenticate(&ssh_url, &ssh_auth)).await?;
+            match authenticated {
+                Ok(endpoint) => {
+                    return Ok(LfsServer {
+                        endpoint,
+                        basic_auth: None,
+                    });
+                }
+                // Servers without git-lfs-authenticate serve public objects over HTTPS
+                Err(e) => tracing::warn!("{}, trying the HTTPS endpoint", e),
+            }
+            return Ok(LfsServer {
+                endpoint: Action {
+                    href: https_endpoint(url).ok_or_else(no_endpoint)?,
+                    ..Default::default()
+                },
+                basic_auth: None,
+            });
+        }
+        let basic_auth = auth.https_for(url).or_else(|| {
+            url.user()
+                .zip(url.password())
+                .map(|(user, password)| (user.to_string(), password.to_string()))
+        });
+        Ok(LfsServer {
+            endpoint: Action {
+                href: https_endpoint(url).ok_or_else(no_endpoint)?,
+                ..Default::default()
+            },
+            basic_auth,
+        })
+    }
+
+    /// Gets the download actions of the objects
+    async fn batch(&self, objects: &[LfsObject]) -> Result<HashMap<String, Action>, GitError> {
+        let body = serde_json::to_vec(&BatchRequest {
+            operation: "download",
+            transfers: ["basic"],
+            objects,
+        })
+        .map_err(|e| GitError::Lfs(e.to_string()))?;
+        let mut request = HTTP_CLIENT
+            .post(format!(
+                "{}/objects/batch",
+                self.endpoint.href.trim_end_matches('/')
+            ))
+            .header("Accept", LFS_MEDIA_TYPE)
+            .header("Content-Type", LFS_MEDIA_TYPE)
+            .body(body);
+        for (name, value) in &self.endpoint.header {
+            request = request.header(name, value);
+        }
+        if let Some((username, password)) = &self.basic_auth {
+            request = request.basic_auth(username, Some(password));
+        }
+        let response = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| GitError::Lfs(format!("Batch request failed: {}", e)))?;
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| GitError::Lfs(format!("Batch request failed: {}", e)))?;
+        let response: BatchResponse = serde_json::from_slice(&body)
+            .map_err(|e| GitError::Lfs(format!("Invalid batch response: {}", e)))?;
+        let mut actions = HashMap::new();
+        for object in response.objects {
+            if let Some(error) = object.error {
+                return Err(GitError::Lfs(format!(
+                    "Object {} is not available ({}): {}",
+                    object.oid, error.code, error.message
+                )));
+            }
+            if let Some(download) = object.actions.and_then(|actions| actions.download) {
+                actions.insert(object.oid, download);
+            }
+        }
+        Ok(actions)
+    }
+}
+
+/// Downloads an object and replaces the pointers with it, keeping their permissions
+async fn download(action: &Action, object: &LfsObject, paths: &[PathBuf]) -> Result<(), GitError> {
+    let Some((first, others)) = paths.split_first() else {
+        return Ok(());
+    };
+    let mut request = HTTP_CLIENT.get(&action.href);
+    for (name, value) in &action.header {
+        request = request.header(name, value);
+    }
+    let mut response = request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| GitError::Lfs(format!("Failed to download {}: {}", object.oid, e)))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(first.file_name().unwrap_or_default());
+    temp_name.push(".lfs");
+    let temp_path = first.with_file_name(temp_name);
+    let mut file = tokio::fs::File::create(&temp_path).await?;
+    let mut hasher = Sha256::new();
+    let mut size = 0;
+    let result = loop {
+        match response.chunk().await {
+            Ok(Some(chunk)) => {
+                hasher.update(&chunk);
+                size += chunk.len() as u64;
+                if let Err(e) = file.write_all(&chunk).await {
+                    break Err(format!("Failed to write {}: {}", object.oid, e));
+                }
+            }
+            Ok(None) => break Ok(()),
+            Err(e) => break Err(format!("Failed to download {}: {}", object.oid, e)),
+        }
+    }
+    .and_then(|()| {
+        if size == object.size && format!("{:x}", hasher.finalize()) == object.oid {
+            Ok(())
+        } else {
+            Err(format!("Downloaded object {} is corrupted", object.oid))
+        }
+    });
+    let result = match file.flush().await {
+        Ok(()) => result,
+        Err(e) => Err(format!("Failed to write {}: {}", object.oid, e)),
+    };
+    drop(file);
+    if let Err(e) = result {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(GitError::Lfs(e));
+    }
+    let permissions = tokio::fs::metadata(first).await?.permissions();
+    tokio::fs::set_permissions(&temp_path, permissions).await?;
+    tokio::fs::rename(&temp_path, first).await?;
+    for path in others {
+        tokio::fs::copy(first, path).await?;
+    }
+    Ok(())
+}
+
+/// Replaces the LFS pointers in the worktree of a repository cloned from `repo_url`
+pub async fn fetch_objects(
+    workdir: &Path,
+    repo_url: &gix::Url,
+    auth: &GitAuth,
+) -> Result<(), GitError> {
+    let pointer_dir = workdir.to_path_buf();
+    let pointers = tokio::task::spawn_blocking(move || {
+        let mut pointers = HashMap::new();
+        find_pointers(&pointer_dir, &mut pointers).map(|()| pointers)
+    })
+    .await??;
+    if pointers.is_empty() {
+        return Ok(());
+    }
+    tracing::info!(
+        "Downloading {} LFS objects into {:?}...",
+        pointers.len(),
+        workdir
+    );
+    let server = LfsServer::discover(repo_url, auth).await?;
+    let objects: Vec<LfsObject> = pointers.keys().cloned().collect();
+    for objects in objects.chunks(BATCH_SIZE) {
+        let actions = server.batch(objects).await?;
+        for object in objects {
+            let action = actions.get(&object.oid).ok_or_else(|| {
+                GitError::Lfs(format!("The server has no download for {}", object.oid))
+            })?;
+            download(action, object, &pointers[object]).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pointer() {
+        let oid = "4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393";
+        let pointer = format!("{}\noid sha256:{}\nsize 12345\n", POINTER_VERSION, oid);
+        assert_eq!(
+            parse_pointer(pointer.as_bytes()),
+            Some(LfsObject {
+                oid: oid.to_string(),
+                size: 12345,
+            })
+        );
+        assert_eq!(parse_pointer(b"oid sha256:abc\nsize 1\n"), None);
+        let truncated = format!("{}\noid sha256:{}\n", POINTER_VERSION, &oid[..10]);
+        assert_eq!(parse_pointer(truncated.as_bytes()), None);
+        assert_eq!(parse_pointer(&[0xff, 0xfe]), None);
+    }
+
+    #[test]
+    fn test_https_endpoint() {
+        let endpoint =
+            |url: &str| https_endpoint(&gix::Url::from_bytes(gix::bstr::BStr::new(url)).unwrap());
+        assert_eq!(
+            endpoint("https://github.com/org/challs").as_deref(),
+            Some("https://github.com/org/challs.git/info/lfs")
+        );
+        assert_eq!(
+            endpoint("http://git.local:3000/org/challs.git/").as_deref(),
+            Some("http://git.local:3000/org/challs.git/info/lfs")
+        );
+        assert_eq!(
+            endpoint("git@github.com:org/challs.git").as_deref(),
+            Some("https://github.com/org/challs.git/info/lfs")
+        );
+        assert_eq!(endpoint("/srv/git/challs"), None);
+    }
+}
@@ -0,0 +1,581 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Builds the images of compose services with a `build:` section in the cluster.
+//!
+//! Every image is built by a Kaniko Job. The manager streams the build context from its synced
+//! checkout to the Job's stdin, so Git LFS files and submodules are included and the repository
+//! credentials never leave the manager. Images are tagged with a hash of their build context, so unchanged services
+//! are not rebuilt on every sync and instances can find their image without asking the builder.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Seek,
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use compose_spec::{ShortOrLong, service::build::Context};
+use k8s_openapi::api::{
+    batch::v1::{Job, JobSpec},
+    core::v1::{
+        Container, KeyToPath, Pod, PodSpec, PodTemplateSpec, SecretVolumeSource, Volume,
+        VolumeMount,
+    },
+};
+use kube::{
+    Api, Client,
+    api::{AttachParams, DeleteParams, ListParams, LogParams, ObjectMeta, PostParams},
+};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+
+const DEFAULT_NAMESPACE: &str = "plfanzen-builds";
+const DEFAULT_EXECUTOR_IMAGE: &str = "gcr.io/kaniko-project/executor:v1.23.2";
+/// Builds taking longer than this are stopped and count as failed
+const BUILD_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Lines of the build log kept for the build status
+const LOG_TAIL_LINES: i64 = 200;
+
+/// Context hashes by challenge directory and build spec, for the commit they were computed at
+type HashCache = (String, HashMap<(PathBuf, ImageBuildSpec), String>);
+static CONTEXT_HASHES: LazyLock<Mutex<HashCache>> = LazyLock::new(Default::default);
+
+/// Settings of the image builder, read from the environment.
+#[derive(Debug, Clone)]
+pub struct BuildConfig {
+    /// Registry and path prefix images are pushed to, e.g. registry.example.com/challenges
+    pub registry: String,
+    /// Namespace the build Jobs run in
+    pub namespace: String,
+    pub executor_image: String,
+    /// Secret of type kubernetes.io/dockerconfigjson with the credentials for the registry
+    pub push_secret: Option<String>,
+    /// Whether the registry is reached over plain HTTP
+    pub insecure_registry: bool,
+}
+
+impl BuildConfig {
+    /// Returns `None` if BUILD_REGISTRY is not set, building images is disabled then.
+    pub fn from_env() -> Option<Self> {
+        let registry = std::env::var("BUILD_REGISTRY").ok()?;
+        Some(Self {
+            registry: registry.trim_end_matches('/').to_string(),
+            namespace: std::env::var("BUILD_NAMESPACE")
+                .unwrap_or_else(|_| DEFAULT_NAMESPACE.to_string()),
+            executor_image: std::env::var("BUILD_EXECUTOR_IMAGE")
+                .unwrap_or_else(|_| DEFAULT_EXECUTOR_IMAGE.to_string()),
+            push_secret: std::env::var("BUILD_PUSH_SECRET").ok(),
+            insecure_registry: std::env::var("BUILD_INSECURE_REGISTRY")
+                .is_ok_and(|v| v == "true" || v == "1"),
+        })
+    }
+}
+
+/// What to build for a service, taken from its `build:` section.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ImageBuildSpec {
+    /// Build context, relative to the challenge directory
+    pub context: PathBuf,
+    /// Dockerfile, relative to the build context
+    pub dockerfile: Option<PathBuf>,
+    pub args: Vec<String>,
+    pub target: Option<String>,
+}
+
+impl ImageBuildSpec {
+    /// Reads the build section of a service, rejecting what can't be built from the repository.
+    pub fn from_service(
+        svc: &compose_spec::Service,
+        chall_dir: &Path,
+    ) -> Result<Option<Self>, String> {
+        let Some(build) = &svc.build else {
+            return Ok(None);
+        };
+        let (context, dockerfile, args, target) = match build.clone() {
+            ShortOrLong::Short(context) => (Some(context), None, Vec::new(), None),
+            ShortOrLong::Long(build) => {
+                let dockerfile = match build.dockerfile {
+                    None => None,
+                    Some(compose_spec::service::build::Dockerfile::File(path)) => Some(path),
+                    Some(compose_spec::service::build::Dockerfile::Inline(_)) => {
+                        return Err("dockerfile_inline is not supported".to_string());
+                    }
+                };
+                (
+                    build.context,
+                    dockerfile,
+                    build.args.into_list().into_iter().collect(),
+                    build.target,
+                )
+            }
+        };
+        let context = match context {
+            None => PathBuf::from("."),
+            Some(Context::Path(path)) => path,
+            Some(Context::Url(url)) => {
+                return Err(format!(
+                    "Build context {} is not supported, it must be a directory of the challenge",
+                    url
+                ));
+            }
+        };
+        let chall_dir = chall_dir
+            .canonicalize()
+            .map_err(|e| format!("Failed to resolve challenge directory: {}", e))?;
+        let resolved = chall_dir.join(&context).canonicalize().map_err(|e| {
+            format!(
+                "Build context {} does not exist: {}",
+                context.to_string_lossy(),
+                e
+            )
+        })?;
+        if !resolved.starts_with(&chall_dir) || !resolved.is_dir() {
+            return Err(format!(
+                "Build context {} must be a directory inside the challenge directory",
+                context.to_string_lossy()
+            ));
+        }
+        Ok(Some(Self {
+            context: resolved
+                .strip_prefix(&chall_dir)
+                .unwrap_or(Path::new(""))
+                .to_path_buf(),
+            dockerfile,
+            args,
+            target,
+        }))
+    }
+
+    /// Hash of everything that goes into the image: the files of the context and the settings.
+    pub fn hash(&self, chall_dir: &Path) -> std::io::Result<String> {
+        let context_dir = chall_dir.join(&self.context);
+        let mut files = Vec::new();
+        collect_files(&context_dir, &context_dir, &mut files)?;
+        files.sort();
+
+        let mut hasher = Sha256::new();
+        for file in files {
+            hasher.update(file.to_string_lossy().as_bytes());
+            hasher.update([0]);
+            hasher.update(std::fs::read(context_dir.join(&file))?);
+            hasher.update([0]);
+        }
+        if let Some(dockerfile) = &self.dockerfile {
+            hasher.update(dockerfile.to_string_lossy().as_bytes());
+        }
+        hasher.update([0]);
+        for arg in &self.args {
+            hasher.update(arg.as_bytes());
+            hasher.update([0]);
+        }
+        if let Some(target) = &self.target {
+            hasher.update(target.as_bytes());
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+}
+
+/// Hash of a build context in a checkout of `commit`. Hashing reads every file of the context,
+/// so the result is kept until another commit is checked out.
+fn cached_hash(spec: &ImageBuildSpec, chall_dir: &Path, commit: &str) -> std::io::Result<String> {
+    let key = (chall_dir.to_path_buf(), spec.clone());
+    {
+        let cache = CONTEXT_HASHES
+            .lock()
+            .expect("Context hash cache lock poisoned");
+        if cache.0 == commit
+            && let Some(hash) = cache.1.get(&key)
+        {
+            return Ok(hash.clone());
+        }
+    }
+    let hash = spec.hash(chall_dir)?;
+    let mut cache = CONTEXT_HASHES
+        .lock()
+        .expect("Context hash cache lock poisoned");
+    if cache.0 != commit {
+        *cache = (commit.to_string(), HashMap::new());
+    }
+    cache.1.insert(key, hash.clone());
+    Ok(hash)
+}
+
+/// Lists all files below `dir`, relative to `root`.
+pub fn collect_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if file_type.is_file() {
+            files.push(path.strip_prefix(root).unwrap_or(&path).to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+/// Packs a build context into a temporary tar.gz, symlinks are kept as they are in the repository.
+fn pack_context(context_dir: &Path) -> std::io::Result<std::fs::File> {
+    let encoder = flate2::write::GzEncoder::new(tempfile::tempfile()?, flate2::Compression::fast());
+    let mut archive = tar::Builder::new(encoder);
+    archive.follow_symlinks(false);
+    archive.append_dir_all(".", context_dir)?;
+    let mut file = archive.into_inner()?.finish()?;
+    file.rewind()?;
+    Ok(file)
+}
+
+/// Tag the image of a service is pushed to
+pub fn image_tag(registry: &str, challenge_id: &str, service: &str, hash: &str) -> String {
+    format!(
+        "{}/{}-{}:{}",
+        registry,
+        challenge_id,
+        service.to_lowercase(),
+        &hash[..16]
+    )
+}
+
+/// Name of the Job building the image with the given tag.
+///
+/// Services of different challenges can share a build context, so the name is derived from the
+/// whole tag rather than the context hash alone to give each of them its own Job.
+fn job_name(tag: &str) -> String {
+    format!("build-{:x}", Sha256::digest(tag.as_bytes()))[..38].to_string()
+}
+
+/// Replaces the build sections of a challenge's services with the images built from them.
+///
+/// `commit` is the checked out commit of the challenge directory, None if it isn't a checkout.
+pub fn use_built_images(
+    compose: &mut compose_spec::Compose,
+    chall_dir: &Path,
+    challenge_id: &str,
+    commit: Option<&str>,
+) -> Result<(), String> {
+    let Some(config) = BuildConfig::from_env() else {
+        // Validation rejects the build sections later on
+        return Ok(());
+    };
+    for (svc_id, svc) in compose.services.iter_mut() {
+        let Some(spec) = ImageBuildSpec::from_service(svc, chall_dir)? else {
+            continue;
+        };
+        let hash = match commit {
+            Some(commit) => cached_hash(&spec, chall_dir, commit),
+            None => spec.hash(chall_dir),
+        }
+        .map_err(|e| format!("Failed to hash build context of {}: {}", svc_id, e))?;
+        let tag = image_tag(&config.registry, challenge_id, &svc_id.to_string(), &hash);
+        svc.image = Some(
+            compose_spec::service::Image::parse(tag)
+                .map_err(|e| format!("Invalid image name for {}: {}", svc_id, e))?,
+        );
+        svc.build = None;
+    }
+    Ok(())
+}
+
+/// Launches build Jobs and waits for them to finish.
+pub struct ImageBuilder {
+    pub kube_client: Client,
+    pub config: BuildConfig,
+}
+
+impl ImageBuilder {
+    fn job(&self, name: &str, tag: &str, challenge_id: &str, spec: &ImageBuildSpec) -> Job {
+        let mut args = vec![
+            // Kaniko reads the build context from stdin, see send_context
+            "--context=tar://stdin".to_string(),
+            format!("--destination={}", tag),
+        ];
+        if let Some(dockerfile) = &spec.dockerfile {
+            args.push(format!("--dockerfile={}", dockerfile.to_string_lossy()));
+        }
+        args.extend(spec.args.iter().map(|arg| format!("--build-arg={}", arg)));
+        if let Some(target) = &spec.target {
+            args.push(format!("--target={}", target));
+        }
+        if self.config.insecure_registry {
+            args.push("--insecure".to_string());
+        }
+
+        let (volumes, volume_mounts) = match &self.config.push_secret {
+            Some(secret) => (
+                Some(vec![Volume {
+                    name: "docker-config".to_string(),
+                    secret: Some(SecretVolumeSource {
+                        secret_name: Some(secret.clone()),
+                        items: Some(vec![KeyToPath {
+                            key: ".dockerconfigjson".to_string(),
+                            path: "config.json".to_string(),
+                            ..Default::default()
+                        }]),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }]),
+                Some(vec![VolumeMount {
+                    name: "docker-config".to_string(),
+                    mount_path: "/kaniko/.docker".to_string(),
+                    read_only: Some(true),
+                    ..Default::default()
+                }]),
+            ),
+            None => (None, None),
+        };
+
+        Job {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                namespace: Some(self.config.namespace.clone()),
+                labels: Some(BTreeMap::from([(
+                    "challenge_id".to_string(),
+                    challenge_id.to_string(),
+                )])),
+                ..Default::default()
+            },
+            spec: Some(JobSpec {
+                backoff_limit: Some(0),
+                active_deadline_seconds: Some(BUILD_TIMEOUT.as_secs() as i64),
+                // Finished Jobs are kept for a day, so builds of the same image aren't repeated
+                ttl_seconds_after_finished: Some(24 * 60 * 60),
+                template: PodTemplateSpec {
+                    spec: Some(PodSpec {
+                        restart_policy: Some("Never".to_string()),
+                        containers: vec![Container {
+                            name: "build".to_string(),
+                            image: Some(self.config.executor_image.clone()),
+                            args: Some(args),
+                            stdin: Some(true),
+                            // Closes stdin once the manager is done sending the context
+                            stdin_once: Some(true),
+                            volume_mounts,
+                            ..Default::default()
+                        }],
+                        volumes,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Waits for the pod of a build Job to start and streams the build context to its stdin.
+    async fn send_context(&self, name: &str, context_dir: &Path) -> Result<(), String> {
+        let context_dir = context_dir.to_path_buf();
+        let context = tokio::task::spawn_blocking(move || pack_context(&context_dir))
+            .await
+            .map_err(|e| format!("Failed to pack build context: {}", e))?
+            .map_err(|e| format!("Failed to pack build context: {}", e))?;
+
+        let pod_api: Api<Pod> = Api::namespaced(self.kube_client.clone(), &self.config.namespace);
+        let lp = ListParams::default().labels(&format!("job-name={}", name));
+        let wait_for_pod = async {
+            loop {
+                let pods = pod_api
+                    .list(&lp)
+                    .await
+                    .map_err(|e| format!("Failed to find build pod: {}", e))?;
+                for pod in pods.items {
+                    match pod.status.and_then(|status| status.phase).as_deref() {
+                        Some("Running") => return Ok(pod.metadata.name.unwrap_or_default()),
+                        Some("Succeeded") | Some("Failed") => {
+                            return Err(
+                                "Build pod stopped before it got the build context".to_string()
+                            );
+                        }
+                        _ => {}
+                    }
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        };
+        let pod_name = tokio::time::timeout(BUILD_TIMEOUT, wait_for_pod)
+            .await
+            .map_err(|_| "Build pod did not start in time".to_string())??;
+
+        let mut process = pod_api
+            .attach(
+                &pod_name,
+                &AttachParams::default()
+                    .container("build")
+                    .stdin(true)
+                    .stdout(false)
+                    .stderr(false),
+            )
+            .await
+            .map_err(|e| format!("Failed to attach to build pod: {}", e))?;
+        let mut stdin = process.stdin().ok_or("Build pod has no stdin")?;
+        tokio::io::copy(&mut tokio::fs::File::from_std(context), &mut stdin)
+            .await
+            .map_err(|e| format!("Failed to send build context: {}", e))?;
+        stdin
+            .shutdown()
+            .await
+            .map_err(|e| format!("Failed to send build context: {}", e))?;
+        // Dropping the process keeps the connection open until the build is done, the context
+        // is fully sent by then
+        Ok(())
+    }
+
+    /// End of the log of a build Job's pod
+    async fn job_log(&self, name: &str) -> String {
+        let pod_api: Api<Pod> = Api::namespaced(self.kube_client.clone(), &self.config.namespace);
+        let pods = match pod_api
+            .list(&ListParams::default().labels(&format!("job-name={}", name)))
+            .await
+        {
+            Ok(pods) => pods,
+            Err(e) => return format!("Failed to find build pod: {}", e),
+        };
+        let Some(pod_name) = pods.items.first().and_then(|pod| pod.metadata.name.clone()) else {
+            return "Build pod not found".to_string();
+        };
+        pod_api
+            .logs(
+                &pod_name,
+                &LogParams {
+                    tail_lines: Some(LOG_TAIL_LINES),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap_or_else(|e| format!("Failed to get build log: {}", e))
+    }
+
+    /// Builds and pushes the image of a service, returning its tag and the end of the build log.
+    ///
+    /// A Job that already built the same image is reused, a failed one is started again. Only
+    /// the manager that created a Job sends it the context, so a Job left behind by a restart
+    /// runs into the build timeout and is started again on the next sync.
+    pub async fn build(
+        &self,
+        chall_dir: &Path,
+        challenge_id: &str,
+        service: &str,
+        spec: &ImageBuildSpec,
+    ) -> Result<(String, String), String> {
+        let hash = spec
+            .hash(chall_dir)
+            .map_err(|e| format!("Failed to hash build context: {}", e))?;
+        let tag = image_tag(&self.config.registry, challenge_id, service, &hash);
+        let name = job_name(&tag);
+        let job_api: Api<Job> = Api::namespaced(self.kube_client.clone(), &self.config.namespace);
+
+        let mut existing = job_api
+            .get_opt(&name)
+            .await
+            .map_err(|e| format!("Failed to get build job: {}", e))?;
+        if existing
+            .as_ref()
+            .and_then(|job| job.status.as_ref())
+            .is_some_and(|status| status.failed.unwrap_or_default() > 0)
+        {
+            job_api
+                .delete(&name, &DeleteParams::background())
+                .await
+                .map_err(|e| format!("Failed to delete failed build job: {}", e))?;
+            // The Job keeps its name until it is gone
+            while job_api.get_opt(&name).await.ok().flatten().is_some() {
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+            existing = None;
+        }
+        if existing.is_none() {
+            job_api
+                .create(
+                    &PostParams::default(),
+                    &self.job(&name, &tag, challenge_id, spec),
+                )
+                .await
+                .map_err(|e| format!("Failed to create build job: {}", e))?;
+            if let Err(e) = self
+                .send_context(&name, &chall_dir.join(&spec.context))
+                .await
+            {
+                let _ = job_api.delete(&name, &DeleteParams::background()).await;
+                return Err(e);
+            }
+        }
+
+        loop {
+            let status = job_api
+                .get_opt(&name)
+                .await
+                .map_err(|e| format!("Failed to get build job: {}", e))?
+                .ok_or("Build job was deleted")?
+                .status
+                .unwrap_or_default();
+            if status.succeeded.unwrap_or_default() > 0 {
+                return Ok((tag, self.job_log(&name).await));
+            }
+            if status.failed.unwrap_or_default() > 0 {
+                return Err(self.job_log(&name).await);
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_changes_with_context() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/Dockerfile"), "FROM alpine").unwrap();
+        let spec = ImageBuildSpec {
+            context: PathBuf::from("src"),
+            dockerfile: None,
+            args: Vec::new(),
+            target: None,
+        };
+        let hash = spec.hash(dir.path()).unwrap();
+        assert_eq!(hash, spec.hash(dir.path()).unwrap());
+
+        std::fs::write(dir.path().join("src/flag.txt"), "flag{test}").unwrap();
+        let changed = spec.hash(dir.path()).unwrap();
+        assert_ne!(hash, changed);
+
+        let with_args = ImageBuildSpec {
+            args: vec!["VERSION=2".to_string()],
+            ..spec
+        };
+        assert_ne!(changed, with_args.hash(dir.path()).unwrap());
+    }
+
+    #[test]
+    fn test_job_name_depends_on_tag() {
+        let hash = "0".repeat(64);
+        let web = job_name(&image_tag("registry", "chall", "web", &hash));
+        let bot = job_name(&image_tag("registry", "chall", "bot", &hash));
+        assert_ne!(web, bot);
+        assert_eq!(web.len(), "build-".len() + 32);
+    }
+
+    #[test]
+    fn test_context_must_stay_in_challenge() {
+        let dir = tempfile::tempdir().unwrap();
+        let chall_dir = dir.path().join("chall");
+        std::fs::create_dir_all(chall_dir.join("web")).unwrap();
+        let svc: compose_spec::Service = serde_yaml::from_str("build: ./web").unwrap();
+        let spec = ImageBuildSpec::from_service(&svc, &chall_dir)
+            .unwrap()
+            .unwrap();
+        assert_eq!(spec.context, PathBuf::from("web"));
+
+        let svc: compose_spec::Service = serde_yaml::from_str("build: ..").unwrap();
+        assert!(ImageBuildSpec::from_service(&svc, &chall_dir).is_err());
+    }
+}
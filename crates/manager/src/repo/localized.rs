@@ -0,0 +1,43 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+/// Locale used as the default text if a text is translated into several languages
+const DEFAULT_LOCALE: &str = "en";
+
+/// Markdown text that is either the same for everyone or given per locale,
+/// e.g. `description_md: {en: ..., de: ...}`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum LocalizedMarkdown {
+    Plain(String),
+    Localized(BTreeMap<String, String>),
+}
+
+impl LocalizedMarkdown {
+    /// The text shown if no translation for the requested locale exists.
+    ///
+    /// For translated texts, this is the English one, or the first locale if there is none.
+    pub fn default_text(&self) -> &str {
+        match self {
+            LocalizedMarkdown::Plain(text) => text,
+            LocalizedMarkdown::Localized(texts) => texts
+                .get(DEFAULT_LOCALE)
+                .or_else(|| texts.values().next())
+                .map(String::as_str)
+                .unwrap_or_default(),
+        }
+    }
+
+    /// All translations by locale, empty for plain texts.
+    pub fn translations(&self) -> HashMap<String, String> {
+        match self {
+            LocalizedMarkdown::Plain(_) => HashMap::new(),
+            LocalizedMarkdown::Localized(texts) => texts.clone().into_iter().collect(),
+        }
+    }
+}
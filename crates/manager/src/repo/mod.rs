@@ -2,9 +2,17 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
+pub mod builds;
 pub mod challenges;
-mod event_config;
+pub mod event_config;
 mod git;
+pub mod image_builder;
+mod localized;
+pub mod release_waves;
+pub mod revision;
 
 pub use event_config::EventConfig;
-pub use git::{get_head_commit_info, sync_repo};
+pub use localized::LocalizedMarkdown;
+pub use git::{
+    CommitInfo, GitAuth, SparseCheckout, get_head_commit, get_head_commit_info, sync_repo,
+};
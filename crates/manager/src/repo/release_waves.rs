@@ -0,0 +1,174 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Releases the waves of the event config at their release time, so the api can announce them
+//! right away instead of clients comparing release times with their clocks.
+//!
+//! The scheduler sleeps until the next wave and publishes it to everyone watching releases. The
+//! waves are loaded again after every sync, as they might have changed.
+
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use tokio::sync::{Notify, broadcast};
+
+use crate::repo::{EventConfig, event_config::ReleaseWave};
+
+/// Waves released this long before somebody starts watching are sent to them as well, so
+/// releases aren't missed while the api restarts
+const REPLAY_WINDOW: TimeDelta = TimeDelta::minutes(10);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReleasedWave {
+    pub name: String,
+    pub release_time: DateTime<Utc>,
+    pub challenge_ids: Vec<String>,
+}
+
+impl ReleasedWave {
+    fn new(name: &str, wave: &ReleaseWave) -> Self {
+        ReleasedWave {
+            name: name.to_string(),
+            release_time: wave.release_time,
+            challenge_ids: wave.challenges.clone(),
+        }
+    }
+}
+
+/// The waves released next after `after`, which are all released at the same time
+fn next_waves(waves: &BTreeMap<String, ReleaseWave>, after: DateTime<Utc>) -> Vec<ReleasedWave> {
+    let Some(next) = waves
+        .values()
+        .map(|wave| wave.release_time)
+        .filter(|release_time| *release_time > after)
+        .min()
+    else {
+        return Vec::new();
+    };
+    waves
+        .iter()
+        .filter(|(_, wave)| wave.release_time == next)
+        .map(|(name, wave)| ReleasedWave::new(name, wave))
+        .collect()
+}
+
+/// The waves released within [`REPLAY_WINDOW`] before `now`, oldest first
+fn recent_waves(waves: &BTreeMap<String, ReleaseWave>, now: DateTime<Utc>) -> Vec<ReleasedWave> {
+    let mut recent: Vec<ReleasedWave> = waves
+        .iter()
+        .filter(|(_, wave)| wave.release_time <= now && wave.release_time > now - REPLAY_WINDOW)
+        .map(|(name, wave)| ReleasedWave::new(name, wave))
+        .collect();
+    recent.sort_by_key(|wave| wave.release_time);
+    recent
+}
+
+pub struct ReleaseScheduler {
+    repo_dir: PathBuf,
+    released: broadcast::Sender<ReleasedWave>,
+    reload: Notify,
+}
+
+impl ReleaseScheduler {
+    /// Starts releasing the waves of the repository checked out at `repo_dir`
+    pub fn spawn(repo_dir: PathBuf) -> Arc<Self> {
+        let scheduler = Arc::new(ReleaseScheduler {
+            repo_dir,
+            released: broadcast::channel(16).0,
+            reload: Notify::new(),
+        });
+        tokio::spawn(scheduler.clone().run());
+        scheduler
+    }
+
+    async fn waves(&self) -> BTreeMap<String, ReleaseWave> {
+        EventConfig::try_load_from_repo(&self.repo_dir)
+            .await
+            .map(|event_config| event_config.release_waves)
+            .unwrap_or_default()
+    }
+
+    async fn run(self: Arc<Self>) {
+        // Waves released before the manager started were already released by the previous one
+        let mut released_until = Utc::now();
+        loop {
+            // Waiting for a reload starts before the waves are loaded, so no sync is missed
+            let reload = self.reload.notified();
+            let mut reload = std::pin::pin!(reload);
+            reload.as_mut().enable();
+            let waves = next_waves(&self.waves().await, released_until);
+            let Some(release_time) = waves.first().map(|wave| wave.release_time) else {
+                reload.await;
+                continue;
+            };
+            let delay = (release_time - Utc::now()).to_std().unwrap_or_default();
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {
+                    released_until = release_time;
+                    for wave in waves {
+                        tracing::info!(
+                            "Releasing wave {} with challenges {:?}",
+                            wave.name,
+                            wave.challenge_ids
+                        );
+                        // Nobody might be watching, which is fine
+                        let _ = self.released.send(wave);
+                    }
+                }
+                _ = reload => {}
+            }
+        }
+    }
+
+    /// Looks for the next wave again, e.g. after a sync changed the event config
+    pub fn reload(&self) {
+        self.reload.notify_waiters();
+    }
+
+    /// The waves released recently, and a receiver for the ones released from now on
+    pub async fn watch(&self) -> (Vec<ReleasedWave>, broadcast::Receiver<ReleasedWave>) {
+        let receiver = self.released.subscribe();
+        (recent_waves(&self.waves().await, Utc::now()), receiver)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wave(release_time: &str, challenges: &[&str]) -> ReleaseWave {
+        ReleaseWave {
+            release_time: release_time.parse().unwrap(),
+            challenges: challenges.iter().map(|id| id.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_next_waves() {
+        let waves = BTreeMap::from([
+            ("first".to_string(), wave("2026-05-01T10:00:00Z", &["web"])),
+            ("second".to_string(), wave("2026-05-01T16:00:00Z", &["pwn"])),
+            (
+                "second-b".to_string(),
+                wave("2026-05-01T16:00:00Z", &["rev"]),
+            ),
+        ]);
+        let next = next_waves(&waves, "2026-05-01T09:00:00Z".parse().unwrap());
+        assert_eq!(next.len(), 1);
+        assert_eq!(next[0].name, "first");
+
+        let next = next_waves(&waves, "2026-05-01T10:00:00Z".parse().unwrap());
+        assert_eq!(
+            next.iter()
+                .map(|wave| wave.name.as_str())
+                .collect::<Vec<_>>(),
+            ["second", "second-b"]
+        );
+        assert!(next_waves(&waves, "2026-05-01T16:00:00Z".parse().unwrap()).is_empty());
+
+        let recent = recent_waves(&waves, "2026-05-01T16:05:00Z".parse().unwrap());
+        assert_eq!(recent.len(), 2);
+        assert!(recent_waves(&waves, "2026-05-01T17:00:00Z".parse().unwrap()).is_empty());
+    }
+}
@@ -0,0 +1,75 @@
+// SPDX-FileCopyrightText: 2026 Aaron Dewes <aaron@nirvati.org>
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! The revision (commit or tag) an event is pinned to, so syncs deploy it instead of the tip of
+//! the branch. It is stored in a file next to the checkout, which is replaced on every sync.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+pub struct RevisionPin {
+    path: PathBuf,
+    revision: Mutex<Option<String>>,
+}
+
+impl RevisionPin {
+    /// Loads the pin of the repository checked out at `repo_dir`
+    pub fn load(repo_dir: &Path) -> Self {
+        let mut file_name = repo_dir.file_name().unwrap_or_default().to_os_string();
+        file_name.push(".revision");
+        let path = repo_dir.with_file_name(file_name);
+        let revision = std::fs::read_to_string(&path)
+            .ok()
+            .map(|revision| revision.trim().to_string())
+            .filter(|revision| !revision.is_empty());
+        RevisionPin {
+            path,
+            revision: Mutex::new(revision),
+        }
+    }
+
+    pub fn get(&self) -> Option<String> {
+        self.revision
+            .lock()
+            .expect("Revision pin lock poisoned")
+            .clone()
+    }
+
+    /// Pins the revision, or follows the branch again if it is None
+    pub fn set(&self, revision: Option<String>) -> std::io::Result<()> {
+        let mut pinned = self.revision.lock().expect("Revision pin lock poisoned");
+        match &revision {
+            Some(revision) => std::fs::write(&self.path, revision)?,
+            None => match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            },
+        }
+        *pinned = revision;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pin_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo_dir = dir.path().join("repo");
+        let pin = RevisionPin::load(&repo_dir);
+        assert_eq!(pin.get(), None);
+
+        pin.set(Some("v1.0".to_string())).unwrap();
+        assert!(dir.path().join("repo.revision").is_file());
+        assert_eq!(RevisionPin::load(&repo_dir).get().as_deref(), Some("v1.0"));
+
+        pin.set(None).unwrap();
+        pin.set(None).unwrap();
+        assert_eq!(RevisionPin::load(&repo_dir).get(), None);
+    }
+}